@@ -0,0 +1,76 @@
+//! Benchmark string interning on an identifier-heavy workload.
+//!
+//! Run with `cargo bench --bench intern`.  Two workloads are timed:
+//! one where most interns find an existing string, as when lexing,
+//! and one where every string is new, so the table keeps growing.
+
+extern crate salt_common;
+
+use salt_common::salt::common::str::intern::StrIntern;
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Number of identifier occurrences in the workload.
+const OCCURRENCES: usize = 2_000_000;
+
+/// Number of distinct identifiers.
+const DISTINCT: usize = 20_000;
+
+/// Number of runs of each benchmark; the fastest is reported.
+const RUNS: usize = 10;
+
+/// Build a workload resembling the identifiers in source code: a
+/// small set of common names, and a long tail of rarer ones, written
+/// into one buffer as a lexer would see them.
+fn workload() -> String {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut source = String::new();
+
+    for _ in 0..OCCURRENCES {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+
+        let n = state as usize;
+
+        if n % 4 == 0 {
+            source.push_str(&format!("identifier_number_{} ", n % DISTINCT));
+        } else {
+            source.push_str(&format!("x{} ", n % 64));
+        }
+    }
+
+    source
+}
+
+/// Time interning every string in `words` into an empty table,
+/// reporting the fastest of several runs.
+fn bench(title: &str, words: &[&str]) {
+    let best = (0..RUNS).map(|_| {
+        let start = Instant::now();
+        let mut tab = StrIntern::new();
+
+        for word in words.iter() {
+            black_box(tab.intern(word));
+        }
+
+        black_box(tab.len());
+        start.elapsed()
+    }).min().unwrap();
+
+    println!("{:<24} {:>8.2} ms  {:>6.1} ns/intern", title,
+             best.as_secs_f64() * 1e3,
+             best.as_secs_f64() * 1e9 / words.len() as f64);
+}
+
+fn main() {
+    let source = workload();
+    let words: Vec<&str> = source.split_whitespace().collect();
+    let unique: Vec<String> = (0..DISTINCT * 25)
+        .map(|i| format!("local_{}", i))
+        .collect();
+    let unique: Vec<&str> = unique.iter().map(|s| s.as_str()).collect();
+
+    bench("repeated identifiers", &words);
+    bench("distinct identifiers", &unique);
+}
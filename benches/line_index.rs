@@ -0,0 +1,78 @@
+//! Benchmark keeping a line index up to date while a file is edited.
+//!
+//! Run with `cargo bench --bench line_index`.  A large file is edited
+//! one keystroke at a time, as in a language server, and the index is
+//! updated either incrementally or by rebuilding it from the text.
+
+extern crate salt_common;
+
+use salt_common::salt::common::position::LineIndex;
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Number of lines in the file.
+const LINES: usize = 100_000;
+
+/// Number of keystrokes.
+const EDITS: usize = 2_000;
+
+/// Number of runs of each benchmark; the fastest is reported.
+const RUNS: usize = 5;
+
+/// Build a file of `LINES` short lines of code.
+fn source() -> String {
+    (0..LINES).map(|i| format!("    let x{} = f(x{});\n", i, i / 2))
+        .collect()
+}
+
+/// Build the keystrokes: typing at scattered places, with a newline
+/// every so often, as byte offsets in the original file and the text
+/// typed.
+fn edits(len: usize) -> Vec<(usize, &'static str)> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+
+    (0..EDITS).map(|i| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+
+        let text = if i % 16 == 0 { "\n" } else { "a" };
+
+        (state as usize % len, text)
+    }).collect()
+}
+
+/// Time applying `edits` to `text`, updating a line index with
+/// `update`, reporting the fastest of several runs.
+fn bench<F>(title: &str, text: &str, edits: &[(usize, &str)], update: F)
+    where F: Fn(&mut LineIndex, &str, usize, &str) {
+    let best = (0..RUNS).map(|_| {
+        let mut text = text.to_string();
+        let mut index = LineIndex::new(&text);
+        let start = Instant::now();
+
+        for &(offset, typed) in edits {
+            text.insert_str(offset, typed);
+            update(&mut index, &text, offset, typed);
+        }
+
+        black_box(index.line_count());
+        start.elapsed()
+    }).min().unwrap();
+
+    println!("{:<24} {:>8.2} ms  {:>8.1} us/edit", title,
+             best.as_secs_f64() * 1e3,
+             best.as_secs_f64() * 1e6 / edits.len() as f64);
+}
+
+fn main() {
+    let text = source();
+    let edits = edits(text.len());
+
+    bench("incremental", &text, &edits, |index, _, offset, typed| {
+        index.edit(offset..offset, typed)
+    });
+    bench("rebuild", &text, &edits, |index, text, _, _| {
+        *index = LineIndex::new(text)
+    });
+}
@@ -0,0 +1,52 @@
+//! Demangle Salt symbols.
+//!
+//! With arguments, prints the demangled form of each argument on its
+//! own line.  Without arguments, copies standard input to standard
+//! output, demangling any Salt symbols it finds, so that backtraces
+//! and profiler output can be piped through it.
+
+extern crate salt_common;
+
+use salt_common::salt::common::demangle::demangle_text;
+use std::env;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::process;
+
+fn filter() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut input = stdin.lock();
+    let mut output = stdout.lock();
+    let mut line = Vec::new();
+
+    while input.read_until(b'\n', &mut line)? != 0 {
+        output.write_all(demangle_text(&String::from_utf8_lossy(&line))
+                         .as_bytes())?;
+        line.clear();
+    }
+
+    output.flush()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = if args.is_empty() {
+        filter()
+    } else {
+        let stdout = io::stdout();
+        let mut output = stdout.lock();
+
+        args.iter().try_for_each(|arg| {
+            writeln!(output, "{}", demangle_text(arg))
+        })
+    };
+
+    if let Err(err) = result {
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("salt-demangle: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -1,7 +1,35 @@
 //! # Salt-Common
 //!
 //! Common definitions for the Salt language compiler.
+//!
+//! Without the `std` feature, the crate is `no_std`, needing only
+//! `alloc`, and provides just the core types, for embedding in places
+//! such as WebAssembly modules: `Point` and `Location`, `Filename`,
+//! `Symbol`, `StrIntern` and `StrArena`, stable hashing, and column
+//! widths.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(not(any(feature = "std", test)))]
+#[macro_use]
+extern crate alloc;
 
 pub mod salt;
 
+#[cfg(any(feature = "std", test))]
 extern crate core;
+
+/// Without the standard library, the parts of it the core types use,
+/// from `core` and `alloc`, so that paths through `std` work either
+/// way.
+#[cfg(not(any(feature = "std", test)))]
+mod std {
+    pub use core::*;
+    pub use alloc::boxed;
+    pub use alloc::collections;
+    pub use alloc::fmt;
+    pub use alloc::slice;
+    pub use alloc::str;
+    pub use alloc::string;
+    pub use alloc::vec;
+}
@@ -0,0 +1,443 @@
+//! Generators of arbitrary values, for property tests.
+//!
+//! Types implementing `Arbitrary` can be generated from a `Gen`, which
+//! draws from a `DeterministicRng`, so that any failure can be
+//! reproduced from its seed.  `check` runs a property on many cases,
+//! and reports the seed of the first one that fails:
+//!
+//! ```ignore
+//! check("merge_commutes", 256, &[], |g| {
+//!     let a = Location::arbitrary(g);
+//!     let b = Location::arbitrary(g);
+//!
+//!     assert_eq!(a.merge(&b), b.merge(&a));
+//! });
+//! ```
+//!
+//! Numbers are drawn from small ranges, set by the generator's size,
+//! so that equal values, and values equal but for one field, come up
+//! often.  Filenames cannot be made from nothing, so positions in
+//! files are generated in the filenames the generator is given.
+//!
+//! Besides positions, there are generators for diagnostic messages,
+//! and for strings, which are names to fill interners with.  The crate
+//! has no dependencies, so these do not plug into `proptest` or
+//! `quickcheck`; `check` plays the part of their test runners.
+//!
+//! This module is built with the `arbitrary` feature, so downstream
+//! crates can use the generators, and `check_order`, in their own
+//! tests.
+
+use salt::common::filename::Filename;
+#[cfg(feature = "std")]
+use salt::common::message::Applicability;
+#[cfg(feature = "std")]
+use salt::common::message::Message;
+#[cfg(feature = "std")]
+use salt::common::message::Severity;
+#[cfg(feature = "std")]
+use salt::common::message::Suggestion;
+use salt::common::position::BasicPosition;
+use salt::common::position::FilePosition;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use salt::common::rng::DeterministicRng;
+use salt::common::symbol::INLINE_LEN;
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::panic::AssertUnwindSafe;
+use std::panic::catch_unwind;
+
+/// The size of a new `Gen`.
+const DEFAULT_SIZE: u32 = 4;
+
+/// The descriptions of generated synthetic positions.
+const SYNTHETIC: [&str; 2] = ["<builtin>", "<prelude>"];
+
+/// The characters of generated strings, including one that is not
+/// ASCII.
+const NAME_CHARS: [char; 4] = ['a', 'b', '_', '\u{e9}'];
+
+/// The text of generated messages, headlines, notes and labels alike.
+#[cfg(feature = "std")]
+const TEXT: [&str; 3] = ["mismatched types", "unused variable `x`",
+                         "expected `i32`"];
+
+/// A source of arbitrary values.
+#[derive(Clone, Debug)]
+pub struct Gen<'a> {
+    rng: DeterministicRng,
+    /// The largest line or column, and the longest vector, generated.
+    size: u32,
+    /// The files positions are generated in.
+    filenames: Vec<Filename<'a>>
+}
+
+/// Types that can be generated for property tests.
+pub trait Arbitrary<'a>: Sized {
+    /// Generate a value from `g`.
+    fn arbitrary(g: &mut Gen<'a>) -> Self;
+}
+
+impl<'a> Gen<'a> {
+    /// Create a generator from `seed`, with no filenames.
+    pub fn new(seed: u64) -> Gen<'a> {
+        Gen { rng: DeterministicRng::new(seed), size: DEFAULT_SIZE,
+              filenames: Vec::new() }
+    }
+
+    /// Set the size, which bounds lines, columns and lengths.  Panics
+    /// if `size` is 0.
+    pub fn with_size(mut self, size: u32) -> Gen<'a> {
+        assert!(size != 0, "generator size must be positive");
+
+        self.size = size;
+        self
+    }
+
+    /// Generate positions in the files `filenames`.
+    pub fn with_filenames(mut self, filenames: &[Filename<'a>]) -> Gen<'a> {
+        self.filenames = filenames.to_vec();
+        self
+    }
+
+    /// Get the size.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Get the random number generator, for drawing values of types
+    /// with no `Arbitrary` implementation.
+    pub fn rng(&mut self) -> &mut DeterministicRng {
+        &mut self.rng
+    }
+
+    /// Get a number from 1 to the size.
+    pub fn up_to_size(&mut self) -> u32 {
+        self.rng.below(self.size as u64) as u32 + 1
+    }
+
+    /// Pick one of `items`.  Panics if there are none.
+    pub fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+        assert!(!items.is_empty(), "nothing to pick from");
+
+        items[self.rng.below(items.len() as u64) as usize].clone()
+    }
+
+    /// Pick one of the generator's filenames.  Panics if it has none.
+    pub fn filename(&mut self) -> Filename<'a> {
+        assert!(!self.filenames.is_empty(), "no filenames to generate in");
+
+        let filenames = self.filenames.clone();
+
+        self.pick(&filenames)
+    }
+
+    /// Generate a vector of up to the size values.
+    pub fn vec<T: Arbitrary<'a>>(&mut self) -> Vec<T> {
+        let len = self.rng.below(self.size as u64 + 1);
+
+        (0..len).map(|_| T::arbitrary(self)).collect()
+    }
+}
+
+impl<'a> Arbitrary<'a> for Point {
+    fn arbitrary(g: &mut Gen<'a>) -> Point {
+        Point { line: g.up_to_size(), col: g.up_to_size() }
+    }
+}
+
+/// Spans may end before they start, as nothing stops them from being
+/// built that way.
+impl<'a> Arbitrary<'a> for Location {
+    fn arbitrary(g: &mut Gen<'a>) -> Location {
+        if g.rng().chance(1, 2) {
+            Location::Point { point: Point::arbitrary(g) }
+        } else {
+            Location::Span { start: Point::arbitrary(g),
+                             end: Point::arbitrary(g) }
+        }
+    }
+}
+
+/// Panics if the generator has no filenames.
+impl<'a> Arbitrary<'a> for FilePosition<'a> {
+    fn arbitrary(g: &mut Gen<'a>) -> FilePosition<'a> {
+        FilePosition { filename: g.filename(), loc: Location::arbitrary(g) }
+    }
+}
+
+/// Panics if the generator has no filenames.
+impl<'a> Arbitrary<'a> for BasicPosition<'a> {
+    fn arbitrary(g: &mut Gen<'a>) -> BasicPosition<'a> {
+        match g.rng().below(4) {
+            0 => BasicPosition::Content { filepos: FilePosition::arbitrary(g) },
+            1 => BasicPosition::File { filename: g.filename() },
+            2 => {
+                let size = g.size() as u64;
+                let len = g.rng().below(size);
+                let idxs = (0..len).map(|_| g.rng().below(2) as usize)
+                    .collect();
+
+                BasicPosition::CmdLine { idxs }
+            }
+            _ => BasicPosition::Synthetic { desc: g.pick(&SYNTHETIC) }
+        }
+    }
+}
+
+/// Strings are names of up to twice `INLINE_LEN` characters, so both
+/// short symbols and interned ones come up.
+impl<'a> Arbitrary<'a> for String {
+    fn arbitrary(g: &mut Gen<'a>) -> String {
+        let len = g.rng().below(2 * INLINE_LEN as u64) + 1;
+
+        (0..len).map(|_| g.pick(&NAME_CHARS)).collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Arbitrary<'a> for Severity {
+    fn arbitrary(g: &mut Gen<'a>) -> Severity {
+        g.pick(&[Severity::Help, Severity::Note, Severity::Warning,
+                 Severity::Error, Severity::Bug])
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Arbitrary<'a> for Applicability {
+    fn arbitrary(g: &mut Gen<'a>) -> Applicability {
+        g.pick(&[Applicability::MachineApplicable,
+                 Applicability::MaybeIncorrect,
+                 Applicability::HasPlaceholders, Applicability::Unspecified])
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Arbitrary<'a> for Suggestion {
+    fn arbitrary(g: &mut Gen<'a>) -> Suggestion {
+        let message = g.pick(&TEXT);
+        let loc = Location::arbitrary(g);
+        let replacement = String::arbitrary(g);
+
+        Suggestion::new(message, loc, replacement, Applicability::arbitrary(g))
+    }
+}
+
+/// Codes are drawn from a few, and text from a few phrases, so that
+/// duplicate messages come up often.  Labels may be missing, or more
+/// than there are positions.
+#[cfg(feature = "std")]
+impl<'a, P: Arbitrary<'a>> Arbitrary<'a> for Message<P> {
+    fn arbitrary(g: &mut Gen<'a>) -> Message<P> {
+        let severity = Severity::arbitrary(g);
+        let mut msg = Message::new(severity, g.pick(&TEXT));
+
+        if g.rng().chance(1, 2) {
+            msg = msg.with_code(format!("E{:04}", g.rng().below(3)));
+        }
+
+        let size = g.size() as u64;
+        let labels = g.rng().below(size + 1);
+
+        msg.positions = g.vec();
+        msg.labels = (0..labels)
+            .map(|_| if g.rng().chance(1, 2) {
+                Some(g.pick(&TEXT).to_string())
+            } else {
+                None
+            })
+            .collect();
+        msg.notes = (0..g.rng().below(3))
+            .map(|_| g.pick(&TEXT).to_string())
+            .collect();
+        msg.suggestions = g.vec();
+        msg
+    }
+}
+
+/// Get the message of a panic, if it has one.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => msg,
+        None => payload.downcast_ref::<String>()
+            .map(String::as_str)
+            .unwrap_or("")
+    }
+}
+
+/// Run `property` on `cases` generators, positions from which are in
+/// the files `filenames`.  The seeds are derived from `name`, so each
+/// property sees its own cases, the same on every run.  Panics if the
+/// property panics for any case, with the seed to give `Gen::new` to
+/// reproduce the failure.
+pub fn check<'a, F>(name: &str, cases: u32, filenames: &[Filename<'a>],
+                    mut property: F)
+    where F: FnMut(&mut Gen<'a>) {
+    let mut seeds = DeterministicRng::for_stream(0, name);
+
+    for case in 0..cases {
+        let seed = seeds.next_u64();
+        let mut g = Gen::new(seed).with_filenames(filenames);
+
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(|| {
+            property(&mut g)
+        })) {
+            panic!("property `{}` failed on case {} (seed {:#x}): {}",
+                   name, case, seed, panic_message(&*payload))
+        }
+    }
+}
+
+/// Check that `Eq`, `Ord`, `PartialOrd` and `Hash` agree on `values`,
+/// and that the order is a total order.  Panics if they do not.
+pub fn check_order<T: Ord + Hash + Debug>(values: &[T]) {
+    let hash = |value: &T| {
+        let mut hasher = DefaultHasher::new();
+
+        value.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    for a in values {
+        assert!(a.eq(a), "{:?} is not equal to itself", a);
+        assert_eq!(a.cmp(a), Ordering::Equal);
+
+        for b in values {
+            assert_eq!(a.partial_cmp(b), Some(a.cmp(b)), "{:?} {:?}", a, b);
+            assert_eq!(a == b, a.cmp(b) == Ordering::Equal,
+                       "{:?} {:?}", a, b);
+            assert_eq!(a.cmp(b), b.cmp(a).reverse(), "{:?} {:?}", a, b);
+
+            if a == b {
+                assert_eq!(hash(a), hash(b), "{:?} {:?}", a, b);
+            }
+
+            for c in values {
+                if a <= b && b <= c {
+                    assert!(a <= c, "{:?} {:?} {:?}", a, b, c);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_gen_deterministic() {
+    let a: Vec<Location> = Gen::new(7).vec();
+    let b: Vec<Location> = Gen::new(7).vec();
+    let mut g = Gen::new(7).with_size(2);
+
+    assert_eq!(a, b);
+
+    for _ in 0..100 {
+        let point = Point::arbitrary(&mut g);
+
+        assert!((1..=2).contains(&point.line) && (1..=2).contains(&point.col));
+    }
+}
+
+#[test]
+fn test_order_laws() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut intern = StrIntern::new();
+    let names = [intern.filename("a.salt"), intern.filename("b.salt")];
+
+    check("order_laws", 16, &names, |g| {
+        check_order(&g.vec::<Point>());
+        check_order(&g.vec::<Location>());
+        check_order(&g.vec::<FilePosition>());
+        check_order(&g.vec::<BasicPosition>());
+    });
+}
+
+#[test]
+fn test_merge_laws() {
+    check("merge_laws", 256, &[], |g| {
+        let a = Location::arbitrary(g);
+        let b = Location::arbitrary(g);
+        let c = Location::arbitrary(g);
+        let ab = a.merge(&b);
+
+        assert_eq!(ab.merge(&c), a.merge(&b.merge(&c)));
+        assert_eq!(ab, b.merge(&a));
+        assert_eq!(ab.merge(&ab), ab);
+        assert!(ab.start() <= a.start() && ab.end() >= a.end());
+
+        // Lines and columns are at most the size, so this covers every
+        // point starting or ending a location, and those around them.
+        for line in 1..=g.size() + 1 {
+            for col in 1..=g.size() + 1 {
+                let point = Point { line, col };
+
+                if a.contains(point) || b.contains(point) {
+                    assert!(ab.contains(point), "{} not in {}", point, ab);
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn test_intern_laws() {
+    use salt::common::str::arena::StrArena;
+    use salt::common::str::intern::StrIntern;
+    use salt::common::symbol::SymbolCtx;
+
+    let arena = StrArena::new();
+    let mut intern = StrIntern::new();
+
+    check("intern_laws", 64, &[], |g| {
+        let word = String::arbitrary(g);
+        let copy = word.clone();
+        let interned = arena.intern(&word);
+
+        assert!(::std::ptr::eq(arena.intern(&copy), interned));
+        assert!(::std::ptr::eq(arena.intern(interned), interned));
+        assert_eq!(intern.symbol(interned), intern.symbol(interned));
+        assert_eq!(intern.symbol(interned).as_str(), word);
+    });
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_message_laws() {
+    use salt::common::cache::decode;
+    use salt::common::cache::encode;
+    use salt::common::filename::FilenameCtx;
+    use salt::common::message::PlainRenderer;
+    use salt::common::message::Renderer;
+    use salt::common::str::intern::StrIntern;
+
+    let mut intern = StrIntern::new();
+    let names = [intern.filename("a.salt"), intern.filename("b.salt")];
+
+    check("message_laws", 64, &names, |g| {
+        let msg = Message::<FilePosition>::arbitrary(g);
+        let bytes = encode(&msg);
+        let decoded: Message<FilePosition> =
+            decode(&bytes, &mut StrIntern::new()).unwrap();
+        let text = PlainRenderer.render(&msg);
+
+        assert_eq!(PlainRenderer.render(&decoded), text);
+        assert!(text.ends_with('\n') && text.contains(&msg.headline));
+
+        for note in &msg.notes {
+            assert!(text.contains(note.as_str()), "{:?} lacks {}", text, note);
+        }
+    });
+}
+
+#[test]
+#[should_panic(expected = "property `failing` failed on case 0 (seed")]
+fn test_check_reports_seed() {
+    check("failing", 4, &[], |g| {
+        assert!(Point::arbitrary(g).line > g.size(), "too small")
+    });
+}
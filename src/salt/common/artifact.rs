@@ -0,0 +1,165 @@
+//! Descriptions of compiler outputs.
+//!
+//! Each file the compiler writes is described by an `Artifact`, and
+//! the artifacts from one compilation are collected in a `Manifest`.
+//! The manifest is written as JSON for drivers and build systems:
+//!
+//! ```text
+//! {"version":1,"artifacts":[{"kind":"object","path":"out/main.o",
+//!  "target":"x86_64-unknown-linux-gnu",
+//!  "hash":"0123456789abcdef0123456789abcdef","phase":"codegen"}]}
+//! ```
+//!
+//! `target` is `null` for artifacts that do not depend on the target,
+//! such as dependency files.  `hash` is the stable fingerprint of the
+//! file's contents.
+
+use salt::common::hash::Fingerprint;
+use salt::common::hash::StableHasher;
+use salt::common::json::Json;
+use salt::common::json::ToJson;
+use salt::common::target::Triple;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::path::PathBuf;
+
+/// The version of the manifest format.  Bump this whenever the format
+/// changes incompatibly.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The kind of an artifact.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ArtifactKind {
+    /// An object file (`object`).
+    Object,
+    /// Intermediate representation, such as LLVM IR (`ir`).
+    IR,
+    /// Assembly (`asm`).
+    Asm,
+    /// A Makefile-style dependency file (`depfile`).
+    DepFile
+}
+
+/// A file produced by the compiler.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Artifact {
+    /// The kind of file.
+    pub kind: ArtifactKind,
+    /// Where the file was written.
+    pub path: PathBuf,
+    /// The target the file was produced for, if it depends on one.
+    pub target: Option<Triple>,
+    /// The fingerprint of the file's contents.
+    pub hash: Fingerprint,
+    /// The name of the phase that produced the file.
+    pub phase: String
+}
+
+/// The artifacts produced by a compilation, in the order they were
+/// written.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Manifest {
+    artifacts: Vec<Artifact>
+}
+
+impl ArtifactKind {
+    /// Get the name of this kind, as written in manifests.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArtifactKind::Object => "object",
+            ArtifactKind::IR => "ir",
+            ArtifactKind::Asm => "asm",
+            ArtifactKind::DepFile => "depfile"
+        }
+    }
+}
+
+impl Artifact {
+    /// Create an `Artifact` for `contents` written to `path`,
+    /// computing its hash.
+    pub fn new<P: Into<PathBuf>, S: Into<String>>(kind: ArtifactKind,
+                                                  path: P,
+                                                  target: Option<Triple>,
+                                                  contents: &[u8],
+                                                  phase: S) -> Artifact {
+        let mut hasher = StableHasher::new();
+
+        hasher.write_bytes(contents);
+
+        Artifact { kind, path: path.into(), target, hash: hasher.finish128(),
+                   phase: phase.into() }
+    }
+}
+
+impl Manifest {
+    /// Create an empty `Manifest`.
+    pub fn new() -> Manifest {
+        Manifest { artifacts: Vec::new() }
+    }
+
+    /// Record an artifact.
+    pub fn push(&mut self, artifact: Artifact) {
+        self.artifacts.push(artifact)
+    }
+
+    /// Get the recorded artifacts.
+    pub fn artifacts(&self) -> &[Artifact] {
+        &self.artifacts
+    }
+}
+
+impl ToJson for ArtifactKind {
+    fn to_json(&self) -> Json {
+        self.name().to_json()
+    }
+}
+
+impl ToJson for Artifact {
+    fn to_json(&self) -> Json {
+        Json::object()
+            .with("kind", self.kind)
+            .with("path", self.path.to_string_lossy().into_owned())
+            .with("target", self.target.as_ref().map(|t| t.to_string()))
+            .with("hash", self.hash.to_string())
+            .with("phase", &self.phase)
+    }
+}
+
+impl ToJson for Manifest {
+    fn to_json(&self) -> Json {
+        Json::object()
+            .with("version", MANIFEST_VERSION)
+            .with("artifacts", &self.artifacts)
+    }
+}
+
+impl Display for ArtifactKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[test]
+fn test_manifest_json() {
+    let mut manifest = Manifest::new();
+    let target: Triple = "x86_64-unknown-linux-gnu".parse().unwrap();
+    let obj = Artifact::new(ArtifactKind::Object, "out/main.o", Some(target),
+                            b"\x7fELF", "codegen");
+    let dep = Artifact::new(ArtifactKind::DepFile, "out/main.d", None,
+                            b"main.o: main.salt\n", "driver");
+    let expected = format!("{{\"version\":1,\"artifacts\":[\
+                            {{\"kind\":\"object\",\"path\":\"out/main.o\",\
+                            \"target\":\"x86_64-unknown-linux-gnu\",\
+                            \"hash\":\"{}\",\"phase\":\"codegen\"}},\
+                            {{\"kind\":\"depfile\",\"path\":\"out/main.d\",\
+                            \"target\":null,\"hash\":\"{}\",\
+                            \"phase\":\"driver\"}}]}}", obj.hash, dep.hash);
+
+    assert_ne!(obj.hash, dep.hash);
+
+    manifest.push(obj);
+    manifest.push(dep);
+
+    assert_eq!(manifest.to_json().to_string(), expected);
+}
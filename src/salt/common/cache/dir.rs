@@ -0,0 +1,370 @@
+use salt::common::cache::DecodeError;
+use salt::common::cache::DecodeResult;
+use salt::common::cache::Decodable;
+use salt::common::cache::Decoder;
+use salt::common::cache::Encodable;
+use salt::common::cache::Encoder;
+use salt::common::cache::decode;
+use salt::common::cache::encode;
+use salt::common::hash::Fingerprint;
+use salt::common::hash::StableHash;
+use salt::common::output::write_atomic;
+use salt::common::query::Query;
+use salt::common::str::intern::StrIntern;
+use salt::common::version::ToolVersion;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Magic number at the start of every cache entry.
+const MAGIC: &[u8; 8] = b"SALTCACH";
+
+/// Version of the entry format.  Bump this whenever the layout of the
+/// header or of any `Encodable` type changes.
+const FORMAT: u32 = 1;
+
+/// A cache directory for one tool version and target.
+#[derive(Clone, Debug)]
+pub struct CacheDir {
+    /// The directory holding entries.
+    dir: PathBuf,
+    /// The tool's cache key.
+    key: String,
+    /// The target name.
+    target: String
+}
+
+/// Errors that can occur loading a cache entry.
+#[derive(Debug)]
+pub enum CacheError {
+    /// An IO error.
+    IO(io::Error),
+    /// The entry is not a cache entry.
+    BadMagic,
+    /// The entry was written by a different tool version, target, or
+    /// entry format.
+    Mismatch {
+        /// The header field that did not match.
+        field: &'static str
+    },
+    /// The entry's contents do not match its fingerprint.
+    Corrupt,
+    /// The entry could not be decoded.
+    Decode(DecodeError)
+}
+
+/// Result type for cache operations.
+pub type CacheResult<T> = ::std::result::Result<T, CacheError>;
+
+/// The header of a cache entry.
+struct Header {
+    /// The entry format.
+    format: u32,
+    /// The tool's cache key.
+    key: String,
+    /// The target name.
+    target: String,
+    /// Fingerprint of the inputs the entry was computed from.
+    input: Fingerprint,
+    /// Fingerprint of the payload.
+    payload: Fingerprint
+}
+
+impl Encodable for Header {
+    fn encode(&self, enc: &mut Encoder) {
+        self.format.encode(enc);
+        self.key.encode(enc);
+        self.target.encode(enc);
+        self.input.encode(enc);
+        self.payload.encode(enc)
+    }
+}
+
+impl<'d> Decodable<'d> for Header {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Header> {
+        Ok(Header { format: u32::decode(dec)?, key: String::decode(dec)?,
+                    target: String::decode(dec)?,
+                    input: Fingerprint::decode(dec)?,
+                    payload: Fingerprint::decode(dec)? })
+    }
+}
+
+/// Make `name` safe to use as a file name.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "+-._".contains(c) { c }
+                 else { '_' })
+        .collect()
+}
+
+impl CacheDir {
+    /// Open the cache for `version` and `target` under `root`,
+    /// creating it if necessary.  Each version and target gets its own
+    /// subdirectory, so caches from other builds are never consulted.
+    pub fn open<P: AsRef<Path>>(root: P, version: &ToolVersion,
+                                target: &str) -> io::Result<CacheDir> {
+        let key = version.cache_key();
+        let dir = root.as_ref().join(sanitize(&key)).join(sanitize(target));
+
+        fs::create_dir_all(&dir)?;
+
+        Ok(CacheDir { dir, key, target: target.to_string() })
+    }
+
+    /// Get the directory holding entries.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the path of the entry `name`.
+    pub fn entry_path(&self, name: &str) -> PathBuf {
+        self.dir.join(sanitize(name) + ".bin")
+    }
+
+    /// Store `payload` as the entry `name`, computed from inputs with
+    /// fingerprint `input`.  The entry is replaced atomically.
+    pub fn store(&self, name: &str, input: Fingerprint,
+                 payload: &[u8]) -> io::Result<()> {
+        let header = Header { format: FORMAT, key: self.key.clone(),
+                              target: self.target.clone(), input,
+                              payload: Fingerprint::of_bytes(payload) };
+        let mut out = MAGIC.to_vec();
+
+        out.extend_from_slice(&encode(&header));
+        out.extend_from_slice(payload);
+
+        write_atomic(self.entry_path(name), &out)
+    }
+
+    /// Load the payload of the entry `name`.  This is `None` if there
+    /// is no entry, or if it was computed from inputs other than
+    /// `input`.  Entries that fail verification are reported as errors;
+    /// callers will usually treat these as misses.
+    pub fn load(&self, name: &str,
+                input: Fingerprint) -> CacheResult<Option<Vec<u8>>> {
+        let data = match fs::read(self.entry_path(name)) {
+            Ok(data) => data,
+            Err(ref err) if err.kind() == ErrorKind::NotFound =>
+                return Ok(None),
+            Err(err) => return Err(CacheError::IO(err))
+        };
+
+        if !data.starts_with(MAGIC) {
+            return Err(CacheError::BadMagic);
+        }
+
+        let mut intern = StrIntern::new();
+        let mut dec = Decoder::new(&data[MAGIC.len()..], &mut intern)
+            .map_err(CacheError::Decode)?;
+        let header = Header::decode(&mut dec).map_err(CacheError::Decode)?;
+
+        if header.format != FORMAT {
+            return Err(CacheError::Mismatch { field: "format" });
+        }
+
+        if header.key != self.key {
+            return Err(CacheError::Mismatch { field: "version" });
+        }
+
+        if header.target != self.target {
+            return Err(CacheError::Mismatch { field: "target" });
+        }
+
+        let payload = dec.rest();
+
+        if Fingerprint::of_bytes(payload) != header.payload {
+            return Err(CacheError::Corrupt);
+        }
+
+        if header.input != input {
+            return Ok(None);
+        }
+
+        Ok(Some(payload.to_vec()))
+    }
+
+    /// Encode and store `value` as the entry `name`.
+    pub fn store_value<T: Encodable + ?Sized>(&self, name: &str,
+                                              input: Fingerprint,
+                                              value: &T) -> io::Result<()> {
+        self.store(name, input, &encode(value))
+    }
+
+    /// Load and decode the entry `name`, for values that do not borrow
+    /// interned strings.
+    pub fn load_value<T>(&self, name: &str,
+                         input: Fingerprint) -> CacheResult<Option<T>>
+        where T: for<'d> Decodable<'d> {
+        match self.load(name, input)? {
+            Some(payload) => {
+                let mut intern = StrIntern::new();
+
+                decode(&payload, &mut intern).map(Some)
+                    .map_err(CacheError::Decode)
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Get the entry name for the result of `Q` on `key`.
+    pub fn query_entry<Q: Query>(key: &Q::Key) -> String {
+        format!("{}-{}", Q::NAME, key.fingerprint())
+    }
+
+    /// Store the result of `Q` on `key`, computed from inputs with
+    /// fingerprint `input`.
+    pub fn store_query<Q: Query>(&self, key: &Q::Key, input: Fingerprint,
+                                 value: &Q::Value) -> io::Result<()>
+        where Q::Value: Encodable {
+        self.store_value(&CacheDir::query_entry::<Q>(key), input, value)
+    }
+
+    /// Load the result of `Q` on `key`, if it was stored from inputs
+    /// with fingerprint `input`.
+    pub fn load_query<Q: Query>(&self, key: &Q::Key,
+                                input: Fingerprint)
+                                -> CacheResult<Option<Q::Value>>
+        where Q::Value: for<'d> Decodable<'d> {
+        self.load_value(&CacheDir::query_entry::<Q>(key), input)
+    }
+
+    /// Remove the entry `name`, if it exists.
+    pub fn remove(&self, name: &str) -> io::Result<()> {
+        match fs::remove_file(self.entry_path(name)) {
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            res => res
+        }
+    }
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
+        match self {
+            CacheError::IO(err) => write!(f, "{}", err),
+            CacheError::BadMagic => write!(f, "not a cache entry"),
+            CacheError::Mismatch { field } =>
+                write!(f, "cache entry {} does not match", field),
+            CacheError::Corrupt => write!(f, "cache entry is corrupt"),
+            CacheError::Decode(err) =>
+                write!(f, "cache entry could not be decoded: {}", err)
+        }
+    }
+}
+
+impl From<io::Error> for CacheError {
+    fn from(err: io::Error) -> CacheError {
+        CacheError::IO(err)
+    }
+}
+
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = ::std::env::temp_dir()
+        .join(format!("salt-cache-test-{}-{}", name, ::std::process::id()));
+
+    let _ = fs::remove_dir_all(&dir);
+
+    dir
+}
+
+#[test]
+fn test_round_trip() {
+    let root = test_dir("round-trip");
+    let cache = CacheDir::open(&root, &ToolVersion::new(1, 2, 3),
+                               "x86_64-linux").unwrap();
+    let input = "source text".fingerprint();
+    let value = vec![(String::from("main"), 42u32)];
+
+    cache.store_value("defs", input, &value).unwrap();
+
+    assert_eq!(cache.load_value::<Vec<(String, u32)>>("defs", input).unwrap(),
+               Some(value));
+    assert_eq!(cache.load_value::<Vec<(String, u32)>>("defs",
+                                                      "changed".fingerprint())
+               .unwrap(), None);
+    assert_eq!(cache.load_value::<u32>("missing", input).unwrap(), None);
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_verification() {
+    let root = test_dir("verify");
+    let cache = CacheDir::open(&root, &ToolVersion::new(1, 0, 0),
+                               "wasm32").unwrap();
+    let input = Fingerprint::ZERO;
+
+    cache.store_value("entry", input, &vec![1u64, 2, 3]).unwrap();
+
+    let path = cache.entry_path("entry");
+    let mut data = fs::read(&path).unwrap();
+    let last = data.len() - 1;
+
+    data[last] ^= 1;
+    fs::write(&path, &data).unwrap();
+
+    match cache.load("entry", input) {
+        Err(CacheError::Corrupt) => (),
+        res => panic!("expected corruption, got {:?}", res)
+    }
+
+    fs::write(&path, b"garbage").unwrap();
+
+    match cache.load("entry", input) {
+        Err(CacheError::BadMagic) => (),
+        res => panic!("expected bad magic, got {:?}", res)
+    }
+
+    let other = CacheDir { target: String::from("x86_64"), ..cache.clone() };
+
+    cache.store_value("entry", input, &0u8).unwrap();
+
+    match other.load("entry", input) {
+        Err(CacheError::Mismatch { field: "target" }) => (),
+        res => panic!("expected mismatch, got {:?}", res)
+    }
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_positions_reinterned() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Location;
+    use salt::common::position::Point;
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("src/main.salt");
+    let loc = Location::Span { start: Point { line: 3, col: 1 },
+                               end: Point { line: 3, col: 9 } };
+    let positions = vec![
+        BasicPosition::Content {
+            filepos: FilePosition { filename, loc: loc.clone() }
+        },
+        BasicPosition::File { filename },
+        BasicPosition::Synthetic { desc: "builtin" }
+    ];
+    let data = encode(&positions);
+    let mut intern = StrIntern::new();
+    let decoded: Vec<BasicPosition> = decode(&data, &mut intern).unwrap();
+
+    assert_eq!(intern.len(), 1);
+
+    match (&decoded[0], &decoded[1]) {
+        (BasicPosition::Content { filepos },
+         BasicPosition::File { filename }) => {
+            assert_eq!(filepos.filename, *filename);
+            assert_eq!(filepos.loc.to_string(), "3.1-9");
+            assert_eq!(filename.to_string(), "src/main.salt");
+        },
+        _ => panic!("wrong positions decoded")
+    }
+
+    assert_eq!(decoded[2], BasicPosition::Synthetic { desc: "builtin" });
+}
@@ -0,0 +1,624 @@
+use salt::common::hash::Fingerprint;
+use salt::common::str::intern::StrIntern;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+/// Types that can be written to an on-disk cache.
+pub trait Encodable {
+    /// Write this value to `enc`.
+    fn encode(&self, enc: &mut Encoder);
+}
+
+/// Types that can be read back from an on-disk cache.  The lifetime
+/// `'d` is that of the data being decoded, which interned strings
+/// borrow from.
+pub trait Decodable<'d>: Sized {
+    /// Read a value from `dec`.
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Self>;
+}
+
+/// Errors that can occur while decoding.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The data ended in the middle of a value.
+    UnexpectedEnd,
+    /// A number did not fit in the type being decoded.
+    Overflow,
+    /// A string was not valid UTF-8.
+    BadUtf8,
+    /// Data remained after the value was decoded.
+    TrailingData,
+    /// A reference to the string table was out of range.
+    BadStringIndex(u64),
+    /// An enum tag was not recognized.
+    BadTag {
+        /// The type being decoded.
+        what: &'static str,
+        /// The unrecognized tag.
+        tag: u64
+    }
+}
+
+/// Result type for decoding.
+pub type DecodeResult<T> = ::std::result::Result<T, DecodeError>;
+
+/// Writer for the cache encoding.  Integers are written as LEB128
+/// varints (zigzag for signed types), and interned strings are written
+/// once into a string table and referred to by index.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    /// The encoded body.
+    body: Vec<u8>,
+    /// Interned strings, in order of first use.
+    strings: Vec<String>,
+    /// Index of each interned string in `strings`.
+    ids: HashMap<String, u64>
+}
+
+/// Reader for the cache encoding.  Interned strings are re-interned
+/// into the supplied table as they are read.
+pub struct Decoder<'d, 'i> {
+    /// The data being decoded.
+    data: &'d [u8],
+    /// The current offset into `data`.
+    pos: usize,
+    /// The string table.
+    strings: Vec<&'d str>,
+    /// The table into which strings are interned.
+    intern: &'i mut StrIntern<'d>
+}
+
+impl Encoder {
+    /// Create an empty `Encoder`.
+    pub fn new() -> Encoder {
+        Encoder { body: Vec::new(), strings: Vec::new(), ids: HashMap::new() }
+    }
+
+    /// Write a single byte.
+    pub fn emit_u8(&mut self, val: u8) {
+        self.body.push(val)
+    }
+
+    /// Write an unsigned integer as a varint.
+    pub fn emit_uint(&mut self, mut val: u64) {
+        while val >= 0x80 {
+            self.body.push((val as u8) | 0x80);
+            val >>= 7;
+        }
+
+        self.body.push(val as u8)
+    }
+
+    /// Write a signed integer as a zigzag varint.
+    pub fn emit_int(&mut self, val: i64) {
+        self.emit_uint(((val << 1) ^ (val >> 63)) as u64)
+    }
+
+    /// Write raw bytes, preceded by their length.
+    pub fn emit_bytes(&mut self, bytes: &[u8]) {
+        self.emit_uint(bytes.len() as u64);
+        self.body.extend_from_slice(bytes)
+    }
+
+    /// Write a string inline.
+    pub fn emit_str(&mut self, s: &str) {
+        self.emit_bytes(s.as_bytes())
+    }
+
+    /// Write an interned string, as an index into the string table.
+    pub fn emit_interned(&mut self, s: &str) {
+        let next = self.strings.len() as u64;
+        let id = match self.ids.get(s) {
+            Some(id) => *id,
+            None => {
+                self.strings.push(s.to_string());
+                self.ids.insert(s.to_string(), next);
+
+                next
+            }
+        };
+
+        self.emit_uint(id)
+    }
+
+    /// Finish encoding, producing the string table followed by the
+    /// body.
+    pub fn finish(self) -> Vec<u8> {
+        let mut table = Encoder::new();
+
+        table.emit_uint(self.strings.len() as u64);
+
+        for s in &self.strings {
+            table.emit_str(s);
+        }
+
+        let mut out = table.body;
+
+        out.extend_from_slice(&self.body);
+
+        out
+    }
+}
+
+impl<'d, 'i> Decoder<'d, 'i> {
+    /// Create a `Decoder` over `data`, which must start with a string
+    /// table as written by `Encoder::finish`.
+    pub fn new(data: &'d [u8], intern: &'i mut StrIntern<'d>)
+               -> DecodeResult<Decoder<'d, 'i>> {
+        let mut dec = Decoder { data, pos: 0, strings: Vec::new(), intern };
+        let len = dec.read_uint()?;
+
+        for _ in 0..len {
+            let s = dec.read_str()?;
+
+            dec.strings.push(s);
+        }
+
+        Ok(dec)
+    }
+
+    /// Get the current offset into the data.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Get the data that has not yet been read.
+    pub fn rest(&self) -> &'d [u8] {
+        &self.data[self.pos..]
+    }
+
+    /// Check whether all of the data has been read.
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    /// Read a single byte.
+    pub fn read_u8(&mut self) -> DecodeResult<u8> {
+        match self.data.get(self.pos) {
+            Some(byte) => {
+                self.pos += 1;
+
+                Ok(*byte)
+            },
+            None => Err(DecodeError::UnexpectedEnd)
+        }
+    }
+
+    /// Read an unsigned varint.
+    pub fn read_uint(&mut self) -> DecodeResult<u64> {
+        let mut out: u64 = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.read_u8()?;
+
+            if shift == 63 && byte > 1 || shift > 63 {
+                return Err(DecodeError::Overflow);
+            }
+
+            out |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(out);
+            }
+
+            shift += 7;
+        }
+    }
+
+    /// Read a zigzag signed varint.
+    pub fn read_int(&mut self) -> DecodeResult<i64> {
+        let val = self.read_uint()?;
+
+        Ok((val >> 1) as i64 ^ -((val & 1) as i64))
+    }
+
+    /// Read length-prefixed raw bytes.
+    pub fn read_bytes(&mut self) -> DecodeResult<&'d [u8]> {
+        let len = usize::try_from(self.read_uint()?)
+            .map_err(|_| DecodeError::Overflow)?;
+        let end = self.pos.checked_add(len).ok_or(DecodeError::Overflow)?;
+
+        if end > self.data.len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+
+        let out = &self.data[self.pos..end];
+
+        self.pos = end;
+
+        Ok(out)
+    }
+
+    /// Read an inline string.
+    pub fn read_str(&mut self) -> DecodeResult<&'d str> {
+        str::from_utf8(self.read_bytes()?).map_err(|_| DecodeError::BadUtf8)
+    }
+
+    /// Read an interned string, interning it into the decoder's table.
+    pub fn read_interned(&mut self) -> DecodeResult<&'d str> {
+        let idx = self.read_uint()?;
+
+        match usize::try_from(idx).ok().and_then(|i| self.strings.get(i)) {
+            Some(s) => Ok(self.intern.intern(s)),
+            None => Err(DecodeError::BadStringIndex(idx))
+        }
+    }
+
+    /// Read a string that must outlive any data, such as the
+    /// description of a synthetic position.  Each distinct string is
+    /// allocated once per process.
+    pub fn read_static(&mut self) -> DecodeResult<&'static str> {
+        static STATICS: OnceLock<Mutex<HashSet<&'static str>>> =
+            OnceLock::new();
+
+        let s = self.read_str()?;
+        let mut statics = STATICS.get_or_init(|| Mutex::new(HashSet::new()))
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+
+        match statics.get(s) {
+            Some(out) => Ok(out),
+            None => {
+                let out: &'static str = Box::leak(s.to_string()
+                                                  .into_boxed_str());
+
+                statics.insert(out);
+
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Encode `value`, producing the string table and body.
+pub fn encode<T: Encodable + ?Sized>(value: &T) -> Vec<u8> {
+    let mut enc = Encoder::new();
+
+    value.encode(&mut enc);
+
+    enc.finish()
+}
+
+/// Decode a value from `data`, interning strings into `intern`.  All
+/// of `data` must be consumed.
+pub fn decode<'d, T: Decodable<'d>>(data: &'d [u8], intern: &mut StrIntern<'d>)
+                                    -> DecodeResult<T> {
+    let mut dec = Decoder::new(data, intern)?;
+    let out = T::decode(&mut dec)?;
+
+    if dec.is_empty() {
+        Ok(out)
+    } else {
+        Err(DecodeError::TrailingData)
+    }
+}
+
+macro_rules! uint_encodable {
+    ($($ty:ty),*) => {
+        $(
+            impl Encodable for $ty {
+                fn encode(&self, enc: &mut Encoder) {
+                    enc.emit_uint(*self as u64)
+                }
+            }
+
+            impl<'d> Decodable<'d> for $ty {
+                fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<$ty> {
+                    <$ty>::try_from(dec.read_uint()?)
+                        .map_err(|_| DecodeError::Overflow)
+                }
+            }
+        )*
+    }
+}
+
+macro_rules! int_encodable {
+    ($($ty:ty),*) => {
+        $(
+            impl Encodable for $ty {
+                fn encode(&self, enc: &mut Encoder) {
+                    enc.emit_int(*self as i64)
+                }
+            }
+
+            impl<'d> Decodable<'d> for $ty {
+                fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<$ty> {
+                    <$ty>::try_from(dec.read_int()?)
+                        .map_err(|_| DecodeError::Overflow)
+                }
+            }
+        )*
+    }
+}
+
+uint_encodable!(u16, u32, u64, usize);
+int_encodable!(i8, i16, i32, i64, isize);
+
+impl Encodable for u8 {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_u8(*self)
+    }
+}
+
+impl<'d> Decodable<'d> for u8 {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<u8> {
+        dec.read_u8()
+    }
+}
+
+impl Encodable for bool {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_u8(*self as u8)
+    }
+}
+
+impl<'d> Decodable<'d> for bool {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<bool> {
+        match dec.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Err(DecodeError::BadTag { what: "bool", tag: tag as u64 })
+        }
+    }
+}
+
+impl Encodable for char {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_uint(*self as u64)
+    }
+}
+
+impl<'d> Decodable<'d> for char {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<char> {
+        let val = dec.read_uint()?;
+
+        u32::try_from(val).ok()
+            .and_then(char::from_u32)
+            .ok_or(DecodeError::BadTag { what: "char", tag: val })
+    }
+}
+
+impl Encodable for () {
+    fn encode(&self, _enc: &mut Encoder) {}
+}
+
+impl<'d> Decodable<'d> for () {
+    fn decode(_dec: &mut Decoder<'d, '_>) -> DecodeResult<()> {
+        Ok(())
+    }
+}
+
+impl Encodable for str {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_str(self)
+    }
+}
+
+impl Encodable for String {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_str(self)
+    }
+}
+
+impl<'d> Decodable<'d> for String {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<String> {
+        dec.read_str().map(|s| s.to_string())
+    }
+}
+
+impl Encodable for Fingerprint {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.body.extend_from_slice(&self.0.to_le_bytes());
+        enc.body.extend_from_slice(&self.1.to_le_bytes());
+    }
+}
+
+impl<'d> Decodable<'d> for Fingerprint {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Fingerprint> {
+        let mut words = [0; 2];
+
+        for word in &mut words {
+            let mut bytes = [0; 8];
+
+            for byte in &mut bytes {
+                *byte = dec.read_u8()?;
+            }
+
+            *word = u64::from_le_bytes(bytes);
+        }
+
+        Ok(Fingerprint(words[0], words[1]))
+    }
+}
+
+impl<T: Encodable + ?Sized> Encodable for &T {
+    fn encode(&self, enc: &mut Encoder) {
+        (**self).encode(enc)
+    }
+}
+
+impl<T: Encodable + ?Sized> Encodable for Box<T> {
+    fn encode(&self, enc: &mut Encoder) {
+        (**self).encode(enc)
+    }
+}
+
+impl<'d, T: Decodable<'d>> Decodable<'d> for Box<T> {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Box<T>> {
+        T::decode(dec).map(Box::new)
+    }
+}
+
+impl<T: Encodable> Encodable for Option<T> {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            None => enc.emit_u8(0),
+            Some(val) => {
+                enc.emit_u8(1);
+                val.encode(enc)
+            }
+        }
+    }
+}
+
+impl<'d, T: Decodable<'d>> Decodable<'d> for Option<T> {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Option<T>> {
+        match dec.read_u8()? {
+            0 => Ok(None),
+            1 => T::decode(dec).map(Some),
+            tag => Err(DecodeError::BadTag { what: "Option", tag: tag as u64 })
+        }
+    }
+}
+
+impl<T: Encodable> Encodable for [T] {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_uint(self.len() as u64);
+
+        for elem in self {
+            elem.encode(enc)
+        }
+    }
+}
+
+impl<T: Encodable> Encodable for Vec<T> {
+    fn encode(&self, enc: &mut Encoder) {
+        self.as_slice().encode(enc)
+    }
+}
+
+impl<'d, T: Decodable<'d>> Decodable<'d> for Vec<T> {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Vec<T>> {
+        let len = dec.read_uint()?;
+        // Don't trust the length for preallocation: every element
+        // takes at least one byte.
+        let mut out = Vec::with_capacity((len as usize).min(dec.rest().len()));
+
+        for _ in 0..len {
+            out.push(T::decode(dec)?);
+        }
+
+        Ok(out)
+    }
+}
+
+impl<A: Encodable, B: Encodable> Encodable for (A, B) {
+    fn encode(&self, enc: &mut Encoder) {
+        self.0.encode(enc);
+        self.1.encode(enc)
+    }
+}
+
+impl<'d, A: Decodable<'d>, B: Decodable<'d>> Decodable<'d> for (A, B) {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<(A, B)> {
+        Ok((A::decode(dec)?, B::decode(dec)?))
+    }
+}
+
+impl<A: Encodable, B: Encodable, C: Encodable> Encodable for (A, B, C) {
+    fn encode(&self, enc: &mut Encoder) {
+        self.0.encode(enc);
+        self.1.encode(enc);
+        self.2.encode(enc)
+    }
+}
+
+impl<'d, A: Decodable<'d>, B: Decodable<'d>,
+     C: Decodable<'d>> Decodable<'d> for (A, B, C) {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<(A, B, C)> {
+        Ok((A::decode(dec)?, B::decode(dec)?, C::decode(dec)?))
+    }
+}
+
+impl<K: Encodable, V: Encodable> Encodable for BTreeMap<K, V> {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_uint(self.len() as u64);
+
+        for (key, val) in self {
+            key.encode(enc);
+            val.encode(enc)
+        }
+    }
+}
+
+impl<'d, K: Decodable<'d> + Ord,
+     V: Decodable<'d>> Decodable<'d> for BTreeMap<K, V> {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<BTreeMap<K, V>> {
+        let len = dec.read_uint()?;
+        let mut out = BTreeMap::new();
+
+        for _ in 0..len {
+            let key = K::decode(dec)?;
+            let val = V::decode(dec)?;
+
+            out.insert(key, val);
+        }
+
+        Ok(out)
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of data"),
+            DecodeError::Overflow => write!(f, "integer overflow"),
+            DecodeError::BadUtf8 => write!(f, "invalid UTF-8 in string"),
+            DecodeError::TrailingData => write!(f, "trailing data"),
+            DecodeError::BadStringIndex(idx) =>
+                write!(f, "string index {} out of range", idx),
+            DecodeError::BadTag { what, tag } =>
+                write!(f, "invalid tag {} for {}", tag, what)
+        }
+    }
+}
+
+#[test]
+fn test_varints() {
+    let vals: Vec<i64> = vec![0, 1, -1, 63, -64, 64, 1 << 40, i64::MIN,
+                              i64::MAX];
+    let uvals: Vec<u64> = vec![0, 127, 128, 300, u64::MAX];
+    let data = encode(&(vals.clone(), uvals.clone()));
+    let mut intern = StrIntern::new();
+
+    assert_eq!(decode(&data, &mut intern), Ok((vals, uvals)));
+}
+
+#[test]
+fn test_interned_once() {
+    let words = vec!["alpha", "beta", "alpha", "alpha"];
+    let mut enc = Encoder::new();
+
+    for word in &words {
+        enc.emit_interned(word);
+    }
+
+    let data = enc.finish();
+    let mut intern = StrIntern::new();
+    let mut dec = Decoder::new(&data, &mut intern).unwrap();
+    let out: Vec<&str> = (0..4).map(|_| dec.read_interned().unwrap())
+        .collect();
+
+    assert_eq!(out, words);
+    assert_eq!(intern.len(), 2);
+    // Table (count, two strings) plus four one-byte indexes.
+    assert_eq!(data.len(), 1 + 6 + 5 + 4);
+}
+
+#[test]
+fn test_truncated() {
+    let data = encode(&vec![String::from("hello")]);
+    let mut intern = StrIntern::new();
+
+    assert_eq!(decode::<Vec<String>>(&data[..data.len() - 1], &mut intern),
+               Err(DecodeError::UnexpectedEnd));
+}
@@ -0,0 +1,31 @@
+//! On-disk incremental compilation cache.
+//!
+//! A `CacheDir` is a directory of cache entries for one tool version
+//! and target.  Each entry records the fingerprint of the inputs it was
+//! computed from, so a later process can reuse it if the inputs are
+//! unchanged, and a fingerprint of its own contents, so that truncated
+//! or corrupted entries are detected rather than trusted.
+//!
+//! Entry payloads use the encoding in `encode`: varint integers, with
+//! interned strings (symbols and filenames) collected into a string
+//! table that is re-interned when the entry is loaded.  The encoding is
+//! always available; `CacheDir` requires the `fs` feature.
+
+#[cfg(feature = "fs")]
+pub use self::dir::CacheDir;
+#[cfg(feature = "fs")]
+pub use self::dir::CacheError;
+#[cfg(feature = "fs")]
+pub use self::dir::CacheResult;
+pub use self::encode::DecodeError;
+pub use self::encode::DecodeResult;
+pub use self::encode::Decodable;
+pub use self::encode::Decoder;
+pub use self::encode::Encodable;
+pub use self::encode::Encoder;
+pub use self::encode::decode;
+pub use self::encode::encode;
+
+#[cfg(feature = "fs")]
+mod dir;
+mod encode;
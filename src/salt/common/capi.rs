@@ -0,0 +1,608 @@
+//! C interface, enabled by the `capi` feature.
+//!
+//! This exposes `#[repr(C)]` mirrors of the position types, with
+//! conversions to and from the Rust types and a few functions for
+//! comparing and formatting positions, so that frontends and plugins
+//! written in other languages share the crate's position model.
+//! Diagnostics are emitted into a `SaltDiagnostics`, which filters
+//! them as a `DiagnosticSink` does and renders them as plain text.
+//! `header` produces the matching C declarations.
+//!
+//! Strings are passed as pointer and length pairs (`SaltStr`) and are
+//! not nul-terminated.
+
+use salt::common::filename::Filename;
+use salt::common::filename::FilenameCtx;
+use salt::common::filename::FilenameTable;
+use salt::common::filename::Normalization;
+use salt::common::message::DiagnosticSink;
+use salt::common::message::Emitted;
+use salt::common::message::Message;
+use salt::common::message::PlainRenderer;
+use salt::common::message::Renderer;
+use salt::common::message::Severity;
+use salt::common::message::SinkConfig;
+use salt::common::position::FilePosition;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use salt::common::str::arena::StrArena;
+use std::cmp::Ordering;
+use std::slice;
+use std::str;
+
+/// A borrowed UTF-8 string.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SaltStr {
+    /// Pointer to the first byte.
+    pub ptr: *const u8,
+    /// Length in bytes.
+    pub len: usize
+}
+
+/// C mirror of `Point`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SaltPoint {
+    /// The line number, starting at 1.
+    pub line: u32,
+    /// The column number, starting at 1.
+    pub col: u32
+}
+
+/// Kinds of `SaltLocation`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SaltLocationKind {
+    /// A span from `start` to `end`.
+    Span = 0,
+    /// A single point, `start`.  `end` is ignored.
+    Point = 1
+}
+
+/// C mirror of `Location`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SaltLocation {
+    /// The kind of location.
+    pub kind: SaltLocationKind,
+    /// The start of a span, or the point.
+    pub start: SaltPoint,
+    /// The end of a span.
+    pub end: SaltPoint
+}
+
+/// C mirror of `FilePosition`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SaltFilePosition {
+    /// The name of the file.
+    pub filename: SaltStr,
+    /// The location in the file.
+    pub loc: SaltLocation
+}
+
+/// C mirror of `Severity`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SaltSeverity {
+    /// A suggestion for fixing a problem.
+    Help = 0,
+    /// Additional information.
+    Note = 1,
+    /// A possible problem, which does not stop compilation.
+    Warning = 2,
+    /// A problem, which causes compilation to fail.
+    Error = 3,
+    /// An internal error in the compiler itself.
+    Bug = 4
+}
+
+/// What `salt_diagnostics_emit` did with a message: the C mirror of
+/// `Emitted`, or that the error limit has been reached.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SaltEmitted {
+    /// The message was kept, to be shown.
+    Shown = 0,
+    /// The message was an allowed warning, and was dropped.
+    Allowed = 1,
+    /// The message was the same as one already shown, and was dropped.
+    Duplicate = 2,
+    /// Too many messages with the message's code were already shown,
+    /// and it was dropped.
+    Suppressed = 3,
+    /// The error limit has been reached, and compilation should stop.
+    /// The message reaching the limit is kept.
+    TooManyErrors = 4
+}
+
+/// Diagnostics emitted by a frontend written in another language.
+/// This is opaque to C, and is created by `salt_diagnostics_new` and
+/// freed by `salt_diagnostics_free`.
+pub struct SaltDiagnostics {
+    /// The messages kept.  This borrows from `arena`, so it is
+    /// declared, and dropped, first.
+    sink: DiagnosticSink<'static, FilePosition<'static>>,
+    /// The table the filenames of positions are interned in.  This
+    /// also borrows from `arena`.
+    filenames: FilenameTable<'static>,
+    /// The arena holding the filenames.  It is boxed so that it does
+    /// not move with the handle, and only kept to be freed with it.
+    _arena: Box<StrArena>
+}
+
+impl SaltStr {
+    /// Borrow `s`.  The result is valid as long as `s` is.
+    pub fn new(s: &str) -> SaltStr {
+        SaltStr { ptr: s.as_ptr(), len: s.len() }
+    }
+
+    /// Get the string this refers to.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to `len` bytes of valid UTF-8 that live for `'a`.
+    pub unsafe fn as_str<'a>(&self) -> &'a str {
+        if self.len == 0 {
+            ""
+        } else {
+            str::from_utf8_unchecked(slice::from_raw_parts(self.ptr, self.len))
+        }
+    }
+}
+
+impl From<Point> for SaltPoint {
+    fn from(point: Point) -> SaltPoint {
+        SaltPoint { line: point.line, col: point.col }
+    }
+}
+
+impl From<SaltPoint> for Point {
+    fn from(point: SaltPoint) -> Point {
+        Point { line: point.line, col: point.col }
+    }
+}
+
+impl<'a> From<&'a Location> for SaltLocation {
+    fn from(loc: &'a Location) -> SaltLocation {
+        match loc {
+            Location::Span { start, end } =>
+                SaltLocation { kind: SaltLocationKind::Span,
+                               start: (*start).into(), end: (*end).into() },
+            Location::Point { point } =>
+                SaltLocation { kind: SaltLocationKind::Point,
+                               start: (*point).into(), end: (*point).into() }
+        }
+    }
+}
+
+impl From<SaltLocation> for Location {
+    fn from(loc: SaltLocation) -> Location {
+        match loc.kind {
+            SaltLocationKind::Span =>
+                Location::Span { start: loc.start.into(),
+                                 end: loc.end.into() },
+            SaltLocationKind::Point =>
+                Location::Point { point: loc.start.into() }
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b FilePosition<'a>> for SaltFilePosition {
+    /// The filename is borrowed from the interned recorded name.
+    fn from(pos: &'b FilePosition<'a>) -> SaltFilePosition {
+        SaltFilePosition { filename: SaltStr::new(pos.filename.recorded()),
+                           loc: (&pos.loc).into() }
+    }
+}
+
+impl From<Severity> for SaltSeverity {
+    fn from(severity: Severity) -> SaltSeverity {
+        match severity {
+            Severity::Help => SaltSeverity::Help,
+            Severity::Note => SaltSeverity::Note,
+            Severity::Warning => SaltSeverity::Warning,
+            Severity::Error => SaltSeverity::Error,
+            Severity::Bug => SaltSeverity::Bug
+        }
+    }
+}
+
+impl From<SaltSeverity> for Severity {
+    fn from(severity: SaltSeverity) -> Severity {
+        match severity {
+            SaltSeverity::Help => Severity::Help,
+            SaltSeverity::Note => Severity::Note,
+            SaltSeverity::Warning => Severity::Warning,
+            SaltSeverity::Error => Severity::Error,
+            SaltSeverity::Bug => Severity::Bug
+        }
+    }
+}
+
+impl From<Emitted> for SaltEmitted {
+    fn from(emitted: Emitted) -> SaltEmitted {
+        match emitted {
+            Emitted::Shown => SaltEmitted::Shown,
+            Emitted::Allowed => SaltEmitted::Allowed,
+            Emitted::Duplicate => SaltEmitted::Duplicate,
+            Emitted::Suppressed => SaltEmitted::Suppressed
+        }
+    }
+}
+
+impl SaltFilePosition {
+    /// Convert to a `FilePosition`, interning the filename with `ctx`.
+    ///
+    /// # Safety
+    ///
+    /// `filename` must be valid as described for `SaltStr::as_str`.
+    pub unsafe fn to_file_position<'a, C>(&self,
+                                          ctx: &mut C) -> FilePosition<'a>
+        where C: FilenameCtx<'a> {
+        let filename: Filename<'a> = ctx.filename(self.filename.as_str());
+
+        FilePosition { filename, loc: self.loc.into() }
+    }
+}
+
+/// Compare two points, returning a negative number, zero, or a
+/// positive number as `a` is before, equal to, or after `b`.
+///
+/// # Safety
+///
+/// `a` and `b` must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn salt_point_cmp(a: *const SaltPoint,
+                                        b: *const SaltPoint) -> i32 {
+    match Point::from(*a).cmp(&Point::from(*b)) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1
+    }
+}
+
+/// Copy `s` into `buf`, truncating and nul-terminating, and return
+/// the length of `s`.
+unsafe fn write_buf(s: &str, buf: *mut u8, len: usize) -> usize {
+    if !buf.is_null() && len > 0 {
+        let n = s.len().min(len - 1);
+        let out = slice::from_raw_parts_mut(buf, len);
+
+        out[..n].copy_from_slice(&s.as_bytes()[..n]);
+        out[n] = 0;
+    }
+
+    s.len()
+}
+
+/// Format a location the same way as `Location`'s `Display`, into
+/// `buf` of size `len`.  Like `snprintf`, this returns the length of
+/// the full text, which may be more than was written.
+///
+/// # Safety
+///
+/// `loc` must be a valid pointer, and `buf` must be null or point to
+/// `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn salt_location_format(loc: *const SaltLocation,
+                                              buf: *mut u8,
+                                              len: usize) -> usize {
+    write_buf(&Location::from(*loc).to_string(), buf, len)
+}
+
+/// Format a file position the same way as `FilePosition`'s `Display`,
+/// into `buf` of size `len`, returning the length of the full text.
+///
+/// # Safety
+///
+/// `pos` must be a valid pointer with a valid filename, and `buf` must
+/// be null or point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn salt_file_position_format(pos: *const SaltFilePosition,
+                                                   buf: *mut u8,
+                                                   len: usize) -> usize {
+    let pos = &*pos;
+    let text = format!("{} {}", pos.filename.as_str(), Location::from(pos.loc));
+
+    write_buf(&text, buf, len)
+}
+
+impl SaltDiagnostics {
+    /// Create an empty collection, filtering messages as `config` says.
+    pub fn new(config: SinkConfig) -> SaltDiagnostics {
+        let arena = Box::new(StrArena::new());
+        // The arena is boxed, so it stays put when the handle moves,
+        // and it is dropped after everything borrowing it.
+        let borrowed: &'static StrArena =
+            unsafe { &*(&*arena as *const StrArena) };
+
+        SaltDiagnostics { sink: DiagnosticSink::new(config),
+                          filenames: FilenameTable::new(
+                              borrowed, Normalization::default()),
+                          _arena: arena }
+    }
+
+    /// Get the messages kept so far, in order.
+    pub fn messages(&self) -> &[Message<FilePosition<'_>>] {
+        self.sink.messages()
+    }
+}
+
+/// Create an empty collection of diagnostics, which stops accepting
+/// messages after `max_errors` errors, or never if it is 0.  Free it
+/// with `salt_diagnostics_free`.
+#[no_mangle]
+pub extern "C" fn salt_diagnostics_new(max_errors: usize)
+                                       -> *mut SaltDiagnostics {
+    let config = SinkConfig { max_errors: Some(max_errors)
+                                              .filter(|&max| max != 0),
+                              ..SinkConfig::new() };
+
+    Box::into_raw(Box::new(SaltDiagnostics::new(config)))
+}
+
+/// Free a collection of diagnostics.
+///
+/// # Safety
+///
+/// `diags` must be null or have come from `salt_diagnostics_new`, and
+/// not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn salt_diagnostics_free(diags: *mut SaltDiagnostics) {
+    if !diags.is_null() {
+        drop(Box::from_raw(diags))
+    }
+}
+
+/// Emit a message with severity `severity`, headline `headline`, and
+/// code `code`, or no code if `code` is empty, at `pos`, or at no
+/// position if `pos` is null.  The strings are copied.
+///
+/// # Safety
+///
+/// `diags` must be a valid collection, `code` and `headline` must be
+/// valid as described for `SaltStr::as_str`, and `pos` must be null
+/// or a valid pointer with a valid filename.
+#[no_mangle]
+pub unsafe extern "C" fn salt_diagnostics_emit(diags: *mut SaltDiagnostics,
+                                               severity: SaltSeverity,
+                                               code: SaltStr,
+                                               headline: SaltStr,
+                                               pos: *const SaltFilePosition)
+                                               -> SaltEmitted {
+    let diags = &mut *diags;
+    let mut msg = Message::new(severity.into(), headline.as_str());
+
+    if code.len != 0 {
+        msg = msg.with_code(code.as_str());
+    }
+
+    if !pos.is_null() {
+        // The table copies the filename into the arena, so it need
+        // not outlive this call.
+        msg = msg.with_position((*pos).to_file_position(&mut diags.filenames));
+    }
+
+    match diags.sink.emit(msg) {
+        Ok(emitted) => emitted.into(),
+        Err(_) => SaltEmitted::TooManyErrors
+    }
+}
+
+/// Get the number of errors kept.
+///
+/// # Safety
+///
+/// `diags` must be a valid collection.
+#[no_mangle]
+pub unsafe extern "C" fn salt_diagnostics_error_count(
+        diags: *const SaltDiagnostics) -> usize {
+    (*diags).sink.error_count()
+}
+
+/// Render the messages kept as plain text, as `PlainRenderer` does,
+/// into `buf` of size `len`, returning the length of the full text.
+///
+/// # Safety
+///
+/// `diags` must be a valid collection, and `buf` must be null or point
+/// to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn salt_diagnostics_render(
+        diags: *const SaltDiagnostics, buf: *mut u8, len: usize) -> usize {
+    let text: String = (*diags).messages()
+        .iter()
+        .map(|msg| PlainRenderer.render(msg))
+        .collect();
+
+    write_buf(&text, buf, len)
+}
+
+/// Get the C header declaring this interface.
+pub fn header() -> String {
+    String::from("\
+/* Generated by salt-common; do not edit. */
+#ifndef SALT_COMMON_H
+#define SALT_COMMON_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef struct SaltStr {
+    const uint8_t *ptr;
+    size_t len;
+} SaltStr;
+
+typedef struct SaltPoint {
+    uint32_t line;
+    uint32_t col;
+} SaltPoint;
+
+typedef enum SaltLocationKind {
+    SALT_LOCATION_SPAN = 0,
+    SALT_LOCATION_POINT = 1
+} SaltLocationKind;
+
+typedef struct SaltLocation {
+    SaltLocationKind kind;
+    SaltPoint start;
+    SaltPoint end;
+} SaltLocation;
+
+typedef struct SaltFilePosition {
+    SaltStr filename;
+    SaltLocation loc;
+} SaltFilePosition;
+
+typedef enum SaltSeverity {
+    SALT_SEVERITY_HELP = 0,
+    SALT_SEVERITY_NOTE = 1,
+    SALT_SEVERITY_WARNING = 2,
+    SALT_SEVERITY_ERROR = 3,
+    SALT_SEVERITY_BUG = 4
+} SaltSeverity;
+
+typedef enum SaltEmitted {
+    SALT_EMITTED_SHOWN = 0,
+    SALT_EMITTED_ALLOWED = 1,
+    SALT_EMITTED_DUPLICATE = 2,
+    SALT_EMITTED_SUPPRESSED = 3,
+    SALT_EMITTED_TOO_MANY_ERRORS = 4
+} SaltEmitted;
+
+typedef struct SaltDiagnostics SaltDiagnostics;
+
+int32_t salt_point_cmp(const SaltPoint *a, const SaltPoint *b);
+size_t salt_location_format(const SaltLocation *loc, uint8_t *buf,
+                            size_t len);
+size_t salt_file_position_format(const SaltFilePosition *pos, uint8_t *buf,
+                                 size_t len);
+
+SaltDiagnostics *salt_diagnostics_new(size_t max_errors);
+void salt_diagnostics_free(SaltDiagnostics *diags);
+SaltEmitted salt_diagnostics_emit(SaltDiagnostics *diags,
+                                  SaltSeverity severity, SaltStr code,
+                                  SaltStr headline,
+                                  const SaltFilePosition *pos);
+size_t salt_diagnostics_error_count(const SaltDiagnostics *diags);
+size_t salt_diagnostics_render(const SaltDiagnostics *diags, uint8_t *buf,
+                               size_t len);
+
+#endif
+")
+}
+
+#[test]
+fn test_location_round_trip() {
+    let loc = Location::Span { start: Point { line: 2, col: 3 },
+                               end: Point { line: 4, col: 1 } };
+    let c = SaltLocation::from(&loc);
+
+    assert_eq!(c.kind, SaltLocationKind::Span);
+    assert_eq!(Location::from(c).to_string(), loc.to_string());
+}
+
+#[test]
+fn test_format() {
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let pos = FilePosition { filename: names.filename("a.salt"),
+                             loc: Location::Point {
+                                 point: Point { line: 7, col: 2 }
+                             } };
+    let c = SaltFilePosition::from(&pos);
+    let mut buf = [0xffu8; 8];
+    let len = unsafe {
+        salt_file_position_format(&c, buf.as_mut_ptr(), buf.len())
+    };
+
+    assert_eq!(len, "a.salt 7.2".len());
+    assert_eq!(&buf, b"a.salt \0");
+
+    let back = unsafe { c.to_file_position(&mut names) };
+
+    assert_eq!(back.filename, pos.filename);
+}
+
+#[test]
+fn test_point_cmp() {
+    let a = SaltPoint { line: 1, col: 9 };
+    let b = SaltPoint { line: 2, col: 1 };
+
+    unsafe {
+        assert_eq!(salt_point_cmp(&a, &b), -1);
+        assert_eq!(salt_point_cmp(&b, &a), 1);
+        assert_eq!(salt_point_cmp(&a, &a), 0);
+    }
+}
+
+#[test]
+fn test_header() {
+    let header = header();
+
+    assert!(header.contains("typedef struct SaltFilePosition {"));
+    assert!(header.contains("salt_location_format("));
+    assert!(header.contains("SALT_SEVERITY_ERROR = 3"));
+    assert!(header.contains("salt_diagnostics_emit("));
+}
+
+#[test]
+fn test_severity_round_trip() {
+    for &severity in &[Severity::Help, Severity::Note, Severity::Warning,
+                       Severity::Error, Severity::Bug] {
+        assert_eq!(Severity::from(SaltSeverity::from(severity)), severity);
+    }
+
+    assert_eq!(SaltSeverity::Error as u32, 3);
+}
+
+#[test]
+fn test_diagnostics() {
+    let name = String::from("a.salt");
+    let pos = SaltFilePosition {
+        filename: SaltStr::new(&name),
+        loc: SaltLocation { kind: SaltLocationKind::Point,
+                            start: SaltPoint { line: 3, col: 5 },
+                            end: SaltPoint { line: 3, col: 5 } }
+    };
+    let none = SaltStr::new("");
+
+    unsafe {
+        let diags = salt_diagnostics_new(2);
+        let emit = |severity, code, headline, pos| {
+            salt_diagnostics_emit(diags, severity, SaltStr::new(code),
+                                  SaltStr::new(headline), pos)
+        };
+
+        let mismatch = || {
+            emit(SaltSeverity::Error, "E0042", "mismatched types", &pos)
+        };
+
+        assert_eq!(mismatch(), SaltEmitted::Shown);
+        assert_eq!(mismatch(), SaltEmitted::Duplicate);
+        assert_eq!(salt_diagnostics_emit(diags, SaltSeverity::Warning, none,
+                                         SaltStr::new("unused"),
+                                         ::std::ptr::null()),
+                   SaltEmitted::Shown);
+        assert_eq!(emit(SaltSeverity::Bug, "", "oops", ::std::ptr::null()),
+                   SaltEmitted::TooManyErrors);
+        assert_eq!(salt_diagnostics_error_count(diags), 2);
+
+        drop(name);
+
+        let len = salt_diagnostics_render(diags, ::std::ptr::null_mut(), 0);
+        let mut buf = vec![0u8; len + 1];
+
+        salt_diagnostics_render(diags, buf.as_mut_ptr(), buf.len());
+
+        assert_eq!(::std::str::from_utf8(&buf[..len]).unwrap(),
+                   "a.salt 3.5: error[E0042]: mismatched types\n\
+                    warning: unused\n\
+                    internal compiler error: oops\n");
+
+        salt_diagnostics_free(diags);
+    }
+}
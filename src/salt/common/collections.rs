@@ -0,0 +1,141 @@
+//! Hash maps and sets keyed by interned values.
+//!
+//! `Symbol`s and `Filename`s hash as one or two words, the addresses
+//! of their interned strings, so the default SipHash hasher, built to
+//! resist attacks on arbitrary keys, is wasted on them.  The maps and
+//! sets here use `PtrHasher`, a multiplicative hasher in the style of
+//! FxHash that mixes each word in a couple of instructions.
+//!
+//! `PtrHasher` is not resistant to collision attacks, which is of no
+//! concern for addresses of strings the compiler allocated itself.
+
+use salt::common::filename::Filename;
+use salt::common::symbol::Symbol;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::BuildHasherDefault;
+use std::hash::Hasher;
+
+/// The multiplier used to mix words, from FxHash.
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// A fast hasher for keys that hash as a few machine words, such as
+/// addresses of interned strings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PtrHasher {
+    hash: u64
+}
+
+/// Builds `PtrHasher`s.
+pub type BuildPtrHasher = BuildHasherDefault<PtrHasher>;
+
+/// A hash map keyed by symbols.
+pub type SymbolMap<'a, V> = HashMap<Symbol<'a>, V, BuildPtrHasher>;
+
+/// A hash set of symbols.
+pub type SymbolSet<'a> = HashSet<Symbol<'a>, BuildPtrHasher>;
+
+/// A hash map keyed by filenames.
+pub type FilenameMap<'a, V> = HashMap<Filename<'a>, V, BuildPtrHasher>;
+
+/// A hash set of filenames.
+pub type FilenameSet<'a> = HashSet<Filename<'a>, BuildPtrHasher>;
+
+impl PtrHasher {
+    /// Mix `word` into the hash.
+    #[inline]
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED)
+    }
+}
+
+impl Hasher for PtrHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+
+            word.copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word))
+        }
+
+        let rest = chunks.remainder();
+
+        if !rest.is_empty() {
+            let mut word = [0; 8];
+
+            word[..rest.len()].copy_from_slice(rest);
+            self.add(u64::from_le_bytes(word))
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, n: u8) {
+        self.add(n as u64)
+    }
+
+    #[inline]
+    fn write_u32(&mut self, n: u32) {
+        self.add(n as u64)
+    }
+
+    #[inline]
+    fn write_u64(&mut self, n: u64) {
+        self.add(n)
+    }
+
+    #[inline]
+    fn write_usize(&mut self, n: usize) {
+        self.add(n as u64)
+    }
+
+    /// The multiplication leaves the low bits, which pick the bucket,
+    /// depending only on the low bits of the input, so the high bits
+    /// are rotated down.
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash.rotate_left(26)
+    }
+}
+
+#[test]
+fn test_symbol_map() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+    use salt::common::symbol::SymbolCtx;
+
+    let mut intern = StrIntern::new();
+    let names: Vec<String> = (0..1000).map(|i| format!("identifier{}", i))
+        .collect();
+    let mut map = SymbolMap::default();
+    let mut set = SymbolSet::default();
+    let mut files = FilenameMap::default();
+
+    for (i, name) in names.iter().enumerate() {
+        map.insert(intern.symbol(name), i);
+        set.insert(intern.symbol(name));
+    }
+
+    files.insert(intern.filename("a.salt"), 1);
+    files.insert(intern.filename("b.salt"), 2);
+
+    assert_eq!(map.len(), 1000);
+    assert_eq!(set.len(), 1000);
+    assert_eq!(map[&intern.symbol(&names[123])], 123);
+    assert!(!set.contains(&intern.symbol("x")));
+    assert_eq!(files[&intern.filename("b.salt")], 2);
+}
+
+#[test]
+fn test_ptr_hasher_spreads() {
+    use std::hash::BuildHasher;
+
+    let build = BuildPtrHasher::default();
+    let buckets: HashSet<u64> = (0..256u64)
+        .map(|i| build.hash_one(0x1000 + i * 16) & 0xff)
+        .collect();
+
+    // Addresses 16 bytes apart should not pile into a few buckets.
+    assert!(buckets.len() > 128, "{} buckets", buckets.len());
+}
@@ -0,0 +1,486 @@
+//! Configuration file loading.
+//!
+//! Configuration files are written in TOML.  They are parsed into a
+//! tree of `ConfigItem`s, each of which remembers its `FilePosition`
+//! in the configuration file, and then converted to typed option
+//! structures through the `FromConfig` trait.  All errors carry the
+//! position of the offending text.
+
+use salt::common::filename::Filename;
+use salt::common::message::Message;
+use salt::common::position::FilePosition;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::btree_map;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+pub mod stack;
+pub mod toml;
+
+/// A configuration value, together with its position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigItem<'a> {
+    /// The value.
+    pub value: ConfigValue<'a>,
+    /// The position of the value in the configuration file.
+    pub pos: FilePosition<'a>
+}
+
+/// A configuration value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigValue<'a> {
+    /// A boolean value.
+    Bool(bool),
+    /// An integer value.
+    Int(i64),
+    /// A floating-point value.
+    Float(f64),
+    /// A string value.
+    Str(String),
+    /// An array of values.
+    Array(Vec<ConfigItem<'a>>),
+    /// A table of values.
+    Table(ConfigTable<'a>)
+}
+
+/// A table mapping keys to configuration values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigTable<'a>(BTreeMap<String, ConfigEntry<'a>>);
+
+/// An entry in a `ConfigTable`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigEntry<'a> {
+    /// The position of the key.
+    pub key_pos: FilePosition<'a>,
+    /// The value.
+    pub item: ConfigItem<'a>
+}
+
+/// Result of converting configuration values.
+pub type ConfigResult<'a, T> = ::std::result::Result<T, Vec<ConfigError<'a>>>;
+
+/// An error in a configuration file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigError<'a> {
+    /// The position of the error.
+    pub pos: FilePosition<'a>,
+    /// The kind of error.
+    pub kind: ConfigErrorKind
+}
+
+/// The kinds of configuration errors.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConfigErrorKind {
+    /// The file is not syntactically valid.
+    Syntax(String),
+    /// A key was defined more than once.
+    DuplicateKey(String),
+    /// A value has the wrong type.
+    WrongType {
+        /// The expected type.
+        expected: &'static str,
+        /// The type that was found.
+        found: &'static str
+    },
+    /// A key is not recognized.
+    UnknownKey(String),
+    /// A required key is missing.
+    MissingKey(String),
+    /// A value has the right type, but is not acceptable.
+    InvalidValue(String)
+}
+
+/// Conversion from configuration values into typed options.
+pub trait FromConfig<'a>: Sized {
+    /// Convert `item`, reporting all problems that are found.
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, Self>;
+}
+
+/// Helper for implementing `FromConfig` on option structures.  Keys
+/// are read with `get` and `require`, errors are accumulated, and
+/// `finish` reports any keys that were never read as unknown.
+pub struct TableReader<'r, 'a: 'r> {
+    table: &'r ConfigTable<'a>,
+    pos: &'r FilePosition<'a>,
+    used: BTreeSet<&'r str>,
+    errors: Vec<ConfigError<'a>>
+}
+
+impl<'a> ConfigValue<'a> {
+    /// Get the name of the value's type, for use in messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ConfigValue::Bool(_) => "boolean",
+            ConfigValue::Int(_) => "integer",
+            ConfigValue::Float(_) => "float",
+            ConfigValue::Str(_) => "string",
+            ConfigValue::Array(_) => "array",
+            ConfigValue::Table(_) => "table"
+        }
+    }
+}
+
+impl<'a> ConfigItem<'a> {
+    /// Create an error at this item's position.
+    pub fn error(&self, kind: ConfigErrorKind) -> ConfigError<'a> {
+        ConfigError { pos: self.pos.clone(), kind }
+    }
+
+    /// Create an error reporting that this item is not of type
+    /// `expected`.
+    pub fn wrong_type(&self, expected: &'static str) -> ConfigError<'a> {
+        self.error(ConfigErrorKind::WrongType {
+            expected, found: self.value.type_name()
+        })
+    }
+
+    /// Get a `TableReader` for this item, if it is a table.
+    pub fn reader(&self) -> ::std::result::Result<TableReader<'_, 'a>,
+                                                  ConfigError<'a>> {
+        match self.value {
+            ConfigValue::Table(ref table) => Ok(TableReader::new(table,
+                                                                 &self.pos)),
+            _ => Err(self.wrong_type("table"))
+        }
+    }
+}
+
+impl<'a> ConfigTable<'a> {
+    /// Create an empty `ConfigTable`.
+    pub fn new() -> ConfigTable<'a> {
+        ConfigTable(BTreeMap::new())
+    }
+
+    /// Look up a key.
+    pub fn get(&self, key: &str) -> Option<&ConfigEntry<'a>> {
+        self.0.get(key)
+    }
+
+    /// Look up a key for modification.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut ConfigEntry<'a>> {
+        self.0.get_mut(key)
+    }
+
+    /// Insert an entry, returning `false` and leaving the table
+    /// unchanged if the key is already present.
+    pub fn insert(&mut self, key: String, entry: ConfigEntry<'a>) -> bool {
+        match self.0.entry(key) {
+            btree_map::Entry::Occupied(_) => false,
+            btree_map::Entry::Vacant(ent) => {
+                ent.insert(entry);
+
+                true
+            }
+        }
+    }
+
+    /// Iterate over the entries, in key order.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, ConfigEntry<'a>> {
+        self.0.iter()
+    }
+
+    /// Get the number of entries.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'r, 'a> TableReader<'r, 'a> {
+    /// Create a `TableReader` for `table`, whose position is `pos`.
+    pub fn new(table: &'r ConfigTable<'a>,
+               pos: &'r FilePosition<'a>) -> TableReader<'r, 'a> {
+        TableReader { table, pos, used: BTreeSet::new(), errors: Vec::new() }
+    }
+
+    /// Read an optional key.  If the key is present but cannot be
+    /// converted, the errors are recorded and `None` is returned.
+    pub fn get<T: FromConfig<'a>>(&mut self, key: &'r str) -> Option<T> {
+        self.used.insert(key);
+
+        match self.table.get(key) {
+            Some(ent) => match T::from_config(&ent.item) {
+                Ok(val) => Some(val),
+                Err(errs) => {
+                    self.errors.extend(errs);
+
+                    None
+                }
+            },
+            None => None
+        }
+    }
+
+    /// Read a key with a default value.
+    pub fn get_or<T: FromConfig<'a>>(&mut self, key: &'r str,
+                                     default: T) -> T {
+        self.get(key).unwrap_or(default)
+    }
+
+    /// Read a required key, recording an error if it is missing.
+    pub fn require<T: FromConfig<'a>>(&mut self, key: &'r str) -> Option<T> {
+        if self.table.get(key).is_none() {
+            self.errors.push(ConfigError {
+                pos: self.pos.clone(),
+                kind: ConfigErrorKind::MissingKey(key.to_string())
+            });
+        }
+
+        self.get(key)
+    }
+
+    /// Record an error that was detected by the caller.
+    pub fn error(&mut self, err: ConfigError<'a>) {
+        self.errors.push(err)
+    }
+
+    /// Check that every key was read, and return all errors found.
+    pub fn finish(mut self) -> ConfigResult<'a, ()> {
+        for (key, ent) in self.table.iter() {
+            if !self.used.contains(key.as_str()) {
+                self.errors.push(ConfigError {
+                    pos: ent.key_pos.clone(),
+                    kind: ConfigErrorKind::UnknownKey(key.clone())
+                });
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            self.errors.sort_by(|a, b| a.pos.cmp(&b.pos));
+
+            Err(self.errors)
+        }
+    }
+}
+
+impl<'a> FromConfig<'a> for bool {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, bool> {
+        match item.value {
+            ConfigValue::Bool(b) => Ok(b),
+            _ => Err(vec![item.wrong_type("boolean")])
+        }
+    }
+}
+
+impl<'a> FromConfig<'a> for i64 {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, i64> {
+        match item.value {
+            ConfigValue::Int(n) => Ok(n),
+            _ => Err(vec![item.wrong_type("integer")])
+        }
+    }
+}
+
+impl<'a> FromConfig<'a> for u32 {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, u32> {
+        match item.value {
+            ConfigValue::Int(n) if n >= 0 && n <= i64::from(u32::MAX) =>
+                Ok(n as u32),
+            ConfigValue::Int(n) => Err(vec![item.error(
+                ConfigErrorKind::InvalidValue(
+                    format!("{} is out of range (0 to {})", n, u32::MAX)))]),
+            _ => Err(vec![item.wrong_type("integer")])
+        }
+    }
+}
+
+impl<'a> FromConfig<'a> for usize {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, usize> {
+        match item.value {
+            ConfigValue::Int(n) if n >= 0 => Ok(n as usize),
+            ConfigValue::Int(n) => Err(vec![item.error(
+                ConfigErrorKind::InvalidValue(
+                    format!("{} is negative", n)))]),
+            _ => Err(vec![item.wrong_type("integer")])
+        }
+    }
+}
+
+impl<'a> FromConfig<'a> for f64 {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, f64> {
+        match item.value {
+            ConfigValue::Float(n) => Ok(n),
+            ConfigValue::Int(n) => Ok(n as f64),
+            _ => Err(vec![item.wrong_type("float")])
+        }
+    }
+}
+
+impl<'a> FromConfig<'a> for String {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, String> {
+        match item.value {
+            ConfigValue::Str(ref s) => Ok(s.clone()),
+            _ => Err(vec![item.wrong_type("string")])
+        }
+    }
+}
+
+impl<'a, T: FromConfig<'a>> FromConfig<'a> for Vec<T> {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, Vec<T>> {
+        match item.value {
+            ConfigValue::Array(ref items) => {
+                let mut out = Vec::with_capacity(items.len());
+                let mut errs = Vec::new();
+
+                for item in items {
+                    match T::from_config(item) {
+                        Ok(val) => out.push(val),
+                        Err(e) => errs.extend(e)
+                    }
+                }
+
+                if errs.is_empty() {
+                    Ok(out)
+                } else {
+                    Err(errs)
+                }
+            },
+            _ => Err(vec![item.wrong_type("array")])
+        }
+    }
+}
+
+impl<'a> FromConfig<'a> for ConfigItem<'a> {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, ConfigItem<'a>> {
+        Ok(item.clone())
+    }
+}
+
+/// Parse the configuration file `filename`, whose contents are `src`,
+/// and convert it to typed options.
+pub fn load<'a, T: FromConfig<'a>>(filename: Filename<'a>,
+                                   src: &str) -> ConfigResult<'a, T> {
+    let root = toml::parse(filename, src).map_err(|err| vec![err])?;
+
+    T::from_config(&root)
+}
+
+impl Display for ConfigErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            ConfigErrorKind::Syntax(msg) => write!(f, "{}", msg),
+            ConfigErrorKind::DuplicateKey(key) =>
+                write!(f, "duplicate key `{}`", key),
+            ConfigErrorKind::WrongType { expected, found } =>
+                write!(f, "expected {}, found {}", expected, found),
+            ConfigErrorKind::UnknownKey(key) =>
+                write!(f, "unknown key `{}`", key),
+            ConfigErrorKind::MissingKey(key) =>
+                write!(f, "missing required key `{}`", key),
+            ConfigErrorKind::InvalidValue(msg) =>
+                write!(f, "invalid value: {}", msg)
+        }
+    }
+}
+
+impl<'a> ConfigError<'a> {
+    /// Convert the error into an error message at its position, for
+    /// rendering with the other diagnostics.
+    pub fn to_message(&self) -> Message<FilePosition<'a>> {
+        Message::error(self.kind.to_string()).with_position(self.pos.clone())
+    }
+}
+
+impl<'a> Display for ConfigError<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}: {}", self.pos, self.kind)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct TestOpts {
+    opt_level: u32,
+    debug: bool,
+    warnings: Vec<String>
+}
+
+#[cfg(test)]
+impl<'a> FromConfig<'a> for TestOpts {
+    fn from_config(item: &ConfigItem<'a>) -> ConfigResult<'a, TestOpts> {
+        let mut reader = item.reader().map_err(|err| vec![err])?;
+        let opt_level = reader.require("opt-level");
+        let debug = reader.get_or("debug", false);
+        let warnings = reader.get_or("warnings", Vec::new());
+
+        reader.finish()?;
+
+        Ok(TestOpts { opt_level: opt_level.unwrap(), debug, warnings })
+    }
+}
+
+#[test]
+fn test_load_typed() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut strs = StrIntern::new();
+    let fname = strs.filename("salt.toml");
+    let opts: TestOpts = load(fname, "opt-level = 2\n\
+                                      warnings = [\"unused\", \"shadow\"]\n")
+        .unwrap();
+
+    assert_eq!(opts, TestOpts { opt_level: 2, debug: false,
+                                warnings: vec![String::from("unused"),
+                                               String::from("shadow")] });
+}
+
+#[test]
+fn test_load_errors() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut strs = StrIntern::new();
+    let fname = strs.filename("salt.toml");
+    let errs = load::<TestOpts>(fname, "opt-level = -1\n\
+                                        debug = \"yes\"\n\
+                                        warning = []\n")
+        .unwrap_err();
+    let msgs: Vec<String> = errs.iter().map(|e| format!("{}", e)).collect();
+
+    assert_eq!(msgs, vec!["salt.toml 1.13-15: invalid value: \
+                           -1 is out of range (0 to 4294967295)",
+                          "salt.toml 2.9-14: expected boolean, found string",
+                          "salt.toml 3.1-8: unknown key `warning`"]);
+}
+
+#[test]
+fn test_load_missing() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut strs = StrIntern::new();
+    let fname = strs.filename("salt.toml");
+    let errs = load::<TestOpts>(fname, "debug = true\n").unwrap_err();
+
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].kind,
+               ConfigErrorKind::MissingKey(String::from("opt-level")));
+}
+
+#[test]
+fn test_to_message() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::message::PlainRenderer;
+    use salt::common::message::Renderer;
+    use salt::common::str::intern::StrIntern;
+
+    let mut strs = StrIntern::new();
+    let fname = strs.filename("salt.toml");
+    let errs = load::<TestOpts>(fname, "opt-level = 1\n\
+                                        debug = 3\n")
+        .unwrap_err();
+    let msg = errs[0].to_message();
+
+    assert!(msg.is_error());
+    assert_eq!(PlainRenderer.render(&msg),
+               "salt.toml 2.9-10: error: expected boolean, found integer\n");
+}
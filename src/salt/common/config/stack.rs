@@ -0,0 +1,374 @@
+//! Layered configuration resolution.
+//!
+//! A `ConfigStack` merges settings from built-in defaults, configuration
+//! files, environment variables, and command-line flags.  Later layers
+//! take precedence over earlier ones, and within a layer the most
+//! recently added setting wins.  Every setting remembers where it came
+//! from, so it is always possible to explain why a setting has the
+//! value it does.
+
+use salt::common::config::ConfigItem;
+use salt::common::config::ConfigValue;
+use salt::common::env::EnvTracker;
+use salt::common::position::FilePosition;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// Configuration layers, in increasing order of precedence.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ConfigLayer {
+    /// Built-in defaults.
+    Default,
+    /// Configuration files.
+    File,
+    /// Environment variables.
+    Env,
+    /// Command-line flags.
+    CmdLine
+}
+
+/// The origin of a configuration setting.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigSource<'a> {
+    /// A built-in default.
+    Default,
+    /// A configuration file, at the given position.
+    File(FilePosition<'a>),
+    /// The named environment variable.
+    Env(String),
+    /// The command-line argument with the given index.
+    CmdLine(usize)
+}
+
+/// A configuration setting, and where it came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigSetting<'a> {
+    /// The value of the setting.
+    pub value: ConfigValue<'a>,
+    /// Where the setting came from.
+    pub source: ConfigSource<'a>
+}
+
+/// An error produced when a resolved setting has the wrong type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SettingError<'a> {
+    /// The key of the setting.
+    pub key: String,
+    /// Where the offending value came from.
+    pub source: ConfigSource<'a>,
+    /// The expected type.
+    pub expected: &'static str,
+    /// The type that was found.
+    pub found: &'static str
+}
+
+/// Layered configuration settings, keyed by dotted key names such as
+/// `lint.unused`.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigStack<'a> {
+    /// All settings for each key, ordered by increasing precedence.
+    settings: BTreeMap<String, Vec<(ConfigLayer, ConfigSetting<'a>)>>
+}
+
+impl<'a> ConfigSource<'a> {
+    /// Get the layer to which settings from this source belong.
+    pub fn layer(&self) -> ConfigLayer {
+        match self {
+            ConfigSource::Default => ConfigLayer::Default,
+            ConfigSource::File(_) => ConfigLayer::File,
+            ConfigSource::Env(_) => ConfigLayer::Env,
+            ConfigSource::CmdLine(_) => ConfigLayer::CmdLine
+        }
+    }
+}
+
+impl<'a> ConfigValue<'a> {
+    /// Interpret a string from the environment or the command line.
+    /// Booleans and numbers are recognized; anything else is treated
+    /// as a string.
+    pub fn parse_scalar(s: &str) -> ConfigValue<'a> {
+        match s {
+            "true" => ConfigValue::Bool(true),
+            "false" => ConfigValue::Bool(false),
+            _ => match s.parse::<i64>() {
+                Ok(n) => ConfigValue::Int(n),
+                Err(_) => match s.parse::<f64>() {
+                    Ok(n) if s.bytes().any(|b| b.is_ascii_digit()) =>
+                        ConfigValue::Float(n),
+                    _ => ConfigValue::Str(s.to_string())
+                }
+            }
+        }
+    }
+}
+
+impl<'a> ConfigStack<'a> {
+    /// Create an empty `ConfigStack`.
+    pub fn new() -> ConfigStack<'a> {
+        ConfigStack { settings: BTreeMap::new() }
+    }
+
+    /// Add a setting for `key`.
+    pub fn set(&mut self, key: &str, value: ConfigValue<'a>,
+               source: ConfigSource<'a>) {
+        let layer = source.layer();
+        let ents = self.settings.entry(key.to_string()).or_default();
+        let idx = ents.iter()
+            .position(|&(l, _)| l > layer)
+            .unwrap_or(ents.len());
+
+        ents.insert(idx, (layer, ConfigSetting { value, source }));
+    }
+
+    /// Add a built-in default for `key`.
+    pub fn set_default(&mut self, key: &str, value: ConfigValue<'a>) {
+        self.set(key, value, ConfigSource::Default)
+    }
+
+    /// Add all settings from a parsed configuration file.  Nested
+    /// tables are flattened into dotted keys.
+    pub fn add_file(&mut self, root: &ConfigItem<'a>) {
+        self.add_item(String::new(), root)
+    }
+
+    fn add_item(&mut self, prefix: String, item: &ConfigItem<'a>) {
+        match item.value {
+            ConfigValue::Table(ref table) => {
+                for (key, ent) in table.iter() {
+                    let key = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+
+                    self.add_item(key, &ent.item)
+                }
+            },
+            ref value => {
+                self.set(&prefix, value.clone(),
+                         ConfigSource::File(item.pos.clone()))
+            }
+        }
+    }
+
+    /// Add settings from environment variables.  Each binding maps an
+    /// environment variable name to a key.  Reads go through `env`, so
+    /// they are recorded as dependencies of the compilation.
+    pub fn add_env(&mut self, env: &EnvTracker, bindings: &[(&str, &str)]) {
+        for &(var, key) in bindings {
+            if let Some(val) = env.var(var) {
+                self.set(key, ConfigValue::parse_scalar(&val),
+                         ConfigSource::Env(var.to_string()))
+            }
+        }
+    }
+
+    /// Add a setting from a `key=value` command-line argument, whose
+    /// index is `idx`.  Returns `false` if the argument has no `=`.
+    pub fn add_cmdline(&mut self, idx: usize, arg: &str) -> bool {
+        match arg.find('=') {
+            Some(pos) => {
+                self.set(arg[..pos].trim(),
+                         ConfigValue::parse_scalar(arg[pos + 1..].trim()),
+                         ConfigSource::CmdLine(idx));
+
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Get the effective setting for `key`.
+    pub fn resolve(&self, key: &str) -> Option<&ConfigSetting<'a>> {
+        self.settings.get(key).and_then(|ents| ents.last())
+            .map(|(_, setting)| setting)
+    }
+
+    /// Get all settings for `key`, starting with the effective one and
+    /// followed by those it overrides.
+    pub fn origins(&self, key: &str) -> Vec<&ConfigSetting<'a>> {
+        match self.settings.get(key) {
+            Some(ents) => ents.iter().rev().map(|(_, setting)| setting)
+                .collect(),
+            None => Vec::new()
+        }
+    }
+
+    /// Describe where the value of `key` comes from, for answering
+    /// questions like "why is this lint on?"
+    pub fn explain(&self, key: &str) -> String {
+        let origins = self.origins(key);
+        let mut iter = origins.iter();
+
+        match iter.next() {
+            None => format!("`{}` is not set", key),
+            Some(first) => {
+                let mut out = format!("`{}` is {} (set by {})", key,
+                                      DisplayValue(&first.value),
+                                      first.source);
+
+                for setting in iter {
+                    out.push_str(&format!("\n  overriding {} (set by {})",
+                                          DisplayValue(&setting.value),
+                                          setting.source));
+                }
+
+                out
+            }
+        }
+    }
+
+    /// Iterate over all keys that have settings.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.settings.keys().map(|s| s.as_str())
+    }
+
+    fn typed<'s, T, F>(&'s self, key: &str, expected: &'static str,
+                       f: F) -> ::std::result::Result<Option<T>,
+                                                      SettingError<'a>>
+        where F: FnOnce(&'s ConfigValue<'a>) -> Option<T> {
+        match self.resolve(key) {
+            None => Ok(None),
+            Some(setting) => match f(&setting.value) {
+                Some(val) => Ok(Some(val)),
+                None => Err(SettingError {
+                    key: key.to_string(), source: setting.source.clone(),
+                    expected, found: setting.value.type_name()
+                })
+            }
+        }
+    }
+
+    /// Get the effective value of a boolean setting.
+    pub fn get_bool(&self, key: &str)
+        -> ::std::result::Result<Option<bool>, SettingError<'a>> {
+        self.typed(key, "boolean", |val| match *val {
+            ConfigValue::Bool(b) => Some(b),
+            _ => None
+        })
+    }
+
+    /// Get the effective value of an integer setting.
+    pub fn get_int(&self, key: &str)
+        -> ::std::result::Result<Option<i64>, SettingError<'a>> {
+        self.typed(key, "integer", |val| match *val {
+            ConfigValue::Int(n) => Some(n),
+            _ => None
+        })
+    }
+
+    /// Get the effective value of a string setting.
+    pub fn get_str(&self, key: &str)
+        -> ::std::result::Result<Option<&str>, SettingError<'a>> {
+        self.typed(key, "string", |val| match *val {
+            ConfigValue::Str(ref s) => Some(s.as_str()),
+            _ => None
+        })
+    }
+}
+
+/// Compact rendering of configuration values in explanations.
+struct DisplayValue<'r, 'a: 'r>(&'r ConfigValue<'a>);
+
+impl<'r, 'a> Display for DisplayValue<'r, 'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self.0 {
+            ConfigValue::Bool(b) => write!(f, "{}", b),
+            ConfigValue::Int(n) => write!(f, "{}", n),
+            ConfigValue::Float(n) => write!(f, "{}", n),
+            ConfigValue::Str(s) => write!(f, "{:?}", s),
+            ConfigValue::Array(items) => {
+                write!(f, "[")?;
+
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", DisplayValue(&item.value))?;
+                }
+
+                write!(f, "]")
+            },
+            ConfigValue::Table(_) => write!(f, "{{...}}")
+        }
+    }
+}
+
+impl<'a> Display for ConfigSource<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(pos) => write!(f, "{}", pos),
+            ConfigSource::Env(var) =>
+                write!(f, "environment variable `{}`", var),
+            ConfigSource::CmdLine(idx) =>
+                write!(f, "command-line argument {}", idx)
+        }
+    }
+}
+
+impl<'a> Display for SettingError<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}: expected {} for `{}`, found {}", self.source,
+               self.expected, self.key, self.found)
+    }
+}
+
+#[test]
+fn test_precedence() {
+    let mut stack = ConfigStack::new();
+
+    stack.add_cmdline(2, "opt-level=3");
+    stack.set_default("opt-level", ConfigValue::Int(0));
+    stack.set_default("debug", ConfigValue::Bool(false));
+
+    assert_eq!(stack.get_int("opt-level"), Ok(Some(3)));
+    assert_eq!(stack.get_bool("debug"), Ok(Some(false)));
+    assert_eq!(stack.get_bool("missing"), Ok(None));
+    assert_eq!(stack.resolve("opt-level").unwrap().source,
+               ConfigSource::CmdLine(2));
+}
+
+#[test]
+fn test_file_and_env() {
+    use salt::common::config::toml;
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut strs = StrIntern::new();
+    let fname = strs.filename("salt.toml");
+    let root = toml::parse(fname, "[lint]\nunused = \"warn\"\n").unwrap();
+    let env = EnvTracker::new();
+    let mut stack = ConfigStack::new();
+
+    ::std::env::set_var("SALT_TEST_STACK_UNUSED", "deny");
+    stack.set_default("lint.unused", ConfigValue::Str(String::from("allow")));
+    stack.add_file(&root);
+
+    assert_eq!(stack.get_str("lint.unused"), Ok(Some("warn")));
+
+    stack.add_env(&env, &[("SALT_TEST_STACK_UNUSED", "lint.unused")]);
+
+    assert_eq!(stack.get_str("lint.unused"), Ok(Some("deny")));
+    assert_eq!(env.recorded().len(), 1);
+    assert_eq!(stack.explain("lint.unused"),
+               "`lint.unused` is \"deny\" (set by environment variable \
+                `SALT_TEST_STACK_UNUSED`)\n  \
+                overriding \"warn\" (set by salt.toml 2.10-16)\n  \
+                overriding \"allow\" (set by default)");
+}
+
+#[test]
+fn test_wrong_type() {
+    let mut stack = ConfigStack::new();
+
+    stack.add_cmdline(1, "debug=maybe");
+
+    let err = stack.get_bool("debug").unwrap_err();
+
+    assert_eq!(format!("{}", err), "command-line argument 1: expected \
+                                    boolean for `debug`, found string");
+}
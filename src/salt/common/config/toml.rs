@@ -0,0 +1,924 @@
+//! A TOML parser that records the position of every key and value.
+//!
+//! This supports the parts of TOML used by configuration files: bare,
+//! quoted, and dotted keys, basic and literal strings, integers,
+//! floats, booleans, arrays, inline tables, table headers, and arrays
+//! of tables.  Multi-line strings and date-times are rejected.
+
+use salt::common::config::ConfigEntry;
+use salt::common::config::ConfigError;
+use salt::common::config::ConfigErrorKind;
+use salt::common::config::ConfigItem;
+use salt::common::config::ConfigTable;
+use salt::common::config::ConfigValue;
+use salt::common::filename::Filename;
+use salt::common::position::FilePosition;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use std::collections::HashSet;
+
+type ParseResult<'a, T> = Result<T, ConfigError<'a>>;
+
+struct Parser<'a, 's> {
+    filename: Filename<'a>,
+    src: &'s str,
+    idx: usize,
+    line: u32,
+    col: u32
+}
+
+/// A key segment, with its position.
+#[derive(Clone)]
+struct Key<'a> {
+    name: String,
+    pos: FilePosition<'a>
+}
+
+/// The tables and values a document has defined, by the names of the
+/// keys leading to them, so that none is defined twice.
+#[derive(Default)]
+struct Defined {
+    /// Tables defined by a `[table]` header.
+    headers: HashSet<Vec<String>>,
+    /// Tables defined by dotted keys, as `b` is by `a.b.c = 1`.
+    dotted: HashSet<Vec<String>>,
+    /// Values given by key/value pairs, which includes inline tables
+    /// and arrays, to which nothing can be added later.
+    values: HashSet<Vec<String>>
+}
+
+impl Defined {
+    /// Get the length of the shortest prefix of `names`, no longer
+    /// than `max`, that names a value given by a key/value pair.
+    fn value_prefix(&self, names: &[String], max: usize) -> Option<usize> {
+        (1..=max).find(|&n| self.values.contains(&names[..n]))
+    }
+
+    /// Forget everything defined under `names`, as it is the name of
+    /// an array of tables that has just gained a new table.
+    fn forget_under(&mut self, names: &[String]) {
+        let under = |other: &Vec<String>| {
+            other.len() > names.len() && other.starts_with(names)
+        };
+
+        self.headers.retain(|other| !under(other));
+        self.dotted.retain(|other| !under(other));
+        self.values.retain(|other| !under(other));
+    }
+}
+
+/// Get the names of the keys in `path`.
+fn key_names(path: &[Key]) -> Vec<String> {
+    path.iter().map(|key| key.name.clone()).collect()
+}
+
+/// Get an error reporting that `path` is defined twice.
+fn duplicate<'a>(path: &[Key<'a>]) -> ConfigError<'a> {
+    ConfigError { pos: path[path.len() - 1].pos.clone(),
+                  kind: ConfigErrorKind::DuplicateKey(ent_name(path)) }
+}
+
+/// Parse `src`, the contents of `filename`, producing the root table.
+pub fn parse<'a>(filename: Filename<'a>,
+                 src: &str) -> ParseResult<'a, ConfigItem<'a>> {
+    let mut parser = Parser { filename, src, idx: 0, line: 1, col: 1 };
+    let root_pos = parser.point_pos();
+    let mut root = ConfigTable::new();
+    // Path to the table that key/value pairs currently go into.
+    let mut current: Vec<Key<'a>> = Vec::new();
+    let mut defined = Defined::default();
+
+    loop {
+        parser.skip_trivia();
+
+        match parser.peek() {
+            None => break,
+            Some(b'[') => {
+                let start = parser.point();
+
+                parser.bump();
+
+                let array = parser.peek() == Some(b'[');
+
+                if array {
+                    parser.bump();
+                }
+
+                parser.skip_ws();
+
+                let path = parser.key()?;
+
+                parser.skip_ws();
+                parser.expect(b']')?;
+
+                if array {
+                    parser.expect(b']')?;
+                }
+
+                let pos = parser.span_pos(start);
+
+                parser.end_line()?;
+
+                let names = key_names(&path);
+
+                if let Some(n) = defined.value_prefix(&names, names.len()) {
+                    return Err(duplicate(&path[..n]));
+                }
+
+                if !array && (defined.headers.contains(&names) ||
+                              defined.dotted.contains(&names)) {
+                    return Err(duplicate(&path));
+                }
+
+                let table = walk(&mut root, &path[..path.len() - 1])?;
+                let last = &path[path.len() - 1];
+
+                if array {
+                    let new = ConfigItem { value: ConfigValue::Table(
+                                               ConfigTable::new()),
+                                           pos: pos.clone() };
+
+                    match table.get_mut(&last.name) {
+                        Some(ConfigEntry {
+                            item: ConfigItem {
+                                value: ConfigValue::Array(ref mut items), ..
+                            }, ..
+                        }) => {
+                            items.push(new);
+                            defined.forget_under(&names)
+                        },
+                        Some(_) => return Err(ConfigError {
+                            pos: last.pos.clone(),
+                            kind: ConfigErrorKind::DuplicateKey(
+                                      ent_name(&path))
+                        }),
+                        None => {
+                            table.insert(last.name.clone(), ConfigEntry {
+                                key_pos: last.pos.clone(),
+                                item: ConfigItem {
+                                    value: ConfigValue::Array(vec![new]), pos
+                                }
+                            });
+                        }
+                    }
+                } else {
+                    match table.get(&last.name) {
+                        Some(&ConfigEntry {
+                            item: ConfigItem {
+                                value: ConfigValue::Table(_), ..
+                            }, ..
+                        }) => {},
+                        Some(_) => return Err(ConfigError {
+                            pos: last.pos.clone(),
+                            kind: ConfigErrorKind::DuplicateKey(
+                                      ent_name(&path))
+                        }),
+                        None => {
+                            table.insert(last.name.clone(), ConfigEntry {
+                                key_pos: last.pos.clone(),
+                                item: ConfigItem {
+                                    value: ConfigValue::Table(
+                                        ConfigTable::new()),
+                                    pos
+                                }
+                            });
+                        }
+                    }
+
+                    defined.headers.insert(names);
+                }
+
+                current = path;
+            },
+            Some(_) => {
+                let path = parser.key()?;
+
+                parser.skip_ws();
+                parser.expect(b'=')?;
+                parser.skip_ws();
+
+                let item = parser.value()?;
+
+                parser.end_line()?;
+
+                let mut names = key_names(&current);
+                let full: Vec<Key<'a>> =
+                    current.iter().chain(path.iter()).cloned().collect();
+
+                names.extend(path.iter().map(|key| key.name.clone()));
+
+                if let Some(n) = defined.value_prefix(&names, names.len() - 1) {
+                    return Err(duplicate(&full[..n]));
+                }
+
+                for n in current.len() + 1..names.len() {
+                    if defined.headers.contains(&names[..n]) {
+                        return Err(duplicate(&full[..n]));
+                    }
+
+                    defined.dotted.insert(names[..n].to_vec());
+                }
+
+                let table = walk(&mut root, &current)?;
+
+                insert(table, path, item)?;
+                defined.values.insert(names);
+            }
+        }
+    }
+
+    Ok(ConfigItem { value: ConfigValue::Table(root), pos: root_pos })
+}
+
+fn ent_name(path: &[Key]) -> String {
+    let names: Vec<&str> = path.iter().map(|k| k.name.as_str()).collect();
+
+    names.join(".")
+}
+
+/// Find the table at `path`, creating tables as needed.  If an
+/// element of the path is an array of tables, the last table in the
+/// array is used.
+fn walk<'t, 'a>(mut table: &'t mut ConfigTable<'a>,
+                path: &[Key<'a>]) -> ParseResult<'a, &'t mut ConfigTable<'a>> {
+    for (i, key) in path.iter().enumerate() {
+        if table.get(&key.name).is_none() {
+            table.insert(key.name.clone(), ConfigEntry {
+                key_pos: key.pos.clone(),
+                item: ConfigItem {
+                    value: ConfigValue::Table(ConfigTable::new()),
+                    pos: key.pos.clone()
+                }
+            });
+        }
+
+        let ent = table.get_mut(&key.name).unwrap();
+
+        table = match ent.item.value {
+            ConfigValue::Table(ref mut sub) => sub,
+            ConfigValue::Array(ref mut items) => match items.last_mut() {
+                Some(&mut ConfigItem { value: ConfigValue::Table(ref mut sub),
+                                       .. }) => sub,
+                _ => return Err(ConfigError {
+                    pos: key.pos.clone(),
+                    kind: ConfigErrorKind::DuplicateKey(ent_name(&path[..=i]))
+                })
+            },
+            _ => return Err(ConfigError {
+                pos: key.pos.clone(),
+                kind: ConfigErrorKind::DuplicateKey(ent_name(&path[..=i]))
+            })
+        }
+    }
+
+    Ok(table)
+}
+
+/// Insert `item` at the dotted key `path` in `table`.
+fn insert<'a>(table: &mut ConfigTable<'a>, mut path: Vec<Key<'a>>,
+              item: ConfigItem<'a>) -> ParseResult<'a, ()> {
+    let last = path.pop().expect("empty key");
+    let table = walk(table, &path)?;
+    let ent = ConfigEntry { key_pos: last.pos.clone(), item };
+
+    if table.insert(last.name.clone(), ent) {
+        Ok(())
+    } else {
+        path.push(last);
+
+        Err(ConfigError {
+            pos: path[path.len() - 1].pos.clone(),
+            kind: ConfigErrorKind::DuplicateKey(ent_name(&path))
+        })
+    }
+}
+
+fn is_bare(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+}
+
+impl<'a, 's> Parser<'a, 's> {
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.idx).cloned()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.src.as_bytes().get(self.idx + n).cloned()
+    }
+
+    fn bump(&mut self) {
+        let b = self.src.as_bytes()[self.idx];
+
+        self.idx += 1;
+
+        if b == b'\n' {
+            self.line += 1;
+            self.col = 1;
+        } else if b & 0xc0 != 0x80 {
+            self.col += 1;
+        }
+    }
+
+    fn point(&self) -> Point {
+        Point { line: self.line, col: self.col }
+    }
+
+    fn point_pos(&self) -> FilePosition<'a> {
+        FilePosition { filename: self.filename,
+                       loc: Location::Point { point: self.point() } }
+    }
+
+    fn span_pos(&self, start: Point) -> FilePosition<'a> {
+        FilePosition { filename: self.filename,
+                       loc: Location::Span { start, end: self.point() } }
+    }
+
+    fn error<T>(&self, msg: String) -> ParseResult<'a, T> {
+        Err(ConfigError { pos: self.point_pos(),
+                          kind: ConfigErrorKind::Syntax(msg) })
+    }
+
+    fn error_at<T>(&self, start: Point, msg: String) -> ParseResult<'a, T> {
+        Err(ConfigError { pos: self.span_pos(start),
+                          kind: ConfigErrorKind::Syntax(msg) })
+    }
+
+    fn describe(&self) -> String {
+        match self.src[self.idx..].chars().next() {
+            None => String::from("end of file"),
+            Some('\n') | Some('\r') => String::from("end of line"),
+            Some(c) => format!("`{}`", c)
+        }
+    }
+
+    fn expect(&mut self, b: u8) -> ParseResult<'a, ()> {
+        if self.peek() == Some(b) {
+            self.bump();
+
+            Ok(())
+        } else {
+            let msg = format!("expected `{}`, found {}", b as char,
+                              self.describe());
+
+            self.error(msg)
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') = self.peek() {
+            self.bump()
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some(b'#') {
+            while let Some(b) = self.peek() {
+                if b == b'\n' {
+                    break;
+                }
+
+                self.bump()
+            }
+        }
+    }
+
+    /// Skip whitespace, comments, and newlines.
+    fn skip_trivia(&mut self) {
+        loop {
+            self.skip_ws();
+            self.skip_comment();
+
+            match self.peek() {
+                Some(b'\n') | Some(b'\r') => self.bump(),
+                _ => break
+            }
+        }
+    }
+
+    /// Consume the rest of a line, which may only contain whitespace
+    /// and a comment.
+    fn end_line(&mut self) -> ParseResult<'a, ()> {
+        self.skip_ws();
+        self.skip_comment();
+
+        match self.peek() {
+            None => Ok(()),
+            Some(b'\n') => {
+                self.bump();
+
+                Ok(())
+            },
+            Some(b'\r') if self.peek_at(1) == Some(b'\n') => {
+                self.bump();
+                self.bump();
+
+                Ok(())
+            },
+            _ => {
+                let msg = format!("expected end of line, found {}",
+                                  self.describe());
+
+                self.error(msg)
+            }
+        }
+    }
+
+    /// Parse a possibly-dotted key.
+    fn key(&mut self) -> ParseResult<'a, Vec<Key<'a>>> {
+        let mut out = vec![self.simple_key()?];
+
+        loop {
+            self.skip_ws();
+
+            if self.peek() == Some(b'.') {
+                self.bump();
+                self.skip_ws();
+                out.push(self.simple_key()?);
+            } else {
+                return Ok(out);
+            }
+        }
+    }
+
+    fn simple_key(&mut self) -> ParseResult<'a, Key<'a>> {
+        let start = self.point();
+        let name = match self.peek() {
+            Some(b'"') => self.basic_string()?,
+            Some(b'\'') => self.literal_string()?,
+            Some(b) if is_bare(b) => {
+                let begin = self.idx;
+
+                while let Some(b) = self.peek() {
+                    if !is_bare(b) {
+                        break;
+                    }
+
+                    self.bump()
+                }
+
+                self.src[begin..self.idx].to_string()
+            },
+            _ => {
+                let msg = format!("expected a key, found {}", self.describe());
+
+                return self.error(msg);
+            }
+        };
+
+        Ok(Key { name, pos: self.span_pos(start) })
+    }
+
+    fn value(&mut self) -> ParseResult<'a, ConfigItem<'a>> {
+        let start = self.point();
+        let value = match self.peek() {
+            Some(b'"') => ConfigValue::Str(self.basic_string()?),
+            Some(b'\'') => ConfigValue::Str(self.literal_string()?),
+            Some(b'[') => ConfigValue::Array(self.array()?),
+            Some(b'{') => ConfigValue::Table(self.inline_table()?),
+            Some(b't') | Some(b'f')
+                if self.src[self.idx..].starts_with("true") ||
+                   self.src[self.idx..].starts_with("false") => {
+                let val = self.src[self.idx..].starts_with("true");
+                let len = if val { 4 } else { 5 };
+
+                for _ in 0..len {
+                    self.bump()
+                }
+
+                ConfigValue::Bool(val)
+            },
+            Some(b) if b.is_ascii_digit() || b == b'+' || b == b'-' ||
+                       b == b'i' || b == b'n' => self.number()?,
+            _ => {
+                let msg = format!("expected a value, found {}",
+                                  self.describe());
+
+                return self.error(msg);
+            }
+        };
+
+        Ok(ConfigItem { value, pos: self.span_pos(start) })
+    }
+
+    fn basic_string(&mut self) -> ParseResult<'a, String> {
+        let start = self.point();
+        let mut out = String::new();
+
+        self.bump();
+
+        if self.src[self.idx..].starts_with("\"\"") {
+            return self.error_at(start, String::from(
+                "multi-line strings are not supported"));
+        }
+
+        loop {
+            match self.src[self.idx..].chars().next() {
+                None | Some('\n') =>
+                    return self.error_at(start,
+                                         String::from("unterminated string")),
+                Some('"') => {
+                    self.bump();
+
+                    return Ok(out);
+                },
+                Some('\\') => {
+                    let esc_start = self.point();
+
+                    self.bump();
+
+                    let c = match self.peek() {
+                        Some(b'u') => self.unicode_escape(esc_start, 4)?,
+                        Some(b'U') => self.unicode_escape(esc_start, 8)?,
+                        Some(b) => {
+                            let c = match b {
+                                b'b' => '\u{8}',
+                                b't' => '\t',
+                                b'n' => '\n',
+                                b'f' => '\u{c}',
+                                b'r' => '\r',
+                                b'"' => '"',
+                                b'\\' => '\\',
+                                _ => return self.error_at(esc_start,
+                                    String::from("invalid escape sequence"))
+                            };
+
+                            self.bump();
+
+                            c
+                        },
+                        None => return self.error_at(esc_start, String::from(
+                            "invalid escape sequence"))
+                    };
+
+                    out.push(c)
+                },
+                Some(c) => {
+                    for _ in 0..c.len_utf8() {
+                        self.bump()
+                    }
+
+                    out.push(c)
+                }
+            }
+        }
+    }
+
+    /// Parse a `\u` or `\U` escape; the parser is positioned at the
+    /// `u`, and `len` is the number of hex digits.
+    fn unicode_escape(&mut self, start: Point,
+                      len: usize) -> ParseResult<'a, char> {
+        let digits = self.src.get(self.idx + 1..self.idx + 1 + len);
+        let code = digits.and_then(|d| {
+            if d.bytes().all(|b| b.is_ascii_hexdigit()) {
+                u32::from_str_radix(d, 16).ok()
+            } else {
+                None
+            }
+        });
+
+        match code.and_then(::std::char::from_u32) {
+            Some(c) => {
+                for _ in 0..=len {
+                    self.bump()
+                }
+
+                Ok(c)
+            },
+            None => self.error_at(start, String::from(
+                "invalid unicode escape"))
+        }
+    }
+
+    fn literal_string(&mut self) -> ParseResult<'a, String> {
+        let start = self.point();
+
+        self.bump();
+
+        if self.src[self.idx..].starts_with("''") {
+            return self.error_at(start, String::from(
+                "multi-line strings are not supported"));
+        }
+
+        let begin = self.idx;
+
+        loop {
+            match self.peek() {
+                None | Some(b'\n') =>
+                    return self.error_at(start,
+                                         String::from("unterminated string")),
+                Some(b'\'') => {
+                    let out = self.src[begin..self.idx].to_string();
+
+                    self.bump();
+
+                    return Ok(out);
+                },
+                Some(_) => self.bump()
+            }
+        }
+    }
+
+    fn number(&mut self) -> ParseResult<'a, ConfigValue<'a>> {
+        let start = self.point();
+        let begin = self.idx;
+
+        while let Some(b) = self.peek() {
+            if b.is_ascii_alphanumeric() || b == b'_' || b == b'.' ||
+               b == b'+' || b == b'-' {
+                self.bump()
+            } else {
+                break;
+            }
+        }
+
+        let text = &self.src[begin..self.idx];
+        let (neg, body) = match text.as_bytes().first() {
+            Some(b'-') => (true, &text[1..]),
+            Some(b'+') => (false, &text[1..]),
+            _ => (false, text)
+        };
+
+        match body {
+            "inf" => return Ok(ConfigValue::Float(if neg {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            })),
+            "nan" => return Ok(ConfigValue::Float(f64::NAN)),
+            _ => {}
+        }
+
+        let underscores_ok = !body.starts_with('_') && !body.ends_with('_') &&
+            !body.contains("__");
+
+        if !underscores_ok {
+            return self.error_at(start, format!("invalid number `{}`", text));
+        }
+
+        let digits: String = body.chars().filter(|c| *c != '_').collect();
+        let radix = if digits.starts_with("0x") {
+            16
+        } else if digits.starts_with("0o") {
+            8
+        } else if digits.starts_with("0b") {
+            2
+        } else {
+            10
+        };
+
+        if radix != 10 {
+            let parsed = if neg || text.starts_with('+') {
+                None
+            } else {
+                i64::from_str_radix(&digits[2..], radix).ok()
+            };
+
+            match parsed {
+                Some(n) => Ok(ConfigValue::Int(n)),
+                None => self.error_at(start,
+                                      format!("invalid integer `{}`", text))
+            }
+        } else if digits.contains(['.', 'e', 'E']) {
+            let valid = digits.as_bytes()[0].is_ascii_digit() &&
+                !digits.contains(".e") && !digits.contains(".E") &&
+                !digits.ends_with('.');
+
+            match digits.parse::<f64>() {
+                Ok(n) if valid => Ok(ConfigValue::Float(if neg { -n }
+                                                        else { n })),
+                _ => self.error_at(start, format!("invalid float `{}`", text))
+            }
+        } else {
+            let signed = if neg {
+                format!("-{}", digits)
+            } else {
+                digits.clone()
+            };
+            let leading_zero = digits.len() > 1 && digits.starts_with('0');
+
+            match signed.parse::<i64>() {
+                Ok(n) if !leading_zero => Ok(ConfigValue::Int(n)),
+                _ => self.error_at(start,
+                                   format!("invalid integer `{}`", text))
+            }
+        }
+    }
+
+    fn array(&mut self) -> ParseResult<'a, Vec<ConfigItem<'a>>> {
+        let mut out = Vec::new();
+
+        self.bump();
+
+        loop {
+            self.skip_trivia();
+
+            if self.peek() == Some(b']') {
+                self.bump();
+
+                return Ok(out);
+            }
+
+            out.push(self.value()?);
+            self.skip_trivia();
+
+            match self.peek() {
+                Some(b',') => self.bump(),
+                Some(b']') => {},
+                _ => {
+                    let msg = format!("expected `,` or `]`, found {}",
+                                      self.describe());
+
+                    return self.error(msg);
+                }
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> ParseResult<'a, ConfigTable<'a>> {
+        let mut out = ConfigTable::new();
+
+        self.bump();
+        self.skip_ws();
+
+        if self.peek() == Some(b'}') {
+            self.bump();
+
+            return Ok(out);
+        }
+
+        loop {
+            self.skip_ws();
+
+            let path = self.key()?;
+
+            self.skip_ws();
+            self.expect(b'=')?;
+            self.skip_ws();
+
+            let item = self.value()?;
+
+            insert(&mut out, path, item)?;
+            self.skip_ws();
+
+            match self.peek() {
+                Some(b',') => self.bump(),
+                Some(b'}') => {
+                    self.bump();
+
+                    return Ok(out);
+                },
+                _ => {
+                    let msg = format!("expected `,` or `}}`, found {}",
+                                      self.describe());
+
+                    return self.error(msg);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_parse(src: &str) -> ParseResult<'static, ConfigItem<'static>> {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut strs = StrIntern::new();
+
+    parse(strs.filename("test.toml"), src)
+}
+
+#[cfg(test)]
+fn lookup<'t, 'a>(item: &'t ConfigItem<'a>,
+                  path: &[&str]) -> &'t ConfigItem<'a> {
+    path.iter().fold(item, |item, key| match item.value {
+        ConfigValue::Table(ref table) => &table.get(key).unwrap().item,
+        _ => panic!("not a table")
+    })
+}
+
+#[test]
+fn test_parse_values() {
+    let root = test_parse("# comment\n\
+                           a = 1_000\n\
+                           b = -0x10 # not allowed\n").unwrap_err();
+
+    assert_eq!(format!("{}", root),
+               "test.toml 3.5-10: invalid integer `-0x10`");
+
+    let root = test_parse("a = 1_000\n\
+                           b = 'lit\\eral'\n\
+                           c = \"esc\\t\\u00e9\"\n\
+                           d = [ 1.5, -2e3,\n  true, ]\n\
+                           e.f = { g = \"h\" }\n").unwrap();
+
+    assert_eq!(lookup(&root, &["a"]).value, ConfigValue::Int(1000));
+    assert_eq!(lookup(&root, &["b"]).value,
+               ConfigValue::Str(String::from("lit\\eral")));
+    assert_eq!(lookup(&root, &["c"]).value,
+               ConfigValue::Str(String::from("esc\t\u{e9}")));
+    assert_eq!(lookup(&root, &["e", "f", "g"]).value,
+               ConfigValue::Str(String::from("h")));
+
+    match lookup(&root, &["d"]).value {
+        ConfigValue::Array(ref items) => {
+            assert_eq!(items[0].value, ConfigValue::Float(1.5));
+            assert_eq!(items[1].value, ConfigValue::Float(-2000.0));
+            assert_eq!(items[2].value, ConfigValue::Bool(true));
+            assert_eq!(format!("{}", items[2].pos), "test.toml 5.3-7");
+        },
+        _ => panic!("expected array")
+    }
+}
+
+#[test]
+fn test_parse_tables() {
+    let root = test_parse("[lint]\n\
+                           level = \"warn\"\n\
+                           [[target]]\n\
+                           name = \"x86_64\"\n\
+                           [[target]]\n\
+                           name = \"wasm32\"\n").unwrap();
+
+    assert_eq!(lookup(&root, &["lint", "level"]).value,
+               ConfigValue::Str(String::from("warn")));
+
+    match lookup(&root, &["target"]).value {
+        ConfigValue::Array(ref items) => {
+            assert_eq!(items.len(), 2);
+            assert_eq!(lookup(&items[1], &["name"]).value,
+                       ConfigValue::Str(String::from("wasm32")));
+        },
+        _ => panic!("expected array")
+    }
+}
+
+#[test]
+fn test_parse_positions() {
+    let root = test_parse("name = \"\u{e9}t\u{e9}\"  \n  x = 1\n").unwrap();
+
+    assert_eq!(format!("{}", lookup(&root, &["name"]).pos), "test.toml 1.8-13");
+    assert_eq!(format!("{}", lookup(&root, &["x"]).pos), "test.toml 2.7-8");
+}
+
+#[test]
+fn test_parse_duplicate() {
+    let err = test_parse("a = 1\na = 2\n").unwrap_err();
+
+    assert_eq!(format!("{}", err), "test.toml 2.1-2: duplicate key `a`");
+
+    let err = test_parse("a = 1\n[a]\n").unwrap_err();
+
+    assert_eq!(err.kind, ConfigErrorKind::DuplicateKey(String::from("a")));
+}
+
+#[test]
+fn test_parse_duplicate_tables() {
+    let dup = |src| match test_parse(src) {
+        Err(ConfigError { kind: ConfigErrorKind::DuplicateKey(name), pos }) =>
+            format!("{} {}", name, pos.loc),
+        other => panic!("expected duplicate key, got {:?}", other)
+    };
+
+    assert_eq!(dup("[a]\nx = 1\n[a]\n"), "a 3.2-3");
+    assert_eq!(dup("a = { x = 1 }\n[a]\n"), "a 2.2-3");
+    assert_eq!(dup("a = { x = 1 }\n[a.b]\n"), "a 2.2-3");
+    assert_eq!(dup("a = { x = 1 }\na.y = 2\n"), "a 2.1-2");
+    assert_eq!(dup("a = [1]\n[[a]]\n"), "a 2.3-4");
+    assert_eq!(dup("a.b.c = 1\n[a.b]\n"), "a.b 2.4-5");
+    assert_eq!(dup("[a.b]\n[a]\nb.c = 1\n"), "a.b 3.1-2");
+    assert_eq!(dup("[a]\nb.c = 1\n[a.b]\n"), "a.b 3.4-5");
+}
+
+#[test]
+fn test_parse_implicit_tables() {
+    // A header may define a table created for a longer header, and
+    // each table in an array of tables is defined afresh.
+    let root = test_parse("[a.b]\nx = 1\n[a]\ny = 2\n\
+                           [[t]]\n[t.u]\nv = { w = 1 }\n\
+                           [[t]]\n[t.u]\nv = { w = 2 }\n\
+                           [a.b.c]\n").unwrap();
+
+    assert_eq!(lookup(&root, &["a", "b", "x"]).value, ConfigValue::Int(1));
+    assert_eq!(lookup(&root, &["a", "y"]).value, ConfigValue::Int(2));
+
+    match lookup(&root, &["t"]).value {
+        ConfigValue::Array(ref items) => {
+            assert_eq!(lookup(&items[1], &["u", "v", "w"]).value,
+                       ConfigValue::Int(2));
+        },
+        _ => panic!("expected array")
+    }
+}
+
+#[test]
+fn test_parse_syntax() {
+    let err = test_parse("a = 1 b = 2\n").unwrap_err();
+
+    assert_eq!(format!("{}", err),
+               "test.toml 1.7: expected end of line, found `b`");
+
+    let err = test_parse("a = \"open\n").unwrap_err();
+
+    assert_eq!(format!("{}", err), "test.toml 1.5-10: unterminated string");
+}
@@ -1,3 +1,209 @@
-pub struct Ctx {
+//! A ready-made context for creating filenames, symbols and positions.
+//!
+//! `FilenameCtx`, `SymbolCtx` and `FilePositionCtx` leave where names
+//! and positions are kept up to the user.  `CommonCtx` implements all
+//! three, keeping strings in a `StrIntern` and positions in a
+//! `PositionTable`, for tools with no need for anything more special.
+//! Created `with_arena`, it keeps generated names in a `StrArena`,
+//! and implements `GensymCtx` too.
 
+use salt::common::filename::Filename;
+use salt::common::filename::FilenameCtx;
+use salt::common::position::FilePosition;
+use salt::common::position::FilePositionCtx;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use salt::common::position::PositionTable;
+use salt::common::str::arena::StrArena;
+use salt::common::str::intern::StrIntern;
+use salt::common::symbol::Gensym;
+use salt::common::symbol::GensymCtx;
+use salt::common::symbol::Symbol;
+use salt::common::symbol::SymbolCtx;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// A context owning a string interner and a table of positions.
+/// Positions are created in the current file, which is set with
+/// `set_file`.
+///
+/// `A` is where generated names are kept: `()` for none, or a
+/// `&StrArena` for a context created `with_arena`, which is the only
+/// kind that can generate names.
+pub struct CommonCtx<'a, A = ()> {
+    strs: StrIntern<'a>,
+    file: Option<Filename<'a>>,
+    positions: PositionTable<'a>,
+    arena: A,
+    gensym: Gensym
+}
+
+impl<'a> CommonCtx<'a> {
+    /// Create a context with no strings, positions or current file.
+    pub fn new() -> CommonCtx<'a> {
+        CommonCtx { strs: StrIntern::new(), file: None,
+                    positions: PositionTable::new(), arena: (),
+                    gensym: Gensym::new() }
+    }
+}
+
+impl<'a> CommonCtx<'a, &'a StrArena> {
+    /// Create a context like `new`, which keeps generated names in
+    /// `arena`.
+    pub fn with_arena(arena: &'a StrArena) -> CommonCtx<'a, &'a StrArena> {
+        CommonCtx { strs: StrIntern::new(), file: None,
+                    positions: PositionTable::new(), arena,
+                    gensym: Gensym::new() }
+    }
+
+    /// Get the counter for generated names, to save it.
+    pub fn gensym_counter(&self) -> Gensym {
+        self.gensym
+    }
+
+    /// Set the counter for generated names, to restore a saved one.
+    pub fn set_gensym_counter(&mut self, gensym: Gensym) {
+        self.gensym = gensym
+    }
+}
+
+impl<'a, A> CommonCtx<'a, A> {
+    /// Make `fname` the file that new positions are in, returning its
+    /// `Filename`.
+    pub fn set_file(&mut self, fname: &'a str) -> Filename<'a> {
+        let filename = self.filename(fname);
+
+        self.file = Some(filename);
+
+        filename
+    }
+
+    /// Get the file that new positions are in, if one has been set.
+    pub fn file(&self) -> Option<Filename<'a>> {
+        self.file
+    }
+
+    /// Get the string interner.
+    pub fn strs(&self) -> &StrIntern<'a> {
+        &self.strs
+    }
+
+    /// Get the positions created so far, in the order they were made.
+    pub fn positions(&self) -> &PositionTable<'a> {
+        &self.positions
+    }
+
+    /// Add `pos` to the position table, and return the stored copy.
+    fn add(&mut self, pos: FilePosition<'a>) -> &FilePosition<'a> {
+        let idx = self.positions.push(pos);
+
+        self.positions.get(idx).unwrap()
+    }
+}
+
+impl<'a> Default for CommonCtx<'a> {
+    fn default() -> CommonCtx<'a> {
+        CommonCtx::new()
+    }
+}
+
+impl<'a, A> Debug for CommonCtx<'a, A> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("CommonCtx")
+            .field("strs", &self.strs.len())
+            .field("file", &self.file)
+            .field("positions", &self.positions)
+            .field("gensym", &self.gensym)
+            .finish()
+    }
+}
+
+impl<'a, A> FilenameCtx<'a> for CommonCtx<'a, A> {
+    fn filename(&mut self, fname: &'a str) -> Filename<'a> {
+        self.strs.filename(fname)
+    }
+}
+
+impl<'a, A> SymbolCtx<'a> for CommonCtx<'a, A> {
+    fn symbol(&mut self, fname: &'a str) -> Symbol<'a> {
+        self.strs.symbol(fname)
+    }
+}
+
+impl<'a> GensymCtx<'a> for CommonCtx<'a, &'a StrArena> {
+    fn gensym(&mut self, prefix: &str) -> Symbol<'a> {
+        self.gensym.fresh(self.arena, prefix)
+    }
+}
+
+/// Positions are in the current file.  Both methods panic if no file
+/// has been set, and `span` panics if its ends are in different files.
+impl<'a, A> FilePositionCtx<'a> for CommonCtx<'a, A> {
+    fn point(&mut self, line: u32, col: u32) -> &FilePosition<'a> {
+        let filename = self.file.expect("no current file");
+        let loc = Location::Point { point: Point { line, col } };
+
+        self.add(FilePosition { filename, loc })
+    }
+
+    fn span(&mut self, start: &FilePosition<'a>,
+            end: &FilePosition<'a>) -> &FilePosition<'a> {
+        assert!(start.filename == end.filename, "span across files");
+
+        let loc = Location::Span { start: start.loc.start(),
+                                   end: end.loc.end() };
+
+        self.add(FilePosition { filename: start.filename, loc })
+    }
+}
+
+#[test]
+fn test_common_ctx() {
+    let mut ctx = CommonCtx::new();
+    let a = ctx.set_file("a.salt");
+
+    assert_eq!(ctx.filename("a.salt"), a);
+    assert_eq!(ctx.file(), Some(a));
+    assert_eq!(ctx.symbol("a_long_identifier"),
+               Symbol::from_static("a_long_identifier"));
+
+    let start = ctx.point(2, 5).clone();
+    let end = ctx.point(3, 9).clone();
+    let span = ctx.span(&start, &end).clone();
+
+    assert_eq!(span.filename, a);
+    assert_eq!(span.loc, Location::Span { start: Point { line: 2, col: 5 },
+                                          end: Point { line: 3, col: 9 } });
+    assert_eq!(ctx.positions().len(), 3);
+    assert_eq!(ctx.positions().get(2), Some(&span));
+}
+
+#[test]
+#[should_panic(expected = "no current file")]
+fn test_no_file() {
+    CommonCtx::new().point(1, 1);
+}
+
+#[test]
+fn test_gensym() {
+    let arena = StrArena::new();
+    let mut ctx = CommonCtx::with_arena(&arena);
+    let first = ctx.gensym("closure");
+    let second = ctx.gensym("closure");
+    let saved = ctx.gensym_counter();
+    let mut other = CommonCtx::with_arena(&arena);
+
+    other.set_gensym_counter(saved);
+
+    assert_ne!(first, second);
+    assert!(first.is_generated());
+    assert_eq!(second.as_str(), "%closure.1");
+    assert_eq!(other.gensym("closure").as_str(), "%closure.2");
+
+    // A context with an arena is otherwise like any other.
+    let a = ctx.set_file("a.salt");
+
+    assert_eq!(ctx.point(1, 1).filename, a);
+    assert_eq!(ctx.symbol("closure"), Symbol::from_static("closure"));
 }
@@ -0,0 +1,107 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// How much debug information to emit (`-g`).  Levels are ordered, so
+/// each includes everything emitted at the levels below it.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DebugInfoLevel {
+    /// No debug information (`-g0`).
+    #[default]
+    None,
+    /// Line tables only, enough for backtraces and profilers (`-g1`).
+    LineTablesOnly,
+    /// Line tables plus functions and types, but no local variables.
+    Limited,
+    /// Everything, including local variables and their locations
+    /// (`-g2`, or `-g` with no level).
+    Full
+}
+
+/// An error indicating that a debug info level was not recognized.  The
+/// level is carried in the error.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UnknownDebugInfoLevel(pub String);
+
+impl DebugInfoLevel {
+    /// Get the name of this level.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugInfoLevel::None => "none",
+            DebugInfoLevel::LineTablesOnly => "line-tables-only",
+            DebugInfoLevel::Limited => "limited",
+            DebugInfoLevel::Full => "full"
+        }
+    }
+
+    /// Check whether any debug information is emitted.
+    pub fn is_enabled(&self) -> bool {
+        *self != DebugInfoLevel::None
+    }
+
+    /// Check whether line tables are emitted.
+    pub fn line_tables(&self) -> bool {
+        *self >= DebugInfoLevel::LineTablesOnly
+    }
+
+    /// Check whether entries for functions and types are emitted.
+    pub fn definitions(&self) -> bool {
+        *self >= DebugInfoLevel::Limited
+    }
+
+    /// Check whether entries for local variables are emitted.
+    pub fn variables(&self) -> bool {
+        *self >= DebugInfoLevel::Full
+    }
+}
+
+/// Levels are accepted by name, or as the numbers used with `-g`:
+/// `0`, `1` and `2`.
+impl FromStr for DebugInfoLevel {
+    type Err = UnknownDebugInfoLevel;
+
+    fn from_str(s: &str) -> ::std::result::Result<DebugInfoLevel,
+                                                  UnknownDebugInfoLevel> {
+        match s {
+            "none" | "0" => Ok(DebugInfoLevel::None),
+            "line-tables-only" | "1" => Ok(DebugInfoLevel::LineTablesOnly),
+            "limited" => Ok(DebugInfoLevel::Limited),
+            "full" | "2" => Ok(DebugInfoLevel::Full),
+            _ => Err(UnknownDebugInfoLevel(s.to_string()))
+        }
+    }
+}
+
+impl Display for DebugInfoLevel {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for UnknownDebugInfoLevel {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "unknown debug info level `{}`, expected one of none, \
+                   line-tables-only, limited, full, 0, 1 or 2", self.0)
+    }
+}
+
+#[test]
+fn test_parse_level() {
+    assert_eq!("1".parse(), Ok(DebugInfoLevel::LineTablesOnly));
+    assert_eq!("limited".parse(), Ok(DebugInfoLevel::Limited));
+    assert_eq!("2".parse(), Ok(DebugInfoLevel::Full));
+    assert_eq!("3".parse::<DebugInfoLevel>(),
+               Err(UnknownDebugInfoLevel(String::from("3"))));
+    assert_eq!(DebugInfoLevel::LineTablesOnly.to_string(), "line-tables-only");
+}
+
+#[test]
+fn test_level_contents() {
+    assert!(!DebugInfoLevel::None.line_tables());
+    assert!(DebugInfoLevel::LineTablesOnly.line_tables());
+    assert!(!DebugInfoLevel::LineTablesOnly.definitions());
+    assert!(DebugInfoLevel::Limited.definitions());
+    assert!(!DebugInfoLevel::Limited.variables());
+    assert!(DebugInfoLevel::Full.variables());
+}
@@ -0,0 +1,371 @@
+//! Symbol name demangling.
+//!
+//! This reverses `salt::common::mangle`, turning mangled Salt symbols
+//! into readable names such as `geom::Point::new(f64, f64)`.  Only the
+//! subset of the Itanium syntax produced by the mangler is accepted;
+//! other names, including most C++ symbols, are left alone.
+
+use salt::common::mangle::PREFIX;
+use salt::common::symbol::SymbolCtx;
+use salt::common::symbol_path::SymbolPath;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// A demangled symbol.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Demangled {
+    /// The components of the name, outermost first.
+    pub path: Vec<String>,
+    /// The disambiguator, or 0 if there was none.
+    pub disambiguator: u32,
+    /// The number of type parameters, or 0 if there was no arity.
+    pub arity: u32,
+    /// The parameter types of a function, written in Salt syntax, or
+    /// `None` for a data symbol.
+    pub params: Option<Vec<String>>
+}
+
+struct Parser<'s> {
+    rest: &'s str
+}
+
+impl<'s> Parser<'s> {
+    fn peek(&self) -> Option<u8> {
+        self.rest.as_bytes().first().cloned()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.rest = &self.rest[1..];
+
+            true
+        } else {
+            false
+        }
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        let len = self.rest.bytes()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+
+        if len == 0 {
+            return None;
+        }
+
+        let out = self.rest[..len].parse().ok()?;
+
+        self.rest = &self.rest[len..];
+
+        Some(out)
+    }
+
+    fn source_name(&mut self) -> Option<&'s str> {
+        let len = usize::try_from(self.number()?).ok()?;
+        let name = self.rest.get(..len)?;
+
+        if name.is_empty() {
+            return None;
+        }
+
+        self.rest = &self.rest[len..];
+
+        Some(name)
+    }
+
+    /// Parse an optional ABI tag of the form `<kind><n>`, returning
+    /// `n`, or 0 if there is no such tag.
+    fn abi_tag(&mut self, kind: char) -> Option<u32> {
+        let saved = self.rest;
+
+        if self.eat(b'B') {
+            let tag = self.source_name()?;
+            let num = match tag.strip_prefix(kind) {
+                Some(num) => num,
+                None => {
+                    self.rest = saved;
+
+                    return Some(0);
+                }
+            };
+
+            if num.starts_with('0') ||
+               !num.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+
+            num.parse().ok()
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Parse the optional disambiguator and arity tags, in that order.
+    fn abi_tags(&mut self) -> Option<(u32, u32)> {
+        let disambiguator = self.abi_tag('s')?;
+        let arity = self.abi_tag('t')?;
+
+        if self.peek() == Some(b'B') {
+            None
+        } else {
+            Some((disambiguator, arity))
+        }
+    }
+
+    /// Parse a name, returning its components, disambiguator and
+    /// arity.
+    fn name(&mut self) -> Option<(Vec<&'s str>, u32, u32)> {
+        let mut path = Vec::new();
+
+        if self.eat(b'N') {
+            while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                path.push(self.source_name()?);
+            }
+
+            let (disambiguator, arity) = self.abi_tags()?;
+
+            if path.is_empty() || !self.eat(b'E') {
+                None
+            } else {
+                Some((path, disambiguator, arity))
+            }
+        } else {
+            path.push(self.source_name()?);
+
+            let (disambiguator, arity) = self.abi_tags()?;
+
+            Some((path, disambiguator, arity))
+        }
+    }
+
+    fn ty(&mut self) -> Option<String> {
+        let code = self.peek()?;
+
+        if code.is_ascii_digit() || code == b'N' {
+            let (path, disambiguator, arity) = self.name()?;
+
+            return if disambiguator == 0 && arity == 0 {
+                Some(path.join("::"))
+            } else {
+                None
+            };
+        }
+
+        self.rest = &self.rest[1..];
+
+        let out = match code {
+            b'v' => String::from("()"),
+            b'b' => String::from("bool"),
+            b'a' => String::from("i8"),
+            b'h' => String::from("u8"),
+            b's' => String::from("i16"),
+            b't' => String::from("u16"),
+            b'i' => String::from("i32"),
+            b'j' => String::from("u32"),
+            b'x' => String::from("i64"),
+            b'y' => String::from("u64"),
+            b'n' => String::from("i128"),
+            b'o' => String::from("u128"),
+            b'f' => String::from("f32"),
+            b'd' => String::from("f64"),
+            b'e' => String::from("f80"),
+            b'g' => String::from("f128"),
+            b'D' if self.eat(b'i') => String::from("char"),
+            b'u' => {
+                let name = self.source_name()?;
+                let (kind, bits) = name.split_at(1);
+
+                if !matches!(kind, "i" | "u" | "f") || bits.is_empty() ||
+                   !bits.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+
+                name.to_string()
+            }
+            b'P' => format!("*{}", self.ty()?),
+            b'R' => format!("&{}", self.ty()?),
+            b'A' => {
+                let len = self.number()?;
+
+                if !self.eat(b'_') {
+                    return None;
+                }
+
+                format!("[{}; {}]", self.ty()?, len)
+            }
+            _ => return None
+        };
+
+        Some(out)
+    }
+}
+
+/// Demangle `s`, returning `None` if it is not a mangled Salt symbol.
+pub fn demangle(s: &str) -> Option<Demangled> {
+    let mut parser = Parser { rest: s.strip_prefix(PREFIX)? };
+    let (path, disambiguator, arity) = parser.name()?;
+    let path = path.iter().map(|s| s.to_string()).collect();
+    let params = if parser.rest.is_empty() {
+        None
+    } else if parser.rest == "v" {
+        Some(Vec::new())
+    } else {
+        let mut params = Vec::new();
+
+        while !parser.rest.is_empty() {
+            params.push(parser.ty()?);
+        }
+
+        Some(params)
+    };
+
+    Some(Demangled { path, disambiguator, arity, params })
+}
+
+/// Demangle `s`, a name mangled by `mangle_path`, interning the
+/// components with `ctx`.  Returns the path and the arity, or `None`
+/// if `s` is not such a name.
+pub fn demangle_path<'a, C>(ctx: &mut C,
+                            s: &'a str) -> Option<(SymbolPath<'a>, u32)>
+    where C: SymbolCtx<'a> {
+    let mut parser = Parser { rest: s.strip_prefix(PREFIX)? };
+    let (path, disambiguator, arity) = parser.name()?;
+
+    if disambiguator != 0 || !parser.rest.is_empty() {
+        return None;
+    }
+
+    Some((path.into_iter().map(|s| ctx.symbol(s)).collect(), arity))
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.'
+}
+
+/// Demangle every mangled Salt symbol appearing in `text`, leaving
+/// everything else unchanged.  This is what `salt-demangle` does to
+/// each line of its input.
+pub fn demangle_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(idx) = rest.find(PREFIX) {
+        let at_boundary = rest[..idx].chars().next_back()
+            .is_none_or(|c| !is_symbol_char(c));
+        let len = rest[idx..].find(|c| !is_symbol_char(c))
+            .unwrap_or(rest.len() - idx);
+        let word = &rest[idx..idx + len];
+
+        out.push_str(&rest[..idx]);
+
+        match demangle(word) {
+            Some(demangled) if at_boundary =>
+                out.push_str(&demangled.to_string()),
+            _ => out.push_str(word)
+        }
+
+        rest = &rest[idx + len..];
+    }
+
+    out.push_str(rest);
+
+    out
+}
+
+impl Display for Demangled {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.path.join("::"))?;
+
+        if self.arity != 0 {
+            let params = vec!["_"; self.arity as usize];
+
+            write!(f, "<{}>", params.join(", "))?;
+        }
+
+        if self.disambiguator != 0 {
+            write!(f, "#{}", self.disambiguator)?;
+        }
+
+        match &self.params {
+            Some(params) => write!(f, "({})", params.join(", ")),
+            None => Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_demangle_roundtrip() {
+    use salt::common::mangle::TypeDesc;
+    use salt::common::mangle::mangle;
+    use salt::common::str::intern::StrIntern;
+    use salt::common::symbol::QualName;
+    use salt::common::symbol::SymbolCtx;
+
+    let mut intern = StrIntern::new();
+    let name = QualName::new(vec![intern.symbol("geom"),
+                                  intern.symbol("Point"),
+                                  intern.symbol("scale")]);
+    let point = QualName::new(vec![intern.symbol("geom"),
+                                   intern.symbol("Point")]);
+    let params = [TypeDesc::Ref(Box::new(TypeDesc::Named(point))),
+                  TypeDesc::Float(64),
+                  TypeDesc::Array(3, Box::new(TypeDesc::Int { bits: 24,
+                                                              signed: false })),
+                  TypeDesc::Ptr(Box::new(TypeDesc::Char))];
+    let mangled = mangle(&name, Some(&params), 2);
+
+    assert_eq!(demangle(&mangled).unwrap().to_string(),
+               "geom::Point::scale#2(&geom::Point, f64, [u24; 3], *char)");
+    assert_eq!(demangle(&mangle(&name, None, 0)).unwrap().to_string(),
+               "geom::Point::scale");
+    assert_eq!(demangle("_Z4mainv").unwrap().to_string(), "main()");
+}
+
+#[test]
+fn test_demangle_bad() {
+    assert_eq!(demangle("main"), None);
+    assert_eq!(demangle("_Z"), None);
+    assert_eq!(demangle("_Z9foo"), None);
+    assert_eq!(demangle("_ZN3fooE"), Some(Demangled {
+        path: vec![String::from("foo")], disambiguator: 0, arity: 0,
+        params: None
+    }));
+    assert_eq!(demangle("_ZN3fooEz"), None);
+    assert_eq!(demangle("_ZN3foo"), None);
+    assert_eq!(demangle("_Z3fooB2s0"), None);
+    assert_eq!(demangle("_Z3fooB2t2B2s1"), None);
+    assert_eq!(demangle("_Z3fooB2x1"), None);
+}
+
+#[test]
+fn test_demangle_path() {
+    use salt::common::mangle::mangle_path;
+    use salt::common::str::intern::StrIntern;
+
+    let mut intern = StrIntern::new();
+
+    for (text, arity) in [("std.Map", 2), ("print", 0), ("a.b.c", 1)] {
+        let path = SymbolPath::parse(&mut intern, text);
+        let mangled = mangle_path(&path, arity);
+        let mut other = StrIntern::new();
+
+        assert_eq!(demangle_path(&mut other, &mangled)
+                       .map(|(path, arity)| (path.to_string(), arity)),
+                   Some((text.to_string(), arity)));
+    }
+
+    assert_eq!(demangle("_ZN3std3MapB2s3B2t2Ev").unwrap().to_string(),
+               "std::Map<_, _>#3()");
+    assert_eq!(demangle_path(&mut intern, "_Z3fooB2s1"), None);
+    assert_eq!(demangle_path(&mut intern, "_Z3foov"), None);
+}
+
+#[test]
+fn test_demangle_text() {
+    assert_eq!(demangle_text("  at _ZN3foo3barEv (foo.salt:3)"),
+               "  at foo::bar() (foo.salt:3)");
+    assert_eq!(demangle_text("x_ZN3foo3barEv _Zbad _Z1fi"),
+               "x_ZN3foo3barEv _Zbad f(i32)");
+}
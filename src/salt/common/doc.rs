@@ -0,0 +1,231 @@
+//! Pretty-printing documents.
+//!
+//! This is Wadler's "prettier printer" algebra.  A `Doc` is built from
+//! text, line breaks, nesting and concatenation.  Each `group` is laid
+//! out flat, with its line breaks as spaces, if it fits in the rest of
+//! the line, and otherwise with its breaks as newlines.  Rendering is
+//! done by `Doc::render` for a given page width:
+//!
+//! ```ignore
+//! let doc = group(concat(vec![text("f("),
+//!                             nest(2, concat(vec![line(), text("x")])),
+//!                             text(")")]));
+//!
+//! assert_eq!(doc.render(80), "f( x)");
+//! assert_eq!(doc.render(4), "f(\n  x)");
+//! ```
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// A document to be pretty-printed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Doc {
+    /// The empty document.
+    #[default]
+    Nil,
+    /// Text, which must not contain newlines.
+    Text(String),
+    /// A line break, or a space if its group is laid out flat.
+    Line,
+    /// Documents one after another.
+    Concat(Vec<Doc>),
+    /// A document with line breaks in it indented by a number of
+    /// columns more than the surrounding document.
+    Nest(usize, Box<Doc>),
+    /// A document laid out flat if it fits on the line.
+    Group(Box<Doc>)
+}
+
+/// How a group is being laid out.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mode {
+    Flat,
+    Break
+}
+
+/// Get the empty document.
+pub fn nil() -> Doc {
+    Doc::Nil
+}
+
+/// Get a document consisting of `str`, which must not contain
+/// newlines.
+pub fn text<S: Into<String>>(str: S) -> Doc {
+    Doc::Text(str.into())
+}
+
+/// Get a line break, which is a space if its group is laid out flat.
+pub fn line() -> Doc {
+    Doc::Line
+}
+
+/// Make `doc` a group, to be laid out flat if it fits on the line.
+pub fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+/// Indent the line breaks in `doc` by `indent` more columns.
+pub fn nest(indent: usize, doc: Doc) -> Doc {
+    Doc::Nest(indent, Box::new(doc))
+}
+
+/// Put `docs` one after another.
+pub fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+/// Check whether `doc`, laid out flat, followed by the rest of the
+/// line from `rest`, fits in `width` columns.
+fn fits(width: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut width = width;
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    while width >= 0 {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true
+            }
+        };
+
+        match doc {
+            Doc::Nil => {}
+            Doc::Text(str) => width -= str.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => width -= 1,
+            Doc::Line => return true,
+            Doc::Concat(docs) =>
+                stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+            Doc::Nest(_, doc) => stack.push((mode, doc)),
+            Doc::Group(doc) => stack.push((mode, doc))
+        }
+    }
+
+    false
+}
+
+impl Doc {
+    /// Put `other` after this document.
+    pub fn append(self, other: Doc) -> Doc {
+        match self {
+            Doc::Concat(mut docs) => {
+                docs.push(other);
+
+                Doc::Concat(docs)
+            }
+            doc => Doc::Concat(vec![doc, other])
+        }
+    }
+
+    /// Lay out the document in `width` columns.  Text too long to fit
+    /// overflows the line.
+    pub fn render(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut col = 0;
+        let mut stack = vec![(0, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Nil => {}
+                Doc::Text(str) => {
+                    out.push_str(str);
+                    col += str.chars().count();
+                }
+                Doc::Line if mode == Mode::Flat => {
+                    out.push(' ');
+                    col += 1;
+                }
+                Doc::Line => {
+                    out.push('\n');
+                    out.extend((0..indent).map(|_| ' '));
+                    col = indent;
+                }
+                Doc::Concat(docs) => stack.extend(
+                    docs.iter().rev().map(|doc| (indent, mode, doc))
+                ),
+                Doc::Nest(more, doc) => stack.push((indent + more, mode, doc)),
+                Doc::Group(doc) => {
+                    let rem = width as isize - col as isize;
+                    let mode = if mode == Mode::Flat ||
+                                  fits(rem, doc, &stack) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+
+                    stack.push((indent, mode, doc))
+                }
+            }
+        }
+
+        out
+    }
+}
+
+impl<'a> From<&'a str> for Doc {
+    fn from(str: &'a str) -> Doc {
+        text(str)
+    }
+}
+
+impl From<String> for Doc {
+    fn from(str: String) -> Doc {
+        text(str)
+    }
+}
+
+/// Documents are displayed laid out in 80 columns.
+impl Display for Doc {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.render(80))
+    }
+}
+
+#[cfg(test)]
+fn call(name: &str, args: Vec<Doc>) -> Doc {
+    let mut body = nil();
+
+    for (i, arg) in args.into_iter().enumerate() {
+        if i > 0 {
+            body = body.append(text(","));
+        }
+
+        body = body.append(line()).append(arg);
+    }
+
+    group(text(name).append(text("(")).append(nest(2, body)).append(text(")")))
+}
+
+#[test]
+fn test_group() {
+    let doc = call("f", vec![text("alpha"), text("beta")]);
+
+    assert_eq!(doc.render(80), "f( alpha, beta)");
+    assert_eq!(doc.render(15), "f( alpha, beta)");
+    assert_eq!(doc.render(14), "f(\n  alpha,\n  beta)");
+}
+
+#[test]
+fn test_nested_groups() {
+    let inner = call("g", vec![text("x"), text("y")]);
+    let doc = call("f", vec![text("long_argument"), inner]);
+
+    // The outer group breaks, and the inner one then fits.
+    assert_eq!(doc.render(20), "f(\n  long_argument,\n  g( x, y))");
+    assert_eq!(doc.render(8),
+               "f(\n  long_argument,\n  g(\n    x,\n    y))");
+    assert_eq!(doc.to_string(), "f( long_argument, g( x, y))");
+}
+
+#[test]
+fn test_fits_rest() {
+    // The group fits, but the text after it on the line does not.
+    let doc = group(text("a").append(line()).append(text("b")))
+        .append(text("cdef"));
+
+    assert_eq!(doc.render(7), "a bcdef");
+    assert_eq!(doc.render(6), "a\nbcdef");
+}
@@ -0,0 +1,134 @@
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::io::Result;
+use std::io::Write;
+
+/// Record of environment variables consulted during a compilation,
+/// along with the values that were observed.  Anything that reads the
+/// environment in a way that can affect output should go through this,
+/// so that dependency files and incremental fingerprints reflect it.
+#[derive(Debug, Default)]
+pub struct EnvTracker(RefCell<BTreeMap<String, Option<String>>>);
+
+impl EnvTracker {
+    /// Create an empty `EnvTracker`.
+    pub fn new() -> EnvTracker {
+        EnvTracker(RefCell::new(BTreeMap::new()))
+    }
+
+    /// Read the environment variable `name`, recording the read.  The
+    /// first value observed is the one that is recorded and returned
+    /// for the remainder of the compilation, so that all phases see a
+    /// consistent environment.
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.0.borrow_mut()
+            .entry(name.to_string())
+            .or_insert_with(|| env::var_os(name)
+                            .map(|val| val.to_string_lossy().into_owned()))
+            .clone()
+    }
+
+    /// Get all recorded variables and their values, sorted by name.
+    pub fn recorded(&self) -> Vec<(String, Option<String>)> {
+        self.0.borrow()
+            .iter()
+            .map(|(name, val)| (name.clone(), val.clone()))
+            .collect()
+    }
+
+    /// Get the names of recorded variables whose values in the current
+    /// environment differ from the recorded values.
+    pub fn changed(&self) -> Vec<String> {
+        self.0.borrow()
+            .iter()
+            .filter(|&(name, val)| {
+                let current = env::var_os(name)
+                    .map(|val| val.to_string_lossy().into_owned());
+
+                current != *val
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Write the recorded variables in dependency-file format, one
+    /// `# env-dep:NAME=VALUE` line per variable.  Variables that were
+    /// not set are written as `# env-dep:NAME`.
+    pub fn write_dep_info<W: Write>(&self, out: &mut W) -> Result<()> {
+        for (name, val) in self.0.borrow().iter() {
+            match val {
+                Some(val) => writeln!(out, "# env-dep:{}={}", name,
+                                      escape_dep(val))?,
+                None => writeln!(out, "# env-dep:{}", name)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Escape newlines and backslashes so a value fits on one line.
+fn escape_dep(val: &str) -> String {
+    val.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+impl Hash for EnvTracker {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.borrow().hash(state)
+    }
+}
+
+/// The fingerprint covers every variable read and its value, so it
+/// changes whenever a build would need to be redone.
+impl StableHash for EnvTracker {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.0.borrow().stable_hash(hasher)
+    }
+}
+
+#[test]
+fn test_records_reads() {
+    let tracker = EnvTracker::new();
+
+    env::set_var("SALT_TEST_ENV_RECORDS", "yes");
+
+    assert_eq!(tracker.var("SALT_TEST_ENV_RECORDS"), Some(String::from("yes")));
+    assert_eq!(tracker.var("SALT_TEST_ENV_UNSET_VAR"), None);
+    assert_eq!(tracker.recorded(),
+               vec![(String::from("SALT_TEST_ENV_RECORDS"),
+                     Some(String::from("yes"))),
+                    (String::from("SALT_TEST_ENV_UNSET_VAR"), None)]);
+}
+
+#[test]
+fn test_changed() {
+    let tracker = EnvTracker::new();
+
+    env::set_var("SALT_TEST_ENV_CHANGED", "a");
+    tracker.var("SALT_TEST_ENV_CHANGED");
+    assert!(tracker.changed().is_empty());
+
+    env::set_var("SALT_TEST_ENV_CHANGED", "b");
+    assert_eq!(tracker.var("SALT_TEST_ENV_CHANGED"), Some(String::from("a")));
+    assert_eq!(tracker.changed(), vec![String::from("SALT_TEST_ENV_CHANGED")]);
+}
+
+#[test]
+fn test_dep_info() {
+    let tracker = EnvTracker::new();
+    let mut out = Vec::new();
+
+    env::set_var("SALT_TEST_ENV_DEP", "x\ny");
+    tracker.var("SALT_TEST_ENV_DEP");
+    tracker.var("SALT_TEST_ENV_DEP_UNSET");
+    tracker.write_dep_info(&mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "# env-dep:SALT_TEST_ENV_DEP=x\\ny\n\
+                # env-dep:SALT_TEST_ENV_DEP_UNSET\n");
+}
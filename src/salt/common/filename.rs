@@ -1,4 +1,6 @@
 use core::clone::Clone;
+use std::cell::RefCell;
+#[cfg(feature = "std")]
 use std::convert::AsRef;
 use std::cmp::Ordering;
 use std::fmt::Debug;
@@ -7,12 +9,68 @@ use std::fmt::Formatter;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::fmt::Result;
+#[cfg(feature = "std")]
+use std::path::Component;
+#[cfg(feature = "std")]
 use std::path::Path;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use salt::common::cache::DecodeResult;
+#[cfg(feature = "std")]
+use salt::common::cache::Decodable;
+#[cfg(feature = "std")]
+use salt::common::cache::DecodeError;
+#[cfg(feature = "std")]
+use salt::common::cache::Decoder;
+#[cfg(feature = "std")]
+use salt::common::cache::Encodable;
+#[cfg(feature = "std")]
+use salt::common::cache::Encoder;
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+#[cfg(feature = "std")]
+use salt::common::str::arena::StrArena;
+use salt::common::str::intern::StrIntern;
+#[cfg(feature = "std")]
+use salt::common::vfs::FileSystem;
 
 /// A distinguished type for filenames.  These are implemented as
 /// references to interned strings, making comparison very easy.
-#[derive(Copy, Eq, Ord)]
-pub struct Filename<'a>(&'a str);
+///
+/// A filename has two forms: the name recorded in output, such as
+/// debug info and diagnostics, and the local path used to open the
+/// file.  They are the same unless the name was remapped, as a
+/// `PathRemap` does for reproducible builds.
+#[derive(Copy, Eq)]
+pub struct Filename<'a> {
+    recorded: &'a str,
+    local: &'a str
+}
+
+/// The name of standard input.  This is a static, rather than a
+/// literal, so that its address is unique.
+static STDIN: [u8; 7] = *b"<stdin>";
+
+/// The local path of files with no path, such as standard input.  As
+/// with `STDIN`, the address is what matters.
+static VIRTUAL: [u8; 9] = *b"<virtual>";
+
+/// Get one of the static names above as a string.
+fn static_name(bytes: &'static [u8]) -> &'static str {
+    ::std::str::from_utf8(bytes).unwrap()
+}
+
+/// Prefix substitutions applied to paths, so that recorded filenames
+/// do not depend on where the sources happen to be, like
+/// `--remap-path-prefix`.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PathRemap {
+    prefixes: Vec<(PathBuf, PathBuf)>
+}
 
 /// Context for creating Filenames.
 pub trait FilenameCtx<'a> {
@@ -20,48 +78,642 @@ pub trait FilenameCtx<'a> {
     fn filename(&mut self, fname: &'a str) -> Filename<'a>;
 }
 
+/// Context for creating Filenames through a shared reference, so that
+/// one context can be used from several places at once, such as by an
+/// AST builder and the parser feeding it.
+///
+/// Any `FilenameCtx` in a `RefCell`, or in a `Mutex` to share it
+/// between threads, is one.  A reference to a shared context is in
+/// turn a `FilenameCtx`, for passing to code that wants one.
+pub trait SharedFilenameCtx<'a> {
+    /// Convert `fname` into a corresponding `Filename`.
+    fn filename(&self, fname: &'a str) -> Filename<'a>;
+}
+
+/// How a `FilenameTable` normalizes paths.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Normalization {
+    /// Remove `.` components and repeated separators, and resolve
+    /// `..` components against the components before them, without
+    /// looking at the filesystem.  This can give the wrong answer when
+    /// a component before a `..` is a symbolic link.
+    #[default]
+    Lexical,
+    /// Resolve the path through the filesystem, giving an absolute
+    /// path with symbolic links resolved.  Paths that do not exist
+    /// are normalized lexically instead.  The filesystem is the disk,
+    /// unless the table was given another with `set_fs`.
+    #[cfg(feature = "fs")]
+    Canonical
+}
+
+/// Context for creating Filenames that normalizes paths before
+/// interning them, so that different spellings of a path, such as
+/// `./foo.salt` and `foo.salt`, give the same `Filename`.  Normalized
+/// paths are copied into a `StrArena`.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct FilenameTable<'a> {
+    arena: &'a StrArena,
+    normalization: Normalization,
+    remap: PathRemap,
+    /// The filesystem paths are canonicalized in, if not the disk.
+    fs: Option<&'a dyn FileSystem>,
+    /// The number of the last anonymous buffer created.
+    buffers: u32,
+    /// The number of the last REPL input created.
+    repl_inputs: u32
+}
+
+/// How to display filenames in diagnostics.  Paths can be shown
+/// relative to a working directory, with a home directory replaced
+/// by `~`, and with leading directories abbreviated to `...` to fit a
+/// length.  Remapped names are only abbreviated, as they were chosen
+/// to be shown as they are, and names of files with no path are shown
+/// unchanged.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FilenameDisplay {
+    cwd: Option<PathBuf>,
+    home: Option<PathBuf>,
+    max_len: Option<usize>
+}
+
+/// A filename displayed according to a `FilenameDisplay`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayFilename<'a, 'b> {
+    name: Filename<'a>,
+    opts: &'b FilenameDisplay
+}
+
+/// Normalize `path` lexically, as `Normalization::Lexical` describes.
+/// The empty path, and paths that cancel out, become `.`.
+#[cfg(feature = "std")]
+pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut out = PathBuf::new();
+
+    for component in path.as_ref().components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match out.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    out.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => out.push("..")
+            },
+            component => out.push(component)
+        }
+    }
+
+    if out.as_os_str().is_empty() {
+        out.push(".")
+    }
+
+    out
+}
+
+#[cfg(feature = "std")]
+impl PathRemap {
+    /// Create a `PathRemap` with no substitutions.
+    pub fn new() -> PathRemap {
+        PathRemap { prefixes: Vec::new() }
+    }
+
+    /// Replace the prefix `from` of paths with `to`.  Prefixes match
+    /// whole components.  If several prefixes match a path, the one
+    /// added last is used.
+    pub fn add<F, T>(&mut self, from: F, to: T)
+        where F: Into<PathBuf>,
+              T: Into<PathBuf> {
+        self.prefixes.push((from.into(), to.into()))
+    }
+
+    /// Check whether there are no substitutions.
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Get `path` with its prefix substituted, if one matches.
+    pub fn remap(&self, path: &Path) -> Option<PathBuf> {
+        self.prefixes.iter().rev().find_map(|(from, to)| {
+            path.strip_prefix(from).ok().map(|rest| {
+                if rest.as_os_str().is_empty() {
+                    to.clone()
+                } else {
+                    to.join(rest)
+                }
+            })
+        })
+    }
+}
+
+/// Create a virtual filename `<kind N>`, for the first `N` after `last`
+/// whose name is not in `arena`, and set `last` to it.
+#[cfg(feature = "std")]
+fn fresh_virtual<'a>(arena: &'a StrArena, kind: &str,
+                     last: &mut u32) -> Filename<'a> {
+    loop {
+        *last += 1;
+
+        let name = format!("<{} {}>", kind, last);
+
+        if arena.get(&name).is_none() {
+            return Filename::virtual_file(arena.intern(&name));
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> FilenameTable<'a> {
+    /// Create a `FilenameTable` keeping normalized paths in `arena`
+    /// and normalizing them as `normalization` says.
+    pub fn new(arena: &'a StrArena,
+               normalization: Normalization) -> FilenameTable<'a> {
+        FilenameTable { arena, normalization, remap: PathRemap::new(),
+                        fs: None, buffers: 0, repl_inputs: 0 }
+    }
+
+    /// Get how paths are normalized.
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Get the prefix substitutions applied to recorded names.
+    pub fn remap(&self) -> &PathRemap {
+        &self.remap
+    }
+
+    /// Set the prefix substitutions applied to recorded names, for
+    /// filenames created from now on.
+    pub fn set_remap(&mut self, remap: PathRemap) {
+        self.remap = remap
+    }
+
+    /// Set the filesystem that `Normalization::Canonical` resolves
+    /// paths in, for filenames created from now on.
+    pub fn set_fs(&mut self, fs: &'a dyn FileSystem) {
+        self.fs = Some(fs)
+    }
+
+    /// Create a filename for a new anonymous buffer, such as an
+    /// editor's unsaved file, named `<buffer N>`.  Names already in
+    /// the arena are skipped, so tables sharing an arena never hand out
+    /// the same buffer.
+    pub fn buffer(&mut self) -> Filename<'a> {
+        fresh_virtual(self.arena, "buffer", &mut self.buffers)
+    }
+
+    /// Create a filename for a new piece of REPL input, named
+    /// `<repl N>`.  As with `buffer`, names already in the arena are
+    /// skipped.
+    pub fn repl(&mut self) -> Filename<'a> {
+        fresh_virtual(self.arena, "repl", &mut self.repl_inputs)
+    }
+
+    /// Get the recorded and local forms of the normalized path `path`.
+    fn forms(&self, path: &Path) -> (String, String) {
+        let local = path.to_string_lossy().into_owned();
+        let recorded = match self.remap.remap(path) {
+            Some(remapped) => remapped.to_string_lossy().into_owned(),
+            None => local.clone()
+        };
+
+        (recorded, local)
+    }
+
+    /// Normalize `fname`.
+    fn normalize(&self, fname: &str) -> PathBuf {
+        match self.normalization {
+            Normalization::Lexical => normalize_path(fname),
+            #[cfg(feature = "fs")]
+            Normalization::Canonical => match self.fs {
+                Some(fs) => fs.canonicalize(Path::new(fname)),
+                None => ::std::fs::canonicalize(fname)
+            }.unwrap_or_else(|_| normalize_path(fname))
+        }
+    }
+
+    /// Get the `Filename` for `fname`, if it has been created already.
+    pub fn get(&self, fname: &str) -> Option<Filename<'a>> {
+        let (recorded, local) = self.forms(&self.normalize(fname));
+
+        Some(Filename { recorded: self.arena.get(&recorded)?,
+                        local: self.arena.get(&local)? })
+    }
+}
+
+/// Paths that are not valid Unicode after normalization have invalid
+/// parts replaced with U+FFFD.  The remapped path is recorded, and the
+/// normalized path kept as the local path.
+#[cfg(feature = "std")]
+impl<'a> FilenameCtx<'a> for FilenameTable<'a> {
+    fn filename(&mut self, fname: &'a str) -> Filename<'a> {
+        let (recorded, local) = self.forms(&self.normalize(fname));
+
+        Filename { recorded: self.arena.intern(&recorded),
+                   local: self.arena.intern(&local) }
+    }
+}
+
+impl<'a> FilenameCtx<'a> for StrIntern<'a> {
+    fn filename(&mut self, fname: &'a str) -> Filename<'a> {
+        Filename::interned(self.intern(fname))
+    }
+}
+
+/// Panics if the context is already borrowed.
+impl<'a, C: FilenameCtx<'a>> SharedFilenameCtx<'a> for RefCell<C> {
+    fn filename(&self, fname: &'a str) -> Filename<'a> {
+        self.borrow_mut().filename(fname)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, C: FilenameCtx<'a>> SharedFilenameCtx<'a> for Mutex<C> {
+    fn filename(&self, fname: &'a str) -> Filename<'a> {
+        self.lock().unwrap_or_else(|err| err.into_inner()).filename(fname)
+    }
+}
+
+impl<'a, C> FilenameCtx<'a> for &C
+    where C: SharedFilenameCtx<'a> + ?Sized {
+    fn filename(&mut self, fname: &'a str) -> Filename<'a> {
+        SharedFilenameCtx::filename(*self, fname)
+    }
+}
+
+/// Get the address of `str`, for comparing interned strings.
+fn addr(str: &str) -> usize {
+    str.as_ptr() as usize
+}
+
+impl<'a> Filename<'a> {
+    /// Create a filename with the interned string `str` as both its
+    /// recorded name and its local path.
+    fn interned(str: &'a str) -> Filename<'a> {
+        Filename { recorded: str, local: str }
+    }
+
+    /// Create a filename for a file with no path, named `name`.
+    fn virtual_file(name: &'a str) -> Filename<'a> {
+        Filename { recorded: name, local: static_name(&VIRTUAL) }
+    }
+
+    /// Get the filename of standard input, `<stdin>`.  This is
+    /// distinct from any file named `<stdin>`.
+    pub fn stdin() -> Filename<'static> {
+        Filename::virtual_file(static_name(&STDIN))
+    }
+
+    /// Check whether this file has no path, like standard input,
+    /// anonymous buffers and REPL input.
+    pub fn is_virtual(&self) -> bool {
+        ::std::ptr::eq(self.local, static_name(&VIRTUAL))
+    }
+
+    /// Get the name recorded in output.
+    pub fn recorded(&self) -> &'a str {
+        self.recorded
+    }
+
+    /// Get the path used to open the file.  Files with no path have
+    /// a placeholder.
+    #[cfg(feature = "std")]
+    pub fn local_path(&self) -> &'a Path {
+        Path::new(self.local)
+    }
+
+    /// Check whether the recorded name differs from the local path.
+    pub fn is_remapped(&self) -> bool {
+        !self.is_virtual() && !::std::ptr::eq(self.recorded, self.local)
+    }
+
+    /// Get the local path of this file relative to the current
+    /// directory, if it is relative, as an absolute path, normalized
+    /// lexically.  Returns `None` if the current directory cannot be
+    /// found, or the file has no path.
+    #[cfg(feature = "std")]
+    pub fn absolute(&self) -> Option<PathBuf> {
+        if self.is_virtual() {
+            return None;
+        }
+
+        ::std::path::absolute(self.local).ok().map(normalize_path)
+    }
+
+    /// Get the local path of this file relative to the directory `base`,
+    /// comparing the paths lexically.  If one of the paths is absolute
+    /// and the other not, both are made absolute first.  Returns `None`
+    /// if there is no such path, as when `base` goes up past where
+    /// the paths diverge, or the paths are on different drives, or
+    /// the file has no path.
+    #[cfg(feature = "std")]
+    pub fn relative_to<P: AsRef<Path>>(&self, base: P) -> Option<PathBuf> {
+        if self.is_virtual() {
+            return None;
+        }
+
+        let base = base.as_ref();
+        let (path, base) = if self.local_path().is_absolute() ==
+                              base.is_absolute() {
+            (normalize_path(self.local), normalize_path(base))
+        } else {
+            (self.absolute()?,
+             normalize_path(::std::path::absolute(base).ok()?))
+        };
+        let mut path = path.components().peekable();
+        let mut base = base.components().peekable();
+        let mut out = PathBuf::new();
+
+        while path.peek().is_some() && path.peek() == base.peek() {
+            path.next();
+            base.next();
+        }
+
+        for component in base {
+            match component {
+                Component::Normal(_) => out.push(".."),
+                Component::CurDir => {}
+                _ => return None
+            }
+        }
+
+        for component in path {
+            match component {
+                Component::Prefix(_) | Component::RootDir => return None,
+                component => out.push(component)
+            }
+        }
+
+        if out.as_os_str().is_empty() {
+            out.push(".")
+        }
+
+        Some(out)
+    }
+
+    /// Get an adapter that displays this filename according to `opts`.
+    #[cfg(feature = "std")]
+    pub fn display_with<'b>(&self,
+                            opts: &'b FilenameDisplay)
+                            -> DisplayFilename<'a, 'b> {
+        DisplayFilename { name: *self, opts }
+    }
+}
+
+#[cfg(feature = "std")]
+impl FilenameDisplay {
+    /// Create options that display filenames as they are recorded.
+    pub fn new() -> FilenameDisplay {
+        FilenameDisplay::default()
+    }
+
+    /// Create options that show paths relative to the current
+    /// directory, and the home directory, from `HOME`, as `~`.
+    pub fn from_env() -> FilenameDisplay {
+        FilenameDisplay { cwd: ::std::env::current_dir().ok(),
+                          home: ::std::env::var_os("HOME")
+                              .filter(|home| !home.is_empty())
+                              .map(PathBuf::from),
+                          max_len: None }
+    }
+
+    /// Show paths inside `dir` relative to it.
+    pub fn with_cwd<P: Into<PathBuf>>(mut self, dir: P) -> FilenameDisplay {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    /// Show paths inside `dir`, other than those shown relative to the
+    /// working directory, starting with `~` in place of `dir`.
+    pub fn with_home<P: Into<PathBuf>>(mut self, dir: P) -> FilenameDisplay {
+        self.home = Some(dir.into());
+        self
+    }
+
+    /// Abbreviate paths longer than `len` characters, replacing as
+    /// many leading directories as needed with `...`.  The last
+    /// component is always shown in full.
+    pub fn with_max_len(mut self, len: usize) -> FilenameDisplay {
+        self.max_len = Some(len);
+        self
+    }
+
+    /// Get `name` as it is displayed.
+    pub fn path(&self, name: &Filename) -> String {
+        if name.is_virtual() {
+            return name.recorded().to_string();
+        }
+
+        let path = if name.is_remapped() {
+            PathBuf::from(name.recorded())
+        } else {
+            self.shorten(name)
+        };
+
+        match self.max_len {
+            Some(len) => abbreviate(&path, len),
+            None => path.display().to_string()
+        }
+    }
+
+    /// Get the path of `name`, relative to the working directory if it
+    /// is inside it, or else from the home directory if it is inside
+    /// that.
+    fn shorten(&self, name: &Filename) -> PathBuf {
+        let inside = |path: &PathBuf| {
+            path.components().next() != Some(Component::ParentDir)
+        };
+
+        if let Some(path) = self.cwd.as_ref()
+                                .and_then(|cwd| name.relative_to(cwd))
+                                .filter(inside) {
+            return path;
+        }
+
+        let path = name.local_path();
+
+        match self.home.as_ref() {
+            Some(home) if path.is_absolute() =>
+                match path.strip_prefix(normalize_path(home)) {
+                    Ok(rest) => Path::new("~").join(rest),
+                    Err(_) => path.to_path_buf()
+                },
+            _ => path.to_path_buf()
+        }
+    }
+}
+
+/// Abbreviate `path` to at most `len` characters, if it is longer,
+/// keeping as many trailing components as fit after `...`.
+#[cfg(feature = "std")]
+fn abbreviate(path: &Path, len: usize) -> String {
+    let full = path.display().to_string();
+    let components: Vec<_> = path.components().collect();
+
+    if full.chars().count() <= len || components.len() < 2 {
+        return full;
+    }
+
+    let mut kept = components.len() - 1;
+    let fits = |kept: usize| {
+        let tail: PathBuf = components[kept..].iter().collect();
+
+        Path::new("...").join(tail).display().to_string()
+    };
+
+    while kept > 1 && fits(kept - 1).chars().count() <= len {
+        kept -= 1;
+    }
+
+    fits(kept)
+}
+
 impl<'a> Clone for Filename<'a> {
     fn clone(&self) -> Filename<'a> {
-        Filename(self.0)
+        *self
     }
 }
 
 impl<'a> Hash for Filename<'a> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_usize(((self.0 as *const _) as *const u8) as usize);
+        state.write_usize(addr(self.recorded));
+        state.write_usize(addr(self.local));
+    }
+}
+
+/// Stable hashing is by the content of the recorded name, not by
+/// address, so fingerprints do not depend on where the interned
+/// string happens to live, or where the sources are.
+impl<'a> StableHash for Filename<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self.recorded)
+    }
+}
+
+/// The remapping is part of what decides recorded names, so it belongs
+/// in fingerprints of build settings.
+#[cfg(feature = "std")]
+impl StableHash for PathRemap {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.prefixes.stable_hash(hasher)
+    }
+}
+
+#[cfg(feature = "std")]
+impl StableHash for Normalization {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let tag: u8 = match self {
+            Normalization::Lexical => 0,
+            #[cfg(feature = "fs")]
+            Normalization::Canonical => 1
+        };
+
+        tag.stable_hash(hasher)
+    }
+}
+
+/// Both forms of a filename are written to the string table, and
+/// re-interned when read back, so that a decoded name equals the live
+/// one when the decoder's table holds its strings.  A tag says which
+/// local path follows the recorded name: none for plain names, whose
+/// two forms are the same string, the local path for remapped names,
+/// and none for files with no path, with standard input kept apart
+/// from other such files.
+#[cfg(feature = "std")]
+impl<'a> Encodable for Filename<'a> {
+    fn encode(&self, enc: &mut Encoder) {
+        if ::std::ptr::eq(self.recorded, static_name(&STDIN)) {
+            enc.emit_u8(3)
+        } else if self.is_virtual() {
+            enc.emit_u8(2);
+            enc.emit_interned(self.recorded)
+        } else if self.is_remapped() {
+            enc.emit_u8(1);
+            enc.emit_interned(self.recorded);
+            enc.emit_interned(self.local)
+        } else {
+            enc.emit_u8(0);
+            enc.emit_interned(self.recorded)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Decodable<'a> for Filename<'a> {
+    fn decode(dec: &mut Decoder<'a, '_>) -> DecodeResult<Filename<'a>> {
+        match dec.read_u8()? {
+            0 => dec.read_interned().map(Filename::interned),
+            1 => {
+                let recorded = dec.read_interned()?;
+
+                Ok(Filename { recorded, local: dec.read_interned()? })
+            },
+            2 => dec.read_interned().map(Filename::virtual_file),
+            3 => Ok(Filename::stdin()),
+            tag => Err(DecodeError::BadTag { what: "Filename",
+                                             tag: tag as u64 })
+        }
     }
 }
 
 impl<'a> PartialEq for Filename<'a> {
     fn eq(&self, other: &Filename<'a>) -> bool {
-        self.0 as *const _ == other.0 as *const _
+        ::std::ptr::eq(self.recorded, other.recorded) &&
+        ::std::ptr::eq(self.local, other.local)
+    }
+}
+
+/// Filenames are ordered by content, so that sorting by filename gives
+/// the same order on every run: by the recorded name, then by the local
+/// path.  Equal filenames are the same interned strings, so ties are
+/// broken by address, as equality is, which keeps the order consistent
+/// with it.
+impl<'a> Ord for Filename<'a> {
+    fn cmp(&self, other: &Filename<'a>) -> Ordering {
+        let key = |name: &Filename| (addr(name.recorded), name.recorded.len(),
+                                     addr(name.local), name.local.len());
+
+        self.recorded.cmp(other.recorded)
+            .then_with(|| self.local.cmp(other.local))
+            .then_with(|| key(self).cmp(&key(other)))
     }
 }
 
 impl<'a> PartialOrd for Filename<'a> {
     fn partial_cmp(&self, other: &Filename<'a>) -> Option<Ordering> {
-        let a = ((self.0 as *const _) as *const u8) as usize;
-        let b = ((other.0 as *const _) as *const u8) as usize;
-
-        Some(a.cmp(&b))
+        Some(self.cmp(other))
     }
 }
 
+/// Filenames are displayed as their recorded name.
 impl<'a> Display for Filename<'a> {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.recorded)
     }
 }
 
 impl<'a> Debug for Filename<'a> {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{:?}", self.0 as *const _)
+        write!(f, "{:?}", self.recorded as *const _)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, 'b> Display for DisplayFilename<'a, 'b> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.opts.path(&self.name))
     }
 }
 
+/// Filenames are used as paths to open files by their local path.
+#[cfg(feature = "std")]
 impl<'a> AsRef<Path> for Filename<'a> {
     fn as_ref(&self) -> &Path {
-        Path::new(self.0)
+        self.local_path()
     }
 }
 
@@ -69,8 +721,8 @@ impl<'a> AsRef<Path> for Filename<'a> {
 fn test_ref_equality_mismatch() {
     let a = "helloa".split_at(5).0;
     let b = "hellob".split_at(5).0;
-    let fa = Filename(a);
-    let fb = Filename(b);
+    let fa = Filename::interned(a);
+    let fb = Filename::interned(b);
 
     assert_ne!(fa, fb)
 }
@@ -78,8 +730,227 @@ fn test_ref_equality_mismatch() {
 #[test]
 fn test_ref_equality_same() {
     let a = "hello";
-    let fa = Filename(a);
-    let fb = Filename(a);
+    let fa = Filename::interned(a);
+    let fb = Filename::interned(a);
 
     assert_eq!(fa, fb)
 }
+
+#[test]
+fn test_order_by_content() {
+    let mut names = StrIntern::new();
+    let b = names.filename("b.salt");
+    let a = names.filename("a.salt");
+    let mut sorted = vec![b, Filename::stdin(), a];
+
+    sorted.sort();
+
+    assert_eq!(sorted, [Filename::stdin(), a, b]);
+
+    // Equal text in different strings still orders consistently with
+    // equality.
+    let text = String::from("a.salt");
+    let a2 = Filename::interned(&text);
+
+    assert_ne!(a.cmp(&a2), Ordering::Equal);
+    assert_eq!(a.cmp(&a2), a2.cmp(&a).reverse());
+    assert!(b > a2);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_normalize_path() {
+    for (path, normal) in [("foo.salt", "foo.salt"),
+                           ("./foo.salt", "foo.salt"),
+                           ("a//b/./../c.salt", "a/c.salt"),
+                           ("../x/../../y", "../../y"), ("/../a/", "/a"),
+                           ("a/..", "."), ("", ".")].iter() {
+        assert_eq!(normalize_path(path), Path::new(normal), "{}", path);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_filename_table() {
+    let arena = StrArena::new();
+    let mut table = FilenameTable::new(&arena, Normalization::Lexical);
+    let a = table.filename("./src/foo.salt");
+    let b = table.filename("src/bar/../foo.salt");
+
+    assert_eq!(a, b);
+    assert_eq!(a.to_string(), "src/foo.salt");
+    assert_eq!(table.get("src//foo.salt"), Some(a));
+    assert_eq!(table.get("src/baz.salt"), None);
+    assert_ne!(table.filename("src/baz.salt"), a);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_relative_to() {
+    let arena = StrArena::new();
+    let mut table = FilenameTable::new(&arena, Normalization::Lexical);
+    let name = table.filename("/home/salt/src/lib/foo.salt");
+
+    assert_eq!(name.relative_to("/home/salt/src"),
+               Some(PathBuf::from("lib/foo.salt")));
+    assert_eq!(name.relative_to("/home/salt/test/"),
+               Some(PathBuf::from("../src/lib/foo.salt")));
+    assert_eq!(name.relative_to("/home/salt/src/lib/foo.salt"),
+               Some(PathBuf::from(".")));
+    assert_eq!(table.filename("a/b.salt").relative_to("../c"), None);
+    assert!(table.filename("a/b.salt").absolute().unwrap().is_absolute());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_remap() {
+    let arena = StrArena::new();
+    let mut table = FilenameTable::new(&arena, Normalization::Lexical);
+    let mut remap = PathRemap::new();
+
+    remap.add("/home/salt", "/src");
+    remap.add("/home/salt/vendor", "/vendor");
+    table.set_remap(remap);
+
+    let name = table.filename("/home/salt/./lib/foo.salt");
+    let vendored = table.filename("/home/salt/vendor/bar.salt");
+    let other = table.filename("/home/saltier/x.salt");
+
+    assert_eq!(name.recorded(), "/src/lib/foo.salt");
+    assert_eq!(name.local_path(), Path::new("/home/salt/lib/foo.salt"));
+    assert_eq!(name.to_string(), "/src/lib/foo.salt");
+    assert!(name.is_remapped());
+    assert_eq!(vendored.recorded(), "/vendor/bar.salt");
+    assert_eq!(other.recorded(), "/home/saltier/x.salt");
+    assert!(!other.is_remapped());
+    assert_eq!(table.get("/home/salt/lib/foo.salt"), Some(name));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_encode_round_trip() {
+    use salt::common::cache::decode;
+    use salt::common::cache::encode;
+
+    let arena = StrArena::new();
+    let mut table = FilenameTable::new(&arena, Normalization::Lexical);
+    let mut remap = PathRemap::new();
+
+    remap.add("/home/salt", "/src");
+    table.set_remap(remap);
+
+    let names = vec![table.filename("/home/salt/lib/foo.salt"),
+                     table.filename("/tmp/bar.salt"),
+                     table.buffer(),
+                     Filename::stdin()];
+    let data = encode(&names);
+    let mut intern = StrIntern::new();
+
+    for name in &names[..3] {
+        intern.intern(name.recorded);
+        intern.intern(name.local);
+    }
+
+    let decoded: Vec<Filename> = decode(&data, &mut intern).unwrap();
+
+    assert_eq!(decoded, names);
+    assert!(decoded[0].is_remapped());
+    assert_eq!(decoded[0].local_path(), Path::new("/home/salt/lib/foo.salt"));
+    assert!(decoded[2].is_virtual());
+    assert_eq!(decoded[3], Filename::stdin());
+
+    let mut fresh = StrIntern::new();
+    let decoded: Vec<Filename> = decode(&data, &mut fresh).unwrap();
+
+    assert_eq!(decoded[0].recorded(), "/src/lib/foo.salt");
+    assert!(decoded[0].is_remapped() && !decoded[1].is_remapped());
+    assert_eq!(decoded[2].to_string(), "<buffer 1>");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_virtual() {
+    let arena = StrArena::new();
+    let mut table = FilenameTable::new(&arena, Normalization::Lexical);
+    let stdin = Filename::stdin();
+    let first = table.buffer();
+    let second = table.buffer();
+    let repl = table.repl();
+
+    assert_eq!(stdin, Filename::stdin());
+    assert_ne!(stdin, table.filename("<stdin>"));
+    assert!(stdin.is_virtual() && !stdin.is_remapped());
+    assert_eq!(stdin.to_string(), "<stdin>");
+    assert_eq!(stdin.absolute(), None);
+    assert_eq!(first.to_string(), "<buffer 1>");
+    assert_eq!(second.to_string(), "<buffer 2>");
+    assert_ne!(first, table.filename("<buffer 1>"));
+    assert_eq!(repl.to_string(), "<repl 1>");
+    assert!(!table.filename("a.salt").is_virtual());
+
+    // Another table over the same arena continues the numbering.
+    let mut other = FilenameTable::new(&arena, Normalization::Lexical);
+
+    assert_eq!(other.buffer().to_string(), "<buffer 3>");
+    assert_eq!(other.repl().to_string(), "<repl 2>");
+    assert_eq!(table.buffer().to_string(), "<buffer 4>");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_shared_filename_ctx() {
+    fn make<'a, C: FilenameCtx<'a>>(mut ctx: C,
+                                    fname: &'a str) -> Filename<'a> {
+        ctx.filename(fname)
+    }
+
+    let arena = StrArena::new();
+    let cell = RefCell::new(FilenameTable::new(&arena,
+                                               Normalization::Lexical));
+    let lock = Mutex::new(StrIntern::new());
+    let a = cell.filename("src/./a.salt");
+
+    assert_eq!(make(&cell, "src/a.salt"), a);
+    assert_eq!(lock.filename("b.salt"), make(&lock, "b.salt"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_filename_display() {
+    let arena = StrArena::new();
+    let mut table = FilenameTable::new(&arena, Normalization::Lexical);
+    let opts = FilenameDisplay::new()
+        .with_cwd("/home/salt/src")
+        .with_home("/home/salt/");
+    let short = opts.clone().with_max_len(16);
+    let deep = table.filename("/opt/salt/lib/core/list.salt");
+
+    assert_eq!(FilenameDisplay::new().path(&deep),
+               "/opt/salt/lib/core/list.salt");
+    assert_eq!(table.filename("/home/salt/src/lib/a.salt")
+                   .display_with(&opts).to_string(), "lib/a.salt");
+    assert_eq!(table.filename("/home/salt/test/b.salt")
+                   .display_with(&opts).to_string(), "~/test/b.salt");
+    assert_eq!(opts.path(&deep), "/opt/salt/lib/core/list.salt");
+    assert_eq!(short.path(&deep), ".../list.salt");
+    assert_eq!(short.path(&table.filename("/a/b.salt")), "/a/b.salt");
+    assert_eq!(short.path(&table.filename("a_very_long_name.salt")),
+               "a_very_long_name.salt");
+    assert_eq!(short.path(&Filename::stdin()), "<stdin>");
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn test_canonical_in_fs() {
+    use salt::common::vfs::MemoryFs;
+
+    let fs = MemoryFs::new().with_file("src/a.salt", "");
+    let arena = StrArena::new();
+    let mut table = FilenameTable::new(&arena, Normalization::Canonical);
+
+    table.set_fs(&fs);
+
+    assert_eq!(table.filename("src/lib/../a.salt").to_string(),
+               "src/a.salt");
+    assert_eq!(table.filename("b/./c.salt").to_string(), "b/c.salt");
+}
@@ -0,0 +1,143 @@
+//! Golden tests for dumps.
+//!
+//! A golden test runs a closure that produces dump text, such as an IR
+//! or symbol table dump, and compares it with a checked-in `.expected`
+//! file.  When the dump changes intentionally, rerunning the tests with
+//! `UPDATE_EXPECT=1` in the environment rewrites the expected files
+//! instead of failing, and the new contents can be reviewed as part of
+//! the change.
+//!
+//! Dumps are produced in a session with `deterministic` set and a
+//! single thread, so that their contents do not depend on scheduling.
+
+use salt::common::output::write_atomic;
+use salt::common::session::Session;
+use salt::common::session::SessionOptions;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// The environment variable that makes golden tests update their
+/// expected files.
+pub const UPDATE_VAR: &str = "UPDATE_EXPECT";
+
+/// Create a session for producing dumps whose contents are stable.
+pub fn dump_session() -> Session {
+    let mut opts = SessionOptions::new();
+
+    opts.deterministic = true;
+    opts.threads = 1;
+
+    Session::with_options(opts)
+}
+
+/// Run `dump` in a session from `dump_session`, and check its output
+/// against the contents of `path`.  Panics with a description of the
+/// first difference if they do not match, unless `UPDATE_EXPECT=1` is
+/// set, in which case `path` is rewritten with the output.
+pub fn check_dump<P, F>(path: P, dump: F)
+    where P: AsRef<Path>,
+          F: FnOnce(&Session) -> String {
+    let actual = dump(&dump_session());
+
+    check_golden(path, &actual)
+}
+
+/// Check `actual` against the contents of `path`, as `check_dump`
+/// does.  This is for dumps that do not need a session.
+pub fn check_golden<P: AsRef<Path>>(path: P, actual: &str) {
+    // This reads the test's environment, not the compilation's, so it
+    // does not go through `EnvTracker`.
+    let update = env::var_os(UPDATE_VAR).is_some_and(|val| val == "1");
+
+    if let Err(msg) = check(path.as_ref(), actual, update) {
+        panic!("{}", msg)
+    }
+}
+
+/// Describe the first line where `expected` and `actual` differ.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(a), Some(b)) if a == b => line += 1,
+            (Some(a), Some(b)) =>
+                return format!("line {}:\n  expected: {}\n  actual:   {}",
+                               line, a, b),
+            (Some(a), None) =>
+                return format!("line {}: missing in actual output:\n  \
+                                expected: {}", line, a),
+            (None, Some(b)) =>
+                return format!("line {}: not in expected output:\n  \
+                                actual:   {}", line, b),
+            (None, None) =>
+                return String::from("trailing newlines differ")
+        }
+    }
+}
+
+fn check(path: &Path, actual: &str,
+         update: bool) -> ::std::result::Result<(), String> {
+    let expected = match fs::read_to_string(path) {
+        Ok(expected) => Some(expected.replace("\r\n", "\n")),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => None,
+        Err(err) => return Err(format!("error reading {}: {}",
+                                       path.display(), err))
+    };
+
+    if expected.as_deref() == Some(actual) {
+        Ok(())
+    } else if update {
+        write_atomic(path, actual.as_bytes())
+            .map_err(|err| format!("error writing {}: {}",
+                                   path.display(), err))
+    } else {
+        match expected {
+            Some(expected) =>
+                Err(format!("dump does not match {}, at {}\n\
+                             rerun with {}=1 to update it\n\
+                             actual output:\n{}", path.display(),
+                            first_difference(&expected, actual),
+                            UPDATE_VAR, actual)),
+            None =>
+                Err(format!("{} does not exist\n\
+                             rerun with {}=1 to create it",
+                            path.display(), UPDATE_VAR))
+        }
+    }
+}
+
+#[test]
+fn test_check_and_update() {
+    let dir = env::temp_dir()
+        .join(format!("salt-golden-test-{}", ::std::process::id()));
+    let path = dir.join("dump.expected");
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    assert!(check(&path, "a\nb\n", false).unwrap_err()
+            .contains("does not exist"));
+    assert_eq!(check(&path, "a\nb\n", true), Ok(()));
+    assert_eq!(check(&path, "a\nb\n", false), Ok(()));
+
+    let err = check(&path, "a\nc\n", false).unwrap_err();
+
+    assert!(err.contains("line 2:\n  expected: b\n  actual:   c"), "{}", err);
+    assert_eq!(check(&path, "a\nc\n", true), Ok(()));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\nc\n");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_dump_session() {
+    let sess = dump_session();
+
+    assert!(sess.options().deterministic);
+    assert_eq!(sess.options().threads, 1);
+}
@@ -0,0 +1,399 @@
+use salt::common::hash::Fingerprint;
+use salt::common::position::FilePosition;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::hash::Hash;
+
+/// A graph of dependencies between things such as source files, for
+/// finding what must be rebuilt when some of them change.  An edge
+/// from `a` to `b` records that `a` depends on `b`, optionally with the
+/// position that made it do so, such as an import.
+///
+/// Nodes are marked dirty directly, or by recording a fingerprint that
+/// differs from the last one recorded.  `rebuild_set` then gives the
+/// dirty nodes and everything depending on them, dependencies first.
+#[derive(Clone, Debug)]
+pub struct DepGraph<'a, N> {
+    nodes: Vec<GraphNode<'a, N>>,
+    index: HashMap<N, usize>
+}
+
+/// A node of a `DepGraph`, with its edges.
+#[derive(Clone, Debug)]
+struct GraphNode<'a, N> {
+    node: N,
+    /// The nodes this depends on, in the order the edges were added,
+    /// with the positions that caused them.
+    deps: Vec<(usize, Option<FilePosition<'a>>)>,
+    /// The nodes depending on this, in the order the edges were added.
+    rdeps: Vec<usize>,
+    /// The fingerprint last recorded.
+    fingerprint: Option<Fingerprint>,
+    dirty: bool
+}
+
+/// A cycle of dependencies.  Each node depends on the next, and the
+/// last on the first.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DepCycle<'a, N> {
+    /// The nodes in the cycle, with the position that caused each to
+    /// depend on the next, if one was given.
+    pub edges: Vec<(N, Option<FilePosition<'a>>)>
+}
+
+impl<'a, N: Clone + Eq + Hash> DepGraph<'a, N> {
+    /// Create an empty `DepGraph`.
+    pub fn new() -> DepGraph<'a, N> {
+        DepGraph { nodes: Vec::new(), index: HashMap::new() }
+    }
+
+    /// Add `node`, if it is not already in the graph.  Returns whether
+    /// it was added.  New nodes are dirty, as they have never been
+    /// built.
+    pub fn add_node(&mut self, node: N) -> bool {
+        if self.index.contains_key(&node) {
+            return false;
+        }
+
+        self.intern(node);
+
+        true
+    }
+
+    /// Record that `from` depends on `to`, adding them if they are not
+    /// in the graph.
+    pub fn add_edge(&mut self, from: N, to: N) {
+        self.add_edge_at(from, to, None)
+    }
+
+    /// Record that `from` depends on `to` because of what is at `pos`,
+    /// as for reporting cycles.  If the edge was added already without
+    /// a position, it is given this one.
+    pub fn add_edge_at<P>(&mut self, from: N, to: N, pos: P)
+        where P: Into<Option<FilePosition<'a>>> {
+        let (from, to) = (self.intern(from), self.intern(to));
+        let pos = pos.into();
+
+        match self.nodes[from].deps.iter_mut().find(|(dep, _)| *dep == to) {
+            Some((_, old)) => {
+                if old.is_none() {
+                    *old = pos
+                }
+            }
+            None => {
+                self.nodes[from].deps.push((to, pos));
+                self.nodes[to].rdeps.push(from)
+            }
+        }
+    }
+
+    /// Remove the edges out of `node`, as before finding its
+    /// dependencies again.
+    pub fn clear_deps(&mut self, node: &N) {
+        let idx = match self.index.get(node) {
+            Some(&idx) => idx,
+            None => return
+        };
+
+        for (dep, _) in ::std::mem::take(&mut self.nodes[idx].deps) {
+            self.nodes[dep].rdeps.retain(|&rdep| rdep != idx)
+        }
+    }
+
+    /// Check whether `node` is in the graph.
+    pub fn contains(&self, node: &N) -> bool {
+        self.index.contains_key(node)
+    }
+
+    /// Get the number of nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check whether the graph has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterate over the nodes, in the order they were added.
+    pub fn nodes(&self) -> impl Iterator<Item = &N> {
+        self.nodes.iter().map(|node| &node.node)
+    }
+
+    /// Get the nodes `node` depends on, in the order the edges were
+    /// added.
+    pub fn deps(&self, node: &N) -> impl Iterator<Item = &N> {
+        self.node(node)
+            .into_iter()
+            .flat_map(move |node| node.deps.iter())
+            .map(move |&(dep, _)| &self.nodes[dep].node)
+    }
+
+    /// Get the nodes depending on `node`, in the order the edges were
+    /// added.
+    pub fn rdeps(&self, node: &N) -> impl Iterator<Item = &N> {
+        self.node(node)
+            .into_iter()
+            .flat_map(move |node| node.rdeps.iter())
+            .map(move |&rdep| &self.nodes[rdep].node)
+    }
+
+    /// Mark `node` dirty, adding it if it is not in the graph.
+    pub fn mark_dirty(&mut self, node: N) {
+        let idx = self.intern(node);
+
+        self.nodes[idx].dirty = true
+    }
+
+    /// Record `fingerprint` as that of `node`, such as of a file's
+    /// contents, adding it if it is not in the graph.  Marks the node
+    /// dirty and returns `true` if the fingerprint differs from the one
+    /// recorded last, or none was.
+    pub fn set_fingerprint(&mut self, node: N,
+                           fingerprint: Fingerprint) -> bool {
+        let idx = self.intern(node);
+        let node = &mut self.nodes[idx];
+        let changed = node.fingerprint != Some(fingerprint);
+
+        node.fingerprint = Some(fingerprint);
+        node.dirty |= changed;
+
+        changed
+    }
+
+    /// Get the fingerprint recorded for `node`.
+    pub fn fingerprint(&self, node: &N) -> Option<Fingerprint> {
+        self.node(node)?.fingerprint
+    }
+
+    /// Check whether `node` is dirty.
+    pub fn is_dirty(&self, node: &N) -> bool {
+        self.node(node).is_some_and(|node| node.dirty)
+    }
+
+    /// Mark every node clean, as after a rebuild.
+    pub fn clear_dirty(&mut self) {
+        for node in &mut self.nodes {
+            node.dirty = false
+        }
+    }
+
+    /// Get every node, with each after those it depends on.  The order
+    /// depends only on the order nodes and edges were added, not on
+    /// hashing.  Returns a cycle if there is one.
+    pub fn topo_order(&self)
+                      -> ::std::result::Result<Vec<&N>, DepCycle<'a, N>> {
+        self.order(&vec![true; self.nodes.len()])
+    }
+
+    /// Get the nodes that must be rebuilt: the dirty nodes and every
+    /// node that depends on one, each after those it depends on.
+    /// Returns a cycle among them if there is one.
+    pub fn rebuild_set(&self)
+                       -> ::std::result::Result<Vec<&N>, DepCycle<'a, N>> {
+        let mut affected = vec![false; self.nodes.len()];
+        let mut work: Vec<usize> = (0..self.nodes.len())
+            .filter(|&idx| self.nodes[idx].dirty)
+            .collect();
+
+        while let Some(idx) = work.pop() {
+            if !affected[idx] {
+                affected[idx] = true;
+                work.extend(&self.nodes[idx].rdeps)
+            }
+        }
+
+        self.order(&affected)
+    }
+
+    /// Get the nodes for which `included` is set in topological order,
+    /// treating dependencies on other nodes as satisfied.
+    fn order(&self, included: &[bool])
+             -> ::std::result::Result<Vec<&N>, DepCycle<'a, N>> {
+        let mut waiting: Vec<usize> = self.nodes.iter()
+            .map(|node| node.deps.iter()
+                 .filter(|&&(dep, _)| included[dep])
+                 .count())
+            .collect();
+        let mut ready: Vec<usize> = (0..self.nodes.len())
+            .rev()
+            .filter(|&idx| included[idx] && waiting[idx] == 0)
+            .collect();
+        let mut out = Vec::new();
+
+        while let Some(idx) = ready.pop() {
+            out.push(&self.nodes[idx].node);
+
+            for &rdep in self.nodes[idx].rdeps.iter().rev() {
+                if included[rdep] {
+                    waiting[rdep] -= 1;
+
+                    if waiting[rdep] == 0 {
+                        ready.push(rdep)
+                    }
+                }
+            }
+        }
+
+        if out.len() == included.iter().filter(|&&inc| inc).count() {
+            Ok(out)
+        } else {
+            let blocked = (0..self.nodes.len())
+                .find(|&idx| included[idx] && waiting[idx] != 0)
+                .unwrap();
+
+            Err(self.cycle_from(blocked, |dep| included[dep] &&
+                                               waiting[dep] != 0))
+        }
+    }
+
+    /// Find a cycle by following edges from `start` to nodes satisfying
+    /// `blocked`, each of which has such an edge out of it.
+    fn cycle_from<F>(&self, start: usize, blocked: F) -> DepCycle<'a, N>
+        where F: Fn(usize) -> bool {
+        let mut path: Vec<(usize, Option<FilePosition<'a>>)> = Vec::new();
+        let mut seen = HashMap::new();
+        let mut idx = start;
+
+        while !seen.contains_key(&idx) {
+            let (dep, pos) = self.nodes[idx].deps.iter()
+                .find(|&&(dep, _)| blocked(dep))
+                .cloned()
+                .unwrap();
+
+            seen.insert(idx, path.len());
+            path.push((idx, pos));
+            idx = dep;
+        }
+
+        let edges = path.split_off(seen[&idx])
+            .into_iter()
+            .map(|(idx, pos)| (self.nodes[idx].node.clone(), pos))
+            .collect();
+
+        DepCycle { edges }
+    }
+
+    /// Get the index of `node`, adding it if it is not in the graph.
+    fn intern(&mut self, node: N) -> usize {
+        if let Some(&idx) = self.index.get(&node) {
+            return idx;
+        }
+
+        let idx = self.nodes.len();
+
+        self.index.insert(node.clone(), idx);
+        self.nodes.push(GraphNode { node, deps: Vec::new(),
+                                    rdeps: Vec::new(), fingerprint: None,
+                                    dirty: true });
+
+        idx
+    }
+
+    /// Get the node `node`, if it is in the graph.
+    fn node(&self, node: &N) -> Option<&GraphNode<'a, N>> {
+        self.index.get(node).map(|&idx| &self.nodes[idx])
+    }
+}
+
+impl<'a, N: Clone + Eq + Hash> Default for DepGraph<'a, N> {
+    fn default() -> DepGraph<'a, N> {
+        DepGraph::new()
+    }
+}
+
+impl<'a, N> DepCycle<'a, N> {
+    /// Get the nodes in the cycle, in order.
+    pub fn nodes(&self) -> impl Iterator<Item = &N> {
+        self.edges.iter().map(|(node, _)| node)
+    }
+}
+
+impl<'a, N: Display> Display for DepCycle<'a, N> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let first = match self.edges.first() {
+            Some((first, _)) => first,
+            None => return Ok(())
+        };
+
+        write!(f, "dependency cycle detected at `{}`", first)?;
+
+        for (i, (node, pos)) in self.edges.iter().enumerate() {
+            let (next, _) = &self.edges[(i + 1) % self.edges.len()];
+
+            write!(f, "\n  `{}` depends on `{}`", node, next)?;
+
+            if let Some(pos) = pos {
+                write!(f, " at {}", pos)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_rebuild_set() {
+    use salt::common::hash::StableHash;
+
+    let mut graph: DepGraph<&str> = DepGraph::new();
+
+    graph.add_edge("main", "lib");
+    graph.add_edge("main", "util");
+    graph.add_edge("lib", "util");
+    graph.add_node("docs");
+
+    assert_eq!(graph.topo_order().unwrap(),
+               [&"util", &"lib", &"main", &"docs"]);
+    assert_eq!(graph.rebuild_set().unwrap().len(), 4);
+
+    for (name, text) in [("main", "a"), ("lib", "b"), ("util", "c"),
+                         ("docs", "d")].iter() {
+        graph.set_fingerprint(name, text.fingerprint());
+    }
+
+    graph.clear_dirty();
+
+    assert!(!graph.set_fingerprint("util", "c".fingerprint()));
+    assert!(graph.rebuild_set().unwrap().is_empty());
+    assert!(graph.set_fingerprint("lib", "b2".fingerprint()));
+    assert_eq!(graph.rebuild_set().unwrap(), [&"lib", &"main"]);
+    assert_eq!(graph.deps(&"main").collect::<Vec<_>>(), [&"lib", &"util"]);
+    assert_eq!(graph.rdeps(&"util").collect::<Vec<_>>(), [&"main", &"lib"]);
+
+    graph.clear_deps(&"main");
+
+    assert_eq!(graph.rdeps(&"util").collect::<Vec<_>>(), [&"lib"]);
+}
+
+#[test]
+fn test_cycle() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::Location;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let import = FilePosition::new(names.filename("b.salt"),
+                                   Location::span((1, 1), (1, 9)));
+    let mut graph = DepGraph::new();
+
+    graph.add_edge("a", "b");
+    graph.add_edge_at("b", "c", import);
+    graph.add_edge("c", "b");
+    graph.add_edge("d", "a");
+
+    let cycle = graph.topo_order().unwrap_err();
+
+    assert_eq!(cycle.nodes().collect::<Vec<_>>(), [&"b", &"c"]);
+    assert_eq!(cycle.to_string(),
+               "dependency cycle detected at `b`\n  \
+                `b` depends on `c` at b.salt 1.1-9\n  \
+                `c` depends on `b`");
+
+    graph.clear_dirty();
+    graph.mark_dirty("d");
+
+    assert_eq!(graph.rebuild_set().unwrap(), [&"d"]);
+}
@@ -0,0 +1,621 @@
+//! Stable hashing.
+//!
+//! `std::hash::Hash` is not suitable for fingerprints that must be the
+//! same from one run of the compiler to the next: interned types hash
+//! by address, and integer hashing depends on the platform's byte
+//! order and word size.  `StableHash` hashes by content, in a fixed
+//! byte order, producing 128-bit `Fingerprint`s that can be used as
+//! incremental compilation keys.
+
+use std::boxed::Box;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+#[cfg(feature = "std")]
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+#[cfg(feature = "std")]
+use std::path::Component;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+use std::string::String;
+use std::vec::Vec;
+
+/// A 128-bit stable fingerprint.
+#[derive(Clone, Copy, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Fingerprint(pub u64, pub u64);
+
+/// A hasher producing stable 128-bit fingerprints, using SipHash-1-3
+/// with 128-bit output and a fixed key.
+#[derive(Clone, Debug)]
+pub struct StableHasher {
+    state: SipState,
+    /// Bytes not yet forming a full 8-byte word.
+    tail: [u8; 8],
+    /// The number of valid bytes in `tail`.
+    ntail: usize,
+    /// The total number of bytes hashed.
+    len: u64
+}
+
+/// Types that can be hashed stably, by content.
+pub trait StableHash {
+    /// Feed this value into `hasher`.
+    fn stable_hash(&self, hasher: &mut StableHasher);
+
+    /// Compute the fingerprint of this value.
+    fn fingerprint(&self) -> Fingerprint {
+        let mut hasher = StableHasher::new();
+
+        self.stable_hash(&mut hasher);
+
+        hasher.finish128()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SipState {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64
+}
+
+impl SipState {
+    fn new(k0: u64, k1: u64) -> SipState {
+        SipState { v0: k0 ^ 0x736f_6d65_7073_6575,
+                   v1: k1 ^ 0x646f_7261_6e64_6f6d ^ 0xee,
+                   v2: k0 ^ 0x6c79_6765_6e65_7261,
+                   v3: k1 ^ 0x7465_6462_7974_6573 }
+    }
+
+    #[inline]
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    #[inline]
+    fn compress(&mut self, m: u64, rounds: usize) {
+        self.v3 ^= m;
+
+        for _ in 0..rounds {
+            self.round();
+        }
+
+        self.v0 ^= m;
+    }
+
+    fn finish(mut self, last: u64, crounds: usize,
+              drounds: usize) -> Fingerprint {
+        self.compress(last, crounds);
+        self.v2 ^= 0xee;
+
+        for _ in 0..drounds {
+            self.round();
+        }
+
+        let lo = self.v0 ^ self.v1 ^ self.v2 ^ self.v3;
+
+        self.v1 ^= 0xdd;
+
+        for _ in 0..drounds {
+            self.round();
+        }
+
+        let hi = self.v0 ^ self.v1 ^ self.v2 ^ self.v3;
+
+        Fingerprint(lo, hi)
+    }
+}
+
+/// Compression and finalization rounds (SipHash-1-3).
+const CROUNDS: usize = 1;
+const DROUNDS: usize = 3;
+
+impl StableHasher {
+    /// Create a `StableHasher`.
+    pub fn new() -> StableHasher {
+        StableHasher { state: SipState::new(0, 0), tail: [0; 8], ntail: 0,
+                       len: 0 }
+    }
+
+    /// Hash raw bytes.
+    pub fn write_bytes(&mut self, mut bytes: &[u8]) {
+        self.len = self.len.wrapping_add(bytes.len() as u64);
+
+        if self.ntail != 0 {
+            let take = (8 - self.ntail).min(bytes.len());
+
+            self.tail[self.ntail..self.ntail + take]
+                .copy_from_slice(&bytes[..take]);
+            self.ntail += take;
+            bytes = &bytes[take..];
+
+            if self.ntail < 8 {
+                return;
+            }
+
+            self.state.compress(u64::from_le_bytes(self.tail), CROUNDS);
+            self.ntail = 0;
+        }
+
+        let mut chunks = bytes.chunks_exact(8);
+
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+
+            word.copy_from_slice(chunk);
+            self.state.compress(u64::from_le_bytes(word), CROUNDS);
+        }
+
+        let rest = chunks.remainder();
+
+        self.tail[..rest.len()].copy_from_slice(rest);
+        self.ntail = rest.len();
+    }
+
+    /// Hash a string, including its length so that adjacent strings
+    /// cannot run together.
+    pub fn write_str(&mut self, s: &str) {
+        self.write_u64(s.len() as u64);
+        self.write_bytes(s.as_bytes());
+    }
+
+    /// Compute the fingerprint of everything hashed so far.
+    pub fn finish128(&self) -> Fingerprint {
+        let mut last = [0; 8];
+
+        last[..self.ntail].copy_from_slice(&self.tail[..self.ntail]);
+        last[7] = self.len as u8;
+
+        self.state.finish(u64::from_le_bytes(last), CROUNDS, DROUNDS)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> StableHasher {
+        StableHasher::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.finish128().0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.write_bytes(bytes)
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.write_bytes(&[n])
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// `usize` is always hashed as 64 bits, so that fingerprints do not
+    /// depend on the platform's word size.
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64)
+    }
+
+    fn write_i8(&mut self, n: i8) {
+        self.write_u8(n as u8)
+    }
+
+    fn write_i16(&mut self, n: i16) {
+        self.write_u16(n as u16)
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u32(n as u32)
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.write_u64(n as u64)
+    }
+
+    fn write_i128(&mut self, n: i128) {
+        self.write_u128(n as u128)
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        self.write_i64(n as i64)
+    }
+}
+
+impl Fingerprint {
+    /// The all-zero fingerprint.
+    pub const ZERO: Fingerprint = Fingerprint(0, 0);
+
+    /// Combine two fingerprints, in an order-dependent way.
+    pub fn combine(self, other: Fingerprint) -> Fingerprint {
+        let mut hasher = StableHasher::new();
+
+        self.stable_hash(&mut hasher);
+        other.stable_hash(&mut hasher);
+
+        hasher.finish128()
+    }
+
+    /// Get the fingerprint as a 128-bit integer.
+    pub fn as_u128(&self) -> u128 {
+        (u128::from(self.1) << 64) | u128::from(self.0)
+    }
+
+    /// Get the low 64 bits of the fingerprint.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    /// Get the fingerprint of the bytes `bytes`, such as the contents
+    /// of a file.  Unlike the fingerprint of a slice, this hashes the
+    /// bytes as a whole, rather than one at a time.
+    pub fn of_bytes(bytes: &[u8]) -> Fingerprint {
+        let mut hasher = StableHasher::new();
+
+        hasher.write_bytes(bytes);
+
+        hasher.finish128()
+    }
+}
+
+impl Debug for Fingerprint {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "Fingerprint({})", self)
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:016x}{:016x}", self.1, self.0)
+    }
+}
+
+macro_rules! stable_hash_int {
+    ($($ty:ty, $write:ident);*) => {
+        $(impl StableHash for $ty {
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                hasher.$write(*self)
+            }
+        })*
+    }
+}
+
+stable_hash_int!(u8, write_u8; u16, write_u16; u32, write_u32;
+                 u64, write_u64; u128, write_u128; usize, write_usize;
+                 i8, write_i8; i16, write_i16; i32, write_i32;
+                 i64, write_i64; i128, write_i128; isize, write_isize);
+
+impl StableHash for bool {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u8(*self as u8)
+    }
+}
+
+impl StableHash for char {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u32(*self as u32)
+    }
+}
+
+impl StableHash for () {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl StableHash for str {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self)
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self)
+    }
+}
+
+impl StableHash for Fingerprint {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(self.0);
+        hasher.write_u64(self.1);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher)
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Box<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher)
+    }
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            None => hasher.write_u8(0),
+            Some(val) => {
+                hasher.write_u8(1);
+                val.stable_hash(hasher)
+            }
+        }
+    }
+}
+
+impl<T: StableHash, E: StableHash> StableHash for ::std::result::Result<T, E> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Ok(val) => {
+                hasher.write_u8(0);
+                val.stable_hash(hasher)
+            },
+            Err(err) => {
+                hasher.write_u8(1);
+                err.stable_hash(hasher)
+            }
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_usize(self.len());
+
+        for elem in self {
+            elem.stable_hash(hasher)
+        }
+    }
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self[..].stable_hash(hasher)
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self[..].stable_hash(hasher)
+    }
+}
+
+impl<A: StableHash, B: StableHash> StableHash for (A, B) {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.0.stable_hash(hasher);
+        self.1.stable_hash(hasher);
+    }
+}
+
+impl<A: StableHash, B: StableHash, C: StableHash> StableHash for (A, B, C) {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.0.stable_hash(hasher);
+        self.1.stable_hash(hasher);
+        self.2.stable_hash(hasher);
+    }
+}
+
+impl<K: StableHash, V: StableHash> StableHash for BTreeMap<K, V> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_usize(self.len());
+
+        for (key, val) in self {
+            key.stable_hash(hasher);
+            val.stable_hash(hasher);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for BTreeSet<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_usize(self.len());
+
+        for elem in self {
+            elem.stable_hash(hasher)
+        }
+    }
+}
+
+/// Hash an unordered collection, by hashing the sorted fingerprints
+/// of its elements.
+#[cfg(feature = "std")]
+fn stable_hash_unordered<I>(len: usize, iter: I, hasher: &mut StableHasher)
+    where I: Iterator<Item = Fingerprint> {
+    let mut prints: Vec<Fingerprint> = iter.collect();
+
+    prints.sort();
+    hasher.write_usize(len);
+
+    for print in prints {
+        print.stable_hash(hasher)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> StableHash for HashMap<K, V, S>
+    where K: StableHash,
+          V: StableHash,
+          S: BuildHasher {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        stable_hash_unordered(self.len(),
+                              self.iter().map(|ent| ent.fingerprint()),
+                              hasher)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: StableHash, S: BuildHasher> StableHash for HashSet<T, S> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        stable_hash_unordered(self.len(),
+                              self.iter().map(|ent| ent.fingerprint()),
+                              hasher)
+    }
+}
+
+/// Paths are hashed by component, so that the separator, and repeated
+/// separators, do not matter.  Components that are not Unicode are
+/// hashed lossily.
+#[cfg(feature = "std")]
+impl StableHash for Path {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_usize(self.components().count());
+
+        for component in self.components() {
+            match component {
+                Component::Prefix(prefix) =>
+                    (*prefix.as_os_str().to_string_lossy()).stable_hash(hasher),
+                Component::RootDir => "/".stable_hash(hasher),
+                Component::CurDir => ".".stable_hash(hasher),
+                Component::ParentDir => "..".stable_hash(hasher),
+                Component::Normal(name) =>
+                    (*name.to_string_lossy()).stable_hash(hasher)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl StableHash for PathBuf {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_path().stable_hash(hasher)
+    }
+}
+
+#[cfg(test)]
+fn siphash24_128(key: &[u8; 16], msg: &[u8]) -> Fingerprint {
+    let mut k0 = [0; 8];
+    let mut k1 = [0; 8];
+
+    k0.copy_from_slice(&key[..8]);
+    k1.copy_from_slice(&key[8..]);
+
+    let mut hasher = StableHasher::new();
+
+    hasher.state = SipState::new(u64::from_le_bytes(k0),
+                                 u64::from_le_bytes(k1));
+
+    let mut chunks = msg.chunks_exact(8);
+
+    for chunk in &mut chunks {
+        let mut word = [0; 8];
+
+        word.copy_from_slice(chunk);
+        hasher.state.compress(u64::from_le_bytes(word), 2);
+    }
+
+    let rest = chunks.remainder();
+    let mut last = [0; 8];
+
+    last[..rest.len()].copy_from_slice(rest);
+    last[7] = msg.len() as u8;
+
+    hasher.state.finish(u64::from_le_bytes(last), 2, 4)
+}
+
+#[test]
+fn test_siphash_reference_vectors() {
+    let mut key = [0u8; 16];
+
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+
+    // Vectors from the SipHash reference implementation (vectors_sip128).
+    assert_eq!(siphash24_128(&key, &[]).as_u128().to_le_bytes(),
+               [0xa3, 0x81, 0x7f, 0x04, 0xba, 0x25, 0xa8, 0xe6,
+                0x6d, 0xf6, 0x72, 0x14, 0xc7, 0x55, 0x02, 0x93]);
+    assert_eq!(siphash24_128(&key, &[0]).as_u128().to_le_bytes(),
+               [0xda, 0x87, 0xc1, 0xd8, 0x6b, 0x99, 0xaf, 0x44,
+                0x34, 0x76, 0x59, 0x11, 0x9b, 0x22, 0xfc, 0x45]);
+}
+
+#[test]
+fn test_streaming_matches_oneshot() {
+    let data: Vec<u8> = (0..100).collect();
+    let mut a = StableHasher::new();
+    let mut b = StableHasher::new();
+
+    a.write_bytes(&data);
+    b.write_bytes(&data[..3]);
+    b.write_bytes(&data[3..20]);
+    b.write_bytes(&data[20..]);
+
+    assert_eq!(a.finish128(), b.finish128());
+}
+
+#[test]
+fn test_fingerprint_distinguishes() {
+    assert_ne!(("ab", "c").fingerprint(), ("a", "bc").fingerprint());
+    assert_ne!(Some(0u32).fingerprint(), None::<u32>.fingerprint());
+    assert_eq!(String::from("x").fingerprint(), "x".fingerprint());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_unordered_collections() {
+    let mut a = HashSet::new();
+    let mut b = HashSet::new();
+
+    for i in 0..50u32 {
+        a.insert(i);
+        b.insert(49 - i);
+    }
+
+    assert_eq!(a.fingerprint(), b.fingerprint());
+}
+
+#[test]
+fn test_fingerprint_display() {
+    assert_eq!(format!("{}", Fingerprint(1, 2)),
+               "00000000000000020000000000000001");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_paths_and_bytes() {
+    assert_eq!(Path::new("src//lib/./a.salt").fingerprint(),
+               PathBuf::from("src/lib/a.salt").fingerprint());
+    assert_ne!(Path::new("/src/a.salt").fingerprint(),
+               Path::new("src/a.salt").fingerprint());
+    assert_eq!([1u8, 2].fingerprint(), vec![1u8, 2].fingerprint());
+    assert_ne!(Fingerprint::of_bytes(b"ab"), Fingerprint::of_bytes(b"ba"));
+}
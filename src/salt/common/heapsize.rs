@@ -0,0 +1,238 @@
+//! Heap usage by subsystem.
+//!
+//! Unlike the `alloc-stats` allocator, which counts every allocation
+//! in the process, this attributes heap usage to the data structures
+//! that hold it.  Types report the bytes they own through `HeapSize`,
+//! and a tool collects the sizes of its long-lived structures (the
+//! interner, source files, position tables, arenas) into a
+//! `HeapReport` when asked, for instance by a language server
+//! publishing memory metrics.  Nothing is counted until a report is
+//! made, so there is no cost otherwise.
+//!
+//! Sizes are estimates: they count the capacity of containers, but
+//! not allocator overhead, and hash table sizes are computed from the
+//! standard library's current layout.
+
+#[cfg(feature = "std")]
+use salt::common::json::Json;
+#[cfg(feature = "std")]
+use salt::common::json::ToJson;
+use std::boxed::Box;
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::mem::size_of;
+use std::mem::size_of_val;
+use std::string::String;
+use std::string::ToString;
+use std::vec::Vec;
+
+/// Types that can report the heap memory they own.
+pub trait HeapSize {
+    /// Get the number of bytes of heap memory owned by this value,
+    /// not counting the value itself.  Memory that is only borrowed is
+    /// not counted.
+    fn heap_size(&self) -> usize;
+}
+
+/// Heap usage of a program's subsystems.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HeapReport {
+    /// Bytes held by each subsystem, in the order they were added.
+    subsystems: Vec<(String, usize)>
+}
+
+/// Estimate the bytes allocated for a hash table with `capacity`
+/// entries of type `T`: a power-of-two number of buckets at most 7/8
+/// full, each with one control byte.
+pub fn hash_table_size<T>(capacity: usize) -> usize {
+    if capacity == 0 {
+        0
+    } else {
+        let buckets = (capacity * 8 / 7).next_power_of_two();
+
+        buckets * (size_of::<T>() + 1)
+    }
+}
+
+macro_rules! impl_heap_size_zero {
+    ($($ty:ty),*) => {
+        $(impl HeapSize for $ty {
+            #[inline]
+            fn heap_size(&self) -> usize {
+                0
+            }
+        })*
+    }
+}
+
+impl_heap_size_zero!(bool, char, u8, u16, u32, u64, u128, usize,
+                     i8, i16, i32, i64, i128, isize, f32, f64, ());
+
+impl<T: ?Sized> HeapSize for &T {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize + ?Sized> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        size_of_val(&**self) + (**self).heap_size()
+    }
+}
+
+impl HeapSize for str {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl<T: HeapSize> HeapSize for [T] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(HeapSize::heap_size).sum()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self[..].heap_size()
+    }
+}
+
+impl<A: HeapSize, B: HeapSize> HeapSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: HeapSize, V: HeapSize, S> HeapSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        hash_table_size::<(K, V)>(self.capacity()) +
+        self.iter().map(|(k, v)| k.heap_size() + v.heap_size()).sum::<usize>()
+    }
+}
+
+/// B-tree nodes are counted as if they were exactly full, which
+/// underestimates by up to half.
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.len() * size_of::<(K, V)>() +
+        self.iter().map(|(k, v)| k.heap_size() + v.heap_size()).sum::<usize>()
+    }
+}
+
+impl HeapReport {
+    /// Create an empty report.
+    pub fn new() -> HeapReport {
+        HeapReport { subsystems: Vec::new() }
+    }
+
+    /// Record `value`'s heap usage under `subsystem`.  Recording the
+    /// same subsystem again adds to its total.
+    pub fn add<T: HeapSize + ?Sized>(&mut self, subsystem: &str, value: &T) {
+        self.add_bytes(subsystem, value.heap_size())
+    }
+
+    /// Record `bytes` under `subsystem`, for structures that count
+    /// their own usage, such as arenas.
+    pub fn add_bytes(&mut self, subsystem: &str, bytes: usize) {
+        match self.subsystems.iter_mut().find(|(name, _)| name == subsystem) {
+            Some((_, total)) => *total += bytes,
+            None => self.subsystems.push((subsystem.to_string(), bytes))
+        }
+    }
+
+    /// Get the bytes recorded for `subsystem`, if any were.
+    pub fn get(&self, subsystem: &str) -> Option<usize> {
+        self.subsystems.iter()
+            .find(|(name, _)| name == subsystem)
+            .map(|&(_, bytes)| bytes)
+    }
+
+    /// Get the recorded subsystems and their usage, in the order they
+    /// were first added.
+    pub fn subsystems(&self) -> &[(String, usize)] {
+        &self.subsystems
+    }
+
+    /// Get the total usage of all subsystems.
+    pub fn total(&self) -> usize {
+        self.subsystems.iter().map(|&(_, bytes)| bytes).sum()
+    }
+}
+
+/// Reports are written as `{"total":N,"subsystems":{"name":N,...}}`.
+#[cfg(feature = "std")]
+impl ToJson for HeapReport {
+    fn to_json(&self) -> Json {
+        let subsystems = self.subsystems.iter()
+            .map(|(name, bytes)| (name.clone(), Json::UInt(*bytes as u64)))
+            .collect();
+
+        Json::object()
+            .with("total", self.total() as u64)
+            .with("subsystems", Json::Object(subsystems))
+    }
+}
+
+impl Display for HeapReport {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let width = self.subsystems.iter().map(|(name, _)| name.len()).max()
+            .unwrap_or(0).max(9);
+
+        writeln!(f, "{:width$}  {:>12}", "subsystem", "bytes", width = width)?;
+
+        for (name, bytes) in &self.subsystems {
+            writeln!(f, "{:width$}  {:>12}", name, bytes, width = width)?;
+        }
+
+        writeln!(f, "{:width$}  {:>12}", "total", self.total(), width = width)
+    }
+}
+
+#[test]
+fn test_heap_size() {
+    let mut v: Vec<String> = Vec::with_capacity(4);
+
+    v.push(String::with_capacity(10));
+
+    assert_eq!(v.heap_size(), 4 * size_of::<String>() + 10);
+    assert_eq!(Box::new(7u64).heap_size(), 8);
+    assert_eq!(Some(String::from("abc")).heap_size(), 3);
+    assert_eq!("borrowed".heap_size(), 0);
+    assert_eq!(hash_table_size::<u64>(0), 0);
+    assert_eq!(hash_table_size::<u64>(14), 16 * 9);
+}
+
+#[test]
+fn test_report() {
+    let mut report = HeapReport::new();
+
+    report.add("interner", &vec![0u32; 4]);
+    report.add_bytes("arenas", 100);
+    report.add_bytes("interner", 4);
+
+    assert_eq!(report.get("interner"), Some(20));
+    assert_eq!(report.get("positions"), None);
+    assert_eq!(report.total(), 120);
+    #[cfg(feature = "std")]
+    assert_eq!(report.to_json().to_string(),
+               "{\"total\":120,\"subsystems\":\
+                {\"interner\":20,\"arenas\":100}}");
+}
@@ -0,0 +1,509 @@
+//! Message protocol for out-of-process plugins.
+//!
+//! Messages are sent as frames: a 4-byte little-endian length followed
+//! by the message in the cache encoding (see `salt::common::cache`), so
+//! positions and symbols in a message are sent with their strings and
+//! re-interned by the receiver.
+//!
+//! A connection starts with each side sending `Hello` with its protocol
+//! version; `IpcChannel::connect` and `IpcChannel::accept` perform this
+//! handshake and reject peers speaking a different version.  The
+//! diagnostic type carried by `IpcMessage::Diagnostic` is a parameter,
+//! so that the protocol does not fix how diagnostics are represented;
+//! plugins will usually send `Message<FilePosition>`.
+
+use salt::common::cache::DecodeError;
+use salt::common::cache::DecodeResult;
+use salt::common::cache::Decodable;
+use salt::common::cache::Decoder;
+use salt::common::cache::Encodable;
+use salt::common::cache::Encoder;
+use salt::common::cache::decode;
+use salt::common::cache::encode;
+use salt::common::str::intern::StrIntern;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::marker::PhantomData;
+
+/// The protocol version.  Bump this whenever the encoding of any
+/// message changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The largest frame that will be accepted, to guard against
+/// allocating huge buffers because of a corrupt length.
+pub const MAX_FRAME: usize = 64 << 20;
+
+/// A protocol message.  `D` is the diagnostic type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpcMessage<D> {
+    /// Opens a connection.
+    Hello {
+        /// The sender's protocol version.
+        version: u32,
+        /// The name of the sender, for messages.
+        name: String
+    },
+    /// Asks the peer to perform `method`.
+    Request {
+        /// Identifies the request, to match it with its response.
+        id: u64,
+        /// The operation to perform.
+        method: String,
+        /// The encoded arguments.
+        body: Vec<u8>
+    },
+    /// Answers a request.
+    Response {
+        /// The `id` of the request.
+        id: u64,
+        /// The encoded result.
+        body: Vec<u8>
+    },
+    /// Reports a diagnostic.
+    Diagnostic(D),
+    /// Closes the connection.
+    Shutdown
+}
+
+/// Errors that can occur in the protocol.
+#[derive(Debug)]
+pub enum IpcError {
+    /// An IO error.
+    IO(io::Error),
+    /// A message could not be decoded.
+    Decode(DecodeError),
+    /// A frame was larger than `MAX_FRAME`.
+    FrameTooLarge(usize),
+    /// The peer speaks a different protocol version.
+    VersionMismatch {
+        /// Our version.
+        ours: u32,
+        /// The peer's version.
+        theirs: u32
+    },
+    /// The peer sent a message that was not expected.
+    Unexpected(&'static str)
+}
+
+/// Result type for protocol operations.
+pub type IpcResult<T> = ::std::result::Result<T, IpcError>;
+
+/// One end of a connection, sending on `W` and receiving on `R`.
+pub struct IpcChannel<R, W, D> {
+    /// The receiving side.
+    reader: R,
+    /// The sending side.
+    writer: W,
+    /// The diagnostic type.
+    diag: PhantomData<D>
+}
+
+impl<D> IpcMessage<D> {
+    /// Create a request with `args` encoded as its body.
+    pub fn request<T: Encodable + ?Sized>(id: u64, method: &str,
+                                          args: &T) -> IpcMessage<D> {
+        IpcMessage::Request { id, method: method.to_string(),
+                              body: encode(args) }
+    }
+
+    /// Create a response with `result` encoded as its body.
+    pub fn response<T: Encodable + ?Sized>(id: u64,
+                                           result: &T) -> IpcMessage<D> {
+        IpcMessage::Response { id, body: encode(result) }
+    }
+}
+
+/// Decode the body of a request or response, interning strings into
+/// `intern`.
+pub fn decode_body<'d, T: Decodable<'d>>(body: &'d [u8],
+                                         intern: &mut StrIntern<'d>)
+                                         -> IpcResult<T> {
+    decode(body, intern).map_err(IpcError::Decode)
+}
+
+impl<D: Encodable> Encodable for IpcMessage<D> {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            IpcMessage::Hello { version, name } => {
+                enc.emit_u8(0);
+                version.encode(enc);
+                name.encode(enc);
+            },
+            IpcMessage::Request { id, method, body } => {
+                enc.emit_u8(1);
+                id.encode(enc);
+                method.encode(enc);
+                enc.emit_bytes(body);
+            },
+            IpcMessage::Response { id, body } => {
+                enc.emit_u8(2);
+                id.encode(enc);
+                enc.emit_bytes(body);
+            },
+            IpcMessage::Diagnostic(diag) => {
+                enc.emit_u8(3);
+                diag.encode(enc);
+            },
+            IpcMessage::Shutdown => enc.emit_u8(4)
+        }
+    }
+}
+
+impl<'d, D: Decodable<'d>> Decodable<'d> for IpcMessage<D> {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<IpcMessage<D>> {
+        match dec.read_u8()? {
+            0 => Ok(IpcMessage::Hello { version: u32::decode(dec)?,
+                                        name: String::decode(dec)? }),
+            1 => Ok(IpcMessage::Request {
+                id: u64::decode(dec)?, method: String::decode(dec)?,
+                body: dec.read_bytes()?.to_vec()
+            }),
+            2 => Ok(IpcMessage::Response {
+                id: u64::decode(dec)?, body: dec.read_bytes()?.to_vec()
+            }),
+            3 => D::decode(dec).map(IpcMessage::Diagnostic),
+            4 => Ok(IpcMessage::Shutdown),
+            tag => Err(DecodeError::BadTag { what: "IpcMessage",
+                                             tag: tag as u64 })
+        }
+    }
+}
+
+/// Write one frame containing `payload`.
+pub fn write_frame<W: Write>(out: &mut W, payload: &[u8]) -> IpcResult<()> {
+    if payload.len() > MAX_FRAME {
+        return Err(IpcError::FrameTooLarge(payload.len()));
+    }
+
+    out.write_all(&(payload.len() as u32).to_le_bytes())?;
+    out.write_all(payload)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Read one frame.  This is `None` if the stream ends cleanly before
+/// the frame starts.
+pub fn read_frame<R: Read>(input: &mut R) -> IpcResult<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    let mut got = 0;
+
+    while got < len.len() {
+        match input.read(&mut len[got..]) {
+            Ok(0) if got == 0 => return Ok(None),
+            Ok(0) => return Err(IpcError::IO(ErrorKind::UnexpectedEof.into())),
+            Ok(n) => got += n,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(IpcError::IO(err))
+        }
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+
+    if len > MAX_FRAME {
+        return Err(IpcError::FrameTooLarge(len));
+    }
+
+    let mut payload = vec![0; len];
+
+    input.read_exact(&mut payload)?;
+
+    Ok(Some(payload))
+}
+
+impl<R: Read, W: Write, D: Encodable> IpcChannel<R, W, D> {
+    /// Create a channel over `reader` and `writer`, without a
+    /// handshake.
+    pub fn new(reader: R, writer: W) -> IpcChannel<R, W, D> {
+        IpcChannel { reader, writer, diag: PhantomData }
+    }
+
+    /// Send `msg`.
+    pub fn send(&mut self, msg: &IpcMessage<D>) -> IpcResult<()> {
+        write_frame(&mut self.writer, &encode(msg))
+    }
+
+    /// Receive the next frame, to be decoded with `decode_message`.
+    /// This is `None` once the peer has closed the connection.
+    pub fn recv_frame(&mut self) -> IpcResult<Option<Vec<u8>>> {
+        read_frame(&mut self.reader)
+    }
+
+    /// Exchange `Hello` messages, sending ours first.
+    fn handshake(&mut self, name: &str, send_first: bool)
+                 -> IpcResult<String> {
+        let hello = IpcMessage::Hello { version: PROTOCOL_VERSION,
+                                        name: name.to_string() };
+
+        if send_first {
+            self.send(&hello)?;
+        }
+
+        let frame = match self.recv_frame()? {
+            Some(frame) => frame,
+            None => return Err(IpcError::Unexpected("end of stream"))
+        };
+        let mut intern = StrIntern::new();
+        // Only a `Hello` is expected, so no diagnostic need be decoded.
+        let peer = match decode_message::<()>(&frame, &mut intern)? {
+            IpcMessage::Hello { version, name } => {
+                if version != PROTOCOL_VERSION {
+                    return Err(IpcError::VersionMismatch {
+                        ours: PROTOCOL_VERSION, theirs: version
+                    });
+                }
+
+                name
+            },
+            _ => return Err(IpcError::Unexpected("message before Hello"))
+        };
+
+        if !send_first {
+            self.send(&hello)?;
+        }
+
+        Ok(peer)
+    }
+
+    /// Open a connection as the client, returning the server's name.
+    pub fn connect(&mut self, name: &str) -> IpcResult<String> {
+        self.handshake(name, true)
+    }
+
+    /// Accept a connection as the server, returning the client's name.
+    pub fn accept(&mut self, name: &str) -> IpcResult<String> {
+        self.handshake(name, false)
+    }
+
+    /// Send `Shutdown` and return the underlying streams.
+    pub fn shutdown(mut self) -> IpcResult<(R, W)> {
+        self.send(&IpcMessage::Shutdown)?;
+
+        Ok((self.reader, self.writer))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl<D: Encodable> IpcChannel<::std::process::ChildStdout,
+                              ::std::process::ChildStdin, D> {
+    /// Spawn a plugin process, with a channel over its standard input
+    /// and output.
+    pub fn spawn(cmd: &mut ::std::process::Command)
+                 -> IpcResult<(::std::process::Child,
+                               IpcChannel<::std::process::ChildStdout,
+                                          ::std::process::ChildStdin, D>)> {
+        use std::process::Stdio;
+
+        let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take()
+            .ok_or(IpcError::Unexpected("child has no stdin"))?;
+        let stdout = child.stdout.take()
+            .ok_or(IpcError::Unexpected("child has no stdout"))?;
+
+        Ok((child, IpcChannel::new(stdout, stdin)))
+    }
+}
+
+impl<D: Encodable> IpcChannel<io::Stdin, io::Stdout, D> {
+    /// Create the plugin's end of a channel, over its standard input
+    /// and output.
+    pub fn stdio() -> IpcChannel<io::Stdin, io::Stdout, D> {
+        IpcChannel::new(io::stdin(), io::stdout())
+    }
+}
+
+/// Decode a message from a frame, interning strings into `intern`.
+pub fn decode_message<'d, D: Decodable<'d>>(frame: &'d [u8],
+                                            intern: &mut StrIntern<'d>)
+                                            -> IpcResult<IpcMessage<D>> {
+    decode(frame, intern).map_err(IpcError::Decode)
+}
+
+impl Display for IpcError {
+    fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
+        match self {
+            IpcError::IO(err) => write!(f, "{}", err),
+            IpcError::Decode(err) => write!(f, "malformed message: {}", err),
+            IpcError::FrameTooLarge(len) =>
+                write!(f, "frame of {} bytes exceeds the limit of {}", len,
+                       MAX_FRAME),
+            IpcError::VersionMismatch { ours, theirs } =>
+                write!(f, "peer speaks protocol version {}, expected {}",
+                       theirs, ours),
+            IpcError::Unexpected(what) => write!(f, "unexpected {}", what)
+        }
+    }
+}
+
+impl From<io::Error> for IpcError {
+    fn from(err: io::Error) -> IpcError {
+        IpcError::IO(err)
+    }
+}
+
+#[test]
+fn test_messages_round_trip() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Location;
+    use salt::common::position::Point;
+
+    let mut names = StrIntern::new();
+    let pos = FilePosition { filename: names.filename("plugin.salt"),
+                             loc: Location::Point {
+                                 point: Point { line: 1, col: 4 }
+                             } };
+    let mut out = Vec::new();
+    let msgs: Vec<IpcMessage<String>> = vec![
+        IpcMessage::request(7, "check", &vec![pos.clone()]),
+        IpcMessage::Diagnostic(String::from("unused import")),
+        IpcMessage::Shutdown
+    ];
+
+    for msg in &msgs {
+        write_frame(&mut out, &encode(msg)).unwrap();
+    }
+
+    let mut input = &out[..];
+    let mut frames = Vec::new();
+
+    while let Some(frame) = read_frame(&mut input).unwrap() {
+        frames.push(frame);
+    }
+
+    assert_eq!(frames.len(), 3);
+
+    let mut intern = StrIntern::new();
+
+    for (frame, msg) in frames.iter().zip(msgs.iter()) {
+        assert_eq!(&decode_message::<String>(frame, &mut intern).unwrap(), msg);
+    }
+
+    let mut intern = StrIntern::new();
+
+    match decode_message::<String>(&frames[0], &mut StrIntern::new()).unwrap() {
+        IpcMessage::Request { body, .. } => {
+            let args: Vec<FilePosition> = decode_body(&body, &mut intern)
+                .unwrap();
+
+            assert_eq!(args[0].filename.to_string(), "plugin.salt");
+        },
+        _ => panic!("expected a request")
+    }
+}
+
+#[test]
+fn test_handshake() {
+    use std::thread;
+
+    let (client_read, server_write) = io::pipe().unwrap();
+    let (server_read, client_write) = io::pipe().unwrap();
+    let server = thread::spawn(move || {
+        let mut chan: IpcChannel<_, _, String> =
+            IpcChannel::new(server_read, server_write);
+
+        chan.accept("server").unwrap()
+    });
+    let mut chan: IpcChannel<_, _, String> =
+        IpcChannel::new(client_read, client_write);
+
+    assert_eq!(chan.connect("client").unwrap(), "server");
+    assert_eq!(server.join().unwrap(), "client");
+}
+
+#[test]
+fn test_message_diagnostic() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::message::Applicability;
+    use salt::common::message::Message;
+    use salt::common::message::Suggestion;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Location;
+    use salt::common::position::Point;
+    use std::thread;
+
+    type Diag<'a> = Message<FilePosition<'a>>;
+
+    fn diagnostic<'a>(names: &mut StrIntern<'a>) -> Diag<'a> {
+        let point = Point { line: 3, col: 9 };
+        let pos = FilePosition { filename: names.filename("plugin.salt"),
+                                 loc: Location::Point { point } };
+
+        Message::warning("unused variable `x`")
+            .with_code("W0012")
+            .with_label(pos.clone(), "never read")
+            .with_position(pos)
+            .with_note("variables starting with `_` are not reported")
+            .with_suggestion(Suggestion::new(
+                "prefix it with `_`", Location::Point { point }, "_",
+                Applicability::MaybeIncorrect))
+    }
+
+    let (client_read, server_write) = io::pipe().unwrap();
+    let (server_read, client_write) = io::pipe().unwrap();
+    let plugin = thread::spawn(move || {
+        let mut chan: IpcChannel<_, _, Diag> =
+            IpcChannel::new(client_read, client_write);
+
+        assert_eq!(chan.connect("plugin").unwrap(), "host");
+        chan.send(&IpcMessage::Diagnostic(diagnostic(&mut StrIntern::new())))
+            .unwrap();
+        chan.shutdown().unwrap();
+    });
+    let mut chan: IpcChannel<_, _, Diag> =
+        IpcChannel::new(server_read, server_write);
+
+    assert_eq!(chan.accept("host").unwrap(), "plugin");
+
+    let frame = chan.recv_frame().unwrap().unwrap();
+    let mut intern = StrIntern::new();
+    let msg = match decode_message::<Diag>(&frame, &mut intern).unwrap() {
+        IpcMessage::Diagnostic(msg) => msg,
+        msg => panic!("expected a diagnostic, got {:?}", msg)
+    };
+
+    plugin.join().unwrap();
+    assert_eq!(msg, diagnostic(&mut intern));
+    assert_eq!(msg.primary().unwrap().filename.to_string(), "plugin.salt");
+    assert_eq!(msg.label(0), Some("never read"));
+
+    let frame = chan.recv_frame().unwrap().unwrap();
+
+    assert_eq!(decode_message::<Diag>(&frame, &mut StrIntern::new())
+               .unwrap(), IpcMessage::Shutdown);
+    assert_eq!(chan.recv_frame().unwrap(), None);
+}
+
+#[test]
+fn test_version_mismatch() {
+    let hello: IpcMessage<String> =
+        IpcMessage::Hello { version: PROTOCOL_VERSION + 1,
+                            name: String::from("future") };
+    let mut input = Vec::new();
+
+    write_frame(&mut input, &encode(&hello)).unwrap();
+
+    let mut chan: IpcChannel<_, _, String> =
+        IpcChannel::new(&input[..], Vec::new());
+
+    match chan.accept("server") {
+        Err(IpcError::VersionMismatch { theirs, .. }) =>
+            assert_eq!(theirs, PROTOCOL_VERSION + 1),
+        res => panic!("expected version mismatch, got {:?}", res)
+    }
+}
+
+#[test]
+fn test_frame_too_large() {
+    let mut input = &((MAX_FRAME + 1) as u32).to_le_bytes()[..];
+
+    match read_frame(&mut input) {
+        Err(IpcError::FrameTooLarge(_)) => (),
+        res => panic!("expected frame too large, got {:?}", res)
+    }
+}
@@ -0,0 +1,210 @@
+//! JSON output.
+//!
+//! Machine-readable output, such as artifact manifests, is built as a
+//! `Json` value and written with `Display`, which produces compact
+//! JSON on a single line.  Object members keep the order in which
+//! they were added, so output is deterministic.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// A JSON value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    /// `null`.
+    Null,
+    /// `true` or `false`.
+    Bool(bool),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned integer.
+    UInt(u64),
+    /// A floating point number.  Non-finite values are written as
+    /// `null`, as JSON cannot represent them.
+    Float(f64),
+    /// A string.
+    String(String),
+    /// An array.
+    Array(Vec<Json>),
+    /// An object, with members in order.
+    Object(Vec<(String, Json)>)
+}
+
+/// Types that can be converted to JSON.
+pub trait ToJson {
+    /// Convert this value to JSON.
+    fn to_json(&self) -> Json;
+}
+
+impl Json {
+    /// Create an empty object.
+    pub fn object() -> Json {
+        Json::Object(Vec::new())
+    }
+
+    /// Add a member to an object, returning the object.  This is
+    /// intended for building objects in a single expression.  Panics
+    /// if `self` is not an object.
+    pub fn with<K: Into<String>, V: ToJson>(mut self, key: K,
+                                            value: V) -> Json {
+        match self {
+            Json::Object(ref mut members) =>
+                members.push((key.into(), value.to_json())),
+            _ => panic!("adding a member to a JSON value that is not an object")
+        }
+
+        self
+    }
+}
+
+/// Write `s` as a JSON string literal.
+pub fn write_str(f: &mut Formatter, s: &str) -> Result {
+    write!(f, "\"")?;
+
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+
+    write!(f, "\"")
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Int(n) => write!(f, "{}", n),
+            Json::UInt(n) => write!(f, "{}", n),
+            Json::Float(n) if n.is_finite() => write!(f, "{:?}", n),
+            Json::Float(_) => write!(f, "null"),
+            Json::String(s) => write_str(f, s),
+            Json::Array(elems) => {
+                write!(f, "[")?;
+
+                for (i, elem) in elems.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", elem)?;
+                }
+
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write_str(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl ToJson for Json {
+    fn to_json(&self) -> Json {
+        self.clone()
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> Json {
+        Json::Bool(*self)
+    }
+}
+
+macro_rules! int_to_json {
+    ($variant:ident, $conv:ty, $($ty:ty),*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json(&self) -> Json {
+                    Json::$variant(*self as $conv)
+                }
+            }
+        )*
+    }
+}
+
+int_to_json!(Int, i64, i8, i16, i32, i64, isize);
+int_to_json!(UInt, u64, u8, u16, u32, u64, usize);
+
+impl ToJson for f64 {
+    fn to_json(&self) -> Json {
+        Json::Float(*self)
+    }
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> Json {
+        Json::String(self.to_string())
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> Json {
+        Json::String(self.clone())
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn to_json(&self) -> Json {
+        (**self).to_json()
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Json {
+        match self {
+            Some(value) => value.to_json(),
+            None => Json::Null
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> Json {
+        Json::Array(self.iter().map(|elem| elem.to_json()).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Json {
+        self[..].to_json()
+    }
+}
+
+#[test]
+fn test_display() {
+    let value = Json::object()
+        .with("name", "a \"quoted\"\n\u{1}name")
+        .with("count", 3u32)
+        .with("delta", -2i32)
+        .with("ratio", 0.5)
+        .with("missing", None::<u32>)
+        .with("list", vec![true, false])
+        .with("empty", Json::object());
+
+    assert_eq!(value.to_string(),
+               "{\"name\":\"a \\\"quoted\\\"\\n\\u0001name\",\"count\":3,\
+                \"delta\":-2,\"ratio\":0.5,\"missing\":null,\
+                \"list\":[true,false],\"empty\":{}}");
+    assert_eq!(Json::Float(f64::NAN).to_string(), "null");
+    assert_eq!(Json::Float(1.0).to_string(), "1.0");
+}
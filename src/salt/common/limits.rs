@@ -0,0 +1,310 @@
+use salt::common::message::ErrorCode;
+use salt::common::message::Message;
+use salt::common::message::code;
+use std::cell::Cell;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// The code of errors reporting the recursion limit.
+pub static E0801: ErrorCode =
+    ErrorCode::new("E0801", "recursion limit reached",
+                   "A pass recursed more deeply than the recursion limit \
+                    allows, usually because of deeply nested or \
+                    self-referential input.  The limit can be raised \
+                    with `--recursion-limit`.");
+
+/// The code of errors reporting the type size limit.
+pub static E0802: ErrorCode =
+    ErrorCode::new("E0802", "type size limit reached",
+                   "A type is larger than the type size limit allows.  \
+                    The limit can be raised with `--type-size-limit`.");
+
+/// The code of errors reporting the macro expansion limit.
+pub static E0803: ErrorCode =
+    ErrorCode::new("E0803", "macro expansion limit reached",
+                   "More macros were expanded than the macro expansion \
+                    limit allows, usually because a macro expands to \
+                    itself.  The limit can be raised with \
+                    `--macro-expansion-limit`.");
+
+/// The code of errors reporting the memory limit.
+pub static E0804: ErrorCode =
+    ErrorCode::new("E0804", "memory limit reached",
+                   "The compiler used more memory than the memory limit \
+                    allows.  The limit can be raised with \
+                    `--memory-limit`.");
+
+/// Register the codes of limit errors.
+pub fn register_codes() {
+    code::register(&[&E0801, &E0802, &E0803, &E0804])
+}
+
+/// Resource limits for a compilation.  These protect passes from
+/// unbounded recursion or expansion on pathological input, so that
+/// the user gets a diagnostic instead of a stack overflow.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Limits {
+    /// Maximum recursion depth (`--recursion-limit`).
+    pub recursion: usize,
+    /// Maximum size of a type, in bytes (`--type-size-limit`).
+    pub type_size: u64,
+    /// Maximum number of macro expansions (`--macro-expansion-limit`).
+    pub macro_expansions: usize,
+    /// Maximum memory usage in bytes, if any (`--memory-limit`).
+    pub memory: Option<usize>
+}
+
+/// The kinds of resource limits.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LimitKind {
+    /// The recursion depth limit.
+    Recursion,
+    /// The type size limit.
+    TypeSize,
+    /// The macro expansion limit.
+    MacroExpansion,
+    /// The memory limit.
+    Memory
+}
+
+/// An error indicating that a resource limit was reached.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LimitError {
+    /// The limit that was reached.
+    pub kind: LimitKind,
+    /// The value of the limit.
+    pub limit: u64
+}
+
+/// Result of checking a resource limit.
+pub type LimitResult<T> = ::std::result::Result<T, LimitError>;
+
+/// Tracks resource usage against a set of `Limits`.  Trackers are not
+/// shared between threads; each worker should have its own.
+#[derive(Debug)]
+pub struct LimitTracker {
+    limits: Limits,
+    depth: Cell<usize>,
+    expansions: Cell<usize>
+}
+
+/// RAII guard for one level of recursion.  The depth is decremented
+/// when the guard is dropped.
+#[derive(Debug)]
+pub struct RecursionGuard<'t> {
+    tracker: &'t LimitTracker
+}
+
+impl Limits {
+    /// Create the default limits.
+    pub fn new() -> Limits {
+        Limits { recursion: 128, type_size: 1 << 32, macro_expansions: 65536,
+                 memory: None }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits::new()
+    }
+}
+
+impl LimitKind {
+    /// Get the command-line flag that controls this limit.
+    pub fn flag(&self) -> &'static str {
+        match self {
+            LimitKind::Recursion => "--recursion-limit",
+            LimitKind::TypeSize => "--type-size-limit",
+            LimitKind::MacroExpansion => "--macro-expansion-limit",
+            LimitKind::Memory => "--memory-limit"
+        }
+    }
+
+    /// Get a description of the limit, for use in messages.
+    pub fn desc(&self) -> &'static str {
+        match self {
+            LimitKind::Recursion => "recursion limit",
+            LimitKind::TypeSize => "type size limit",
+            LimitKind::MacroExpansion => "macro expansion limit",
+            LimitKind::Memory => "memory limit"
+        }
+    }
+
+    /// Get the code of errors reporting that this limit was reached.
+    pub fn code(&self) -> &'static ErrorCode {
+        match self {
+            LimitKind::Recursion => &E0801,
+            LimitKind::TypeSize => &E0802,
+            LimitKind::MacroExpansion => &E0803,
+            LimitKind::Memory => &E0804
+        }
+    }
+}
+
+impl LimitError {
+    /// Convert the error into an error message at `pos`, the position
+    /// of the input that reached the limit.
+    pub fn to_message<P>(&self, pos: P) -> Message<P> {
+        Message::error(format!("{} reached ({})", self.kind.desc(),
+                               self.limit))
+            .with_code(self.kind.code())
+            .with_position(pos)
+            .with_note(format!("use `{}` to increase it", self.kind.flag()))
+    }
+}
+
+impl LimitTracker {
+    /// Create a `LimitTracker` enforcing `limits`.
+    pub fn new(limits: Limits) -> LimitTracker {
+        LimitTracker { limits, depth: Cell::new(0), expansions: Cell::new(0) }
+    }
+
+    /// Get the limits being enforced.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Get the current recursion depth.
+    pub fn depth(&self) -> usize {
+        self.depth.get()
+    }
+
+    /// Enter one level of recursion.  The returned guard must be held
+    /// for the duration of the recursive call.
+    pub fn enter(&self) -> LimitResult<RecursionGuard<'_>> {
+        let depth = self.depth.get();
+
+        if depth >= self.limits.recursion {
+            Err(LimitError { kind: LimitKind::Recursion,
+                             limit: self.limits.recursion as u64 })
+        } else {
+            self.depth.set(depth + 1);
+
+            Ok(RecursionGuard { tracker: self })
+        }
+    }
+
+    /// Check the size of a type against the type size limit.
+    pub fn check_type_size(&self, size: u64) -> LimitResult<()> {
+        if size > self.limits.type_size {
+            Err(LimitError { kind: LimitKind::TypeSize,
+                             limit: self.limits.type_size })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Record one macro expansion.
+    pub fn count_expansion(&self) -> LimitResult<()> {
+        let count = self.expansions.get();
+
+        if count >= self.limits.macro_expansions {
+            Err(LimitError { kind: LimitKind::MacroExpansion,
+                             limit: self.limits.macro_expansions as u64 })
+        } else {
+            self.expansions.set(count + 1);
+
+            Ok(())
+        }
+    }
+
+    /// Check current memory usage, in bytes, against the memory limit.
+    pub fn check_memory(&self, used: usize) -> LimitResult<()> {
+        match self.limits.memory {
+            Some(limit) if used > limit =>
+                Err(LimitError { kind: LimitKind::Memory,
+                                 limit: limit as u64 }),
+            _ => Ok(())
+        }
+    }
+}
+
+impl<'t> Drop for RecursionGuard<'t> {
+    fn drop(&mut self) {
+        self.tracker.depth.set(self.tracker.depth.get() - 1)
+    }
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{} reached ({}), use `{}` to increase it",
+               self.kind.desc(), self.limit, self.kind.flag())
+    }
+}
+
+#[cfg(test)]
+fn test_recurse(tracker: &LimitTracker, n: usize) -> LimitResult<usize> {
+    let _guard = tracker.enter()?;
+
+    if n == 0 {
+        Ok(tracker.depth())
+    } else {
+        test_recurse(tracker, n - 1)
+    }
+}
+
+#[test]
+fn test_recursion_limit() {
+    let tracker = LimitTracker::new(Limits { recursion: 10, ..Limits::new() });
+
+    assert_eq!(test_recurse(&tracker, 9), Ok(10));
+    assert_eq!(tracker.depth(), 0);
+
+    let err = test_recurse(&tracker, 10).unwrap_err();
+
+    assert_eq!(tracker.depth(), 0);
+    assert_eq!(format!("{}", err), "recursion limit reached (10), \
+                                    use `--recursion-limit` to increase it");
+}
+
+#[test]
+fn test_expansion_limit() {
+    let tracker = LimitTracker::new(Limits { macro_expansions: 2,
+                                             ..Limits::new() });
+
+    assert!(tracker.count_expansion().is_ok());
+    assert!(tracker.count_expansion().is_ok());
+    assert_eq!(tracker.count_expansion().unwrap_err().kind,
+               LimitKind::MacroExpansion);
+}
+
+#[test]
+fn test_size_and_memory() {
+    let tracker = LimitTracker::new(Limits { type_size: 100, memory: Some(10),
+                                             ..Limits::new() });
+
+    assert!(tracker.check_type_size(100).is_ok());
+    assert!(tracker.check_type_size(101).is_err());
+    assert!(tracker.check_memory(10).is_ok());
+    assert_eq!(tracker.check_memory(11).unwrap_err().kind, LimitKind::Memory);
+}
+
+#[test]
+fn test_to_message() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::message::PlainRenderer;
+    use salt::common::message::Renderer;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Location;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    let mut strs = StrIntern::new();
+    let pos = FilePosition {
+        filename: strs.filename("deep.salt"),
+        loc: Location::Span { start: Point { line: 4, col: 1 },
+                              end: Point { line: 4, col: 9 } }
+    };
+    let tracker = LimitTracker::new(Limits { recursion: 1, ..Limits::new() });
+    let _guard = tracker.enter().unwrap();
+    let msg = tracker.enter().unwrap_err().to_message(pos.clone());
+
+    register_codes();
+    assert_eq!(msg.primary(), Some(&pos));
+    assert_eq!(msg.code.as_deref().and_then(code::explain),
+               Some(E0801.explanation()));
+    assert_eq!(PlainRenderer.render(&msg),
+               "deep.salt 4.1-9: error[E0801]: recursion limit reached (1)\n  \
+                note: use `--recursion-limit` to increase it\n");
+}
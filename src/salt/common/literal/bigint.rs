@@ -0,0 +1,418 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::ops::Add;
+use std::ops::Mul;
+use std::ops::Shl;
+use std::ops::Shr;
+use std::ops::Sub;
+
+/// An arbitrary-precision unsigned integer.  This is meant for exact
+/// arithmetic on literals, which are rarely more than a few words
+/// long, and favors simplicity over speed.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct BigUint {
+    /// Little-endian 32-bit limbs, with no trailing zeros.
+    limbs: Vec<u32>
+}
+
+impl BigUint {
+    /// Create a `BigUint` equal to 0.
+    pub fn zero() -> BigUint {
+        BigUint { limbs: Vec::new() }
+    }
+
+    /// Create a `BigUint` from a `u64`.
+    pub fn from_u64(n: u64) -> BigUint {
+        BigUint::from_u128(n as u128)
+    }
+
+    /// Create a `BigUint` from a `u128`.
+    pub fn from_u128(mut n: u128) -> BigUint {
+        let mut limbs = Vec::new();
+
+        while n != 0 {
+            limbs.push(n as u32);
+            n >>= 32;
+        }
+
+        BigUint { limbs }
+    }
+
+    fn normalize(mut self) -> BigUint {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+
+        self
+    }
+
+    /// Check whether this is 0.
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Get the number of bits needed to represent this number, which
+    /// is 0 for 0.
+    pub fn bits(&self) -> u64 {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() as u64 * 32 -
+                         top.leading_zeros() as u64,
+            None => 0
+        }
+    }
+
+    /// Get bit `n`, counting from the least significant bit.
+    pub fn bit(&self, n: u64) -> bool {
+        match self.limbs.get((n / 32) as usize) {
+            Some(limb) => limb & (1 << (n % 32)) != 0,
+            None => false
+        }
+    }
+
+    /// Get the number of trailing zero bits, or `None` for 0.
+    pub fn trailing_zeros(&self) -> Option<u64> {
+        self.limbs.iter()
+            .position(|&limb| limb != 0)
+            .map(|idx| idx as u64 * 32 +
+                       self.limbs[idx].trailing_zeros() as u64)
+    }
+
+    /// Convert to a `u128`, if the value fits.
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.limbs.len() > 4 {
+            None
+        } else {
+            Some(self.limbs.iter().rev()
+                 .fold(0, |acc, &limb| (acc << 32) | limb as u128))
+        }
+    }
+
+    /// Convert to a `u64`, if the value fits.
+    pub fn to_u64(&self) -> Option<u64> {
+        self.to_u128().and_then(|n| if n >> 64 == 0 {
+            Some(n as u64)
+        } else {
+            None
+        })
+    }
+
+    /// Multiply by `m` and add `a`, in place.
+    pub fn mul_add_small(&mut self, m: u32, a: u32) {
+        let mut carry = a as u64;
+
+        for limb in self.limbs.iter_mut() {
+            let n = *limb as u64 * m as u64 + carry;
+
+            *limb = n as u32;
+            carry = n >> 32;
+        }
+
+        if carry != 0 {
+            self.limbs.push(carry as u32);
+        }
+
+        *self = ::std::mem::take(self).normalize();
+    }
+
+    /// Divide by `d` in place, returning the remainder.  Panics if `d`
+    /// is 0.
+    pub fn divrem_small(&mut self, d: u32) -> u32 {
+        assert!(d != 0, "division by zero");
+
+        let mut rem = 0u64;
+
+        for limb in self.limbs.iter_mut().rev() {
+            let n = (rem << 32) | *limb as u64;
+
+            *limb = (n / d as u64) as u32;
+            rem = n % d as u64;
+        }
+
+        *self = ::std::mem::take(self).normalize();
+
+        rem as u32
+    }
+
+    /// Divide by `d`, returning the quotient and remainder.  Panics if
+    /// `d` is 0.
+    pub fn divrem(&self, d: &BigUint) -> (BigUint, BigUint) {
+        assert!(!d.is_zero(), "division by zero");
+
+        if d.limbs.len() == 1 {
+            let mut quot = self.clone();
+            let rem = quot.divrem_small(d.limbs[0]);
+
+            return (quot, BigUint::from_u64(rem as u64));
+        }
+
+        let mut quot = BigUint { limbs: vec![0; self.limbs.len()] };
+        let mut rem = BigUint::zero();
+
+        for i in (0..self.bits()).rev() {
+            rem = &rem << 1;
+
+            if self.bit(i) {
+                rem.mul_add_small(1, 1);
+            }
+
+            if rem >= *d {
+                rem = &rem - d;
+                quot.limbs[(i / 32) as usize] |= 1 << (i % 32);
+            }
+        }
+
+        (quot.normalize(), rem)
+    }
+
+    /// Compute the greatest common divisor of two numbers.
+    pub fn gcd(&self, other: &BigUint) -> BigUint {
+        let mut a = self.clone();
+        let mut b = other.clone();
+
+        while !b.is_zero() {
+            let (_, rem) = a.divrem(&b);
+
+            a = b;
+            b = rem;
+        }
+
+        a
+    }
+
+    /// Compute `base` raised to `exp`.
+    pub fn pow(base: u32, exp: u32) -> BigUint {
+        let mut out = BigUint::from_u64(1);
+
+        for _ in 0..exp {
+            out.mul_add_small(base, 0);
+        }
+
+        out
+    }
+
+    /// Parse digits in `radix`, which must be between 2 and 36.  No
+    /// sign, prefix or separators are accepted.
+    pub fn from_str_radix(s: &str, radix: u32) -> Option<BigUint> {
+        if s.is_empty() {
+            return None;
+        }
+
+        let mut out = BigUint::zero();
+
+        for c in s.chars() {
+            out.mul_add_small(radix, c.to_digit(radix)?);
+        }
+
+        Some(out)
+    }
+
+    /// Write this number in `radix`, which must be between 2 and 36,
+    /// using lowercase letters.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return String::from("0");
+        }
+
+        let mut n = self.clone();
+        let mut digits = Vec::new();
+
+        while !n.is_zero() {
+            let d = n.divrem_small(radix);
+
+            digits.push(::std::char::from_digit(d, radix).unwrap());
+        }
+
+        digits.iter().rev().collect()
+    }
+}
+
+impl<'a> Add<&'a BigUint> for &'a BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let len = self.limbs.len().max(other.limbs.len());
+        let mut limbs = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+
+        for i in 0..len {
+            let n = *self.limbs.get(i).unwrap_or(&0) as u64 +
+                    *other.limbs.get(i).unwrap_or(&0) as u64 + carry;
+
+            limbs.push(n as u32);
+            carry = n >> 32;
+        }
+
+        limbs.push(carry as u32);
+
+        BigUint { limbs }.normalize()
+    }
+}
+
+/// Subtraction panics if the result would be negative.
+impl<'a> Sub<&'a BigUint> for &'a BigUint {
+    type Output = BigUint;
+
+    fn sub(self, other: &BigUint) -> BigUint {
+        assert!(*self >= *other, "subtraction underflow");
+
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0i64;
+
+        for (i, &limb) in self.limbs.iter().enumerate() {
+            let sub = *other.limbs.get(i).unwrap_or(&0) as i64 + borrow;
+            let mut n = limb as i64 - sub;
+
+            borrow = if n < 0 {
+                n += 1 << 32;
+
+                1
+            } else {
+                0
+            };
+
+            limbs.push(n as u32);
+        }
+
+        BigUint { limbs }.normalize()
+    }
+}
+
+impl<'a> Mul<&'a BigUint> for &'a BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let n = limbs[i + j] as u64 + a as u64 * b as u64 + carry;
+
+                limbs[i + j] = n as u32;
+                carry = n >> 32;
+            }
+
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+
+        BigUint { limbs }.normalize()
+    }
+}
+
+impl Shl<u64> for &BigUint {
+    type Output = BigUint;
+
+    fn shl(self, n: u64) -> BigUint {
+        if self.is_zero() {
+            return BigUint::zero();
+        }
+
+        let words = (n / 32) as usize;
+        let bits = (n % 32) as u32;
+        let mut limbs = vec![0; words];
+        let mut carry = 0;
+
+        for &limb in self.limbs.iter() {
+            if bits == 0 {
+                limbs.push(limb);
+            } else {
+                limbs.push((limb << bits) | carry);
+                carry = limb >> (32 - bits);
+            }
+        }
+
+        limbs.push(carry);
+
+        BigUint { limbs }.normalize()
+    }
+}
+
+impl Shr<u64> for &BigUint {
+    type Output = BigUint;
+
+    fn shr(self, n: u64) -> BigUint {
+        let words = (n / 32) as usize;
+        let bits = (n % 32) as u32;
+
+        if words >= self.limbs.len() {
+            return BigUint::zero();
+        }
+
+        let rest = &self.limbs[words..];
+        let limbs = rest.iter().enumerate().map(|(i, &limb)| {
+            if bits == 0 {
+                limb
+            } else {
+                let high = rest.get(i + 1)
+                    .map_or(0, |&next| next << (32 - bits));
+
+                (limb >> bits) | high
+            }
+        }).collect();
+
+        BigUint { limbs }.normalize()
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.limbs.len().cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev()
+                       .cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigUint {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.to_str_radix(10))
+    }
+}
+
+#[test]
+fn test_radix_roundtrip() {
+    let digits = "123456789012345678901234567890123456789012345678901234567890";
+    let n = BigUint::from_str_radix(digits, 10).unwrap();
+
+    assert_eq!(n.to_string(), digits);
+    assert_eq!(BigUint::from_str_radix(&n.to_str_radix(16), 16), Some(n));
+    assert_eq!(BigUint::from_str_radix("ff", 16).unwrap().to_u64(), Some(255));
+    assert_eq!(BigUint::from_str_radix("12", 2), None);
+    assert_eq!(BigUint::zero().to_string(), "0");
+}
+
+#[test]
+fn test_arithmetic() {
+    let a = BigUint::from_u128(u128::MAX);
+    let one = BigUint::from_u64(1);
+    let b = &a + &one;
+
+    assert_eq!(b.bits(), 129);
+    assert_eq!(b.to_u128(), None);
+    assert_eq!(&b - &one, a);
+    assert_eq!((&b >> 1).bits(), 128);
+    assert_eq!(&(&b >> 100) << 100, b);
+    assert_eq!(b.trailing_zeros(), Some(128));
+    assert_eq!(&BigUint::from_u64(6) * &BigUint::from_u64(7),
+               BigUint::from_u64(42));
+}
+
+#[test]
+fn test_divrem() {
+    let big = &BigUint::pow(10, 40) + &BigUint::from_u64(7);
+    let d = BigUint::pow(10, 20);
+    let (q, r) = big.divrem(&d);
+
+    assert_eq!(q, BigUint::pow(10, 20));
+    assert_eq!(r, BigUint::from_u64(7));
+    assert_eq!(BigUint::from_u64(84).gcd(&BigUint::from_u64(36)),
+               BigUint::from_u64(12));
+}
@@ -0,0 +1,96 @@
+use salt::common::literal::FloatFormat;
+use salt::common::literal::IntLit;
+use salt::common::literal::SoftFloat;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// A constant value, as produced by constant folding.  This is shared
+/// between frontends and middle ends, so it has no notion of types
+/// beyond the kind of each value.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ConstValue {
+    /// An integer.
+    Int(IntLit),
+    /// A floating point number.
+    Float(SoftFloat),
+    /// A boolean.
+    Bool(bool),
+    /// A string.
+    String(String),
+    /// The unit value.
+    Unit,
+    /// An aggregate of values, such as a structure or array.
+    Aggregate(Vec<ConstValue>)
+}
+
+impl ConstValue {
+    /// Get the name of the kind of this value, for use in diagnostics.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            ConstValue::Int(_) => "integer",
+            ConstValue::Float(_) => "float",
+            ConstValue::Bool(_) => "boolean",
+            ConstValue::String(_) => "string",
+            ConstValue::Unit => "unit",
+            ConstValue::Aggregate(_) => "aggregate"
+        }
+    }
+}
+
+/// Values are written compactly, in a form meant for messages such as
+/// "expected constant 1, found "one"".  Floats that can be converted
+/// exactly to `f64` are written as the shortest decimal that reads
+/// back the same; wider ones are written as their encoding.
+impl Display for ConstValue {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            ConstValue::Int(n) => write!(f, "{}", n),
+            ConstValue::Float(n) => match (n.format, n.to_f64()) {
+                (FloatFormat::F32, Some(v)) => write!(f, "{:?}", v as f32),
+                (_, Some(v)) => write!(f, "{:?}", v),
+                (format, None) => write!(f, "{}(0x{:x})", format, n.bits)
+            },
+            ConstValue::Bool(b) => write!(f, "{}", b),
+            ConstValue::String(s) => write!(f, "\"{}\"", s.escape_debug()),
+            ConstValue::Unit => write!(f, "()"),
+            ConstValue::Aggregate(values) => {
+                write!(f, "{{")?;
+
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", value)?;
+                }
+
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[test]
+fn test_display() {
+    let half = SoftFloat { format: FloatFormat::F32, bits: 0x3f00_0000 };
+    let tenth = SoftFloat { format: FloatFormat::F64,
+                            bits: 0.1f64.to_bits() as u128 };
+    let value = ConstValue::Aggregate(vec![
+        ConstValue::Int("0xffu8".parse().unwrap()),
+        ConstValue::Float(half),
+        ConstValue::Float(tenth),
+        ConstValue::Bool(true),
+        ConstValue::String(String::from("a\"b\n")),
+        ConstValue::Unit,
+        ConstValue::Aggregate(vec![])
+    ]);
+
+    assert_eq!(value.to_string(),
+               "{0xffu8, 0.5, 0.1, true, \"a\\\"b\\n\", (), {}}");
+    assert_eq!(ConstValue::Float(SoftFloat::infinity(FloatFormat::F64, true))
+               .to_string(), "-inf");
+    assert_eq!(ConstValue::Float(SoftFloat::zero(FloatFormat::F128, false))
+               .to_string(), "f128(0x0)");
+    assert_eq!(value.kind_name(), "aggregate");
+}
@@ -0,0 +1,217 @@
+use salt::common::literal::BigUint;
+use salt::common::literal::ConstValue;
+use salt::common::literal::FloatFormat;
+use salt::common::literal::IntLit;
+use salt::common::literal::Rounding;
+use salt::common::literal::Signedness;
+use salt::common::literal::SoftFloat;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// A numeric type that an integer literal can be converted to.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NumType {
+    /// An integer type, `bits` wide.
+    Int {
+        /// The width, in bits.
+        bits: u32,
+        /// Whether the type is signed.
+        signedness: Signedness
+    },
+    /// A floating point type.
+    Float(FloatFormat)
+}
+
+/// The way in which a conversion changed a value.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ConversionLoss {
+    /// The value's bits were kept, but read with the other signedness,
+    /// as when converting -1 to `u8` gives 255.
+    SignChange,
+    /// High bits were discarded, as when converting 300 to `u8` gives
+    /// 44.
+    Truncation,
+    /// The value was rounded to a nearby floating point value.
+    PrecisionLoss,
+    /// The value was too large for a floating point type, and became
+    /// infinity.
+    Overflow
+}
+
+/// The result of converting an integer literal to a numeric type, with
+/// the classification of any loss.  The `Display` form is written for
+/// use as a diagnostic note.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct IntConversion {
+    /// The literal being converted.
+    pub from: IntLit,
+    /// The type converted to.
+    pub to: NumType,
+    /// The value produced.  Integer conversions wrap, as a cast would.
+    pub value: ConstValue,
+    /// How the value was changed, or `None` if it was preserved.
+    pub loss: Option<ConversionLoss>
+}
+
+impl NumType {
+    /// Create an integer type.
+    pub fn int(bits: u32, signedness: Signedness) -> NumType {
+        NumType::Int { bits, signedness }
+    }
+}
+
+/// Reduce `n` modulo `2^bits` to the value of an integer type, as a
+/// two's complement cast would.
+fn wrap(n: &IntLit, bits: u32, signedness: Signedness) -> IntLit {
+    let modulus = BigUint::pow(2, bits);
+    let (_, mut low) = n.magnitude().divrem(&modulus);
+
+    if n.is_negative() && !low.is_zero() {
+        low = &modulus - &low;
+    }
+
+    if signedness == Signedness::Signed && bits > 0 &&
+       low.bit(bits as u64 - 1) {
+        IntLit::new(true, &modulus - &low, n.radix)
+    } else {
+        IntLit::new(false, low, n.radix)
+    }
+}
+
+impl IntLit {
+    /// Convert the literal to `to`, classifying any change in value.
+    /// For integer types, the value is kept if it fits; otherwise it
+    /// is a sign change if the literal fits in `bits` bits under the
+    /// other signedness, and a truncation if not.
+    pub fn convert(&self, to: NumType) -> IntConversion {
+        let (value, loss) = match to {
+            NumType::Int { bits, signedness } => {
+                let other = match signedness {
+                    Signedness::Signed => Signedness::Unsigned,
+                    Signedness::Unsigned => Signedness::Signed
+                };
+                let loss = if self.fits(bits, signedness) {
+                    None
+                } else if self.fits(bits, other) {
+                    Some(ConversionLoss::SignChange)
+                } else {
+                    Some(ConversionLoss::Truncation)
+                };
+
+                (ConstValue::Int(wrap(self, bits, signedness)), loss)
+            }
+            NumType::Float(format) => {
+                let (value, rounding) = SoftFloat::from_int(format, self);
+                let loss = match rounding {
+                    Rounding::Exact => None,
+                    Rounding::Overflow => Some(ConversionLoss::Overflow),
+                    _ => Some(ConversionLoss::PrecisionLoss)
+                };
+
+                (ConstValue::Float(value), loss)
+            }
+        };
+
+        IntConversion { from: self.clone(), to, value, loss }
+    }
+}
+
+impl IntConversion {
+    /// Check whether the value was preserved.
+    pub fn is_lossless(&self) -> bool {
+        self.loss.is_none()
+    }
+}
+
+impl Display for NumType {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            NumType::Int { bits, signedness } =>
+                write!(f, "{}{}", signedness.prefix(), bits),
+            NumType::Float(format) => write!(f, "{}", format)
+        }
+    }
+}
+
+impl Display for ConversionLoss {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            ConversionLoss::SignChange => write!(f, "sign change"),
+            ConversionLoss::Truncation => write!(f, "truncation"),
+            ConversionLoss::PrecisionLoss => write!(f, "loss of precision"),
+            ConversionLoss::Overflow => write!(f, "overflow")
+        }
+    }
+}
+
+impl Display for IntConversion {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self.loss {
+            None => write!(f, "`{}` converts to `{}` exactly", self.from,
+                           self.to),
+            Some(ConversionLoss::SignChange) =>
+                write!(f, "converting `{}` to `{}` changes its sign, \
+                           giving `{}`", self.from, self.to, self.value),
+            Some(ConversionLoss::Truncation) =>
+                write!(f, "converting `{}` to `{}` truncates it to `{}`",
+                       self.from, self.to, self.value),
+            Some(ConversionLoss::PrecisionLoss) =>
+                write!(f, "converting `{}` to `{}` rounds it to `{}`",
+                       self.from, self.to, self.value),
+            Some(ConversionLoss::Overflow) =>
+                write!(f, "`{}` is too large for `{}`", self.from, self.to)
+        }
+    }
+}
+
+#[test]
+fn test_int_conversion() {
+    let int = |s: &str| s.parse::<IntLit>().unwrap();
+    let i64 = NumType::int(64, Signedness::Signed);
+    let u64 = NumType::int(64, Signedness::Unsigned);
+    let u8 = NumType::int(8, Signedness::Unsigned);
+    let min = int("-9223372036854775808");
+
+    assert!(min.convert(i64).is_lossless());
+    assert_eq!(min.convert(i64).value, ConstValue::Int(min.clone()));
+
+    let conv = min.convert(u64);
+
+    assert_eq!(conv.loss, Some(ConversionLoss::SignChange));
+    assert_eq!(conv.value, ConstValue::Int(min.neg()));
+    assert_eq!(min.neg().convert(i64).value, ConstValue::Int(min.clone()));
+
+    let conv = int("-9223372036854775809").convert(i64);
+
+    assert_eq!(conv.loss, Some(ConversionLoss::Truncation));
+    assert_eq!(conv.value, ConstValue::Int(int("9223372036854775807")));
+
+    let conv = int("300").convert(u8);
+
+    assert_eq!(conv.loss, Some(ConversionLoss::Truncation));
+    assert_eq!(conv.to_string(),
+               "converting `300` to `u8` truncates it to `44`");
+    assert_eq!(int("-1").convert(u8).to_string(),
+               "converting `-1` to `u8` changes its sign, giving `255`");
+    assert_eq!(int("-0x80").convert(u8).value, ConstValue::Int(int("0x80")));
+    assert_eq!(int("-129").convert(u8).loss,
+               Some(ConversionLoss::Truncation));
+}
+
+#[test]
+fn test_float_conversion() {
+    let int = |s: &str| s.parse::<IntLit>().unwrap();
+    let f32 = NumType::Float(FloatFormat::F32);
+
+    assert!(int("16777216").convert(f32).is_lossless());
+
+    let conv = int("16777217").convert(f32);
+
+    assert_eq!(conv.loss, Some(ConversionLoss::PrecisionLoss));
+    assert_eq!(conv.to_string(),
+               "converting `16777217` to `f32` rounds it to `16777216.0`");
+    assert_eq!(int("-70000").convert(NumType::Float(FloatFormat::F16)).loss,
+               Some(ConversionLoss::Overflow));
+    assert!(int("0").convert(f32).is_lossless());
+}
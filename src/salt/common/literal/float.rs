@@ -0,0 +1,507 @@
+use salt::common::literal::BigUint;
+use salt::common::literal::IntLit;
+use salt::common::literal::Radix;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// An IEEE-754 binary floating point format.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum FloatFormat {
+    /// binary16, half precision.
+    F16,
+    /// binary32, single precision.
+    F32,
+    /// binary64, double precision.
+    F64,
+    /// binary128, quadruple precision.
+    F128
+}
+
+/// A floating point value in one of the IEEE formats, held as its
+/// encoding.  This does not depend on the host's floating point
+/// support, so values are the same on every machine.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SoftFloat {
+    /// The format of the value.
+    pub format: FloatFormat,
+    /// The IEEE encoding, in the low bits.
+    pub bits: u128
+}
+
+/// The effect of rounding a literal to a floating point format.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Rounding {
+    /// The literal was represented exactly.
+    Exact,
+    /// The literal was rounded to a nearby finite value.
+    Inexact,
+    /// The literal was too large, and became infinity.
+    Overflow,
+    /// The literal was nonzero, but too small, and became zero.
+    Underflow
+}
+
+/// A floating point literal, stored exactly along with the text it
+/// was written as.  Decimal literals (`1.5e3`) and hexadecimal literals
+/// (`0x1.8p3`, with a required binary exponent) are accepted.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FloatLit {
+    /// The literal as written.
+    text: String,
+    /// Whether the literal is negative.
+    negative: bool,
+    /// The digits of the literal, as an integer.
+    mantissa: BigUint,
+    /// The exponent: the value is `mantissa * 10^exp` for decimal
+    /// literals, and `mantissa * 2^exp` for hexadecimal literals.
+    exp: i64,
+    /// The radix of the mantissa.
+    radix: Radix,
+    /// The type suffix, such as `f32`, if any.
+    suffix: Option<String>
+}
+
+/// Errors that can occur when parsing a `FloatLit`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum FloatLitError {
+    /// There were no digits in the mantissa.
+    NoDigits,
+    /// The exponent had no digits.
+    BadExponent,
+    /// A hexadecimal literal had no binary exponent.
+    MissingExponent,
+    /// The suffix was not an identifier.
+    BadSuffix(String)
+}
+
+/// Literals with decimal exponents beyond these bounds are certain to
+/// overflow or underflow every format, and are not computed exactly.
+const MAX_DEC_EXP: i64 = 4940;
+const MIN_DEC_EXP: i64 = -4970;
+const MAX_BIN_EXP: i64 = 16400;
+const MIN_BIN_EXP: i64 = -16500;
+
+impl FloatFormat {
+    /// Get the number of exponent bits.
+    pub fn exp_bits(&self) -> u32 {
+        match self {
+            FloatFormat::F16 => 5,
+            FloatFormat::F32 => 8,
+            FloatFormat::F64 => 11,
+            FloatFormat::F128 => 15
+        }
+    }
+
+    /// Get the number of stored significand bits, which excludes the
+    /// implicit leading bit.
+    pub fn mantissa_bits(&self) -> u32 {
+        match self {
+            FloatFormat::F16 => 10,
+            FloatFormat::F32 => 23,
+            FloatFormat::F64 => 52,
+            FloatFormat::F128 => 112
+        }
+    }
+
+    /// Get the total width of the format, in bits.
+    pub fn bits(&self) -> u32 {
+        1 + self.exp_bits() + self.mantissa_bits()
+    }
+
+    fn bias(&self) -> i64 {
+        (1 << (self.exp_bits() - 1)) - 1
+    }
+}
+
+impl SoftFloat {
+    /// Create an infinity.
+    pub fn infinity(format: FloatFormat, negative: bool) -> SoftFloat {
+        let exp = (1u128 << format.exp_bits()) - 1;
+
+        SoftFloat::from_parts(format, negative, exp, 0)
+    }
+
+    /// Create a zero.
+    pub fn zero(format: FloatFormat, negative: bool) -> SoftFloat {
+        SoftFloat::from_parts(format, negative, 0, 0)
+    }
+
+    fn from_parts(format: FloatFormat, negative: bool, exp: u128,
+                  mantissa: u128) -> SoftFloat {
+        let sign = (negative as u128) << (format.bits() - 1);
+
+        SoftFloat { format,
+                    bits: sign | (exp << format.mantissa_bits()) | mantissa }
+    }
+
+    fn exp_field(&self) -> u128 {
+        (self.bits >> self.format.mantissa_bits()) &
+        ((1 << self.format.exp_bits()) - 1)
+    }
+
+    fn mantissa_field(&self) -> u128 {
+        self.bits & ((1 << self.format.mantissa_bits()) - 1)
+    }
+
+    /// Check whether the sign bit is set.
+    pub fn is_negative(&self) -> bool {
+        self.bits >> (self.format.bits() - 1) != 0
+    }
+
+    /// Check whether this is positive or negative zero.
+    pub fn is_zero(&self) -> bool {
+        self.exp_field() == 0 && self.mantissa_field() == 0
+    }
+
+    /// Check whether this is positive or negative infinity.
+    pub fn is_infinite(&self) -> bool {
+        self.exp_field() == (1 << self.format.exp_bits()) - 1 &&
+        self.mantissa_field() == 0
+    }
+
+    /// Check whether this is a NaN.
+    pub fn is_nan(&self) -> bool {
+        self.exp_field() == (1 << self.format.exp_bits()) - 1 &&
+        self.mantissa_field() != 0
+    }
+
+    /// Convert an integer literal to `format`, rounding to nearest with
+    /// ties to even, and report whether rounding occurred.
+    pub fn from_int(format: FloatFormat, n: &IntLit) -> (SoftFloat, Rounding) {
+        if n.magnitude().is_zero() {
+            (SoftFloat::zero(format, false), Rounding::Exact)
+        } else {
+            round_ratio(format, n.is_negative(), n.magnitude(),
+                        &BigUint::from_u64(1))
+        }
+    }
+
+    /// Negate the value.  This only flips the sign bit, and is exact.
+    pub fn neg(&self) -> SoftFloat {
+        SoftFloat { format: self.format,
+                    bits: self.bits ^ (1 << (self.format.bits() - 1)) }
+    }
+
+    /// Convert to an `f64`.  This is exact, and so only possible for
+    /// formats no wider than `F64`.
+    pub fn to_f64(&self) -> Option<f64> {
+        match self.format {
+            FloatFormat::F16 => {
+                let sign = if self.is_negative() { -1.0 } else { 1.0 };
+                let exp = self.exp_field() as i32;
+                let mantissa = self.mantissa_field() as f64;
+
+                Some(sign * match exp {
+                    0 => mantissa * 2f64.powi(-24),
+                    31 if mantissa == 0.0 => f64::INFINITY,
+                    31 => f64::NAN,
+                    _ => (1024.0 + mantissa) * 2f64.powi(exp - 25)
+                })
+            }
+            FloatFormat::F32 => Some(f32::from_bits(self.bits as u32) as f64),
+            FloatFormat::F64 => Some(f64::from_bits(self.bits as u64)),
+            FloatFormat::F128 => None
+        }
+    }
+}
+
+/// Round `num / den` to `format`, using round-to-nearest-even.
+fn round_ratio(format: FloatFormat, negative: bool, num: &BigUint,
+               den: &BigUint) -> (SoftFloat, Rounding) {
+    let prec = format.mantissa_bits() as i64 + 1;
+    let emin = 1 - format.bias();
+    let emax = format.bias();
+
+    // Scale so that the quotient has at least two bits beyond the
+    // precision, for the rounding and sticky bits.
+    let scale = prec + 3 - (num.bits() as i64 - den.bits() as i64);
+    let (quot, rem) = if scale >= 0 {
+        (num << scale as u64).divrem(den)
+    } else {
+        num.divrem(&(den << (-scale) as u64))
+    };
+    let nbits = quot.bits() as i64;
+    let lead_exp = nbits - 1 - scale;
+    let drop = if lead_exp >= emin {
+        nbits - prec
+    } else {
+        nbits - prec + (emin - lead_exp)
+    };
+    let half = drop >= 1 && quot.bit(drop as u64 - 1);
+    let below = !rem.is_zero() ||
+                quot.trailing_zeros().is_some_and(|tz| tz + 1 < drop as u64);
+    let mut mantissa = (&quot >> drop as u64).to_u128()
+        .expect("rounded mantissa too large");
+    let mut exp = drop - scale;
+
+    if half && (below || mantissa & 1 == 1) {
+        mantissa += 1;
+
+        if mantissa == 1 << prec {
+            mantissa >>= 1;
+            exp += 1;
+        }
+    }
+
+    let inexact = half || below;
+
+    if mantissa >= 1 << (prec - 1) {
+        let unbiased = exp + prec - 1;
+
+        if unbiased > emax {
+            (SoftFloat::infinity(format, negative), Rounding::Overflow)
+        } else {
+            let biased = (unbiased + format.bias()) as u128;
+            let value = SoftFloat::from_parts(format, negative, biased,
+                                              mantissa - (1 << (prec - 1)));
+
+            (value, if inexact { Rounding::Inexact } else { Rounding::Exact })
+        }
+    } else if mantissa == 0 {
+        (SoftFloat::zero(format, negative), Rounding::Underflow)
+    } else {
+        let value = SoftFloat::from_parts(format, negative, 0, mantissa);
+
+        (value, if inexact { Rounding::Inexact } else { Rounding::Exact })
+    }
+}
+
+impl FloatLit {
+    /// Get the literal as it was written.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the type suffix, if any.
+    pub fn suffix(&self) -> Option<&str> {
+        self.suffix.as_deref()
+    }
+
+    /// Check whether the literal is negative.
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Get the radix the literal was written in.
+    pub fn radix(&self) -> Radix {
+        self.radix
+    }
+
+    /// Convert the literal to `format`, rounding to nearest with ties
+    /// to even, and report whether rounding occurred.
+    pub fn to_float(&self, format: FloatFormat) -> (SoftFloat, Rounding) {
+        if self.mantissa.is_zero() {
+            return (SoftFloat::zero(format, self.negative), Rounding::Exact);
+        }
+
+        let (base, max, min) = if self.radix == Radix::Hex {
+            (2, MAX_BIN_EXP, MIN_BIN_EXP)
+        } else {
+            (10, MAX_DEC_EXP, MIN_DEC_EXP)
+        };
+        let digits = self.mantissa.to_str_radix(base).len() as i64;
+
+        if self.exp > max {
+            (SoftFloat::infinity(format, self.negative), Rounding::Overflow)
+        } else if self.exp + digits < min {
+            (SoftFloat::zero(format, self.negative), Rounding::Underflow)
+        } else if self.exp >= 0 {
+            let num = &self.mantissa * &BigUint::pow(base, self.exp as u32);
+
+            round_ratio(format, self.negative, &num, &BigUint::from_u64(1))
+        } else {
+            let den = BigUint::pow(base, (-self.exp) as u32);
+
+            round_ratio(format, self.negative, &self.mantissa, &den)
+        }
+    }
+}
+
+fn is_suffix(suffix: &str) -> bool {
+    suffix.starts_with(|c: char| c.is_alphabetic()) &&
+    suffix.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Split `s` into the digits (without separators) at its start in
+/// `radix`, and the rest.
+fn take_digits(s: &str, radix: u32) -> (String, &str) {
+    let end = s.find(|c: char| c != '_' && !c.is_digit(radix))
+        .unwrap_or(s.len());
+
+    (s[..end].chars().filter(|&c| c != '_').collect(), &s[end..])
+}
+
+/// Parse an exponent: an optional sign and decimal digits.  Exponents
+/// too large for an `i64` saturate, which is harmless given the bounds
+/// applied in `to_float`.
+fn parse_exp(s: &str) -> ::std::result::Result<(i64, &str), FloatLitError> {
+    let (negative, s) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s)
+    };
+    let (digits, rest) = take_digits(s, 10);
+
+    if digits.is_empty() {
+        return Err(FloatLitError::BadExponent);
+    }
+
+    let exp = digits.parse::<i64>().unwrap_or(i64::MAX);
+
+    Ok((if negative { -exp } else { exp }, rest))
+}
+
+impl FromStr for FloatLit {
+    type Err = FloatLitError;
+
+    fn from_str(s: &str) -> ::std::result::Result<FloatLit, FloatLitError> {
+        let (negative, rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s)
+        };
+        let (radix, rest) = match rest.get(..2) {
+            Some("0x") | Some("0X") => (Radix::Hex, &rest[2..]),
+            _ => (Radix::Decimal, rest)
+        };
+        let (int_digits, rest) = take_digits(rest, radix.value());
+        let (frac_digits, rest) = match rest.strip_prefix('.') {
+            Some(rest) => take_digits(rest, radix.value()),
+            None => (String::new(), rest)
+        };
+
+        if int_digits.is_empty() && frac_digits.is_empty() {
+            return Err(FloatLitError::NoDigits);
+        }
+
+        let mut digits = int_digits;
+
+        digits.push_str(&frac_digits);
+
+        let mantissa = BigUint::from_str_radix(&digits, radix.value())
+            .expect("digits already checked");
+        let (exp, rest) = match (radix, rest.as_bytes().first()) {
+            (Radix::Hex, Some(b'p')) | (Radix::Hex, Some(b'P')) =>
+                parse_exp(&rest[1..])?,
+            (Radix::Hex, _) => return Err(FloatLitError::MissingExponent),
+            (_, Some(b'e')) | (_, Some(b'E')) => parse_exp(&rest[1..])?,
+            _ => (0, rest)
+        };
+        let frac_len = frac_digits.len() as i64;
+        let exp = if radix == Radix::Hex {
+            exp.saturating_sub(frac_len * 4)
+        } else {
+            exp.saturating_sub(frac_len)
+        };
+        let suffix = if rest.is_empty() {
+            None
+        } else if is_suffix(rest) {
+            Some(rest.to_string())
+        } else {
+            return Err(FloatLitError::BadSuffix(rest.to_string()));
+        };
+
+        Ok(FloatLit { text: s.to_string(), negative, mantissa, exp, radix,
+                      suffix })
+    }
+}
+
+impl Display for FloatFormat {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "f{}", self.bits())
+    }
+}
+
+/// Literals are written as they were in the source.
+impl Display for FloatLit {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl Display for FloatLitError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            FloatLitError::NoDigits =>
+                write!(f, "floating point literal has no digits"),
+            FloatLitError::BadExponent =>
+                write!(f, "floating point literal has an empty exponent"),
+            FloatLitError::MissingExponent =>
+                write!(f, "hexadecimal floating point literal requires a \
+                           `p` exponent"),
+            FloatLitError::BadSuffix(suffix) =>
+                write!(f, "invalid suffix `{}` for floating point literal",
+                       suffix)
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_f64(s: &str) -> (f64, Rounding) {
+    let (value, rounding) = s.parse::<FloatLit>().unwrap()
+        .to_float(FloatFormat::F64);
+
+    (value.to_f64().unwrap(), rounding)
+}
+
+#[test]
+fn test_decimal_f64() {
+    assert_eq!(test_f64("1.5"), (1.5, Rounding::Exact));
+    assert_eq!(test_f64("0.1"), (0.1, Rounding::Inexact));
+    assert_eq!(test_f64("-2.5e-3"), (-2.5e-3, Rounding::Inexact));
+    assert_eq!(test_f64("1_000.25e2"), (100025.0, Rounding::Exact));
+    assert_eq!(test_f64("1.7976931348623157e308"),
+               (f64::MAX, Rounding::Inexact));
+    assert_eq!(test_f64("1.8e308"), (f64::INFINITY, Rounding::Overflow));
+    assert_eq!(test_f64("5e-324"), (5e-324, Rounding::Inexact));
+    assert_eq!(test_f64("2e-324"), (0.0, Rounding::Underflow));
+    assert_eq!(test_f64("2.2250738585072011e-308"),
+               (2.2250738585072011e-308, Rounding::Inexact));
+    assert_eq!(test_f64("1e99999999999999999999").1, Rounding::Overflow);
+    assert_eq!(test_f64("0.0"), (0.0, Rounding::Exact));
+}
+
+#[test]
+fn test_ties_to_even() {
+    // 2^53 + 1 is halfway between 2^53 and 2^53 + 2.
+    assert_eq!(test_f64("9007199254740993"),
+               (9007199254740992.0, Rounding::Inexact));
+    assert_eq!(test_f64("9007199254740995"),
+               (9007199254740996.0, Rounding::Inexact));
+}
+
+#[test]
+fn test_hex_and_formats() {
+    assert_eq!(test_f64("0x1.8p3"), (12.0, Rounding::Exact));
+    assert_eq!(test_f64("0x1p-1074"), (5e-324, Rounding::Exact));
+
+    let lit: FloatLit = "0.1f32".parse().unwrap();
+    let (value, rounding) = lit.to_float(FloatFormat::F32);
+
+    assert_eq!(lit.suffix(), Some("f32"));
+    assert_eq!(lit.to_string(), "0.1f32");
+    assert_eq!(value.to_f64(), Some(0.1f32 as f64));
+    assert_eq!(rounding, Rounding::Inexact);
+
+    let (value, rounding) = "65520".parse::<FloatLit>().unwrap()
+        .to_float(FloatFormat::F16);
+
+    assert!(value.is_infinite());
+    assert_eq!(rounding, Rounding::Overflow);
+
+    let (value, _) = "1.0".parse::<FloatLit>().unwrap()
+        .to_float(FloatFormat::F128);
+
+    assert_eq!(value.bits, 0x3fff << 112);
+}
+
+#[test]
+fn test_parse_bad() {
+    assert_eq!(".".parse::<FloatLit>(), Err(FloatLitError::NoDigits));
+    assert_eq!("1e".parse::<FloatLit>(), Err(FloatLitError::BadExponent));
+    assert_eq!("0x1.8".parse::<FloatLit>(),
+               Err(FloatLitError::MissingExponent));
+    assert_eq!("1.5.2".parse::<FloatLit>(),
+               Err(FloatLitError::BadSuffix(String::from(".2"))));
+}
@@ -0,0 +1,334 @@
+use salt::common::literal::BigUint;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// The radix in which a literal was written.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Radix {
+    /// Base 2, written with a `0b` prefix.
+    Binary,
+    /// Base 8, written with a `0o` prefix.
+    Octal,
+    /// Base 10, written with no prefix.
+    Decimal,
+    /// Base 16, written with a `0x` prefix.
+    Hex
+}
+
+/// Whether an integer type is signed.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Signedness {
+    /// Two's complement, with values from `-2^(n-1)` to `2^(n-1) - 1`.
+    Signed,
+    /// Values from 0 to `2^n - 1`.
+    Unsigned
+}
+
+/// An integer literal of any size, stored exactly.  The radix and
+/// suffix are kept so that diagnostics can show the literal as it was
+/// written.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct IntLit {
+    /// Whether the literal is negative.  This is never set for 0.
+    negative: bool,
+    /// The absolute value.
+    magnitude: BigUint,
+    /// The radix the literal was written in.
+    pub radix: Radix,
+    /// The type suffix, such as `u8`, if any.
+    pub suffix: Option<String>
+}
+
+/// Errors that can occur when parsing an `IntLit`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum IntLitError {
+    /// There were no digits.
+    NoDigits,
+    /// The suffix was not an identifier.
+    BadSuffix(String)
+}
+
+/// An error indicating that a literal does not fit in a fixed-width
+/// integer type.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct IntOverflow {
+    /// The width of the type, in bits.
+    pub bits: u32,
+    /// Whether the type is signed.
+    pub signed: bool
+}
+
+impl Radix {
+    /// Get the numeric value of this radix.
+    pub fn value(&self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+            Radix::Hex => 16
+        }
+    }
+
+    /// Get the prefix used to write literals in this radix.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Radix::Binary => "0b",
+            Radix::Octal => "0o",
+            Radix::Decimal => "",
+            Radix::Hex => "0x"
+        }
+    }
+}
+
+impl Signedness {
+    /// Get the prefix used for integer types of this signedness, as in
+    /// `i32` and `u32`.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Signedness::Signed => "i",
+            Signedness::Unsigned => "u"
+        }
+    }
+}
+
+impl IntLit {
+    /// Create an `IntLit` with no suffix.
+    pub fn new(negative: bool, magnitude: BigUint, radix: Radix) -> IntLit {
+        IntLit { negative: negative && !magnitude.is_zero(), magnitude,
+                 radix, suffix: None }
+    }
+
+    /// Create a decimal `IntLit` from an `i128`.
+    pub fn from_i128(n: i128) -> IntLit {
+        IntLit::new(n < 0, BigUint::from_u128(n.unsigned_abs()),
+                    Radix::Decimal)
+    }
+
+    /// Create a decimal `IntLit` from a `u128`.
+    pub fn from_u128(n: u128) -> IntLit {
+        IntLit::new(false, BigUint::from_u128(n), Radix::Decimal)
+    }
+
+    /// Check whether the literal is negative.
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Get the absolute value of the literal.
+    pub fn magnitude(&self) -> &BigUint {
+        &self.magnitude
+    }
+
+    /// Negate the literal.
+    pub fn neg(&self) -> IntLit {
+        IntLit { negative: !self.negative && !self.magnitude.is_zero(),
+                 ..self.clone() }
+    }
+
+    /// Check whether the literal is representable in an integer type
+    /// `width` bits wide.  A width of 0 holds only 0.
+    pub fn fits(&self, width: u32, signedness: Signedness) -> bool {
+        let bits = self.magnitude.bits();
+        let width = width as u64;
+
+        match signedness {
+            Signedness::Unsigned => !self.negative && bits <= width,
+            // The most negative value, -2^(width-1), is the one case
+            // where the magnitude needs all `width` bits.
+            Signedness::Signed if self.negative =>
+                bits < width ||
+                (bits == width &&
+                 self.magnitude.trailing_zeros() == Some(width - 1)),
+            Signedness::Signed => bits == 0 || bits < width
+        }
+    }
+
+    /// Convert to an unsigned integer `bits` wide, which must be at
+    /// most 128.
+    pub fn to_u128(&self, bits: u32) -> ::std::result::Result<u128,
+                                                              IntOverflow> {
+        assert!(bits <= 128, "integer width {} is too large", bits);
+
+        match self.magnitude.to_u128() {
+            Some(n) if !self.negative &&
+                       self.magnitude.bits() <= bits as u64 => Ok(n),
+            _ => Err(IntOverflow { bits, signed: false })
+        }
+    }
+
+    /// Convert to a signed integer `bits` wide, which must be between
+    /// 1 and 128.
+    pub fn to_i128(&self, bits: u32) -> ::std::result::Result<i128,
+                                                              IntOverflow> {
+        assert!((1..=128).contains(&bits), "invalid integer width {}", bits);
+
+        let err = IntOverflow { bits, signed: true };
+        let n = self.magnitude.to_u128().ok_or(err)?;
+        let limit = 1u128 << (bits - 1);
+
+        if self.negative && n <= limit {
+            Ok((n as i128).wrapping_neg())
+        } else if !self.negative && n < limit {
+            Ok(n as i128)
+        } else {
+            Err(err)
+        }
+    }
+}
+
+impl FromStr for IntLit {
+    type Err = IntLitError;
+
+    /// Parse a literal: an optional `-`, an optional radix prefix,
+    /// digits with optional `_` separators, and an optional suffix.
+    /// The suffix starts at the first character that is not a digit
+    /// in the radix, so `0xffu8` has the suffix `u8`.
+    fn from_str(s: &str) -> ::std::result::Result<IntLit, IntLitError> {
+        let (negative, rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s)
+        };
+        let (radix, rest) = match rest.get(..2) {
+            Some("0x") | Some("0X") => (Radix::Hex, &rest[2..]),
+            Some("0o") | Some("0O") => (Radix::Octal, &rest[2..]),
+            Some("0b") | Some("0B") => (Radix::Binary, &rest[2..]),
+            _ => (Radix::Decimal, rest)
+        };
+        let end = rest.find(|c: char| c != '_' && !c.is_digit(radix.value()))
+            .unwrap_or(rest.len());
+        let digits: String = rest[..end].chars()
+            .filter(|&c| c != '_')
+            .collect();
+        let suffix = &rest[end..];
+        let magnitude = BigUint::from_str_radix(&digits, radix.value())
+            .ok_or(IntLitError::NoDigits)?;
+        let mut out = IntLit::new(negative, magnitude, radix);
+
+        if !suffix.is_empty() {
+            if !suffix.starts_with(|c: char| c.is_alphabetic()) ||
+               !suffix.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(IntLitError::BadSuffix(suffix.to_string()));
+            }
+
+            out.suffix = Some(suffix.to_string());
+        }
+
+        Ok(out)
+    }
+}
+
+impl Display for Radix {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Radix::Binary => write!(f, "binary"),
+            Radix::Octal => write!(f, "octal"),
+            Radix::Decimal => write!(f, "decimal"),
+            Radix::Hex => write!(f, "hexadecimal")
+        }
+    }
+}
+
+/// Literals are written in their original radix, with their suffix.
+impl Display for IntLit {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+
+        write!(f, "{}{}", self.radix.prefix(),
+               self.magnitude.to_str_radix(self.radix.value()))?;
+
+        match &self.suffix {
+            Some(suffix) => write!(f, "{}", suffix),
+            None => Ok(())
+        }
+    }
+}
+
+impl Display for Signedness {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Signedness::Signed => write!(f, "signed"),
+            Signedness::Unsigned => write!(f, "unsigned")
+        }
+    }
+}
+
+impl Display for IntLitError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            IntLitError::NoDigits => write!(f, "integer literal has no digits"),
+            IntLitError::BadSuffix(suffix) =>
+                write!(f, "invalid suffix `{}` for integer literal", suffix)
+        }
+    }
+}
+
+impl Display for IntOverflow {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "literal out of range for {}{}",
+               if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
+#[test]
+fn test_parse_and_display() {
+    let lit: IntLit = "0xFF_FFu16".parse().unwrap();
+
+    assert_eq!(lit.radix, Radix::Hex);
+    assert_eq!(lit.suffix, Some(String::from("u16")));
+    assert_eq!(lit.to_u128(16), Ok(0xffff));
+    assert_eq!(lit.to_string(), "0xffffu16");
+
+    let lit: IntLit = "-0b1010".parse().unwrap();
+
+    assert!(lit.is_negative());
+    assert_eq!(lit.to_i128(8), Ok(-10));
+    assert_eq!(lit.to_string(), "-0b1010");
+    assert_eq!("-0".parse::<IntLit>().unwrap().to_string(), "0");
+
+    let big = "340282366920938463463374607431768211456";
+
+    assert_eq!(big.parse::<IntLit>().unwrap().to_string(), big);
+}
+
+#[test]
+fn test_parse_bad() {
+    assert_eq!("0x".parse::<IntLit>(), Err(IntLitError::NoDigits));
+    assert_eq!("_".parse::<IntLit>(), Err(IntLitError::NoDigits));
+    assert_eq!("12.5".parse::<IntLit>(),
+               Err(IntLitError::BadSuffix(String::from(".5"))));
+}
+
+#[test]
+fn test_conversion() {
+    let lit: IntLit = "128".parse().unwrap();
+
+    assert_eq!(lit.to_u128(8), Ok(128));
+    assert_eq!(lit.to_i128(8), Err(IntOverflow { bits: 8, signed: true }));
+    assert_eq!(lit.neg().to_i128(8), Ok(-128));
+    assert_eq!(lit.neg().to_u128(8), Err(IntOverflow { bits: 8,
+                                                       signed: false }));
+    assert_eq!(IntLit::from_i128(i128::MIN).to_i128(128), Ok(i128::MIN));
+    assert_eq!(IntLit::from_u128(u128::MAX).to_u128(128), Ok(u128::MAX));
+    assert_eq!(IntOverflow { bits: 8, signed: false }.to_string(),
+               "literal out of range for u8");
+}
+
+#[test]
+fn test_fits() {
+    let min: IntLit = "-9223372036854775808".parse().unwrap();
+
+    assert!(min.fits(64, Signedness::Signed));
+    assert!(!min.fits(64, Signedness::Unsigned));
+    assert!(!min.neg().fits(64, Signedness::Signed));
+    assert!(min.neg().fits(64, Signedness::Unsigned));
+    assert!(!"-9223372036854775809".parse::<IntLit>().unwrap()
+            .fits(64, Signedness::Signed));
+    assert!(!"-3".parse::<IntLit>().unwrap().fits(2, Signedness::Signed));
+    assert!(IntLit::from_i128(0).fits(0, Signedness::Signed));
+    assert!(!IntLit::from_i128(1).fits(1, Signedness::Signed));
+    assert!(IntLit::from_i128(-1).fits(1, Signedness::Signed));
+}
@@ -0,0 +1,39 @@
+//! Literal values.
+//!
+//! Literals are stored exactly, independent of any target, so that
+//! constant folding gives the same results whatever machine the
+//! compiler runs on.  Each literal also keeps enough of its original
+//! spelling for diagnostics to show it as it was written.
+
+pub use self::bigint::BigUint;
+pub use self::constant::ConstValue;
+pub use self::convert::ConversionLoss;
+pub use self::convert::IntConversion;
+pub use self::convert::NumType;
+pub use self::float::FloatFormat;
+pub use self::float::FloatLit;
+pub use self::float::FloatLitError;
+pub use self::float::Rounding;
+pub use self::float::SoftFloat;
+pub use self::int::IntLit;
+pub use self::int::IntLitError;
+pub use self::int::IntOverflow;
+pub use self::int::Radix;
+pub use self::int::Signedness;
+pub use self::rational::RationalLit;
+pub use self::rational::RationalLitError;
+pub use self::rational::RoundingMode;
+pub use self::text::CharLit;
+pub use self::text::Escape;
+pub use self::text::EscapeKind;
+pub use self::text::StrLit;
+pub use self::text::TextLitError;
+pub use self::text::TextLitErrorKind;
+
+mod bigint;
+mod constant;
+mod convert;
+mod float;
+mod int;
+mod rational;
+mod text;
@@ -0,0 +1,352 @@
+use salt::common::literal::BigUint;
+use salt::common::literal::IntLit;
+use salt::common::literal::Radix;
+use salt::common::literal::Rounding;
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// How to round a value that falls between two representable values.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum RoundingMode {
+    /// Round toward zero, discarding the fraction.
+    TowardZero,
+    /// Round toward negative infinity.
+    Floor,
+    /// Round toward positive infinity.
+    Ceiling,
+    /// Round to the nearest value, with ties going to the even one.
+    #[default]
+    NearestEven,
+    /// Round to the nearest value, with ties going away from zero.
+    NearestAway
+}
+
+/// An exact rational number, held as an arbitrary-precision fraction in
+/// lowest terms.  This is meant for frontends that need exact
+/// fractional constants, such as decimal amounts or fixed-point
+/// coefficients, which binary floating point cannot represent.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RationalLit {
+    /// Whether the value is negative.  This is never set for 0.
+    negative: bool,
+    /// The absolute value of the numerator.
+    num: BigUint,
+    /// The denominator, which is never 0.
+    den: BigUint
+}
+
+/// Errors that can occur when parsing a `RationalLit`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum RationalLitError {
+    /// A part of the literal had no digits, or had something other
+    /// than decimal digits.
+    BadDigits,
+    /// The denominator was 0.
+    ZeroDenominator
+}
+
+impl RoundingMode {
+    /// Get the name of this rounding mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoundingMode::TowardZero => "toward-zero",
+            RoundingMode::Floor => "floor",
+            RoundingMode::Ceiling => "ceiling",
+            RoundingMode::NearestEven => "nearest-even",
+            RoundingMode::NearestAway => "nearest-away"
+        }
+    }
+}
+
+impl RationalLit {
+    /// Create a `RationalLit` equal to `num / den`, reducing it to
+    /// lowest terms.  Panics if `den` is 0.
+    pub fn new(negative: bool, num: BigUint, den: BigUint) -> RationalLit {
+        assert!(!den.is_zero(), "zero denominator");
+
+        let gcd = num.gcd(&den);
+        let (num, _) = num.divrem(&gcd);
+        let (den, _) = den.divrem(&gcd);
+
+        RationalLit { negative: negative && !num.is_zero(), num, den }
+    }
+
+    /// Create a `RationalLit` equal to an integer literal.
+    pub fn from_int(n: &IntLit) -> RationalLit {
+        RationalLit { negative: n.is_negative(), num: n.magnitude().clone(),
+                      den: BigUint::from_u64(1) }
+    }
+
+    /// Create a `RationalLit` from a fixed-point value: the integer
+    /// `n` divided by `scale`.  Panics if `scale` is 0.
+    pub fn from_fixed(n: &IntLit, scale: &BigUint) -> RationalLit {
+        RationalLit::new(n.is_negative(), n.magnitude().clone(), scale.clone())
+    }
+
+    /// Check whether the value is negative.
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Check whether the value is an integer.
+    pub fn is_integer(&self) -> bool {
+        self.den == BigUint::from_u64(1)
+    }
+
+    /// Get the absolute value of the numerator, in lowest terms.
+    pub fn numerator(&self) -> &BigUint {
+        &self.num
+    }
+
+    /// Get the denominator, in lowest terms.
+    pub fn denominator(&self) -> &BigUint {
+        &self.den
+    }
+
+    /// Negate the value.
+    pub fn neg(&self) -> RationalLit {
+        RationalLit { negative: !self.negative && !self.num.is_zero(),
+                      ..self.clone() }
+    }
+
+    /// Add two values.
+    pub fn add(&self, other: &RationalLit) -> RationalLit {
+        let a = &self.num * &other.den;
+        let b = &other.num * &self.den;
+        let den = &self.den * &other.den;
+
+        if self.negative == other.negative {
+            RationalLit::new(self.negative, &a + &b, den)
+        } else if a >= b {
+            RationalLit::new(self.negative, &a - &b, den)
+        } else {
+            RationalLit::new(other.negative, &b - &a, den)
+        }
+    }
+
+    /// Subtract `other` from this value.
+    pub fn sub(&self, other: &RationalLit) -> RationalLit {
+        self.add(&other.neg())
+    }
+
+    /// Multiply two values.
+    pub fn mul(&self, other: &RationalLit) -> RationalLit {
+        RationalLit::new(self.negative != other.negative,
+                         &self.num * &other.num, &self.den * &other.den)
+    }
+
+    /// Divide this value by `other`, or return `None` if `other` is 0.
+    pub fn div(&self, other: &RationalLit) -> Option<RationalLit> {
+        if other.num.is_zero() {
+            None
+        } else {
+            Some(RationalLit::new(self.negative != other.negative,
+                                  &self.num * &other.den,
+                                  &self.den * &other.num))
+        }
+    }
+
+    /// Round to an integer using `mode`, and report whether rounding
+    /// occurred.  The result is only ever `Exact` or `Inexact`.
+    pub fn round(&self, mode: RoundingMode) -> (IntLit, Rounding) {
+        let (mut quot, rem) = self.num.divrem(&self.den);
+
+        if rem.is_zero() {
+            return (IntLit::new(self.negative, quot, Radix::Decimal),
+                    Rounding::Exact);
+        }
+
+        // Compare the discarded fraction to one half.
+        let half = (&rem << 1).cmp(&self.den);
+        let away = match mode {
+            RoundingMode::TowardZero => false,
+            RoundingMode::Floor => self.negative,
+            RoundingMode::Ceiling => !self.negative,
+            RoundingMode::NearestEven =>
+                half == Ordering::Greater ||
+                (half == Ordering::Equal && quot.bit(0)),
+            RoundingMode::NearestAway => half != Ordering::Less
+        };
+
+        if away {
+            quot.mul_add_small(1, 1);
+        }
+
+        (IntLit::new(self.negative, quot, Radix::Decimal), Rounding::Inexact)
+    }
+
+    /// Convert to a fixed-point value with the given scale: the result
+    /// is this value multiplied by `scale` and rounded to an integer
+    /// using `mode`.
+    pub fn to_fixed(&self, scale: &BigUint,
+                    mode: RoundingMode) -> (IntLit, Rounding) {
+        let scaled = RationalLit::new(self.negative, &self.num * scale,
+                                      self.den.clone());
+
+        scaled.round(mode)
+    }
+
+    /// Convert to a binary fixed-point value with `frac_bits` bits
+    /// after the point, as in the Q number format.
+    pub fn to_binary_fixed(&self, frac_bits: u32,
+                           mode: RoundingMode) -> (IntLit, Rounding) {
+        self.to_fixed(&BigUint::pow(2, frac_bits), mode)
+    }
+
+    /// Convert to a decimal fixed-point value with `digits` digits
+    /// after the point.
+    pub fn to_decimal_fixed(&self, digits: u32,
+                            mode: RoundingMode) -> (IntLit, Rounding) {
+        self.to_fixed(&BigUint::pow(10, digits), mode)
+    }
+}
+
+impl Ord for RationalLit {
+    fn cmp(&self, other: &RationalLit) -> Ordering {
+        let a = &self.num * &other.den;
+        let b = &other.num * &self.den;
+
+        match (self.negative, other.negative) {
+            (false, false) => a.cmp(&b),
+            (true, true) => b.cmp(&a),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less
+        }
+    }
+}
+
+impl PartialOrd for RationalLit {
+    fn partial_cmp(&self, other: &RationalLit) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for RationalLit {
+    type Err = RationalLitError;
+
+    /// Parse an optional `-` followed by decimal digits, with an
+    /// optional fractional part (`1.25`) or denominator (`5/4`).
+    fn from_str(s: &str) -> ::std::result::Result<RationalLit,
+                                                  RationalLitError> {
+        let (negative, rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s)
+        };
+        let digits = |s: &str| BigUint::from_str_radix(s, 10)
+            .ok_or(RationalLitError::BadDigits);
+
+        if let Some((num, den)) = rest.split_once('/') {
+            let den = digits(den)?;
+
+            if den.is_zero() {
+                return Err(RationalLitError::ZeroDenominator);
+            }
+
+            Ok(RationalLit::new(negative, digits(num)?, den))
+        } else if let Some((int, frac)) = rest.split_once('.') {
+            let scale = BigUint::pow(10, frac.len() as u32);
+            let num = &(&digits(int)? * &scale) + &digits(frac)?;
+
+            Ok(RationalLit::new(negative, num, scale))
+        } else {
+            Ok(RationalLit::new(negative, digits(rest)?,
+                                BigUint::from_u64(1)))
+        }
+    }
+}
+
+impl Display for RoundingMode {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Values are written in lowest terms, as `-3/4`, or as an integer if
+/// the denominator is 1.
+impl Display for RationalLit {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+
+        if self.is_integer() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl Display for RationalLitError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            RationalLitError::BadDigits =>
+                write!(f, "expected decimal digits in rational literal"),
+            RationalLitError::ZeroDenominator =>
+                write!(f, "rational literal has a zero denominator")
+        }
+    }
+}
+
+#[test]
+fn test_parse_and_arithmetic() {
+    let a: RationalLit = "6/8".parse().unwrap();
+    let b: RationalLit = "-1.25".parse().unwrap();
+
+    assert_eq!(a.to_string(), "3/4");
+    assert_eq!(b.to_string(), "-5/4");
+    assert_eq!(a.add(&b).to_string(), "-1/2");
+    assert_eq!(a.sub(&b).to_string(), "2");
+    assert_eq!(a.mul(&b).to_string(), "-15/16");
+    assert_eq!(a.div(&b).unwrap().to_string(), "-3/5");
+    assert_eq!(a.div(&"0.0".parse().unwrap()), None);
+    assert!(b < a);
+    assert_eq!(a.add(&a.neg()).to_string(), "0");
+    assert_eq!("1/0".parse::<RationalLit>(),
+               Err(RationalLitError::ZeroDenominator));
+    assert_eq!("1.".parse::<RationalLit>(), Err(RationalLitError::BadDigits));
+}
+
+#[test]
+fn test_round() {
+    let modes = [RoundingMode::TowardZero, RoundingMode::Floor,
+                 RoundingMode::Ceiling, RoundingMode::NearestEven,
+                 RoundingMode::NearestAway];
+    let cases = [("5/2", [2, 2, 3, 2, 3]),
+                 ("-5/2", [-2, -3, -2, -2, -3]),
+                 ("7/2", [3, 3, 4, 4, 4]),
+                 ("-2.6", [-2, -3, -2, -3, -3])];
+
+    for (value, expected) in cases.iter() {
+        let value: RationalLit = value.parse().unwrap();
+
+        for (mode, &expected) in modes.iter().zip(expected.iter()) {
+            let (n, rounding) = value.round(*mode);
+
+            assert_eq!(n.to_i128(64), Ok(expected), "{} {}", value, mode);
+            assert_eq!(rounding, Rounding::Inexact);
+        }
+    }
+
+    assert_eq!("-4".parse::<RationalLit>().unwrap()
+               .round(RoundingMode::Floor).1, Rounding::Exact);
+}
+
+#[test]
+fn test_fixed() {
+    let value: RationalLit = "1/3".parse().unwrap();
+    let (n, rounding) = value.to_decimal_fixed(4, RoundingMode::NearestEven);
+
+    assert_eq!(n.to_i128(32), Ok(3333));
+    assert_eq!(rounding, Rounding::Inexact);
+
+    let value: RationalLit = "-0.75".parse().unwrap();
+    let (n, rounding) = value.to_binary_fixed(15, RoundingMode::NearestEven);
+
+    assert_eq!(n.to_i128(16), Ok(-24576));
+    assert_eq!(rounding, Rounding::Exact);
+    assert_eq!(RationalLit::from_fixed(&n, &BigUint::pow(2, 15)), value);
+}
@@ -0,0 +1,484 @@
+use salt::common::position::Location;
+use salt::common::position::Point;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::ops::Range;
+
+/// The kind of an escape sequence.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EscapeKind {
+    /// A single-character escape: `\n`, `\r`, `\t`, `\\`, `\0`, `\'`
+    /// or `\"`.
+    Simple,
+    /// A byte escape, `\xNN`, limited to ASCII.
+    Hex,
+    /// A Unicode escape, `\u{N}`, with 1 to 6 hex digits.
+    Unicode,
+    /// A line continuation: a `\` at the end of a line, which removes
+    /// the line break, `\n` or `\r\n`, and the whitespace that follows
+    /// it.
+    Continuation
+}
+
+/// An escape sequence in a string or character literal.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Escape {
+    /// The kind of escape.
+    pub kind: EscapeKind,
+    /// The byte range of the escape in the literal's spelling.
+    pub spelling: Range<usize>,
+    /// The byte range of the text the escape produced, in the
+    /// literal's value.  This is empty for line continuations.
+    pub value: Range<usize>
+}
+
+/// A string literal.  Both the spelling (including quotes) and the
+/// value are kept, along with the position of every escape, so that a
+/// diagnostic about part of the value can point at the text that
+/// produced it.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct StrLit {
+    spelling: String,
+    value: String,
+    escapes: Vec<Escape>
+}
+
+/// A character literal.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CharLit {
+    spelling: String,
+    value: char,
+    escape: Option<Escape>
+}
+
+/// The kind of an error in a string or character literal.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TextLitErrorKind {
+    /// The literal was not enclosed in the right quotes.
+    Unterminated,
+    /// A quote inside the literal was not escaped.
+    BareQuote,
+    /// A `\` was followed by an unrecognized character.
+    UnknownEscape,
+    /// A `\x` escape did not have two hex digits.
+    BadHex,
+    /// A `\x` escape was above `\x7f`.
+    HexOutOfRange,
+    /// A `\u` escape was malformed.
+    BadUnicode,
+    /// A `\u` escape was not a Unicode scalar value.
+    UnicodeOutOfRange,
+    /// A character literal did not contain exactly one character.
+    CharLength
+}
+
+/// An error in a string or character literal.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TextLitError {
+    /// What was wrong.
+    pub kind: TextLitErrorKind,
+    /// The byte range of the problem in the literal's spelling.
+    pub span: Range<usize>
+}
+
+fn error<T>(kind: TextLitErrorKind,
+            span: Range<usize>) -> ::std::result::Result<T, TextLitError> {
+    Err(TextLitError { kind, span })
+}
+
+/// Decode the body of a literal (the spelling between the quotes,
+/// which starts at byte `base` of the spelling), appending to `value`
+/// and recording escapes.
+fn unescape(body: &str, base: usize, quote: char, value: &mut String,
+            escapes: &mut Vec<Escape>)
+            -> ::std::result::Result<(), TextLitError> {
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((idx, c)) = chars.next() {
+        let start = base + idx;
+
+        if c == quote {
+            return error(TextLitErrorKind::BareQuote, start..start + 1);
+        }
+
+        if c != '\\' {
+            value.push(c);
+
+            continue;
+        }
+
+        let value_start = value.len();
+        let (kind, end) = match chars.next() {
+            Some((i, c)) => {
+                let simple = match c {
+                    'n' => Some('\n'),
+                    'r' => Some('\r'),
+                    't' => Some('\t'),
+                    '\\' => Some('\\'),
+                    '0' => Some('\0'),
+                    '\'' => Some('\''),
+                    '"' => Some('"'),
+                    _ => None
+                };
+
+                match (simple, c) {
+                    (Some(out), _) => {
+                        value.push(out);
+
+                        (EscapeKind::Simple, i + 1)
+                    }
+                    (None, 'x') => {
+                        let digits = body.get(i + 1..i + 3)
+                            .filter(|d| d.chars()
+                                    .all(|c| c.is_ascii_hexdigit()));
+                        let digits = match digits {
+                            Some(digits) => digits,
+                            None => return error(TextLitErrorKind::BadHex,
+                                                 start..base + i + 1)
+                        };
+                        let byte = u8::from_str_radix(digits, 16).unwrap();
+
+                        if byte > 0x7f {
+                            return error(TextLitErrorKind::HexOutOfRange,
+                                         start..base + i + 3);
+                        }
+
+                        chars.next();
+                        chars.next();
+                        value.push(byte as char);
+
+                        (EscapeKind::Hex, i + 3)
+                    }
+                    (None, 'u') => {
+                        let rest = &body[i + 1..];
+                        let close = match rest.find('}') {
+                            Some(close) if rest.starts_with('{') => close,
+                            _ => return error(TextLitErrorKind::BadUnicode,
+                                              start..base + i + 1)
+                        };
+                        let digits = &rest[1..close];
+                        let end = i + 1 + close + 1;
+
+                        if digits.is_empty() || digits.len() > 6 ||
+                           !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                            return error(TextLitErrorKind::BadUnicode,
+                                         start..base + end);
+                        }
+
+                        let code = u32::from_str_radix(digits, 16).unwrap();
+                        let out = match ::std::char::from_u32(code) {
+                            Some(out) => out,
+                            None => return error(
+                                TextLitErrorKind::UnicodeOutOfRange,
+                                start..base + end)
+                        };
+
+                        while chars.peek().is_some_and(|&(j, _)| j < end) {
+                            chars.next();
+                        }
+
+                        value.push(out);
+
+                        (EscapeKind::Unicode, end)
+                    }
+                    (None, '\n') | (None, '\r')
+                        if c == '\n' || body[i + 1..].starts_with('\n') => {
+                        let mut end = i + 1;
+
+                        while let Some(&(j, c)) = chars.peek() {
+                            if !c.is_whitespace() {
+                                break;
+                            }
+
+                            chars.next();
+                            end = j + c.len_utf8();
+                        }
+
+                        (EscapeKind::Continuation, end)
+                    }
+                    (None, c) => return error(TextLitErrorKind::UnknownEscape,
+                                              start..base + i + c.len_utf8())
+                }
+            }
+            // The closing quote was escaped.
+            None => return error(TextLitErrorKind::Unterminated,
+                                 start..start + 2)
+        };
+
+        escapes.push(Escape { kind, spelling: start..base + end,
+                              value: value_start..value.len() });
+    }
+
+    Ok(())
+}
+
+/// Get the body of a literal enclosed in `quote`.
+fn body(spelling: &str, quote: char)
+        -> ::std::result::Result<&str, TextLitError> {
+    if spelling.len() >= 2 && spelling.starts_with(quote) &&
+       spelling.ends_with(quote) {
+        Ok(&spelling[1..spelling.len() - 1])
+    } else {
+        error(TextLitErrorKind::Unterminated, 0..spelling.len())
+    }
+}
+
+/// Get the location of the byte range `range` of `spelling`, given the
+/// point at which the spelling starts.  Columns count characters.
+fn location(spelling: &str, range: &Range<usize>, start: Point) -> Location {
+    let point_at = |offset: usize| {
+        spelling[..offset].chars().fold(start, |point, c| if c == '\n' {
+            point.next_line()
+        } else {
+            point.next_col()
+        })
+    };
+
+    if range.is_empty() {
+        Location::Point { point: point_at(range.start) }
+    } else {
+        Location::Span { start: point_at(range.start),
+                         end: point_at(range.end) }
+    }
+}
+
+impl StrLit {
+    /// Parse a string literal, given its spelling including the
+    /// enclosing double quotes.
+    pub fn parse(spelling: &str) -> ::std::result::Result<StrLit,
+                                                          TextLitError> {
+        let body = body(spelling, '"')?;
+        let mut value = String::with_capacity(body.len());
+        let mut escapes = Vec::new();
+
+        unescape(body, 1, '"', &mut value, &mut escapes)?;
+
+        Ok(StrLit { spelling: spelling.to_string(), value, escapes })
+    }
+
+    /// Get the literal as it was written, including quotes.
+    pub fn spelling(&self) -> &str {
+        &self.spelling
+    }
+
+    /// Get the value of the literal, with escapes decoded.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Get the escapes in the literal, in order.
+    pub fn escapes(&self) -> &[Escape] {
+        &self.escapes
+    }
+
+    /// Get the byte range of the spelling that produced the byte at
+    /// `offset` in the value.  This is the whole escape if the byte
+    /// came from one.
+    pub fn spelling_span(&self, offset: usize) -> Range<usize> {
+        // Bytes between escapes are copied verbatim, so the spelling
+        // is offset from the value by the growth of the escapes so far.
+        let mut delta = 1;
+
+        for escape in &self.escapes {
+            if escape.value.contains(&offset) {
+                return escape.spelling.clone();
+            } else if escape.value.start > offset {
+                break;
+            }
+
+            delta += escape.spelling.len() - escape.value.len();
+        }
+
+        let len = self.value[offset..].chars().next()
+            .map_or(1, |c| c.len_utf8());
+
+        offset + delta..offset + delta + len
+    }
+
+    /// Get the location of the byte range `range` of the spelling,
+    /// given the point at which the literal starts.
+    pub fn location(&self, range: &Range<usize>, start: Point) -> Location {
+        location(&self.spelling, range, start)
+    }
+}
+
+impl CharLit {
+    /// Parse a character literal, given its spelling including the
+    /// enclosing single quotes.
+    pub fn parse(spelling: &str) -> ::std::result::Result<CharLit,
+                                                          TextLitError> {
+        let body = body(spelling, '\'')?;
+        let mut value = String::new();
+        let mut escapes = Vec::new();
+
+        unescape(body, 1, '\'', &mut value, &mut escapes)?;
+
+        let mut chars = value.chars();
+
+        match (chars.next(), chars.next(), escapes.len() <= 1) {
+            (Some(c), None, true) =>
+                Ok(CharLit { spelling: spelling.to_string(), value: c,
+                             escape: escapes.pop() }),
+            _ => error(TextLitErrorKind::CharLength, 0..spelling.len())
+        }
+    }
+
+    /// Get the literal as it was written, including quotes.
+    pub fn spelling(&self) -> &str {
+        &self.spelling
+    }
+
+    /// Get the value of the literal.
+    pub fn value(&self) -> char {
+        self.value
+    }
+
+    /// Get the escape that produced the value, if there was one.
+    pub fn escape(&self) -> Option<&Escape> {
+        self.escape.as_ref()
+    }
+
+    /// Get the location of the byte range `range` of the spelling,
+    /// given the point at which the literal starts.
+    pub fn location(&self, range: &Range<usize>, start: Point) -> Location {
+        location(&self.spelling, range, start)
+    }
+}
+
+/// Literals are written as they were in the source.
+impl Display for StrLit {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.spelling)
+    }
+}
+
+/// Literals are written as they were in the source.
+impl Display for CharLit {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.spelling)
+    }
+}
+
+impl Display for TextLitErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            TextLitErrorKind::Unterminated => write!(f, "unterminated literal"),
+            TextLitErrorKind::BareQuote => write!(f, "unescaped quote"),
+            TextLitErrorKind::UnknownEscape =>
+                write!(f, "unknown escape sequence"),
+            TextLitErrorKind::BadHex =>
+                write!(f, "expected two hex digits after `\\x`"),
+            TextLitErrorKind::HexOutOfRange =>
+                write!(f, "`\\x` escape out of range, must be at most \
+                           `\\x7f`"),
+            TextLitErrorKind::BadUnicode =>
+                write!(f, "expected 1 to 6 hex digits in braces after `\\u`"),
+            TextLitErrorKind::UnicodeOutOfRange =>
+                write!(f, "`\\u` escape is not a Unicode scalar value"),
+            TextLitErrorKind::CharLength =>
+                write!(f, "character literal must contain exactly one \
+                           character")
+        }
+    }
+}
+
+impl Display for TextLitError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+#[test]
+fn test_str_escapes() {
+    let lit = StrLit::parse("\"a\\tb\\x41\\u{e9}c\"").unwrap();
+
+    assert_eq!(lit.value(), "a\tbA\u{e9}c");
+    assert_eq!(lit.escapes().len(), 3);
+    assert_eq!(lit.escapes()[1], Escape { kind: EscapeKind::Hex,
+                                          spelling: 5..9, value: 3..4 });
+    assert_eq!(&lit.spelling()[lit.escapes()[2].spelling.clone()],
+               "\\u{e9}");
+    assert_eq!(lit.to_string(), "\"a\\tb\\x41\\u{e9}c\"");
+}
+
+#[test]
+fn test_spelling_span() {
+    let lit = StrLit::parse("\"ab\\0cd\\u{e9}e\"").unwrap();
+    let nul = lit.value().find('\0').unwrap();
+
+    assert_eq!(lit.spelling_span(nul), 3..5);
+    assert_eq!(lit.spelling_span(0), 1..2);
+    assert_eq!(&lit.spelling()[lit.spelling_span(3)], "c");
+    assert_eq!(&lit.spelling()[lit.spelling_span(5)], "\\u{e9}");
+    assert_eq!(&lit.spelling()[lit.spelling_span(7)], "e");
+
+    match lit.location(&lit.spelling_span(nul), Point { line: 4, col: 10 }) {
+        Location::Span { start, end } => {
+            assert_eq!(start, Point { line: 4, col: 13 });
+            assert_eq!(end, Point { line: 4, col: 15 });
+        }
+        loc => panic!("expected a span, got {:?}", loc)
+    }
+}
+
+#[test]
+fn test_continuation() {
+    let lit = StrLit::parse("\"one \\\n    two\"").unwrap();
+
+    assert_eq!(lit.value(), "one two");
+    assert_eq!(lit.escapes()[0].kind, EscapeKind::Continuation);
+    assert_eq!(&lit.spelling()[lit.spelling_span(4)], "t");
+
+    let crlf = StrLit::parse("\"ab\\\r\n   cd\"").unwrap();
+
+    assert_eq!(crlf.value(), "abcd");
+    assert_eq!(crlf.escapes()[0].kind, EscapeKind::Continuation);
+    assert_eq!(crlf.escapes()[0].spelling, 3..9);
+    assert_eq!(StrLit::parse("\"ab\\\rcd\"").unwrap_err().kind,
+               TextLitErrorKind::UnknownEscape);
+
+    match lit.location(&(11..12), Point { line: 1, col: 1 }) {
+        Location::Span { start, end } => {
+            assert_eq!(start, Point { line: 2, col: 5 });
+            assert_eq!(end, Point { line: 2, col: 6 });
+        }
+        loc => panic!("expected a span, got {:?}", loc)
+    }
+}
+
+#[test]
+fn test_str_errors() {
+    assert_eq!(StrLit::parse("\"abc").unwrap_err().kind,
+               TextLitErrorKind::Unterminated);
+    assert_eq!(StrLit::parse("\"abc\\\"").unwrap_err().kind,
+               TextLitErrorKind::Unterminated);
+    assert_eq!(StrLit::parse("\"abc\\\\\"").unwrap().value(), "abc\\");
+    assert_eq!(StrLit::parse("\"a\"b\"").unwrap_err(),
+               TextLitError { kind: TextLitErrorKind::BareQuote,
+                              span: 2..3 });
+    assert_eq!(StrLit::parse("\"a\\qb\"").unwrap_err(),
+               TextLitError { kind: TextLitErrorKind::UnknownEscape,
+                              span: 2..4 });
+    assert_eq!(StrLit::parse("\"\\xff\"").unwrap_err(),
+               TextLitError { kind: TextLitErrorKind::HexOutOfRange,
+                              span: 1..5 });
+    assert_eq!(StrLit::parse("\"\\u{d800}\"").unwrap_err(),
+               TextLitError { kind: TextLitErrorKind::UnicodeOutOfRange,
+                              span: 1..9 });
+    assert_eq!(StrLit::parse("\"\\u{}\"").unwrap_err().kind,
+               TextLitErrorKind::BadUnicode);
+}
+
+#[test]
+fn test_char_lit() {
+    let lit = CharLit::parse("'\\n'").unwrap();
+
+    assert_eq!(lit.value(), '\n');
+    assert_eq!(lit.escape().map(|e| e.spelling.clone()), Some(1..3));
+    assert_eq!(CharLit::parse("'\u{e9}'").unwrap().value(), '\u{e9}');
+    assert_eq!(CharLit::parse("'ab'").unwrap_err().kind,
+               TextLitErrorKind::CharLength);
+    assert_eq!(CharLit::parse("''").unwrap_err().kind,
+               TextLitErrorKind::CharLength);
+}
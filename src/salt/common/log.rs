@@ -0,0 +1,487 @@
+//! Phase timing and debug logging.
+//!
+//! Compiler phases are wrapped in `phase!`, which times them:
+//!
+//! ```ignore
+//! let typed = phase!("typecheck", {
+//!     log!(Debug, "checking {} items", items.len());
+//!     typecheck(items)
+//! });
+//! ```
+//!
+//! The wall time of every phase is always recorded, and `timings`
+//! gets the totals by phase name.  With the `alloc-stats` feature, and
+//! `memstats::CountingAlloc` installed, so are the bytes allocated and
+//! the peak heap usage.  A tool that wants a timeline calls
+//! `enable_trace` at startup, and `trace` at shutdown to get every
+//! phase, and every message logged, as events in the Chrome trace
+//! format, which `chrome://tracing` and Perfetto can show.
+//!
+//! Messages logged with `log!` are written to standard error if their
+//! level is enabled, prefixed with the innermost phase.  Only errors
+//! and warnings are enabled by default.
+
+use salt::common::json::Json;
+use salt::common::json::ToJson;
+#[cfg(feature = "alloc-stats")]
+use salt::common::memstats::PhaseStart;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fmt::Arguments;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The most verbose level being logged.
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+
+/// Whether trace events are being recorded.
+static TRACING: AtomicBool = AtomicBool::new(false);
+
+/// The time from which trace timestamps are measured.
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// The recorded timings and trace events.
+static STATE: Mutex<State> = Mutex::new(State { timings: Vec::new(),
+                                                events: Vec::new() });
+
+/// The next thread number to hand out.
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// The number of this thread in trace events.
+    static THREAD: Cell<u64> = const { Cell::new(0) };
+    /// The phases this thread is in, innermost last.
+    static PHASES: RefCell<Vec<&'static str>> = const {
+        RefCell::new(Vec::new())
+    };
+}
+
+/// The verbosity of a logged message.  Levels are ordered, with more
+/// verbose levels greater.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Level {
+    /// Problems that stop the tool.
+    Error,
+    /// Possible problems.
+    Warn,
+    /// Progress a user might want to see.
+    Info,
+    /// Details for debugging the tool.
+    Debug,
+    /// Everything.
+    Trace
+}
+
+/// The total wall time spent in a phase, and with the `alloc-stats`
+/// feature, the memory it used.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PhaseTiming {
+    /// The name of the phase.
+    pub name: &'static str,
+    /// The number of times the phase ran.
+    pub count: u64,
+    /// The total time, over all runs and threads.
+    pub total: Duration,
+    /// The total bytes allocated, over all runs and threads.
+    #[cfg(feature = "alloc-stats")]
+    pub allocated: u64,
+    /// The highest heap usage during any run.
+    #[cfg(feature = "alloc-stats")]
+    pub peak: usize
+}
+
+/// Times a phase, from when it is created until it is dropped.  Use
+/// `phase!` rather than creating these directly.
+#[derive(Debug)]
+pub struct PhaseGuard {
+    name: &'static str,
+    start: Instant,
+    #[cfg(feature = "alloc-stats")]
+    memory: PhaseStart
+}
+
+/// An event in the Chrome trace format: a phase, with its duration, or
+/// a logged message, at an instant.
+#[derive(Clone, Debug)]
+struct Event {
+    name: String,
+    category: &'static str,
+    thread: u64,
+    /// The start, in microseconds since `EPOCH`.
+    start: u64,
+    /// The duration in microseconds, for phases.
+    duration: Option<u64>
+}
+
+#[derive(Debug)]
+struct State {
+    /// Timings, in order of first completion.
+    timings: Vec<PhaseTiming>,
+    events: Vec<Event>
+}
+
+impl Level {
+    /// Get the name of this level, as shown in logged messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warning",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace"
+        }
+    }
+
+    fn from_u8(n: u8) -> Level {
+        match n {
+            0 => Level::Error,
+            1 => Level::Warn,
+            2 => Level::Info,
+            3 => Level::Debug,
+            _ => Level::Trace
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Log messages of level `level` and less verbose.
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed)
+}
+
+/// Get the most verbose level being logged.
+pub fn level() -> Level {
+    Level::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+/// Check whether messages of level `level` are logged.
+#[inline]
+pub fn is_enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Start recording trace events.
+pub fn enable_trace() {
+    EPOCH.get_or_init(Instant::now);
+    TRACING.store(true, Ordering::Relaxed)
+}
+
+/// Stop recording trace events.  Events recorded so far are kept.
+pub fn disable_trace() {
+    TRACING.store(false, Ordering::Relaxed)
+}
+
+/// Check whether trace events are being recorded.
+#[inline]
+pub fn is_tracing() -> bool {
+    TRACING.load(Ordering::Relaxed)
+}
+
+fn state() -> ::std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Get the number of the current thread in trace events.
+fn thread() -> u64 {
+    THREAD.with(|thread| {
+        if thread.get() == 0 {
+            thread.set(NEXT_THREAD.fetch_add(1, Ordering::Relaxed))
+        }
+
+        thread.get()
+    })
+}
+
+/// Get the time since `EPOCH` of `at`, in microseconds.
+fn micros(at: Instant) -> u64 {
+    let epoch = *EPOCH.get_or_init(Instant::now);
+
+    at.saturating_duration_since(epoch).as_micros() as u64
+}
+
+/// Get the innermost phase the current thread is in.
+pub fn current_phase() -> Option<&'static str> {
+    PHASES.with(|phases| phases.borrow().last().copied())
+}
+
+/// Log the message `args` at level `level`, if it is enabled.  Use
+/// `log!` rather than calling this directly.
+pub fn log(level: Level, args: Arguments) {
+    if !is_enabled(level) {
+        return;
+    }
+
+    let msg = args.to_string();
+
+    match current_phase() {
+        Some(phase) => eprintln!("[{}] {}: {}", phase, level, msg),
+        None => eprintln!("{}: {}", level, msg)
+    }
+
+    if is_tracing() {
+        let event = Event { name: msg, category: level.name(),
+                            thread: thread(), start: micros(Instant::now()),
+                            duration: None };
+
+        state().events.push(event)
+    }
+}
+
+/// Get the total time spent in each phase that has finished, in order
+/// of first completion.
+pub fn timings() -> Vec<PhaseTiming> {
+    state().timings.clone()
+}
+
+/// Get the trace events recorded so far, in the Chrome trace format.
+pub fn trace() -> Json {
+    let events: Vec<Json> = state().events.iter().map(|event| {
+        let json = Json::object()
+            .with("name", event.name.as_str())
+            .with("cat", event.category)
+            .with("ph", if event.duration.is_some() { "X" } else { "i" })
+            .with("ts", event.start)
+            .with("pid", 1u64)
+            .with("tid", event.thread);
+
+        match event.duration {
+            Some(duration) => json.with("dur", duration),
+            None => json.with("s", "t")
+        }
+    }).collect();
+
+    Json::object()
+        .with("traceEvents", events)
+        .with("displayTimeUnit", "ms")
+}
+
+impl PhaseGuard {
+    /// Start timing the phase `name`.
+    pub fn start(name: &'static str) -> PhaseGuard {
+        PHASES.with(|phases| phases.borrow_mut().push(name));
+
+        PhaseGuard { name, start: Instant::now(),
+                     #[cfg(feature = "alloc-stats")]
+                     memory: PhaseStart::now() }
+    }
+
+    /// Get the name of the phase.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the time since the phase started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        #[cfg(feature = "alloc-stats")]
+        let memory = self.memory.finish(self.name);
+        let mut state = state();
+
+        PHASES.with(|phases| phases.borrow_mut().pop());
+
+        match state.timings.iter_mut().find(|timing| timing.name == self.name) {
+            Some(timing) => {
+                timing.count += 1;
+                timing.total += elapsed;
+
+                #[cfg(feature = "alloc-stats")]
+                {
+                    timing.allocated += memory.allocated;
+                    timing.peak = timing.peak.max(memory.peak);
+                }
+            }
+            None => state.timings.push(PhaseTiming {
+                name: self.name, count: 1, total: elapsed,
+                #[cfg(feature = "alloc-stats")]
+                allocated: memory.allocated,
+                #[cfg(feature = "alloc-stats")]
+                peak: memory.peak
+            })
+        }
+
+        if is_tracing() {
+            let event = Event { name: self.name.to_string(),
+                                category: "phase", thread: thread(),
+                                start: micros(self.start),
+                                duration: Some(elapsed.as_micros() as u64) };
+
+            state.events.push(event)
+        }
+    }
+}
+
+impl ToJson for PhaseTiming {
+    fn to_json(&self) -> Json {
+        let json = Json::object()
+            .with("name", self.name)
+            .with("count", self.count)
+            .with("seconds", self.total.as_secs_f64());
+
+        #[cfg(feature = "alloc-stats")]
+        let json = json.with("allocated", self.allocated)
+            .with("peak", self.peak as u64);
+
+        json
+    }
+}
+
+/// Run the block `body` as the phase `name`, timing it, and with the
+/// `alloc-stats` feature measuring its memory, and produce the value
+/// of the block.  Given a `ProgressSink`, as in
+/// `phase!("parse", sess.progress(), { ... })`, the start and end of
+/// the phase are also reported to it.
+#[macro_export]
+macro_rules! phase {
+    ($name:expr, $sink:expr, $body:block) => {{
+        let _progress = $crate::salt::common::progress::PhaseProgress::start(
+            $sink, $name, None);
+
+        phase!($name, $body)
+    }};
+    ($name:expr, $body:block) => {{
+        let _phase = $crate::salt::common::log::PhaseGuard::start($name);
+
+        $body
+    }}
+}
+
+/// Log a message at a level, given by name, such as
+/// `log!(Debug, "checking {}", name)`.  The arguments are not
+/// evaluated if the level is not enabled.
+#[macro_export]
+macro_rules! log {
+    ($level:ident, $($arg:tt)+) => {{
+        let level = $crate::salt::common::log::Level::$level;
+
+        if $crate::salt::common::log::is_enabled(level) {
+            $crate::salt::common::log::log(level, format_args!($($arg)+))
+        }
+    }}
+}
+
+#[test]
+fn test_phase() {
+    let value = phase!("test_phase.outer", {
+        assert_eq!(current_phase(), Some("test_phase.outer"));
+
+        for _ in 0..3 {
+            phase!("test_phase.inner", {
+                assert_eq!(current_phase(), Some("test_phase.inner"));
+            })
+        }
+
+        42
+    });
+    let timings = timings();
+    let find = |name| timings.iter().find(|timing| timing.name == name);
+
+    assert_eq!(value, 42);
+    assert_eq!(current_phase(), None);
+    assert_eq!(find("test_phase.outer").map(|timing| timing.count), Some(1));
+    assert_eq!(find("test_phase.inner").map(|timing| timing.count), Some(3));
+    assert!(find("test_phase.outer").unwrap().total >=
+            find("test_phase.inner").unwrap().total);
+}
+
+#[test]
+fn test_phase_progress() {
+    use salt::common::progress::ProgressSink;
+
+    struct Record(Mutex<Vec<String>>);
+
+    impl ProgressSink for Record {
+        fn phase_started(&self, phase: &str, total: Option<u64>) {
+            self.0.lock().unwrap().push(format!("start {} {:?}", phase, total))
+        }
+
+        fn progress(&self, phase: &str, done: u64, _total: Option<u64>) {
+            self.0.lock().unwrap().push(format!("{} {}", phase, done))
+        }
+
+        fn phase_finished(&self, phase: &str) {
+            self.0.lock().unwrap().push(format!("finish {}", phase))
+        }
+    }
+
+    let sink = Record(Mutex::new(Vec::new()));
+    let value = phase!("test_phase_progress", &sink, {
+        assert_eq!(current_phase(), Some("test_phase_progress"));
+        42
+    });
+
+    assert_eq!(value, 42);
+    assert_eq!(current_phase(), None);
+    assert_eq!(sink.0.into_inner().unwrap(),
+               ["start test_phase_progress None",
+                "finish test_phase_progress"]);
+}
+
+#[cfg(feature = "alloc-stats")]
+#[test]
+fn test_phase_memory() {
+    let len = phase!("test_phase_memory.outer", {
+        phase!("test_phase_memory.inner", { vec![0u8; 1 << 20] }).len()
+    });
+    let timings = timings();
+    let find = |name| timings.iter().find(|timing| timing.name == name)
+        .unwrap();
+    let outer = find("test_phase_memory.outer");
+
+    assert_eq!(len, 1 << 20);
+    assert!(find("test_phase_memory.inner").allocated >= 1 << 20);
+    assert!(outer.allocated >= find("test_phase_memory.inner").allocated);
+    assert!(outer.peak >= 1 << 20);
+    assert!(outer.to_json().to_string().contains("\"allocated\":"));
+}
+
+#[test]
+fn test_trace() {
+    let was_tracing = is_tracing();
+
+    enable_trace();
+    phase!("test_trace.phase", {
+        log!(Error, "test_trace.message {}", 1);
+    });
+
+    // Tracing is global, so leave it as the other tests found it.
+    if !was_tracing {
+        disable_trace();
+    }
+
+    let trace = trace().to_string();
+
+    assert!(trace.starts_with("{\"traceEvents\":["));
+    assert!(trace.contains("{\"name\":\"test_trace.message 1\",\
+                            \"cat\":\"error\",\"ph\":\"i\""), "{}", trace);
+    assert!(trace.contains("{\"name\":\"test_trace.phase\",\
+                            \"cat\":\"phase\",\"ph\":\"X\""), "{}", trace);
+    assert!(trace.ends_with("],\"displayTimeUnit\":\"ms\"}"));
+}
+
+#[test]
+fn test_level() {
+    assert!(is_enabled(Level::Error));
+    assert!(Level::Debug > Level::Info);
+    assert_eq!(Level::from_u8(Level::Debug as u8), Level::Debug);
+    assert_eq!(Level::Warn.to_string(), "warning");
+}
@@ -0,0 +1,259 @@
+//! Symbol name mangling.
+//!
+//! Salt symbols are mangled using the syntax of the Itanium C++ ABI,
+//! so that existing tools such as `c++filt` can read them:
+//!
+//! - a name with one component is an unscoped name, `_Z3foo`; a name
+//!   with several is a nested name, `_ZN3foo3barE`
+//! - a function's parameter types follow its name, with `v` for a
+//!   function taking no parameters; data symbols have none
+//! - a nonzero disambiguator, which distinguishes items that would
+//!   otherwise have the same name, is an ABI tag `s<n>` on the last
+//!   component, so `_ZN3foo3barB2s1Ev` demangles as
+//!   `foo::bar[abi:s1]()`
+//! - a nonzero type arity, the number of type parameters of a generic
+//!   item named by a `SymbolPath`, is an ABI tag `t<n>` after any
+//!   disambiguator, so `_ZN3std3MapB2t2E` demangles as
+//!   `std::Map[abi:t2]`
+//!
+//! Names are not compressed with substitutions, so a mangled name is
+//! readable by any Itanium demangler, but is not necessarily the name
+//! a C++ compiler would produce for the same declaration.  Components
+//! must not be empty or start with a digit.
+
+use salt::common::str::arena::StrArena;
+use salt::common::str::intern::StrIntern;
+use salt::common::symbol::QualName;
+use salt::common::symbol::Symbol;
+use salt::common::symbol::SymbolCtx;
+use salt::common::symbol_path::SymbolPath;
+use std::fmt::Write;
+
+/// A type, as far as mangling is concerned.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TypeDesc<'a> {
+    /// The unit type (`v`).
+    Void,
+    /// The boolean type (`b`).
+    Bool,
+    /// A Unicode scalar value (`Di`, C++'s `char32_t`).
+    Char,
+    /// An integer type of the given width in bits and signedness.
+    Int {
+        /// The width in bits.
+        bits: u32,
+        /// Whether the type is signed.
+        signed: bool
+    },
+    /// A floating point type of the given width in bits.
+    Float(u32),
+    /// A pointer (`P`).
+    Ptr(Box<TypeDesc<'a>>),
+    /// A reference (`R`).
+    Ref(Box<TypeDesc<'a>>),
+    /// An array with the given number of elements (`A<n>_`).
+    Array(u64, Box<TypeDesc<'a>>),
+    /// A named type.
+    Named(QualName<'a>)
+}
+
+/// The prefix of every mangled name.
+pub const PREFIX: &str = "_Z";
+
+fn push_source_name(out: &mut String, name: &str) {
+    debug_assert!(!name.is_empty() &&
+                  !name.as_bytes()[0].is_ascii_digit(),
+                  "unmangleable name component {:?}", name);
+
+    write!(out, "{}{}", name.len(), name).unwrap();
+}
+
+/// Write the ABI tag `<kind><n>`, if `n` is not 0.
+fn push_abi_tag(out: &mut String, kind: char, n: u32) {
+    if n != 0 {
+        let tag = format!("{}{}", kind, n);
+
+        write!(out, "B{}{}", tag.len(), tag).unwrap();
+    }
+}
+
+fn push_abi_tags(out: &mut String, disambiguator: u32, arity: u32) {
+    push_abi_tag(out, 's', disambiguator);
+    push_abi_tag(out, 't', arity);
+}
+
+fn push_name(out: &mut String, path: &[Symbol], disambiguator: u32,
+             arity: u32) {
+    match path {
+        [sym] => {
+            push_source_name(out, sym.as_str());
+            push_abi_tags(out, disambiguator, arity);
+        }
+        path => {
+            out.push('N');
+
+            for sym in path {
+                push_source_name(out, sym.as_str());
+            }
+
+            push_abi_tags(out, disambiguator, arity);
+            out.push('E');
+        }
+    }
+}
+
+fn push_type(out: &mut String, ty: &TypeDesc) {
+    match ty {
+        TypeDesc::Void => out.push('v'),
+        TypeDesc::Bool => out.push('b'),
+        TypeDesc::Char => out.push_str("Di"),
+        TypeDesc::Int { bits, signed } => match (bits, signed) {
+            (8, true) => out.push('a'),
+            (8, false) => out.push('h'),
+            (16, true) => out.push('s'),
+            (16, false) => out.push('t'),
+            (32, true) => out.push('i'),
+            (32, false) => out.push('j'),
+            (64, true) => out.push('x'),
+            (64, false) => out.push('y'),
+            (128, true) => out.push('n'),
+            (128, false) => out.push('o'),
+            (bits, true) => push_vendor_type(out, 'i', *bits),
+            (bits, false) => push_vendor_type(out, 'u', *bits)
+        },
+        TypeDesc::Float(bits) => match bits {
+            32 => out.push('f'),
+            64 => out.push('d'),
+            80 => out.push('e'),
+            128 => out.push('g'),
+            bits => push_vendor_type(out, 'f', *bits)
+        },
+        TypeDesc::Ptr(inner) => {
+            out.push('P');
+            push_type(out, inner)
+        }
+        TypeDesc::Ref(inner) => {
+            out.push('R');
+            push_type(out, inner)
+        }
+        TypeDesc::Array(len, inner) => {
+            write!(out, "A{}_", len).unwrap();
+            push_type(out, inner)
+        }
+        TypeDesc::Named(name) => push_name(out, name.path(), 0, 0)
+    }
+}
+
+/// Write a vendor extended type, `u<len><kind><bits>`, for types with
+/// no Itanium code, such as `i24`.
+fn push_vendor_type(out: &mut String, kind: char, bits: u32) {
+    let name = format!("{}{}", kind, bits);
+
+    write!(out, "u{}{}", name.len(), name).unwrap();
+}
+
+/// Mangle `name`.  `params` are the parameter types of a function, or
+/// `None` for a data symbol; `disambiguator` is 0 unless the name must
+/// be distinguished from another with the same path.
+pub fn mangle(name: &QualName, params: Option<&[TypeDesc]>,
+              disambiguator: u32) -> String {
+    let mut out = String::from(PREFIX);
+
+    push_name(&mut out, name.path(), disambiguator, 0);
+
+    match params {
+        Some([]) => out.push('v'),
+        Some(params) => {
+            for param in params {
+                push_type(&mut out, param)
+            }
+        }
+        None => {}
+    }
+
+    out
+}
+
+/// Mangle `path`, the name of an item with `arity` type parameters,
+/// as a data symbol.  `demangle_path` reverses this.  Panics if `path`
+/// is empty.
+pub fn mangle_path(path: &SymbolPath, arity: u32) -> String {
+    let mut out = String::from(PREFIX);
+
+    assert!(!path.is_empty(), "mangling the root path");
+    push_name(&mut out, path.segments(), 0, arity);
+
+    out
+}
+
+/// Mangle `name` as `mangle` does, and intern the result in `intern`,
+/// keeping the mangled name in `arena`.  Each distinct mangled name is
+/// copied into the arena once.
+pub fn mangle_symbol<'a>(intern: &mut StrIntern<'a>, arena: &'a StrArena,
+                         name: &QualName, params: Option<&[TypeDesc]>,
+                         disambiguator: u32) -> Symbol<'a> {
+    let mangled = mangle(name, params, disambiguator);
+
+    intern.symbol(arena.intern(&mangled))
+}
+
+#[cfg(test)]
+fn test_name<'a>(intern: &mut StrIntern<'a>, path: &[&'a str]) -> QualName<'a> {
+    QualName::new(path.iter().map(|s| intern.symbol(s)).collect())
+}
+
+#[test]
+fn test_mangle_names() {
+    let mut intern = StrIntern::new();
+    let foo = test_name(&mut intern, &["foo"]);
+    let bar = test_name(&mut intern, &["foo", "bar"]);
+
+    assert_eq!(mangle(&foo, None, 0), "_Z3foo");
+    assert_eq!(mangle(&foo, Some(&[]), 0), "_Z3foov");
+    assert_eq!(mangle(&bar, None, 0), "_ZN3foo3barE");
+    assert_eq!(mangle(&bar, Some(&[]), 1), "_ZN3foo3barB2s1Ev");
+    assert_eq!(mangle(&foo, None, 12), "_Z3fooB3s12");
+}
+
+#[test]
+fn test_mangle_path() {
+    let mut intern = StrIntern::new();
+    let map = SymbolPath::parse(&mut intern, "std.Map");
+    let print = SymbolPath::parse(&mut intern, "print");
+
+    assert_eq!(mangle_path(&map, 2), "_ZN3std3MapB2t2E");
+    assert_eq!(mangle_path(&map, 0), "_ZN3std3MapE");
+    assert_eq!(mangle_path(&print, 1), "_Z5printB2t1");
+}
+
+#[test]
+fn test_mangle_types() {
+    let mut intern = StrIntern::new();
+    let name = test_name(&mut intern, &["f"]);
+    let point = test_name(&mut intern, &["geom", "Point"]);
+    let params = [TypeDesc::Int { bits: 32, signed: true },
+                  TypeDesc::Int { bits: 64, signed: false },
+                  TypeDesc::Int { bits: 24, signed: true },
+                  TypeDesc::Float(64),
+                  TypeDesc::Ptr(Box::new(TypeDesc::Char)),
+                  TypeDesc::Ref(Box::new(TypeDesc::Named(point))),
+                  TypeDesc::Array(4, Box::new(TypeDesc::Bool))];
+
+    assert_eq!(mangle(&name, Some(&params), 0),
+               "_Z1fiyu3i24dPDiRN4geom5PointEA4_b");
+}
+
+#[test]
+fn test_mangle_symbol_interned() {
+    let arena = StrArena::new();
+    let mut intern = StrIntern::new();
+    let name = test_name(&mut intern, &["a", "b"]);
+    let first = mangle_symbol(&mut intern, &arena, &name, Some(&[]), 0);
+    let second = mangle_symbol(&mut intern, &arena, &name, Some(&[]), 0);
+
+    assert_eq!(first, second);
+    assert_eq!(first.as_str(), "_ZN1a1bEv");
+    assert_eq!(first, intern.symbol("_ZN1a1bEv"));
+    assert_eq!(arena.get("_ZN1a1bEv").map(str::as_ptr),
+               Some(first.as_str().as_ptr()));
+}
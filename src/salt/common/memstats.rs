@@ -0,0 +1,263 @@
+//! Allocation accounting, enabled by the `alloc-stats` feature.
+//!
+//! A tool opts in by installing `CountingAlloc` as its global
+//! allocator:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOC: CountingAlloc<System> = CountingAlloc::new(System);
+//! ```
+//!
+//! Phases are then wrapped with `MemoryReport::phase`, which records
+//! the bytes allocated and the peak heap usage during each phase.  The
+//! `phase!` macro of `log` records them too, in its timings.
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that wraps another allocator and counts the
+/// bytes allocated through it.
+pub struct CountingAlloc<A> {
+    inner: A
+}
+
+/// A snapshot of allocation counters.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MemStats {
+    /// Bytes currently allocated.
+    pub current: usize,
+    /// Peak bytes allocated since the last `reset_peak`.
+    pub peak: usize,
+    /// Total bytes ever allocated.
+    pub total: u64
+}
+
+/// Memory usage of a single phase.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PhaseMemory {
+    /// The name of the phase.
+    pub name: String,
+    /// Bytes allocated during the phase.
+    pub allocated: u64,
+    /// Peak heap usage during the phase.
+    pub peak: usize,
+    /// Change in heap usage over the phase.
+    pub retained: isize
+}
+
+/// The allocation counters at the start of a phase, for measuring the
+/// phase when it ends.
+#[derive(Clone, Copy, Debug)]
+pub struct PhaseStart {
+    before: MemStats
+}
+
+/// Per-phase memory usage for a compilation.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryReport {
+    phases: Vec<PhaseMemory>
+}
+
+impl<A> CountingAlloc<A> {
+    /// Wrap `inner` with allocation counting.
+    pub const fn new(inner: A) -> CountingAlloc<A> {
+        CountingAlloc { inner }
+    }
+}
+
+fn record_alloc(size: usize) {
+    let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+
+    TOTAL.fetch_add(size as u64, Ordering::Relaxed);
+    PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+fn record_dealloc(size: usize) {
+    CURRENT.fetch_sub(size, Ordering::Relaxed);
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let out = self.inner.alloc(layout);
+
+        if !out.is_null() {
+            record_alloc(layout.size());
+        }
+
+        out
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let out = self.inner.alloc_zeroed(layout);
+
+        if !out.is_null() {
+            record_alloc(layout.size());
+        }
+
+        out
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout,
+                      new_size: usize) -> *mut u8 {
+        let out = self.inner.realloc(ptr, layout, new_size);
+
+        if !out.is_null() {
+            record_dealloc(layout.size());
+            record_alloc(new_size);
+        }
+
+        out
+    }
+}
+
+/// Get the current allocation counters.  These are all zero unless
+/// `CountingAlloc` is installed as the global allocator.
+pub fn stats() -> MemStats {
+    MemStats { current: CURRENT.load(Ordering::Relaxed),
+               peak: PEAK.load(Ordering::Relaxed),
+               total: TOTAL.load(Ordering::Relaxed) }
+}
+
+/// Reset the peak counter to the current usage.
+pub fn reset_peak() {
+    PEAK.store(CURRENT.load(Ordering::Relaxed), Ordering::Relaxed)
+}
+
+impl PhaseStart {
+    /// Start measuring a phase, resetting the peak counter.
+    pub fn now() -> PhaseStart {
+        let before = stats();
+
+        reset_peak();
+
+        PhaseStart { before }
+    }
+
+    /// End the phase `name`, getting its memory usage.  The peak from
+    /// before the phase is put back, so that phases can be nested: an
+    /// enclosing phase's peak is the highest before, during and after
+    /// the phases inside it.
+    pub fn finish(self, name: &str) -> PhaseMemory {
+        let after = stats();
+
+        PEAK.fetch_max(self.before.peak, Ordering::Relaxed);
+
+        PhaseMemory {
+            name: name.to_string(),
+            allocated: after.total - self.before.total,
+            peak: after.peak,
+            retained: after.current as isize - self.before.current as isize
+        }
+    }
+}
+
+impl MemoryReport {
+    /// Create an empty `MemoryReport`.
+    pub fn new() -> MemoryReport {
+        MemoryReport { phases: Vec::new() }
+    }
+
+    /// Run `f` as the phase `name`, recording its memory usage.
+    pub fn phase<R, F: FnOnce() -> R>(&mut self, name: &str, f: F) -> R {
+        let start = PhaseStart::now();
+        let out = f();
+
+        self.phases.push(start.finish(name));
+
+        out
+    }
+
+    /// Get the recorded phases, in the order they ran.
+    pub fn phases(&self) -> &[PhaseMemory] {
+        &self.phases
+    }
+}
+
+/// Format a byte count with a binary unit suffix.
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut val = bytes as f64;
+    let mut unit = 0;
+
+    while val >= 1024.0 && unit < UNITS.len() - 1 {
+        val /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", val, UNITS[unit])
+    }
+}
+
+impl Display for MemoryReport {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let width = self.phases.iter().map(|p| p.name.len()).max()
+            .unwrap_or(0).max(5);
+
+        writeln!(f, "{:width$}  {:>12}  {:>12}", "phase", "allocated", "peak",
+                 width = width)?;
+
+        for phase in &self.phases {
+            writeln!(f, "{:width$}  {:>12}  {:>12}", phase.name,
+                     human_bytes(phase.allocated),
+                     human_bytes(phase.peak as u64), width = width)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[global_allocator]
+static TEST_ALLOC: CountingAlloc<::std::alloc::System> =
+    CountingAlloc::new(::std::alloc::System);
+
+#[test]
+fn test_phase_accounting() {
+    let mut report = MemoryReport::new();
+    let v = report.phase("alloc", || vec![0u8; 1 << 20]);
+
+    assert_eq!(v.len(), 1 << 20);
+    assert!(report.phases()[0].allocated >= 1 << 20);
+    assert!(report.phases()[0].peak >= 1 << 20);
+}
+
+#[test]
+fn test_nested_phases() {
+    let outer = PhaseStart::now();
+    let big = vec![0u8; 1 << 20];
+
+    drop(big);
+
+    // The inner phase resets the peak, but the outer still sees its own.
+    PhaseStart::now().finish("inner");
+
+    let outer = outer.finish("outer");
+
+    assert!(outer.peak >= 1 << 20);
+    assert!(outer.allocated >= 1 << 20);
+}
+
+#[test]
+fn test_human_bytes() {
+    assert_eq!(human_bytes(100), "100 B");
+    assert_eq!(human_bytes(1536), "1.5 KiB");
+    assert_eq!(human_bytes(3 << 20), "3.0 MiB");
+}
@@ -0,0 +1,151 @@
+//! Error codes.
+//!
+//! Each compiler phase declares the codes of its messages as statics,
+//! with a long-form explanation, and registers them at startup:
+//!
+//! ```ignore
+//! static E0042: ErrorCode =
+//!     ErrorCode::new("E0042", "mismatched types",
+//!                    "A value was used where a value of a different \
+//!                     type was expected.");
+//!
+//! register(&[&E0042]);
+//!
+//! Message::error("mismatched types").with_code(&E0042)
+//! ```
+//!
+//! Codes are stable: once issued, a code always refers to the same
+//! problem, so explanations can be looked up by code with `explain`.
+//!
+//! Codes of warnings can belong to a group, such as `unused`, so that a
+//! `WarningPolicy` can set the level of the whole group at once:
+//!
+//! ```ignore
+//! static W0007: ErrorCode =
+//!     ErrorCode::new("W0007", "unused variable", "...").in_group("unused");
+//! ```
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::sync::Mutex;
+
+/// Every code that has been registered.
+static REGISTRY: Mutex<Vec<&'static ErrorCode>> = Mutex::new(Vec::new());
+
+/// A stable code identifying a kind of message.
+#[derive(Debug, Eq, Hash, PartialEq)]
+pub struct ErrorCode {
+    code: &'static str,
+    summary: &'static str,
+    explanation: &'static str,
+    group: Option<&'static str>
+}
+
+impl ErrorCode {
+    /// Create an error code `code`, with a one-line summary of the
+    /// problem and a long-form explanation.
+    pub const fn new(code: &'static str, summary: &'static str,
+                     explanation: &'static str) -> ErrorCode {
+        ErrorCode { code, summary, explanation, group: None }
+    }
+
+    /// Get this code in the group `group`.
+    pub const fn in_group(mut self, group: &'static str) -> ErrorCode {
+        self.group = Some(group);
+        self
+    }
+
+    /// Get the code, such as `E0042`.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Get the one-line summary of the problem.
+    pub fn summary(&self) -> &'static str {
+        self.summary
+    }
+
+    /// Get the long-form explanation of the problem.
+    pub fn explanation(&self) -> &'static str {
+        self.explanation
+    }
+
+    /// Get the group of this code, if it is in one.
+    pub fn group(&self) -> Option<&'static str> {
+        self.group
+    }
+}
+
+/// Codes are displayed as just the code.
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+/// Register `codes`, so they can be looked up.  Registering a code
+/// again is harmless.  Panics if a different `ErrorCode` with the same
+/// code has already been registered.
+pub fn register(codes: &[&'static ErrorCode]) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|err| err.into_inner());
+
+    for code in codes {
+        match registry.iter().find(|other| other.code == code.code) {
+            Some(other) => assert!(::std::ptr::eq(*other, *code),
+                                   "error code {} registered twice", code),
+            None => registry.push(code)
+        }
+    }
+}
+
+/// Get the registered `ErrorCode` for `code`, if there is one.
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    REGISTRY.lock().unwrap_or_else(|err| err.into_inner())
+        .iter()
+        .find(|other| other.code == code)
+        .copied()
+}
+
+/// Get the explanation of `code`, if it has been registered.
+pub fn explain(code: &str) -> Option<&'static str> {
+    lookup(code).map(ErrorCode::explanation)
+}
+
+/// Get every registered code, sorted by code.
+pub fn codes() -> Vec<&'static ErrorCode> {
+    let mut out = REGISTRY.lock().unwrap_or_else(|err| err.into_inner())
+        .clone();
+
+    out.sort_by_key(|code| code.code);
+
+    out
+}
+
+#[cfg(test)]
+static TEST_E9001: ErrorCode =
+    ErrorCode::new("E9001", "test problem", "Explanation of E9001.")
+    .in_group("test");
+
+#[test]
+fn test_registry() {
+    register(&[&TEST_E9001]);
+    register(&[&TEST_E9001]);
+
+    assert_eq!(explain("E9001"), Some("Explanation of E9001."));
+    assert_eq!(lookup("E9001").map(ErrorCode::summary), Some("test problem"));
+    assert_eq!(explain("E9999"), None);
+    assert_eq!(codes().iter().filter(|code| code.code() == "E9001").count(),
+               1);
+    assert_eq!(TEST_E9001.to_string(), "E9001");
+    assert_eq!(lookup("E9001").and_then(ErrorCode::group), Some("test"));
+}
+
+#[test]
+#[should_panic(expected = "E9002 registered twice")]
+fn test_duplicate() {
+    static A: ErrorCode = ErrorCode::new("E9002", "a", "A.");
+    static B: ErrorCode = ErrorCode::new("E9002", "b", "B.");
+
+    register(&[&A, &B]);
+}
@@ -0,0 +1,434 @@
+//! Diagnostic messages.
+//!
+//! A `Message` is the one representation of a diagnostic shared by all
+//! Salt tools: a severity, an optional code, a headline, the positions
+//! it refers to, any notes, and any suggested fixes.  Positions can be
+//! of any type that implements `PositionInfo`, so each tool can use its
+//! own position representation.  Messages are turned into text by a
+//! `Renderer`.
+
+use salt::common::cache::DecodeError;
+use salt::common::cache::DecodeResult;
+use salt::common::cache::Decodable;
+use salt::common::cache::Decoder;
+use salt::common::cache::Encodable;
+use salt::common::cache::Encoder;
+use salt::common::position::Location;
+use salt::common::position::PositionInfo;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+pub use self::code::ErrorCode;
+pub use self::policy::ALL_WARNINGS;
+pub use self::policy::LintLevel;
+pub use self::policy::UnknownLintLevel;
+pub use self::policy::WarningPolicy;
+pub use self::render::ColorRenderer;
+pub use self::render::ContextRenderer;
+pub use self::render::JsonRenderer;
+pub use self::render::PlainRenderer;
+pub use self::render::Renderer;
+pub use self::render::render_with_context;
+pub use self::sink::DiagnosticSink;
+pub use self::sink::Emitted;
+pub use self::sink::SinkConfig;
+pub use self::sink::TooManyErrors;
+
+pub mod code;
+mod policy;
+mod render;
+pub mod sarif;
+mod sink;
+
+/// The severity of a message.  Severities are ordered, with more
+/// severe messages greater.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// A suggestion for fixing a problem.
+    Help,
+    /// Additional information.
+    Note,
+    /// A possible problem, which does not stop compilation.
+    Warning,
+    /// A problem, which causes compilation to fail.
+    Error,
+    /// An internal error in the compiler itself.
+    Bug
+}
+
+/// A diagnostic message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Message<P> {
+    /// The severity of the message.
+    pub severity: Severity,
+    /// The message's code, such as `E0042`, if it has one.
+    pub code: Option<String>,
+    /// A one-line description of the problem.
+    pub headline: String,
+    /// The positions the message refers to.  The first is the primary
+    /// position, and the rest are related positions.
+    pub positions: Vec<P>,
+    /// Labels for the positions, by index, such as "expected because
+    /// of this".  Positions past the end, or labelled `None`, have no
+    /// label of their own.
+    pub labels: Vec<Option<String>>,
+    /// Additional notes, shown after the headline and positions.
+    pub notes: Vec<String>,
+    /// Suggested fixes, shown after the notes.
+    pub suggestions: Vec<Suggestion>
+}
+
+/// How confident a tool can be in applying a suggestion without a
+/// person looking at it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Applicability {
+    /// The suggestion is certainly what was meant, and can be applied
+    /// automatically.
+    MachineApplicable,
+    /// The suggestion is probably what was meant, but may not be, or
+    /// may not compile.
+    MaybeIncorrect,
+    /// The replacement contains placeholders, such as `/* type */`,
+    /// that must be filled in.
+    HasPlaceholders,
+    /// Nothing is known about the suggestion.
+    Unspecified
+}
+
+/// A suggested edit to fix the problem a message describes: replacing
+/// the text at a location in the file of the message's primary position
+/// with new text.  A point location inserts the text there, and an
+/// empty replacement deletes the span.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Suggestion {
+    /// A description of the edit, such as "use `==` to compare".
+    pub message: String,
+    /// The location to replace.
+    pub loc: Location,
+    /// The text to replace it with.
+    pub replacement: String,
+    /// Whether the edit can be applied automatically.
+    pub applicability: Applicability
+}
+
+impl Severity {
+    /// Get the name of this severity, as shown in messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Help => "help",
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Bug => "internal compiler error"
+        }
+    }
+
+    /// Check whether messages of this severity cause compilation to
+    /// fail.
+    pub fn is_error(&self) -> bool {
+        *self >= Severity::Error
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Applicability {
+    /// Get the name of this applicability, as used in JSON output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+            Applicability::HasPlaceholders => "has-placeholders",
+            Applicability::Unspecified => "unspecified"
+        }
+    }
+}
+
+impl Display for Applicability {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Suggestion {
+    /// Create a suggestion described by `message` to replace the text
+    /// at `loc` with `replacement`.
+    pub fn new<S, R>(message: S, loc: Location, replacement: R,
+                     applicability: Applicability) -> Suggestion
+        where S: Into<String>,
+              R: Into<String> {
+        Suggestion { message: message.into(), loc,
+                     replacement: replacement.into(), applicability }
+    }
+
+    /// Check whether the suggestion can be applied automatically.
+    pub fn is_machine_applicable(&self) -> bool {
+        self.applicability == Applicability::MachineApplicable
+    }
+}
+
+/// Describes the edit, such as "replace 3.5-9 with `x`".
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self.loc {
+            Location::Point { point } =>
+                write!(f, "insert `{}` at {}", self.replacement, point),
+            ref loc if self.replacement.is_empty() =>
+                write!(f, "remove {}", loc),
+            ref loc =>
+                write!(f, "replace {} with `{}`", loc, self.replacement)
+        }
+    }
+}
+
+impl<P> Message<P> {
+    /// Create a message with severity `severity` and headline
+    /// `headline`, and no code, positions, notes or suggestions.
+    pub fn new<S: Into<String>>(severity: Severity,
+                                headline: S) -> Message<P> {
+        Message { severity, code: None, headline: headline.into(),
+                  positions: Vec::new(), labels: Vec::new(),
+                  notes: Vec::new(), suggestions: Vec::new() }
+    }
+
+    /// Create an error message.
+    pub fn error<S: Into<String>>(headline: S) -> Message<P> {
+        Message::new(Severity::Error, headline)
+    }
+
+    /// Create a warning message.
+    pub fn warning<S: Into<String>>(headline: S) -> Message<P> {
+        Message::new(Severity::Warning, headline)
+    }
+
+    /// Create a note message.
+    pub fn note<S: Into<String>>(headline: S) -> Message<P> {
+        Message::new(Severity::Note, headline)
+    }
+
+    /// Create a message for an internal compiler error.
+    pub fn bug<S: Into<String>>(headline: S) -> Message<P> {
+        Message::new(Severity::Bug, headline)
+    }
+
+    /// Set the message's code, which may be given as a string or an
+    /// `ErrorCode`.
+    pub fn with_code<S: ToString>(mut self, code: S) -> Message<P> {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    /// Add a position.  The first position added is the primary one.
+    pub fn with_position(mut self, pos: P) -> Message<P> {
+        self.positions.push(pos);
+        self
+    }
+
+    /// Add a position labelled with `label`.  The first position added
+    /// is the primary one.
+    pub fn with_label<S: Into<String>>(mut self, pos: P,
+                                       label: S) -> Message<P> {
+        self.labels.resize(self.positions.len(), None);
+        self.labels.push(Some(label.into()));
+        self.positions.push(pos);
+        self
+    }
+
+    /// Add a note.
+    pub fn with_note<S: Into<String>>(mut self, note: S) -> Message<P> {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Add a suggested fix.
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Message<P> {
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    /// Get the suggestions that can be applied automatically.
+    pub fn machine_applicable(&self) -> impl Iterator<Item = &Suggestion> {
+        self.suggestions.iter().filter(|s| s.is_machine_applicable())
+    }
+
+    /// Get the primary position, if there is one.
+    pub fn primary(&self) -> Option<&P> {
+        self.positions.first()
+    }
+
+    /// Check whether this message causes compilation to fail.
+    pub fn is_error(&self) -> bool {
+        self.severity.is_error()
+    }
+}
+
+impl<'a, P: PositionInfo<'a>> Message<P> {
+    /// Get the label of the position at index `idx`: its own label, or
+    /// for a related position, its description.
+    pub fn label(&'a self, idx: usize) -> Option<&'a str> {
+        match self.labels.get(idx) {
+            Some(Some(label)) => Some(label),
+            _ if idx == 0 => None,
+            _ => self.positions.get(idx).and_then(|pos| pos.description())
+        }
+    }
+}
+
+impl Encodable for Severity {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_u8(*self as u8)
+    }
+}
+
+impl<'d> Decodable<'d> for Severity {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Severity> {
+        match dec.read_u8()? {
+            0 => Ok(Severity::Help),
+            1 => Ok(Severity::Note),
+            2 => Ok(Severity::Warning),
+            3 => Ok(Severity::Error),
+            4 => Ok(Severity::Bug),
+            tag => Err(DecodeError::BadTag { what: "Severity",
+                                             tag: tag as u64 })
+        }
+    }
+}
+
+impl Encodable for Applicability {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_u8(*self as u8)
+    }
+}
+
+impl<'d> Decodable<'d> for Applicability {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Applicability> {
+        match dec.read_u8()? {
+            0 => Ok(Applicability::MachineApplicable),
+            1 => Ok(Applicability::MaybeIncorrect),
+            2 => Ok(Applicability::HasPlaceholders),
+            3 => Ok(Applicability::Unspecified),
+            tag => Err(DecodeError::BadTag { what: "Applicability",
+                                             tag: tag as u64 })
+        }
+    }
+}
+
+impl Encodable for Suggestion {
+    fn encode(&self, enc: &mut Encoder) {
+        self.message.encode(enc);
+        self.loc.encode(enc);
+        self.replacement.encode(enc);
+        self.applicability.encode(enc);
+    }
+}
+
+impl<'d> Decodable<'d> for Suggestion {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Suggestion> {
+        Ok(Suggestion { message: String::decode(dec)?,
+                        loc: Location::decode(dec)?,
+                        replacement: String::decode(dec)?,
+                        applicability: Applicability::decode(dec)? })
+    }
+}
+
+impl<P: Encodable> Encodable for Message<P> {
+    fn encode(&self, enc: &mut Encoder) {
+        self.severity.encode(enc);
+        self.code.encode(enc);
+        self.headline.encode(enc);
+        self.positions.encode(enc);
+        self.labels.encode(enc);
+        self.notes.encode(enc);
+        self.suggestions.encode(enc);
+    }
+}
+
+impl<'d, P: Decodable<'d>> Decodable<'d> for Message<P> {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Message<P>> {
+        Ok(Message { severity: Severity::decode(dec)?,
+                     code: Option::decode(dec)?,
+                     headline: String::decode(dec)?,
+                     positions: Vec::decode(dec)?,
+                     labels: Vec::decode(dec)?,
+                     notes: Vec::decode(dec)?,
+                     suggestions: Vec::decode(dec)? })
+    }
+}
+
+#[test]
+fn test_severity() {
+    assert!(Severity::Bug > Severity::Error);
+    assert!(Severity::Error.is_error());
+    assert!(!Severity::Warning.is_error());
+    assert_eq!(Severity::Warning.to_string(), "warning");
+}
+
+#[test]
+fn test_builder() {
+    let msg: Message<()> = Message::error("mismatched types")
+        .with_code("E0042")
+        .with_position(())
+        .with_note("expected `i32`");
+
+    assert!(msg.is_error());
+    assert_eq!(msg.code.as_deref(), Some("E0042"));
+    assert_eq!(msg.primary(), Some(&()));
+    assert_eq!(msg.notes, ["expected `i32`"]);
+
+    let code = ErrorCode::new("E0042", "mismatched types", "");
+    let msg: Message<()> = Message::error("mismatched types")
+        .with_code(&code);
+
+    assert_eq!(msg.code.as_deref(), Some("E0042"));
+}
+
+#[test]
+fn test_labels() {
+    use salt::common::position::BasicPosition;
+
+    let msg: Message<BasicPosition> = Message::error("mismatched types")
+        .with_label(BasicPosition::Synthetic { desc: "a" }, "found `&str`")
+        .with_position(BasicPosition::Synthetic { desc: "b" })
+        .with_label(BasicPosition::Synthetic { desc: "c" },
+                    "expected because of this");
+
+    assert_eq!(msg.positions.len(), 3);
+    assert_eq!(msg.label(0), Some("found `&str`"));
+    assert_eq!(msg.label(1), Some("b"));
+    assert_eq!(msg.label(2), Some("expected because of this"));
+    assert_eq!(msg.label(3), None);
+    assert_eq!(Message::error("x").with_position(BasicPosition::Synthetic {
+        desc: "a"
+    }).label(0), None);
+}
+
+#[test]
+fn test_suggestion() {
+    use salt::common::position::Point;
+
+    let span = Location::Span { start: Point { line: 3, col: 5 },
+                                end: Point { line: 3, col: 9 } };
+    let point = Location::Point { point: Point { line: 3, col: 5 } };
+    let msg: Message<()> = Message::error("mismatched types")
+        .with_suggestion(Suggestion::new("convert it", span.clone(),
+                                         "x as i32",
+                                         Applicability::MaybeIncorrect))
+        .with_suggestion(Suggestion::new("dereference it", point, "*",
+                                         Applicability::MachineApplicable))
+        .with_suggestion(Suggestion::new("remove it", span, "",
+                                         Applicability::Unspecified));
+
+    assert_eq!(msg.suggestions[0].to_string(),
+               "replace 3.5-9 with `x as i32`");
+    assert_eq!(msg.suggestions[1].to_string(), "insert `*` at 3.5");
+    assert_eq!(msg.suggestions[2].to_string(), "remove 3.5-9");
+    assert_eq!(msg.machine_applicable().map(|s| s.message.as_str())
+               .collect::<Vec<_>>(), ["dereference it"]);
+    assert_eq!(Applicability::HasPlaceholders.to_string(),
+               "has-placeholders");
+}
@@ -0,0 +1,222 @@
+use salt::common::message::Message;
+use salt::common::message::Severity;
+use salt::common::message::code;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// How warnings with a code are reported.  Levels are ordered, with
+/// stricter levels greater.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LintLevel {
+    /// The warning is not reported (`-A`).
+    Allow,
+    /// The warning is reported as a warning (`-W`).
+    Warn,
+    /// The warning is reported as an error (`-D`).
+    Deny,
+    /// Like `Deny`, but later settings cannot lower the level (`-F`).
+    Forbid
+}
+
+/// An error indicating that a lint level name or flag was not
+/// recognized.  The name is carried in the error.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UnknownLintLevel(pub String);
+
+/// The key matching every warning, with or without a code.
+pub const ALL_WARNINGS: &str = "warnings";
+
+/// Levels for warnings, set by code, by group of codes, or for all
+/// warnings with `warnings`.  Settings apply in order, each overriding
+/// those before it for the warnings it covers, as `-W` and `-D` flags
+/// given in order on a command line do, except that nothing overrides
+/// `Forbid`.
+///
+/// A `DiagnosticSink` applies its policy to each warning before
+/// keeping it.  Errors are never affected.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WarningPolicy {
+    /// The settings, in order, as a code, group or `warnings`, and a
+    /// level.
+    settings: Vec<(String, LintLevel)>
+}
+
+const LEVELS: [(LintLevel, &str, &str); 4] = [
+    (LintLevel::Allow, "allow", "-A"),
+    (LintLevel::Warn, "warn", "-W"),
+    (LintLevel::Deny, "deny", "-D"),
+    (LintLevel::Forbid, "forbid", "-F")
+];
+
+impl LintLevel {
+    /// Get the name of this level, as used in configuration files.
+    pub fn name(&self) -> &'static str {
+        LEVELS.iter()
+            .find(|(level, _, _)| level == self)
+            .map(|(_, name, _)| *name)
+            .expect("lint level missing from table")
+    }
+
+    /// Get the command-line flag setting this level.
+    pub fn flag(&self) -> &'static str {
+        LEVELS.iter()
+            .find(|(level, _, _)| level == self)
+            .map(|(_, _, flag)| *flag)
+            .expect("lint level missing from table")
+    }
+
+    /// Get the level set by the command-line flag `flag`, such as
+    /// `-D`.
+    pub fn from_flag(flag: &str) -> ::std::result::Result<LintLevel,
+                                                          UnknownLintLevel> {
+        LEVELS.iter()
+            .find(|(_, _, name)| *name == flag)
+            .map(|(level, _, _)| *level)
+            .ok_or_else(|| UnknownLintLevel(flag.to_string()))
+    }
+}
+
+impl Display for LintLevel {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for LintLevel {
+    type Err = UnknownLintLevel;
+
+    fn from_str(s: &str) -> ::std::result::Result<LintLevel,
+                                                  UnknownLintLevel> {
+        LEVELS.iter()
+            .find(|(_, name, _)| *name == s)
+            .map(|(level, _, _)| *level)
+            .ok_or_else(|| UnknownLintLevel(s.to_string()))
+    }
+}
+
+impl Display for UnknownLintLevel {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "unknown lint level `{}`", self.0)
+    }
+}
+
+impl WarningPolicy {
+    /// Create a policy reporting every warning as a warning.
+    pub fn new() -> WarningPolicy {
+        WarningPolicy { settings: Vec::new() }
+    }
+
+    /// Set the level of the warnings matching `key`, a code, a group,
+    /// or `warnings`, to `level`.
+    pub fn set(&mut self, key: &str, level: LintLevel) {
+        self.settings.push((key.to_string(), level))
+    }
+
+    /// Set a level from the command-line flag `flag`, such as `-D`,
+    /// with the argument `key`.
+    pub fn set_flag(&mut self, flag: &str, key: &str)
+                    -> ::std::result::Result<(), UnknownLintLevel> {
+        self.set(key, LintLevel::from_flag(flag)?);
+
+        Ok(())
+    }
+
+    /// Get the level of warnings with the code `code`, or of warnings
+    /// with no code if it is `None`.
+    pub fn level(&self, code: Option<&str>) -> LintLevel {
+        let mut out = LintLevel::Warn;
+
+        for (key, level) in &self.settings {
+            if out != LintLevel::Forbid && WarningPolicy::covers(key, code) {
+                out = *level
+            }
+        }
+
+        out
+    }
+
+    /// Check whether the key `key`, a code, a group, or `warnings`,
+    /// covers warnings with the code `code`, or with no code if it is
+    /// `None`.
+    pub fn covers(key: &str, code: Option<&str>) -> bool {
+        key == ALL_WARNINGS || Some(key) == code ||
+        code.and_then(code::lookup)
+            .and_then(|code| code.group())
+            .is_some_and(|group| group == key)
+    }
+
+    /// Apply the policy to `msg`: drop it if it is an allowed warning,
+    /// and make it an error if it is a denied one.  Messages that are
+    /// not warnings are unchanged.
+    pub fn apply<P>(&self, mut msg: Message<P>) -> Option<Message<P>> {
+        if msg.severity != Severity::Warning {
+            return Some(msg);
+        }
+
+        match self.level(msg.code.as_deref()) {
+            LintLevel::Allow => None,
+            LintLevel::Warn => Some(msg),
+            LintLevel::Deny | LintLevel::Forbid => {
+                msg.severity = Severity::Error;
+
+                Some(msg)
+            }
+        }
+    }
+
+    /// Check whether the policy sets no levels.
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+}
+
+#[test]
+fn test_lint_level() {
+    assert_eq!("deny".parse(), Ok(LintLevel::Deny));
+    assert_eq!(LintLevel::from_flag("-F"), Ok(LintLevel::Forbid));
+    assert_eq!(LintLevel::Allow.flag(), "-A");
+    assert_eq!("loud".parse::<LintLevel>().unwrap_err().to_string(),
+               "unknown lint level `loud`");
+    assert!(LintLevel::Forbid > LintLevel::Deny);
+}
+
+#[test]
+fn test_warning_policy() {
+    use salt::common::message::ErrorCode;
+
+    static W9101: ErrorCode =
+        ErrorCode::new("W9101", "unused variable", "").in_group("unused9");
+    static W9102: ErrorCode =
+        ErrorCode::new("W9102", "unused import", "").in_group("unused9");
+
+    code::register(&[&W9101, &W9102]);
+
+    let mut policy = WarningPolicy::new();
+    let warning = |code: &str| -> Message<()> {
+        Message::warning("unused").with_code(code)
+    };
+
+    assert_eq!(policy.level(Some("W9101")), LintLevel::Warn);
+
+    policy.set_flag("-D", "unused9").unwrap();
+    policy.set("W9102", LintLevel::Allow);
+
+    assert_eq!(policy.level(Some("W9101")), LintLevel::Deny);
+    assert_eq!(policy.apply(warning("W9101")).unwrap().severity,
+               Severity::Error);
+    assert_eq!(policy.apply(warning("W9102")), None);
+    assert_eq!(policy.apply(warning("W9999")).unwrap().severity,
+               Severity::Warning);
+    assert!(policy.apply(Message::<()>::error("x").with_code("W9102"))
+            .is_some());
+
+    policy.set(ALL_WARNINGS, LintLevel::Forbid);
+    policy.set("W9101", LintLevel::Allow);
+
+    assert_eq!(policy.level(None), LintLevel::Forbid);
+    assert_eq!(policy.level(Some("W9101")), LintLevel::Forbid);
+    assert_eq!(policy.set_flag("-X", "W9101"),
+               Err(UnknownLintLevel(String::from("-X"))));
+}
@@ -0,0 +1,824 @@
+use salt::common::filename::Filename;
+use salt::common::json::Json;
+use salt::common::message::Message;
+use salt::common::message::Severity;
+use salt::common::message::Suggestion;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use salt::common::position::PositionInfo;
+use salt::common::position::SourceProvider;
+use std::collections::BTreeSet;
+
+/// Turns messages with positions in files named with lifetime `'a`
+/// into text.
+pub trait Renderer<'a> {
+    /// Render `msg`, ending with a newline.
+    fn render<P: PositionInfo<'a>>(&self, msg: &'a Message<P>) -> String;
+}
+
+/// A `Renderer` producing plain text, for logs and files:
+///
+/// ```text
+/// a.salt 3.5-9: error[E0042]: mismatched types
+///   a.salt 1.1-4
+///   note: expected `i32`
+///   help: convert it: replace 3.5-9 with `x as i32`
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PlainRenderer;
+
+/// A `Renderer` producing the same layout as `PlainRenderer`, with ANSI
+/// colors for terminals.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ColorRenderer;
+
+/// A `Renderer` that shows the source lines at each position, with the
+/// span underlined, for terminals.  The primary position is underlined
+/// with `^` and related ones with `-`, and positions in the same file
+/// are shown together, in line order:
+///
+/// ```text
+/// error[E0042]: mismatched types
+///  --> a.salt 3.14-17
+///   |
+/// 1 | fn f(x: i32) {
+///   |         --- expected because of this
+/// ...
+/// 3 | let x: i32 = "a";
+///   |              ^^^ found `&str`
+///   = note: expected `i32`
+///   = help: convert it: replace 3.14-17 with `x as i32`
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ContextRenderer<'s, S: 's> {
+    sources: &'s S,
+    color: bool
+}
+
+/// A `Renderer` producing one line of JSON per message, for CI tools
+/// and editors:
+///
+/// ```text
+/// {"severity":"error","code":"E0042","message":"mismatched types",
+///  "rendered":"a.salt 3.5-9: error[E0042]: mismatched types\n",
+///  "positions":[{"file":"a.salt","primary":true,"label":null,
+///                "start":{"line":3,"col":5},"end":{"line":3,"col":9}}],
+///  "notes":[],
+///  "suggestions":[{"message":"convert it","file":"a.salt",
+///                  "start":{"line":3,"col":5},"end":{"line":3,"col":9},
+///                  "replacement":"x as i32",
+///                  "applicability":"maybe-incorrect"}]}
+/// ```
+///
+/// `rendered` is the message as `PlainRenderer` would show it.  Each
+/// position's children follow it in `positions`.  Positions with no
+/// file have a `null` file and their description as the label, and
+/// positions with no location in the file have no `start` or `end`.
+/// Suggestions apply to the file of the primary position, which is
+/// repeated in each so that editors can apply them directly.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct JsonRenderer;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+
+/// Get the ANSI color used for `severity`.
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Help => "\x1b[1;32m",
+        Severity::Note => "\x1b[1;36m",
+        Severity::Warning => "\x1b[1;33m",
+        Severity::Error | Severity::Bug => "\x1b[1;31m"
+    }
+}
+
+/// Describe `pos`, as its file and location, or its description if it
+/// is not in a file.
+fn position_text<'a, P: PositionInfo<'a>>(pos: &'a P) -> Option<String> {
+    match pos.location() {
+        Some((filename, Some(loc))) => Some(format!("{} {}", filename, loc)),
+        Some((filename, None)) => Some(filename.to_string()),
+        None => pos.description().map(String::from)
+    }
+}
+
+/// Get the label for the `idx`th child of `pos`, `child`.  The
+/// children of a position in a macro expansion are labelled as the
+/// macro's use and definition; others are labelled with their
+/// description.
+pub fn child_label<'a, P: PositionInfo<'a>>(pos: &P, idx: usize,
+                                            child: &P) -> Option<String> {
+    match (pos.macro_name(), idx) {
+        (Some(name), 0) => Some(format!("in expansion of `{}`", name)),
+        (Some(name), 1) => Some(format!("`{}` defined here", name)),
+        _ => child.description().map(String::from)
+    }
+}
+
+/// Add `pos` and its children to `out`, as related positions, labelling
+/// `pos` with `label`.
+fn related<'a, P: PositionInfo<'a>>(pos: &'a P, label: Option<&str>,
+                                    color: bool, out: &mut String) {
+    if let Some(text) = position_text(pos) {
+        out.push_str("  ");
+        out.push_str(&paint(color, BOLD, &text));
+
+        // The description was already used if there is no file.
+        match label {
+            Some(label) if pos.location().is_some() => {
+                out.push_str(": ");
+                out.push_str(label);
+            }
+            _ => {}
+        }
+
+        out.push('\n');
+    }
+
+    related_children(pos, color, out)
+}
+
+/// Add the children of `pos` to `out`, as related positions.
+fn related_children<'a, P: PositionInfo<'a>>(pos: &'a P, color: bool,
+                                             out: &mut String) {
+    for (idx, child) in pos.children().iter().enumerate() {
+        let label = child_label(pos, idx, *child);
+
+        related(*child, label.as_deref(), color, out)
+    }
+}
+
+/// Wrap `text` in the ANSI style `style`, if `color` is set.
+fn paint(color: bool, style: &str, text: &str) -> String {
+    if color {
+        format!("{}{}{}", style, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Describe `suggestion`, as shown after a message's notes.
+fn suggestion_text(suggestion: &Suggestion) -> String {
+    format!("{}: {}", suggestion.message, suggestion)
+}
+
+/// Render `msg`, with ANSI colors if `color` is set.
+fn render_text<'a, P: PositionInfo<'a>>(msg: &'a Message<P>,
+                                        color: bool) -> String {
+    let mut out = String::new();
+    let mut label = msg.severity.name().to_string();
+
+    if let Some(code) = &msg.code {
+        label = format!("{}[{}]", label, code);
+    }
+
+    if let Some(text) = msg.primary().and_then(position_text) {
+        out.push_str(&paint(color, BOLD, &text));
+        out.push_str(": ");
+    }
+
+    out.push_str(&paint(color, severity_color(msg.severity), &label));
+    out.push_str(": ");
+    out.push_str(&paint(color, BOLD, &msg.headline));
+    out.push('\n');
+
+    if let Some(primary) = msg.primary() {
+        related_children(primary, color, &mut out)
+    }
+
+    for (i, pos) in msg.positions.iter().enumerate().skip(1) {
+        related(pos, msg.label(i), color, &mut out)
+    }
+
+    for note in &msg.notes {
+        out.push_str("  ");
+        out.push_str(&paint(color, severity_color(Severity::Note), "note"));
+        out.push_str(": ");
+        out.push_str(note);
+        out.push('\n');
+    }
+
+    for suggestion in &msg.suggestions {
+        out.push_str("  ");
+        out.push_str(&paint(color, severity_color(Severity::Help), "help"));
+        out.push_str(": ");
+        out.push_str(&suggestion_text(suggestion));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Add `pos` and its children to `out`, as JSON objects, labelling
+/// `pos` with `label`.
+fn position_json<'a, P: PositionInfo<'a>>(pos: &'a P, label: Option<&str>,
+                                          primary: bool, out: &mut Vec<Json>) {
+    let point = |point: &Point| Json::object()
+        .with("line", point.line as u64)
+        .with("col", point.col as u64);
+    let json = match pos.location() {
+        Some((filename, loc)) => {
+            let json = Json::object()
+                .with("file", filename.to_string())
+                .with("primary", primary)
+                .with("label", label);
+
+            match loc {
+                Some(loc) => json.with("start", point(&loc.start()))
+                    .with("end", point(&loc.end())),
+                None => json
+            }
+        }
+        None => Json::object()
+            .with("file", Json::Null)
+            .with("primary", primary)
+            .with("label", pos.description())
+    };
+
+    out.push(json);
+
+    for (idx, child) in pos.children().iter().enumerate() {
+        let label = child_label(pos, idx, *child);
+
+        position_json(*child, label.as_deref(), false, out)
+    }
+}
+
+/// Get `suggestion` as a JSON object, applying to the file of the
+/// message's primary position, `primary`.
+fn suggestion_json<'a, P: PositionInfo<'a>>(suggestion: &Suggestion,
+                                            primary: Option<&'a P>) -> Json {
+    let point = |point: Point| Json::object()
+        .with("line", point.line as u64)
+        .with("col", point.col as u64);
+    let file = primary.and_then(|pos| pos.location())
+        .map(|(filename, _)| filename.to_string());
+
+    Json::object()
+        .with("message", suggestion.message.as_str())
+        .with("file", file)
+        .with("start", point(suggestion.loc.start()))
+        .with("end", point(suggestion.loc.end()))
+        .with("replacement", suggestion.replacement.as_str())
+        .with("applicability", suggestion.applicability.name())
+}
+
+/// A span to underline in a snippet of source lines.
+struct Underline<'l> {
+    /// The start of the span.
+    start: Point,
+    /// The end of the span.
+    end: Point,
+    /// The last line shown for the span.  A span ending at the start
+    /// of a line does not include that line.
+    last: u32,
+    /// The label shown after the underline on the last line.
+    label: Option<&'l str>,
+    /// Whether this is the message's primary position, underlined with
+    /// `^` rather than `-`.
+    primary: bool
+}
+
+/// Get the file of `pos`, the position as shown in a snippet header,
+/// and the span to underline, if `pos` should show source context and
+/// its lines are available from `sources`.
+fn underline<'a, 'l, P, S>(pos: &'a P, label: Option<&'l str>, primary: bool,
+                           sources: &S)
+                           -> Option<(Filename<'a>, String, Underline<'l>)>
+    where P: PositionInfo<'a>,
+          S: SourceProvider<'a> {
+    let (filename, loc) = match pos.location() {
+        Some((filename, Some(loc))) if pos.show_ctx() => (*filename, loc),
+        _ => return None
+    };
+    let (start, end) = match loc {
+        Location::Span { start, end } => (*start, *end),
+        Location::Point { point } => (*point, *point)
+    };
+    let last = if end.col <= 1 && end.line > start.line {
+        end.line - 1
+    } else {
+        end.line
+    };
+
+    if (start.line..=last).all(|line| sources.source_line(filename,
+                                                          line).is_some()) {
+        Some((filename, format!("{} {}", filename, loc),
+              Underline { start, end, last, label, primary }))
+    } else {
+        None
+    }
+}
+
+/// Add a snippet of the lines of `filename` covered by `spans` to `out`,
+/// each line shown once followed by the underlines of the spans on it,
+/// and the snippet headed by `arrow` and `header`.  Lines not covered
+/// by any span are elided.
+fn snippet<'a, S>(filename: Filename<'a>, arrow: &str, header: &str,
+                  spans: &[Underline], sources: &S, color: bool,
+                  out: &mut String)
+    where S: SourceProvider<'a> {
+    let lines: BTreeSet<u32> = spans.iter()
+        .flat_map(|span| span.start.line..=span.last)
+        .collect();
+    let width = lines.iter().next_back().map_or(1, |line| {
+        line.to_string().len()
+    });
+    let gutter = " ".repeat(width);
+    let bar = paint(color, BOLD, "|");
+    let tabs = sources.tab_stops();
+    let mut prev = None;
+
+    out.push_str(&format!("{}{} {}\n", gutter, paint(color, BOLD, arrow),
+                          header));
+    out.push_str(&format!("{} {}\n", gutter, bar));
+
+    for line in lines {
+        let text = sources.source_line(filename, line).unwrap_or("");
+
+        if prev.is_some_and(|prev| line > prev + 1) {
+            out.push_str("...\n");
+        }
+
+        prev = Some(line);
+        out.push_str(&format!("{:>width$} {} {}\n", line, bar,
+                              tabs.expand(text), width = width));
+
+        for span in spans {
+            if line < span.start.line || line > span.last {
+                continue;
+            }
+
+            let from = if line == span.start.line {
+                span.start.col.saturating_sub(1) as usize
+            } else {
+                0
+            };
+            let to = if line == span.end.line {
+                span.end.col.saturating_sub(1) as usize
+            } else {
+                text.chars().count()
+            };
+            // Underlines go under the cells the span is shown in, with
+            // tabs expanded.
+            let from = tabs.visual_column(text, from);
+            let to = tabs.visual_column(text, to).max(from + 1);
+            let (mark, style) = if span.primary {
+                ("^", severity_color(Severity::Error))
+            } else {
+                ("-", severity_color(Severity::Note))
+            };
+
+            out.push_str(&format!("{} {} {}{}", gutter, bar,
+                                  " ".repeat(from),
+                                  paint(color, style,
+                                        &mark.repeat(to - from))));
+
+            match span.label {
+                Some(label) if line == span.last => {
+                    out.push(' ');
+                    out.push_str(label);
+                }
+                _ => {}
+            }
+
+            out.push('\n');
+        }
+    }
+}
+
+/// Add the source context for `pos` and its children to `out`,
+/// labelling `pos` with `label`.
+fn context<'a, P, S>(pos: &'a P, label: Option<&str>, sources: &S,
+                     color: bool, out: &mut String)
+    where P: PositionInfo<'a>,
+          S: SourceProvider<'a> {
+    match underline(pos, label, true, sources) {
+        Some((filename, header, span)) =>
+            snippet(filename, "-->", &header, &[span], sources, color, out),
+        None => match pos.location() {
+            Some((filename, Some(loc))) => {
+                out.push_str(&format!(" --> {} {}", filename, loc));
+
+                if let Some(label) = label {
+                    out.push_str(": ");
+                    out.push_str(label);
+                }
+
+                out.push('\n');
+            }
+            Some((filename, None)) =>
+                out.push_str(&format!(" --> {}\n", filename)),
+            None => if let Some(desc) = pos.description() {
+                out.push_str(&format!(" --> {}\n", desc));
+            }
+        }
+    }
+
+    context_children(pos, sources, color, out)
+}
+
+/// Add the source context for the children of `pos` to `out`.
+fn context_children<'a, P, S>(pos: &'a P, sources: &S, color: bool,
+                              out: &mut String)
+    where P: PositionInfo<'a>,
+          S: SourceProvider<'a> {
+    for (idx, child) in pos.children().iter().enumerate() {
+        let label = child_label(pos, idx, *child);
+
+        context(*child, label.as_deref(), sources, color, out)
+    }
+}
+
+/// Render the position `pos` with the source lines it covers, taken
+/// from `sources`, and the span underlined with carets and labelled
+/// with `label`.  If `pos` should not show source context, or its
+/// lines are not available, only the position is shown.
+pub fn render_with_context<'a, P, S>(pos: &'a P, label: Option<&str>,
+                                     sources: &S) -> String
+    where P: PositionInfo<'a>,
+          S: SourceProvider<'a> {
+    let mut out = String::new();
+
+    context(pos, label, sources, false, &mut out);
+
+    out
+}
+
+impl<'s, S> ContextRenderer<'s, S> {
+    /// Create a `ContextRenderer` showing source lines from `sources`,
+    /// with ANSI colors if `color` is set.
+    pub fn new(sources: &'s S, color: bool) -> ContextRenderer<'s, S> {
+        ContextRenderer { sources, color }
+    }
+}
+
+impl<'a> Renderer<'a> for PlainRenderer {
+    fn render<P: PositionInfo<'a>>(&self, msg: &'a Message<P>) -> String {
+        render_text(msg, false)
+    }
+}
+
+impl<'a> Renderer<'a> for ColorRenderer {
+    fn render<P: PositionInfo<'a>>(&self, msg: &'a Message<P>) -> String {
+        render_text(msg, true)
+    }
+}
+
+impl<'a> Renderer<'a> for JsonRenderer {
+    fn render<P: PositionInfo<'a>>(&self, msg: &'a Message<P>) -> String {
+        let mut positions = Vec::new();
+
+        for (i, pos) in msg.positions.iter().enumerate() {
+            position_json(pos, msg.label(i), i == 0, &mut positions)
+        }
+
+        let json = Json::object()
+            .with("severity", msg.severity.name())
+            .with("code", msg.code.as_ref())
+            .with("message", msg.headline.as_str())
+            .with("rendered", render_text(msg, false))
+            .with("positions", positions)
+            .with("notes", &msg.notes)
+            .with("suggestions", msg.suggestions.iter()
+                  .map(|s| suggestion_json(s, msg.primary()))
+                  .collect::<Vec<_>>());
+
+        format!("{}\n", json)
+    }
+}
+
+impl<'a, 's, S: SourceProvider<'a>> Renderer<'a> for ContextRenderer<'s, S> {
+    fn render<P: PositionInfo<'a>>(&self, msg: &'a Message<P>) -> String {
+        let mut out = String::new();
+        let mut label = msg.severity.name().to_string();
+
+        if let Some(code) = &msg.code {
+            label = format!("{}[{}]", label, code);
+        }
+
+        out.push_str(&paint(self.color, severity_color(msg.severity),
+                            &label));
+        out.push_str(": ");
+        out.push_str(&paint(self.color, BOLD, &msg.headline));
+        out.push('\n');
+
+        let mut shown = vec![false; msg.positions.len()];
+
+        for (i, pos) in msg.positions.iter().enumerate() {
+            if shown[i] {
+                continue;
+            }
+
+            let (filename, header, first) =
+                match underline(pos, msg.label(i), i == 0, self.sources) {
+                    Some(found) => found,
+                    None => {
+                        context(pos, msg.label(i), self.sources, self.color,
+                                &mut out);
+
+                        continue;
+                    }
+                };
+            let mut spans = vec![first];
+            let mut group = vec![pos];
+
+            // Later positions in the same file are shown in the same
+            // snippet, interleaved by line.
+            for (j, other) in msg.positions.iter().enumerate().skip(i + 1) {
+                match underline(other, msg.label(j), false, self.sources) {
+                    Some((file, _, span)) if !shown[j] &&
+                                             file == filename => {
+                        shown[j] = true;
+                        spans.push(span);
+                        group.push(other);
+                    }
+                    _ => {}
+                }
+            }
+
+            // Snippets of files other than the primary position's are
+            // marked as secondary.
+            let arrow = if i == 0 { "-->" } else { ":::" };
+
+            snippet(filename, arrow, &header, &spans, self.sources,
+                    self.color, &mut out);
+
+            for pos in group {
+                context_children(pos, self.sources, self.color, &mut out)
+            }
+        }
+
+        for note in &msg.notes {
+            out.push_str(&format!("  = {}: {}\n",
+                                  paint(self.color, BOLD, "note"), note));
+        }
+
+        for suggestion in &msg.suggestions {
+            out.push_str(&format!("  = {}: {}\n",
+                                  paint(self.color, BOLD, "help"),
+                                  suggestion_text(suggestion)));
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+fn test_message<'a>(names: &mut ::salt::common::str::intern::StrIntern<'a>)
+                    -> Message<::salt::common::position::BasicPosition<'a>> {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Location;
+    use salt::common::position::Point;
+
+    let filename = names.filename("a.salt");
+    let span = |line, start, end| BasicPosition::Content {
+        filepos: FilePosition {
+            filename,
+            loc: Location::Span { start: Point { line, col: start },
+                                  end: Point { line, col: end } }
+        }
+    };
+
+    Message::error("mismatched types")
+        .with_code("E0042")
+        .with_position(span(3, 5, 9))
+        .with_position(span(1, 1, 4))
+        .with_position(BasicPosition::Synthetic { desc: "<prelude>" })
+        .with_note("expected `i32`")
+}
+
+#[test]
+fn test_plain() {
+    use salt::common::position::BasicPosition;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let msg = test_message(&mut names);
+
+    assert_eq!(PlainRenderer.render(&msg),
+               "a.salt 3.5-9: error[E0042]: mismatched types\n  \
+                a.salt 1.1-4\n  \
+                <prelude>\n  \
+                note: expected `i32`\n");
+
+    let bare: Message<BasicPosition> = Message::warning("unused import");
+
+    assert_eq!(PlainRenderer.render(&bare), "warning: unused import\n");
+}
+
+#[test]
+fn test_color() {
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let msg = test_message(&mut names);
+    let out = ColorRenderer.render(&msg);
+
+    assert!(out.starts_with("\x1b[1ma.salt 3.5-9\x1b[0m: \
+                             \x1b[1;31merror[E0042]\x1b[0m: "), "{:?}", out);
+    assert!(out.ends_with("\x1b[1;36mnote\x1b[0m: expected `i32`\n"));
+}
+
+#[test]
+fn test_json() {
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let msg = test_message(&mut names);
+
+    assert_eq!(JsonRenderer.render(&msg),
+               "{\"severity\":\"error\",\"code\":\"E0042\",\
+                \"message\":\"mismatched types\",\
+                \"rendered\":\"a.salt 3.5-9: error[E0042]: mismatched \
+                types\\n  a.salt 1.1-4\\n  <prelude>\\n  \
+                note: expected `i32`\\n\",\
+                \"positions\":[\
+                {\"file\":\"a.salt\",\"primary\":true,\"label\":null,\
+                \"start\":{\"line\":3,\"col\":5},\
+                \"end\":{\"line\":3,\"col\":9}},\
+                {\"file\":\"a.salt\",\"primary\":false,\"label\":null,\
+                \"start\":{\"line\":1,\"col\":1},\
+                \"end\":{\"line\":1,\"col\":4}},\
+                {\"file\":null,\"primary\":false,\
+                \"label\":\"<prelude>\"}],\
+                \"notes\":[\"expected `i32`\"],\"suggestions\":[]}\n");
+}
+
+#[test]
+fn test_suggestions() {
+    use salt::common::message::Applicability;
+    use salt::common::position::BasicPosition;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let span = Location::Span { start: Point { line: 3, col: 5 },
+                                end: Point { line: 3, col: 9 } };
+    let msg = test_message(&mut names)
+        .with_suggestion(Suggestion::new("convert it", span, "x as i32",
+                                         Applicability::MaybeIncorrect));
+
+    assert!(PlainRenderer.render(&msg)
+            .ends_with("  note: expected `i32`\n  \
+                        help: convert it: replace 3.5-9 with `x as i32`\n"));
+    assert!(JsonRenderer.render(&msg)
+            .ends_with("\"suggestions\":[{\"message\":\"convert it\",\
+                        \"file\":\"a.salt\",\
+                        \"start\":{\"line\":3,\"col\":5},\
+                        \"end\":{\"line\":3,\"col\":9},\
+                        \"replacement\":\"x as i32\",\
+                        \"applicability\":\"maybe-incorrect\"}]}\n"));
+
+    let bare: Message<BasicPosition> = Message::warning("unused import")
+        .with_suggestion(Suggestion::new("remove it", msg.suggestions[0]
+                                         .loc.clone(), "",
+                                         Applicability::MachineApplicable));
+
+    assert!(JsonRenderer.render(&bare).contains("\"file\":null"));
+}
+
+#[test]
+fn test_context() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Point;
+    use salt::common::position::SourceManager;
+    use salt::common::str::intern::StrIntern;
+    use salt::common::str::width::TabStops;
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("a.salt");
+    let mut sources = SourceManager::new();
+    let pos = |start: (u32, u32), end: (u32, u32)| FilePosition {
+        filename,
+        loc: Location::Span { start: Point { line: start.0, col: start.1 },
+                              end: Point { line: end.0, col: end.1 } }
+    };
+
+    sources.add(filename, String::from("fn f() {\n\tlet x: i32 = \"a\";\n}\n"));
+
+    assert_eq!(render_with_context(&pos((2, 14), (2, 17)), Some("here"),
+                                   &sources),
+               " --> a.salt 2.14-17\n  \
+                  |\n\
+                2 |         let x: i32 = \"a\";\n  \
+                  |                     ^^^ here\n");
+    assert_eq!(render_with_context(&pos((1, 8), (3, 2)), None, &sources),
+               " --> a.salt 1.8-3.2\n  \
+                  |\n\
+                1 | fn f() {\n  \
+                  |        ^\n\
+                2 |         let x: i32 = \"a\";\n  \
+                  | ^^^^^^^^^^^^^^^^^^^^^^^^^\n\
+                3 | }\n  \
+                  | ^\n");
+    assert_eq!(render_with_context(&pos((9, 1), (9, 2)), Some("gone"),
+                                   &sources),
+               " --> a.salt 9.1-2: gone\n");
+
+    sources.set_tab_stops(TabStops::new(4));
+    assert_eq!(render_with_context(&pos((2, 1), (2, 5)), None, &sources),
+               " --> a.salt 2.1-5\n  \
+                  |\n\
+                2 |     let x: i32 = \"a\";\n  \
+                  | ^^^^^^^\n");
+    sources.set_tab_stops(TabStops::default());
+
+    let msg = Message::error("mismatched types")
+        .with_code("E0042")
+        .with_position(BasicPosition::from(pos((2, 14), (2, 17))))
+        .with_position(BasicPosition::Synthetic { desc: "<prelude>" })
+        .with_note("expected `i32`");
+
+    assert_eq!(ContextRenderer::new(&sources, false).render(&msg),
+               "error[E0042]: mismatched types\n \
+                --> a.salt 2.14-17\n  \
+                  |\n\
+                2 |         let x: i32 = \"a\";\n  \
+                  |                     ^^^\n \
+                --> <prelude>\n  \
+                = note: expected `i32`\n");
+}
+
+#[test]
+fn test_multi_span() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::FilePosition;
+    use salt::common::position::SourceManager;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let mut sources = SourceManager::new();
+    let pos = |filename, line, start, end| BasicPosition::from(FilePosition {
+        filename,
+        loc: Location::Span { start: Point { line, col: start },
+                              end: Point { line, col: end } }
+    });
+
+    sources.add(a, String::from("fn f(x: i32) {\n\n  x = \"a\";\n\n\n}\n"));
+    sources.add(b, String::from("type T = i32;\n"));
+
+    let msg = Message::error("mismatched types")
+        .with_label(pos(a, 3, 7, 10), "found `&str`")
+        .with_label(pos(b, 1, 10, 13), "`T` defined here")
+        .with_label(pos(a, 1, 9, 12), "expected because of this")
+        .with_label(pos(a, 6, 1, 2), "in this function");
+
+    assert_eq!(ContextRenderer::new(&sources, false).render(&msg),
+               "error: mismatched types\n \
+                --> a.salt 3.7-10\n  \
+                  |\n\
+                1 | fn f(x: i32) {\n  \
+                  |         --- expected because of this\n\
+                ...\n\
+                3 |   x = \"a\";\n  \
+                  |       ^^^ found `&str`\n\
+                ...\n\
+                6 | }\n  \
+                  | - in this function\n \
+                ::: b.salt 1.10-13\n  \
+                  |\n\
+                1 | type T = i32;\n  \
+                  |          --- `T` defined here\n");
+    assert_eq!(PlainRenderer.render(&msg),
+               "a.salt 3.7-10: error: mismatched types\n  \
+                b.salt 1.10-13: `T` defined here\n  \
+                a.salt 1.9-12: expected because of this\n  \
+                a.salt 6.1-2: in this function\n");
+}
+
+#[test]
+fn test_expansion() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::ExpandedPosition;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("a.salt");
+    let pos = |line, start, end| -> ExpandedPosition {
+        BasicPosition::from(FilePosition {
+            filename,
+            loc: Location::Span { start: Point { line, col: start },
+                                  end: Point { line, col: end } }
+        }).into()
+    };
+    let use_site = pos(5, 3, 12);
+    let def_site = pos(1, 1, 20);
+    let expansion = ExpandedPosition::expansion("twice", &use_site,
+                                                &def_site);
+    let msg = Message::error("mismatched types").with_position(expansion);
+
+    assert_eq!(PlainRenderer.render(&msg),
+               "a.salt 5.3-12: error: mismatched types\n  \
+                a.salt 5.3-12: in expansion of `twice`\n  \
+                a.salt 1.1-20: `twice` defined here\n");
+}
@@ -0,0 +1,238 @@
+//! SARIF output.
+//!
+//! `sarif_log` converts a batch of messages into a SARIF 2.1.0 log,
+//! the format security and code-scanning tools ingest.  Each message
+//! becomes a result, and each code used becomes a rule, described
+//! from the error-code registry if the code is registered.
+
+use salt::common::json::Json;
+use salt::common::message::Message;
+use salt::common::message::Severity;
+use salt::common::message::code;
+use salt::common::message::render::child_label;
+use salt::common::position::Location;
+use salt::common::position::PositionInfo;
+use salt::common::version::ToolVersion;
+
+/// The SARIF version produced.
+const SARIF_VERSION: &str = "2.1.0";
+/// The schema of the SARIF version produced.
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Get the SARIF level for `severity`.
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Help | Severity::Note => "note",
+        Severity::Warning => "warning",
+        Severity::Error | Severity::Bug => "error"
+    }
+}
+
+/// Get a SARIF message object with text `text`.
+fn message(text: &str) -> Json {
+    Json::object().with("text", text)
+}
+
+/// Get a SARIF region for `loc`.  SARIF columns, like ours, count from
+/// 1 and end just past the region.
+fn region(loc: &Location) -> Json {
+    let start = loc.start();
+    let region = Json::object()
+        .with("startLine", start.line as u64)
+        .with("startColumn", start.col as u64);
+
+    match loc {
+        Location::Span { end, .. } => region
+            .with("endLine", end.line as u64)
+            .with("endColumn", end.col as u64),
+        _ => region
+    }
+}
+
+/// Get a SARIF location for `pos`, labelled with `label`, if it is in
+/// a file.
+fn location<'a, P: PositionInfo<'a>>(pos: &'a P,
+                                     label: Option<&str>) -> Option<Json> {
+    pos.location().map(|(filename, loc)| {
+        let artifact = Json::object().with("uri", filename.to_string());
+        let physical = match loc {
+            Some(loc) => Json::object()
+                .with("artifactLocation", artifact)
+                .with("region", region(loc)),
+            None => Json::object().with("artifactLocation", artifact)
+        };
+        let json = Json::object().with("physicalLocation", physical);
+
+        match label {
+            Some(label) => json.with("message", message(label)),
+            None => json
+        }
+    })
+}
+
+/// Add SARIF locations for `pos` and its children to `out`, labelling
+/// `pos` with `label`.
+fn related<'a, P: PositionInfo<'a>>(pos: &'a P, label: Option<&str>,
+                                    out: &mut Vec<Json>) {
+    if let Some(json) = location(pos, label) {
+        out.push(json.with("id", out.len() as u64));
+    }
+
+    related_children(pos, out)
+}
+
+/// Add SARIF locations for the children of `pos` to `out`.
+fn related_children<'a, P: PositionInfo<'a>>(pos: &'a P,
+                                             out: &mut Vec<Json>) {
+    for (idx, child) in pos.children().iter().enumerate() {
+        let label = child_label(pos, idx, *child);
+
+        related(*child, label.as_deref(), out)
+    }
+}
+
+/// Get the SARIF rule for the code `code`.
+fn rule(code: &str) -> Json {
+    let rule = Json::object().with("id", code);
+
+    match code::lookup(code) {
+        Some(code) => rule
+            .with("shortDescription", message(code.summary()))
+            .with("fullDescription", message(code.explanation())),
+        None => rule
+    }
+}
+
+/// Get the SARIF result for `msg`, whose code is rule `rule_index`.
+fn result<'a, P: PositionInfo<'a>>(msg: &'a Message<P>,
+                                   rule_index: Option<usize>) -> Json {
+    let mut text = msg.headline.clone();
+    let mut related_locs = Vec::new();
+
+    for note in &msg.notes {
+        text.push_str("\nnote: ");
+        text.push_str(note);
+    }
+
+    if let Some(primary) = msg.primary() {
+        related_children(primary, &mut related_locs)
+    }
+
+    for (i, pos) in msg.positions.iter().enumerate().skip(1) {
+        related(pos, msg.label(i), &mut related_locs)
+    }
+
+    let mut json = Json::object();
+
+    if let (Some(code), Some(idx)) = (&msg.code, rule_index) {
+        json = json.with("ruleId", code.as_str())
+            .with("ruleIndex", idx as u64);
+    }
+
+    json.with("level", level(msg.severity))
+        .with("message", message(&text))
+        .with("locations", msg.primary()
+              .and_then(|pos| location(pos, msg.label(0)))
+              .into_iter()
+              .collect::<Vec<_>>())
+        .with("relatedLocations", related_locs)
+}
+
+/// Convert `msgs`, produced by the tool `tool` at version `version`,
+/// into a SARIF log with a single run.  The log is written with
+/// `Display`.
+pub fn sarif_log<'a, P>(tool: &str, version: &ToolVersion,
+                        msgs: &'a [Message<P>]) -> Json
+    where P: PositionInfo<'a> {
+    let mut codes: Vec<&str> = Vec::new();
+    let mut results = Vec::new();
+
+    for msg in msgs {
+        let rule_index = msg.code.as_ref().map(|code| {
+            match codes.iter().position(|other| other == code) {
+                Some(idx) => idx,
+                None => {
+                    codes.push(code);
+
+                    codes.len() - 1
+                }
+            }
+        });
+
+        results.push(result(msg, rule_index))
+    }
+
+    let rules: Vec<Json> = codes.iter().map(|code| rule(code)).collect();
+    let driver = Json::object()
+        .with("name", tool)
+        .with("version", version.to_string())
+        .with("rules", rules);
+    let run = Json::object()
+        .with("tool", Json::object().with("driver", driver))
+        .with("results", results);
+
+    Json::object()
+        .with("version", SARIF_VERSION)
+        .with("$schema", SARIF_SCHEMA)
+        .with("runs", vec![run])
+}
+
+#[test]
+fn test_sarif() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::message::ErrorCode;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    static TEST_E9101: ErrorCode =
+        ErrorCode::new("E9101", "test problem", "Explanation of E9101.");
+
+    code::register(&[&TEST_E9101]);
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("a.salt");
+    let pos = |line, start, end| BasicPosition::Content {
+        filepos: FilePosition {
+            filename,
+            loc: Location::Span { start: Point { line, col: start },
+                                  end: Point { line, col: end } }
+        }
+    };
+    let msgs = [Message::error("bad thing")
+                    .with_code(&TEST_E9101)
+                    .with_position(pos(3, 5, 9))
+                    .with_position(pos(1, 1, 4))
+                    .with_note("try harder"),
+                Message::warning("odd thing").with_position(pos(2, 1, 2)),
+                Message::error("bad thing again").with_code("E9101")];
+    let log = sarif_log("saltc", &ToolVersion::new(1, 2, 3), &msgs);
+    let out = log.to_string();
+
+    assert!(out.starts_with("{\"version\":\"2.1.0\",\"$schema\":"), "{}", out);
+    assert!(out.contains("\"driver\":{\"name\":\"saltc\",\
+                          \"version\":\"1.2.3\",\"rules\":[{\"id\":\"E9101\",\
+                          \"shortDescription\":{\"text\":\"test problem\"},\
+                          \"fullDescription\":{\"text\":\
+                          \"Explanation of E9101.\"}}]}"), "{}", out);
+    assert!(out.contains("{\"ruleId\":\"E9101\",\"ruleIndex\":0,\
+                          \"level\":\"error\",\"message\":{\"text\":\
+                          \"bad thing\\nnote: try harder\"},\
+                          \"locations\":[{\"physicalLocation\":\
+                          {\"artifactLocation\":{\"uri\":\"a.salt\"},\
+                          \"region\":{\"startLine\":3,\"startColumn\":5,\
+                          \"endLine\":3,\"endColumn\":9}}}],\
+                          \"relatedLocations\":[{\"physicalLocation\":\
+                          {\"artifactLocation\":{\"uri\":\"a.salt\"},\
+                          \"region\":{\"startLine\":1,\"startColumn\":1,\
+                          \"endLine\":1,\"endColumn\":4}},\"id\":0}]}"),
+            "{}", out);
+    assert!(out.contains("{\"level\":\"warning\",\
+                          \"message\":{\"text\":\"odd thing\"}"), "{}", out);
+    assert!(out.contains("{\"ruleId\":\"E9101\",\"ruleIndex\":0,\
+                          \"level\":\"error\",\
+                          \"message\":{\"text\":\"bad thing again\"},\
+                          \"locations\":[],\"relatedLocations\":[]}"),
+            "{}", out);
+}
@@ -0,0 +1,479 @@
+use salt::common::filename::Filename;
+use salt::common::message::LintLevel;
+use salt::common::message::Message;
+use salt::common::message::Severity;
+use salt::common::message::WarningPolicy;
+use salt::common::metrics;
+use salt::common::metrics::KeyedCounter;
+use salt::common::position::Location;
+use salt::common::position::PositionInfo;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+static DIAGNOSTICS_EMITTED: KeyedCounter =
+    KeyedCounter::new("diagnostics.emitted");
+static DIAGNOSTICS_DUPLICATE: KeyedCounter =
+    KeyedCounter::new("diagnostics.duplicate");
+static DIAGNOSTICS_SUPPRESSED: KeyedCounter =
+    KeyedCounter::new("diagnostics.suppressed");
+
+/// Settings for a `DiagnosticSink`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SinkConfig {
+    /// Whether to drop messages with the same code (or, without a
+    /// code, the same headline) and primary position as one already
+    /// shown.
+    pub dedup: bool,
+    /// The most messages to show with any one code, if there is a
+    /// limit.
+    pub per_code: Option<usize>,
+    /// The number of errors after which to stop compiling, if any
+    /// (`--error-limit`).
+    pub max_errors: Option<usize>
+}
+
+/// What a `DiagnosticSink` did with a message.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Emitted {
+    /// The message was kept, to be shown.
+    Shown,
+    /// The message was a warning allowed by the sink's policy or
+    /// suppressed in its region of source, and was dropped.
+    Allowed,
+    /// The message was the same as one already shown, and was dropped.
+    Duplicate,
+    /// Too many messages with the message's code were already shown,
+    /// and it was dropped.
+    Suppressed
+}
+
+/// An error indicating that the error limit was reached, and
+/// compilation should stop.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TooManyErrors {
+    /// The value of the limit.
+    pub limit: usize
+}
+
+/// A region of source in which some warnings are not reported, such as
+/// an item with an `allow` pragma.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Suppression<'a> {
+    filename: Filename<'a>,
+    loc: Location,
+    /// The codes, groups or `warnings` suppressed.
+    keys: Vec<String>
+}
+
+/// Collects messages from the passes of a compilation, keeping cascades
+/// of errors from drowning the ones that matter.  Warnings are first
+/// dropped if the frontend suppressed them in the region of source they
+/// are in, unless the policy forbids them.  Then they are allowed, kept
+/// or made errors by the sink's `WarningPolicy`.  Then
+/// duplicate messages are dropped, messages with a code past its limit
+/// are counted rather than kept and summarized at the end, and reaching
+/// the error limit tells the caller to stop.  With metrics enabled, the
+/// messages kept, dropped as duplicates and suppressed are counted for
+/// each file, in `diagnostics.emitted`, `diagnostics.duplicate` and
+/// `diagnostics.suppressed`.
+#[derive(Debug)]
+pub struct DiagnosticSink<'a, P> {
+    config: SinkConfig,
+    policy: WarningPolicy,
+    /// The regions with suppressed warnings.
+    suppressions: Vec<Suppression<'a>>,
+    /// The messages kept, in order.
+    messages: Vec<Message<P>>,
+    /// Indexes into `messages` of the messages with each key hash.
+    seen: HashMap<u64, Vec<usize>>,
+    /// The number of messages shown with each code.
+    shown: HashMap<String, usize>,
+    /// The number of messages suppressed with each code, in order of
+    /// first suppression.
+    suppressed: Vec<(String, usize)>,
+    /// The number of errors kept.
+    errors: usize
+}
+
+impl SinkConfig {
+    /// Get the default settings: deduplicate, with no limits.
+    pub fn new() -> SinkConfig {
+        SinkConfig { dedup: true, per_code: None, max_errors: None }
+    }
+}
+
+impl Default for SinkConfig {
+    fn default() -> SinkConfig {
+        SinkConfig::new()
+    }
+}
+
+/// Get the text identifying what `msg` is about: its code, or its
+/// headline if it has none.
+fn key_text<P>(msg: &Message<P>) -> &str {
+    msg.code.as_deref().unwrap_or(&msg.headline)
+}
+
+/// Count `msg` in `metric` under the file of its primary position, or
+/// `<none>` if it has none.
+fn count<'a, P: PositionInfo<'a>>(metric: &'static KeyedCounter,
+                                  msg: &Message<P>) {
+    if metrics::is_enabled() {
+        match msg.primary().and_then(|pos| pos.file_location()) {
+            Some((filename, _)) => metric.incr(filename),
+            None => metric.incr("<none>")
+        }
+    }
+}
+
+/// Check whether `a` and `b` are duplicates.
+fn same<P: Eq>(a: &Message<P>, b: &Message<P>) -> bool {
+    key_text(a) == key_text(b) && a.primary() == b.primary()
+}
+
+impl<'a, P: Eq + Hash + PositionInfo<'a>> DiagnosticSink<'a, P> {
+    /// Create an empty sink with settings `config`.
+    pub fn new(config: SinkConfig) -> DiagnosticSink<'a, P> {
+        DiagnosticSink { config, policy: WarningPolicy::new(),
+                         suppressions: Vec::new(), messages: Vec::new(),
+                         seen: HashMap::new(),
+                         shown: HashMap::new(), suppressed: Vec::new(),
+                         errors: 0 }
+    }
+
+    /// Set the policy for warnings to `policy`.
+    pub fn with_policy(mut self,
+                       policy: WarningPolicy) -> DiagnosticSink<'a, P> {
+        self.policy = policy;
+        self
+    }
+
+    /// Get the policy for warnings.
+    pub fn policy(&self) -> &WarningPolicy {
+        &self.policy
+    }
+
+    /// Suppress the warnings matching any of `keys`, each a code, a
+    /// group, or `warnings`, whose primary position starts at `loc` in
+    /// `filename`.
+    pub fn suppress(&mut self, filename: Filename<'a>, loc: Location,
+                    keys: &[&str]) {
+        let keys = keys.iter().map(|key| key.to_string()).collect();
+
+        self.suppressions.push(Suppression { filename, loc, keys })
+    }
+
+    /// Check whether `msg` is a warning suppressed in its region of
+    /// source.
+    fn is_suppressed(&self, msg: &Message<P>) -> bool {
+        let code = msg.code.as_deref();
+        let (filename, start) = match msg.primary()
+            .and_then(|pos| pos.file_location()) {
+            Some((filename, Some(loc))) => (filename, loc.start()),
+            _ => return false
+        };
+
+        msg.severity == Severity::Warning &&
+        self.policy.level(code) != LintLevel::Forbid &&
+        self.suppressions.iter().any(|region| {
+            region.filename == filename &&
+            (region.loc.contains(start) || region.loc.start() == start) &&
+            region.keys.iter().any(|key| WarningPolicy::covers(key, code))
+        })
+    }
+
+    /// Get the settings.
+    pub fn config(&self) -> &SinkConfig {
+        &self.config
+    }
+
+    /// Offer `msg` to the sink, returning what was done with it, or an
+    /// error if the error limit has been reached.  The message reaching
+    /// the limit is kept; any after it are dropped.
+    pub fn emit(&mut self, msg: Message<P>)
+                -> ::std::result::Result<Emitted, TooManyErrors> {
+        if let Some(limit) = self.config.max_errors {
+            if self.errors >= limit {
+                return Err(TooManyErrors { limit });
+            }
+        }
+
+        if self.is_suppressed(&msg) {
+            return Ok(Emitted::Allowed);
+        }
+
+        let msg = match self.policy.apply(msg) {
+            Some(msg) => msg,
+            None => return Ok(Emitted::Allowed)
+        };
+        let mut hasher = DefaultHasher::new();
+
+        key_text(&msg).hash(&mut hasher);
+        msg.primary().hash(&mut hasher);
+
+        let hash = hasher.finish();
+
+        if self.config.dedup {
+            if let Some(idxs) = self.seen.get(&hash) {
+                if idxs.iter().any(|idx| same(&self.messages[*idx], &msg)) {
+                    count(&DIAGNOSTICS_DUPLICATE, &msg);
+
+                    return Ok(Emitted::Duplicate);
+                }
+            }
+        }
+
+        if let (Some(code), Some(limit)) = (&msg.code, self.config.per_code) {
+            let shown = self.shown.entry(code.clone()).or_insert(0);
+
+            if *shown >= limit {
+                match self.suppressed.iter_mut().find(|(c, _)| c == code) {
+                    Some((_, count)) => *count += 1,
+                    None => self.suppressed.push((code.clone(), 1))
+                }
+
+                count(&DIAGNOSTICS_SUPPRESSED, &msg);
+
+                return Ok(Emitted::Suppressed);
+            }
+
+            *shown += 1;
+        }
+
+        if msg.is_error() {
+            self.errors += 1;
+        }
+
+        count(&DIAGNOSTICS_EMITTED, &msg);
+        self.seen.entry(hash).or_default().push(self.messages.len());
+        self.messages.push(msg);
+
+        match self.config.max_errors {
+            Some(limit) if self.errors >= limit =>
+                Err(TooManyErrors { limit }),
+            _ => Ok(Emitted::Shown)
+        }
+    }
+
+    /// Get the messages kept so far, in order.
+    pub fn messages(&self) -> &[Message<P>] {
+        &self.messages
+    }
+
+    /// Get the number of errors kept.
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    /// Check whether any errors were kept.
+    pub fn has_errors(&self) -> bool {
+        self.errors != 0
+    }
+
+    /// Get the number of messages suppressed for having a code past
+    /// its limit.
+    pub fn suppressed_count(&self) -> usize {
+        self.suppressed.iter().map(|(_, count)| count).sum()
+    }
+
+    /// Get the messages kept, followed by a note for each code with
+    /// suppressed messages, saying how many were suppressed.
+    pub fn finish(self) -> Vec<Message<P>> {
+        let mut messages = self.messages;
+
+        for (code, count) in self.suppressed {
+            let plural = if count == 1 { "" } else { "s" };
+
+            messages.push(Message::note(format!("{} similar message{} \
+                                                 suppressed", count, plural))
+                          .with_code(code));
+        }
+
+        messages
+    }
+}
+
+impl<'a, P> Default for DiagnosticSink<'a, P>
+    where P: Eq + Hash + PositionInfo<'a> {
+    fn default() -> DiagnosticSink<'a, P> {
+        DiagnosticSink::new(SinkConfig::new())
+    }
+}
+
+impl Display for TooManyErrors {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "aborting after {} errors, use `--error-limit` to \
+                   increase the limit", self.limit)
+    }
+}
+
+#[test]
+fn test_dedup() {
+    use salt::common::position::BasicPosition;
+
+    let pos = |desc| BasicPosition::Synthetic { desc };
+    let mut sink = DiagnosticSink::default();
+
+    assert_eq!(sink.emit(Message::error("mismatched types").with_code("E1")
+                         .with_position(pos("a"))), Ok(Emitted::Shown));
+    assert_eq!(sink.emit(Message::error("mismatched types").with_code("E1")
+                         .with_position(pos("a"))), Ok(Emitted::Duplicate));
+    assert_eq!(sink.emit(Message::error("mismatched types").with_code("E1")
+                         .with_position(pos("b"))), Ok(Emitted::Shown));
+    assert_eq!(sink.emit(Message::warning("unused").with_position(pos("a"))),
+               Ok(Emitted::Shown));
+    assert_eq!(sink.emit(Message::warning("unused").with_position(pos("a"))),
+               Ok(Emitted::Duplicate));
+    assert_eq!(sink.error_count(), 2);
+    assert_eq!(sink.finish().len(), 3);
+
+    let mut sink = DiagnosticSink::new(SinkConfig { dedup: false,
+                                                    ..SinkConfig::new() });
+
+    for _ in 0..2 {
+        assert_eq!(sink.emit(Message::error("x").with_position(pos("a"))),
+                   Ok(Emitted::Shown));
+    }
+}
+
+#[test]
+fn test_limits() {
+    use salt::common::position::BasicPosition;
+
+    const DESCS: [&str; 5] = ["a", "b", "c", "d", "e"];
+
+    let mut sink = DiagnosticSink::new(SinkConfig {
+        per_code: Some(2), max_errors: Some(5), ..SinkConfig::new()
+    });
+    let results: Vec<_> = DESCS.iter()
+        .map(|desc| sink.emit(Message::error("mismatched types")
+                              .with_code("E1")
+                              .with_position(BasicPosition::Synthetic {
+                                  desc
+                              })))
+        .collect();
+
+    assert_eq!(results, [Ok(Emitted::Shown), Ok(Emitted::Shown),
+                         Ok(Emitted::Suppressed), Ok(Emitted::Suppressed),
+                         Ok(Emitted::Suppressed)]);
+    assert_eq!(sink.suppressed_count(), 3);
+
+    for i in 0..2 {
+        assert_eq!(sink.emit(Message::error(format!("other {}", i))),
+                   Ok(Emitted::Shown));
+    }
+
+    let err = sink.emit(Message::error("last")).unwrap_err();
+
+    assert_eq!(err.to_string(), "aborting after 5 errors, use \
+                                 `--error-limit` to increase the limit");
+    assert_eq!(sink.emit(Message::error("dropped")), Err(err));
+    assert_eq!(sink.error_count(), 5);
+
+    let messages = sink.finish();
+
+    assert_eq!(messages.len(), 6);
+    assert_eq!(messages[5].headline, "3 similar messages suppressed");
+    assert_eq!(messages[5].code.as_deref(), Some("E1"));
+}
+
+#[test]
+fn test_policy() {
+    use salt::common::position::BasicPosition;
+
+    let mut policy = WarningPolicy::new();
+
+    policy.set("W9201", LintLevel::Allow);
+    policy.set("W9202", LintLevel::Deny);
+
+    let mut sink: DiagnosticSink<BasicPosition> =
+        DiagnosticSink::new(SinkConfig {
+        max_errors: Some(1), ..SinkConfig::new()
+    }).with_policy(policy);
+
+    assert_eq!(sink.emit(Message::warning("a").with_code("W9201")),
+               Ok(Emitted::Allowed));
+    assert_eq!(sink.emit(Message::warning("b").with_code("W9203")),
+               Ok(Emitted::Shown));
+    assert!(sink.emit(Message::warning("c").with_code("W9202")).is_err());
+    assert!(sink.messages()[1].is_error());
+}
+
+#[test]
+fn test_suppress() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let span = |line, end_line| Location::Span {
+        start: Point { line, col: 1 }, end: Point { line: end_line, col: 1 }
+    };
+    let warning = |filename, line| Message::warning("unused variable")
+        .with_code("W9301")
+        .with_position(BasicPosition::from(FilePosition {
+            filename, loc: span(line, line + 1)
+        }));
+    let mut policy = WarningPolicy::new();
+
+    policy.set("W9302", LintLevel::Forbid);
+
+    let mut sink = DiagnosticSink::default().with_policy(policy);
+
+    sink.suppress(a, span(10, 20), &["W9301", "W9302"]);
+
+    assert_eq!(sink.emit(warning(a, 12)), Ok(Emitted::Allowed));
+    assert_eq!(sink.emit(warning(a, 9)), Ok(Emitted::Shown));
+    assert_eq!(sink.emit(warning(a, 20)), Ok(Emitted::Shown));
+    assert_eq!(sink.emit(warning(b, 12)), Ok(Emitted::Shown));
+    assert_eq!(sink.emit(warning(a, 13).with_code("W9302")),
+               Ok(Emitted::Shown));
+    assert_eq!(sink.emit(Message::error("mismatched types")
+                         .with_position(BasicPosition::from(FilePosition {
+                             filename: a, loc: span(14, 15)
+                         }))), Ok(Emitted::Shown));
+}
+
+#[test]
+fn test_file_metrics() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::FilePosition;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    const FILE: &str = "test_file_metrics.salt";
+
+    let mut names = StrIntern::new();
+    let filename = names.filename(FILE);
+    let pos = |line| FilePosition {
+        filename, loc: Location::Point { point: Point { line, col: 1 } }
+    };
+    let mut sink = DiagnosticSink::new(SinkConfig {
+        per_code: Some(1), ..SinkConfig::new()
+    });
+
+    metrics::enable();
+
+    for &line in [1, 1, 2, 3].iter() {
+        sink.emit(Message::warning("unused").with_code("W1")
+                  .with_position(pos(line))).unwrap();
+    }
+
+    sink.emit(Message::error("mismatched types")
+              .with_position(pos(1))).unwrap();
+
+    assert_eq!(DIAGNOSTICS_EMITTED.get(FILE), 2);
+    assert_eq!(DIAGNOSTICS_DUPLICATE.get(FILE), 1);
+    assert_eq!(DIAGNOSTICS_SUPPRESSED.get(FILE), 2);
+    assert_eq!(metrics::report().keyed("diagnostics.emitted", FILE),
+               Some(2));
+}
@@ -0,0 +1,404 @@
+//! Named counters and histograms.
+//!
+//! Metrics are declared as statics next to the code that updates them:
+//!
+//! ```ignore
+//! static HITS: Counter = Counter::new("intern.hits");
+//!
+//! HITS.incr();
+//! ```
+//!
+//! A `KeyedCounter` keeps a count for each of a set of keys only known
+//! at run time, such as the number of diagnostics for each file.
+//!
+//! Collection is off by default, in which case updating a metric is a
+//! single relaxed load and branch.  A tool that wants metrics calls
+//! `enable` at startup, and `report` at shutdown to get the values of
+//! every metric that was updated, which can be written out as JSON to
+//! track values such as the intern hit rate across releases.
+
+use salt::common::json::Json;
+use salt::common::json::ToJson;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// Whether metrics are being collected.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Every metric that has been updated since collection was enabled.
+static REGISTRY: Mutex<Vec<Metric>> = Mutex::new(Vec::new());
+
+/// The number of histogram buckets: one for 0, and one for each power
+/// of two up to `2^63`.
+const BUCKETS: usize = 65;
+
+/// A named counter.
+#[derive(Debug)]
+pub struct Counter {
+    name: &'static str,
+    value: AtomicU64,
+    registered: AtomicBool
+}
+
+/// A named histogram of `u64` values, with logarithmic buckets.
+#[derive(Debug)]
+pub struct Histogram {
+    name: &'static str,
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+    /// Bucket 0 counts zeros, and bucket `n` counts values from
+    /// `2^(n-1)` to `2^n - 1`.
+    buckets: [AtomicU64; BUCKETS],
+    registered: AtomicBool
+}
+
+/// A named set of counters, one for each key.
+#[derive(Debug)]
+pub struct KeyedCounter {
+    name: &'static str,
+    values: Mutex<BTreeMap<String, u64>>,
+    registered: AtomicBool
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Metric {
+    Counter(&'static Counter),
+    Histogram(&'static Histogram),
+    Keyed(&'static KeyedCounter)
+}
+
+/// The values of a histogram at the time of a report.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HistogramSummary {
+    /// The name of the histogram.
+    pub name: String,
+    /// The number of values recorded.
+    pub count: u64,
+    /// The sum of the values recorded, saturating at `u64::MAX`.
+    pub sum: u64,
+    /// The largest value recorded.
+    pub max: u64,
+    /// Nonempty buckets, as the upper bound of each bucket and the
+    /// number of values in it.
+    pub buckets: Vec<(u64, u64)>
+}
+
+/// The values of all metrics that were updated, sorted by name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MetricsReport {
+    /// Counter names and values.
+    pub counters: Vec<(String, u64)>,
+    /// Histogram summaries.
+    pub histograms: Vec<HistogramSummary>,
+    /// Keyed counter names, and the value for each key, sorted by key.
+    pub keyed: Vec<(String, Vec<(String, u64)>)>
+}
+
+/// Start collecting metrics.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed)
+}
+
+/// Stop collecting metrics.  Values collected so far are kept.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed)
+}
+
+/// Check whether metrics are being collected.
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn register(registered: &AtomicBool, metric: Metric) {
+    if !registered.swap(true, Ordering::Relaxed) {
+        REGISTRY.lock().unwrap_or_else(|err| err.into_inner()).push(metric)
+    }
+}
+
+/// Get the values of every metric updated while collection was
+/// enabled.
+pub fn report() -> MetricsReport {
+    let registry = REGISTRY.lock().unwrap_or_else(|err| err.into_inner());
+    let mut out = MetricsReport::default();
+
+    for metric in registry.iter() {
+        match metric {
+            Metric::Counter(counter) =>
+                out.counters.push((counter.name.to_string(), counter.get())),
+            Metric::Histogram(histogram) =>
+                out.histograms.push(histogram.summary()),
+            Metric::Keyed(keyed) =>
+                out.keyed.push((keyed.name.to_string(), keyed.values()))
+        }
+    }
+
+    out.counters.sort();
+    out.histograms.sort_by(|a, b| a.name.cmp(&b.name));
+    out.keyed.sort();
+
+    out
+}
+
+impl Counter {
+    /// Create a counter named `name`.
+    pub const fn new(name: &'static str) -> Counter {
+        Counter { name, value: AtomicU64::new(0),
+                  registered: AtomicBool::new(false) }
+    }
+
+    /// Add `n` to the counter, if metrics are enabled.
+    #[inline]
+    pub fn add(&'static self, n: u64) {
+        if is_enabled() {
+            register(&self.registered, Metric::Counter(self));
+            self.value.fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    /// Add 1 to the counter, if metrics are enabled.
+    #[inline]
+    pub fn incr(&'static self) {
+        self.add(1)
+    }
+
+    /// Get the name of the counter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the value of the counter.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl Histogram {
+    /// Create a histogram named `name`.
+    pub const fn new(name: &'static str) -> Histogram {
+        Histogram { name, count: AtomicU64::new(0), sum: AtomicU64::new(0),
+                    max: AtomicU64::new(0),
+                    buckets: [const { AtomicU64::new(0) }; BUCKETS],
+                    registered: AtomicBool::new(false) }
+    }
+
+    /// Record `value`, if metrics are enabled.
+    #[inline]
+    pub fn record(&'static self, value: u64) {
+        if is_enabled() {
+            let bucket = (u64::BITS - value.leading_zeros()) as usize;
+
+            register(&self.registered, Metric::Histogram(self));
+            self.count.fetch_add(1, Ordering::Relaxed);
+            self.max.fetch_max(value, Ordering::Relaxed);
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+
+            // Saturate rather than wrap, without a lock.
+            let _ = self.sum.fetch_update(Ordering::Relaxed,
+                                          Ordering::Relaxed, |sum| {
+                Some(sum.saturating_add(value))
+            });
+        }
+    }
+
+    /// Get the name of the histogram.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the current values of the histogram.
+    pub fn summary(&self) -> HistogramSummary {
+        let buckets = self.buckets.iter().enumerate()
+            .map(|(i, n)| (i, n.load(Ordering::Relaxed)))
+            .filter(|&(_, n)| n != 0)
+            .map(|(i, n)| (if i == 0 { 0 } else { u64::MAX >> (64 - i) }, n))
+            .collect();
+
+        HistogramSummary { name: self.name.to_string(),
+                           count: self.count.load(Ordering::Relaxed),
+                           sum: self.sum.load(Ordering::Relaxed),
+                           max: self.max.load(Ordering::Relaxed), buckets }
+    }
+}
+
+impl KeyedCounter {
+    /// Create a keyed counter named `name`.
+    pub const fn new(name: &'static str) -> KeyedCounter {
+        KeyedCounter { name, values: Mutex::new(BTreeMap::new()),
+                       registered: AtomicBool::new(false) }
+    }
+
+    /// Add `n` to the counter for `key`, if metrics are enabled.  The
+    /// key is only formatted if they are.
+    #[inline]
+    pub fn add<K: Display>(&'static self, key: K, n: u64) {
+        if is_enabled() {
+            let mut values = self.values.lock()
+                .unwrap_or_else(|err| err.into_inner());
+
+            register(&self.registered, Metric::Keyed(self));
+            *values.entry(key.to_string()).or_insert(0) += n;
+        }
+    }
+
+    /// Add 1 to the counter for `key`, if metrics are enabled.
+    #[inline]
+    pub fn incr<K: Display>(&'static self, key: K) {
+        self.add(key, 1)
+    }
+
+    /// Get the name of the counter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the value of the counter for `key`.
+    pub fn get(&self, key: &str) -> u64 {
+        let values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+
+        values.get(key).cloned().unwrap_or(0)
+    }
+
+    /// Get the value for each key, sorted by key.
+    pub fn values(&self) -> Vec<(String, u64)> {
+        let values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+
+        values.iter().map(|(key, &value)| (key.clone(), value)).collect()
+    }
+}
+
+impl HistogramSummary {
+    /// Get the mean of the recorded values, if there are any.
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum as f64 / self.count as f64)
+        }
+    }
+}
+
+impl MetricsReport {
+    /// Get the value of the counter `name`, if it was updated.
+    pub fn counter(&self, name: &str) -> Option<u64> {
+        self.counters.iter()
+            .find(|(counter, _)| counter == name)
+            .map(|&(_, value)| value)
+    }
+
+    /// Get the summary of the histogram `name`, if it was updated.
+    pub fn histogram(&self, name: &str) -> Option<&HistogramSummary> {
+        self.histograms.iter().find(|histogram| histogram.name == name)
+    }
+
+    /// Get the value of the keyed counter `name` for `key`, if it was
+    /// updated.
+    pub fn keyed(&self, name: &str, key: &str) -> Option<u64> {
+        self.keyed.iter()
+            .find(|(keyed, _)| keyed == name)
+            .and_then(|(_, values)| values.iter().find(|(k, _)| k == key))
+            .map(|&(_, value)| value)
+    }
+}
+
+/// Buckets are written as `[[upper bound, count], ...]`.
+impl ToJson for HistogramSummary {
+    fn to_json(&self) -> Json {
+        let buckets = self.buckets.iter()
+            .map(|&(bound, n)| Json::Array(vec![Json::UInt(bound),
+                                                Json::UInt(n)]))
+            .collect();
+
+        Json::object()
+            .with("count", self.count)
+            .with("sum", self.sum)
+            .with("max", self.max)
+            .with("buckets", Json::Array(buckets))
+    }
+}
+
+/// Reports are written as
+/// `{"counters":{...},"histograms":{...},"keyed":{...}}`, keyed by
+/// metric name, with each keyed counter an object keyed by its keys.
+impl ToJson for MetricsReport {
+    fn to_json(&self) -> Json {
+        let counters = self.counters.iter()
+            .map(|(name, value)| (name.clone(), Json::UInt(*value)))
+            .collect();
+        let histograms = self.histograms.iter()
+            .map(|histogram| (histogram.name.clone(), histogram.to_json()))
+            .collect();
+        let keyed = self.keyed.iter()
+            .map(|(name, values)| {
+                let values = values.iter()
+                    .map(|(key, value)| (key.clone(), Json::UInt(*value)))
+                    .collect();
+
+                (name.clone(), Json::Object(values))
+            })
+            .collect();
+
+        Json::object()
+            .with("counters", Json::Object(counters))
+            .with("histograms", Json::Object(histograms))
+            .with("keyed", Json::Object(keyed))
+    }
+}
+
+#[test]
+fn test_metrics() {
+    static COUNTER: Counter = Counter::new("test.counter");
+    static SIZES: Histogram = Histogram::new("test.sizes");
+
+    enable();
+    COUNTER.incr();
+    COUNTER.add(2);
+
+    for &value in [0, 1, 5, 6, 100].iter() {
+        SIZES.record(value);
+    }
+
+    let report = report();
+    let sizes = report.histogram("test.sizes").unwrap();
+
+    assert_eq!(report.counter("test.counter"), Some(3));
+    assert_eq!(sizes.count, 5);
+    assert_eq!(sizes.max, 100);
+    assert_eq!(sizes.mean(), Some(22.4));
+    assert_eq!(sizes.buckets, vec![(0, 1), (1, 1), (7, 2), (127, 1)]);
+    assert_eq!(sizes.to_json().to_string(),
+               "{\"count\":5,\"sum\":112,\"max\":100,\
+                \"buckets\":[[0,1],[1,1],[7,2],[127,1]]}");
+}
+
+#[test]
+fn test_keyed() {
+    static BY_FILE: KeyedCounter = KeyedCounter::new("test.by_file");
+
+    enable();
+    BY_FILE.incr("b.salt");
+    BY_FILE.add("a.salt", 2);
+    BY_FILE.incr("b.salt");
+
+    let report = report();
+
+    assert_eq!(BY_FILE.values(), [(String::from("a.salt"), 2),
+                                  (String::from("b.salt"), 2)]);
+    assert_eq!(report.keyed("test.by_file", "a.salt"), Some(2));
+    assert_eq!(report.keyed("test.by_file", "c.salt"), None);
+    assert!(report.to_json().to_string()
+            .contains("\"test.by_file\":{\"a.salt\":2,\"b.salt\":2}"));
+}
+
+#[test]
+fn test_unregistered() {
+    static UNUSED: Counter = Counter::new("test.unused");
+
+    assert_eq!(UNUSED.get(), 0);
+    assert_eq!(report().counter("test.unused"), None);
+}
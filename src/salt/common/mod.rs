@@ -1,4 +1,71 @@
+#[cfg(any(feature = "arbitrary", test))]
+pub mod arbitrary;
+#[cfg(feature = "std")]
+pub mod artifact;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "std")]
+pub mod collections;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod ctx;
+#[cfg(feature = "std")]
+pub mod debuginfo;
+#[cfg(feature = "std")]
+pub mod demangle;
+#[cfg(feature = "std")]
+pub mod doc;
+#[cfg(feature = "std")]
+pub mod env;
 pub mod filename;
+#[cfg(feature = "fs")]
+pub mod golden;
+#[cfg(feature = "std")]
+pub mod graph;
+pub mod hash;
+pub mod heapsize;
+#[cfg(feature = "std")]
+pub mod ipc;
+#[cfg(feature = "std")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod limits;
+#[cfg(feature = "std")]
+pub mod literal;
+#[cfg(feature = "std")]
+pub mod log;
+#[cfg(feature = "std")]
+pub mod mangle;
+#[cfg(feature = "alloc-stats")]
+pub mod memstats;
+#[cfg(feature = "std")]
+pub mod message;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod ordered;
+#[cfg(feature = "fs")]
+pub mod output;
 pub mod position;
+#[cfg(feature = "std")]
+pub mod progress;
+#[cfg(feature = "std")]
+pub mod query;
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod session;
 pub mod str;
 pub mod symbol;
+#[cfg(feature = "std")]
+pub mod symbol_path;
+#[cfg(feature = "std")]
+pub mod table;
+#[cfg(feature = "std")]
+pub mod target;
+#[cfg(feature = "std")]
+pub mod version;
+#[cfg(feature = "std")]
+pub mod vfs;
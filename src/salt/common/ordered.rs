@@ -0,0 +1,213 @@
+//! Infrastructure for deterministic output from parallel compilation.
+//!
+//! Work is divided into jobs, each of which is identified by a
+//! `JobKey` that does not depend on scheduling (for example, the index
+//! of a file and then of an item within it).  Anything a job produces
+//! that ends up in output (diagnostics, dumps, dependency entries) is
+//! collected in an `OrderedBuffer` and released in key order, so that
+//! running with any number of threads produces byte-identical output.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+/// A stable ordering key for a unit of work.  Keys are sequences of
+/// indexes, compared lexicographically, so a job's sub-jobs sort
+/// immediately after it and before the job's successor.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct JobKey(Vec<u32>);
+
+/// A buffer that collects items produced by concurrent jobs and
+/// releases them in a reproducible order.
+pub struct OrderedBuffer<T> {
+    /// Whether to order output by job key.
+    deterministic: bool,
+    /// Buffered items, keyed by job key and then arrival order.
+    items: Mutex<BTreeMap<(JobKey, usize), T>>,
+    /// Arrival counter, used to break ties and for non-deterministic
+    /// mode.
+    seq: AtomicUsize
+}
+
+impl JobKey {
+    /// Get the key for the root job.
+    pub fn root() -> JobKey {
+        JobKey(Vec::new())
+    }
+
+    /// Get the key of the `idx`th sub-job of this job.
+    pub fn child(&self, idx: u32) -> JobKey {
+        let mut out = self.0.clone();
+
+        out.push(idx);
+
+        JobKey(out)
+    }
+
+    /// Get the components of the key.
+    pub fn indexes(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl<T> OrderedBuffer<T> {
+    /// Create an `OrderedBuffer`.  If `deterministic` is set, items
+    /// are released in job key order; otherwise they are released in
+    /// the order they arrived.
+    pub fn new(deterministic: bool) -> OrderedBuffer<T> {
+        OrderedBuffer { deterministic, items: Mutex::new(BTreeMap::new()),
+                        seq: AtomicUsize::new(0) }
+    }
+
+    /// Add an item produced by the job `key`.  Items from the same
+    /// job keep their relative order.
+    pub fn push(&self, key: &JobKey, item: T) {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let key = if self.deterministic {
+            key.clone()
+        } else {
+            JobKey::root()
+        };
+
+        self.items.lock().unwrap().insert((key, seq), item);
+    }
+
+    /// Remove and return all buffered items.
+    pub fn drain(&self) -> Vec<T> {
+        let items = ::std::mem::take(&mut *self.items.lock().unwrap());
+
+        items.into_values().collect()
+    }
+
+    /// Get the number of buffered items.
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    /// Check whether the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.items.lock().unwrap().is_empty()
+    }
+}
+
+/// Apply `f` to every element of `inputs` using up to `threads`
+/// threads, returning results in input order regardless of how the
+/// work was scheduled.  `f` is given the job key for each input.
+pub fn par_map<I, O, F>(inputs: Vec<I>, threads: usize, f: F) -> Vec<O>
+    where I: Send, O: Send, F: Fn(&JobKey, I) -> O + Sync {
+    let threads = threads.max(1);
+    let root = JobKey::root();
+
+    if threads == 1 || inputs.len() <= 1 {
+        return inputs.into_iter()
+            .enumerate()
+            .map(|(i, input)| f(&root.child(i as u32), input))
+            .collect();
+    }
+
+    let len = inputs.len();
+    let work = Mutex::new(inputs.into_iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(len));
+
+    thread::scope(|scope| {
+        for _ in 0..threads.min(len) {
+            scope.spawn(|| loop {
+                let next = work.lock().unwrap().next();
+
+                match next {
+                    Some((i, input)) => {
+                        let out = f(&root.child(i as u32), input);
+
+                        results.lock().unwrap().push((i, out));
+                    },
+                    None => break
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+
+    results.sort_by_key(|&(i, _)| i);
+
+    results.into_iter().map(|(_, out)| out).collect()
+}
+
+/// Combine per-job results in job key order.  This makes reductions
+/// whose combining function is not commutative (such as concatenating
+/// output) independent of scheduling.
+pub fn ordered_reduce<T, A, F>(mut results: Vec<(JobKey, T)>, init: A,
+                               f: F) -> A
+    where F: FnMut(A, T) -> A {
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
+    results.into_iter().map(|(_, val)| val).fold(init, f)
+}
+
+#[test]
+fn test_job_key_order() {
+    let root = JobKey::root();
+    let a = root.child(0);
+    let a1 = a.child(1);
+    let b = root.child(1);
+
+    assert!(a < a1 && a1 < b);
+    assert_eq!(a1.indexes(), &[0, 1]);
+}
+
+#[test]
+fn test_buffer_deterministic() {
+    let buf = OrderedBuffer::new(true);
+    let root = JobKey::root();
+
+    buf.push(&root.child(2), "c");
+    buf.push(&root.child(0), "a1");
+    buf.push(&root.child(1), "b");
+    buf.push(&root.child(0), "a2");
+
+    assert_eq!(buf.drain(), vec!["a1", "a2", "b", "c"]);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_buffer_arrival() {
+    let buf = OrderedBuffer::new(false);
+    let root = JobKey::root();
+
+    buf.push(&root.child(2), "c");
+    buf.push(&root.child(0), "a");
+
+    assert_eq!(buf.drain(), vec!["c", "a"]);
+}
+
+#[test]
+fn test_par_map_matches_serial() {
+    let inputs: Vec<u32> = (0..100).collect();
+    let buf = OrderedBuffer::new(true);
+    let serial = par_map(inputs.clone(), 1, |_, n| n * 2);
+    let parallel = par_map(inputs, 8, |key, n| {
+        buf.push(key, format!("job {}", n));
+
+        n * 2
+    });
+    let expected: Vec<String> = (0..100).map(|n| format!("job {}", n))
+        .collect();
+
+    assert_eq!(serial, parallel);
+    assert_eq!(buf.drain(), expected);
+}
+
+#[test]
+fn test_ordered_reduce() {
+    let root = JobKey::root();
+    let results = vec![(root.child(1), "b"), (root.child(0), "a")];
+    let out = ordered_reduce(results, String::new(), |mut acc, s| {
+        acc.push_str(s);
+
+        acc
+    });
+
+    assert_eq!(out, "ab");
+}
@@ -0,0 +1,194 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::fs::remove_file;
+use std::fs::rename;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// Counter used to generate distinct temporary names within a process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// An output file that is written atomically.  Contents are written to
+/// a temporary file in the destination directory, which is renamed
+/// over the destination by `commit`.  If the `OutputFile` is dropped
+/// without being committed (including during a panic), the temporary
+/// file is removed and the destination is left untouched.
+pub struct OutputFile {
+    /// The final destination path.
+    dest: PathBuf,
+    /// The temporary file path.
+    tmp: PathBuf,
+    /// The open temporary file, or `None` once committed.
+    file: Option<File>
+}
+
+impl OutputFile {
+    /// Create an `OutputFile` that will be written to `dest`.
+    pub fn create<P: AsRef<Path>>(dest: P) -> Result<OutputFile> {
+        let dest = dest.as_ref().to_path_buf();
+        let name = match dest.file_name() {
+            Some(name) => name.to_os_string(),
+            None => return Err(Error::new(ErrorKind::InvalidInput,
+                                          "output path has no file name"))
+        };
+        let dir = match dest.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from(".")
+        };
+
+        loop {
+            let count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let mut tmpname = OsString::from(".");
+
+            tmpname.push(&name);
+            tmpname.push(format!(".{}.{}.tmp", process::id(), count));
+
+            let tmp = dir.join(tmpname);
+
+            match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+                Ok(file) => return Ok(OutputFile { dest, tmp,
+                                                   file: Some(file) }),
+                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err)
+            }
+        }
+    }
+
+    /// Get the destination path.
+    pub fn path(&self) -> &Path {
+        &self.dest
+    }
+
+    /// Get the path of the temporary file being written.
+    pub fn temp_path(&self) -> &Path {
+        &self.tmp
+    }
+
+    /// Flush all contents to disk and atomically replace the
+    /// destination with them.
+    pub fn commit(mut self) -> Result<()> {
+        let mut file = self.file.take().expect("output file already closed");
+
+        file.flush()?;
+        file.sync_all()?;
+        drop(file);
+
+        match rename(&self.tmp, &self.dest) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let _ = remove_file(&self.tmp);
+
+                Err(err)
+            }
+        }
+    }
+
+    /// Abandon the output, removing the temporary file.  This is
+    /// equivalent to dropping the `OutputFile`.
+    pub fn discard(self) {}
+
+    fn file(&mut self) -> &mut File {
+        self.file.as_mut().expect("output file already closed")
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.file().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file().flush()
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            drop(file);
+
+            let _ = remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Atomically write `contents` to `dest`.
+pub fn write_atomic<P: AsRef<Path>>(dest: P, contents: &[u8]) -> Result<()> {
+    let mut out = OutputFile::create(dest)?;
+
+    out.write_all(contents)?;
+    out.commit()
+}
+
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = ::std::env::temp_dir()
+        .join(format!("salt-output-{}-{}", name, process::id()));
+
+    ::std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+#[test]
+fn test_commit() {
+    let dir = test_dir("commit");
+    let dest = dir.join("out.o");
+    let mut out = OutputFile::create(&dest).unwrap();
+    let tmp = out.temp_path().to_path_buf();
+
+    out.write_all(b"hello").unwrap();
+    assert!(!dest.exists());
+    out.commit().unwrap();
+
+    assert!(!tmp.exists());
+    assert_eq!(::std::fs::read(&dest).unwrap(), b"hello");
+
+    ::std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_drop_preserves_old() {
+    let dir = test_dir("drop");
+    let dest = dir.join("out.o");
+
+    write_atomic(&dest, b"old").unwrap();
+
+    let mut out = OutputFile::create(&dest).unwrap();
+    let tmp = out.temp_path().to_path_buf();
+
+    out.write_all(b"new and truncated").unwrap();
+    drop(out);
+
+    assert!(!tmp.exists());
+    assert_eq!(::std::fs::read(&dest).unwrap(), b"old");
+
+    ::std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_panic_cleans_up() {
+    let dir = test_dir("panic");
+    let dest = dir.join("out.o");
+    let tmp = ::std::panic::catch_unwind(|| {
+        let mut out = OutputFile::create(&dest).unwrap();
+
+        out.write_all(b"partial").unwrap();
+
+        panic!("{}", out.temp_path().display())
+    }).unwrap_err();
+    let tmp = tmp.downcast::<String>().unwrap();
+
+    assert!(!Path::new(tmp.as_str()).exists());
+    assert!(!dest.exists());
+
+    ::std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,570 @@
+//! Emission of DWARF debugging information from `DWARFPosition`s.
+//!
+//! A backend annotates the code it generates with `DWARFPosition`s,
+//! in address order, and `DwarfEmitter` turns them into the contents
+//! of the `.debug_line`, `.debug_abbrev` and `.debug_info` sections
+//! for one compilation unit.  The output is DWARF 4, 32-bit format,
+//! little-endian, with 8-byte addresses.
+//!
+//! Addresses are offsets from the start of the code, and the
+//! sections assume they are the only unit in the object, so the
+//! backend must relocate the addresses (`DW_LNE_set_address` and
+//! `DW_AT_low_pc`) when it places the code.
+
+use salt::common::filename::Filename;
+use salt::common::position::DWARFPosition;
+use salt::common::position::FilePosition;
+use std::fmt::Display;
+
+/// The DWARF version produced.
+const VERSION: u16 = 4;
+/// The size of an address in bytes.
+const ADDRESS_SIZE: u8 = 8;
+/// The smallest line advance a special opcode encodes.
+const LINE_BASE: i64 = -5;
+/// The number of line advances special opcodes encode.
+const LINE_RANGE: i64 = 14;
+/// The first special opcode.
+const OPCODE_BASE: u8 = 13;
+/// The number of operands of each standard opcode.
+const STANDARD_OPCODE_LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+/// Standard opcode to advance the address.
+const DW_LNS_ADVANCE_PC: u8 = 0x02;
+/// Standard opcode to advance the line.
+const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+/// Standard opcode to set the file.
+const DW_LNS_SET_FILE: u8 = 0x04;
+/// Standard opcode to set the column.
+const DW_LNS_SET_COLUMN: u8 = 0x05;
+/// Extended opcode to end a sequence.
+const DW_LNE_END_SEQUENCE: u8 = 0x01;
+/// Extended opcode to set the address.
+const DW_LNE_SET_ADDRESS: u8 = 0x02;
+
+/// Abbreviation code of the compilation unit entry.
+const ABBREV_COMPILE_UNIT: u8 = 1;
+/// Abbreviation code of subprogram entries.
+const ABBREV_SUBPROGRAM: u8 = 2;
+
+/// The `.debug_abbrev` section, describing the two kinds of entry
+/// produced.  Every code, tag, attribute and form is below 128, so
+/// each is a single-byte LEB128.
+const DEBUG_ABBREV: [u8; 29] = [
+    // Compilation unit, with children: name (string), stmt_list
+    // (sec_offset), low_pc (addr), high_pc (data8).
+    ABBREV_COMPILE_UNIT, 0x11, 1,
+    0x03, 0x08, 0x10, 0x17, 0x11, 0x01, 0x12, 0x07, 0, 0,
+    // Subprogram, without children: name (string), decl_file
+    // (data4), decl_line (data4), low_pc (addr), high_pc (data8).
+    ABBREV_SUBPROGRAM, 0x2e, 0,
+    0x03, 0x08, 0x3a, 0x06, 0x3b, 0x06, 0x11, 0x01, 0x12, 0x07, 0, 0,
+    0
+];
+
+/// The contents of the debugging sections for one compilation unit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DwarfSections {
+    /// The `.debug_line` section.
+    pub debug_line: Vec<u8>,
+    /// The `.debug_abbrev` section.
+    pub debug_abbrev: Vec<u8>,
+    /// The `.debug_info` section.
+    pub debug_info: Vec<u8>
+}
+
+/// The code generated for a definition.
+#[derive(Clone, Debug)]
+struct Subprogram<D> {
+    /// The definition's ID.
+    id: D,
+    /// The line table's index of the file of the definition.
+    file: u32,
+    /// The line of the definition.
+    line: u32,
+    /// The lowest address of the definition's code.
+    low: u64,
+    /// The address just past the definition's code.
+    high: u64
+}
+
+/// Builder for the debugging sections of a compilation unit, from
+/// positions annotating generated code.  Each annotation applies from
+/// its address up to the next; the code of a definition runs from its
+/// first annotated address to the end of its last annotation.
+pub struct DwarfEmitter<'a, D> {
+    /// The name of the compilation unit.
+    name: String,
+    /// The size of the smallest instruction, by which all address
+    /// differences are divided.
+    min_inst_length: u8,
+    /// The files in the line table.
+    files: Vec<Filename<'a>>,
+    /// The line number program.
+    program: Vec<u8>,
+    /// The definitions, in the order their code first appears.
+    subprograms: Vec<Subprogram<D>>,
+    /// The lowest address annotated.
+    low: Option<u64>,
+    /// The address of the last annotation, and the index of its
+    /// definition.
+    last: Option<(u64, Option<usize>)>,
+    /// The address register of the line number program, if in a
+    /// sequence.
+    address: Option<u64>,
+    /// The file register of the line number program.
+    file: u32,
+    /// The line register of the line number program.
+    line: u32,
+    /// The column register of the line number program.
+    col: u32
+}
+
+/// Append `value` to `out` as unsigned LEB128.
+fn uleb128(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+
+        value >>= 7;
+
+        if value == 0 {
+            out.push(byte);
+
+            return;
+        }
+
+        out.push(byte | 0x80)
+    }
+}
+
+/// Append `value` to `out` as signed LEB128.
+fn sleb128(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+
+        value >>= 7;
+
+        if (value == 0 && byte & 0x40 == 0) ||
+           (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+
+            return;
+        }
+
+        out.push(byte | 0x80)
+    }
+}
+
+/// Append `s` to `out` as a null-terminated string.
+fn string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(s.as_bytes());
+    out.push(0)
+}
+
+/// Prefix `body` with its length, as a 32-bit DWARF unit is.
+fn with_length(body: Vec<u8>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + 4);
+
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend(body);
+
+    out
+}
+
+impl<'a, D: Clone + Display + Eq> DwarfEmitter<'a, D> {
+    /// Create an emitter for the compilation unit `name`, whose
+    /// instructions are multiples of `min_inst_length` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_inst_length` is 0.
+    pub fn new(name: &str, min_inst_length: u8) -> DwarfEmitter<'a, D> {
+        assert!(min_inst_length > 0, "minimum instruction length is 0");
+
+        DwarfEmitter { name: name.to_string(), min_inst_length,
+                       files: Vec::new(), program: Vec::new(),
+                       subprograms: Vec::new(), low: None, last: None,
+                       address: None, file: 1, line: 1, col: 0 }
+    }
+
+    /// Get the files in the line table, in order; DWARF numbers them
+    /// from 1.
+    pub fn files(&self) -> &[Filename<'a>] {
+        &self.files
+    }
+
+    /// Get the line table's index of `filename`, adding it if needed.
+    fn file_index(&mut self, filename: Filename<'a>) -> u32 {
+        match self.files.iter().position(|file| *file == filename) {
+            Some(idx) => idx as u32 + 1,
+            None => {
+                self.files.push(filename);
+
+                self.files.len() as u32
+            }
+        }
+    }
+
+    /// Get the index of the definition `id` at `pos`, adding it with
+    /// code starting at `address` if needed.
+    fn subprogram(&mut self, id: &D, pos: &FilePosition<'a>,
+                  address: u64) -> usize {
+        match self.subprograms.iter().position(|sub| sub.id == *id) {
+            Some(idx) => idx,
+            None => {
+                let file = self.file_index(pos.filename);
+
+                self.subprograms.push(Subprogram {
+                    id: id.clone(), file, line: pos.loc.start().line,
+                    low: address, high: address
+                });
+
+                self.subprograms.len() - 1
+            }
+        }
+    }
+
+    /// Get the number of minimum-length instructions from `from` to
+    /// `address`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `address` is before `from`.
+    fn advance(&self, from: u64, address: u64) -> u64 {
+        assert!(address >= from, "address {:#x} is before {:#x}",
+                address, from);
+
+        (address - from) / self.min_inst_length as u64
+    }
+
+    /// Append a row to the line number program, for `pos` at
+    /// `address`.
+    fn row(&mut self, address: u64, pos: &FilePosition<'a>) {
+        let file = self.file_index(pos.filename);
+        let start = pos.loc.start();
+        let advance = match self.address {
+            Some(from) => self.advance(from, address),
+            None => {
+                self.program.extend_from_slice(&[0, 1 + ADDRESS_SIZE,
+                                                 DW_LNE_SET_ADDRESS]);
+                self.program.extend_from_slice(&address.to_le_bytes());
+
+                0
+            }
+        };
+        let mut line_delta = start.line as i64 - self.line as i64;
+
+        if file != self.file {
+            self.program.push(DW_LNS_SET_FILE);
+            uleb128(&mut self.program, file as u64);
+        }
+
+        if start.col != self.col {
+            self.program.push(DW_LNS_SET_COLUMN);
+            uleb128(&mut self.program, start.col as u64);
+        }
+
+        if !(LINE_BASE..LINE_BASE + LINE_RANGE).contains(&line_delta) {
+            self.program.push(DW_LNS_ADVANCE_LINE);
+            sleb128(&mut self.program, line_delta);
+            line_delta = 0;
+        }
+
+        let special = |advance: u64| {
+            (line_delta - LINE_BASE) as u64 + LINE_RANGE as u64 * advance +
+                OPCODE_BASE as u64
+        };
+
+        if special(advance) <= 255 {
+            self.program.push(special(advance) as u8)
+        } else {
+            self.program.push(DW_LNS_ADVANCE_PC);
+            uleb128(&mut self.program, advance);
+            self.program.push(special(0) as u8)
+        }
+
+        self.address = Some(address);
+        self.file = file;
+        self.line = start.line;
+        self.col = start.col;
+    }
+
+    /// Annotate the code at `address` with `pos`.  Positions with no
+    /// file position end the code of the previous annotation without
+    /// adding a row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `address` is before that of the previous annotation.
+    pub fn add<T>(&mut self, address: u64, pos: &DWARFPosition<'a, T, D>) {
+        if let Some((last, _)) = self.last {
+            self.advance(last, address);
+        }
+
+        self.end_last(address);
+
+        let def = pos.definition()
+            .map(|(id, pos)| self.subprogram(id, pos, address));
+
+        if let Some(filepos) = pos.file_position() {
+            self.row(address, filepos)
+        }
+
+        self.low.get_or_insert(address);
+        self.last = Some((address, def));
+    }
+
+    /// End the code of the last annotation at `address`.
+    fn end_last(&mut self, address: u64) {
+        if let Some((_, Some(idx))) = self.last {
+            let sub = &mut self.subprograms[idx];
+
+            sub.high = sub.high.max(address)
+        }
+    }
+
+    /// End the sequence of rows at `address`, the end of the code, as
+    /// the code of a unit should be.  Later annotations start a new
+    /// sequence, and may go back to lower addresses.
+    pub fn end_sequence(&mut self, address: u64) {
+        if let Some((last, _)) = self.last {
+            self.advance(last, address);
+        }
+
+        self.end_last(address);
+        self.last = None;
+
+        if let Some(from) = self.address {
+            let advance = self.advance(from, address);
+
+            if advance > 0 {
+                self.program.push(DW_LNS_ADVANCE_PC);
+                uleb128(&mut self.program, advance);
+            }
+
+            self.program.extend_from_slice(&[0, 1, DW_LNE_END_SEQUENCE]);
+            self.address = None;
+            self.file = 1;
+            self.line = 1;
+            self.col = 0;
+        }
+    }
+
+    /// Get the `.debug_line` section.
+    fn debug_line(&self) -> Vec<u8> {
+        let mut header = vec![self.min_inst_length, 1, 1, LINE_BASE as u8,
+                              LINE_RANGE as u8, OPCODE_BASE];
+
+        header.extend_from_slice(&STANDARD_OPCODE_LENGTHS);
+        // No include directories: all files are relative to the
+        // compilation directory.
+        header.push(0);
+
+        for file in &self.files {
+            string(&mut header, &file.to_string());
+            // Directory, modification time and length.
+            header.extend_from_slice(&[0, 0, 0]);
+        }
+
+        header.push(0);
+
+        let mut body = VERSION.to_le_bytes().to_vec();
+
+        body.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        body.extend(header);
+        body.extend_from_slice(&self.program);
+
+        with_length(body)
+    }
+
+    /// Get the `.debug_info` section, for code from `low` to `high`.
+    fn debug_info(&self, low: u64, high: u64) -> Vec<u8> {
+        let mut body = VERSION.to_le_bytes().to_vec();
+
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.push(ADDRESS_SIZE);
+        body.push(ABBREV_COMPILE_UNIT);
+        string(&mut body, &self.name);
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&low.to_le_bytes());
+        body.extend_from_slice(&(high - low).to_le_bytes());
+
+        for sub in &self.subprograms {
+            body.push(ABBREV_SUBPROGRAM);
+            string(&mut body, &sub.id.to_string());
+            body.extend_from_slice(&sub.file.to_le_bytes());
+            body.extend_from_slice(&sub.line.to_le_bytes());
+            body.extend_from_slice(&sub.low.to_le_bytes());
+            body.extend_from_slice(&(sub.high - sub.low).to_le_bytes());
+        }
+
+        body.push(0);
+
+        with_length(body)
+    }
+
+    /// End the code at `end`, and get the sections.
+    pub fn finish(mut self, end: u64) -> DwarfSections {
+        self.end_sequence(end);
+
+        let low = self.low.unwrap_or(end).min(end);
+        let high = self.subprograms.iter().map(|sub| sub.high)
+            .fold(end, u64::max);
+
+        DwarfSections { debug_line: self.debug_line(),
+                        debug_abbrev: DEBUG_ABBREV.to_vec(),
+                        debug_info: self.debug_info(low, high) }
+    }
+}
+
+#[test]
+fn test_dwarf_emit() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::Location;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("a.salt");
+    let filepos = |line, col| FilePosition {
+        filename, loc: Location::Point { point: Point { line, col } }
+    };
+    let def = |line, col| DWARFPosition::Def { id: "main",
+                                               pos: filepos(line, col) };
+    let block: DWARFPosition<(), &str> =
+        DWARFPosition::Block { ctx: Box::new(def(2, 1)), pos: filepos(3, 5) };
+    let far: DWARFPosition<(), &str> = DWARFPosition::Basic {
+        pos: BasicPosition::Content { filepos: filepos(40, 1) }
+    };
+    let mut emitter = DwarfEmitter::new("a.salt", 1);
+
+    emitter.add::<()>(0x10, &def(2, 1));
+    emitter.add(0x14, &block);
+    emitter.add(0x400, &far);
+
+    let sections = emitter.finish(0x408);
+
+    assert_eq!(sections.debug_line[4..],
+               [4, 0, 30, 0, 0, 0,
+                1, 1, 1, 0xfb, 14, 13, 0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1, 0,
+                b'a', b'.', b's', b'a', b'l', b't', 0, 0, 0, 0, 0,
+                // Set the address to 0x10, then column 1, line 2.
+                0, 9, 2, 0x10, 0, 0, 0, 0, 0, 0, 0, 5, 1, 19,
+                // Column 5, line 3, 4 bytes on.
+                5, 5, 75,
+                // Column 1, line 40, 0x3ec bytes on.
+                5, 1, 3, 37, 2, 0xec, 7, 18,
+                // End 8 bytes on.
+                2, 8, 0, 1, 1][..]);
+    assert_eq!(&sections.debug_line[..4],
+               &(sections.debug_line.len() as u32 - 4).to_le_bytes());
+    assert_eq!(sections.debug_info[4..],
+               [4, 0, 0, 0, 0, 0, 8,
+                1, b'a', b'.', b's', b'a', b'l', b't', 0, 0, 0, 0, 0,
+                0x10, 0, 0, 0, 0, 0, 0, 0, 0xf8, 3, 0, 0, 0, 0, 0, 0,
+                2, b'm', b'a', b'i', b'n', 0, 1, 0, 0, 0, 2, 0, 0, 0,
+                0x10, 0, 0, 0, 0, 0, 0, 0, 0xf0, 3, 0, 0, 0, 0, 0, 0,
+                0][..]);
+}
+
+#[test]
+fn test_dwarf_file_change() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::Location;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let content = |filename, line| DWARFPosition::Basic {
+        pos: BasicPosition::Content {
+            filepos: FilePosition {
+                filename,
+                loc: Location::Point { point: Point { line, col: 0 } }
+            }
+        }
+    };
+    let mut emitter: DwarfEmitter<&str> = DwarfEmitter::new("u", 4);
+
+    emitter.add::<()>(0x0, &content(a, 1));
+    emitter.add::<()>(0x8, &content(b, 1));
+    emitter.add::<()>(0xc, &content(a, 3));
+
+    assert_eq!(emitter.files(), [a, b]);
+
+    let sections = emitter.finish(0x10);
+
+    assert_eq!(sections.debug_line,
+               [69, 0, 0, 0, 4, 0, 40, 0, 0, 0,
+                4, 1, 1, 0xfb, 14, 13, 0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1, 0,
+                b'a', b'.', b's', b'a', b'l', b't', 0, 0, 0, 0,
+                b'b', b'.', b's', b'a', b'l', b't', 0, 0, 0, 0, 0,
+                // Set the address to 0, on line 1 of file 1.
+                0, 9, 2, 0, 0, 0, 0, 0, 0, 0, 0, 18,
+                // File 2, 2 instructions of 4 bytes on.
+                4, 2, 46,
+                // File 1, line 3, 1 instruction on.
+                4, 1, 34,
+                // End 1 instruction on.
+                2, 1, 0, 1, 1]);
+}
+
+#[test]
+fn test_dwarf_subprograms() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::BasicPosition;
+    use salt::common::position::Location;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("a.salt");
+    let def = |id, line| DWARFPosition::Def {
+        id, pos: FilePosition {
+            filename, loc: Location::Point { point: Point { line, col: 0 } }
+        }
+    };
+    let pad: DWARFPosition<(), &str> = DWARFPosition::Basic {
+        pos: BasicPosition::Synthetic { desc: "padding" }
+    };
+    let mut emitter = DwarfEmitter::new("u", 1);
+
+    emitter.add::<()>(0x20, &def("g", 5));
+    // Code with no file position ends `g` without adding a row.
+    emitter.add(0x28, &pad);
+    emitter.add::<()>(0x30, &def("h", 9));
+
+    let sections = emitter.finish(0x38);
+
+    assert_eq!(sections.debug_info,
+               [85, 0, 0, 0, 4, 0, 0, 0, 0, 0, 8,
+                // The unit covers 0x20 to 0x38.
+                1, b'u', 0, 0, 0, 0, 0,
+                0x20, 0, 0, 0, 0, 0, 0, 0, 0x18, 0, 0, 0, 0, 0, 0, 0,
+                // `g`, from line 5, covers 0x20 to 0x28.
+                2, b'g', 0, 1, 0, 0, 0, 5, 0, 0, 0,
+                0x20, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+                // `h`, from line 9, covers 0x30 to 0x38.
+                2, b'h', 0, 1, 0, 0, 0, 9, 0, 0, 0,
+                0x30, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+                0]);
+}
+
+#[test]
+fn test_dwarf_empty() {
+    let sections = DwarfEmitter::<&str>::new("u", 1).finish(0);
+
+    assert_eq!(sections.debug_line,
+               [26, 0, 0, 0, 4, 0, 20, 0, 0, 0,
+                1, 1, 1, 0xfb, 14, 13, 0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1,
+                0, 0]);
+    assert_eq!(sections.debug_abbrev, DEBUG_ABBREV);
+    assert_eq!(sections.debug_info,
+               [31, 0, 0, 0, 4, 0, 0, 0, 0, 0, 8,
+                1, b'u', 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0]);
+}
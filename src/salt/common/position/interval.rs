@@ -0,0 +1,334 @@
+use salt::common::heapsize::HeapSize;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use std::iter::FromIterator;
+use std::vec::Vec;
+
+/// An index of the locations of things in one file, such as syntax
+/// tree nodes, for finding those at a point, like the node under a
+/// cursor, or those overlapping a location.
+///
+/// This is an interval tree: a balanced binary tree ordered by start
+/// and end, in which each node records the greatest end in its
+/// subtree.  Insertion takes O(log n) time, and a query O(log n + k)
+/// time for k results.  Whether a location is at a point, or overlaps
+/// another, is as `Location::contains` and `Location::overlaps` say.
+#[derive(Clone, Debug)]
+pub struct IntervalIndex<Id> {
+    nodes: Vec<IntervalNode<Id>>,
+    root: Option<usize>
+}
+
+/// A node of an `IntervalIndex`, one for each location inserted.
+#[derive(Clone, Debug)]
+struct IntervalNode<Id> {
+    loc: Location,
+    id: Id,
+    left: Option<usize>,
+    right: Option<usize>,
+    /// The height of the subtree rooted here.
+    height: u32,
+    /// The greatest end of any location in the subtree rooted here.
+    max_end: Point
+}
+
+/// Swap the ends of `loc` if it is a span that ends before it starts.
+fn turn_around(loc: Location) -> Location {
+    match loc {
+        Location::Span { start, end } if end < start =>
+            Location::Span { start: end, end: start },
+        loc => loc
+    }
+}
+
+impl<Id> IntervalIndex<Id> {
+    /// Create an empty `IntervalIndex`.
+    pub fn new() -> IntervalIndex<Id> {
+        IntervalIndex { nodes: Vec::new(), root: None }
+    }
+
+    /// Get the number of locations inserted.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check whether no locations have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Insert `loc`, as the location of `id`.  Spans that end before
+    /// they start are turned around.
+    pub fn insert(&mut self, loc: Location, id: Id) {
+        let loc = turn_around(loc);
+        let new = self.nodes.len();
+
+        self.nodes.push(IntervalNode { max_end: loc.end(), loc, id,
+                                       left: None, right: None,
+                                       height: 1 });
+
+        let root = self.root;
+
+        self.root = Some(self.insert_at(root, new))
+    }
+
+    /// Get the ids of the locations containing `point`, ordered by
+    /// start, then end, then when they were inserted.
+    pub fn query_point(&self, point: Point) -> Vec<&Id> {
+        let mut out = Vec::new();
+
+        self.visit(self.root, point, point,
+                   &|loc| loc.contains(point), &mut out);
+
+        out.into_iter().map(|node| &node.id).collect()
+    }
+
+    /// Get the ids of the locations overlapping `loc`, ordered as for
+    /// `query_point`.  A span that ends before it starts is turned
+    /// around.
+    pub fn query_range(&self, loc: &Location) -> Vec<&Id> {
+        let loc = turn_around(loc.clone());
+        let mut out = Vec::new();
+
+        self.visit(self.root, loc.start(), loc.end(),
+                   &|other| other.overlaps(&loc), &mut out);
+
+        out.into_iter().map(|node| &node.id).collect()
+    }
+
+    /// Get the id of the innermost location containing `point`: the
+    /// one that starts last, and of those, ends first.  Of identical
+    /// locations, the one inserted last is innermost, as children are
+    /// usually inserted after their parents.
+    pub fn innermost(&self, point: Point) -> Option<&Id> {
+        let mut out = Vec::new();
+
+        self.visit(self.root, point, point,
+                   &|loc| loc.contains(point), &mut out);
+
+        out.into_iter()
+            .max_by(|a, b| a.loc.start().cmp(&b.loc.start())
+                            .then(b.loc.end().cmp(&a.loc.end())))
+            .map(|node| &node.id)
+    }
+
+    /// Iterate over the locations and ids, in the order they were
+    /// inserted.
+    pub fn iter(&self) -> impl Iterator<Item = (&Location, &Id)> {
+        self.nodes.iter().map(|node| (&node.loc, &node.id))
+    }
+
+    /// Collect, in order, the nodes in the subtree at `node` whose
+    /// locations might touch the points from `start` to `end`, and
+    /// satisfy `keep`.
+    fn visit<'s, F>(&'s self, node: Option<usize>, start: Point, end: Point,
+                    keep: &F, out: &mut Vec<&'s IntervalNode<Id>>)
+        where F: Fn(&Location) -> bool {
+        let node = match node {
+            Some(idx) if self.nodes[idx].max_end >= start => &self.nodes[idx],
+            _ => return
+        };
+
+        self.visit(node.left, start, end, keep, out);
+
+        if node.loc.start() <= end {
+            if keep(&node.loc) {
+                out.push(node)
+            }
+
+            self.visit(node.right, start, end, keep, out)
+        }
+    }
+
+    /// Insert the node `new` into the subtree at `node`, returning the
+    /// root of the subtree after rebalancing.
+    fn insert_at(&mut self, node: Option<usize>, new: usize) -> usize {
+        let idx = match node {
+            Some(idx) => idx,
+            None => return new
+        };
+        let key = |node: &IntervalNode<Id>| (node.loc.start(), node.loc.end());
+
+        if key(&self.nodes[new]) < key(&self.nodes[idx]) {
+            let left = self.nodes[idx].left;
+
+            self.nodes[idx].left = Some(self.insert_at(left, new))
+        } else {
+            let right = self.nodes[idx].right;
+
+            self.nodes[idx].right = Some(self.insert_at(right, new))
+        }
+
+        self.rebalance(idx)
+    }
+
+    /// Get the height of the subtree at `node`.
+    fn height(&self, node: Option<usize>) -> u32 {
+        node.map_or(0, |idx| self.nodes[idx].height)
+    }
+
+    /// Recompute the height and greatest end of `idx` from its
+    /// children.
+    fn update(&mut self, idx: usize) {
+        let (left, right) = (self.nodes[idx].left, self.nodes[idx].right);
+        let max_end = [left, right].iter()
+            .filter_map(|child| child.map(|child| self.nodes[child].max_end))
+            .fold(self.nodes[idx].loc.end(), Point::max);
+
+        self.nodes[idx].height = self.height(left).max(self.height(right)) + 1;
+        self.nodes[idx].max_end = max_end
+    }
+
+    /// Rotate the subtree at `idx` right, returning its new root.
+    fn rotate_right(&mut self, idx: usize) -> usize {
+        let left = self.nodes[idx].left.unwrap();
+
+        self.nodes[idx].left = self.nodes[left].right;
+        self.nodes[left].right = Some(idx);
+        self.update(idx);
+        self.update(left);
+
+        left
+    }
+
+    /// Rotate the subtree at `idx` left, returning its new root.
+    fn rotate_left(&mut self, idx: usize) -> usize {
+        let right = self.nodes[idx].right.unwrap();
+
+        self.nodes[idx].right = self.nodes[right].left;
+        self.nodes[right].left = Some(idx);
+        self.update(idx);
+        self.update(right);
+
+        right
+    }
+
+    /// Restore the balance of the subtree at `idx`, whose children
+    /// differ in height by at most 2, returning its new root.
+    fn rebalance(&mut self, idx: usize) -> usize {
+        let (left, right) = (self.nodes[idx].left, self.nodes[idx].right);
+
+        self.update(idx);
+
+        if self.height(left) > self.height(right) + 1 {
+            let left = left.unwrap();
+
+            if self.height(self.nodes[left].left) <
+               self.height(self.nodes[left].right) {
+                self.nodes[idx].left = Some(self.rotate_left(left))
+            }
+
+            self.rotate_right(idx)
+        } else if self.height(right) > self.height(left) + 1 {
+            let right = right.unwrap();
+
+            if self.height(self.nodes[right].right) <
+               self.height(self.nodes[right].left) {
+                self.nodes[idx].right = Some(self.rotate_right(right))
+            }
+
+            self.rotate_left(idx)
+        } else {
+            idx
+        }
+    }
+}
+
+impl<Id> Default for IntervalIndex<Id> {
+    fn default() -> IntervalIndex<Id> {
+        IntervalIndex::new()
+    }
+}
+
+impl<Id> Extend<(Location, Id)> for IntervalIndex<Id> {
+    fn extend<T: IntoIterator<Item = (Location, Id)>>(&mut self, iter: T) {
+        for (loc, id) in iter {
+            self.insert(loc, id)
+        }
+    }
+}
+
+impl<Id> FromIterator<(Location, Id)> for IntervalIndex<Id> {
+    fn from_iter<T>(iter: T) -> IntervalIndex<Id>
+        where T: IntoIterator<Item = (Location, Id)> {
+        let mut index = IntervalIndex::new();
+
+        index.extend(iter);
+
+        index
+    }
+}
+
+impl<Id: HeapSize> HeapSize for IntervalNode<Id> {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+    }
+}
+
+impl<Id: HeapSize> HeapSize for IntervalIndex<Id> {
+    fn heap_size(&self) -> usize {
+        self.nodes.heap_size()
+    }
+}
+
+#[test]
+fn test_innermost() {
+    let index: IntervalIndex<&str> = vec![
+        (Location::span((1, 1), (9, 1)), "fn"),
+        (Location::span((2, 5), (2, 20)), "call"),
+        (Location::span((2, 10), (2, 14)), "arg"),
+        (Location::span((2, 10), (2, 14)), "name"),
+        (Location::point((3, 1)), "empty")
+    ].into_iter().collect();
+    let at = |line, col| index.innermost(Point { line, col }).cloned();
+
+    assert_eq!(at(2, 11), Some("name"));
+    assert_eq!(at(2, 14), Some("call"));
+    assert_eq!(at(3, 1), Some("empty"));
+    assert_eq!(at(9, 1), None);
+    assert_eq!(index.query_point(Point { line: 2, col: 12 }),
+               [&"fn", &"call", &"arg", &"name"]);
+    assert_eq!(index.query_range(&Location::span((2, 1), (2, 6))),
+               [&"fn", &"call"]);
+    assert_eq!(index.query_range(&Location::span((3, 1), (2, 1))),
+               [&"fn", &"call", &"arg", &"name"]);
+}
+
+#[test]
+fn test_against_scan() {
+    use salt::common::arbitrary::Arbitrary;
+    use salt::common::arbitrary::check;
+
+    check("interval_index", 64, &[], |g| {
+        let locs: Vec<Location> = g.vec();
+        let mut index = IntervalIndex::new();
+
+        for (id, loc) in locs.iter().enumerate() {
+            index.insert(loc.clone(), id)
+        }
+
+        let point = Point::arbitrary(g);
+        let range = Location::arbitrary(g);
+        let scan = |keep: &dyn Fn(&Location) -> bool| {
+            locs.iter()
+                .enumerate()
+                .filter(|(_, loc)| keep(&turn_around((*loc).clone())))
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>()
+        };
+        let mut at: Vec<usize> =
+            index.query_point(point).into_iter().cloned().collect();
+        let mut overlapping: Vec<usize> =
+            index.query_range(&range).into_iter().cloned().collect();
+
+        at.sort();
+        overlapping.sort();
+
+        assert_eq!(at, scan(&|loc| loc.contains(point)));
+        assert_eq!(overlapping,
+                   scan(&|loc| loc.overlaps(&turn_around(range.clone()))));
+        assert!(index.height(index.root) <= 2 * (locs.len() as u32 + 1)
+                                                  .next_power_of_two()
+                                                  .trailing_zeros() + 1);
+    });
+}
@@ -0,0 +1,165 @@
+//! Conversions between positions and those of the Language Server
+//! Protocol.
+//!
+//! LSP positions have 0-based lines, and columns ("characters")
+//! counted in UTF-16 code units, whereas `Point`s have 1-based lines
+//! and columns counted in characters.  Converting a column needs the
+//! text of its line, which comes from a `SourceProvider`.
+
+use salt::common::filename::Filename;
+use salt::common::json::Json;
+use salt::common::json::ToJson;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use salt::common::position::SourceProvider;
+
+/// An LSP `Position`: a 0-based line, and a 0-based offset in UTF-16
+/// code units within the line.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Position {
+    /// The line, starting at 0.
+    pub line: u32,
+    /// The offset in the line, in UTF-16 code units, starting at 0.
+    pub character: u32
+}
+
+/// An LSP `Range`, from `start` up to but not including `end`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Range {
+    /// The start of the range.
+    pub start: Position,
+    /// The end of the range.
+    pub end: Position
+}
+
+/// Convert `point`, which is on a line with text `line`, to an LSP
+/// position.  Columns past the end of the line are kept, counting one
+/// code unit each.
+pub fn point_to_lsp(point: Point, line: &str) -> Position {
+    let col = point.col.saturating_sub(1) as usize;
+    let units: usize = line.chars().take(col).map(char::len_utf16).sum();
+    let past_end = col.saturating_sub(line.chars().count());
+
+    Position { line: point.line.saturating_sub(1),
+               character: (units + past_end) as u32 }
+}
+
+/// Convert `pos`, which is on a line with text `line`, to a `Point`.
+/// As LSP requires, offsets past the end of the line refer to its end.
+/// Offsets in the middle of a surrogate pair refer to the character it
+/// encodes.
+pub fn lsp_to_point(pos: Position, line: &str) -> Point {
+    let mut units = 0;
+    let mut col: u64 = 0;
+
+    for c in line.chars() {
+        if units >= pos.character as usize {
+            break;
+        }
+
+        units += c.len_utf16();
+        col += 1;
+    }
+
+    if units > pos.character as usize {
+        col -= 1;
+    }
+
+    Point::saturating(pos.line as u64 + 1, col + 1)
+}
+
+/// Convert `loc`, in the file `filename`, to an LSP range.  Returns
+/// `None` if the text of its lines is not available from `sources`.
+pub fn location_to_lsp<'a, S>(filename: Filename<'a>, loc: &Location,
+                              sources: &S) -> Option<Range>
+    where S: SourceProvider<'a> {
+    let convert = |point: Point| {
+        sources.source_line(filename, point.line)
+            .map(|line| point_to_lsp(point, line))
+    };
+
+    Some(Range { start: convert(loc.start())?, end: convert(loc.end())? })
+}
+
+/// Convert `range`, in the file `filename`, to a `Location`.  Empty
+/// ranges become point locations.  Returns `None` if the text of its
+/// lines is not available from `sources`.
+pub fn lsp_to_location<'a, S>(filename: Filename<'a>, range: Range,
+                              sources: &S) -> Option<Location>
+    where S: SourceProvider<'a> {
+    let convert = |pos: Position| {
+        sources.source_line(filename, pos.line + 1)
+            .map(|line| lsp_to_point(pos, line))
+    };
+    let start = convert(range.start)?;
+    let end = convert(range.end)?;
+
+    if start == end {
+        Some(Location::Point { point: start })
+    } else {
+        Some(Location::Span { start, end })
+    }
+}
+
+/// Positions are written as LSP expects: `{"line":N,"character":N}`.
+impl ToJson for Position {
+    fn to_json(&self) -> Json {
+        Json::object()
+            .with("line", self.line as u64)
+            .with("character", self.character as u64)
+    }
+}
+
+/// Ranges are written as LSP expects: `{"start":...,"end":...}`.
+impl ToJson for Range {
+    fn to_json(&self) -> Json {
+        Json::object()
+            .with("start", self.start.to_json())
+            .with("end", self.end.to_json())
+    }
+}
+
+#[test]
+fn test_point_conversion() {
+    // 'é' is one UTF-16 unit, and '😀' two.
+    let line = "aé😀b";
+
+    for (col, character) in [(1, 0), (2, 1), (3, 2), (4, 4), (5, 5)].iter() {
+        let point = Point { line: 3, col: *col };
+        let pos = Position { line: 2, character: *character };
+
+        assert_eq!(point_to_lsp(point, line), pos);
+        assert_eq!(lsp_to_point(pos, line), point);
+    }
+
+    assert_eq!(point_to_lsp(Point { line: 1, col: 7 }, line).character, 7);
+    assert_eq!(lsp_to_point(Position { line: 0, character: 3 }, line).col, 3);
+    assert_eq!(lsp_to_point(Position { line: 0, character: 99 }, line).col,
+               5);
+}
+
+#[test]
+fn test_location_conversion() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::SourceManager;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("a.salt");
+    let mut sources = SourceManager::new();
+
+    sources.add(filename, String::from("let 😀 = 1;\nf(😀)\n"));
+
+    let loc = Location::Span { start: Point { line: 1, col: 5 },
+                               end: Point { line: 2, col: 4 } };
+    let range = location_to_lsp(filename, &loc, &sources).unwrap();
+
+    assert_eq!(range, Range { start: Position { line: 0, character: 4 },
+                              end: Position { line: 1, character: 4 } });
+    assert_eq!(lsp_to_location(filename, range, &sources), Some(loc.clone()));
+    assert_eq!(range.to_json().to_string(),
+               "{\"start\":{\"line\":0,\"character\":4},\
+                \"end\":{\"line\":1,\"character\":4}}");
+    assert_eq!(location_to_lsp(names.filename("b.salt"), &loc, &sources),
+               None);
+}
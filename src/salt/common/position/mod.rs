@@ -1,13 +1,83 @@
+#[cfg(feature = "std")]
+use salt::common::cache::DecodeError;
+#[cfg(feature = "std")]
+use salt::common::cache::DecodeResult;
+#[cfg(feature = "std")]
+use salt::common::cache::Decodable;
+#[cfg(feature = "std")]
+use salt::common::cache::Decoder;
+#[cfg(feature = "std")]
+use salt::common::cache::Encodable;
+#[cfg(feature = "std")]
+use salt::common::cache::Encoder;
 use salt::common::filename::Filename;
+#[cfg(feature = "std")]
+use salt::common::filename::FilenameDisplay;
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+use std::boxed::Box;
 use std::convert::From;
 use std::cmp::Ordering;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result;
+use std::vec::Vec;
+
+pub use self::interval::IntervalIndex;
+#[cfg(feature = "std")]
+pub use self::nodes::NodeId;
+#[cfg(feature = "std")]
+pub use self::nodes::NodePositions;
+#[cfg(feature = "std")]
+pub use self::source::FileLoader;
+#[cfg(feature = "std")]
+pub use self::source::LineIndex;
+#[cfg(feature = "std")]
+pub use self::source::SourceCache;
+#[cfg(feature = "std")]
+pub use self::source::SourceFile;
+#[cfg(feature = "std")]
+pub use self::source::SourceManager;
+#[cfg(feature = "std")]
+pub use self::source::SourceProvider;
+#[cfg(feature = "std")]
+pub use self::sourcemap::SourceMap;
+#[cfg(feature = "std")]
+pub use self::span::ByteSpan;
+#[cfg(feature = "std")]
+pub use self::span::FileTable;
+#[cfg(feature = "std")]
+pub use self::table::PositionTable;
+#[cfg(feature = "std")]
+pub use self::table::decode_stream;
+#[cfg(feature = "std")]
+pub use self::table::encode_stream;
+
+#[cfg(feature = "dwarf")]
+pub mod dwarf_emit;
+mod interval;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+#[cfg(feature = "std")]
+mod nodes;
+#[cfg(feature = "std")]
+mod source;
+#[cfg(feature = "std")]
+mod sourcemap;
+#[cfg(feature = "std")]
+mod span;
+#[cfg(feature = "std")]
+mod table;
 
 /// A single point in a file.
-#[derive(Copy, Eq, Hash, Ord)]
+///
+/// Lines and columns start at 1, and go up to `Point::MAX`.  The
+/// values outside that range are reserved for two sentinels:
+/// `Point::UNKNOWN`, with both fields 0, and `Point::END_OF_FILE`, with
+/// both fields `u32::MAX`.  Sentinels order like any other point, so
+/// `UNKNOWN` comes before every point and `END_OF_FILE` after.
+#[derive(Copy, Eq, Hash)]
 pub struct Point {
     /// The line number, starting at 1
     pub line: u32,
@@ -15,6 +85,97 @@ pub struct Point {
     pub col: u32
 }
 
+impl Point {
+    /// The largest valid line or column number.  Larger numbers, as in
+    /// huge generated files, saturate to this.
+    pub const MAX: u32 = u32::MAX - 1;
+
+    /// A point whose line and column are not known.
+    pub const UNKNOWN: Point = Point { line: 0, col: 0 };
+
+    /// The point just past the end of a file, whatever its length.
+    pub const END_OF_FILE: Point = Point { line: u32::MAX, col: u32::MAX };
+
+    /// Create a point at `line` and `col`.  Returns `None` if either is
+    /// 0 or more than `Point::MAX`.
+    pub fn new(line: u32, col: u32) -> Option<Point> {
+        let point = Point { line, col };
+
+        if point.is_valid() {
+            Some(point)
+        } else {
+            None
+        }
+    }
+
+    /// Create a point at `line` and `col`, clamping each to between 1
+    /// and `Point::MAX`.
+    pub fn saturating(line: u64, col: u64) -> Point {
+        let clamp = |index: u64| index.clamp(1, Point::MAX as u64) as u32;
+
+        Point { line: clamp(line), col: clamp(col) }
+    }
+
+    /// Check whether the line and column are both in range.
+    pub fn is_valid(&self) -> bool {
+        valid_index(self.line) && valid_index(self.col)
+    }
+
+    /// Check whether this is `Point::UNKNOWN`.
+    pub fn is_unknown(&self) -> bool {
+        *self == Point::UNKNOWN
+    }
+
+    /// Check whether this is `Point::END_OF_FILE`.
+    pub fn is_end_of_file(&self) -> bool {
+        *self == Point::END_OF_FILE
+    }
+
+    /// Get the start of the next line, saturating at `Point::MAX`.
+    pub fn next_line(&self) -> Point {
+        Point::saturating(self.line as u64 + 1, 1)
+    }
+
+    /// Get the next column on the same line, saturating at
+    /// `Point::MAX`.
+    pub fn next_col(&self) -> Point {
+        Point::saturating(self.line as u64, self.col as u64 + 1)
+    }
+}
+
+/// Points can be made from a pair of a line and a column.  These
+/// should be in range, or a sentinel, which is checked in debug
+/// builds.
+impl From<(u32, u32)> for Point {
+    fn from((line, col): (u32, u32)) -> Point {
+        let point = Point { line, col };
+
+        debug_assert_valid(point);
+        point
+    }
+}
+
+/// Check, in debug builds, that `point` is valid or a sentinel.
+fn debug_assert_valid(point: Point) {
+    debug_assert!(point.is_valid() || point.is_unknown() ||
+                  point.is_end_of_file(),
+                  "line or column out of range in {:?}", point)
+}
+
+/// Check whether `index` is a valid line or column number.
+fn valid_index(index: u32) -> bool {
+    index != 0 && index <= Point::MAX
+}
+
+/// Write the line or column number `index`, or `?` if it is not valid.
+fn write_index(f: &mut Formatter, index: u32) -> Result {
+    if valid_index(index) {
+        write!(f, "{}", index)
+    } else {
+        write!(f, "?")
+    }
+}
+
 impl Clone for Point {
     fn clone(&self) -> Self {
         Point { line: self.line, col: self.col }
@@ -27,9 +188,15 @@ impl PartialEq for Point {
     }
 }
 
+impl Ord for Point {
+    fn cmp(&self, other: &Point) -> Ordering {
+        self.line.cmp(&other.line).then(self.col.cmp(&other.col))
+    }
+}
+
 impl PartialOrd for Point {
     fn partial_cmp(&self, other: &Point) -> Option<Ordering> {
-        Some(self.line.cmp(&other.line).then(self.col.cmp(&other.col)))
+        Some(self.cmp(other))
     }
 }
 
@@ -39,14 +206,24 @@ impl Debug for Point {
     }
 }
 
+/// Sentinels are displayed as `?` and `EOF`, and other out of range
+/// numbers as `?`.
 impl Display for Point {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{}.{}", self.line, self.col)
+        if self.is_unknown() {
+            write!(f, "?")
+        } else if self.is_end_of_file() {
+            write!(f, "EOF")
+        } else {
+            write_index(f, self.line)?;
+            write!(f, ".")?;
+            write_index(f, self.col)
+        }
     }
 }
 
 /// A location in a source file.
-#[derive(Debug, Eq, Hash, Ord)]
+#[derive(Clone, Debug, Eq, Hash)]
 pub enum Location {
    /// A span in a source file, starting at `start` and ending at `end`.
     Span {
@@ -62,12 +239,109 @@ pub enum Location {
     }
 }
 
+impl Location {
+    /// Create a location at `point`.
+    pub fn point<P: Into<Point>>(point: P) -> Location {
+        let point = point.into();
+
+        debug_assert_valid(point);
+        Location::Point { point }
+    }
+
+    /// Create a span from `start` to `end`, swapping them if `end` is
+    /// before `start`.  A span that starts where it ends is a point.
+    pub fn span<P: Into<Point>>(start: P, end: P) -> Location {
+        let (start, end) = (start.into(), end.into());
+
+        debug_assert_valid(start);
+        debug_assert_valid(end);
+
+        match start.cmp(&end) {
+            Ordering::Less => Location::Span { start, end },
+            Ordering::Equal => Location::Point { point: start },
+            Ordering::Greater => Location::Span { start: end, end: start }
+        }
+    }
+
+    /// Get the first point of the location.
+    pub fn start(&self) -> Point {
+        match self {
+            Location::Span { start, .. } => *start,
+            Location::Point { point } => *point
+        }
+    }
+
+    /// Get the end of the location: the point just past a span, or the
+    /// point itself.
+    pub fn end(&self) -> Point {
+        match self {
+            Location::Span { end, .. } => *end,
+            Location::Point { point } => *point
+        }
+    }
+
+    /// Get the smallest location covering both `self` and `other`, such
+    /// as the span of a parent node from those of its children.  As
+    /// spans do not include their end, a point at or past the end of
+    /// the other location ends the span at its next column.
+    pub fn merge(&self, other: &Location) -> Location {
+        let start = self.start().min(other.start());
+        let end = self.past_end().max(other.past_end());
+
+        if self == other {
+            self.clone()
+        } else {
+            Location::Span { start, end }
+        }
+    }
+
+    /// Get the first point past the location: the end of a span, or
+    /// the column after a point.
+    fn past_end(&self) -> Point {
+        match self {
+            Location::Span { end, .. } => *end,
+            Location::Point { point } => point.next_col()
+        }
+    }
+
+    /// Check whether `point` is in this location.  Spans do not include
+    /// their end point; a point location contains only itself.
+    pub fn contains(&self, point: Point) -> bool {
+        match self {
+            Location::Span { start, end } => *start <= point && point < *end,
+            Location::Point { point: p } => *p == point
+        }
+    }
+
+    /// Check whether `self` and `other` have any point in common.
+    pub fn overlaps(&self, other: &Location) -> bool {
+        match (self, other) {
+            (Location::Point { point }, loc) |
+            (loc, Location::Point { point }) => loc.contains(*point),
+            (Location::Span { start: start1, end: end1 },
+             Location::Span { start: start2, end: end2 }) =>
+                start1 < end2 && start2 < end1
+        }
+    }
+
+    /// Get the number of lines the location touches.
+    pub fn len_lines(&self) -> u32 {
+        self.end().line.saturating_sub(self.start().line) + 1
+    }
+}
+
+impl From<Point> for Location {
+    fn from(point: Point) -> Location {
+        Location::point(point)
+    }
+}
+
 impl<'a> PartialEq for Location {
     fn eq(&self, other: &Location) -> bool {
         match (self, other) {
             (Location::Span { start: start1, end: end1 },
              Location::Span { start: start2, end: end2 }) =>
-                start1 == end1 && start2 == end2,
+                start1 == start2 && end1 == end2,
 
             (Location::Point { point: point1 },
              Location::Point { point: point2 }) =>
@@ -78,30 +352,38 @@ impl<'a> PartialEq for Location {
     }
 }
 
-impl<'a> PartialOrd for Location {
-    fn partial_cmp(&self, other: &Location) -> Option<Ordering> {
+impl<'a> Ord for Location {
+    fn cmp(&self, other: &Location) -> Ordering {
         match (self, other) {
             (Location::Span { start: start1, end: end1 },
              Location::Span { start: start2, end: end2 }) =>
-                Some(start1.cmp(start2).then(end1.cmp(end2))),
+                start1.cmp(start2).then(end1.cmp(end2)),
 
             (Location::Span { .. }, Location::Point { .. }) =>
-                Some(Ordering::Less),
+                Ordering::Less,
 
             (Location::Point { .. }, Location::Span { .. }) =>
-                Some(Ordering::Greater),
+                Ordering::Greater,
 
             (Location::Point { point: point1 },
              Location::Point { point: point2 }) =>
-                Some(point1.cmp(point2))
+                point1.cmp(point2)
         }
     }
 }
 
+impl<'a> PartialOrd for Location {
+    fn partial_cmp(&self, other: &Location) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl<'a> Display for Location {
     fn fmt(&self, f: &mut Formatter) -> Result {
         match self {
-            Location::Span { start, end } if start.line == end.line =>
+            Location::Span { start, end } if start.line == end.line &&
+                                             start.is_valid() &&
+                                             end.is_valid() =>
                 write!(f, "{}.{}-{}", start.line, start.col, end.col),
             Location::Span { start, end } =>
                 write!(f, "{}-{}", start, end),
@@ -112,7 +394,7 @@ impl<'a> Display for Location {
 
 /// A position referring to a point in the file `filename`, at
 /// location `loc`.
-#[derive(Debug, Eq, Hash, Ord)]
+#[derive(Clone, Debug, Eq, Hash)]
 pub struct FilePosition<'a> {
     /// The file in which this occurs.
     pub filename: Filename<'a>,
@@ -120,6 +402,15 @@ pub struct FilePosition<'a> {
     pub loc: Location
 }
 
+/// A file position with its filename displayed according to a
+/// `FilenameDisplay`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayFilePosition<'a, 'b> {
+    pos: &'b FilePosition<'a>,
+    opts: &'b FilenameDisplay
+}
+
 pub trait FilePositionCtx<'a> {
     fn point(&mut self, line: u32, col: u32) -> &FilePosition<'a>;
 
@@ -127,15 +418,66 @@ pub trait FilePositionCtx<'a> {
             end: &FilePosition<'a>) -> &FilePosition<'a>;
 }
 
+impl<'a> FilePosition<'a> {
+    /// Create a position at `loc` in the file `filename`.
+    pub fn new<L: Into<Location>>(filename: Filename<'a>,
+                                  loc: L) -> FilePosition<'a> {
+        FilePosition { filename, loc: loc.into() }
+    }
+
+    /// Get the smallest position covering both `self` and `other`, if
+    /// they are in the same file.
+    pub fn merge(&self, other: &FilePosition<'a>) -> Option<FilePosition<'a>> {
+        if self.filename == other.filename {
+            Some(FilePosition { filename: self.filename,
+                                loc: self.loc.merge(&other.loc) })
+        } else {
+            None
+        }
+    }
+
+    /// Check whether `point` in the file `filename` is in this
+    /// position.
+    pub fn contains(&self, filename: Filename<'a>, point: Point) -> bool {
+        self.filename == filename && self.loc.contains(point)
+    }
+
+    /// Check whether `self` and `other` are in the same file and have
+    /// any point in common.
+    pub fn overlaps(&self, other: &FilePosition<'a>) -> bool {
+        self.filename == other.filename && self.loc.overlaps(&other.loc)
+    }
+
+    /// Get the number of lines the position touches.
+    pub fn len_lines(&self) -> u32 {
+        self.loc.len_lines()
+    }
+
+    /// Get an adapter that displays this position with its filename
+    /// displayed according to `opts`.
+    #[cfg(feature = "std")]
+    pub fn display_with<'b>(&'b self,
+                            opts: &'b FilenameDisplay)
+                            -> DisplayFilePosition<'a, 'b> {
+        DisplayFilePosition { pos: self, opts }
+    }
+}
+
 impl<'a> PartialEq for FilePosition<'a> {
     fn eq(&self, other: &FilePosition<'a>) -> bool {
         self.filename == other.filename && self.loc == other.loc
     }
 }
 
+impl<'a> Ord for FilePosition<'a> {
+    fn cmp(&self, other: &FilePosition<'a>) -> Ordering {
+        self.filename.cmp(&other.filename).then(self.loc.cmp(&other.loc))
+    }
+}
+
 impl<'a> PartialOrd for FilePosition<'a> {
     fn partial_cmp(&self, other: &FilePosition<'a>) -> Option<Ordering> {
-        Some(self.filename.cmp(&other.filename).then(self.loc.cmp(&other.loc)))
+        Some(self.cmp(other))
     }
 }
 
@@ -146,8 +488,16 @@ impl<'a> Display for FilePosition<'a> {
     }
 }
 
+#[cfg(feature = "std")]
+impl<'a, 'b> Display for DisplayFilePosition<'a, 'b> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{} {}", self.pos.filename.display_with(self.opts),
+               self.pos.loc)
+    }
+}
+
 /// A basic position type, useful for compiler frontends.
-#[derive(Debug, Eq, Hash, Ord)]
+#[derive(Debug, Eq, Hash)]
 pub enum BasicPosition<'a> {
     /// A position referring to a point in a file.
     Content {
@@ -195,42 +545,48 @@ impl<'a> PartialEq for BasicPosition<'a> {
     }
 }
 
-impl<'a> PartialOrd for BasicPosition<'a> {
-    fn partial_cmp(&self, other: &BasicPosition<'a>) -> Option<Ordering> {
+impl<'a> Ord for BasicPosition<'a> {
+    fn cmp(&self, other: &BasicPosition<'a>) -> Ordering {
         match (self, other) {
             (BasicPosition::Content { filepos: filepos1 },
              BasicPosition::Content { filepos: filepos2 }) =>
-                Some(filepos1.cmp(filepos2)),
+                filepos1.cmp(filepos2),
 
-            (BasicPosition::Content { .. }, _) => Some(Ordering::Less),
+            (BasicPosition::Content { .. }, _) => Ordering::Less,
 
-            (_, BasicPosition::Content { .. }) => Some(Ordering::Greater),
+            (_, BasicPosition::Content { .. }) => Ordering::Greater,
 
             (BasicPosition::File { filename: filename1 },
              BasicPosition::File { filename: filename2 }) =>
-                Some(filename1.cmp(filename2)),
+                filename1.cmp(filename2),
 
-            (BasicPosition::File { .. }, _) => Some(Ordering::Less),
+            (BasicPosition::File { .. }, _) => Ordering::Less,
 
-            (_, BasicPosition::File { .. }) => Some(Ordering::Greater),
+            (_, BasicPosition::File { .. }) => Ordering::Greater,
 
             (BasicPosition::CmdLine { idxs: idxs1 },
              BasicPosition::CmdLine { idxs: idxs2 }) =>
-                Some(idxs1.cmp(idxs2)),
+                idxs1.cmp(idxs2),
 
-            (BasicPosition::CmdLine { .. }, _) => Some(Ordering::Less),
+            (BasicPosition::CmdLine { .. }, _) => Ordering::Less,
 
-            (_, BasicPosition::CmdLine { .. }) => Some(Ordering::Greater),
+            (_, BasicPosition::CmdLine { .. }) => Ordering::Greater,
 
             (BasicPosition::Synthetic { desc: desc1 },
              BasicPosition::Synthetic { desc: desc2 }) =>
-                Some(desc1.cmp(desc2))
+                desc1.cmp(desc2)
         }
     }
 }
 
+impl<'a> PartialOrd for BasicPosition<'a> {
+    fn partial_cmp(&self, other: &BasicPosition<'a>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// Expanded position with DWARF information.
-#[derive(Debug, Eq, Hash, Ord)]
+#[derive(Debug, Eq, Hash)]
 pub enum DWARFPosition<'a, T, D> {
     /// A position within a definition.
     Def {
@@ -284,45 +640,102 @@ impl<'a, T: Eq, D: Eq> PartialEq for DWARFPosition<'a, T, D> {
     }
 }
 
-impl<'a, T: Ord, D: Ord> PartialOrd for DWARFPosition<'a, T, D> {
-    fn partial_cmp(&self, other: &DWARFPosition<'a, T, D>) -> Option<Ordering> {
+impl<'a, T: Ord, D: Ord> Ord for DWARFPosition<'a, T, D> {
+    fn cmp(&self, other: &DWARFPosition<'a, T, D>) -> Ordering {
         match (self, other) {
             (DWARFPosition::Def { id: id1, pos: pos1 },
              DWARFPosition::Def { id: id2, pos: pos2 }) =>
-                Some(id1.cmp(id2).then(pos1.cmp(pos2))),
+                id1.cmp(id2).then(pos1.cmp(pos2)),
 
-            (DWARFPosition::Def { .. }, _) => Some(Ordering::Less),
+            (DWARFPosition::Def { .. }, _) => Ordering::Less,
 
-            (_, DWARFPosition::Def { .. }) => Some(Ordering::Greater),
+            (_, DWARFPosition::Def { .. }) => Ordering::Greater,
 
             (DWARFPosition::TypeDef { id: id1, pos: pos1 },
              DWARFPosition::TypeDef { id: id2, pos: pos2 }) =>
-                Some(id1.cmp(id2).then(pos1.cmp(pos2))),
+                id1.cmp(id2).then(pos1.cmp(pos2)),
 
-            (DWARFPosition::TypeDef { .. }, _) => Some(Ordering::Less),
+            (DWARFPosition::TypeDef { .. }, _) => Ordering::Less,
 
-            (_, DWARFPosition::TypeDef { .. }) => Some(Ordering::Greater),
+            (_, DWARFPosition::TypeDef { .. }) => Ordering::Greater,
 
             (DWARFPosition::Block { ctx: ctx1, pos: pos1 },
              DWARFPosition::Block { ctx: ctx2, pos: pos2 }) =>
-                Some(ctx1.cmp(ctx2).then(pos1.cmp(pos2))),
+                ctx1.cmp(ctx2).then(pos1.cmp(pos2)),
 
-            (DWARFPosition::Block { .. }, _) => Some(Ordering::Less),
+            (DWARFPosition::Block { .. }, _) => Ordering::Less,
 
-            (_, DWARFPosition::Block { .. }) => Some(Ordering::Greater),
+            (_, DWARFPosition::Block { .. }) => Ordering::Greater,
 
             (DWARFPosition::Basic { pos: pos1 },
              DWARFPosition::Basic { pos: pos2 }) =>
-                Some(pos1.cmp(pos2))
+                pos1.cmp(pos2)
+        }
+    }
+}
+
+impl<'a, T: Ord, D: Ord> PartialOrd for DWARFPosition<'a, T, D> {
+    fn partial_cmp(&self, other: &DWARFPosition<'a, T, D>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a, T, D> DWARFPosition<'a, T, D> {
+    /// Get the position in the source file, if there is one.
+    pub fn file_position(&self) -> Option<&FilePosition<'a>> {
+        match self {
+            DWARFPosition::Def { pos, .. } |
+            DWARFPosition::TypeDef { pos, .. } |
+            DWARFPosition::Block { pos, .. } => Some(pos),
+            DWARFPosition::Basic {
+                pos: BasicPosition::Content { filepos }
+            } => Some(filepos),
+            DWARFPosition::Basic { .. } => None
+        }
+    }
+
+    /// Get the ID and position of the definition this is in, following
+    /// blocks out to their definition.
+    pub fn definition(&self) -> Option<(&D, &FilePosition<'a>)> {
+        match self {
+            DWARFPosition::Def { id, pos } => Some((id, pos)),
+            DWARFPosition::Block { ctx, .. } => ctx.definition(),
+            _ => None
         }
     }
 }
 
+/// A position that may be in code produced by expanding a macro.
+/// Expansions refer to the positions of the macro's use and definition,
+/// which may themselves be in expansions, so a chain of nested
+/// expansions can be followed back to the source.
+#[derive(Debug, Eq, Hash, PartialEq)]
+pub enum ExpandedPosition<'p, 'a: 'p> {
+    /// A position that is not in an expansion.
+    Basic {
+        /// The position information.
+        pos: BasicPosition<'a>
+    },
+    /// A position in code produced by expanding a macro.
+    Expansion {
+        /// The name of the macro.
+        name: &'a str,
+        /// The positions of the macro's use and of its definition, in
+        /// that order.
+        sites: [&'p ExpandedPosition<'p, 'a>; 2]
+    }
+}
+
 /// Get information about position representations.
 pub trait PositionInfo<'a> {
     /// Get the basic position
     fn location(&self) -> Option<(&'a Filename, Option<&'a Location>)>;
 
+    /// Get the file and location, like `location`, but by value, so
+    /// that they can be had from a position that is not borrowed for
+    /// `'a`.
+    fn file_location(&self) -> Option<(Filename<'a>, Option<Location>)>;
+
     /// Get the children of the current position.
     fn children(&self) -> &[&Self];
 
@@ -333,6 +746,14 @@ pub trait PositionInfo<'a> {
 
     /// Get a textual description of the message.
     fn description(&self) -> Option<&'a str>;
+
+    /// Get the name of the macro whose expansion this position is in,
+    /// if it is in one.  The children of such a position are then the
+    /// positions of the macro's use and of its definition, in that
+    /// order.
+    fn macro_name(&self) -> Option<&'a str> {
+        None
+    }
 }
 
 impl<'a> From<FilePosition<'a>> for BasicPosition<'a> {
@@ -354,6 +775,10 @@ impl<'a> PositionInfo<'a> for FilePosition<'a> {
         Some((&self.filename, Some(&self.loc)))
     }
 
+    fn file_location(&self) -> Option<(Filename<'a>, Option<Location>)> {
+        Some((self.filename, Some(self.loc.clone())))
+    }
+
     fn children(&self) -> &[&Self] { &[] }
     fn show_ctx(&self) -> bool { true }
     fn description(&self) -> Option<&'a str> { None }
@@ -369,6 +794,15 @@ impl<'a> PositionInfo<'a> for BasicPosition<'a> {
         }
     }
 
+    fn file_location(&self) -> Option<(Filename<'a>, Option<Location>)> {
+        match self {
+            BasicPosition::Content { filepos } => filepos.file_location(),
+            BasicPosition::File { filename } => Some((*filename, None)),
+            BasicPosition::CmdLine { .. } => None,
+            BasicPosition::Synthetic { .. } => None
+        }
+    }
+
     fn description(&self) -> Option<&'a str> {
         match self {
             BasicPosition::Synthetic { desc } => Some(desc),
@@ -385,3 +819,584 @@ impl<'a> PositionInfo<'a> for BasicPosition<'a> {
         }
     }
 }
+
+impl<'p, 'a: 'p> ExpandedPosition<'p, 'a> {
+    /// Create a position for code produced by expanding the macro
+    /// `name`, used at `use_site` and defined at `def_site`.
+    pub fn expansion(name: &'a str, use_site: &'p ExpandedPosition<'p, 'a>,
+                     def_site: &'p ExpandedPosition<'p, 'a>)
+                     -> ExpandedPosition<'p, 'a> {
+        ExpandedPosition::Expansion { name, sites: [use_site, def_site] }
+    }
+
+    /// Get the position of the use of the macro, if this is in an
+    /// expansion.
+    pub fn use_site(&self) -> Option<&'p ExpandedPosition<'p, 'a>> {
+        match self {
+            ExpandedPosition::Expansion { sites, .. } => Some(sites[0]),
+            ExpandedPosition::Basic { .. } => None
+        }
+    }
+
+    /// Get the position of the definition of the macro, if this is in
+    /// an expansion.
+    pub fn def_site(&self) -> Option<&'p ExpandedPosition<'p, 'a>> {
+        match self {
+            ExpandedPosition::Expansion { sites, .. } => Some(sites[1]),
+            ExpandedPosition::Basic { .. } => None
+        }
+    }
+
+    /// Follow use sites out of any expansions, to the position in the
+    /// source that caused this one.
+    pub fn source(&self) -> &ExpandedPosition<'p, 'a> {
+        match self {
+            ExpandedPosition::Expansion { sites, .. } => sites[0].source(),
+            ExpandedPosition::Basic { .. } => self
+        }
+    }
+}
+
+impl<'p, 'a> From<BasicPosition<'a>> for ExpandedPosition<'p, 'a> {
+    /// Create an ExpandedPosition from a BasicPosition
+    fn from(pos: BasicPosition<'a>) -> ExpandedPosition<'p, 'a> {
+        ExpandedPosition::Basic { pos }
+    }
+}
+
+/// An expansion is located at the use of the macro, which is what
+/// appears in the source.
+impl<'p, 'a: 'p> PositionInfo<'p> for ExpandedPosition<'p, 'a> {
+    fn location(&self) -> Option<(&'p Filename<'_>, Option<&'p Location>)> {
+        match self {
+            ExpandedPosition::Basic { pos } => pos.location(),
+            ExpandedPosition::Expansion { sites, .. } => sites[0].location()
+        }
+    }
+
+    fn file_location(&self) -> Option<(Filename<'p>, Option<Location>)> {
+        match self {
+            ExpandedPosition::Basic { pos } => pos.file_location(),
+            ExpandedPosition::Expansion { sites, .. } =>
+                sites[0].file_location()
+        }
+    }
+
+    fn children(&self) -> &[&Self] {
+        match self {
+            ExpandedPosition::Basic { .. } => &[],
+            ExpandedPosition::Expansion { sites, .. } => sites
+        }
+    }
+
+    fn show_ctx(&self) -> bool {
+        match self {
+            ExpandedPosition::Basic { pos } => pos.show_ctx(),
+            ExpandedPosition::Expansion { sites, .. } => sites[0].show_ctx()
+        }
+    }
+
+    fn description(&self) -> Option<&'p str> {
+        match self {
+            ExpandedPosition::Basic { pos } => pos.description(),
+            ExpandedPosition::Expansion { .. } => None
+        }
+    }
+
+    fn macro_name(&self) -> Option<&'p str> {
+        match self {
+            ExpandedPosition::Basic { .. } => None,
+            ExpandedPosition::Expansion { name, .. } => Some(name)
+        }
+    }
+}
+
+impl StableHash for Point {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.line.stable_hash(hasher);
+        self.col.stable_hash(hasher);
+    }
+}
+
+impl StableHash for Location {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Location::Span { start, end } => {
+                0u8.stable_hash(hasher);
+                start.stable_hash(hasher);
+                end.stable_hash(hasher);
+            },
+            Location::Point { point } => {
+                1u8.stable_hash(hasher);
+                point.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+impl<'a> StableHash for FilePosition<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.filename.stable_hash(hasher);
+        self.loc.stable_hash(hasher);
+    }
+}
+
+impl<'a> StableHash for BasicPosition<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            BasicPosition::Content { filepos } => {
+                0u8.stable_hash(hasher);
+                filepos.stable_hash(hasher);
+            },
+            BasicPosition::File { filename } => {
+                1u8.stable_hash(hasher);
+                filename.stable_hash(hasher);
+            },
+            BasicPosition::CmdLine { idxs } => {
+                2u8.stable_hash(hasher);
+                idxs.stable_hash(hasher);
+            },
+            BasicPosition::Synthetic { desc } => {
+                3u8.stable_hash(hasher);
+                desc.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+impl<'a, T: StableHash, D: StableHash> StableHash for DWARFPosition<'a, T, D> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            DWARFPosition::Def { id, pos } => {
+                0u8.stable_hash(hasher);
+                id.stable_hash(hasher);
+                pos.stable_hash(hasher);
+            },
+            DWARFPosition::TypeDef { id, pos } => {
+                1u8.stable_hash(hasher);
+                id.stable_hash(hasher);
+                pos.stable_hash(hasher);
+            },
+            DWARFPosition::Block { ctx, pos } => {
+                2u8.stable_hash(hasher);
+                ctx.stable_hash(hasher);
+                pos.stable_hash(hasher);
+            },
+            DWARFPosition::Basic { pos } => {
+                3u8.stable_hash(hasher);
+                pos.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encodable for Point {
+    fn encode(&self, enc: &mut Encoder) {
+        self.line.encode(enc);
+        self.col.encode(enc);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'d> Decodable<'d> for Point {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Point> {
+        Ok(Point { line: u32::decode(dec)?, col: u32::decode(dec)? })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encodable for Location {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            Location::Span { start, end } => {
+                enc.emit_u8(0);
+                start.encode(enc);
+                end.encode(enc);
+            },
+            Location::Point { point } => {
+                enc.emit_u8(1);
+                point.encode(enc);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'d> Decodable<'d> for Location {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Location> {
+        match dec.read_u8()? {
+            0 => Ok(Location::Span { start: Point::decode(dec)?,
+                                     end: Point::decode(dec)? }),
+            1 => Ok(Location::Point { point: Point::decode(dec)? }),
+            tag => Err(DecodeError::BadTag { what: "Location",
+                                             tag: tag as u64 })
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Encodable for FilePosition<'a> {
+    fn encode(&self, enc: &mut Encoder) {
+        self.filename.encode(enc);
+        self.loc.encode(enc);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Decodable<'a> for FilePosition<'a> {
+    fn decode(dec: &mut Decoder<'a, '_>) -> DecodeResult<FilePosition<'a>> {
+        Ok(FilePosition { filename: Filename::decode(dec)?,
+                          loc: Location::decode(dec)? })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Encodable for BasicPosition<'a> {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            BasicPosition::Content { filepos } => {
+                enc.emit_u8(0);
+                filepos.encode(enc);
+            },
+            BasicPosition::File { filename } => {
+                enc.emit_u8(1);
+                filename.encode(enc);
+            },
+            BasicPosition::CmdLine { idxs } => {
+                enc.emit_u8(2);
+                idxs.encode(enc);
+            },
+            BasicPosition::Synthetic { desc } => {
+                enc.emit_u8(3);
+                enc.emit_str(desc);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Decodable<'a> for BasicPosition<'a> {
+    fn decode(dec: &mut Decoder<'a, '_>) -> DecodeResult<BasicPosition<'a>> {
+        match dec.read_u8()? {
+            0 => Ok(BasicPosition::Content {
+                filepos: FilePosition::decode(dec)?
+            }),
+            1 => Ok(BasicPosition::File { filename: Filename::decode(dec)? }),
+            2 => Ok(BasicPosition::CmdLine { idxs: Vec::decode(dec)? }),
+            3 => Ok(BasicPosition::Synthetic { desc: dec.read_static()? }),
+            tag => Err(DecodeError::BadTag { what: "BasicPosition",
+                                             tag: tag as u64 })
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T: Encodable, D: Encodable> Encodable for DWARFPosition<'a, T, D> {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            DWARFPosition::Def { id, pos } => {
+                enc.emit_u8(0);
+                id.encode(enc);
+                pos.encode(enc);
+            },
+            DWARFPosition::TypeDef { id, pos } => {
+                enc.emit_u8(1);
+                id.encode(enc);
+                pos.encode(enc);
+            },
+            DWARFPosition::Block { ctx, pos } => {
+                enc.emit_u8(2);
+                ctx.encode(enc);
+                pos.encode(enc);
+            },
+            DWARFPosition::Basic { pos } => {
+                enc.emit_u8(3);
+                pos.encode(enc);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T: Decodable<'a>, D: Decodable<'a>> Decodable<'a>
+    for DWARFPosition<'a, T, D> {
+    fn decode(dec: &mut Decoder<'a, '_>)
+              -> DecodeResult<DWARFPosition<'a, T, D>> {
+        match dec.read_u8()? {
+            0 => Ok(DWARFPosition::Def { id: D::decode(dec)?,
+                                         pos: FilePosition::decode(dec)? }),
+            1 => Ok(DWARFPosition::TypeDef { id: T::decode(dec)?,
+                                             pos: FilePosition::decode(dec)? }),
+            2 => Ok(DWARFPosition::Block { ctx: Box::decode(dec)?,
+                                           pos: FilePosition::decode(dec)? }),
+            3 => Ok(DWARFPosition::Basic { pos: BasicPosition::decode(dec)? }),
+            tag => Err(DecodeError::BadTag { what: "DWARFPosition",
+                                             tag: tag as u64 })
+        }
+    }
+}
+
+#[cfg(test)]
+fn span(start: (u32, u32), end: (u32, u32)) -> Location {
+    Location::Span { start: Point { line: start.0, col: start.1 },
+                     end: Point { line: end.0, col: end.1 } }
+}
+
+#[test]
+fn test_point_sentinels() {
+    let last = Point::new(Point::MAX, 1).unwrap();
+
+    assert_eq!(Point::new(0, 1), None);
+    assert_eq!(Point::new(1, u32::MAX), None);
+    assert_eq!(Point::saturating(0, 1 << 40), Point { line: 1,
+                                                      col: Point::MAX });
+    assert_eq!(last.next_line(), last);
+    assert_eq!(last.next_col(), Point { line: Point::MAX, col: 2 });
+    assert!(Point::UNKNOWN < last && last < Point::END_OF_FILE);
+    assert!(!Point::UNKNOWN.is_valid() && !Point::END_OF_FILE.is_valid());
+    assert_eq!(Point::UNKNOWN.to_string(), "?");
+    assert_eq!(Point::END_OF_FILE.to_string(), "EOF");
+    assert_eq!(Point { line: 3, col: 0 }.to_string(), "3.?");
+    assert_eq!(span((3, 1), (3, 4)).to_string(), "3.1-4");
+    assert_eq!(Location::Span { start: Point { line: 3, col: 2 },
+                                end: Point::END_OF_FILE }.to_string(),
+               "3.2-EOF");
+}
+
+#[test]
+fn test_location_constructors() {
+    use salt::common::str::intern::StrIntern;
+    use salt::common::filename::FilenameCtx;
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("a.salt");
+
+    assert_eq!(Point::from((2, 3)), Point { line: 2, col: 3 });
+    assert_eq!(Location::span((1, 4), (1, 1)), span((1, 1), (1, 4)));
+    assert_eq!(Location::span((2, 1), (2, 1)), Location::point((2, 1)));
+    assert_eq!(Location::span(Point::UNKNOWN, Point::END_OF_FILE).start(),
+               Point::UNKNOWN);
+    assert_eq!(FilePosition::new(filename, Point::from((1, 2))),
+               FilePosition { filename,
+                              loc: Location::Point { point: (1, 2).into() } });
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "line or column out of range")]
+fn test_location_invalid_point() {
+    Location::point((0, 1));
+}
+
+#[test]
+fn test_location_merge() {
+    let a = span((1, 5), (1, 9));
+    let b = span((2, 1), (3, 4));
+    let p = Location::Point { point: Point { line: 1, col: 2 } };
+    let merged = a.merge(&b).merge(&p);
+
+    assert_eq!(merged.start(), Point { line: 1, col: 2 });
+    assert_eq!(merged.end(), Point { line: 3, col: 4 });
+    assert_eq!(merged.len_lines(), 3);
+    assert_eq!(p.merge(&p).len_lines(), 1);
+
+    // Spans do not include their ends, so merged points end them.
+    let q = Location::point((1, 5));
+    let pq = p.merge(&q);
+
+    assert_eq!(pq, span((1, 2), (1, 6)));
+    assert!(pq.contains(p.start()) && pq.contains(q.start()));
+    assert_eq!(span((1, 1), (1, 4)).merge(&Location::point((1, 4))),
+               span((1, 1), (1, 5)));
+    assert_eq!(a.merge(&Location::point((1, 6))), a);
+
+    match p.merge(&p) {
+        Location::Point { point } => assert_eq!(point, p.start()),
+        loc => panic!("expected a point, got {}", loc)
+    }
+}
+
+#[test]
+fn test_location_contains() {
+    let a = span((1, 5), (2, 3));
+    let p = Location::Point { point: Point { line: 2, col: 1 } };
+
+    assert!(a.contains(Point { line: 1, col: 5 }));
+    assert!(a.contains(Point { line: 1, col: 80 }));
+    assert!(!a.contains(Point { line: 2, col: 3 }));
+    assert!(!a.contains(Point { line: 1, col: 4 }));
+    assert!(a.overlaps(&span((2, 2), (4, 1))));
+    assert!(!a.overlaps(&span((2, 3), (4, 1))));
+    assert!(a.overlaps(&p) && p.overlaps(&a));
+    assert!(!p.overlaps(&span((1, 1), (1, 5))));
+}
+
+#[test]
+fn test_file_position_merge() {
+    use salt::common::str::intern::StrIntern;
+    use salt::common::filename::FilenameCtx;
+
+    let mut names = StrIntern::new();
+    let a = FilePosition { filename: names.filename("a.salt"),
+                           loc: span((1, 1), (1, 4)) };
+    let b = FilePosition { filename: names.filename("a.salt"),
+                           loc: span((1, 3), (2, 1)) };
+    let c = FilePosition { filename: names.filename("c.salt"),
+                           loc: span((1, 1), (1, 4)) };
+    let merged = a.merge(&b).unwrap();
+
+    assert_eq!(merged.loc.end(), Point { line: 2, col: 1 });
+    assert!(a.merge(&c).is_none());
+    assert!(a.overlaps(&b));
+    assert!(!a.overlaps(&c));
+    assert!(a.contains(b.filename, Point { line: 1, col: 3 }));
+    assert!(!c.contains(b.filename, Point { line: 1, col: 3 }));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_file_position_display_with() {
+    use salt::common::str::intern::StrIntern;
+    use salt::common::filename::FilenameCtx;
+
+    let mut names = StrIntern::new();
+    let pos = FilePosition { filename: names.filename("/src/lib/a.salt"),
+                             loc: span((1, 1), (1, 4)) };
+    let opts = FilenameDisplay::new().with_cwd("/src");
+
+    assert_eq!(pos.to_string(), "/src/lib/a.salt 1.1-4");
+    assert_eq!(pos.display_with(&opts).to_string(), "lib/a.salt 1.1-4");
+}
+
+#[cfg(test)]
+use salt::common::arbitrary::check_order;
+#[cfg(test)]
+use salt::common::rng::DeterministicRng;
+
+#[cfg(test)]
+fn random_point(rng: &mut DeterministicRng) -> Point {
+    Point { line: rng.below(3) as u32 + 1, col: rng.below(3) as u32 + 1 }
+}
+
+#[cfg(test)]
+fn random_location(rng: &mut DeterministicRng) -> Location {
+    if rng.chance(1, 2) {
+        Location::Point { point: random_point(rng) }
+    } else {
+        Location::Span { start: random_point(rng), end: random_point(rng) }
+    }
+}
+
+#[cfg(test)]
+fn random_file_position<'a>(rng: &mut DeterministicRng,
+                            names: &[Filename<'a>]) -> FilePosition<'a> {
+    FilePosition { filename: names[rng.below(names.len() as u64) as usize],
+                   loc: random_location(rng) }
+}
+
+#[cfg(test)]
+fn random_basic_position<'a>(rng: &mut DeterministicRng,
+                             names: &[Filename<'a>]) -> BasicPosition<'a> {
+    match rng.below(4) {
+        0 => BasicPosition::Content {
+            filepos: random_file_position(rng, names)
+        },
+        1 => BasicPosition::File {
+            filename: names[rng.below(names.len() as u64) as usize]
+        },
+        2 => BasicPosition::CmdLine {
+            idxs: (0..rng.below(3)).map(|_| rng.below(2) as usize).collect()
+        },
+        _ => BasicPosition::Synthetic {
+            desc: ["<builtin>", "<prelude>"][rng.below(2) as usize]
+        }
+    }
+}
+
+#[cfg(test)]
+fn random_dwarf_position<'a>(rng: &mut DeterministicRng, names: &[Filename<'a>],
+                             depth: u32) -> DWARFPosition<'a, u8, u8> {
+    match rng.below(if depth == 0 { 3 } else { 4 }) {
+        0 => DWARFPosition::Def { id: rng.below(2) as u8,
+                                  pos: random_file_position(rng, names) },
+        1 => DWARFPosition::TypeDef { id: rng.below(2) as u8,
+                                      pos: random_file_position(rng, names) },
+        2 => DWARFPosition::Basic { pos: random_basic_position(rng, names) },
+        _ => DWARFPosition::Block {
+            ctx: Box::new(random_dwarf_position(rng, names, depth - 1)),
+            pos: random_file_position(rng, names)
+        }
+    }
+}
+
+#[test]
+fn test_order_points_and_locations() {
+    let mut rng = DeterministicRng::new(1);
+    let points: Vec<Point> = (0..12).map(|_| random_point(&mut rng)).collect();
+    let locs: Vec<Location> =
+        (0..40).map(|_| random_location(&mut rng)).collect();
+
+    check_order(&points);
+    check_order(&locs);
+
+    let a = Location::Span { start: Point { line: 1, col: 1 },
+                             end: Point { line: 1, col: 4 } };
+    let b = Location::Span { start: Point { line: 1, col: 1 },
+                             end: Point { line: 1, col: 5 } };
+
+    assert_eq!(a, a.clone());
+    assert_ne!(a, b);
+    assert!(a < b);
+}
+
+#[test]
+fn test_order_positions() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let text = "b.salt.x";
+    let mut intern = StrIntern::new();
+    // A prefix of another name, at the same address.
+    let names = [intern.filename("a.salt"), intern.filename(text),
+                 intern.filename(&text[..6])];
+    let mut rng = DeterministicRng::new(2);
+    let filepos: Vec<FilePosition> =
+        (0..40).map(|_| random_file_position(&mut rng, &names)).collect();
+    let basic: Vec<BasicPosition> =
+        (0..40).map(|_| random_basic_position(&mut rng, &names)).collect();
+    let dwarf: Vec<DWARFPosition<u8, u8>> =
+        (0..40).map(|_| random_dwarf_position(&mut rng, &names, 2)).collect();
+
+    check_order(&names);
+    check_order(&filepos);
+    check_order(&basic);
+    check_order(&dwarf);
+}
+
+#[test]
+fn test_expanded_position() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let filename = names.filename("a.salt");
+    let pos = |loc| ExpandedPosition::from(BasicPosition::from(
+        FilePosition { filename, loc }
+    ));
+    let use_site = pos(span((5, 3), (5, 12)));
+    let def_site = pos(span((1, 1), (1, 20)));
+    let inner = ExpandedPosition::expansion("twice", &use_site, &def_site);
+    let outer = ExpandedPosition::expansion("thrice", &inner, &def_site);
+
+    assert_eq!(outer.use_site(), Some(&inner));
+    assert_eq!(outer.def_site(), Some(&def_site));
+    assert_eq!(outer.source(), &use_site);
+    assert_eq!(outer.macro_name(), Some("thrice"));
+    assert_eq!(outer.location(), use_site.location());
+    assert_eq!(outer.file_location(), use_site.file_location());
+    assert_eq!(outer.children(), [&inner, &def_site]);
+    assert_eq!(use_site.use_site(), None);
+    assert_eq!(use_site.macro_name(), None);
+    assert!(use_site.children().is_empty());
+}
@@ -0,0 +1,322 @@
+use salt::common::collections::FilenameMap;
+use salt::common::filename::Filename;
+use salt::common::heapsize::HeapSize;
+use salt::common::heapsize::hash_table_size;
+use salt::common::position::FilePosition;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+/// Ids of syntax tree nodes, or anything else numbered densely from 0,
+/// for keying side tables.
+pub trait NodeId: Copy {
+    /// Get the index of this id.
+    fn index(self) -> usize;
+
+    /// Get the id with index `idx`.
+    fn from_index(idx: usize) -> Self;
+}
+
+impl NodeId for usize {
+    fn index(self) -> usize {
+        self
+    }
+
+    fn from_index(idx: usize) -> usize {
+        idx
+    }
+}
+
+impl NodeId for u32 {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn from_index(idx: usize) -> u32 {
+        idx as u32
+    }
+}
+
+/// The number of entries sharing a base line.
+const BLOCK: usize = 64;
+
+/// Entry kinds, in the low two bits.
+const KIND_ABSENT: u64 = 0;
+const KIND_POINT: u64 = 1;
+const KIND_SPAN: u64 = 2;
+/// The entry is an index into the overflow table.
+const KIND_OVERFLOW: u64 = 3;
+
+/// Field widths of a packed entry.  After the kind come the file, the
+/// start line relative to the block's base, the start column, the end
+/// line relative to the start, and the end column.
+const FILE_BITS: u32 = 16;
+const LINE_BITS: u32 = 14;
+const COL_BITS: u32 = 12;
+const END_LINE_BITS: u32 = 8;
+
+/// A side table of positions of syntax tree nodes, keyed by node id,
+/// so that nodes need not hold their positions themselves.
+///
+/// Entries are packed into 8 bytes each, with the line stored as a
+/// delta from a base line shared by each block of 64 consecutive ids.
+/// Nodes numbered in source order have nearby positions, so nearly
+/// all entries fit; those that do not are kept whole in an overflow
+/// table.  Lookup is O(1) either way.
+///
+/// Unlike `PositionTable`, which holds `FilePosition`s and hands out
+/// references to them, positions are rebuilt on lookup.
+#[derive(Clone, Debug)]
+pub struct NodePositions<'a, I> {
+    /// The files of the positions; entries hold indexes into this.
+    files: Vec<Filename<'a>>,
+    /// The index of each file in `files`.
+    file_idxs: FilenameMap<'a, u32>,
+    /// The base line of each block.
+    bases: Vec<u32>,
+    /// The packed entries, by node index.
+    entries: Vec<u64>,
+    /// Positions that do not fit in an entry.
+    overflow: Vec<FilePosition<'a>>,
+    /// The number of present entries.
+    len: usize,
+    ids: PhantomData<I>
+}
+
+/// Get `value`, if it fits in `bits` bits.
+fn field(value: u64, bits: u32) -> Option<u64> {
+    if value < 1 << bits {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Get the `bits` bits of `entry` starting at `shift`.
+fn unpack(entry: u64, shift: u32, bits: u32) -> u64 {
+    entry >> shift & ((1 << bits) - 1)
+}
+
+impl<'a, I: NodeId> NodePositions<'a, I> {
+    /// Create an empty table.
+    pub fn new() -> NodePositions<'a, I> {
+        NodePositions { files: Vec::new(), file_idxs: FilenameMap::default(),
+                        bases: Vec::new(), entries: Vec::new(),
+                        overflow: Vec::new(), len: 0, ids: PhantomData }
+    }
+
+    /// Get the index of `filename` in `files`, adding it if needed.
+    fn file_index(&mut self, filename: Filename<'a>) -> u32 {
+        let files = &mut self.files;
+
+        *self.file_idxs.entry(filename).or_insert_with(|| {
+            files.push(filename);
+
+            files.len() as u32 - 1
+        })
+    }
+
+    /// Pack `pos` into an entry for a block with base line `base`, if
+    /// it fits.
+    fn pack(&mut self, base: u32, pos: &FilePosition<'a>) -> Option<u64> {
+        let (start, end, kind) = match pos.loc {
+            Location::Point { point } => (point, point, KIND_POINT),
+            Location::Span { start, end } => (start, end, KIND_SPAN)
+        };
+        let line = field(start.line.checked_sub(base)? as u64, LINE_BITS)?;
+        let col = field(start.col as u64, COL_BITS)?;
+        let end_line = field(end.line.checked_sub(start.line)? as u64,
+                             END_LINE_BITS)?;
+        let end_col = field(end.col as u64, COL_BITS)?;
+        let file = field(self.file_index(pos.filename) as u64, FILE_BITS)?;
+
+        Some(kind | file << 2 | line << (2 + FILE_BITS) |
+             col << (2 + FILE_BITS + LINE_BITS) |
+             end_line << (2 + FILE_BITS + LINE_BITS + COL_BITS) |
+             end_col << (2 + FILE_BITS + LINE_BITS + COL_BITS +
+                         END_LINE_BITS))
+    }
+
+    /// Set the position of `id` to `pos`, replacing any it had.
+    pub fn insert(&mut self, id: I, pos: FilePosition<'a>) {
+        let idx = id.index();
+        let block = idx / BLOCK;
+
+        if idx >= self.entries.len() {
+            self.entries.resize(idx + 1, KIND_ABSENT);
+        }
+
+        if block >= self.bases.len() {
+            // The first position in a block picks its base line, a
+            // little before it so that later nodes can go back a bit.
+            let base = pos.loc.start().line.saturating_sub(BLOCK as u32);
+
+            self.bases.resize(block + 1, 0);
+            self.bases[block] = base;
+        }
+
+        if self.entries[idx] == KIND_ABSENT {
+            self.len += 1;
+        }
+
+        self.entries[idx] = match self.pack(self.bases[block], &pos) {
+            Some(entry) => entry,
+            None => {
+                self.overflow.push(pos);
+
+                KIND_OVERFLOW | (self.overflow.len() as u64 - 1) << 2
+            }
+        }
+    }
+
+    /// Get the position of `id`, if it has one.
+    pub fn get(&self, id: I) -> Option<FilePosition<'a>> {
+        let idx = id.index();
+        let entry = *self.entries.get(idx)?;
+        let kind = entry & 3;
+
+        if kind == KIND_ABSENT {
+            return None;
+        } else if kind == KIND_OVERFLOW {
+            return Some(self.overflow[(entry >> 2) as usize].clone());
+        }
+
+        let shift = 2 + FILE_BITS;
+        let line = self.bases[idx / BLOCK] +
+                   unpack(entry, shift, LINE_BITS) as u32;
+        let start = Point {
+            line, col: unpack(entry, shift + LINE_BITS, COL_BITS) as u32
+        };
+        let end = Point {
+            line: line + unpack(entry, shift + LINE_BITS + COL_BITS,
+                                END_LINE_BITS) as u32,
+            col: unpack(entry, shift + LINE_BITS + COL_BITS + END_LINE_BITS,
+                        COL_BITS) as u32
+        };
+        let filename = self.files[unpack(entry, 2, FILE_BITS) as usize];
+        let loc = if kind == KIND_POINT {
+            Location::Point { point: start }
+        } else {
+            Location::Span { start, end }
+        };
+
+        Some(FilePosition { filename, loc })
+    }
+
+    /// Check whether `id` has a position.
+    pub fn contains(&self, id: I) -> bool {
+        self.entries.get(id.index())
+            .is_some_and(|entry| *entry != KIND_ABSENT)
+    }
+
+    /// Get the number of ids with positions.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether no id has a position.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the ids with positions, and their positions, in order of
+    /// id.
+    pub fn iter(&self) -> impl Iterator<Item = (I, FilePosition<'a>)> + '_ {
+        (0..self.entries.len()).filter_map(move |idx| {
+            let id = I::from_index(idx);
+
+            self.get(id).map(|pos| (id, pos))
+        })
+    }
+}
+
+impl<'a, I: NodeId> Default for NodePositions<'a, I> {
+    fn default() -> NodePositions<'a, I> {
+        NodePositions::new()
+    }
+}
+
+impl<'a, I> HeapSize for NodePositions<'a, I> {
+    fn heap_size(&self) -> usize {
+        self.files.capacity() * size_of::<Filename>() +
+        hash_table_size::<(Filename, u32)>(self.file_idxs.capacity()) +
+        self.bases.capacity() * size_of::<u32>() +
+        self.entries.capacity() * size_of::<u64>() +
+        self.overflow.capacity() * size_of::<FilePosition>()
+    }
+}
+
+#[test]
+fn test_node_positions() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let span = |filename, line, col, end_line, end_col| FilePosition {
+        filename, loc: Location::Span {
+            start: Point { line, col }, end: Point { line: end_line,
+                                                     col: end_col }
+        }
+    };
+    let point = FilePosition {
+        filename: b, loc: Location::Point { point: Point { line: 70, col: 3 } }
+    };
+    let mut table: NodePositions<u32> = NodePositions::new();
+    let expected = [(0, span(a, 100, 5, 100, 9)),
+                    (1, span(a, 98, 1, 120, 2)),
+                    (2, point.clone()),
+                    // Far before the base, past the column limit, and
+                    // over too many lines: all overflow.
+                    (3, span(a, 1, 1, 1, 2)),
+                    (4, span(a, 101, 5000, 101, 5001)),
+                    (5, span(a, 101, 1, 900, 1)),
+                    (200, span(b, 70000, 1, 70000, 4))];
+
+    for (id, pos) in expected.iter() {
+        table.insert(*id, pos.clone());
+    }
+
+    assert_eq!(table.len(), expected.len());
+    assert_eq!(table.overflow.len(), 3);
+    assert_eq!(table.get(6), None);
+    assert!(!table.contains(1000) && table.contains(200));
+
+    for (id, pos) in expected.iter() {
+        assert_eq!(table.get(*id).as_ref(), Some(pos));
+    }
+
+    assert_eq!(table.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+               [0, 1, 2, 3, 4, 5, 200]);
+
+    table.insert(2, span(a, 99, 1, 99, 2));
+
+    assert_eq!(table.len(), expected.len());
+    assert_eq!(table.get(2), Some(span(a, 99, 1, 99, 2)));
+}
+
+#[test]
+fn test_node_positions_density() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let file = names.filename("big.salt");
+    let mut table = NodePositions::new();
+
+    for i in 0..10000usize {
+        let line = 1 + i as u32 / 4;
+        let col = 1 + (i as u32 % 4) * 10;
+
+        table.insert(i, FilePosition {
+            filename: file,
+            loc: Location::Span { start: Point { line, col },
+                                  end: Point { line, col: col + 5 } }
+        });
+    }
+
+    assert!(table.overflow.is_empty());
+    assert!(table.heap_size() * 4 < 10000 * size_of::<FilePosition>());
+}
@@ -0,0 +1,1019 @@
+use salt::common::filename::Filename;
+use salt::common::hash::Fingerprint;
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+use salt::common::heapsize::HeapSize;
+use salt::common::heapsize::hash_table_size;
+use salt::common::ordered::JobKey;
+use salt::common::ordered::OrderedBuffer;
+use salt::common::position::FilePosition;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use salt::common::progress::PhaseProgress;
+use salt::common::progress::ProgressSink;
+use salt::common::str::width::ColumnMode;
+use salt::common::str::width::TabStops;
+use std::collections::HashMap;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Result;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+/// An index of where each line of some text starts, which can be
+/// updated for edits to the text without rescanning it.  Lines are
+/// separated by `\n`; a `\r` before it is part of the line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LineIndex {
+    /// The byte offset at which each line starts.  The first is
+    /// always 0.
+    starts: Vec<usize>
+}
+
+/// The contents of a source file, with an index of where each line
+/// starts, for converting between byte offsets and `Point`s.  As
+/// elsewhere, lines and columns start at 1.  Columns count characters
+/// unless a different `ColumnMode` is set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceFile<'a> {
+    /// The name of the file.
+    name: Filename<'a>,
+    /// The contents of the file.
+    contents: String,
+    /// Where each line starts.
+    lines: LineIndex,
+    /// How columns are counted.
+    mode: ColumnMode,
+    /// Where tabs advance to, when counting display width.
+    tabs: TabStops
+}
+
+/// A source of file contents, used to show source context in
+/// diagnostics.
+pub trait SourceProvider<'a> {
+    /// Get the text of line `line` of the file `filename`, without its
+    /// line terminator, if it is available.
+    fn source_line(&self, filename: Filename<'a>, line: u32) -> Option<&str>;
+
+    /// Get where tabs in source lines advance to, for lining up
+    /// diagnostics with them.
+    fn tab_stops(&self) -> TabStops {
+        TabStops::default()
+    }
+}
+
+/// Owner of the contents of loaded source files.
+#[derive(Clone, Debug, Default)]
+pub struct SourceManager<'a> {
+    files: HashMap<Filename<'a>, SourceFile<'a>>,
+    mode: ColumnMode,
+    tabs: TabStops
+}
+
+/// A way to read source files, so that a `SourceCache` can read them
+/// from disk, or from a virtual filesystem.  Each `FileSystem` is one.
+pub trait FileLoader {
+    /// Read the contents of the file at `path`.
+    fn load(&self, path: &Path) -> Result<String>;
+}
+
+/// A `SourceProvider` for files that are read on demand, such as those
+/// whose contents a renderer needs to show snippets.  Files are read
+/// through a `FileLoader`, and kept until the total size of those kept
+/// exceeds a limit, when the least recently loaded are evicted.
+///
+/// As `SourceProvider` only borrows the cache, `source_line` only finds
+/// files already loaded; load the files a diagnostic refers to with
+/// `load` before rendering it.
+#[derive(Clone, Debug)]
+pub struct SourceCache<'a, L> {
+    loader: L,
+    files: HashMap<Filename<'a>, CachedFile<'a>>,
+    /// The most bytes of contents to keep.
+    limit: usize,
+    /// The bytes of contents kept.
+    size: usize,
+    /// The number of loads so far, for ordering uses of files.
+    clock: u64,
+    mode: ColumnMode,
+    tabs: TabStops
+}
+
+/// A file kept in a `SourceCache`.
+#[derive(Clone, Debug)]
+struct CachedFile<'a> {
+    file: SourceFile<'a>,
+    /// The value of the clock when the file was last loaded.
+    last_used: u64
+}
+
+/// The default limit on the size of files kept by a `SourceCache`.
+const DEFAULT_CACHE_LIMIT: usize = 64 << 20;
+
+/// Read the file `name` through `loader` by its local path.  Files with
+/// no path cannot be read, and give an error of kind `NotFound`.
+fn read_file<L: FileLoader>(loader: &L, name: Filename) -> Result<String> {
+    if name.is_virtual() {
+        return Err(Error::new(ErrorKind::NotFound,
+                              format!("{} has no path", name)));
+    }
+
+    loader.load(name.local_path())
+}
+
+impl LineIndex {
+    /// Create an index of the lines in `text`.
+    pub fn new(text: &str) -> LineIndex {
+        let starts = ::std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        LineIndex { starts }
+    }
+
+    /// Get the number of lines.  A trailing newline starts an empty
+    /// last line.
+    pub fn line_count(&self) -> u32 {
+        self.starts.len() as u32
+    }
+
+    /// Get the byte offset at which each line starts.
+    pub fn starts(&self) -> &[usize] {
+        &self.starts
+    }
+
+    /// Get the byte offset at which line `line` starts.
+    pub fn line_start(&self, line: u32) -> Option<usize> {
+        self.starts.get((line as usize).checked_sub(1)?).cloned()
+    }
+
+    /// Get the line that byte offset `offset` is on.
+    pub fn line_of(&self, offset: usize) -> u32 {
+        self.starts.partition_point(|&start| start <= offset) as u32
+    }
+
+    /// Update the index for replacing the bytes `range` of the text
+    /// with `text`.  This takes time in the number of lines after the
+    /// edit, but does not look at the rest of the text.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) {
+        assert!(range.start <= range.end, "edit range ends before it starts");
+
+        // Lines starting just after a newline in the range go.
+        let first = self.starts.partition_point(|&start| start <= range.start);
+        let last = self.starts.partition_point(|&start| start <= range.end);
+        let added = text.match_indices('\n')
+            .map(|(i, _)| range.start + i + 1);
+        let delta = text.len() as isize - range.len() as isize;
+
+        self.starts.splice(first..last, added);
+
+        let after = first + text.matches('\n').count();
+
+        for start in &mut self.starts[after..] {
+            *start = (*start as isize + delta) as usize;
+        }
+    }
+}
+
+impl<'a> SourceFile<'a> {
+    /// Create a `SourceFile` named `name` with contents `contents`.
+    pub fn new(name: Filename<'a>, contents: String) -> SourceFile<'a> {
+        let lines = LineIndex::new(&contents);
+
+        SourceFile { name, contents, lines, mode: ColumnMode::default(),
+                     tabs: TabStops::default() }
+    }
+
+    /// Get how columns are counted.
+    pub fn column_mode(&self) -> ColumnMode {
+        self.mode
+    }
+
+    /// Set how columns are counted.
+    pub fn set_column_mode(&mut self, mode: ColumnMode) {
+        self.mode = mode
+    }
+
+    /// Get where tabs advance to, when counting display width.
+    pub fn tab_stops(&self) -> TabStops {
+        self.tabs
+    }
+
+    /// Set where tabs advance to, when counting display width.
+    pub fn set_tab_stops(&mut self, tabs: TabStops) {
+        self.tabs = tabs
+    }
+
+    /// Get the visual column of `point`, counting from 1, with tabs
+    /// expanded to this file's tab stops.  Returns `None` if the point
+    /// is not in the file.
+    pub fn visual_column(&self, point: Point) -> Option<u32> {
+        let offset = self.point_to_offset(point)?;
+        let (start, _) = self.line_range(point.line)?;
+
+        Some(self.tabs.visual_width(&self.contents[start..offset]) as u32 + 1)
+    }
+
+    /// Get the point on line `line` shown at visual column `col`,
+    /// counting from 1, with tabs expanded to this file's tab stops.
+    /// Columns in the middle of a tab or wide character refer to that
+    /// character.  Returns `None` if the line is not in the file, or
+    /// the column is past its end.
+    pub fn visual_to_point(&self, line: u32, col: u32) -> Option<Point> {
+        let (start, end) = self.line_range(line)?;
+        let text = &self.contents[start..end];
+        let col = (col as usize).checked_sub(1)?;
+        let char_col = self.tabs.char_column(text, col);
+        let offset = text.char_indices().map(|(i, _)| i)
+            .chain(Some(text.len()))
+            .nth(char_col)?;
+
+        self.offset_to_point(start + offset)
+    }
+
+    /// Get the name of the file.
+    pub fn name(&self) -> Filename<'a> {
+        self.name
+    }
+
+    /// Get the fingerprint of the contents of the file, to tell
+    /// whether they have changed since an earlier run.
+    pub fn content_fingerprint(&self) -> Fingerprint {
+        Fingerprint::of_bytes(self.contents.as_bytes())
+    }
+
+    /// Get the contents of the file.
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Get the number of lines in the file.  A trailing newline starts
+    /// an empty last line.
+    pub fn line_count(&self) -> u32 {
+        self.lines.line_count()
+    }
+
+    /// Get the index of where each line starts.
+    pub fn line_index(&self) -> &LineIndex {
+        &self.lines
+    }
+
+    /// Replace the bytes `range` of the file with `text`, updating the
+    /// line index without rescanning the file.  Panics if the range is
+    /// out of bounds or not on character boundaries.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) {
+        self.contents.replace_range(range.clone(), text);
+        self.lines.edit(range, text)
+    }
+
+    /// Get the byte range of line `line`, not including its line
+    /// terminator.
+    fn line_range(&self, line: u32) -> Option<(usize, usize)> {
+        let idx = (line as usize).checked_sub(1)?;
+        let start = *self.lines.starts.get(idx)?;
+        let end = match self.lines.starts.get(idx + 1) {
+            Some(next) => next - 1,
+            None => self.contents.len()
+        };
+        let text = &self.contents[start..end];
+
+        Some((start, start + text.strip_suffix('\r').unwrap_or(text).len()))
+    }
+
+    /// Get the text of line `line`, without its line terminator.
+    pub fn line(&self, line: u32) -> Option<&str> {
+        self.line_range(line).map(|(start, end)| &self.contents[start..end])
+    }
+
+    /// Get the point at byte offset `offset`.  Returns `None` if
+    /// `offset` is past the end of the file or not on a character
+    /// boundary.  The end of the file is a valid offset.
+    pub fn offset_to_point(&self, offset: usize) -> Option<Point> {
+        if !self.contents.is_char_boundary(offset) {
+            return None;
+        }
+
+        let line = self.lines.line_of(offset);
+        let start = self.lines.starts[line as usize - 1];
+        let col = self.mode.width(&self.contents[start..offset], self.tabs);
+
+        Some(Point { line, col: col as u32 + 1 })
+    }
+
+    /// Get the byte offset of `point`.  Returns `None` if the point is
+    /// not in the file, or its column is in the middle of a character
+    /// or, when counting display width, a grapheme cluster.  The
+    /// column just past the end of a line is valid, and refers to its
+    /// line terminator.
+    pub fn point_to_offset(&self, point: Point) -> Option<usize> {
+        let (start, end) = self.line_range(point.line)?;
+        let col = (point.col as usize).checked_sub(1)?;
+
+        self.mode.offset(&self.contents[start..end], col, self.tabs)
+            .map(|i| start + i)
+    }
+
+    /// Get the text of the lines covered by `loc`, without the last
+    /// line's terminator, to show as context in diagnostics.  Returns
+    /// `None` if `loc` is not in the file.
+    pub fn snippet(&self, loc: &Location) -> Option<&str> {
+        let (first, last) = match *loc {
+            Location::Span { start, end } => (start.line, end.line),
+            Location::Point { point } => (point.line, point.line)
+        };
+        let (start, _) = self.line_range(first)?;
+        let (_, end) = self.line_range(last)?;
+
+        if start <= end {
+            Some(&self.contents[start..end])
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> SourceManager<'a> {
+    /// Create an empty `SourceManager`.
+    pub fn new() -> SourceManager<'a> {
+        SourceManager { files: HashMap::new(), mode: ColumnMode::default(),
+                        tabs: TabStops::default() }
+    }
+
+    /// Get how columns are counted.
+    pub fn column_mode(&self) -> ColumnMode {
+        self.mode
+    }
+
+    /// Set how columns are counted, in files registered already and
+    /// from now on.
+    pub fn set_column_mode(&mut self, mode: ColumnMode) {
+        self.mode = mode;
+
+        for file in self.files.values_mut() {
+            file.set_column_mode(mode)
+        }
+    }
+
+    /// Get where tabs advance to.
+    pub fn tab_stops(&self) -> TabStops {
+        self.tabs
+    }
+
+    /// Set where tabs advance to, in files registered already and from
+    /// now on.  This affects columns counted by display width, and
+    /// how diagnostics line up with source lines.
+    pub fn set_tab_stops(&mut self, tabs: TabStops) {
+        self.tabs = tabs;
+
+        for file in self.files.values_mut() {
+            file.set_tab_stops(tabs)
+        }
+    }
+
+    /// Register `contents` as the contents of the file `name`,
+    /// replacing any contents registered before.
+    pub fn add(&mut self, name: Filename<'a>,
+               contents: String) -> &SourceFile<'a> {
+        let mut file = SourceFile::new(name, contents);
+
+        file.set_column_mode(self.mode);
+        file.set_tab_stops(self.tabs);
+        self.files.insert(name, file);
+
+        &self.files[&name]
+    }
+
+    /// Read the files `names` through `loader`, on up to `threads`
+    /// threads, and register their contents.  Files are registered in
+    /// the order of `names`, whatever order they are read in, so that
+    /// the result is the same on every run.  Returns the files that
+    /// could not be read, with their errors, in the same order.  Files
+    /// with no path give an error of kind `NotFound`.  Each file read,
+    /// or not, is reported to `progress` as a unit of work in the phase
+    /// `load`.
+    pub fn load_many<L>(&mut self, loader: &L, names: &[Filename<'a>],
+                        threads: usize, progress: &dyn ProgressSink)
+                        -> Vec<(Filename<'a>, Error)>
+        where L: FileLoader + Sync {
+        let phase = PhaseProgress::start(progress, "load",
+                                         Some(names.len() as u64));
+        let root = JobKey::root();
+        let loaded = OrderedBuffer::new(true);
+        let next = AtomicUsize::new(0);
+        let work = || loop {
+            let idx = next.fetch_add(1, Ordering::Relaxed);
+            let name = match names.get(idx) {
+                Some(&name) => name,
+                None => break
+            };
+            // Indexing the lines is most of the work after reading, so
+            // the threads do that too.
+            let file = read_file(loader, name)
+                .map(|contents| SourceFile::new(name, contents));
+
+            loaded.push(&root.child(idx as u32), (name, file));
+            phase.advance(1);
+        };
+
+        thread::scope(|scope| {
+            for _ in 1..threads.min(names.len()) {
+                scope.spawn(work);
+            }
+
+            work()
+        });
+
+        let mut errors = Vec::new();
+
+        for (name, file) in loaded.drain() {
+            match file {
+                Ok(mut file) => {
+                    file.set_column_mode(self.mode);
+                    file.set_tab_stops(self.tabs);
+                    self.files.insert(name, file);
+                },
+                Err(err) => errors.push((name, err))
+            }
+        }
+
+        errors
+    }
+
+    /// Register `contents` as the contents of standard input.  Use
+    /// `FilenameTable::buffer` or `FilenameTable::repl` to name other
+    /// sources with no path, and `add` to register them.
+    pub fn add_stdin(&mut self, contents: String) -> &SourceFile<'a> {
+        self.add(Filename::stdin(), contents)
+    }
+
+    /// Read all of standard input and register it as with `add_stdin`.
+    pub fn read_stdin(&mut self) -> Result<&SourceFile<'a>> {
+        let mut contents = String::new();
+
+        ::std::io::stdin().read_to_string(&mut contents)?;
+
+        Ok(self.add_stdin(contents))
+    }
+
+    /// Get the file `name`, if it has been registered.
+    pub fn get(&self, name: Filename<'a>) -> Option<&SourceFile<'a>> {
+        self.files.get(&name)
+    }
+
+    /// Replace the bytes `range` of the file `name` with `text`, as
+    /// `SourceFile::edit` does.  Returns `None` if the file has not been
+    /// registered.
+    pub fn edit(&mut self, name: Filename<'a>, range: Range<usize>,
+                text: &str) -> Option<&SourceFile<'a>> {
+        let file = self.files.get_mut(&name)?;
+
+        file.edit(range, text);
+
+        Some(file)
+    }
+
+    /// Get the number of registered files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check whether no files are registered.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Get the point at byte offset `offset` in the file `name`.
+    pub fn offset_to_point(&self, name: Filename<'a>,
+                           offset: usize) -> Option<Point> {
+        self.get(name)?.offset_to_point(offset)
+    }
+
+    /// Get the byte offset of `point` in the file `name`.
+    pub fn point_to_offset(&self, name: Filename<'a>,
+                           point: Point) -> Option<usize> {
+        self.get(name)?.point_to_offset(point)
+    }
+
+    /// Get the text of the lines covered by `pos`, as
+    /// `SourceFile::snippet` does.
+    pub fn snippet(&self, pos: &FilePosition<'a>) -> Option<&str> {
+        self.get(pos.filename)?.snippet(&pos.loc)
+    }
+}
+
+impl<'a> SourceProvider<'a> for SourceManager<'a> {
+    fn source_line(&self, filename: Filename<'a>, line: u32) -> Option<&str> {
+        self.get(filename)?.line(line)
+    }
+
+    fn tab_stops(&self) -> TabStops {
+        self.tabs
+    }
+}
+
+impl<F: Fn(&Path) -> Result<String>> FileLoader for F {
+    fn load(&self, path: &Path) -> Result<String> {
+        self(path)
+    }
+}
+
+impl<'a, L: FileLoader> SourceCache<'a, L> {
+    /// Create an empty `SourceCache` reading files through `loader`.
+    pub fn new(loader: L) -> SourceCache<'a, L> {
+        SourceCache { loader, files: HashMap::new(),
+                      limit: DEFAULT_CACHE_LIMIT, size: 0, clock: 0,
+                      mode: ColumnMode::default(),
+                      tabs: TabStops::default() }
+    }
+
+    /// Set the most bytes of file contents to keep.  The file loaded
+    /// last is always kept, even if it is larger than this.
+    pub fn with_limit(mut self, bytes: usize) -> SourceCache<'a, L> {
+        self.limit = bytes;
+        self
+    }
+
+    /// Set how columns are counted in loaded files.
+    pub fn with_column_mode(mut self, mode: ColumnMode) -> SourceCache<'a, L> {
+        self.mode = mode;
+        self
+    }
+
+    /// Set where tabs advance to in loaded files.
+    pub fn with_tab_stops(mut self, tabs: TabStops) -> SourceCache<'a, L> {
+        self.tabs = tabs;
+        self
+    }
+
+    /// Get the loader.
+    pub fn loader(&self) -> &L {
+        &self.loader
+    }
+
+    /// Get the file `name`, reading it through the loader by its local
+    /// path if it is not kept already.  Files with no path cannot be
+    /// loaded, and give an error of kind `NotFound`.
+    pub fn load(&mut self, name: Filename<'a>) -> Result<&SourceFile<'a>> {
+        self.clock += 1;
+
+        if !self.files.contains_key(&name) {
+            let contents = read_file(&self.loader, name)?;
+            let mut file = SourceFile::new(name, contents);
+
+            file.set_column_mode(self.mode);
+            file.set_tab_stops(self.tabs);
+            self.size += file.contents().len();
+            self.files.insert(name, CachedFile { file, last_used: 0 });
+            self.evict_over_limit(name);
+        }
+
+        let cached = self.files.get_mut(&name).unwrap();
+
+        cached.last_used = self.clock;
+
+        Ok(&cached.file)
+    }
+
+    /// Get the file `name`, if it is kept.
+    pub fn get(&self, name: Filename<'a>) -> Option<&SourceFile<'a>> {
+        self.files.get(&name).map(|cached| &cached.file)
+    }
+
+    /// Stop keeping the file `name`, so it is read again next time, as
+    /// when it has changed.  Returns whether it was kept.
+    pub fn evict(&mut self, name: Filename<'a>) -> bool {
+        match self.files.remove(&name) {
+            Some(cached) => {
+                self.size -= cached.file.contents().len();
+
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Stop keeping any files.
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.size = 0;
+    }
+
+    /// Get the number of files kept.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check whether no files are kept.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Get the total bytes of the contents of the files kept.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Evict the least recently loaded files, other than `keep`, until
+    /// the size is within the limit.
+    fn evict_over_limit(&mut self, keep: Filename<'a>) {
+        while self.size > self.limit {
+            let oldest = self.files.iter()
+                .filter(|(name, _)| **name != keep)
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(name, _)| *name);
+
+            match oldest {
+                Some(name) => { self.evict(name); }
+                None => break
+            }
+        }
+    }
+}
+
+impl<'a, L: FileLoader> SourceProvider<'a> for SourceCache<'a, L> {
+    fn source_line(&self, filename: Filename<'a>, line: u32) -> Option<&str> {
+        self.get(filename)?.line(line)
+    }
+
+    fn tab_stops(&self) -> TabStops {
+        self.tabs
+    }
+}
+
+/// Source files hash by name and contents, and how columns are counted,
+/// which decides the points that offsets become.
+impl<'a> StableHash for SourceFile<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.name.stable_hash(hasher);
+        self.content_fingerprint().stable_hash(hasher);
+        self.mode.stable_hash(hasher);
+        self.tabs.stable_hash(hasher);
+    }
+}
+
+impl HeapSize for LineIndex {
+    fn heap_size(&self) -> usize {
+        self.starts.heap_size()
+    }
+}
+
+impl<'a> HeapSize for SourceFile<'a> {
+    fn heap_size(&self) -> usize {
+        self.contents.heap_size() + self.lines.heap_size()
+    }
+}
+
+impl<'a, L> HeapSize for SourceCache<'a, L> {
+    fn heap_size(&self) -> usize {
+        hash_table_size::<(Filename, CachedFile)>(self.files.capacity()) +
+        self.files.values()
+            .map(|cached| cached.file.heap_size())
+            .sum::<usize>()
+    }
+}
+
+impl<'a> HeapSize for SourceManager<'a> {
+    fn heap_size(&self) -> usize {
+        hash_table_size::<(Filename, SourceFile)>(self.files.capacity()) +
+        self.files.values().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+#[test]
+fn test_offset_to_point() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let file = SourceFile::new(names.filename("a.salt"),
+                               String::from("ab\r\nλx\n\nend"));
+
+    assert_eq!(file.line_count(), 4);
+    assert_eq!(file.offset_to_point(0), Some(Point { line: 1, col: 1 }));
+    assert_eq!(file.offset_to_point(2), Some(Point { line: 1, col: 3 }));
+    assert_eq!(file.offset_to_point(4), Some(Point { line: 2, col: 1 }));
+    assert_eq!(file.offset_to_point(5), None);
+    assert_eq!(file.offset_to_point(6), Some(Point { line: 2, col: 2 }));
+    assert_eq!(file.offset_to_point(8), Some(Point { line: 3, col: 1 }));
+    assert_eq!(file.offset_to_point(12), Some(Point { line: 4, col: 4 }));
+    assert_eq!(file.offset_to_point(13), None);
+    assert_eq!(file.line(1), Some("ab"));
+    assert_eq!(file.line(2), Some("λx"));
+    assert_eq!(file.line(3), Some(""));
+    assert_eq!(file.line(5), None);
+}
+
+#[test]
+fn test_point_to_offset() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let file = SourceFile::new(names.filename("a.salt"),
+                               String::from("ab\r\nλx\n\nend"));
+
+    for offset in [0, 1, 2, 4, 6, 7, 8, 9, 12].iter() {
+        let point = file.offset_to_point(*offset).unwrap();
+
+        assert_eq!(file.point_to_offset(point), Some(*offset));
+    }
+
+    assert_eq!(file.point_to_offset(Point { line: 1, col: 4 }), None);
+    assert_eq!(file.point_to_offset(Point { line: 0, col: 1 }), None);
+    assert_eq!(file.point_to_offset(Point { line: 3, col: 0 }), None);
+    assert_eq!(file.point_to_offset(Point { line: 5, col: 1 }), None);
+}
+
+#[test]
+fn test_snippet() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let name = names.filename("a.salt");
+    let mut sources = SourceManager::new();
+
+    sources.add(name, String::from("fn f() {\n  g()\n}\n"));
+
+    let span = FilePosition {
+        filename: name,
+        loc: Location::Span { start: Point { line: 1, col: 1 },
+                              end: Point { line: 2, col: 6 } }
+    };
+    let point = FilePosition {
+        filename: name, loc: Location::Point { point: Point { line: 2,
+                                                              col: 3 } }
+    };
+    let missing = FilePosition {
+        filename: names.filename("b.salt"), loc: point.loc.clone()
+    };
+
+    assert_eq!(sources.snippet(&span), Some("fn f() {\n  g()"));
+    assert_eq!(sources.snippet(&point), Some("  g()"));
+    assert_eq!(sources.snippet(&missing), None);
+    assert_eq!(sources.offset_to_point(name, 11),
+               Some(Point { line: 2, col: 3 }));
+    assert_eq!(sources.point_to_offset(name, Point { line: 3, col: 1 }),
+               Some(15));
+}
+
+#[test]
+fn test_column_mode() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let name = names.filename("a.salt");
+    let mut sources = SourceManager::new();
+
+    sources.add(name, String::from("s = \"日本\"; x\n"));
+    assert_eq!(sources.offset_to_point(name, 13),
+               Some(Point { line: 1, col: 10 }));
+
+    sources.set_column_mode(ColumnMode::DisplayWidth);
+    assert_eq!(sources.offset_to_point(name, 13),
+               Some(Point { line: 1, col: 12 }));
+    assert_eq!(sources.point_to_offset(name, Point { line: 1, col: 12 }),
+               Some(13));
+    assert_eq!(sources.point_to_offset(name, Point { line: 1, col: 7 }),
+               None);
+
+    sources.set_column_mode(ColumnMode::Bytes);
+    assert_eq!(sources.offset_to_point(name, 13),
+               Some(Point { line: 1, col: 14 }));
+}
+
+#[test]
+fn test_tab_stops() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let name = names.filename("a.salt");
+    let mut sources = SourceManager::new();
+
+    sources.add(name, String::from("\tx = 1;\n"));
+
+    let file = sources.get(name).unwrap();
+
+    assert_eq!(file.visual_column(Point { line: 1, col: 2 }), Some(9));
+    assert_eq!(file.visual_to_point(1, 9), Some(Point { line: 1, col: 2 }));
+    assert_eq!(file.visual_to_point(1, 4), Some(Point { line: 1, col: 1 }));
+
+    sources.set_tab_stops(TabStops::new(2));
+    sources.set_column_mode(ColumnMode::DisplayWidth);
+
+    assert_eq!(sources.offset_to_point(name, 1),
+               Some(Point { line: 1, col: 3 }));
+    assert_eq!(sources.get(name).unwrap().visual_to_point(1, 3),
+               Some(Point { line: 1, col: 3 }));
+}
+
+#[test]
+fn test_line_index_edit() {
+    use salt::common::rng::DeterministicRng;
+
+    let mut rng = DeterministicRng::new(521);
+    let mut text = String::from("fn f() {\n  g();\n}\n");
+    let mut index = LineIndex::new(&text);
+    let pieces = ["", "x", "\n", "a\nb", "\n\n", "\r\n", "long text"];
+
+    for _ in 0..500 {
+        let a = rng.below(text.len() as u64 + 1) as usize;
+        let b = rng.below(text.len() as u64 + 1) as usize;
+        let range = a.min(b)..a.max(b);
+        let piece = pieces[rng.below(pieces.len() as u64) as usize];
+
+        text.replace_range(range.clone(), piece);
+        index.edit(range, piece);
+        assert_eq!(index, LineIndex::new(&text), "{:?}", text);
+    }
+
+    assert_eq!(index.line_start(1), Some(0));
+    assert_eq!(index.line_of(text.len()), index.line_count());
+}
+
+#[test]
+fn test_source_edit() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let name = names.filename("a.salt");
+    let mut sources = SourceManager::new();
+
+    sources.add(name, String::from("fn f() {\n  g();\n}\n"));
+
+    let file = sources.edit(name, 11..15, "h(\n    1);\n  i()").unwrap();
+
+    assert_eq!(file.contents(), "fn f() {\n  h(\n    1);\n  i()\n}\n");
+    assert_eq!(file.line_count(), 6);
+    assert_eq!(file.line(3), Some("    1);"));
+    assert_eq!(file.offset_to_point(24), Some(Point { line: 4, col: 3 }));
+    assert!(sources.edit(names.filename("b.salt"), 0..0, "x").is_none());
+}
+
+#[test]
+fn test_add_stdin() {
+    use salt::common::filename::FilenameTable;
+    use salt::common::filename::Normalization;
+    use salt::common::str::arena::StrArena;
+
+    let arena = StrArena::new();
+    let mut table = FilenameTable::new(&arena, Normalization::Lexical);
+    let buffer = table.buffer();
+    let mut manager = SourceManager::new();
+
+    manager.add_stdin("from stdin\n".to_string());
+    manager.add(buffer, "unsaved\n".to_string());
+
+    assert_eq!(manager.len(), 2);
+    assert_eq!(manager.get(Filename::stdin()).unwrap().contents(),
+               "from stdin\n");
+    assert_eq!(manager.get(buffer).unwrap().line(1), Some("unsaved"));
+}
+
+#[test]
+fn test_load_many() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Records the number of files loaded, between the start and end
+    /// of the phase.
+    struct Record(Mutex<Vec<Option<u64>>>);
+
+    impl ProgressSink for Record {
+        fn phase_started(&self, phase: &str, total: Option<u64>) {
+            assert_eq!((phase, total), ("load", Some(17)));
+            self.0.lock().unwrap().push(None)
+        }
+
+        fn progress(&self, _phase: &str, done: u64, _total: Option<u64>) {
+            self.0.lock().unwrap().push(Some(done))
+        }
+
+        fn phase_finished(&self, _phase: &str) {
+            self.0.lock().unwrap().push(None)
+        }
+    }
+
+    let paths: Vec<String> = (0..16).map(|i| format!("{}.salt", i))
+        .collect();
+    let mut names = StrIntern::new();
+    let files: Vec<Filename> = paths.iter()
+        .map(|path| names.filename(path))
+        .chain(Some(Filename::stdin()))
+        .collect();
+    // Early files take longest, so that threads finish out of order,
+    // and files with odd numbers are missing.
+    let loader = |path: &Path| {
+        let n: u64 = path.to_str().unwrap().trim_end_matches(".salt")
+            .parse().unwrap();
+
+        thread::sleep(Duration::from_millis(16 - n));
+
+        if n % 2 == 0 {
+            Ok(format!("file {}\n", n))
+        } else {
+            Err(Error::new(ErrorKind::NotFound, format!("no file {}", n)))
+        }
+    };
+    let load = |threads| {
+        let mut manager = SourceManager::new();
+        let progress = Record(Mutex::new(Vec::new()));
+        let errors: Vec<String> = manager.load_many(&loader, &files, threads,
+                                                    &progress)
+            .into_iter()
+            .map(|(name, err)| format!("{}: {}", name, err))
+            .collect();
+        let mut events = progress.0.into_inner().unwrap();
+
+        // Threads may report out of order, but each count once.
+        events[1..17].sort();
+        assert_eq!(events, Some(None).into_iter()
+                   .chain((1..=17).map(Some))
+                   .chain(Some(None))
+                   .collect::<Vec<_>>());
+
+        (manager, errors)
+    };
+    let (serial, serial_errors) = load(1);
+    let (parallel, parallel_errors) = load(8);
+
+    assert_eq!(parallel_errors, serial_errors);
+    assert_eq!(parallel_errors[..2],
+               ["1.salt: no file 1", "3.salt: no file 3"]);
+    assert_eq!(parallel_errors.last().unwrap(),
+               "<stdin>: <stdin> has no path");
+    assert_eq!(parallel_errors.len(), 9);
+    assert_eq!(parallel.len(), 8);
+    assert_eq!(parallel.get(files[4]).unwrap().line(1), Some("file 4"));
+    assert_eq!(serial.get(files[4]), parallel.get(files[4]));
+}
+
+#[test]
+fn test_source_cache() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+    use std::cell::Cell;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let reads = Cell::new(0);
+    let loader = |path: &Path| {
+        reads.set(reads.get() + 1);
+
+        match path.to_str() {
+            Some("a.salt") => Ok("first\nsecond\n".to_string()),
+            Some("b.salt") => Ok("other\n".to_string()),
+            _ => Err(Error::new(ErrorKind::NotFound, "no such file"))
+        }
+    };
+    let mut cache = SourceCache::new(loader).with_limit(16);
+
+    assert_eq!(cache.load(a).unwrap().line(2), Some("second"));
+    assert_eq!(cache.source_line(a, 1), Some("first"));
+    assert_eq!(cache.load(a).unwrap().line_count(), 3);
+    assert_eq!(reads.get(), 1);
+    assert_eq!(cache.size(), 13);
+
+    cache.load(b).unwrap();
+
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.source_line(a, 1), None);
+    assert_eq!(cache.source_line(b, 1), Some("other"));
+    assert!(cache.load(names.filename("c.salt")).is_err());
+    assert_eq!(cache.load(Filename::stdin()).unwrap_err().kind(),
+               ErrorKind::NotFound);
+    assert!(cache.evict(b) && !cache.evict(b));
+    assert!(cache.is_empty());
+    assert_eq!(cache.size(), 0);
+}
+
+#[test]
+fn test_source_fingerprint() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let mut file = SourceFile::new(a, "let x = 1;\n".to_string());
+    let before = file.fingerprint();
+
+    assert_eq!(SourceFile::new(a, "let x = 1;\n".to_string()).fingerprint(),
+               before);
+
+    file.edit(8..9, "2");
+
+    assert_ne!(file.fingerprint(), before);
+    assert_ne!(file.content_fingerprint(),
+               SourceFile::new(a, "let x = 1;\n".to_string())
+                   .content_fingerprint());
+}
@@ -0,0 +1,248 @@
+use salt::common::filename::Filename;
+use salt::common::json::Json;
+use salt::common::json::ToJson;
+use salt::common::position::FilePosition;
+use salt::common::position::Point;
+
+/// The characters of base 64, as used by source maps.
+const BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A map from positions in generated code, such as C produced by a
+/// backend, to the positions in the original source they came from.
+/// A mapping covers the generated line from its column up to the next
+/// mapping on that line.  It serializes as a version 3 source map.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SourceMap<'a> {
+    /// The name of the generated file.
+    file: String,
+    /// The mappings, sorted by generated position.
+    mappings: Vec<(Point, FilePosition<'a>)>
+}
+
+/// Append `value` to `out` as a base 64 VLQ, as source maps encode
+/// numbers: sign in the lowest bit, then 5 bits per digit, with a
+/// continuation bit.
+fn vlq(out: &mut String, value: i64) {
+    let mut rest = if value < 0 {
+        (value.unsigned_abs() << 1) | 1
+    } else {
+        (value as u64) << 1
+    };
+
+    loop {
+        let digit = (rest & 0x1f) as usize;
+
+        rest >>= 5;
+
+        if rest == 0 {
+            out.push(BASE64[digit] as char);
+
+            return;
+        }
+
+        out.push(BASE64[digit | 0x20] as char)
+    }
+}
+
+impl<'a> SourceMap<'a> {
+    /// Create an empty source map for the generated file `file`.
+    pub fn new(file: &str) -> SourceMap<'a> {
+        SourceMap { file: file.to_string(), mappings: Vec::new() }
+    }
+
+    /// Get the name of the generated file.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Record that the code at `generated` came from `original`,
+    /// replacing any mapping already at `generated`.
+    pub fn add(&mut self, generated: Point, original: FilePosition<'a>) {
+        match self.mappings.binary_search_by(|(pos, _)| pos.cmp(&generated)) {
+            Ok(idx) => self.mappings[idx].1 = original,
+            Err(idx) => self.mappings.insert(idx, (generated, original))
+        }
+    }
+
+    /// Get the original position of the code at `generated`: that of
+    /// the last mapping on its line at or before it.
+    pub fn lookup(&self, generated: Point) -> Option<&FilePosition<'a>> {
+        let idx = match self.mappings
+            .binary_search_by(|(pos, _)| pos.cmp(&generated)) {
+            Ok(idx) => idx,
+            Err(0) => return None,
+            Err(idx) => idx - 1
+        };
+        let (pos, original) = &self.mappings[idx];
+
+        if pos.line == generated.line {
+            Some(original)
+        } else {
+            None
+        }
+    }
+
+    /// Get the mappings, in order of generated position.
+    pub fn mappings(&self) -> &[(Point, FilePosition<'a>)] {
+        &self.mappings
+    }
+
+    /// Get the number of mappings.
+    pub fn len(&self) -> usize {
+        self.mappings.len()
+    }
+
+    /// Check whether there are no mappings.
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+
+    /// Get the original files, in order of first mapping.
+    pub fn sources(&self) -> Vec<Filename<'a>> {
+        let mut sources = Vec::new();
+
+        for (_, original) in &self.mappings {
+            if !sources.contains(&original.filename) {
+                sources.push(original.filename)
+            }
+        }
+
+        sources
+    }
+
+    /// Get the `mappings` string of the version 3 format, for the
+    /// original files `sources`.  Lines and columns are from 0, and
+    /// each field is relative to the one before it, columns in the
+    /// generated code only within a line.
+    fn encode_mappings(&self, sources: &[Filename<'a>]) -> String {
+        let mut out = String::new();
+        let mut line = 1;
+        let mut col = 1;
+        let mut prev = (0, 1, 1);
+
+        for (generated, original) in &self.mappings {
+            if generated.line != line {
+                for _ in line..generated.line {
+                    out.push(';')
+                }
+
+                line = generated.line;
+                col = 1;
+            } else if !out.is_empty() && !out.ends_with(';') {
+                out.push(',')
+            }
+
+            let source = sources.iter()
+                .position(|name| *name == original.filename)
+                .unwrap() as i64;
+            let start = original.loc.start();
+
+            vlq(&mut out, generated.col as i64 - col as i64);
+            vlq(&mut out, source - prev.0);
+            vlq(&mut out, start.line as i64 - prev.1 as i64);
+            vlq(&mut out, start.col as i64 - prev.2 as i64);
+            col = generated.col;
+            prev = (source, start.line, start.col);
+        }
+
+        out
+    }
+}
+
+impl<'a> ToJson for SourceMap<'a> {
+    fn to_json(&self) -> Json {
+        let sources = self.sources();
+        let names: Vec<String> =
+            sources.iter().map(|name| name.to_string()).collect();
+
+        Json::object()
+            .with("version", 3u64)
+            .with("file", self.file.as_str())
+            .with("sources", names)
+            .with("names", Vec::<Json>::new())
+            .with("mappings", self.encode_mappings(&sources))
+    }
+}
+
+#[test]
+fn test_source_map() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::Location;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let point = |line, col| Point { line, col };
+    let filepos = |filename, line, col| FilePosition {
+        filename, loc: Location::Point { point: point(line, col) }
+    };
+    let mut map = SourceMap::new("out.c");
+
+    map.add(point(3, 5), filepos(a, 2, 1));
+    map.add(point(1, 1), filepos(a, 1, 1));
+    map.add(point(3, 1), filepos(b, 10, 3));
+    map.add(point(3, 20), filepos(a, 1, 17));
+
+    assert_eq!(map.len(), 4);
+    assert_eq!(map.lookup(point(1, 7)), Some(&filepos(a, 1, 1)));
+    assert_eq!(map.lookup(point(2, 1)), None);
+    assert_eq!(map.lookup(point(3, 4)), Some(&filepos(b, 10, 3)));
+    assert_eq!(map.lookup(point(3, 5)), Some(&filepos(a, 2, 1)));
+    assert_eq!(map.lookup(point(3, 80)), Some(&filepos(a, 1, 17)));
+    assert_eq!(map.to_json().to_string(),
+               "{\"version\":3,\"file\":\"out.c\",\
+                \"sources\":[\"a.salt\",\"b.salt\"],\"names\":[],\
+                \"mappings\":\"AAAA;;ACSE,IDRF,eADgB\"}");
+}
+
+#[test]
+fn test_vlq() {
+    let encode = |value| {
+        let mut out = String::new();
+
+        vlq(&mut out, value);
+
+        out
+    };
+
+    assert_eq!(encode(0), "A");
+    assert_eq!(encode(1), "C");
+    assert_eq!(encode(-1), "D");
+    assert_eq!(encode(15), "e");
+    assert_eq!(encode(-15), "f");
+    assert_eq!(encode(16), "gB");
+    assert_eq!(encode(-16), "hB");
+    assert_eq!(encode(1000), "w+B");
+    assert_eq!(encode(-1000), "x+B");
+}
+
+#[test]
+fn test_source_map_backwards() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::Location;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let point = |line, col| Point { line, col };
+    let filepos = |filename, line, col| FilePosition {
+        filename, loc: Location::Point { point: point(line, col) }
+    };
+    let mut map = SourceMap::new("out.c");
+
+    // Each mapping goes back in the original source, and the last
+    // goes back to the first source, so every delta but the generated
+    // columns is negative at some point, some over several digits.
+    map.add(point(1, 10), filepos(a, 50, 30));
+    map.add(point(1, 12), filepos(a, 3, 1));
+    map.add(point(2, 1), filepos(b, 1, 1));
+    map.add(point(2, 5), filepos(a, 100, 1));
+
+    assert_eq!(map.to_json().to_string(),
+               "{\"version\":3,\"file\":\"out.c\",\
+                \"sources\":[\"a.salt\",\"b.salt\"],\"names\":[],\
+                \"mappings\":\"SAiD6B,EA/C7B;ACFA,IDmGA\"}");
+}
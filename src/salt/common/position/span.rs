@@ -0,0 +1,203 @@
+use salt::common::cache::DecodeResult;
+use salt::common::cache::Decodable;
+use salt::common::cache::Decoder;
+use salt::common::cache::Encodable;
+use salt::common::cache::Encoder;
+use salt::common::filename::Filename;
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+use salt::common::position::FilePosition;
+use salt::common::position::Location;
+use salt::common::position::SourceManager;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// A compact position: a byte range in a file identified by a number
+/// from a `FileTable`.  At 12 bytes, this is small enough for every
+/// AST node to carry one, and is expanded into a `FilePosition` only
+/// when needed, such as when reporting a diagnostic.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ByteSpan {
+    /// The file, as numbered by a `FileTable`.
+    pub file_id: u32,
+    /// The byte offset of the start of the span.
+    pub start: u32,
+    /// The byte offset just past the end of the span.
+    pub end: u32
+}
+
+/// A table numbering files, for expanding `ByteSpan`s.
+#[derive(Clone, Debug, Default)]
+pub struct FileTable<'a> {
+    files: Vec<Filename<'a>>,
+    ids: HashMap<Filename<'a>, u32>
+}
+
+impl ByteSpan {
+    /// Create a `ByteSpan` from `start` to `end` in the file
+    /// `file_id`.  Panics if `end` is before `start`.
+    pub fn new(file_id: u32, start: u32, end: u32) -> ByteSpan {
+        assert!(start <= end, "span ends before it starts");
+
+        ByteSpan { file_id, start, end }
+    }
+
+    /// Get the length of the span in bytes.
+    pub fn len(&self) -> u32 {
+        self.end - self.start
+    }
+
+    /// Check whether the span is empty.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Check whether byte `offset` is in the span.
+    pub fn contains(&self, offset: u32) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    /// Get the smallest span covering both `self` and `other`, if they
+    /// are in the same file.
+    pub fn merge(&self, other: &ByteSpan) -> Option<ByteSpan> {
+        if self.file_id == other.file_id {
+            Some(ByteSpan { file_id: self.file_id,
+                            start: self.start.min(other.start),
+                            end: self.end.max(other.end) })
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> FileTable<'a> {
+    /// Create an empty `FileTable`.
+    pub fn new() -> FileTable<'a> {
+        FileTable { files: Vec::new(), ids: HashMap::new() }
+    }
+
+    /// Get the number for `filename`, assigning a new one if it has
+    /// none.  Panics if the table is full.
+    pub fn add(&mut self, filename: Filename<'a>) -> u32 {
+        let files = &mut self.files;
+
+        *self.ids.entry(filename).or_insert_with(|| {
+            let id = u32::try_from(files.len()).expect("file table full");
+
+            files.push(filename);
+
+            id
+        })
+    }
+
+    /// Get the number for `filename`, if it has one.
+    pub fn id(&self, filename: Filename<'a>) -> Option<u32> {
+        self.ids.get(&filename).cloned()
+    }
+
+    /// Get the file numbered `file_id`, if there is one.
+    pub fn filename(&self, file_id: u32) -> Option<Filename<'a>> {
+        self.files.get(file_id as usize).cloned()
+    }
+
+    /// Get the number of files in the table.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Expand `span` into a `FilePosition`, using the contents of its
+    /// file in `sources`.  Empty spans become point locations.  Returns
+    /// `None` if the file is not in the table or in `sources`, or the
+    /// span is not in the file.
+    pub fn expand(&self, span: ByteSpan,
+                  sources: &SourceManager<'a>) -> Option<FilePosition<'a>> {
+        let filename = self.filename(span.file_id)?;
+        let file = sources.get(filename)?;
+        let start = file.offset_to_point(span.start as usize)?;
+        let loc = if span.is_empty() {
+            Location::Point { point: start }
+        } else {
+            Location::Span { start,
+                             end: file.offset_to_point(span.end as usize)? }
+        };
+
+        Some(FilePosition { filename, loc })
+    }
+}
+
+/// File ids are numbered in the order files were added to a
+/// `FileTable`, so byte spans hash stably only if files are added in the
+/// same order every run.
+impl StableHash for ByteSpan {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.file_id.stable_hash(hasher);
+        self.start.stable_hash(hasher);
+        self.end.stable_hash(hasher);
+    }
+}
+
+impl Encodable for ByteSpan {
+    fn encode(&self, enc: &mut Encoder) {
+        self.file_id.encode(enc);
+        self.start.encode(enc);
+        self.end.encode(enc);
+    }
+}
+
+impl<'d> Decodable<'d> for ByteSpan {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<ByteSpan> {
+        Ok(ByteSpan { file_id: u32::decode(dec)?, start: u32::decode(dec)?,
+                      end: u32::decode(dec)? })
+    }
+}
+
+#[test]
+fn test_byte_span() {
+    let a = ByteSpan::new(0, 4, 8);
+    let b = ByteSpan::new(0, 6, 12);
+
+    assert_eq!(::std::mem::size_of::<ByteSpan>(), 12);
+    assert_eq!(a.len(), 4);
+    assert!(a.contains(4) && !a.contains(8));
+    assert_eq!(a.merge(&b), Some(ByteSpan::new(0, 4, 12)));
+    assert_eq!(a.merge(&ByteSpan::new(1, 0, 1)), None);
+}
+
+#[test]
+fn test_expand() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::Point;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let mut files = FileTable::new();
+    let mut sources = SourceManager::new();
+    let id = files.add(a);
+
+    sources.add(a, String::from("fn f() {\n  g()\n}\n"));
+
+    assert_eq!(files.add(a), id);
+    assert_eq!(files.id(a), Some(id));
+    assert_eq!(files.filename(id), Some(a));
+
+    let pos = files.expand(ByteSpan::new(id, 11, 14), &sources).unwrap();
+
+    assert_eq!(pos.filename, a);
+    assert_eq!(pos.loc.start(), Point { line: 2, col: 3 });
+    assert_eq!(pos.loc.end(), Point { line: 2, col: 6 });
+
+    match files.expand(ByteSpan::new(id, 3, 3), &sources).unwrap().loc {
+        Location::Point { point } =>
+            assert_eq!(point, Point { line: 1, col: 4 }),
+        loc => panic!("expected a point, got {}", loc)
+    }
+
+    assert_eq!(files.expand(ByteSpan::new(id, 0, 99), &sources), None);
+    assert_eq!(files.expand(ByteSpan::new(7, 0, 1), &sources), None);
+}
@@ -0,0 +1,326 @@
+use salt::common::cache::DecodeError;
+use salt::common::cache::DecodeResult;
+use salt::common::cache::Decodable;
+use salt::common::cache::Decoder;
+use salt::common::cache::Encodable;
+use salt::common::cache::Encoder;
+use salt::common::filename::Filename;
+use salt::common::position::FilePosition;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use std::slice::Iter;
+
+/// A table of file positions with a compact encoding.  Each position
+/// is encoded as a delta from the one before it, so a table sorted by
+/// file and line (as position tables in the incremental cache and
+/// source maps usually are) takes a few bytes per span.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PositionTable<'a> {
+    positions: Vec<FilePosition<'a>>
+}
+
+/// The filename changed from the previous entry.
+const FILE_CHANGED: u64 = 1;
+/// The entry is a point.
+const KIND_POINT: u64 = 0;
+/// The entry is a span within a single line.
+const KIND_LINE_SPAN: u64 = 1;
+/// The entry is a span covering more than one line.
+const KIND_SPAN: u64 = 2;
+/// Line deltas below this are packed into the header.
+const SMALL_LINE_DELTA: i64 = 15;
+
+impl<'a> PositionTable<'a> {
+    /// Create an empty `PositionTable`.
+    pub fn new() -> PositionTable<'a> {
+        PositionTable { positions: Vec::new() }
+    }
+
+    /// Add `pos` to the table, returning its index.
+    pub fn push(&mut self, pos: FilePosition<'a>) -> usize {
+        self.positions.push(pos);
+
+        self.positions.len() - 1
+    }
+
+    /// Get the position at index `idx`.
+    pub fn get(&self, idx: usize) -> Option<&FilePosition<'a>> {
+        self.positions.get(idx)
+    }
+
+    /// Get the number of positions.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Check whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Get an iterator over the positions, in index order.
+    pub fn iter(&self) -> Iter<'_, FilePosition<'a>> {
+        self.positions.iter()
+    }
+}
+
+impl<'a> From<Vec<FilePosition<'a>>> for PositionTable<'a> {
+    fn from(positions: Vec<FilePosition<'a>>) -> PositionTable<'a> {
+        PositionTable { positions }
+    }
+}
+
+/// Signed difference between two line or column numbers.
+fn delta(to: u32, from: u32) -> i64 {
+    to as i64 - from as i64
+}
+
+/// Apply a delta from `read_int`, checking that the result is in range.
+fn apply(from: u32, delta: i64) -> DecodeResult<u32> {
+    let out = from as i64 + delta;
+
+    if out < 0 || out > u32::MAX as i64 {
+        Err(DecodeError::Overflow)
+    } else {
+        Ok(out as u32)
+    }
+}
+
+/// Write `positions` to `enc` compactly, each as a delta from the one
+/// before it.  This is the encoding of `PositionTable`.
+///
+/// Each position is encoded as a header (whether the file changed,
+/// the kind of location, and the line delta if it is small), the
+/// filename if it changed, then the start point as a line delta
+/// (unless it was in the header) and either a column delta (on the
+/// same line) or an absolute column, and finally the end of a span
+/// relative to its start.  A short span on a nearby line takes three
+/// bytes.  Filenames go in the encoder's string table, so each is
+/// written once and referred to by index after that.
+pub fn encode_stream(enc: &mut Encoder, positions: &[FilePosition]) {
+    let mut file = None;
+    let mut prev = Point { line: 0, col: 0 };
+
+    enc.emit_uint(positions.len() as u64);
+
+    for pos in positions {
+        let changed = file != Some(pos.filename);
+        let (start, kind) = match pos.loc {
+            Location::Point { point } => (point, KIND_POINT),
+            Location::Span { start, end } if start.line == end.line =>
+                (start, KIND_LINE_SPAN),
+            Location::Span { start, .. } => (start, KIND_SPAN)
+        };
+
+        if changed {
+            prev = Point { line: 0, col: 0 };
+        }
+
+        let line_delta = delta(start.line, prev.line);
+        let small = if (0..SMALL_LINE_DELTA).contains(&line_delta) {
+            line_delta as u64 + 1
+        } else {
+            0
+        };
+
+        enc.emit_uint(small << 3 | kind << 1 |
+                      if changed { FILE_CHANGED } else { 0 });
+
+        if changed {
+            pos.filename.encode(enc);
+            file = Some(pos.filename);
+        }
+
+        if small == 0 {
+            enc.emit_int(line_delta);
+        }
+
+        if start.line == prev.line {
+            enc.emit_int(delta(start.col, prev.col));
+        } else {
+            enc.emit_uint(start.col as u64);
+        }
+
+        match pos.loc {
+            Location::Span { end, .. } if kind == KIND_LINE_SPAN =>
+                enc.emit_int(delta(end.col, start.col)),
+            Location::Span { end, .. } => {
+                enc.emit_int(delta(end.line, start.line));
+                enc.emit_uint(end.col as u64);
+            },
+            Location::Point { .. } => ()
+        }
+
+        prev = start;
+    }
+}
+
+/// Read positions written by `encode_stream` from `dec`.
+pub fn decode_stream<'a>(dec: &mut Decoder<'a, '_>)
+                         -> DecodeResult<Vec<FilePosition<'a>>> {
+    let len = dec.read_uint()?;
+    let mut file: Option<Filename<'a>> = None;
+    let mut prev = Point { line: 0, col: 0 };
+    let mut positions = Vec::with_capacity((len as usize)
+                                           .min(dec.rest().len()));
+
+    for _ in 0..len {
+        let header = dec.read_uint()?;
+        let kind = header >> 1 & 3;
+        let small = header >> 3;
+
+        if header & FILE_CHANGED != 0 {
+            file = Some(Filename::decode(dec)?);
+            prev = Point { line: 0, col: 0 };
+        }
+
+        let filename = match file {
+            Some(filename) => filename,
+            None => return Err(DecodeError::BadTag { what: "FilePosition",
+                                                     tag: header })
+        };
+        let line_delta = if small == 0 {
+            dec.read_int()?
+        } else {
+            small as i64 - 1
+        };
+        let line = apply(prev.line, line_delta)?;
+        let col = if line == prev.line {
+            apply(prev.col, dec.read_int()?)?
+        } else {
+            u32::decode(dec)?
+        };
+        let start = Point { line, col };
+        let loc = match kind {
+            KIND_POINT => Location::Point { point: start },
+            KIND_LINE_SPAN => {
+                let col = apply(start.col, dec.read_int()?)?;
+
+                Location::Span { start, end: Point { line, col } }
+            },
+            KIND_SPAN => {
+                let line = apply(start.line, dec.read_int()?)?;
+                let col = u32::decode(dec)?;
+
+                Location::Span { start, end: Point { line, col } }
+            },
+            _ => return Err(DecodeError::BadTag { what: "FilePosition",
+                                                  tag: header })
+        };
+
+        positions.push(FilePosition { filename, loc });
+        prev = start;
+    }
+
+    Ok(positions)
+}
+
+impl<'a> Encodable for PositionTable<'a> {
+    fn encode(&self, enc: &mut Encoder) {
+        encode_stream(enc, &self.positions)
+    }
+}
+
+impl<'a> Decodable<'a> for PositionTable<'a> {
+    fn decode(dec: &mut Decoder<'a, '_>) -> DecodeResult<PositionTable<'a>> {
+        decode_stream(dec).map(PositionTable::from)
+    }
+}
+
+#[cfg(test)]
+fn span(filename: Filename, line: u32, col: u32, end_line: u32,
+        end_col: u32) -> FilePosition {
+    FilePosition { filename,
+                   loc: Location::Span { start: Point { line, col },
+                                         end: Point { line: end_line,
+                                                      col: end_col } } }
+}
+
+#[test]
+fn test_round_trip() {
+    use salt::common::cache::decode;
+    use salt::common::cache::encode;
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let table = PositionTable::from(vec![
+        span(a, 1, 5, 1, 9),
+        span(a, 1, 12, 1, 14),
+        FilePosition { filename: a,
+                       loc: Location::Point { point: Point { line: 3,
+                                                             col: 2 } } },
+        span(a, 4, 1, 9, 2),
+        span(a, 40, 3, 40, 3),
+        span(a, 2, 8, 2, 30),
+        span(b, 2, 7, 2, 8),
+        span(a, 1, 1, 1, 2)
+    ]);
+    let data = encode(&table);
+    let mut intern = StrIntern::new();
+    let decoded: PositionTable = decode(&data, &mut intern).unwrap();
+
+    assert_eq!(decoded.len(), table.len());
+
+    for (x, y) in decoded.iter().zip(table.iter()) {
+        assert_eq!(x.filename.to_string(), y.filename.to_string());
+        assert_eq!(x.loc.to_string(), y.loc.to_string());
+    }
+}
+
+#[test]
+fn test_density() {
+    use salt::common::cache::encode;
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let file = names.filename("big.salt");
+    let table = PositionTable::from((0..10000)
+        .map(|i| span(file, 1 + i / 4, 1 + (i % 4) * 10,
+                      1 + i / 4, 6 + (i % 4) * 10))
+        .collect::<Vec<_>>());
+    let compact = encode(&table);
+    let generic = encode(&table.positions);
+
+    assert!(compact.len() <= 3 * table.len() + 16);
+    assert!(compact.len() * 2 < generic.len());
+}
+
+#[test]
+fn test_stream() {
+    use salt::common::cache::encode;
+    use salt::common::filename::FilenameCtx;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let b = names.filename("b.salt");
+    let positions: Vec<_> = (0..100)
+        .map(|i| span(if i % 10 == 0 { b } else { a }, 1 + i, 1, 1 + i, 5))
+        .collect();
+    let mut enc = Encoder::new();
+
+    encode_stream(&mut enc, &positions);
+    enc.emit_uint(42);
+
+    let data = enc.finish();
+    let mut intern = StrIntern::new();
+    let mut dec = Decoder::new(&data, &mut intern).unwrap();
+    let decoded = decode_stream(&mut dec).unwrap();
+
+    assert_eq!(dec.read_uint(), Ok(42));
+    assert!(dec.is_empty());
+    assert_eq!(decoded.len(), positions.len());
+
+    for (x, y) in decoded.iter().zip(positions.iter()) {
+        assert_eq!(x.filename.to_string(), y.filename.to_string());
+        assert_eq!(x.loc, y.loc);
+    }
+
+    // Each filename is written once, however often the file changes.
+    assert_eq!(data.len(), encode(&PositionTable::from(positions)).len() + 1);
+    assert_eq!(data.iter().filter(|b| **b == b'b').count(), 1);
+}
@@ -0,0 +1,242 @@
+//! Progress reporting for long compilations.
+//!
+//! Drivers report progress through the session's `ProgressSink`: each
+//! phase is started, advanced as units of work (files, items, codegen
+//! units) are processed, and finished.  `PhaseProgress` does this
+//! bookkeeping for a phase, and can be shared between threads.  IDEs
+//! and CLIs install a sink with `Session::set_progress`; by default,
+//! progress goes nowhere.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// A receiver of progress reports.  Methods may be called from several
+/// threads at once.
+pub trait ProgressSink: Send + Sync {
+    /// The phase `phase` has started, and will process `total` units
+    /// of work, if that is known.
+    fn phase_started(&self, phase: &str, total: Option<u64>);
+
+    /// `done` units of work in `phase` have been processed so far.
+    fn progress(&self, phase: &str, done: u64, total: Option<u64>);
+
+    /// The phase `phase` has finished.
+    fn phase_finished(&self, phase: &str);
+}
+
+/// A `ProgressSink` that ignores all reports.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NoProgress;
+
+/// A `ProgressSink` that draws a progress bar on a terminal, redrawing
+/// a single line as work is done.
+pub struct TerminalProgress<W> {
+    state: Mutex<TerminalState<W>>,
+    width: usize
+}
+
+struct TerminalState<W> {
+    out: W,
+    /// The last line drawn, to avoid drawing the same line again.
+    last: String
+}
+
+/// Progress of a single phase, reported to a `ProgressSink`.  The
+/// phase is finished when this is dropped.
+pub struct PhaseProgress<'a> {
+    sink: &'a dyn ProgressSink,
+    phase: String,
+    total: Option<u64>,
+    done: AtomicU64
+}
+
+impl ProgressSink for NoProgress {
+    fn phase_started(&self, _phase: &str, _total: Option<u64>) {}
+
+    fn progress(&self, _phase: &str, _done: u64, _total: Option<u64>) {}
+
+    fn phase_finished(&self, _phase: &str) {}
+}
+
+impl<W: Write + Send> TerminalProgress<W> {
+    /// Create a `TerminalProgress` that writes to `out`, with a bar
+    /// `width` characters wide.
+    pub fn new(out: W, width: usize) -> TerminalProgress<W> {
+        TerminalProgress { state: Mutex::new(TerminalState {
+                               out, last: String::new()
+                           }), width }
+    }
+
+    /// Get the line to draw for `done` of `total` units in `phase`.
+    fn line(&self, phase: &str, done: u64, total: Option<u64>) -> String {
+        match total {
+            Some(total) if total > 0 => {
+                let filled = (done.min(total) as u128 * self.width as u128 /
+                              total as u128) as usize;
+
+                format!("{} [{}{}] {}/{}", phase, "=".repeat(filled),
+                        " ".repeat(self.width - filled), done, total)
+            }
+            _ => format!("{} {}", phase, done)
+        }
+    }
+
+    fn draw(&self, line: String, end: bool) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if line != state.last || end {
+            // Pad to erase any longer line drawn before.
+            let pad = state.last.len().saturating_sub(line.len());
+            let nl = if end { "\n" } else { "" };
+
+            // Progress is advisory, so errors writing it are ignored.
+            let _ = write!(state.out, "\r{}{}{}", line, " ".repeat(pad), nl);
+            let _ = state.out.flush();
+
+            state.last = if end { String::new() } else { line };
+        }
+    }
+
+    /// Get the output, once progress reporting is finished.
+    pub fn into_inner(self) -> W {
+        self.state.into_inner().unwrap_or_else(|e| e.into_inner()).out
+    }
+}
+
+impl<W: Write + Send> ProgressSink for TerminalProgress<W> {
+    fn phase_started(&self, phase: &str, total: Option<u64>) {
+        self.draw(self.line(phase, 0, total), false)
+    }
+
+    fn progress(&self, phase: &str, done: u64, total: Option<u64>) {
+        self.draw(self.line(phase, done, total), false)
+    }
+
+    fn phase_finished(&self, phase: &str) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let line = if state.last.starts_with(phase) {
+            state.last.clone()
+        } else {
+            phase.to_string()
+        };
+
+        drop(state);
+        self.draw(format!("{} done", line), true)
+    }
+}
+
+impl<'a> PhaseProgress<'a> {
+    /// Start the phase `phase` on `sink`, with `total` units of work,
+    /// if that is known.
+    pub fn start(sink: &'a dyn ProgressSink, phase: &str,
+                 total: Option<u64>) -> PhaseProgress<'a> {
+        sink.phase_started(phase, total);
+
+        PhaseProgress { sink, phase: phase.to_string(), total,
+                        done: AtomicU64::new(0) }
+    }
+
+    /// Record that `n` more units of work have been processed.
+    pub fn advance(&self, n: u64) {
+        let done = self.done.fetch_add(n, Ordering::Relaxed) + n;
+
+        self.sink.progress(&self.phase, done, self.total)
+    }
+
+    /// Get the number of units of work processed so far.
+    pub fn done(&self) -> u64 {
+        self.done.load(Ordering::Relaxed)
+    }
+}
+
+impl<'a> Drop for PhaseProgress<'a> {
+    fn drop(&mut self) {
+        self.sink.phase_finished(&self.phase)
+    }
+}
+
+impl Default for Box<dyn ProgressSink> {
+    fn default() -> Box<dyn ProgressSink> {
+        Box::new(NoProgress)
+    }
+}
+
+impl Debug for dyn ProgressSink {
+    fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
+        write!(f, "ProgressSink")
+    }
+}
+
+impl<W> Debug for TerminalProgress<W> {
+    fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
+        f.debug_struct("TerminalProgress")
+            .field("width", &self.width)
+            .finish()
+    }
+}
+
+#[test]
+fn test_terminal_progress() {
+    let term = TerminalProgress::new(Vec::new(), 4);
+
+    {
+        let phase = PhaseProgress::start(&term, "parse", Some(4));
+
+        phase.advance(1);
+        phase.advance(0);
+        phase.advance(3);
+        assert_eq!(phase.done(), 4);
+    }
+
+    {
+        let phase = PhaseProgress::start(&term, "link", None);
+
+        phase.advance(2);
+    }
+
+    let out = String::from_utf8(term.into_inner()).unwrap();
+
+    assert_eq!(out, "\rparse [    ] 0/4\
+                     \rparse [=   ] 1/4\
+                     \rparse [====] 4/4\
+                     \rparse [====] 4/4 done\n\
+                     \rlink 0\
+                     \rlink 2\
+                     \rlink 2 done\n");
+}
+
+#[test]
+fn test_session_progress() {
+    use salt::common::session::Session;
+    use std::sync::Arc;
+
+    struct Record(Arc<Mutex<Vec<String>>>);
+
+    impl ProgressSink for Record {
+        fn phase_started(&self, phase: &str, total: Option<u64>) {
+            self.0.lock().unwrap().push(format!("start {} {:?}", phase, total))
+        }
+
+        fn progress(&self, phase: &str, done: u64, _total: Option<u64>) {
+            self.0.lock().unwrap().push(format!("{} {}", phase, done))
+        }
+
+        fn phase_finished(&self, phase: &str) {
+            self.0.lock().unwrap().push(format!("finish {}", phase))
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut sess = Session::new();
+
+    sess.start_phase("ignored", None).advance(1);
+    sess.set_progress(Record(events.clone()));
+    sess.start_phase("load", Some(2)).advance(2);
+
+    assert_eq!(*events.lock().unwrap(),
+               ["start load Some(2)", "load 2", "finish load"]);
+}
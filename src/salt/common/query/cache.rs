@@ -0,0 +1,284 @@
+use salt::common::query::QueryStats;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// Hooks called as a `QueryCache` is used, so that a tool can track
+/// dependencies between cached values, as by recording in a
+/// `graph::DepGraph` that whatever it is computing read `key`.  Each
+/// hook does nothing unless overridden.
+pub trait CacheHooks<K> {
+    /// Called when the value for `key` is read, whether it was cached
+    /// or had to be computed.
+    fn on_read(&self, _key: &K) {}
+
+    /// Called before the value for `key` is computed.
+    fn on_compute(&self, _key: &K) {}
+
+    /// Called when the value for `key` is invalidated.
+    fn on_invalidate(&self, _key: &K) {}
+}
+
+/// No hooks.
+impl<K> CacheHooks<K> for () {}
+
+/// A memo table, for tools that want to cache results, such as parse
+/// results keyed by `Filename` and the fingerprint of the contents,
+/// without the revisions and dependency tracking of a `QueryEngine`.
+///
+/// Values are computed on first request, and cloned out on each
+/// request after.  The table is only borrowed while it is looked up,
+/// so computations may use other caches, or this one for other keys.
+/// A computation that requests its own key panics, as that would
+/// never finish.
+pub struct QueryCache<K, V, H = ()> {
+    values: RefCell<HashMap<K, V>>,
+    /// The keys whose values are being computed.
+    running: RefCell<HashSet<K>>,
+    hooks: H,
+    stats: Cell<QueryStats>
+}
+
+/// Removes a key from the running set when its computation finishes
+/// or unwinds.
+struct RunningGuard<'a, K: Eq + Hash> {
+    running: &'a RefCell<HashSet<K>>,
+    key: &'a K
+}
+
+impl<'a, K: Eq + Hash> Drop for RunningGuard<'a, K> {
+    fn drop(&mut self) {
+        self.running.borrow_mut().remove(self.key);
+    }
+}
+
+impl<K, V> QueryCache<K, V>
+    where K: Clone + Debug + Eq + Hash,
+          V: Clone {
+    /// Create an empty `QueryCache` with no hooks.
+    pub fn new() -> QueryCache<K, V> {
+        QueryCache::with_hooks(())
+    }
+}
+
+impl<K, V, H> QueryCache<K, V, H>
+    where K: Clone + Debug + Eq + Hash,
+          V: Clone,
+          H: CacheHooks<K> {
+    /// Create an empty `QueryCache` calling `hooks`.
+    pub fn with_hooks(hooks: H) -> QueryCache<K, V, H> {
+        QueryCache { values: RefCell::new(HashMap::new()),
+                     running: RefCell::new(HashSet::new()), hooks,
+                     stats: Cell::new(QueryStats::default()) }
+    }
+
+    /// Get the hooks.
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    /// Get the value for `key`, computing it with `compute` if it is
+    /// not cached.  Panics if `compute` requests `key` itself.
+    pub fn get_or_compute<F>(&self, key: &K, compute: F) -> V
+        where F: FnOnce(&K) -> V {
+        self.hooks.on_read(key);
+
+        if let Some(value) = self.values.borrow().get(key) {
+            self.count(|stats| stats.hits += 1);
+
+            return value.clone();
+        }
+
+        if !self.running.borrow_mut().insert(key.clone()) {
+            panic!("cycle detected when computing {:?}", key)
+        }
+
+        let value = {
+            let _guard = RunningGuard { running: &self.running, key };
+
+            self.hooks.on_compute(key);
+            self.count(|stats| stats.misses += 1);
+
+            compute(key)
+        };
+
+        self.values.borrow_mut().insert(key.clone(), value.clone());
+
+        value
+    }
+
+    /// Get the value for `key`, if it is cached.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let value = self.values.borrow().get(key).cloned();
+
+        if value.is_some() {
+            self.hooks.on_read(key);
+            self.count(|stats| stats.hits += 1);
+        }
+
+        value
+    }
+
+    /// Cache `value` as the value for `key`, as for a value computed
+    /// elsewhere, returning the value cached before.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.values.borrow_mut().insert(key, value)
+    }
+
+    /// Forget the value for `key`, so it is computed again when next
+    /// requested.  Returns whether it was cached.
+    pub fn invalidate(&self, key: &K) -> bool {
+        let cached = self.values.borrow_mut().remove(key).is_some();
+
+        if cached {
+            self.hooks.on_invalidate(key);
+        }
+
+        cached
+    }
+
+    /// Forget the values for the keys satisfying `stale`, such as those
+    /// for an older version of a file.  Returns how many there were.
+    pub fn invalidate_where<F>(&self, mut stale: F) -> usize
+        where F: FnMut(&K) -> bool {
+        let keys: Vec<K> = self.values.borrow()
+            .keys()
+            .filter(|key| stale(key))
+            .cloned()
+            .collect();
+
+        for key in &keys {
+            self.invalidate(key);
+        }
+
+        keys.len()
+    }
+
+    /// Forget every value.
+    pub fn clear(&self) {
+        self.invalidate_where(|_| true);
+    }
+
+    /// Get the number of values cached.
+    pub fn len(&self) -> usize {
+        self.values.borrow().len()
+    }
+
+    /// Check whether no values are cached.
+    pub fn is_empty(&self) -> bool {
+        self.values.borrow().is_empty()
+    }
+
+    /// Get the hit and miss counts.  There are no revisions, so no
+    /// results are ever green.
+    pub fn stats(&self) -> QueryStats {
+        self.stats.get()
+    }
+
+    /// Update the hit and miss counts with `update`.
+    fn count<F: FnOnce(&mut QueryStats)>(&self, update: F) {
+        let mut stats = self.stats.get();
+
+        update(&mut stats);
+        self.stats.set(stats)
+    }
+}
+
+impl<K, V> Default for QueryCache<K, V>
+    where K: Clone + Debug + Eq + Hash,
+          V: Clone {
+    fn default() -> QueryCache<K, V> {
+        QueryCache::new()
+    }
+}
+
+#[test]
+fn test_query_cache() {
+    use salt::common::filename::Filename;
+    use salt::common::filename::FilenameCtx;
+    use salt::common::hash::Fingerprint;
+    use salt::common::hash::StableHash;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let a = names.filename("a.salt");
+    let parses = Cell::new(0);
+    let parse = |key: &(Filename, Fingerprint)| {
+        parses.set(parses.get() + 1);
+
+        key.0.to_string().len()
+    };
+    let cache = QueryCache::new();
+    let v1 = (a, "let x = 1;".fingerprint());
+    let v2 = (a, "let x = 2;".fingerprint());
+
+    assert_eq!(cache.get_or_compute(&v1, parse), 6);
+    assert_eq!(cache.get_or_compute(&v1, parse), 6);
+    assert_eq!(cache.get_or_compute(&v2, parse), 6);
+    assert_eq!(parses.get(), 2);
+    assert_eq!(cache.stats(), QueryStats { hits: 1, green: 0, misses: 2 });
+    assert_eq!(cache.invalidate_where(|&(_, print)| print == v1.1), 1);
+    assert_eq!(cache.get(&v1), None);
+    assert_eq!(cache.get(&v2), Some(6));
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn test_query_cache_hooks() {
+    use salt::common::graph::DepGraph;
+
+    /// Records that the key being computed depends on each key read
+    /// while computing it.
+    #[derive(Default)]
+    struct Tracker {
+        graph: RefCell<DepGraph<'static, u32>>,
+        active: RefCell<Vec<u32>>
+    }
+
+    impl CacheHooks<u32> for Tracker {
+        fn on_read(&self, key: &u32) {
+            if let Some(&parent) = self.active.borrow().last() {
+                self.graph.borrow_mut().add_edge(parent, *key)
+            }
+        }
+    }
+
+    fn fib(cache: &QueryCache<u32, u64, Tracker>, n: u32) -> u64 {
+        cache.get_or_compute(&n, |&n| {
+            if n < 2 {
+                return n as u64;
+            }
+
+            cache.hooks().active.borrow_mut().push(n);
+
+            let value = fib(cache, n - 1) + fib(cache, n - 2);
+
+            cache.hooks().active.borrow_mut().pop();
+
+            value
+        })
+    }
+
+    let cache = QueryCache::with_hooks(Tracker::default());
+
+    assert_eq!(fib(&cache, 20), 6765);
+    assert_eq!(cache.stats().misses, 21);
+
+    let graph = cache.hooks().graph.borrow();
+
+    assert_eq!(graph.deps(&5).collect::<Vec<_>>(), [&4, &3]);
+    assert_eq!(graph.topo_order().unwrap().first(), Some(&&1));
+}
+
+#[test]
+#[should_panic(expected = "cycle detected when computing 0")]
+fn test_query_cache_cycle() {
+    fn get(cache: &QueryCache<u32, u32>, n: u32) -> u32 {
+        cache.get_or_compute(&n, |&n| get(cache, 1 - n))
+    }
+
+    get(&QueryCache::new(), 0);
+}
@@ -0,0 +1,143 @@
+use salt::common::message::Message;
+use salt::common::position::FilePosition;
+use salt::common::position::PositionTable;
+use salt::common::query::DepNode;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+
+/// One query in a cycle.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CycleFrame {
+    /// The query result being computed.
+    pub node: DepNode,
+    /// A description of the query and key, such as `type_of(Foo)`.
+    pub label: String,
+    /// The index, in the session's `PositionTable`, of the source
+    /// position that caused the query to be computed, if the query can
+    /// describe one.
+    pub pos: Option<usize>
+}
+
+/// A cycle between queries.  The first frame is the query that was
+/// requested again while it was still being computed; each frame
+/// requires the next, and the last requires the first.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QueryCycle {
+    /// The queries making up the cycle.
+    pub frames: Vec<CycleFrame>
+}
+
+impl QueryCycle {
+    /// Get the names of the queries in the cycle, in order.
+    pub fn queries(&self) -> Vec<&'static str> {
+        self.frames.iter().map(|frame| frame.node.query).collect()
+    }
+
+    /// Convert the cycle into an error message, for rendering with the
+    /// other diagnostics.  The position of each frame that has one is
+    /// looked up in `positions`, the session's table, and labelled with
+    /// the query computed there, and the chain of queries follows as
+    /// notes.
+    pub fn to_message<'a>(&self, positions: &PositionTable<'a>)
+                          -> Message<FilePosition<'a>> {
+        let first = match self.frames.first() {
+            Some(first) => first,
+            None => return Message::error("cycle detected")
+        };
+        let mut msg = Message::error(format!("cycle detected when \
+                                              computing `{}`",
+                                             first.label));
+
+        for frame in &self.frames {
+            if let Some(pos) = frame.pos.and_then(|idx| positions.get(idx)) {
+                let label = format!("computing `{}`", frame.label);
+
+                msg = msg.with_label(pos.clone(), label);
+            }
+        }
+
+        for frame in &self.frames[1..] {
+            msg = msg.with_note(format!("...which requires computing `{}`",
+                                        frame.label));
+        }
+
+        msg.with_note(format!("...which again requires computing `{}`, \
+                               completing the cycle", first.label))
+    }
+}
+
+/// Positions are only known by index, so they are not shown; use
+/// `QueryCycle::to_message` to show them.
+impl Display for CycleFrame {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "`{}`", self.label)
+    }
+}
+
+impl Display for QueryCycle {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let mut frames = self.frames.iter();
+
+        if let Some(first) = frames.next() {
+            write!(f, "cycle detected when computing {}", first)?;
+
+            for frame in frames {
+                write!(f, "\n  ...which requires computing {}", frame)?;
+            }
+
+            write!(f, "\n  ...which again requires computing `{}`, \
+                       completing the cycle", first.label)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn test_cycle() -> QueryCycle {
+    QueryCycle { frames: vec![
+        CycleFrame { node: DepNode::new("type_of", &1u32),
+                     label: String::from("type_of(Foo)"), pos: Some(0) },
+        CycleFrame { node: DepNode::new("type_of", &2u32),
+                     label: String::from("type_of(Bar)"), pos: None }
+    ] }
+}
+
+#[test]
+fn test_display() {
+    let cycle = test_cycle();
+
+    assert_eq!(cycle.to_string(),
+               "cycle detected when computing `type_of(Foo)`\n  \
+                ...which requires computing `type_of(Bar)`\n  \
+                ...which again requires computing `type_of(Foo)`, \
+                completing the cycle");
+    assert_eq!(cycle.queries(), vec!["type_of", "type_of"]);
+}
+
+#[test]
+fn test_to_message() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::Location;
+    use salt::common::str::intern::StrIntern;
+
+    // The filename borrows from a local, not from anything `'static`.
+    let name = String::from("a.salt");
+    let filename = StrIntern::new().filename(&name);
+    let mut positions = PositionTable::new();
+
+    positions.push(FilePosition::new(filename,
+                                     Location::span((1, 6), (1, 9))));
+
+    let msg = test_cycle().to_message(&positions);
+
+    assert!(msg.is_error());
+    assert_eq!(msg.headline, "cycle detected when computing `type_of(Foo)`");
+    assert_eq!(msg.primary().map(|pos| pos.to_string()),
+               Some(String::from("a.salt 1.6-9")));
+    assert_eq!(msg.labels, [Some(String::from("computing `type_of(Foo)`"))]);
+    assert_eq!(msg.notes, ["...which requires computing `type_of(Bar)`",
+                           "...which again requires computing \
+                            `type_of(Foo)`, completing the cycle"]);
+}
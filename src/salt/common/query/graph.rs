@@ -0,0 +1,194 @@
+use salt::common::hash::Fingerprint;
+use salt::common::hash::StableHash;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::fmt::Write;
+
+/// Identifies one query result: the query and the fingerprint of
+/// its key.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DepNode {
+    /// The name of the query.
+    pub query: &'static str,
+    /// The fingerprint of the key.
+    pub key: Fingerprint
+}
+
+/// The dependency graph between query results.  An edge from `a` to
+/// `b` records that computing `a` read `b`.
+#[derive(Clone, Debug, Default)]
+pub struct DepGraph {
+    /// Labels for nodes, for dumps.
+    labels: HashMap<DepNode, String>,
+    /// Edges from each node to the nodes it read, in the order they
+    /// were first read.
+    deps: HashMap<DepNode, Vec<DepNode>>,
+    /// Edges from each node to the nodes that read it.
+    rdeps: HashMap<DepNode, BTreeSet<DepNode>>
+}
+
+impl DepNode {
+    /// Create the node for query `query` with key `key`.
+    pub fn new<K: StableHash>(query: &'static str, key: &K) -> DepNode {
+        DepNode { query, key: key.fingerprint() }
+    }
+}
+
+impl DepGraph {
+    /// Create an empty `DepGraph`.
+    pub fn new() -> DepGraph {
+        DepGraph { labels: HashMap::new(), deps: HashMap::new(),
+                   rdeps: HashMap::new() }
+    }
+
+    /// Add a node, with a label built from its key.
+    pub fn add_node<K: Debug>(&mut self, node: DepNode, key: &K) {
+        self.labels.entry(node)
+            .or_insert_with(|| format!("{}({:?})", node.query, key));
+    }
+
+    /// Record that `from` read `to`.
+    pub fn add_edge(&mut self, from: DepNode, to: DepNode) {
+        let deps = self.deps.entry(from).or_default();
+
+        if !deps.contains(&to) {
+            deps.push(to);
+        }
+
+        self.rdeps.entry(to).or_default().insert(from);
+    }
+
+    /// Remove all edges out of `node`, in preparation for recomputing it.
+    pub fn clear_deps(&mut self, node: &DepNode) {
+        if let Some(deps) = self.deps.remove(node) {
+            for dep in deps {
+                if let Some(rdeps) = self.rdeps.get_mut(&dep) {
+                    rdeps.remove(node);
+                }
+            }
+        }
+    }
+
+    /// Get the nodes read by `node`, in the order they were first read.
+    pub fn deps(&self, node: &DepNode) -> impl Iterator<Item = &DepNode> {
+        self.deps.get(node).into_iter().flat_map(|deps| deps.iter())
+    }
+
+    /// Get the nodes that read `node`.
+    pub fn rdeps(&self, node: &DepNode) -> impl Iterator<Item = &DepNode> {
+        self.rdeps.get(node).into_iter().flat_map(|rdeps| rdeps.iter())
+    }
+
+    /// Get every node that transitively depends on `node`, not
+    /// including `node` itself.
+    pub fn dependents(&self, node: &DepNode) -> HashSet<DepNode> {
+        let mut out = HashSet::new();
+        let mut work = vec![*node];
+
+        while let Some(next) = work.pop() {
+            for rdep in self.rdeps(&next) {
+                if out.insert(*rdep) {
+                    work.push(*rdep);
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Get the label of `node`.
+    pub fn label(&self, node: &DepNode) -> Option<&str> {
+        self.labels.get(node).map(|s| s.as_str())
+    }
+
+    /// Get the number of nodes.
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Check whether the graph is empty.
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Render the graph in Graphviz DOT format.  Nodes are listed in
+    /// label order so the output is stable.
+    pub fn to_dot(&self) -> String {
+        let mut nodes: Vec<(&str, &DepNode)> = self.labels.iter()
+            .map(|(node, label)| (label.as_str(), node))
+            .collect();
+
+        nodes.sort();
+
+        let ids: HashMap<&DepNode, usize> = nodes.iter()
+            .enumerate()
+            .map(|(i, &(_, node))| (node, i))
+            .collect();
+        let mut out = String::from("digraph queries {\n");
+
+        for (i, &(label, _)) in nodes.iter().enumerate() {
+            let _ = writeln!(out, "    n{} [label={:?}];", i, label);
+        }
+
+        for &(_, node) in &nodes {
+            for dep in self.deps(node) {
+                if let Some(to) = ids.get(dep) {
+                    let _ = writeln!(out, "    n{} -> n{};", ids[node], to);
+                }
+            }
+        }
+
+        out.push_str("}\n");
+
+        out
+    }
+}
+
+impl Display for DepNode {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}({})", self.query, self.key)
+    }
+}
+
+#[test]
+fn test_dependents() {
+    let mut graph = DepGraph::new();
+    let a = DepNode::new("a", &0u32);
+    let b = DepNode::new("b", &0u32);
+    let c = DepNode::new("c", &0u32);
+    let d = DepNode::new("d", &0u32);
+
+    graph.add_edge(a, b);
+    graph.add_edge(b, c);
+    graph.add_edge(d, c);
+
+    let deps = graph.dependents(&c);
+
+    assert_eq!(deps.len(), 3);
+    assert!(deps.contains(&a) && deps.contains(&b) && deps.contains(&d));
+
+    graph.clear_deps(&b);
+
+    assert_eq!(graph.dependents(&c).len(), 1);
+}
+
+#[test]
+fn test_dot() {
+    let mut graph = DepGraph::new();
+    let a = DepNode::new("a", &1u32);
+    let b = DepNode::new("b", &2u32);
+
+    graph.add_node(a, &1u32);
+    graph.add_node(b, &2u32);
+    graph.add_edge(a, b);
+
+    assert_eq!(graph.to_dot(), "digraph queries {\n    \
+                                n0 [label=\"a(1)\"];\n    \
+                                n1 [label=\"b(2)\"];\n    \
+                                n0 -> n1;\n}\n");
+}
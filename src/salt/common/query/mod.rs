@@ -0,0 +1,624 @@
+//! Memoized queries over a `Session`.
+//!
+//! A query is a pure function from a key to a value, computed with
+//! access to the `Session`.  Queries may call other queries.  Results
+//! are memoized by the session's `QueryEngine`, so asking the same
+//! question twice does not repeat the work.
+//!
+//! Some queries are inputs: their values are supplied with `set`
+//! rather than computed (for example, the contents of a source file).
+//! While a query runs, every query it reads is recorded in a `DepGraph`.
+//!
+//! Invalidation uses red-green marking.  Setting an input starts a new
+//! revision.  A result from an earlier revision is revalidated by
+//! checking, in the order they were read, whether any of its
+//! dependencies changed since it was last verified; if none did, it is
+//! marked green and reused without recomputing.  A result that is
+//! recomputed to the same fingerprint as before keeps its old change
+//! revision, so its own dependents stay green.
+//!
+//! A query that requires itself, directly or through other queries,
+//! is reported as a `QueryCycle`.  Queries that can recover from a
+//! cycle (for example, by producing an error type) do so through
+//! `Query::recover`; otherwise the cycle is raised as a panic whose
+//! payload is the `QueryCycle`.
+
+use salt::common::hash::Fingerprint;
+use salt::common::hash::StableHash;
+use salt::common::metrics::Counter;
+use salt::common::session::Session;
+use std::any::Any;
+use std::any::TypeId;
+use std::cell::Cell;
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::hash::Hash;
+use std::panic::panic_any;
+use std::rc::Rc;
+
+pub use self::cache::CacheHooks;
+pub use self::cache::QueryCache;
+pub use self::cycle::CycleFrame;
+pub use self::cycle::QueryCycle;
+pub use self::graph::DepGraph;
+pub use self::graph::DepNode;
+
+mod cache;
+mod cycle;
+mod graph;
+
+/// Process-wide counts of query results, for `metrics`.  These add up
+/// the `QueryStats` of every engine.
+static QUERY_HITS: Counter = Counter::new("query.hits");
+static QUERY_GREEN: Counter = Counter::new("query.green");
+static QUERY_MISSES: Counter = Counter::new("query.misses");
+
+/// A memoizable computation.
+pub trait Query: 'static {
+    /// The type of keys.
+    type Key: Clone + Debug + Eq + Hash + StableHash + 'static;
+    /// The type of results.  Results are fingerprinted to detect when
+    /// recomputing a query did not change its value.
+    type Value: Clone + StableHash + 'static;
+
+    /// The name of the query, for use in messages and dumps.
+    const NAME: &'static str;
+
+    /// Compute the value for `key`.  This must be a pure function of
+    /// `key` and the results of other queries.  Input queries are
+    /// never computed, and should panic.
+    fn compute(sess: &Session, key: &Self::Key) -> Self::Value;
+
+    /// Get the index, in the session's `PositionTable`, of the source
+    /// position that caused `key` to be computed, for use in cycle
+    /// diagnostics.  Keys are `'static`, while filenames borrow from
+    /// the session's arena, so positions are referred to by index, and
+    /// looked up by `QueryCycle::to_message`.
+    fn position(_sess: &Session, _key: &Self::Key) -> Option<usize> {
+        None
+    }
+
+    /// Produce a value for `key` when it is found to depend on itself.
+    /// Returning `None` raises the cycle as a panic.  Recovered values
+    /// are not memoized.
+    fn recover(_sess: &Session, _key: &Self::Key,
+               _cycle: &QueryCycle) -> Option<Self::Value> {
+        None
+    }
+}
+
+/// A memoized result.
+struct Memo<V> {
+    /// The memoized value.
+    value: V,
+    /// The fingerprint of `value`.
+    fingerprint: Fingerprint,
+    /// The revision in which the value was last known to be valid.
+    verified_at: u64,
+    /// The revision in which the value last changed.
+    changed_at: u64,
+    /// Whether this is an input value, supplied by `set`.
+    input: bool
+}
+
+/// Memoized results for one query.
+struct QueryStorage<Q: Query> {
+    memos: RefCell<HashMap<Q::Key, Memo<Q::Value>>>
+}
+
+/// Gets the index of the position that caused a result to be computed.
+type DescribePosition = Rc<dyn Fn(&Session) -> Option<usize>>;
+
+/// Operations on a node in the graph that do not need its query type.
+struct NodeOps {
+    /// Bring the result up to date, and return the revision in which
+    /// it last changed.
+    revalidate: Rc<dyn Fn(&Session) -> u64>,
+    /// Describe the position that caused the result to be computed.
+    position: DescribePosition
+}
+
+/// Hit and miss counts for memoized queries.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct QueryStats {
+    /// Requests answered from a result verified in this revision.
+    pub hits: u64,
+    /// Requests answered by marking an older result green, because
+    /// none of its dependencies changed.
+    pub green: u64,
+    /// Requests that required computing a result.
+    pub misses: u64
+}
+
+/// Storage and bookkeeping for all queries in a session.
+#[derive(Default)]
+pub struct QueryEngine {
+    /// Storage for each query, keyed by the query's type.
+    storages: RefCell<HashMap<TypeId, Rc<dyn Any>>>,
+    /// The current revision.
+    revision: Cell<u64>,
+    /// Hit and miss counters.
+    stats: Cell<QueryStats>,
+    /// The queries currently being computed, innermost last.
+    active: RefCell<Vec<DepNode>>,
+    /// Dependencies recorded between query results.
+    graph: RefCell<DepGraph>,
+    /// Type-erased operations on each node in the graph.
+    nodes: RefCell<HashMap<DepNode, NodeOps>>,
+    /// Cycles detected so far.
+    cycles: RefCell<Vec<QueryCycle>>
+}
+
+/// Pops the active query when a computation finishes or unwinds.
+struct ActiveGuard<'a>(&'a RefCell<Vec<DepNode>>);
+
+impl<'a> Drop for ActiveGuard<'a> {
+    fn drop(&mut self) {
+        self.0.borrow_mut().pop();
+    }
+}
+
+impl<Q: Query> QueryStorage<Q> {
+    fn new() -> QueryStorage<Q> {
+        QueryStorage { memos: RefCell::new(HashMap::new()) }
+    }
+}
+
+impl QueryEngine {
+    /// Create an empty `QueryEngine`.
+    pub fn new() -> QueryEngine {
+        QueryEngine { storages: RefCell::new(HashMap::new()),
+                      revision: Cell::new(0),
+                      stats: Cell::new(QueryStats::default()),
+                      active: RefCell::new(Vec::new()),
+                      graph: RefCell::new(DepGraph::new()),
+                      nodes: RefCell::new(HashMap::new()),
+                      cycles: RefCell::new(Vec::new()) }
+    }
+
+    /// Get the storage for `Q`, creating it if necessary.  The storage
+    /// map is not borrowed once this returns, so queries can recurse.
+    fn storage<Q: Query>(&self) -> Rc<QueryStorage<Q>> {
+        let any = self.storages.borrow_mut()
+            .entry(TypeId::of::<Q>())
+            .or_insert_with(|| Rc::new(QueryStorage::<Q>::new()))
+            .clone();
+
+        any.downcast::<QueryStorage<Q>>()
+            .unwrap_or_else(|_| panic!("query storage type mismatch"))
+    }
+
+    /// Get the current revision.
+    pub fn revision(&self) -> u64 {
+        self.revision.get()
+    }
+
+    /// Get the hit and miss counts.
+    pub fn stats(&self) -> QueryStats {
+        self.stats.get()
+    }
+
+    /// Get the dependency graph recorded so far.
+    pub fn graph(&self) -> Ref<'_, DepGraph> {
+        self.graph.borrow()
+    }
+
+    /// Render the dependency graph in Graphviz DOT format.
+    pub fn dump_dot(&self) -> String {
+        self.graph.borrow().to_dot()
+    }
+
+    /// Get the cycles detected so far, including those that were
+    /// recovered from.
+    pub fn cycles(&self) -> Vec<QueryCycle> {
+        self.cycles.borrow().clone()
+    }
+
+    /// Add `node` to the graph, and record how to operate on it.
+    fn register<Q: Query>(&self, node: DepNode, key: &Q::Key) {
+        self.graph.borrow_mut().add_node(node, key);
+        self.nodes.borrow_mut().entry(node).or_insert_with(|| {
+            let revalidate_key = key.clone();
+            let position_key = key.clone();
+
+            NodeOps {
+                revalidate: Rc::new(move |sess: &Session| {
+                    sess.queries().fetch::<Q>(sess, &revalidate_key).1
+                }),
+                position: Rc::new(move |sess: &Session| {
+                    Q::position(sess, &position_key)
+                })
+            }
+        });
+    }
+
+    /// Build the cycle formed by requesting `node` again, if it is
+    /// still being computed.
+    fn find_cycle(&self, sess: &Session, node: &DepNode) -> Option<QueryCycle> {
+        let active = self.active.borrow().clone();
+        let start = active.iter().position(|n| n == node)?;
+        let frames = active[start..].iter()
+            .map(|node| {
+                let label = self.graph.borrow().label(node)
+                    .map(|label| label.to_string())
+                    .unwrap_or_else(|| node.to_string());
+                let position = self.nodes.borrow().get(node)
+                    .map(|ops| ops.position.clone());
+
+                CycleFrame { node: *node, label,
+                             pos: position.and_then(|position| position(sess)) }
+            })
+            .collect();
+
+        Some(QueryCycle { frames })
+    }
+
+    /// Check whether every dependency of `node` is unchanged since
+    /// `verified_at`, bringing each up to date in turn.  Dependencies
+    /// are checked in the order they were read, and checking stops at
+    /// the first change, so a dependency that a recomputation would no
+    /// longer read is never brought up to date.
+    fn deps_unchanged(&self, sess: &Session, node: &DepNode,
+                      verified_at: u64) -> bool {
+        let deps: Vec<DepNode> = self.graph.borrow().deps(node)
+            .cloned()
+            .collect();
+
+        deps.iter().all(|dep| {
+            let revalidate = self.nodes.borrow().get(dep)
+                .map(|ops| ops.revalidate.clone());
+
+            match revalidate {
+                Some(revalidate) => revalidate(sess) <= verified_at,
+                None => false
+            }
+        })
+    }
+
+    /// Get the value of `Q` for `key`, and the revision in which it
+    /// last changed.  This does not record a dependency.
+    fn fetch<Q: Query>(&self, sess: &Session,
+                       key: &Q::Key) -> (Q::Value, u64) {
+        let node = DepNode::new(Q::NAME, key);
+
+        self.register::<Q>(node, key);
+
+        if let Some(cycle) = self.find_cycle(sess, &node) {
+            self.cycles.borrow_mut().push(cycle.clone());
+
+            match Q::recover(sess, key, &cycle) {
+                Some(value) => return (value, self.revision.get()),
+                None => panic_any(cycle)
+            }
+        }
+
+        let storage = self.storage::<Q>();
+        let revision = self.revision.get();
+        let memoized = storage.memos.borrow().get(key)
+            .map(|memo| (memo.input || memo.verified_at == revision,
+                         memo.verified_at));
+
+        match memoized {
+            Some((true, _)) => {
+                let mut stats = self.stats.get();
+
+                stats.hits += 1;
+                self.stats.set(stats);
+                QUERY_HITS.incr();
+
+                let memos = storage.memos.borrow();
+                let memo = &memos[key];
+
+                return (memo.value.clone(), memo.changed_at);
+            },
+            Some((false, verified_at))
+                if self.deps_unchanged(sess, &node, verified_at) => {
+                let mut stats = self.stats.get();
+
+                stats.green += 1;
+                self.stats.set(stats);
+                QUERY_GREEN.incr();
+
+                let mut memos = storage.memos.borrow_mut();
+                let memo = memos.get_mut(key)
+                    .expect("memo removed during revalidation");
+
+                memo.verified_at = revision;
+
+                return (memo.value.clone(), memo.changed_at);
+            },
+            _ => ()
+        }
+
+        let mut stats = self.stats.get();
+
+        stats.misses += 1;
+        self.stats.set(stats);
+        QUERY_MISSES.incr();
+        self.graph.borrow_mut().clear_deps(&node);
+
+        let value = {
+            self.active.borrow_mut().push(node);
+
+            let _guard = ActiveGuard(&self.active);
+
+            Q::compute(sess, key)
+        };
+        let fingerprint = value.fingerprint();
+        let mut memos = storage.memos.borrow_mut();
+        let changed_at = match memos.get(key) {
+            Some(old) if old.fingerprint == fingerprint => old.changed_at,
+            _ => revision
+        };
+
+        memos.insert(key.clone(), Memo {
+            value: value.clone(), fingerprint, verified_at: revision,
+            changed_at, input: false
+        });
+
+        (value, changed_at)
+    }
+
+    /// Get the value of `Q` for `key`, computing it if there is no
+    /// memoized result, or if a dependency of the memoized result has
+    /// changed.  If another query is running, this records that it
+    /// read `Q`.
+    pub fn get<Q: Query>(&self, sess: &Session, key: &Q::Key) -> Q::Value {
+        let parent = self.active.borrow().last().cloned();
+
+        if let Some(parent) = parent {
+            self.graph.borrow_mut()
+                .add_edge(parent, DepNode::new(Q::NAME, key));
+        }
+
+        self.fetch::<Q>(sess, key).0
+    }
+
+    /// Set the value of the input query `Q` for `key`, starting a new
+    /// revision.  If the new value has the same fingerprint as the old
+    /// one, the input is not considered changed, and results that read
+    /// it will be marked green.
+    pub fn set<Q: Query>(&self, key: Q::Key, value: Q::Value) {
+        let revision = self.revision.get() + 1;
+        let node = DepNode::new(Q::NAME, &key);
+        let fingerprint = value.fingerprint();
+        let storage = self.storage::<Q>();
+        let mut memos = storage.memos.borrow_mut();
+        let changed_at = match memos.get(&key) {
+            Some(old) if old.fingerprint == fingerprint => old.changed_at,
+            _ => revision
+        };
+
+        self.revision.set(revision);
+        self.register::<Q>(node, &key);
+        memos.insert(key, Memo {
+            value, fingerprint, verified_at: revision, changed_at,
+            input: true
+        });
+    }
+
+    /// Discard all memoized results for `Q`, including inputs.
+    pub fn clear<Q: Query>(&self) {
+        self.storage::<Q>().memos.borrow_mut().clear()
+    }
+}
+
+impl Debug for QueryEngine {
+    fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
+        f.debug_struct("QueryEngine")
+            .field("queries", &self.storages.borrow().len())
+            .field("revision", &self.revision.get())
+            .field("stats", &self.stats.get())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+struct TestText;
+
+#[cfg(test)]
+impl Query for TestText {
+    type Key = u32;
+    type Value = String;
+
+    const NAME: &'static str = "text";
+
+    fn compute(_sess: &Session, key: &u32) -> String {
+        panic!("input {} not set", key)
+    }
+}
+
+#[cfg(test)]
+struct TestWords;
+
+#[cfg(test)]
+impl Query for TestWords {
+    type Key = u32;
+    type Value = usize;
+
+    const NAME: &'static str = "words";
+
+    fn compute(sess: &Session, key: &u32) -> usize {
+        sess.query::<TestText>(key).split_whitespace().count()
+    }
+}
+
+#[cfg(test)]
+struct TestTotal;
+
+#[cfg(test)]
+impl Query for TestTotal {
+    type Key = Vec<u32>;
+    type Value = usize;
+
+    const NAME: &'static str = "total";
+
+    fn compute(sess: &Session, key: &Vec<u32>) -> usize {
+        key.iter().map(|k| sess.query::<TestWords>(k)).sum()
+    }
+}
+
+#[test]
+fn test_memoized() {
+    let sess = Session::new();
+
+    sess.set_input::<TestText>(1, String::from("a b c"));
+    sess.set_input::<TestText>(2, String::from("d e"));
+
+    assert_eq!(sess.query::<TestTotal>(&vec![1, 2]), 5);
+
+    let before = sess.queries().stats();
+
+    assert_eq!(sess.query::<TestTotal>(&vec![1, 2]), 5);
+    assert_eq!(sess.queries().stats().misses, before.misses);
+    assert_eq!(sess.queries().stats().hits, before.hits + 1);
+}
+
+#[test]
+fn test_new_revision() {
+    let sess = Session::new();
+
+    sess.set_input::<TestText>(1, String::from("a b c"));
+    assert_eq!(sess.query::<TestWords>(&1), 3);
+
+    sess.set_input::<TestText>(1, String::from("a"));
+    assert_eq!(sess.query::<TestWords>(&1), 1);
+    assert_eq!(sess.queries().revision(), 2);
+}
+
+#[test]
+fn test_precise_invalidation() {
+    let sess = Session::new();
+
+    sess.set_input::<TestText>(1, String::from("a b c"));
+    sess.set_input::<TestText>(2, String::from("d e"));
+    assert_eq!(sess.query::<TestWords>(&1), 3);
+    assert_eq!(sess.query::<TestWords>(&2), 2);
+
+    sess.set_input::<TestText>(2, String::from("d"));
+
+    let before = sess.queries().stats();
+
+    assert_eq!(sess.query::<TestWords>(&1), 3);
+    assert_eq!(sess.queries().stats().misses, before.misses);
+    assert_eq!(sess.query::<TestWords>(&2), 1);
+    assert_eq!(sess.queries().stats().misses, before.misses + 1);
+}
+
+#[test]
+fn test_records_deps() {
+    let sess = Session::new();
+
+    sess.set_input::<TestText>(1, String::from("a b c"));
+    sess.query::<TestTotal>(&vec![1]);
+
+    let graph = sess.queries().graph();
+    let total = DepNode::new(TestTotal::NAME, &vec![1u32]);
+    let words = DepNode::new(TestWords::NAME, &1u32);
+    let text = DepNode::new(TestText::NAME, &1u32);
+
+    assert_eq!(graph.deps(&total).collect::<Vec<_>>(), vec![&words]);
+    assert_eq!(graph.deps(&words).collect::<Vec<_>>(), vec![&text]);
+    assert_eq!(graph.label(&total), Some("total([1])"));
+}
+
+#[test]
+fn test_green_on_same_input() {
+    let sess = Session::new();
+
+    sess.set_input::<TestText>(1, String::from("a b c"));
+    assert_eq!(sess.query::<TestTotal>(&vec![1]), 3);
+
+    sess.set_input::<TestText>(1, String::from("a b c"));
+
+    let before = sess.queries().stats();
+
+    assert_eq!(sess.query::<TestTotal>(&vec![1]), 3);
+    assert_eq!(sess.queries().stats().misses, before.misses);
+    assert_eq!(sess.queries().stats().green, before.green + 2);
+}
+
+#[test]
+fn test_backdating() {
+    let sess = Session::new();
+
+    sess.set_input::<TestText>(1, String::from("a b c"));
+    assert_eq!(sess.query::<TestTotal>(&vec![1]), 3);
+
+    // The word count is recomputed, but does not change, so the total
+    // is marked green rather than recomputed.
+    sess.set_input::<TestText>(1, String::from("x y z"));
+
+    let before = sess.queries().stats();
+
+    assert_eq!(sess.query::<TestTotal>(&vec![1]), 3);
+    assert_eq!(sess.queries().stats().misses, before.misses + 1);
+    assert_eq!(sess.queries().stats().green, before.green + 1);
+}
+
+#[cfg(test)]
+struct TestCyclic;
+
+#[cfg(test)]
+impl Query for TestCyclic {
+    type Key = u32;
+    type Value = u32;
+
+    const NAME: &'static str = "cyclic";
+
+    fn compute(sess: &Session, key: &u32) -> u32 {
+        sess.query::<TestCyclic>(&((key + 1) % 2)) + 1
+    }
+
+    fn position(_sess: &Session, key: &u32) -> Option<usize> {
+        Some(*key as usize + 10)
+    }
+}
+
+#[cfg(test)]
+struct TestRecover;
+
+#[cfg(test)]
+impl Query for TestRecover {
+    type Key = u32;
+    type Value = u32;
+
+    const NAME: &'static str = "recover";
+
+    fn compute(sess: &Session, key: &u32) -> u32 {
+        sess.query::<TestRecover>(key) + 1
+    }
+
+    fn recover(_sess: &Session, _key: &u32,
+               _cycle: &QueryCycle) -> Option<u32> {
+        Some(0)
+    }
+}
+
+#[test]
+fn test_cycle_panics() {
+    use std::panic::AssertUnwindSafe;
+    use std::panic::catch_unwind;
+
+    let sess = Session::new();
+    let err = catch_unwind(AssertUnwindSafe(|| sess.query::<TestCyclic>(&0)))
+        .expect_err("cycle not detected");
+    let cycle = err.downcast_ref::<QueryCycle>().expect("not a QueryCycle");
+
+    assert_eq!(cycle.queries(), vec!["cyclic", "cyclic"]);
+    assert_eq!(cycle.frames[0].label, "cyclic(0)");
+    assert_eq!(cycle.frames[1].pos, Some(11));
+    assert_eq!(sess.queries().cycles().len(), 1);
+    assert!(sess.queries().active.borrow().is_empty());
+}
+
+#[test]
+fn test_cycle_recover() {
+    let sess = Session::new();
+
+    assert_eq!(sess.query::<TestRecover>(&7), 1);
+    assert_eq!(sess.queries().cycles()[0].queries(), vec!["recover"]);
+}
@@ -0,0 +1,164 @@
+//! Deterministic random numbers.
+//!
+//! Passes that use randomized heuristics, such as salting hash tables
+//! or sampling in probabilistic checks, must still produce the same
+//! output for the same input.  They should get their generator from
+//! `Session::rng`, which derives it from the session's seed (`--seed`)
+//! and a name for the stream, so that one pass drawing more numbers
+//! does not change the numbers seen by another.  The seed should be
+//! included in crash reports, so that failures can be reproduced.
+
+use salt::common::hash::StableHasher;
+
+/// A seeded pseudo-random number generator using xoshiro256**, with
+/// its state initialized by splitmix64.  This is fast and has good
+/// statistical properties, but is not suitable for cryptography.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeterministicRng {
+    /// The seed the generator was created from.
+    seed: u64,
+    /// The xoshiro256** state.
+    state: [u64; 4]
+}
+
+/// Advance a splitmix64 state and return the next output.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+    let mut z = *state;
+
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+    z ^ (z >> 31)
+}
+
+impl DeterministicRng {
+    /// Create a generator from `seed`.
+    pub fn new(seed: u64) -> DeterministicRng {
+        let mut sm = seed;
+        let state = [splitmix64(&mut sm), splitmix64(&mut sm),
+                     splitmix64(&mut sm), splitmix64(&mut sm)];
+
+        DeterministicRng { seed, state }
+    }
+
+    /// Create a generator for the stream named `stream`, derived from
+    /// `seed`.  Different streams are independent of each other.
+    pub fn for_stream(seed: u64, stream: &str) -> DeterministicRng {
+        let mut hasher = StableHasher::new();
+
+        hasher.write_str(stream);
+
+        DeterministicRng::new(seed ^ hasher.finish128().as_u64())
+    }
+
+    /// Get the seed this generator was created from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Get the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let out = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        out
+    }
+
+    /// Get the next 32 random bits.
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Get a number uniformly distributed in `[0, bound)`.  Panics if
+    /// `bound` is 0.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound != 0, "empty range");
+
+        // Reject the values that would make some results more likely.
+        let zone = u64::MAX - (u64::MAX - bound + 1) % bound;
+
+        loop {
+            let n = self.next_u64();
+
+            if n <= zone {
+                return n % bound;
+            }
+        }
+    }
+
+    /// Get a number uniformly distributed in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Return `true` with probability `num / den`.  Panics if `den` is
+    /// 0.
+    pub fn chance(&mut self, num: u64, den: u64) -> bool {
+        self.below(den) < num
+    }
+
+    /// Shuffle `items` into a random order.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+
+            items.swap(i, j);
+        }
+    }
+}
+
+#[test]
+fn test_reference_output() {
+    // splitmix64 and xoshiro256** outputs for seed 0, from the
+    // reference implementations.
+    let mut sm = 0;
+
+    assert_eq!(splitmix64(&mut sm), 0xe220_a839_7b1d_cdaf);
+
+    let mut rng = DeterministicRng { seed: 0, state: [1, 2, 3, 4] };
+
+    assert_eq!(rng.next_u64(), 11520);
+    assert_eq!(rng.next_u64(), 0);
+    assert_eq!(rng.next_u64(), 1509978240);
+}
+
+#[test]
+fn test_streams() {
+    let a: Vec<u64> = {
+        let mut rng = DeterministicRng::for_stream(42, "layout");
+
+        (0..4).map(|_| rng.next_u64()).collect()
+    };
+    let mut again = DeterministicRng::for_stream(42, "layout");
+    let mut other = DeterministicRng::for_stream(42, "hash-salt");
+
+    assert_eq!((0..4).map(|_| again.next_u64()).collect::<Vec<_>>(), a);
+    assert_ne!(other.next_u64(), a[0]);
+}
+
+#[test]
+fn test_ranges() {
+    let mut rng = DeterministicRng::new(7);
+    let mut items: Vec<u32> = (0..20).collect();
+
+    for _ in 0..1000 {
+        assert!(rng.below(3) < 3);
+        assert!((0.0..1.0).contains(&rng.next_f64()));
+    }
+
+    assert!(!rng.chance(0, 5));
+    assert!(rng.chance(5, 5));
+    rng.shuffle(&mut items);
+    items.sort();
+    assert_eq!(items, (0..20).collect::<Vec<_>>());
+}
@@ -0,0 +1,461 @@
+use salt::common::debuginfo::DebugInfoLevel;
+use salt::common::env::EnvTracker;
+use salt::common::filename::Filename;
+use salt::common::filename::FilenameCtx;
+use salt::common::filename::FilenameTable;
+use salt::common::filename::Normalization;
+use salt::common::limits::Limits;
+use salt::common::message::DiagnosticSink;
+use salt::common::message::Emitted;
+use salt::common::message::Message;
+use salt::common::message::TooManyErrors;
+use salt::common::ordered::OrderedBuffer;
+use salt::common::position::FilePosition;
+use salt::common::position::FilePositionCtx;
+use salt::common::position::Location;
+use salt::common::position::Point;
+use salt::common::position::PositionInfo;
+use salt::common::position::PositionTable;
+use salt::common::position::SourceManager;
+use salt::common::position::SourceProvider;
+use salt::common::progress::NoProgress;
+use salt::common::progress::PhaseProgress;
+use salt::common::progress::ProgressSink;
+use salt::common::query::Query;
+use salt::common::query::QueryEngine;
+use salt::common::rng::DeterministicRng;
+use salt::common::str::arena::StrArena;
+use salt::common::str::intern::StrIntern;
+use salt::common::str::width::TabStops;
+use salt::common::symbol::Gensym;
+use salt::common::symbol::GensymCtx;
+use salt::common::symbol::Symbol;
+use salt::common::symbol::SymbolCtx;
+use salt::common::symbol::SymbolTable;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::hash::Hash;
+
+/// Options controlling a compilation session.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionOptions {
+    /// Produce output that is byte-identical regardless of the number
+    /// of threads used (`--deterministic`).  Output from parallel jobs
+    /// is buffered and released in a stable order.
+    pub deterministic: bool,
+    /// The number of threads to use for parallel phases.
+    pub threads: usize,
+    /// Resource limits.
+    pub limits: Limits,
+    /// How much debug information to emit (`-g`).
+    pub debug_info: DebugInfoLevel,
+    /// The seed for randomized heuristics (`--seed`).
+    pub seed: u64
+}
+
+/// State for a single compilation, shared between compiler phases.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Options for the compilation.
+    opts: SessionOptions,
+    /// Environment variables consulted during the compilation.
+    env: EnvTracker,
+    /// Memoized query results.
+    queries: QueryEngine,
+    /// Where progress is reported.
+    progress: Box<dyn ProgressSink>
+}
+
+/// A `Session` together with everything the phases of a compilation
+/// share that borrows from the arena `'a`: the string interner, the
+/// filename table, the symbol table, the loaded sources, the positions
+/// created so far, and the sink collecting diagnostics with positions
+/// of type `P`.  This is the one object passed between phases.
+///
+/// Filenames are created through the `FilenameTable`, so they are
+/// normalized and remapped, and positions are created in the current
+/// file, which is set with `set_file`.
+pub struct SessionCtx<'a, P = FilePosition<'a>> {
+    sess: Session,
+    arena: &'a StrArena,
+    strs: StrIntern<'a>,
+    filenames: FilenameTable<'a>,
+    symbols: SymbolTable<'a>,
+    sources: SourceManager<'a>,
+    file: Option<Filename<'a>>,
+    positions: PositionTable<'a>,
+    gensym: Gensym,
+    diagnostics: DiagnosticSink<'a, P>
+}
+
+impl SessionOptions {
+    /// Create the default options: single-threaded, nondeterministic
+    /// output ordering, no debug information, and a seed of 0.
+    pub fn new() -> SessionOptions {
+        SessionOptions { deterministic: false, threads: 1,
+                         limits: Limits::new(),
+                         debug_info: DebugInfoLevel::None, seed: 0 }
+    }
+}
+
+impl Default for SessionOptions {
+    fn default() -> SessionOptions {
+        SessionOptions::new()
+    }
+}
+
+impl Session {
+    /// Create a new `Session` with default options.
+    pub fn new() -> Session {
+        Session::with_options(SessionOptions::new())
+    }
+
+    /// Create a new `Session` with the given options.
+    pub fn with_options(opts: SessionOptions) -> Session {
+        Session { opts, env: EnvTracker::new(), queries: QueryEngine::new(),
+                  progress: Box::new(NoProgress) }
+    }
+
+    /// Get the options for this session.
+    pub fn options(&self) -> &SessionOptions {
+        &self.opts
+    }
+
+    /// Create a buffer for output produced by parallel jobs, which
+    /// respects the `deterministic` option.
+    pub fn output_buffer<T>(&self) -> OrderedBuffer<T> {
+        OrderedBuffer::new(self.opts.deterministic)
+    }
+
+    /// Read the environment variable `name`.  The read is recorded so
+    /// that dependency files and incremental fingerprints account for
+    /// it; phases should always use this instead of `std::env::var`.
+    pub fn env_var(&self, name: &str) -> Option<String> {
+        self.env.var(name)
+    }
+
+    /// Get the record of environment variables read so far.
+    pub fn env_deps(&self) -> &EnvTracker {
+        &self.env
+    }
+
+    /// Create a random number generator for the stream `stream`,
+    /// seeded from the `seed` option.  Each pass should use its own
+    /// stream name.
+    pub fn rng(&self, stream: &str) -> DeterministicRng {
+        DeterministicRng::for_stream(self.opts.seed, stream)
+    }
+
+    /// Get the value of the query `Q` for `key`, using a memoized
+    /// result if possible.
+    pub fn query<Q: Query>(&self, key: &Q::Key) -> Q::Value {
+        self.queries.get::<Q>(self, key)
+    }
+
+    /// Set the value of the input query `Q` for `key`.
+    pub fn set_input<Q: Query>(&self, key: Q::Key, value: Q::Value) {
+        self.queries.set::<Q>(key, value)
+    }
+
+    /// Get the query engine.
+    pub fn queries(&self) -> &QueryEngine {
+        &self.queries
+    }
+
+    /// Report progress to `sink` instead of discarding it.
+    pub fn set_progress<S: ProgressSink + 'static>(&mut self, sink: S) {
+        self.progress = Box::new(sink)
+    }
+
+    /// Get the sink progress is reported to.
+    pub fn progress(&self) -> &dyn ProgressSink {
+        &*self.progress
+    }
+
+    /// Start reporting progress for the phase `phase`, which will
+    /// process `total` units of work, if that is known.  The phase is
+    /// finished when the result is dropped.
+    pub fn start_phase(&self, phase: &str,
+                       total: Option<u64>) -> PhaseProgress<'_> {
+        PhaseProgress::start(&*self.progress, phase, total)
+    }
+}
+
+impl<'a, P: Eq + Hash + PositionInfo<'a>> SessionCtx<'a, P> {
+    /// Create a context for `sess`, keeping filenames and generated
+    /// names in `arena`, with no sources, positions or diagnostics.
+    pub fn new(sess: Session, arena: &'a StrArena) -> SessionCtx<'a, P> {
+        SessionCtx { sess, arena, strs: StrIntern::new(),
+                     filenames: FilenameTable::new(arena,
+                                                   Normalization::default()),
+                     symbols: SymbolTable::new(),
+                     sources: SourceManager::new(), file: None,
+                     positions: PositionTable::new(), gensym: Gensym::new(),
+                     diagnostics: DiagnosticSink::default() }
+    }
+
+    /// Use `diagnostics` to collect diagnostics, replacing the default
+    /// sink.
+    pub fn with_diagnostics(mut self, diagnostics: DiagnosticSink<'a, P>)
+                            -> SessionCtx<'a, P> {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Get the session.
+    pub fn session(&self) -> &Session {
+        &self.sess
+    }
+
+    /// Get the session, to change it.
+    pub fn session_mut(&mut self) -> &mut Session {
+        &mut self.sess
+    }
+
+    /// Get the arena names are kept in.
+    pub fn arena(&self) -> &'a StrArena {
+        self.arena
+    }
+
+    /// Get the string interner.
+    pub fn strs(&self) -> &StrIntern<'a> {
+        &self.strs
+    }
+
+    /// Get the filename table.
+    pub fn filenames(&self) -> &FilenameTable<'a> {
+        &self.filenames
+    }
+
+    /// Get the filename table, to change how paths are remapped, or to
+    /// name buffers and REPL input.
+    pub fn filenames_mut(&mut self) -> &mut FilenameTable<'a> {
+        &mut self.filenames
+    }
+
+    /// Get the symbol table.
+    pub fn symbols(&self) -> &SymbolTable<'a> {
+        &self.symbols
+    }
+
+    /// Get the symbol table, to issue ids.
+    pub fn symbols_mut(&mut self) -> &mut SymbolTable<'a> {
+        &mut self.symbols
+    }
+
+    /// Get the loaded sources.
+    pub fn sources(&self) -> &SourceManager<'a> {
+        &self.sources
+    }
+
+    /// Get the loaded sources, to load more.
+    pub fn sources_mut(&mut self) -> &mut SourceManager<'a> {
+        &mut self.sources
+    }
+
+    /// Get the diagnostics collected so far.
+    pub fn diagnostics(&self) -> &DiagnosticSink<'a, P> {
+        &self.diagnostics
+    }
+
+    /// Get the diagnostic sink, to suppress warnings in regions of
+    /// source.
+    pub fn diagnostics_mut(&mut self) -> &mut DiagnosticSink<'a, P> {
+        &mut self.diagnostics
+    }
+
+    /// Offer `msg` to the diagnostic sink.  See `DiagnosticSink::emit`.
+    pub fn emit(&mut self, msg: Message<P>)
+                -> ::std::result::Result<Emitted, TooManyErrors> {
+        self.diagnostics.emit(msg)
+    }
+
+    /// Check whether any errors have been reported.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.has_errors()
+    }
+
+    /// Make `fname` the file that new positions are in, returning its
+    /// `Filename`.
+    pub fn set_file(&mut self, fname: &'a str) -> Filename<'a> {
+        let filename = self.filenames.filename(fname);
+
+        self.file = Some(filename);
+
+        filename
+    }
+
+    /// Get the file that new positions are in, if one has been set.
+    pub fn file(&self) -> Option<Filename<'a>> {
+        self.file
+    }
+
+    /// Get the positions created so far, in the order they were made.
+    pub fn positions(&self) -> &PositionTable<'a> {
+        &self.positions
+    }
+
+    /// Add `pos` to the position table, returning its index, as for a
+    /// query key that refers to where the query was needed.
+    pub fn push_position(&mut self, pos: FilePosition<'a>) -> usize {
+        self.positions.push(pos)
+    }
+
+    /// Get the counter for generated names, to save it.
+    pub fn gensym_counter(&self) -> Gensym {
+        self.gensym
+    }
+
+    /// Set the counter for generated names, to restore a saved one.
+    pub fn set_gensym_counter(&mut self, gensym: Gensym) {
+        self.gensym = gensym
+    }
+
+    /// Add `pos` to the position table, and return the stored copy.
+    fn add(&mut self, pos: FilePosition<'a>) -> &FilePosition<'a> {
+        let idx = self.positions.push(pos);
+
+        self.positions.get(idx).unwrap()
+    }
+}
+
+impl<'a, P> Debug for SessionCtx<'a, P> where P: Debug {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("SessionCtx")
+            .field("sess", &self.sess)
+            .field("strs", &self.strs.len())
+            .field("filenames", &self.filenames)
+            .field("symbols", &self.symbols)
+            .field("sources", &self.sources)
+            .field("file", &self.file)
+            .field("positions", &self.positions)
+            .field("gensym", &self.gensym)
+            .field("diagnostics", &self.diagnostics)
+            .finish()
+    }
+}
+
+impl<'a, P> FilenameCtx<'a> for SessionCtx<'a, P> {
+    fn filename(&mut self, fname: &'a str) -> Filename<'a> {
+        self.filenames.filename(fname)
+    }
+}
+
+impl<'a, P> SymbolCtx<'a> for SessionCtx<'a, P> {
+    fn symbol(&mut self, fname: &'a str) -> Symbol<'a> {
+        self.strs.symbol(fname)
+    }
+}
+
+impl<'a, P> GensymCtx<'a> for SessionCtx<'a, P> {
+    fn gensym(&mut self, prefix: &str) -> Symbol<'a> {
+        self.gensym.fresh(self.arena, prefix)
+    }
+}
+
+/// Positions are in the current file.  Both methods panic if no file
+/// has been set, and `span` panics if its ends are in different files.
+impl<'a, P> FilePositionCtx<'a> for SessionCtx<'a, P>
+    where P: Eq + Hash + PositionInfo<'a> {
+    fn point(&mut self, line: u32, col: u32) -> &FilePosition<'a> {
+        let filename = self.file.expect("no current file");
+        let loc = Location::Point { point: Point { line, col } };
+
+        self.add(FilePosition { filename, loc })
+    }
+
+    fn span(&mut self, start: &FilePosition<'a>,
+            end: &FilePosition<'a>) -> &FilePosition<'a> {
+        assert!(start.filename == end.filename, "span across files");
+
+        let loc = Location::Span { start: start.loc.start(),
+                                   end: end.loc.end() };
+
+        self.add(FilePosition { filename: start.filename, loc })
+    }
+}
+
+impl<'a, P> SourceProvider<'a> for SessionCtx<'a, P> {
+    fn source_line(&self, filename: Filename<'a>, line: u32) -> Option<&str> {
+        self.sources.source_line(filename, line)
+    }
+
+    fn tab_stops(&self) -> TabStops {
+        self.sources.tab_stops()
+    }
+}
+
+#[test]
+fn test_env_var_tracked() {
+    let sess = Session::new();
+
+    ::std::env::set_var("SALT_TEST_SESSION_ENV", "1");
+
+    assert_eq!(sess.env_var("SALT_TEST_SESSION_ENV"), Some(String::from("1")));
+    assert_eq!(sess.env_deps().recorded().len(), 1);
+}
+
+#[test]
+fn test_session_ctx() {
+    let arena = StrArena::new();
+    let mut ctx: SessionCtx = SessionCtx::new(Session::new(), &arena);
+    let a = ctx.set_file("src/./a.salt");
+
+    assert_eq!(ctx.filename("src/a.salt"), a);
+    assert_eq!(ctx.symbol("x"), ctx.symbol("x"));
+    assert!(ctx.gensym("t") != ctx.gensym("t"));
+
+    let start = ctx.point(1, 1).clone();
+    let end = ctx.point(1, 5).clone();
+    let span = ctx.span(&start, &end).clone();
+
+    assert_eq!(ctx.positions().len(), 3);
+    ctx.sources_mut().add(a, String::from("let x = 1;\n"));
+    assert_eq!(ctx.source_line(a, 1), Some("let x = 1;"));
+    assert!(!ctx.has_errors());
+    ctx.emit(Message::error("bad").with_position(span)).unwrap();
+    assert!(ctx.has_errors());
+    assert!(ctx.session().options().threads >= 1);
+}
+
+#[test]
+fn test_query_cycle_positions() {
+    use salt::common::query::QueryCycle;
+
+    /// A query that requires itself, keyed by the index of the position
+    /// that needed it.
+    struct Loop;
+
+    impl Query for Loop {
+        type Key = usize;
+        type Value = u32;
+
+        const NAME: &'static str = "loop";
+
+        fn compute(sess: &Session, key: &usize) -> u32 {
+            sess.query::<Loop>(key)
+        }
+
+        fn position(_sess: &Session, key: &usize) -> Option<usize> {
+            Some(*key)
+        }
+
+        fn recover(_sess: &Session, _key: &usize,
+                   _cycle: &QueryCycle) -> Option<u32> {
+            Some(0)
+        }
+    }
+
+    let arena = StrArena::new();
+    let mut ctx: SessionCtx = SessionCtx::new(Session::new(), &arena);
+    let a = ctx.set_file("a.salt");
+    let pos = FilePosition::new(a, Location::point((3, 7)));
+    let idx = ctx.push_position(pos.clone());
+
+    assert_eq!(ctx.session().query::<Loop>(&idx), 0);
+
+    let cycle = ctx.session().queries().cycles()[0].clone();
+
+    assert_eq!(cycle.to_message(ctx.positions()).primary(), Some(&pos));
+}
@@ -0,0 +1,230 @@
+use salt::common::heapsize::HeapSize;
+#[cfg(feature = "std")]
+use salt::common::heapsize::hash_table_size;
+use salt::common::str::intern::InternStats;
+use std::cell::RefCell;
+#[cfg(not(feature = "std"))]
+use std::collections::BTreeSet;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result;
+#[cfg(not(feature = "std"))]
+use std::mem::size_of;
+use std::slice;
+use std::str;
+use std::string::String;
+use std::vec::Vec;
+
+/// The size of the chunks strings are copied into.
+const CHUNK_SIZE: usize = 4096;
+
+/// The set of interned strings.  As with `StrIntern`'s table, this is
+/// a B-tree without the standard library.
+#[cfg(feature = "std")]
+type Table = HashSet<&'static str>;
+#[cfg(not(feature = "std"))]
+type Table = BTreeSet<&'static str>;
+
+/// String interning table that owns its strings.  Unlike `StrIntern`,
+/// which requires strings to outlive the table, `StrArena` copies each
+/// new string into memory it owns, so lexers can intern token text
+/// from temporary buffers.  Interned strings live as long as the
+/// arena, and equal strings get the same reference.
+pub struct StrArena(RefCell<ArenaInner>);
+
+struct ArenaInner {
+    /// Memory strings are copied into.  Chunks are never grown past
+    /// their capacity, so strings in them never move.
+    chunks: Vec<String>,
+    /// The interned strings.  These point into `chunks`; they are not
+    /// really `'static`, and are only handed out borrowed from the
+    /// arena.
+    strs: Table
+}
+
+impl ArenaInner {
+    /// Get the number of strings the table can hold without growing.
+    #[cfg(feature = "std")]
+    fn capacity(&self) -> usize {
+        self.strs.capacity()
+    }
+
+    /// Get the number of strings the table can hold without growing.
+    #[cfg(not(feature = "std"))]
+    fn capacity(&self) -> usize {
+        self.strs.len()
+    }
+
+    /// Get the heap memory used by the table, not counting the chunks.
+    #[cfg(feature = "std")]
+    fn table_size(&self) -> usize {
+        hash_table_size::<&str>(self.strs.capacity())
+    }
+
+    /// Get the heap memory used by the table, not counting the chunks.
+    #[cfg(not(feature = "std"))]
+    fn table_size(&self) -> usize {
+        self.strs.len() * size_of::<&str>()
+    }
+
+    /// Copy `str` into a chunk.
+    fn alloc(&mut self, str: &str) -> &'static str {
+        let fits = self.chunks.last()
+            .is_some_and(|chunk| chunk.capacity() - chunk.len() >= str.len());
+
+        if !fits {
+            self.chunks.push(String::with_capacity(CHUNK_SIZE.max(str.len())))
+        }
+
+        let chunk = self.chunks.last_mut().unwrap();
+        let start = chunk.len();
+
+        chunk.push_str(str);
+
+        // The chunk has not reallocated, and never will, so the copy
+        // stays where it is until the arena is dropped.
+        unsafe {
+            let bytes = slice::from_raw_parts(chunk.as_ptr().add(start),
+                                              str.len());
+
+            str::from_utf8_unchecked(bytes)
+        }
+    }
+}
+
+impl StrArena {
+    /// Create an empty `StrArena`.
+    pub fn new() -> StrArena {
+        StrArena(RefCell::new(ArenaInner { chunks: Vec::new(),
+                                           strs: Table::new() }))
+    }
+
+    /// Intern a given string, copying it into the arena if it has not
+    /// been interned before.  This will return a distinguished
+    /// reference to a string equal to str, relative to this StrArena
+    /// structure.
+    pub fn intern(&self, str: &str) -> &str {
+        let mut inner = self.0.borrow_mut();
+
+        match inner.strs.get(str) {
+            Some(interned) => interned,
+            None => {
+                let interned = inner.alloc(str);
+
+                inner.strs.insert(interned);
+
+                interned
+            }
+        }
+    }
+
+    /// Get the interned string equal to `str`, if there is one.
+    pub fn get(&self, str: &str) -> Option<&str> {
+        self.0.borrow().strs.get(str).copied()
+    }
+
+    /// Get the number of strings in the arena.
+    pub fn len(&self) -> usize {
+        self.0.borrow().strs.len()
+    }
+
+    /// Check whether the arena is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().strs.is_empty()
+    }
+
+    /// Get the strings in the arena.
+    pub fn strings(&self) -> Vec<&str> {
+        self.0.borrow().strs.iter().copied().collect()
+    }
+
+    /// Get statistics on the arena, with a histogram of lengths if
+    /// `lengths` is set.  The heap size includes the chunks.
+    fn collect_stats(&self, lengths: bool) -> InternStats {
+        let inner = self.0.borrow();
+
+        InternStats::new(inner.strs.iter().copied(), inner.capacity(),
+                         self.heap_size(), lengths)
+    }
+
+    /// Get statistics on the arena, without a histogram of lengths.
+    pub fn stats(&self) -> InternStats {
+        self.collect_stats(false)
+    }
+
+    /// Get statistics on the arena, with a histogram of lengths.
+    pub fn stats_with_lengths(&self) -> InternStats {
+        self.collect_stats(true)
+    }
+}
+
+impl Default for StrArena {
+    fn default() -> StrArena {
+        StrArena::new()
+    }
+}
+
+impl Debug for StrArena {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_list()
+            .entries(self.strings())
+            .finish()
+    }
+}
+
+/// Both the chunks and the table are counted.
+impl HeapSize for StrArena {
+    fn heap_size(&self) -> usize {
+        let inner = self.0.borrow();
+
+        inner.chunks.heap_size() + inner.table_size()
+    }
+}
+
+#[test]
+fn test_str_arena() {
+    let arena = StrArena::new();
+    let first = {
+        let tmp = String::from("token");
+
+        arena.intern(&tmp)
+    };
+    let again = arena.intern(&String::from("token"));
+    let other = arena.intern("other");
+
+    assert!(::std::ptr::eq(first, again));
+    assert!(!::std::ptr::eq(first, other));
+    assert_eq!(first, "token");
+    assert_eq!(arena.len(), 2);
+    assert!(::std::ptr::eq(arena.get("other").unwrap(), other));
+    assert_eq!(arena.get("missing"), None);
+}
+
+#[test]
+fn test_str_arena_chunks() {
+    let arena = StrArena::new();
+    let long = "x".repeat(CHUNK_SIZE * 2);
+    let strs: Vec<String> = (0..2000).map(|i| format!("ident{}", i))
+        .collect();
+    let interned: Vec<&str> = strs.iter().map(|s| arena.intern(s)).collect();
+    let big = arena.intern(&long);
+
+    for (s, interned) in strs.iter().zip(interned.iter()) {
+        assert_eq!(s, interned);
+        assert!(::std::ptr::eq(arena.intern(s), *interned));
+    }
+
+    assert_eq!(big, long);
+    assert_eq!(arena.len(), 2001);
+    assert!(arena.heap_size() >= CHUNK_SIZE * 4);
+
+    let stats = arena.stats_with_lengths();
+
+    assert_eq!(stats.count, 2001);
+    assert_eq!(stats.bytes, strs.iter().map(String::len).sum::<usize>() +
+                            long.len());
+    assert_eq!(stats.heap_size, arena.heap_size());
+    assert_eq!(stats.lengths.unwrap().last(), Some(&(16383, 1)));
+}
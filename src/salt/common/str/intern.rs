@@ -1,15 +1,93 @@
+use salt::common::heapsize::HeapSize;
+#[cfg(feature = "std")]
+use salt::common::heapsize::hash_table_size;
+#[cfg(feature = "std")]
+use salt::common::metrics::Counter;
+#[cfg(feature = "std")]
+use salt::common::metrics::Histogram;
+#[cfg(not(feature = "std"))]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use std::collections::btree_map::Entry;
+#[cfg(not(feature = "std"))]
+use std::collections::btree_map::Values;
+#[cfg(feature = "std")]
 use std::collections::hash_map::Entry;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
 use std::collections::hash_map::Values;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result;
+#[cfg(feature = "std")]
+use std::hash::BuildHasher;
 use std::iter::ExactSizeIterator;
 use std::iter::FusedIterator;
 use std::iter::Iterator;
+#[cfg(not(feature = "std"))]
+use std::mem::size_of;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+use std::vec::Vec;
+
+/// Interns of strings already in a table.
+#[cfg(feature = "std")]
+static INTERN_HITS: Counter = Counter::new("intern.hits");
+
+/// Interns of new strings.
+#[cfg(feature = "std")]
+static INTERN_MISSES: Counter = Counter::new("intern.misses");
+
+/// Lengths of new strings.
+#[cfg(feature = "std")]
+static INTERN_LENGTHS: Histogram = Histogram::new("intern.lengths");
+
+/// The number of shards in a `SyncStrIntern`.
+#[cfg(feature = "std")]
+const SHARDS: usize = 16;
+
+/// The table strings are interned in.  Without the standard library,
+/// which has the only hash tables, it is a B-tree, which has no
+/// capacity to reserve or report.
+#[cfg(feature = "std")]
+type Table<'s> = HashMap<&'s str, &'s str>;
+#[cfg(not(feature = "std"))]
+type Table<'s> = BTreeMap<&'s str, &'s str>;
+
+/// Statistics on an interning table, for accounting for the memory
+/// interning uses.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct InternStats {
+    /// The number of strings.
+    pub count: usize,
+    /// The total length of the strings, in bytes.
+    pub bytes: usize,
+    /// The number of strings the table can hold without growing.
+    pub capacity: usize,
+    /// The heap memory the table uses, as `HeapSize` counts it.
+    pub heap_size: usize,
+    /// The lengths of the strings, if requested, as the upper bound of
+    /// each nonempty bucket and the number of strings in it.  Buckets
+    /// are logarithmic, as in `HistogramSummary`.
+    pub lengths: Option<Vec<(u64, u64)>>
+}
 
 /// String interning table.
-pub struct StrIntern<'s>(HashMap<&'s str, &'s str>);
+pub struct StrIntern<'s>(Table<'s>);
+
+/// String interning table that can be shared between threads.  Strings
+/// are divided between shards by hash, each with its own lock, so
+/// threads interning different strings rarely contend.  Interning
+/// gives the same guarantee as `StrIntern`: equal strings get the
+/// same reference.
+#[cfg(feature = "std")]
+pub struct SyncStrIntern<'s> {
+    hasher: RandomState,
+    shards: Box<[Mutex<HashMap<&'s str, &'s str>>]>
+}
 
 /// Iterator over strings in a `StrIntern` table.
 pub struct Strings<'s>(Values<'s, &'s str, &'s str>);
@@ -51,37 +129,138 @@ impl<'s> ExactSizeIterator for Strings<'s> {
 
 impl<'s> FusedIterator for Strings<'s> {}
 
+impl InternStats {
+    /// Collect statistics on the strings `strs`, in a table that can
+    /// hold `capacity` strings and uses `heap_size` bytes of heap, with
+    /// a histogram of lengths if `lengths` is set.
+    pub fn new<'s, I>(strs: I, capacity: usize, heap_size: usize,
+                      lengths: bool) -> InternStats
+        where I: IntoIterator<Item = &'s str> {
+        let mut buckets = [0; u64::BITS as usize + 1];
+        let mut count = 0;
+        let mut bytes = 0;
+
+        for str in strs {
+            let len = str.len() as u64;
+
+            buckets[(u64::BITS - len.leading_zeros()) as usize] += 1;
+            count += 1;
+            bytes += str.len();
+        }
+
+        let lengths = if lengths {
+            Some(buckets.iter().enumerate()
+                 .filter(|&(_, &n)| n != 0)
+                 .map(|(i, &n)| (if i == 0 { 0 } else { u64::MAX >> (64 - i) },
+                                 n))
+                 .collect())
+        } else {
+            None
+        };
+
+        InternStats { count, bytes, capacity, heap_size, lengths }
+    }
+
+    /// Get the fraction of the capacity in use, from 0 to 1.
+    pub fn occupancy(&self) -> f64 {
+        if self.capacity == 0 {
+            0.0
+        } else {
+            self.count as f64 / self.capacity as f64
+        }
+    }
+}
+
 impl<'s> StrIntern<'s> {
     /// Create a `StrIntern` table.
     pub fn new() -> StrIntern<'s> {
-        StrIntern(HashMap::new())
+        StrIntern(Table::new())
     }
 
     /// Create a `StrIntern` table with a given capacity.
+    #[cfg(feature = "std")]
     pub fn with_capacity(capacity: usize) -> StrIntern<'s> {
         StrIntern(HashMap::with_capacity(capacity))
     }
 
+    /// Create a `StrIntern` table with a given capacity.
+    #[cfg(not(feature = "std"))]
+    pub fn with_capacity(_capacity: usize) -> StrIntern<'s> {
+        StrIntern::new()
+    }
+
+    /// Create a `StrIntern` table holding `strs`, such as a language's
+    /// keywords, so that it starts warm.
+    pub fn with_strings(strs: &[&'s str]) -> StrIntern<'s> {
+        let mut table = StrIntern::with_capacity(strs.len());
+
+        for str in strs {
+            table.0.entry(str).or_insert(str);
+        }
+
+        table
+    }
+
     /// Intern a given string.  This will return a distinguished
     /// reference to a string equal to str, relative to this StrIntern
     /// structure.
     pub fn intern(&mut self, str: &'s str) -> &'s str {
         match self.0.entry(str) {
-            Entry::Occupied(ent) => ent.get(),
-            Entry::Vacant(ent) => ent.insert(str)
+            Entry::Occupied(ent) => {
+                #[cfg(feature = "std")]
+                INTERN_HITS.incr();
+
+                ent.get()
+            }
+            Entry::Vacant(ent) => {
+                #[cfg(feature = "std")]
+                INTERN_MISSES.incr();
+                #[cfg(feature = "std")]
+                INTERN_LENGTHS.record(str.len() as u64);
+
+                ent.insert(str)
+            }
         }
     }
 
+    /// Get the interned string equal to `str`, if there is one.  Unlike
+    /// `intern`, `str` need not live as long as the table.
+    pub fn get(&self, str: &str) -> Option<&'s str> {
+        self.0.get(str).cloned()
+    }
+
     /// Reserves capacity for at least additional more elements.
+    #[cfg(feature = "std")]
     pub fn reserve(&mut self, additional: usize) {
         self.0.reserve(additional)
     }
 
+    /// Reserves capacity for at least additional more elements.
+    #[cfg(not(feature = "std"))]
+    pub fn reserve(&mut self, _additional: usize) {}
+
     /// Shrinks the capacity of the underlying map as much as possible.
+    #[cfg(feature = "std")]
     pub fn shrink_to_fit(&mut self) {
         self.0.shrink_to_fit()
     }
 
+    /// Shrinks the capacity of the underlying map as much as possible.
+    #[cfg(not(feature = "std"))]
+    pub fn shrink_to_fit(&mut self) {}
+
+    /// Get the number of strings the table can hold without growing.
+    #[cfg(feature = "std")]
+    fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// Get the number of strings the table can hold without growing.
+    #[cfg(not(feature = "std"))]
+    fn capacity(&self) -> usize {
+        self.0.len()
+    }
+
     /// Get an iterator for the strings in the table.
     pub fn strings(&self) -> Strings {
         Strings(self.0.values())
@@ -91,6 +270,181 @@ impl<'s> StrIntern<'s> {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    /// Get statistics on the table, without a histogram of lengths.
+    pub fn stats(&self) -> InternStats {
+        InternStats::new(self.0.keys().cloned(), self.capacity(),
+                         self.heap_size(), false)
+    }
+
+    /// Get statistics on the table, with a histogram of lengths.
+    pub fn stats_with_lengths(&self) -> InternStats {
+        InternStats::new(self.0.keys().cloned(), self.capacity(),
+                         self.heap_size(), true)
+    }
+}
+
+/// The strings themselves are borrowed, so only the table is counted.
+impl<'s> HeapSize for StrIntern<'s> {
+    #[cfg(feature = "std")]
+    fn heap_size(&self) -> usize {
+        hash_table_size::<(&'s str, &'s str)>(self.0.capacity())
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn heap_size(&self) -> usize {
+        self.0.len() * size_of::<(&'s str, &'s str)>()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'s> SyncStrIntern<'s> {
+    /// Create a `SyncStrIntern` table.
+    pub fn new() -> SyncStrIntern<'s> {
+        SyncStrIntern::with_capacity(0)
+    }
+
+    /// Create a `SyncStrIntern` table with a given capacity.
+    pub fn with_capacity(capacity: usize) -> SyncStrIntern<'s> {
+        let per_shard = capacity.div_ceil(SHARDS);
+        let shards = (0..SHARDS)
+            .map(|_| Mutex::new(HashMap::with_capacity(per_shard)))
+            .collect();
+
+        SyncStrIntern { hasher: RandomState::new(), shards }
+    }
+
+    /// Get the shard holding strings equal to `str`.
+    fn shard(&self, str: &str) -> &Mutex<HashMap<&'s str, &'s str>> {
+        let hash = self.hasher.hash_one(str);
+
+        &self.shards[hash as usize % SHARDS]
+    }
+
+    /// Intern a given string.  This will return a distinguished
+    /// reference to a string equal to str, relative to this
+    /// SyncStrIntern structure.  This may be called from several
+    /// threads at once.
+    pub fn intern(&self, str: &'s str) -> &'s str {
+        let mut shard = self.shard(str).lock()
+            .unwrap_or_else(|err| err.into_inner());
+
+        match shard.entry(str) {
+            Entry::Occupied(ent) => {
+                #[cfg(feature = "std")]
+                INTERN_HITS.incr();
+
+                ent.get()
+            }
+            Entry::Vacant(ent) => {
+                #[cfg(feature = "std")]
+                INTERN_MISSES.incr();
+                #[cfg(feature = "std")]
+                INTERN_LENGTHS.record(str.len() as u64);
+
+                ent.insert(str)
+            }
+        }
+    }
+
+    /// Get the interned string equal to `str`, if there is one.
+    pub fn get(&self, str: &str) -> Option<&'s str> {
+        self.shard(str).lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(str).cloned()
+    }
+
+    /// Get the strings in the table.  Strings interned while this runs
+    /// may or may not be included.
+    pub fn strings(&self) -> Vec<&'s str> {
+        self.shards.iter()
+            .flat_map(|shard| {
+                shard.lock().unwrap_or_else(|err| err.into_inner())
+                    .values().cloned().collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Get the number of strings in the table.
+    pub fn len(&self) -> usize {
+        self.shards.iter()
+            .map(|shard| shard.lock()
+                 .unwrap_or_else(|err| err.into_inner()).len())
+            .sum()
+    }
+
+    /// Check whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the total capacity of the shards.
+    fn capacity(&self) -> usize {
+        self.shards.iter()
+            .map(|shard| shard.lock()
+                 .unwrap_or_else(|err| err.into_inner()).capacity())
+            .sum()
+    }
+
+    /// Get statistics on the table, without a histogram of lengths.
+    /// Strings interned while this runs may or may not be counted.
+    pub fn stats(&self) -> InternStats {
+        InternStats::new(self.strings(), self.capacity(), self.heap_size(),
+                         false)
+    }
+
+    /// Get statistics on the table, with a histogram of lengths.
+    pub fn stats_with_lengths(&self) -> InternStats {
+        InternStats::new(self.strings(), self.capacity(), self.heap_size(),
+                         true)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'s> Default for SyncStrIntern<'s> {
+    fn default() -> SyncStrIntern<'s> {
+        SyncStrIntern::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'s> Debug for SyncStrIntern<'s> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_list()
+            .entries(self.strings())
+            .finish()
+    }
+}
+
+/// Merge the shards into a single-threaded table, for when a parallel
+/// phase is done.  Interned references stay the same.
+#[cfg(feature = "std")]
+impl<'s> From<SyncStrIntern<'s>> for StrIntern<'s> {
+    fn from(table: SyncStrIntern<'s>) -> StrIntern<'s> {
+        let mut out = HashMap::with_capacity(table.len());
+
+        for shard in table.shards.into_vec() {
+            out.extend(shard.into_inner()
+                       .unwrap_or_else(|err| err.into_inner()))
+        }
+
+        StrIntern(out)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'s> HeapSize for SyncStrIntern<'s> {
+    fn heap_size(&self) -> usize {
+        ::std::mem::size_of_val(&*self.shards) +
+        self.shards.iter()
+            .map(|shard| {
+                let shard = shard.lock()
+                    .unwrap_or_else(|err| err.into_inner());
+
+                hash_table_size::<(&'s str, &'s str)>(shard.capacity())
+            })
+            .sum::<usize>()
+    }
 }
 
 #[test]
@@ -144,3 +498,130 @@ fn test_str_intern_different() {
 
     assert_ne!(interna, internb)
 }
+
+#[test]
+fn test_str_intern_grow() {
+    let strs: Vec<String> = (0..1000).map(|i| format!("id{}", i)).collect();
+    let mut tab = StrIntern::with_capacity(4);
+    let interned: Vec<&str> = strs.iter().map(|s| tab.intern(s)).collect();
+
+    assert_eq!(tab.len(), 1000);
+    assert_eq!(tab.strings().len(), 1000);
+    assert_eq!(tab.strings().count(), 1000);
+
+    tab.reserve(5000);
+
+    for (s, interned) in strs.iter().zip(interned.iter()) {
+        let again = tab.intern(s.clone().leak());
+
+        assert!(::std::ptr::eq(again, *interned));
+        assert!(::std::ptr::eq(tab.get(s).unwrap(), *interned));
+    }
+
+    tab.shrink_to_fit();
+
+    assert_eq!(tab.len(), 1000);
+    assert_eq!(tab.get("id999"), Some("id999"));
+    assert_eq!(tab.get("id1000"), None);
+    assert_eq!(StrIntern::new().get("id0"), None);
+    assert!(tab.heap_size() >= 1000 * 32);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_sync_str_intern() {
+    let strs: Vec<String> = (0..400).map(|i| format!("id{}", i % 100))
+        .collect();
+    let tab = SyncStrIntern::new();
+    let results: Vec<Vec<&str>> = ::std::thread::scope(|scope| {
+        let workers: Vec<_> = strs.chunks(100)
+            .map(|chunk| {
+                let tab = &tab;
+
+                scope.spawn(move || {
+                    chunk.iter().map(|s| tab.intern(s)).collect()
+                })
+            })
+            .collect();
+
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+
+    assert_eq!(tab.len(), 100);
+
+    for interned in &results[1..] {
+        for (a, b) in results[0].iter().zip(interned.iter()) {
+            assert!(::std::ptr::eq(*a, *b));
+        }
+    }
+
+    assert!(::std::ptr::eq(tab.get("id7").unwrap(), results[0][7]));
+    assert_eq!(tab.get("id100"), None);
+
+    let mut merged = StrIntern::from(tab);
+
+    assert_eq!(merged.len(), 100);
+    assert!(::std::ptr::eq(merged.intern("id7"), results[0][7]));
+}
+
+#[test]
+fn test_str_intern_stats() {
+    let mut table = StrIntern::new();
+
+    assert_eq!(table.stats().count, 0);
+    assert_eq!(table.stats().occupancy(), 0.0);
+
+    for str in ["a", "bb", "ccc", "dddd", "bb"] {
+        table.intern(str);
+    }
+
+    let stats = table.stats_with_lengths();
+
+    assert_eq!(stats.count, 4);
+    assert_eq!(stats.bytes, 10);
+    assert!(stats.capacity >= 4);
+    assert_eq!(stats.heap_size, table.heap_size());
+    assert_eq!(stats.lengths, Some(vec![(1, 1), (3, 2), (7, 1)]));
+    assert!(stats.occupancy() > 0.0 && stats.occupancy() <= 1.0);
+    assert_eq!(table.stats().lengths, None);
+}
+
+#[test]
+fn test_str_intern_stats_buckets() {
+    let mut table = StrIntern::new();
+
+    assert_eq!(table.stats_with_lengths().lengths, Some(vec![]));
+
+    // The empty string has a bucket of its own, and each power of two
+    // starts a new one.
+    for str in ["", "a", "bc", "def", "ghij", "klmnopq", "rstuvwxy"] {
+        table.intern(str);
+    }
+
+    let stats = table.stats_with_lengths();
+
+    assert_eq!(stats.count, 7);
+    assert_eq!(stats.bytes, 25);
+    assert_eq!(stats.lengths,
+               Some(vec![(0, 1), (1, 1), (3, 2), (7, 2), (15, 1)]));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_sync_str_intern_stats() {
+    let tab = SyncStrIntern::new();
+
+    assert_eq!(tab.stats().count, 0);
+    assert_eq!(tab.stats().bytes, 0);
+
+    for str in ["", "a", "bc", "a"] {
+        tab.intern(str);
+    }
+
+    let stats = tab.stats_with_lengths();
+
+    assert_eq!(stats.count, 3);
+    assert_eq!(stats.bytes, 3);
+    assert_eq!(stats.heap_size, tab.heap_size());
+    assert_eq!(stats.lengths, Some(vec![(0, 1), (1, 1), (3, 1)]));
+}
@@ -1 +1,3 @@
+pub mod arena;
 pub mod intern;
+pub mod width;
@@ -0,0 +1,476 @@
+//! Columns in text.
+//!
+//! A column can be counted in bytes, characters, UTF-16 code units
+//! (as the Language Server Protocol does), or cells on a terminal,
+//! where CJK ideographs and most emoji take two cells and combining
+//! marks none.  `ColumnMode` selects one of these.
+//!
+//! Display widths follow Unicode's East Asian Width property, and
+//! grapheme clusters its extended grapheme cluster rules, for the
+//! characters that matter in source code and diagnostics: combining
+//! marks, variation selectors, emoji modifiers and zero width joiner
+//! sequences, regional indicator pairs, Hangul jamo, and CR LF.  The
+//! tables are compact approximations rather than the full Unicode
+//! database, which this crate does not carry.
+
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+use std::boxed::Box;
+use std::str::CharIndices;
+use std::string::String;
+
+/// How columns are counted.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ColumnMode {
+    /// Each byte is a column.
+    Bytes,
+    /// Each character is a column.  This is how columns are counted
+    /// throughout Salt, unless a different mode is asked for.
+    #[default]
+    Chars,
+    /// Each grapheme cluster takes its width in cells on a terminal.
+    DisplayWidth,
+    /// Each UTF-16 code unit is a column.
+    Utf16
+}
+
+/// Where tabs advance to: stops every `width` columns.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TabStops {
+    width: usize
+}
+
+/// Iterator over the grapheme clusters of a string.
+#[derive(Clone, Debug)]
+pub struct Graphemes<'s> {
+    str: &'s str,
+    chars: CharIndices<'s>
+}
+
+/// Characters that take two cells: East Asian Wide and Fullwidth
+/// characters, and emoji presented as pictures by default.
+const WIDE: &[(char, char)] = &[
+    ('\u{1100}', '\u{115f}'), ('\u{231a}', '\u{231b}'),
+    ('\u{2329}', '\u{232a}'), ('\u{23e9}', '\u{23ec}'),
+    ('\u{23f0}', '\u{23f0}'), ('\u{23f3}', '\u{23f3}'),
+    ('\u{25fd}', '\u{25fe}'), ('\u{2614}', '\u{2615}'),
+    ('\u{2648}', '\u{2653}'), ('\u{267f}', '\u{267f}'),
+    ('\u{2693}', '\u{2693}'), ('\u{26a1}', '\u{26a1}'),
+    ('\u{26aa}', '\u{26ab}'), ('\u{26bd}', '\u{26be}'),
+    ('\u{26c4}', '\u{26c5}'), ('\u{26ce}', '\u{26ce}'),
+    ('\u{26d4}', '\u{26d4}'), ('\u{26ea}', '\u{26ea}'),
+    ('\u{26f2}', '\u{26f3}'), ('\u{26f5}', '\u{26f5}'),
+    ('\u{26fa}', '\u{26fa}'), ('\u{26fd}', '\u{26fd}'),
+    ('\u{2705}', '\u{2705}'), ('\u{270a}', '\u{270b}'),
+    ('\u{2728}', '\u{2728}'), ('\u{274c}', '\u{274c}'),
+    ('\u{274e}', '\u{274e}'), ('\u{2753}', '\u{2755}'),
+    ('\u{2757}', '\u{2757}'), ('\u{2795}', '\u{2797}'),
+    ('\u{27b0}', '\u{27b0}'), ('\u{27bf}', '\u{27bf}'),
+    ('\u{2b1b}', '\u{2b1c}'), ('\u{2b50}', '\u{2b50}'),
+    ('\u{2b55}', '\u{2b55}'), ('\u{2e80}', '\u{303e}'),
+    ('\u{3041}', '\u{33ff}'), ('\u{3400}', '\u{4dbf}'),
+    ('\u{4e00}', '\u{9fff}'), ('\u{a000}', '\u{a4cf}'),
+    ('\u{a960}', '\u{a97f}'), ('\u{ac00}', '\u{d7a3}'),
+    ('\u{f900}', '\u{faff}'), ('\u{fe10}', '\u{fe19}'),
+    ('\u{fe30}', '\u{fe6f}'), ('\u{ff00}', '\u{ff60}'),
+    ('\u{ffe0}', '\u{ffe6}'), ('\u{16fe0}', '\u{16fe4}'),
+    ('\u{17000}', '\u{18cff}'), ('\u{1b000}', '\u{1b2ff}'),
+    ('\u{1f004}', '\u{1f004}'), ('\u{1f0cf}', '\u{1f0cf}'),
+    ('\u{1f18e}', '\u{1f18e}'), ('\u{1f191}', '\u{1f19a}'),
+    ('\u{1f200}', '\u{1f202}'), ('\u{1f210}', '\u{1f23b}'),
+    ('\u{1f240}', '\u{1f248}'), ('\u{1f250}', '\u{1f251}'),
+    ('\u{1f260}', '\u{1f265}'), ('\u{1f300}', '\u{1f320}'),
+    ('\u{1f32d}', '\u{1f335}'), ('\u{1f337}', '\u{1f37c}'),
+    ('\u{1f37e}', '\u{1f393}'), ('\u{1f3a0}', '\u{1f3ca}'),
+    ('\u{1f3cf}', '\u{1f3d3}'), ('\u{1f3e0}', '\u{1f3f0}'),
+    ('\u{1f3f4}', '\u{1f3f4}'), ('\u{1f3f8}', '\u{1f43e}'),
+    ('\u{1f440}', '\u{1f440}'), ('\u{1f442}', '\u{1f4fc}'),
+    ('\u{1f4ff}', '\u{1f53d}'), ('\u{1f54b}', '\u{1f54e}'),
+    ('\u{1f550}', '\u{1f567}'), ('\u{1f57a}', '\u{1f57a}'),
+    ('\u{1f595}', '\u{1f596}'), ('\u{1f5a4}', '\u{1f5a4}'),
+    ('\u{1f5fb}', '\u{1f64f}'), ('\u{1f680}', '\u{1f6c5}'),
+    ('\u{1f6cc}', '\u{1f6cc}'), ('\u{1f6d0}', '\u{1f6d2}'),
+    ('\u{1f6d5}', '\u{1f6d7}'), ('\u{1f6eb}', '\u{1f6ec}'),
+    ('\u{1f6f4}', '\u{1f6fc}'), ('\u{1f7e0}', '\u{1f7eb}'),
+    ('\u{1f90c}', '\u{1f93a}'), ('\u{1f93c}', '\u{1f945}'),
+    ('\u{1f947}', '\u{1f9ff}'), ('\u{1fa70}', '\u{1faff}'),
+    ('\u{20000}', '\u{2fffd}'), ('\u{30000}', '\u{3fffd}')
+];
+
+/// Characters that take no cells, and that extend the grapheme
+/// cluster before them: combining marks, joiners, variation
+/// selectors, emoji modifiers and tags, and Hangul medial vowels and
+/// final consonants.
+const EXTEND: &[(char, char)] = &[
+    ('\u{300}', '\u{36f}'), ('\u{483}', '\u{489}'),
+    ('\u{591}', '\u{5bd}'), ('\u{5bf}', '\u{5bf}'),
+    ('\u{5c1}', '\u{5c2}'), ('\u{5c4}', '\u{5c5}'),
+    ('\u{5c7}', '\u{5c7}'), ('\u{610}', '\u{61a}'),
+    ('\u{64b}', '\u{65f}'), ('\u{670}', '\u{670}'),
+    ('\u{6d6}', '\u{6dc}'), ('\u{6df}', '\u{6e4}'),
+    ('\u{6e7}', '\u{6e8}'), ('\u{6ea}', '\u{6ed}'),
+    ('\u{900}', '\u{903}'), ('\u{93a}', '\u{93c}'),
+    ('\u{93e}', '\u{94f}'), ('\u{951}', '\u{957}'),
+    ('\u{962}', '\u{963}'), ('\u{e31}', '\u{e31}'),
+    ('\u{e34}', '\u{e3a}'), ('\u{e47}', '\u{e4e}'),
+    ('\u{1160}', '\u{11ff}'), ('\u{1ab0}', '\u{1aff}'),
+    ('\u{1dc0}', '\u{1dff}'), ('\u{200c}', '\u{200d}'),
+    ('\u{20d0}', '\u{20ff}'), ('\u{302a}', '\u{302f}'),
+    ('\u{3099}', '\u{309a}'), ('\u{d7b0}', '\u{d7ff}'),
+    ('\u{fe00}', '\u{fe0f}'), ('\u{fe20}', '\u{fe2f}'),
+    ('\u{1f3fb}', '\u{1f3ff}'), ('\u{e0020}', '\u{e007f}'),
+    ('\u{e0100}', '\u{e01ef}')
+];
+
+/// Characters that take no cells but do not join with others.
+const ZERO_WIDTH: &[(char, char)] = &[
+    ('\u{200b}', '\u{200b}'), ('\u{200e}', '\u{200f}'),
+    ('\u{2060}', '\u{2064}'), ('\u{feff}', '\u{feff}')
+];
+
+const ZWJ: char = '\u{200d}';
+const EMOJI_PRESENTATION: char = '\u{fe0f}';
+
+/// Check whether `c` is in one of the sorted ranges `table`.
+fn in_table(table: &[(char, char)], c: char) -> bool {
+    table.binary_search_by(|&(lo, hi)| {
+        if hi < c {
+            ::std::cmp::Ordering::Less
+        } else if lo > c {
+            ::std::cmp::Ordering::Greater
+        } else {
+            ::std::cmp::Ordering::Equal
+        }
+    }).is_ok()
+}
+
+/// Check whether `c` is a regional indicator, half of a flag.
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1f1e6}'..='\u{1f1ff}').contains(&c)
+}
+
+/// Get the number of cells `c` takes on a terminal.  Control
+/// characters other than tab take none.  A tab's width depends on
+/// where it is, and is counted as one here; `TabStops` knows better.
+pub fn char_width(c: char) -> usize {
+    if c == '\t' {
+        1
+    } else if c < ' ' || ('\u{7f}'..'\u{a0}').contains(&c) ||
+              in_table(EXTEND, c) || in_table(ZERO_WIDTH, c) {
+        0
+    } else if in_table(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Get the number of cells the grapheme cluster `grapheme` takes on a
+/// terminal.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    let mut chars = grapheme.chars();
+
+    match chars.next() {
+        Some(c) if is_regional_indicator(c) => 2,
+        Some(c) if grapheme.contains(EMOJI_PRESENTATION) =>
+            char_width(c).max(2),
+        Some(c) => char_width(c),
+        None => 0
+    }
+}
+
+/// Get the number of cells `str` takes on a terminal, starting at the
+/// start of a line, with the default tab stops.
+pub fn display_width(str: &str) -> usize {
+    TabStops::default().visual_width(str)
+}
+
+/// Get an iterator over the grapheme clusters in `str`.
+pub fn graphemes(str: &str) -> Graphemes<'_> {
+    Graphemes { str, chars: str.char_indices() }
+}
+
+impl<'s> Iterator for Graphemes<'s> {
+    type Item = &'s str;
+
+    fn next(&mut self) -> Option<&'s str> {
+        let (start, first) = self.chars.next()?;
+        let mut prev = first;
+        let mut end = start + first.len_utf8();
+        let mut rest = self.chars.clone();
+
+        while let Some((i, c)) = rest.next() {
+            let joins = match (prev, c) {
+                ('\r', '\n') => true,
+                ('\r', _) | ('\n', _) => false,
+                (ZWJ, _) => true,
+                (_, c) if in_table(EXTEND, c) => true,
+                (p, c) => is_regional_indicator(p) &&
+                          is_regional_indicator(c) &&
+                          self.str[start..i].chars().count() == 1
+            };
+
+            if !joins {
+                break;
+            }
+
+            prev = c;
+            end = i + c.len_utf8();
+            self.chars = rest.clone();
+        }
+
+        Some(&self.str[start..end])
+    }
+}
+
+impl TabStops {
+    /// Create tab stops every `width` columns.  Panics if `width` is 0.
+    pub fn new(width: usize) -> TabStops {
+        assert!(width > 0, "tab stops must be at least one column apart");
+
+        TabStops { width }
+    }
+
+    /// Get the number of columns between tab stops.
+    pub fn width(self) -> usize {
+        self.width
+    }
+
+    /// Get the column, counting from 0, that a tab at `col` advances
+    /// to.
+    pub fn next(self, col: usize) -> usize {
+        (col / self.width + 1) * self.width
+    }
+
+    /// Get the visual column, counting from 0, after the grapheme
+    /// cluster `grapheme` shown at visual column `col`.
+    fn advance(self, col: usize, grapheme: &str) -> usize {
+        if grapheme == "\t" {
+            self.next(col)
+        } else {
+            col + grapheme_width(grapheme)
+        }
+    }
+
+    /// Get the number of cells `text` takes on a terminal, starting at
+    /// the start of a line.
+    pub fn visual_width(self, text: &str) -> usize {
+        graphemes(text).fold(0, |col, grapheme| self.advance(col, grapheme))
+    }
+
+    /// Get the visual column, counting from 0, of character column
+    /// `col` in `line`.  Columns past the end of the line take one
+    /// cell each.
+    pub fn visual_column(self, line: &str, col: usize) -> usize {
+        let (offset, extra) = match line.char_indices().nth(col) {
+            Some((offset, _)) => (offset, 0),
+            None => (line.len(), col - line.chars().count())
+        };
+
+        self.visual_width(&line[..offset]) + extra
+    }
+
+    /// Get the character column, counting from 0, of the character
+    /// shown at visual column `col` in `line`.  Columns in the middle
+    /// of a tab or a wide character refer to that character.  Columns
+    /// past the end of the line take one cell each.
+    pub fn char_column(self, line: &str, col: usize) -> usize {
+        let mut visual = 0;
+        let mut chars = 0;
+
+        for grapheme in graphemes(line) {
+            let next = self.advance(visual, grapheme);
+
+            if next > col {
+                return chars;
+            }
+
+            visual = next;
+            chars += grapheme.chars().count();
+        }
+
+        chars + (col - visual)
+    }
+
+    /// Replace the tabs in `line` with spaces up to the next tab stop.
+    pub fn expand(self, line: &str) -> String {
+        let mut out = String::with_capacity(line.len());
+        let mut col = 0;
+
+        for grapheme in graphemes(line) {
+            let next = self.advance(col, grapheme);
+
+            if grapheme == "\t" {
+                out.extend((col..next).map(|_| ' '));
+            } else {
+                out.push_str(grapheme);
+            }
+
+            col = next;
+        }
+
+        out
+    }
+}
+
+/// Tab stops are every 8 columns by default.
+impl Default for TabStops {
+    fn default() -> TabStops {
+        TabStops { width: 8 }
+    }
+}
+
+impl StableHash for TabStops {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.width.stable_hash(hasher)
+    }
+}
+
+impl StableHash for ColumnMode {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let tag: u8 = match self {
+            ColumnMode::Bytes => 0,
+            ColumnMode::Chars => 1,
+            ColumnMode::DisplayWidth => 2,
+            ColumnMode::Utf16 => 3
+        };
+
+        tag.stable_hash(hasher)
+    }
+}
+
+impl ColumnMode {
+    /// Get the width of `text`, from the start of a line, in columns.
+    /// When counting display width, tabs advance to the next of the
+    /// tab stops `tabs`.
+    pub fn width(self, text: &str, tabs: TabStops) -> usize {
+        match self {
+            ColumnMode::Bytes => text.len(),
+            ColumnMode::Chars => text.chars().count(),
+            ColumnMode::DisplayWidth => tabs.visual_width(text),
+            ColumnMode::Utf16 => text.chars().map(char::len_utf16).sum()
+        }
+    }
+
+    /// Get the units that columns are stepped over by in `text`.
+    fn units(self, text: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            ColumnMode::DisplayWidth => Box::new(graphemes(text)),
+            _ => Box::new(text.char_indices()
+                          .map(move |(i, c)| &text[i..i + c.len_utf8()]))
+        }
+    }
+
+    /// Get the column, counting from 0, at byte offset `offset` in
+    /// `line`, with tab stops `tabs`.  `offset` must be on a character
+    /// boundary.
+    pub fn column(self, line: &str, offset: usize, tabs: TabStops) -> usize {
+        self.width(&line[..offset], tabs)
+    }
+
+    /// Get the byte offset in `line` of column `col`, counting from 0,
+    /// with tab stops `tabs`.  The column just past the end of the line
+    /// is valid.  Returns `None` if `col` is past that, or in the
+    /// middle of a character, or of a grapheme cluster or tab when
+    /// counting display width.
+    pub fn offset(self, line: &str, col: usize,
+                  tabs: TabStops) -> Option<usize> {
+        let mut offset = 0;
+        let mut pos = 0;
+
+        for unit in self.units(line) {
+            if pos >= col {
+                break;
+            }
+
+            pos = match self {
+                ColumnMode::DisplayWidth => tabs.advance(pos, unit),
+                _ => pos + self.width(unit, tabs)
+            };
+            offset += unit.len();
+        }
+
+        if pos == col {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn test_display_width() {
+    assert_eq!(display_width("abc"), 3);
+    assert_eq!(display_width("日本語"), 6);
+    assert_eq!(display_width("e\u{301}"), 1);
+    assert_eq!(display_width("😀"), 2);
+    assert_eq!(display_width("👍🏽"), 2);
+    assert_eq!(display_width("👨\u{200d}👩\u{200d}👧"), 2);
+    assert_eq!(display_width("🇫🇷"), 2);
+    assert_eq!(display_width("\u{2764}\u{fe0f}"), 2);
+    assert_eq!(display_width("a\u{200b}b"), 2);
+    assert_eq!(display_width("\x1b"), 0);
+}
+
+#[test]
+fn test_graphemes() {
+    let text = "ae\u{301}\r\n🇫🇷🇩🇪👨\u{200d}👩한";
+
+    assert_eq!(graphemes(text).collect::<Vec<_>>(),
+               ["a", "e\u{301}", "\r\n", "🇫🇷", "🇩🇪",
+                "👨\u{200d}👩", "한"]);
+    assert_eq!(graphemes("").next(), None);
+}
+
+#[test]
+fn test_column_mode() {
+    let line = "aé日😀b";
+    let end = line.len();
+
+    let tabs = TabStops::default();
+
+    assert_eq!(ColumnMode::Bytes.column(line, end, tabs), 11);
+    assert_eq!(ColumnMode::Chars.column(line, end, tabs), 5);
+    assert_eq!(ColumnMode::DisplayWidth.column(line, end, tabs), 7);
+    assert_eq!(ColumnMode::Utf16.column(line, end, tabs), 6);
+
+    for mode in [ColumnMode::Bytes, ColumnMode::Chars,
+                 ColumnMode::DisplayWidth, ColumnMode::Utf16].iter() {
+        for (offset, _) in line.char_indices().chain(Some((end, ' '))) {
+            let col = mode.column(line, offset, tabs);
+
+            assert_eq!(mode.offset(line, col, tabs), Some(offset),
+                       "{:?}", mode);
+        }
+    }
+
+    assert_eq!(ColumnMode::DisplayWidth.offset(line, 3, tabs), None);
+    assert_eq!(ColumnMode::Utf16.offset(line, 4, tabs), None);
+    assert_eq!(ColumnMode::Bytes.offset(line, 2, tabs), None);
+    assert_eq!(ColumnMode::Chars.offset(line, 6, tabs), None);
+}
+
+#[test]
+fn test_tab_stops() {
+    let line = "a\tb\t\t日c";
+    let eight = TabStops::default();
+    let four = TabStops::new(4);
+
+    assert_eq!(eight.next(0), 8);
+    assert_eq!(eight.next(8), 16);
+    assert_eq!(eight.visual_width(line), 27);
+    assert_eq!(four.visual_width(line), 15);
+    assert_eq!(four.expand(line), "a   b       日c");
+
+    // Character columns: a=0 tab=1 b=2 tab=3 tab=4 日=5 c=6.
+    for (char_col, visual) in [(0, 0), (1, 1), (2, 4), (3, 5), (4, 8),
+                               (5, 12), (6, 14), (7, 15), (9, 17)].iter() {
+        assert_eq!(four.visual_column(line, *char_col), *visual);
+        assert_eq!(four.char_column(line, *visual), *char_col);
+    }
+
+    assert_eq!(four.char_column(line, 3), 1);
+    assert_eq!(four.char_column(line, 13), 5);
+    assert_eq!(ColumnMode::DisplayWidth.offset(line, 3, four), None);
+    assert_eq!(ColumnMode::DisplayWidth.offset(line, 4, four), Some(2));
+}
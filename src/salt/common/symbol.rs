@@ -1,67 +1,777 @@
 use core::clone::Clone;
+use std::cell::RefCell;
+#[cfg(feature = "std")]
 use std::convert::AsRef;
+use std::convert::TryFrom;
 use std::cmp::Ordering;
+#[cfg(not(feature = "std"))]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::marker::PhantomData;
+#[cfg(feature = "std")]
 use std::path::Path;
+use std::slice;
+use std::str;
+use std::string::String;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+use std::vec::Vec;
+#[cfg(feature = "std")]
+use salt::common::cache::DecodeError;
+#[cfg(feature = "std")]
+use salt::common::cache::DecodeResult;
+#[cfg(feature = "std")]
+use salt::common::cache::Decodable;
+#[cfg(feature = "std")]
+use salt::common::cache::Decoder;
+#[cfg(feature = "std")]
+use salt::common::cache::Encodable;
+#[cfg(feature = "std")]
+use salt::common::cache::Encoder;
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+#[cfg(feature = "std")]
+use salt::common::heapsize::hash_table_size;
+use salt::common::str::arena::StrArena;
+use salt::common::str::intern::InternStats;
+use salt::common::str::intern::StrIntern;
+
+/// The longest string whose `Symbol` is made without interning it.
+pub const INLINE_LEN: usize = 8;
+
+/// Bit set in `Symbol::len` for short symbols, which are compared by
+/// their bytes.  No string is long enough to have this bit set in its
+/// length.
+const INLINE_TAG: usize = !(usize::MAX >> 1);
 
 /// A distinguished type for symbols.  These are implemented as
 /// references to interned strings, making comparison very easy.
-#[derive(Copy, Eq, Ord)]
-pub struct Symbol<'a>(&'a str);
+///
+/// Most identifiers are short, so strings of up to `INLINE_LEN` bytes
+/// are not interned.  Such a short symbol holds a pointer to the
+/// caller's copy of the string, which must live for `'a`, and its
+/// length tagged with `INLINE_TAG`; none of the string's bytes are
+/// kept in the symbol itself.  Whether a symbol is short depends only
+/// on the length of its string, so short symbols compare by the bytes
+/// they point to, at most `INLINE_LEN` of them, and interned ones by
+/// address, and comparison stays O(1) either way.  Either way, a
+/// symbol is the size of a `&str`.
+#[derive(Clone, Copy)]
+pub struct Symbol<'a> {
+    /// The address of the string.
+    ptr: *const u8,
+    /// The length of the string, with `INLINE_TAG` set if the string
+    /// is short.
+    len: usize,
+    marker: PhantomData<&'a str>
+}
+
+// Symbols are shared references to strings, so they can be shared
+// between threads like a `&str`.
+unsafe impl<'a> Send for Symbol<'a> {}
+unsafe impl<'a> Sync for Symbol<'a> {}
+
+/// Get the `Symbol` for a string literal of at most `INLINE_LEN` bytes,
+/// as a constant.  This needs no interner, and costs nothing at run
+/// time, which makes it suitable for well-known names such as keywords
+/// used in hot comparisons.  Longer names are rejected at compile time;
+/// declare them with `well_known_symbols!` instead.
+#[macro_export]
+macro_rules! intern {
+    ($name:expr) => {{
+        const SYM: $crate::salt::common::symbol::Symbol<'static> =
+            match $crate::salt::common::symbol::Symbol::inline($name) {
+                Some(sym) => sym,
+                None => panic!("intern! names must be at most 8 bytes")
+            };
+
+        SYM
+    }}
+}
+
+/// Declare statics for well-known symbols, along with a function that
+/// registers them with an interner.  The function must be called on a
+/// new interner, before anything else is interned, so that the
+/// interner's copies of these strings are the statics themselves:
+///
+/// ```ignore
+/// well_known_symbols! {
+///     pub fn register_symbols;
+///     INITIALIZE = "initialize";
+///     SELF = "self";
+/// }
+/// ```
+///
+/// Symbols short enough to be compared by their bytes need no
+/// registration, but may be declared here too, to keep well-known
+/// names in one place.
+#[macro_export]
+macro_rules! well_known_symbols {
+    ($(#[$attr:meta])* $vis:vis fn $register:ident;
+     $($(#[$sattr:meta])* $name:ident = $text:expr;)*) => {
+        $($(#[$sattr])*
+          $vis static $name: $crate::salt::common::symbol::Symbol<'static> =
+              $crate::salt::common::symbol::Symbol::from_static($text);)*
+
+        $(#[$attr])*
+        /// Register the well-known symbols with `intern`, which must
+        /// not have interned any of them already.  Panics if it has.
+        $vis fn $register<'a>(
+            intern: &mut $crate::salt::common::str::intern::StrIntern<'a>) {
+            $(let text: &'static str = $name.as_str();
+
+              if text.len() > $crate::salt::common::symbol::INLINE_LEN {
+                  assert!(intern.intern(text).as_ptr() == text.as_ptr(),
+                          "symbol {:?} was interned before registration",
+                          text);
+              })*
+        }
+    }
+}
+
+/// Declare constants for keywords, each at most `INLINE_LEN` bytes,
+/// along with lists of them all.
+macro_rules! keywords {
+    ($($(#[$attr:meta])* $name:ident = $text:expr;)*) => {
+        $($(#[$attr])* pub const $name: Symbol<'static> = intern!($text);)*
+
+        /// All the keywords, in order of declaration.
+        pub const ALL: &[Symbol<'static>] = &[$($name),*];
+
+        /// The text of all the keywords, in the same order as `ALL`.
+        /// This is a static, so that tables holding these strings all
+        /// hold the same copies.
+        pub static STRINGS: &[&str] = &[$($text),*];
+    }
+}
+
+/// Symbols for Salt's keywords.  All are short enough to be compared
+/// by their bytes, so these are constants, and comparing a symbol with one
+/// needs no interner.
+///
+/// Frontends can start with tables already holding the keywords, from
+/// `interner` and `symbol_table`.  In the latter, keyword `ALL[i]` has
+/// the id `i`.
+pub mod kw {
+    use salt::common::str::intern::StrIntern;
+    use salt::common::symbol::Symbol;
+    use salt::common::symbol::SymbolTable;
+
+    keywords! {
+        AS = "as";
+        BREAK = "break";
+        CASE = "case";
+        CONST = "const";
+        CONTINUE = "continue";
+        ELSE = "else";
+        ENUM = "enum";
+        FALSE = "false";
+        FN = "fn";
+        FOR = "for";
+        IF = "if";
+        IMPORT = "import";
+        IN = "in";
+        LET = "let";
+        MATCH = "match";
+        MODULE = "module";
+        MUT = "mut";
+        RETURN = "return";
+        SELF = "self";
+        STATIC = "static";
+        STRUCT = "struct";
+        TRAIT = "trait";
+        TRUE = "true";
+        TYPE = "type";
+        USE = "use";
+        WHERE = "where";
+        WHILE = "while";
+    }
+
+    /// Check whether `sym` is a keyword.
+    pub fn is_keyword(sym: Symbol) -> bool {
+        ALL.contains(&sym)
+    }
+
+    /// Create a `StrIntern` holding the keywords.
+    pub fn interner<'a>() -> StrIntern<'a> {
+        StrIntern::with_strings(STRINGS)
+    }
+
+    /// Create a `SymbolTable` in which the keywords have the first ids,
+    /// in the order of `ALL`.
+    pub fn symbol_table<'a>() -> SymbolTable<'a> {
+        SymbolTable::with_symbols(ALL)
+    }
+}
+
+/// A qualified name: a path of symbols, outermost first, written
+/// `a::b::c`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct QualName<'a>(pub Vec<Symbol<'a>>);
+
+/// The namespace a name is declared in.  A name may be declared once
+/// in each, so the type `Foo` and the value `Foo` are different.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Namespace {
+    /// Values: variables, functions and constants.
+    Value,
+    /// Types.
+    Type,
+    /// Modules.
+    Module
+}
+
+/// A symbol in a namespace.  Like `Symbol`, this compares, orders and
+/// hashes by the identity of the interned string, and then by
+/// namespace.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct QualifiedSymbol<'a> {
+    /// The symbol.
+    pub sym: Symbol<'a>,
+    /// The namespace.
+    pub ns: Namespace
+}
+
+/// The character generated names start with.  It cannot start an
+/// identifier, so generated names never collide with those in source.
+pub const GENERATED_MARK: char = '%';
+
+/// A counter for generated names, such as `%tmp.17`.  Each name gets
+/// the next number, whatever its prefix, so names from one counter
+/// never repeat.  Save the counter with the rest of the state, so
+/// that names generated after it is restored are still fresh.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Gensym(u64);
+
+/// Context for creating generated names.
+pub trait GensymCtx<'a>: SymbolCtx<'a> {
+    /// Create a symbol named `prefix` followed by a number, which is
+    /// different from all other symbols, generated or not.
+    fn gensym(&mut self, prefix: &str) -> Symbol<'a>;
+}
+
+/// A small integer handle for a symbol, issued by a `SymbolTable`.
+/// Unlike a `Symbol`, this has no lifetime, so it can be stored in IR
+/// nodes and serialized; it means nothing without the table that
+/// issued it.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SymbolId(u32);
+
+/// A table numbering symbols, for converting between `Symbol`s and
+/// `SymbolId`s.  Ids are issued in order, starting at 0.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable<'a> {
+    syms: Vec<Symbol<'a>>,
+    ids: IdTable<'a>
+}
+
+/// The ids issued by a `SymbolTable`.  As with `StrIntern`'s table,
+/// this is a B-tree without the standard library.
+#[cfg(feature = "std")]
+type IdTable<'a> = HashMap<Symbol<'a>, SymbolId>;
+#[cfg(not(feature = "std"))]
+type IdTable<'a> = BTreeMap<Symbol<'a>, SymbolId>;
 
 /// Context for creating Symbols.
 pub trait SymbolCtx<'a> {
     /// Convert `fname` into a corresponding `Symbol`.
     fn symbol(&mut self, fname: &'a str) -> Symbol<'a>;
+
+    /// Convert `fname` into a `QualifiedSymbol` in the namespace `ns`.
+    fn qualified_symbol(&mut self, ns: Namespace,
+                        fname: &'a str) -> QualifiedSymbol<'a> {
+        QualifiedSymbol::new(ns, self.symbol(fname))
+    }
+
+    /// Convert `fname` into a `QualifiedSymbol` for a value.
+    fn value_symbol(&mut self, fname: &'a str) -> QualifiedSymbol<'a> {
+        self.qualified_symbol(Namespace::Value, fname)
+    }
+
+    /// Convert `fname` into a `QualifiedSymbol` for a type.
+    fn type_symbol(&mut self, fname: &'a str) -> QualifiedSymbol<'a> {
+        self.qualified_symbol(Namespace::Type, fname)
+    }
+
+    /// Convert `fname` into a `QualifiedSymbol` for a module.
+    fn module_symbol(&mut self, fname: &'a str) -> QualifiedSymbol<'a> {
+        self.qualified_symbol(Namespace::Module, fname)
+    }
+}
+
+/// Context for creating Symbols through a shared reference, so that
+/// one context can be used from several places at once.
+///
+/// As with `SharedFilenameCtx`, any `SymbolCtx` in a `RefCell` or a
+/// `Mutex` is one, and a reference to one is a `SymbolCtx`.
+pub trait SharedSymbolCtx<'a> {
+    /// Convert `fname` into a corresponding `Symbol`.
+    fn symbol(&self, fname: &'a str) -> Symbol<'a>;
+}
+
+/// Panics if the context is already borrowed.
+impl<'a, C: SymbolCtx<'a>> SharedSymbolCtx<'a> for RefCell<C> {
+    fn symbol(&self, fname: &'a str) -> Symbol<'a> {
+        self.borrow_mut().symbol(fname)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, C: SymbolCtx<'a>> SharedSymbolCtx<'a> for Mutex<C> {
+    fn symbol(&self, fname: &'a str) -> Symbol<'a> {
+        self.lock().unwrap_or_else(|err| err.into_inner()).symbol(fname)
+    }
+}
+
+impl<'a, C> SymbolCtx<'a> for &C
+    where C: SharedSymbolCtx<'a> + ?Sized {
+    fn symbol(&mut self, fname: &'a str) -> Symbol<'a> {
+        SharedSymbolCtx::symbol(*self, fname)
+    }
+}
+
+/// Short strings never reach the interner; their symbols point to the
+/// caller's copy.
+impl<'a> SymbolCtx<'a> for StrIntern<'a> {
+    fn symbol(&mut self, fname: &'a str) -> Symbol<'a> {
+        if fname.len() <= INLINE_LEN {
+            Symbol::from_interned(fname)
+        } else {
+            Symbol::from_interned(self.intern(fname))
+        }
+    }
+}
+
+impl<'a> Symbol<'a> {
+    /// Create a symbol referring to the interned string `str`, which is
+    /// compared by address.
+    const fn interned(str: &'a str) -> Symbol<'a> {
+        Symbol { ptr: str.as_ptr(), len: str.len(), marker: PhantomData }
+    }
+
+    /// Create a symbol from `str`, which must be interned unless it is
+    /// short enough to be compared by its bytes.
+    fn from_interned(str: &'a str) -> Symbol<'a> {
+        Symbol::inline(str).unwrap_or(Symbol::interned(str))
+    }
+
+    /// Create a symbol pointing to `str` without an interner, if it is
+    /// short enough to be compared by its bytes.
+    pub const fn inline(str: &'a str) -> Option<Symbol<'a>> {
+        if str.len() <= INLINE_LEN {
+            Some(Symbol { ptr: str.as_ptr(), len: str.len() | INLINE_TAG,
+                          marker: PhantomData })
+        } else {
+            None
+        }
+    }
+
+    /// Create a symbol for a static string.  A long string must be
+    /// registered with the interner, by interning this symbol's
+    /// string before anything else, for the symbol to be equal to
+    /// those the interner produces; `well_known_symbols!` arranges
+    /// this.
+    pub const fn from_static(str: &'static str) -> Symbol<'static> {
+        match Symbol::inline(str) {
+            Some(sym) => sym,
+            None => Symbol::interned(str)
+        }
+    }
+
+    /// Check whether this symbol was created by `gensym`.
+    pub fn is_generated(&self) -> bool {
+        self.as_str().starts_with(GENERATED_MARK)
+    }
+
+    fn is_inline(&self) -> bool {
+        self.len & INLINE_TAG != 0
+    }
+
+    /// Get the string for this symbol.
+    pub fn as_str(&self) -> &'a str {
+        // Every symbol holds the address of a `str` of `len` bytes that
+        // lives for `'a`.
+        unsafe {
+            str::from_utf8_unchecked(slice::from_raw_parts(
+                self.ptr, self.len & !INLINE_TAG))
+        }
+    }
+
+    /// Get the order key for this symbol: the bytes of a short string,
+    /// padded with zeros, or the address of an interned one.
+    fn key(&self) -> (bool, u64) {
+        if self.is_inline() {
+            let mut bytes = [0; INLINE_LEN];
+            let str = self.as_str().as_bytes();
+
+            bytes[..str.len()].copy_from_slice(str);
+
+            (true, u64::from_be_bytes(bytes))
+        } else {
+            (false, self.ptr as usize as u64)
+        }
+    }
+}
+
+impl<'a> QualName<'a> {
+    /// Create a `QualName` from its components, outermost first.
+    pub fn new(path: Vec<Symbol<'a>>) -> QualName<'a> {
+        QualName(path)
+    }
+
+    /// Get the components of the name, outermost first.
+    pub fn path(&self) -> &[Symbol<'a>] {
+        &self.0
+    }
+
+    /// Get the innermost component of the name, if there is one.
+    pub fn last(&self) -> Option<Symbol<'a>> {
+        self.0.last().cloned()
+    }
+}
+
+impl Gensym {
+    /// Create a counter starting at 0.
+    pub fn new() -> Gensym {
+        Gensym(0)
+    }
+
+    /// Get the number the next name will have.
+    pub fn count(&self) -> u64 {
+        self.0
+    }
+
+    /// Get the next name with the prefix `prefix`, as a string.
+    pub fn name(&mut self, prefix: &str) -> String {
+        let name = format!("{}{}.{}", GENERATED_MARK, prefix, self.0);
+
+        self.0 += 1;
+
+        name
+    }
+
+    /// Create a symbol for the next name with the prefix `prefix`,
+    /// keeping the name in `arena`.
+    pub fn fresh<'a>(&mut self, arena: &'a StrArena,
+                     prefix: &str) -> Symbol<'a> {
+        let name = self.name(prefix);
+
+        Symbol::from_interned(arena.intern(&name))
+    }
 }
 
-impl<'a> Clone for Symbol<'a> {
-    fn clone(&self) -> Symbol<'a> {
-        Symbol(self.0)
+impl Namespace {
+    /// Get the name of the namespace, for messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Namespace::Value => "value",
+            Namespace::Type => "type",
+            Namespace::Module => "module"
+        }
+    }
+}
+
+impl<'a> QualifiedSymbol<'a> {
+    /// Create a `QualifiedSymbol` for `sym` in the namespace `ns`.
+    pub fn new(ns: Namespace, sym: Symbol<'a>) -> QualifiedSymbol<'a> {
+        QualifiedSymbol { sym, ns }
+    }
+
+    /// Get the string for this symbol.
+    pub fn as_str(&self) -> &'a str {
+        self.sym.as_str()
+    }
+}
+
+impl SymbolId {
+    /// Get the index of this id in its table.
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl<'a> SymbolTable<'a> {
+    /// Create an empty `SymbolTable`.
+    pub fn new() -> SymbolTable<'a> {
+        SymbolTable { syms: Vec::new(), ids: IdTable::new() }
+    }
+
+    /// Create a `SymbolTable` issuing ids to `syms` in order, so the
+    /// symbol `syms[i]` has the id `i`.  Repeated symbols keep their
+    /// first id, and take up no others.
+    pub fn with_symbols(syms: &[Symbol<'a>]) -> SymbolTable<'a> {
+        let mut table = SymbolTable::new();
+
+        for sym in syms {
+            table.id(*sym);
+        }
+
+        table
+    }
+
+    /// Get the id for `sym`, issuing a new one if it has none.  Panics
+    /// if the table is full.
+    pub fn id(&mut self, sym: Symbol<'a>) -> SymbolId {
+        let syms = &mut self.syms;
+
+        *self.ids.entry(sym).or_insert_with(|| {
+            let id = u32::try_from(syms.len()).expect("symbol table full");
+
+            syms.push(sym);
+
+            SymbolId(id)
+        })
+    }
+
+    /// Get the id for `sym`, if it has one.
+    pub fn get(&self, sym: Symbol<'a>) -> Option<SymbolId> {
+        self.ids.get(&sym).cloned()
+    }
+
+    /// Get the symbol for `id`.  Panics if `id` was not issued by this
+    /// table.
+    pub fn symbol(&self, id: SymbolId) -> Symbol<'a> {
+        self.syms[id.index()]
+    }
+
+    /// Get the string for `id`.  Panics if `id` was not issued by this
+    /// table.
+    pub fn resolve(&self, id: SymbolId) -> &'a str {
+        self.syms[id.index()].as_str()
+    }
+
+    /// Get the number of ids issued.
+    pub fn len(&self) -> usize {
+        self.syms.len()
+    }
+
+    /// Check whether no ids have been issued.
+    pub fn is_empty(&self) -> bool {
+        self.syms.is_empty()
+    }
+
+    /// Get the number of ids the table can hold without growing, and
+    /// the heap memory it uses for them.
+    #[cfg(feature = "std")]
+    fn id_table_size(&self) -> (usize, usize) {
+        let capacity = self.ids.capacity();
+
+        (capacity, hash_table_size::<(Symbol<'a>, SymbolId)>(capacity))
+    }
+
+    /// Get the number of ids the table can hold without growing, and
+    /// the heap memory it uses for them.
+    #[cfg(not(feature = "std"))]
+    fn id_table_size(&self) -> (usize, usize) {
+        let len = self.ids.len();
+
+        (len, len * ::std::mem::size_of::<(Symbol<'a>, SymbolId)>())
+    }
+
+    /// Get statistics on the table, with a histogram of lengths if
+    /// `lengths` is set.  The strings belong to the interner, so only
+    /// the table is counted in the heap size.
+    fn collect_stats(&self, lengths: bool) -> InternStats {
+        let (capacity, ids_size) = self.id_table_size();
+        let heap_size =
+            self.syms.capacity() * ::std::mem::size_of::<Symbol<'a>>() +
+            ids_size;
+
+        InternStats::new(self.syms.iter().map(Symbol::as_str),
+                         capacity, heap_size, lengths)
+    }
+
+    /// Get statistics on the table, without a histogram of lengths.
+    pub fn stats(&self) -> InternStats {
+        self.collect_stats(false)
+    }
+
+    /// Get statistics on the table, with a histogram of lengths.
+    pub fn stats_with_lengths(&self) -> InternStats {
+        self.collect_stats(true)
     }
 }
 
 impl<'a> Hash for Symbol<'a> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_usize(((self.0 as *const _) as *const u8) as usize);
+        state.write_u64(self.key().1);
+    }
+}
+
+/// Stable hashing is by content, not by address, so fingerprints do
+/// not depend on where the interned string happens to live.
+impl<'a> StableHash for Symbol<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self.as_str())
+    }
+}
+
+/// Symbols are written to the string table, and re-interned when read
+/// back.
+#[cfg(feature = "std")]
+impl<'a> Encodable for Symbol<'a> {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_interned(self.as_str())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Decodable<'a> for Symbol<'a> {
+    fn decode(dec: &mut Decoder<'a, '_>) -> DecodeResult<Symbol<'a>> {
+        dec.read_interned().map(Symbol::from_interned)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encodable for Gensym {
+    fn encode(&self, enc: &mut Encoder) {
+        self.0.encode(enc)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'d> Decodable<'d> for Gensym {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Gensym> {
+        u64::decode(dec).map(Gensym)
+    }
+}
+
+impl StableHash for Namespace {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u8(*self as u8)
+    }
+}
+
+impl<'a> StableHash for QualifiedSymbol<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.ns.stable_hash(hasher);
+        self.sym.stable_hash(hasher)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encodable for Namespace {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.emit_u8(*self as u8)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'d> Decodable<'d> for Namespace {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<Namespace> {
+        match dec.read_u8()? {
+            0 => Ok(Namespace::Value),
+            1 => Ok(Namespace::Type),
+            2 => Ok(Namespace::Module),
+            tag => Err(DecodeError::BadTag { what: "Namespace",
+                                             tag: tag as u64 })
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Encodable for QualifiedSymbol<'a> {
+    fn encode(&self, enc: &mut Encoder) {
+        self.ns.encode(enc);
+        self.sym.encode(enc)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Decodable<'a> for QualifiedSymbol<'a> {
+    fn decode(dec: &mut Decoder<'a, '_>) -> DecodeResult<QualifiedSymbol<'a>> {
+        let ns = Namespace::decode(dec)?;
+
+        Ok(QualifiedSymbol::new(ns, Symbol::decode(dec)?))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encodable for SymbolId {
+    fn encode(&self, enc: &mut Encoder) {
+        self.0.encode(enc)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'d> Decodable<'d> for SymbolId {
+    fn decode(dec: &mut Decoder<'d, '_>) -> DecodeResult<SymbolId> {
+        u32::decode(dec).map(SymbolId)
     }
 }
 
 impl<'a> PartialEq for Symbol<'a> {
     fn eq(&self, other: &Symbol<'a>) -> bool {
-        self.0 as *const _ == other.0 as *const _
+        self.len == other.len && self.key() == other.key()
+    }
+}
+
+impl<'a> Eq for Symbol<'a> {}
+
+/// Symbols are ordered by identity, not by content: short symbols by
+/// their bytes, and interned ones by address.
+impl<'a> Ord for Symbol<'a> {
+    fn cmp(&self, other: &Symbol<'a>) -> Ordering {
+        self.key().cmp(&other.key()).then(self.len.cmp(&other.len))
     }
 }
 
 impl<'a> PartialOrd for Symbol<'a> {
     fn partial_cmp(&self, other: &Symbol<'a>) -> Option<Ordering> {
-        let a = ((self.0 as *const _) as *const u8) as usize;
-        let b = ((other.0 as *const _) as *const u8) as usize;
-
-        Some(a.cmp(&b))
+        Some(self.cmp(other))
     }
 }
 
 impl<'a> Display for Symbol<'a> {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.as_str())
     }
 }
 
 impl<'a> Debug for Symbol<'a> {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl<'a> Display for QualName<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        for (i, sym) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, "::")?;
+            }
+
+            write!(f, "{}", sym)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Namespace {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Only the symbol is written; messages say which namespace is meant.
+impl<'a> Display for QualifiedSymbol<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.sym)
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> AsRef<Path> for Symbol<'a> {
     fn as_ref(&self) -> &Path {
-        Path::new(self.0)
+        Path::new(self.as_str())
     }
 }
 
@@ -69,8 +779,8 @@ impl<'a> AsRef<Path> for Symbol<'a> {
 fn test_ref_equality_mismatch() {
     let a = "helloa".split_at(5).0;
     let b = "hellob".split_at(5).0;
-    let fa = Symbol(a);
-    let fb = Symbol(b);
+    let fa = Symbol::interned(a);
+    let fb = Symbol::interned(b);
 
     assert_ne!(fa, fb)
 }
@@ -78,8 +788,234 @@ fn test_ref_equality_mismatch() {
 #[test]
 fn test_ref_equality_same() {
     let a = "hello";
-    let fa = Symbol(a);
-    let fb = Symbol(a);
+    let fa = Symbol::interned(a);
+    let fb = Symbol::interned(a);
 
     assert_eq!(fa, fb)
 }
+
+#[test]
+fn test_inline() {
+    let mut intern = StrIntern::new();
+    let a = String::from("short");
+    let b = String::from("short");
+    let sa = intern.symbol(&a);
+    let sb = intern.symbol(&b);
+    let long = intern.symbol("longer than eight");
+
+    assert_eq!(sa, sb);
+    assert_eq!(intern.len(), 1);
+    assert_eq!(sa.as_str(), "short");
+    assert_eq!(long.as_str(), "longer than eight");
+    assert_eq!(intern.symbol("").as_str(), "");
+    assert_eq!(intern.symbol("12345678").as_str(), "12345678");
+    assert_ne!(intern.symbol("a"), intern.symbol("a\0"));
+    assert_ne!(sa, long);
+    assert_eq!(Symbol::inline(&a), Some(sa));
+    assert_eq!(Symbol::inline("longer than eight"), None);
+
+    // The string outlives the symbol, short or not.
+    let kept = { let sym = intern.symbol(&a); sym.as_str() };
+
+    assert_eq!(kept, "short");
+    assert_eq!(::std::mem::size_of::<Symbol>(), ::std::mem::size_of::<&str>());
+}
+
+#[cfg(test)]
+well_known_symbols! {
+    fn register_test_symbols;
+    TEST_SHORT = "short";
+    TEST_LONG = "a well-known name";
+}
+
+#[test]
+fn test_well_known() {
+    let mut intern = StrIntern::new();
+
+    register_test_symbols(&mut intern);
+
+    let text = String::from("a well-known name");
+
+    assert_eq!(intern.symbol(&text), TEST_LONG);
+    assert_eq!(intern.symbol("short"), TEST_SHORT);
+    assert_eq!(intern!("short"), TEST_SHORT);
+    assert_eq!(intern!("self").as_str(), "self");
+}
+
+#[test]
+#[should_panic(expected = "interned before registration")]
+fn test_well_known_late() {
+    let mut intern = StrIntern::new();
+    let text = String::from("a well-known name");
+
+    intern.symbol(&text);
+    register_test_symbols(&mut intern);
+}
+
+#[test]
+fn test_keywords() {
+    let mut intern = kw::interner();
+    let table = kw::symbol_table();
+
+    assert_eq!(intern.len(), kw::ALL.len());
+    assert!(::std::ptr::eq(intern.intern("let"), kw::STRINGS[13]));
+    assert_eq!(intern.symbol("let"), kw::LET);
+    assert_eq!(kw::FN.as_str(), "fn");
+    assert!(kw::is_keyword(intern.symbol("while")));
+    assert!(!kw::is_keyword(intern.symbol("whilst")));
+    assert_eq!(table.len(), kw::ALL.len());
+    assert_eq!(table.get(kw::LET).unwrap().index(), 13);
+    assert_eq!(table.symbol(table.get(kw::USE).unwrap()), kw::USE);
+
+    for (sym, text) in kw::ALL.iter().zip(kw::STRINGS) {
+        assert_eq!(sym.as_str(), *text);
+    }
+}
+
+#[test]
+fn test_keywords_edges() {
+    let mut cold = StrIntern::new();
+    let mut table = kw::symbol_table();
+    let text = String::from("continue");
+
+    // Keywords are short, so any interner gives the same symbols.
+    assert_eq!(cold.symbol(&text), kw::CONTINUE);
+    assert_eq!(kw::CONTINUE.as_str().len(), INLINE_LEN);
+    assert_eq!(cold.symbol("as"), kw::AS);
+
+    for near in ["", "Let", "le", "lets", "fn ", "self_"] {
+        assert!(!kw::is_keyword(cold.symbol(near)), "{:?}", near);
+    }
+
+    let id = table.id(cold.symbol("main"));
+
+    assert_eq!(id.index(), kw::ALL.len());
+    assert_eq!(table.id(kw::WHILE), table.get(kw::WHILE).unwrap());
+    assert_eq!(table.len(), kw::ALL.len() + 1);
+}
+
+#[test]
+fn test_qualified_symbol() {
+    use std::collections::HashSet;
+
+    let mut intern = StrIntern::new();
+    let ty = intern.type_symbol("a_long_type_name");
+    let value = intern.value_symbol("a_long_type_name");
+    let set: HashSet<_> = [ty, value, intern.type_symbol("a_long_type_name")]
+        .iter().cloned().collect();
+
+    assert_ne!(ty, value);
+    assert_eq!(ty.sym, value.sym);
+    assert_eq!(ty, intern.qualified_symbol(Namespace::Type,
+                                           "a_long_type_name"));
+    assert_eq!(set.len(), 2);
+    assert!(value < ty);
+    assert_eq!(intern.module_symbol("std").ns, Namespace::Module);
+    assert_eq!(ty.to_string(), "a_long_type_name");
+    assert_eq!(ty.ns.to_string(), "type");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_qualified_symbol_astral() {
+    use salt::common::cache::decode;
+    use salt::common::cache::encode;
+
+    // Characters outside the basic multilingual plane, which take
+    // surrogate pairs in UTF-16, in names short and interned.
+    let mut intern = StrIntern::new();
+    let names = ["\u{1d53d}", "\u{1d53d}\u{1d546}\u{1d546}\u{1d54a}"];
+    let mut syms = Vec::new();
+
+    for name in names {
+        for ns in [Namespace::Module, Namespace::Value, Namespace::Type] {
+            syms.push(intern.qualified_symbol(ns, name))
+        }
+    }
+
+    let data = encode(&syms);
+    let mut decoded_intern = StrIntern::new();
+    let decoded: Vec<QualifiedSymbol> =
+        decode(&data, &mut decoded_intern).unwrap();
+
+    for (i, a) in syms.iter().enumerate() {
+        assert_eq!(*a, intern.qualified_symbol(a.ns, names[i / 3]));
+        assert_eq!(a.to_string(), names[i / 3]);
+        assert_eq!(decoded[i].ns, a.ns);
+        assert_eq!(decoded[i].as_str(), a.as_str());
+
+        for b in &syms[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+
+    assert_eq!(syms[0].sym, syms[2].sym);
+    assert!(syms[1] < syms[2] && syms[2] < syms[0]);
+    assert_eq!(intern.len(), 1);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_gensym() {
+    use salt::common::cache::decode;
+    use salt::common::cache::encode;
+
+    let arena = StrArena::new();
+    let mut intern = StrIntern::new();
+    let mut gensym = Gensym::new();
+    let tmp = gensym.fresh(&arena, "tmp");
+    let long = gensym.fresh(&arena, "lowered_closure");
+    let bytes = encode(&gensym);
+    let mut restored: Gensym = decode(&bytes, &mut intern).unwrap();
+
+    assert_eq!(tmp.as_str(), "%tmp.0");
+    assert_eq!(long.as_str(), "%lowered_closure.1");
+    assert!(tmp.is_generated() && long.is_generated());
+    assert!(!intern.symbol("tmp").is_generated());
+    assert_eq!(restored.count(), 2);
+    assert_eq!(restored.fresh(&arena, "tmp").as_str(), "%tmp.2");
+}
+
+#[test]
+fn test_qual_name_display() {
+    let mut intern = StrIntern::new();
+    let name = QualName::new(vec![intern.symbol("std"), intern.symbol("io"),
+                                  intern.symbol("read")]);
+
+    assert_eq!(name.to_string(), "std::io::read");
+    assert_eq!(name.last().map(|sym| sym.as_str()), Some("read"));
+}
+
+#[test]
+fn test_symbol_table() {
+    let mut intern = StrIntern::new();
+    let mut table = SymbolTable::new();
+    let long = intern.symbol("a_long_identifier");
+    let short = intern.symbol("x");
+    let a = table.id(long);
+    let b = table.id(short);
+
+    assert_eq!(a.index(), 0);
+    assert_eq!(b.index(), 1);
+    assert_eq!(table.id(intern.symbol("a_long_identifier")), a);
+    assert_eq!(table.symbol(a), long);
+    assert_eq!(table.resolve(b), "x");
+    assert_eq!(table.get(intern.symbol("missing")), None);
+    assert_eq!(table.len(), 2);
+    assert_eq!(table.stats_with_lengths().lengths,
+               Some(vec![(1, 1), (31, 1)]));
+    assert_eq!(table.stats().bytes, 18);
+}
+
+#[test]
+fn test_shared_symbol_ctx() {
+    let intern = RefCell::new(StrIntern::new());
+    let a = intern.symbol("longer than eight");
+    let mut shared = &intern;
+
+    assert_eq!(shared.symbol("longer than eight"), a);
+    assert_eq!(shared.value_symbol("x").sym, intern.symbol("x"));
+    assert_eq!(intern.borrow().len(), 1);
+    #[cfg(feature = "std")]
+    assert_eq!(Mutex::new(StrIntern::new()).symbol("x").as_str(), "x");
+}
@@ -0,0 +1,234 @@
+//! Dotted paths of symbols.
+//!
+//! A `SymbolPath` is a module-qualified name such as `std.io.print`,
+//! held as its interned segments rather than as one string, so paths
+//! compare segment by segment in constant time per segment, and
+//! taking the parent or checking for a prefix needs no string work.
+
+use salt::common::cache::DecodeResult;
+use salt::common::cache::Decodable;
+use salt::common::cache::Decoder;
+use salt::common::cache::Encodable;
+use salt::common::cache::Encoder;
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+use salt::common::symbol::QualName;
+use salt::common::symbol::Symbol;
+use salt::common::symbol::SymbolCtx;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::iter::FromIterator;
+use std::slice::Iter;
+
+/// A path of symbols, outermost first, written `a.b.c`.  The empty
+/// path is the root.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SymbolPath<'a>(Vec<Symbol<'a>>);
+
+impl<'a> SymbolPath<'a> {
+    /// Create the empty path.
+    pub fn root() -> SymbolPath<'a> {
+        SymbolPath(Vec::new())
+    }
+
+    /// Create a path from its segments, outermost first.
+    pub fn new(segments: Vec<Symbol<'a>>) -> SymbolPath<'a> {
+        SymbolPath(segments)
+    }
+
+    /// Create a path from the dotted string `path`, interning each
+    /// segment with `ctx`.  The empty string is the root.
+    pub fn parse<C>(ctx: &mut C, path: &'a str) -> SymbolPath<'a>
+        where C: SymbolCtx<'a> {
+        if path.is_empty() {
+            SymbolPath::root()
+        } else {
+            SymbolPath(path.split('.').map(|seg| ctx.symbol(seg)).collect())
+        }
+    }
+
+    /// Get the segments, outermost first.
+    pub fn segments(&self) -> &[Symbol<'a>] {
+        &self.0
+    }
+
+    /// Get an iterator over the segments, outermost first.
+    pub fn iter_segments(&self) -> Iter<'_, Symbol<'a>> {
+        self.0.iter()
+    }
+
+    /// Get the number of segments.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check whether this is the root.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get the innermost segment, if there is one.
+    pub fn last(&self) -> Option<Symbol<'a>> {
+        self.0.last().cloned()
+    }
+
+    /// Get the path without its innermost segment, or `None` for the
+    /// root.
+    pub fn parent(&self) -> Option<SymbolPath<'a>> {
+        self.0.split_last()
+            .map(|(_, parent)| SymbolPath(parent.to_vec()))
+    }
+
+    /// Get this path with `segment` added to the end.
+    pub fn join(&self, segment: Symbol<'a>) -> SymbolPath<'a> {
+        let mut segments = Vec::with_capacity(self.0.len() + 1);
+
+        segments.extend_from_slice(&self.0);
+        segments.push(segment);
+
+        SymbolPath(segments)
+    }
+
+    /// Get this path with the segments of `other` added to the end.
+    pub fn join_path(&self, other: &SymbolPath<'a>) -> SymbolPath<'a> {
+        let mut segments = self.0.clone();
+
+        segments.extend_from_slice(&other.0);
+
+        SymbolPath(segments)
+    }
+
+    /// Check whether `prefix` is this path or one of its ancestors.
+    pub fn starts_with(&self, prefix: &SymbolPath<'a>) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+
+    /// Get the rest of this path after `prefix`, if `prefix` is this
+    /// path or one of its ancestors.
+    pub fn strip_prefix(&self,
+                        prefix: &SymbolPath<'a>) -> Option<SymbolPath<'a>> {
+        if self.starts_with(prefix) {
+            Some(SymbolPath(self.0[prefix.0.len()..].to_vec()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Display for SymbolPath<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        for (i, sym) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, ".")?;
+            }
+
+            write!(f, "{}", sym)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> FromIterator<Symbol<'a>> for SymbolPath<'a> {
+    fn from_iter<I: IntoIterator<Item = Symbol<'a>>>(iter: I) -> Self {
+        SymbolPath(iter.into_iter().collect())
+    }
+}
+
+impl<'a> From<QualName<'a>> for SymbolPath<'a> {
+    fn from(name: QualName<'a>) -> SymbolPath<'a> {
+        SymbolPath(name.0)
+    }
+}
+
+impl<'a> From<SymbolPath<'a>> for QualName<'a> {
+    fn from(path: SymbolPath<'a>) -> QualName<'a> {
+        QualName(path.0)
+    }
+}
+
+impl<'a> StableHash for SymbolPath<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.0.stable_hash(hasher)
+    }
+}
+
+impl<'a> Encodable for SymbolPath<'a> {
+    fn encode(&self, enc: &mut Encoder) {
+        self.0.encode(enc)
+    }
+}
+
+impl<'a> Decodable<'a> for SymbolPath<'a> {
+    fn decode(dec: &mut Decoder<'a, '_>) -> DecodeResult<SymbolPath<'a>> {
+        Vec::decode(dec).map(SymbolPath)
+    }
+}
+
+#[test]
+fn test_symbol_path() {
+    use salt::common::str::intern::StrIntern;
+
+    let mut intern = StrIntern::new();
+    let print = SymbolPath::parse(&mut intern, "std.io.print");
+    let io = SymbolPath::parse(&mut intern, "std.io");
+    let other = SymbolPath::parse(&mut intern, "std.iota");
+
+    assert_eq!(print.len(), 3);
+    assert_eq!(print.to_string(), "std.io.print");
+    assert_eq!(print.parent(), Some(io.clone()));
+    assert_eq!(io.join(intern.symbol("print")), print);
+    assert!(print.starts_with(&io));
+    assert!(!other.starts_with(&io));
+    assert!(print.starts_with(&SymbolPath::root()));
+    assert_eq!(print.strip_prefix(&io).unwrap().to_string(), "print");
+    assert_eq!(other.strip_prefix(&io), None);
+    assert_eq!(io.join_path(&print.strip_prefix(&io).unwrap()), print);
+    assert_eq!(print.iter_segments().map(|sym| sym.as_str())
+               .collect::<Vec<_>>(), ["std", "io", "print"]);
+    assert_eq!(SymbolPath::parse(&mut intern, ""), SymbolPath::root());
+    assert_eq!(SymbolPath::root().parent(), None);
+    assert_eq!(QualName::from(print.clone()).to_string(), "std::io::print");
+}
+
+#[test]
+fn test_symbol_path_empty() {
+    use salt::common::cache::decode;
+    use salt::common::cache::encode;
+    use salt::common::str::intern::StrIntern;
+
+    let mut intern = StrIntern::new();
+    let root = SymbolPath::root();
+    let io = SymbolPath::parse(&mut intern, "std.io");
+    let std = intern.symbol("std");
+
+    assert!(root.is_empty() && root.len() == 0);
+    assert_eq!(root.to_string(), "");
+    assert_eq!(root.last(), None);
+    assert_eq!(root.iter_segments().count(), 0);
+    assert_eq!(root.join(std), SymbolPath::new(vec![std]));
+    assert_eq!(root.join(std).parent(), Some(SymbolPath::root()));
+    assert_eq!(root.join_path(&io), io);
+    assert_eq!(io.join_path(&root), io);
+    assert!(root.starts_with(&root));
+    assert!(!root.starts_with(&io));
+    assert_eq!(root.strip_prefix(&root), Some(SymbolPath::root()));
+    assert_eq!(io.strip_prefix(&io), Some(SymbolPath::root()));
+    assert_eq!(root.strip_prefix(&io), None);
+    assert!(root < io);
+    assert_eq!(QualName::from(root.clone()).0, vec![]);
+
+    // Empty segments are segments, so these are not the root.
+    let dots = SymbolPath::parse(&mut intern, ".");
+
+    assert_eq!(dots.len(), 2);
+    assert_eq!(dots.to_string(), ".");
+    assert_ne!(dots, root);
+
+    let data = encode(&vec![root.clone(), dots.clone()]);
+    let mut decoded_intern = StrIntern::new();
+    let decoded: Vec<SymbolPath> = decode(&data, &mut decoded_intern).unwrap();
+
+    assert_eq!(decoded, [root, dots]);
+}
@@ -0,0 +1,180 @@
+//! Scoped symbol tables.
+//!
+//! A `ScopedTable` maps `Symbol`s to values through a stack of
+//! scopes, as name resolution needs: a binding in an inner scope
+//! shadows bindings of the same symbol in outer scopes until its scope
+//! is popped.  Symbols compare in O(1), so lookups cost one hash table
+//! probe whatever the depth of nesting.
+
+use salt::common::symbol::Symbol;
+use std::collections::HashMap;
+use std::slice::Iter;
+
+/// A table mapping symbols to values of type `V`, with nested scopes.
+/// There is always at least one scope, the outermost.
+#[derive(Clone, Debug)]
+pub struct ScopedTable<'a, V> {
+    /// For each symbol, its bindings from outermost to innermost, with
+    /// the depth of the scope each is in.
+    bindings: HashMap<Symbol<'a>, Vec<(usize, V)>>,
+    /// For each scope, the symbols bound in it, in the order they were
+    /// first bound.
+    scopes: Vec<Vec<Symbol<'a>>>
+}
+
+/// Iterator over the bindings in the innermost scope of a
+/// `ScopedTable`, in the order they were made.
+#[derive(Clone, Debug)]
+pub struct Innermost<'t, 'a: 't, V: 't> {
+    table: &'t ScopedTable<'a, V>,
+    syms: Iter<'t, Symbol<'a>>
+}
+
+impl<'a, V> ScopedTable<'a, V> {
+    /// Create a table with only the outermost scope, which is empty.
+    pub fn new() -> ScopedTable<'a, V> {
+        ScopedTable { bindings: HashMap::new(), scopes: vec![Vec::new()] }
+    }
+
+    /// Get the number of scopes, including the outermost.
+    pub fn depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Enter a new innermost scope.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Vec::new())
+    }
+
+    /// Leave the innermost scope, removing its bindings, and return
+    /// them in the order they were made.  Panics if only the outermost
+    /// scope is left.
+    pub fn pop_scope(&mut self) -> Vec<(Symbol<'a>, V)> {
+        assert!(self.scopes.len() > 1, "popped the outermost scope");
+
+        let syms = self.scopes.pop().unwrap();
+
+        syms.into_iter()
+            .map(|sym| {
+                let stack = self.bindings.get_mut(&sym).unwrap();
+                let (_, value) = stack.pop().unwrap();
+
+                if stack.is_empty() {
+                    self.bindings.remove(&sym);
+                }
+
+                (sym, value)
+            })
+            .collect()
+    }
+
+    /// Bind `sym` to `value` in the innermost scope.  If `sym` was
+    /// already bound in that scope, the binding is replaced and the
+    /// old value returned; bindings in outer scopes are shadowed.
+    pub fn insert(&mut self, sym: Symbol<'a>, value: V) -> Option<V> {
+        let depth = self.scopes.len();
+        let stack = self.bindings.entry(sym).or_default();
+
+        match stack.last_mut() {
+            Some((d, old)) if *d == depth =>
+                Some(::std::mem::replace(old, value)),
+            _ => {
+                stack.push((depth, value));
+                self.scopes.last_mut().unwrap().push(sym);
+
+                None
+            }
+        }
+    }
+
+    /// Get the innermost binding of `sym`.
+    pub fn get(&self, sym: Symbol<'a>) -> Option<&V> {
+        self.lookup(sym).map(|(_, value)| value)
+    }
+
+    /// Get the innermost binding of `sym`, mutably.
+    pub fn get_mut(&mut self, sym: Symbol<'a>) -> Option<&mut V> {
+        self.bindings.get_mut(&sym)
+            .and_then(|stack| stack.last_mut())
+            .map(|(_, value)| value)
+    }
+
+    /// Get the innermost binding of `sym`, with the depth of the scope
+    /// it is in, counting the outermost scope as 1.
+    pub fn lookup(&self, sym: Symbol<'a>) -> Option<(usize, &V)> {
+        self.bindings.get(&sym)
+            .and_then(|stack| stack.last())
+            .map(|(depth, value)| (*depth, value))
+    }
+
+    /// Get the binding of `sym` in the innermost scope, ignoring outer
+    /// scopes.  This is for detecting duplicate definitions.
+    pub fn get_innermost(&self, sym: Symbol<'a>) -> Option<&V> {
+        self.lookup(sym)
+            .filter(|&(depth, _)| depth == self.scopes.len())
+            .map(|(_, value)| value)
+    }
+
+    /// Get an iterator over the bindings in the innermost scope.
+    pub fn innermost(&self) -> Innermost<'_, 'a, V> {
+        Innermost { table: self, syms: self.scopes.last().unwrap().iter() }
+    }
+}
+
+impl<'a, V> Default for ScopedTable<'a, V> {
+    fn default() -> ScopedTable<'a, V> {
+        ScopedTable::new()
+    }
+}
+
+impl<'t, 'a, V> Iterator for Innermost<'t, 'a, V> {
+    type Item = (Symbol<'a>, &'t V);
+
+    fn next(&mut self) -> Option<(Symbol<'a>, &'t V)> {
+        let table = self.table;
+
+        self.syms.next()
+            .map(|sym| (*sym, &table.bindings[sym].last().unwrap().1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.syms.size_hint()
+    }
+}
+
+impl<'t, 'a, V> ExactSizeIterator for Innermost<'t, 'a, V> {}
+
+#[test]
+fn test_shadowing() {
+    let x = Symbol::from_static("x");
+    let y = Symbol::from_static("y");
+    let long = Symbol::from_static("a_long_identifier");
+    let mut table = ScopedTable::new();
+
+    assert_eq!(table.insert(x, 1), None);
+    assert_eq!(table.insert(long, 2), None);
+    table.push_scope();
+    assert_eq!(table.insert(x, 10), None);
+    assert_eq!(table.insert(y, 20), None);
+    assert_eq!(table.insert(x, 11), Some(10));
+
+    assert_eq!(table.depth(), 2);
+    assert_eq!(table.get(x), Some(&11));
+    assert_eq!(table.lookup(long), Some((1, &2)));
+    assert_eq!(table.get_innermost(long), None);
+    assert_eq!(table.innermost().collect::<Vec<_>>(),
+               [(x, &11), (y, &20)]);
+
+    *table.get_mut(long).unwrap() += 1;
+
+    assert_eq!(table.pop_scope(), [(x, 11), (y, 20)]);
+    assert_eq!(table.get(x), Some(&1));
+    assert_eq!(table.get(y), None);
+    assert_eq!(table.get_innermost(long), Some(&3));
+}
+
+#[test]
+#[should_panic(expected = "outermost scope")]
+fn test_pop_outermost() {
+    ScopedTable::<()>::new().pop_scope();
+}
@@ -0,0 +1,257 @@
+use salt::common::target::triple::Arch;
+use salt::common::target::triple::Triple;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// A calling convention.  `C` stands for the platform's C convention,
+/// and is resolved against a `Triple` to one of the specific
+/// conventions by `CallConv::resolve`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum CallConv {
+    /// The platform's C calling convention (`C`).
+    C,
+    /// Salt's own calling convention, which is not stable between
+    /// compiler versions (`salt`).
+    #[default]
+    Salt,
+    /// A convention that passes as much as possible in registers
+    /// (`fast`).
+    Fast,
+    /// A convention for rarely-called functions, which preserves as
+    /// many registers as possible (`cold`).
+    Cold,
+    /// The System V AMD64 convention (`sysv64`).
+    SysV64,
+    /// The Microsoft x64 convention (`win64`).
+    Win64,
+    /// The ARM 64-bit procedure call standard (`aapcs64`).
+    AAPCS64,
+    /// The WebAssembly C convention (`wasm`).
+    Wasm
+}
+
+/// An error indicating that a calling convention name was not
+/// recognized.  The name is carried in the error.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UnknownCallConv(pub String);
+
+/// A class of registers in which a value can be passed.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RegClass {
+    /// General-purpose integer registers.
+    Int,
+    /// Floating point registers.
+    Float,
+    /// Vector registers.
+    Vector
+}
+
+/// How an argument or return value is passed.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum PassMode {
+    /// Not passed at all, as for zero-sized values.
+    Ignore,
+    /// Passed by value in one register of the given class.
+    Direct(RegClass),
+    /// Passed by value in several registers, one for each eightbyte
+    /// (or other unit the convention uses) of the value, in order.
+    Split(Vec<RegClass>),
+    /// Passed by reference: the caller places the value in memory and
+    /// passes its address.  If `by_val` is set, the memory is a copy
+    /// owned by the callee (LLVM's `byval`); otherwise it belongs to
+    /// the caller and must not be modified.
+    Indirect {
+        /// Whether the callee owns the memory.
+        by_val: bool
+    }
+}
+
+/// The ABI of one argument or return value.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ArgAbi {
+    /// The size of the value, in bytes.
+    pub size: u64,
+    /// The alignment of the value, in bytes.
+    pub align: u64,
+    /// How the value is passed.
+    pub mode: PassMode
+}
+
+/// The ABI of a function: its calling convention, and how each
+/// argument and its return value are passed.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FnAbi {
+    /// The calling convention.
+    pub conv: CallConv,
+    /// How the return value is passed.  A value passed `Indirect` is
+    /// returned through a pointer supplied by the caller.
+    pub ret: ArgAbi,
+    /// How each argument is passed, in order.
+    pub args: Vec<ArgAbi>,
+    /// Whether the function takes a variable number of arguments.
+    pub variadic: bool
+}
+
+const CALL_CONVS: [(CallConv, &str); 8] = [
+    (CallConv::C, "C"),
+    (CallConv::Salt, "salt"),
+    (CallConv::Fast, "fast"),
+    (CallConv::Cold, "cold"),
+    (CallConv::SysV64, "sysv64"),
+    (CallConv::Win64, "win64"),
+    (CallConv::AAPCS64, "aapcs64"),
+    (CallConv::Wasm, "wasm")
+];
+
+impl CallConv {
+    /// Get the name of this calling convention.
+    pub fn name(&self) -> &'static str {
+        CALL_CONVS.iter()
+            .find(|(conv, _)| conv == self)
+            .map(|(_, name)| *name)
+            .expect("calling convention missing from table")
+    }
+
+    /// Resolve this convention for `triple`.  `C` becomes the
+    /// platform's C convention, and the architecture-specific
+    /// conventions are checked against the architecture.  Returns
+    /// `None` if the convention is not available on `triple`.
+    pub fn resolve(&self, triple: &Triple) -> Option<CallConv> {
+        match (self, &triple.arch) {
+            (CallConv::C, Arch::X86_64) if triple.is_windows() =>
+                Some(CallConv::Win64),
+            (CallConv::C, Arch::X86_64) => Some(CallConv::SysV64),
+            (CallConv::C, Arch::AArch64) => Some(CallConv::AAPCS64),
+            (CallConv::C, Arch::Wasm32) => Some(CallConv::Wasm),
+            (CallConv::C, _) => None,
+            (CallConv::SysV64, Arch::X86_64) |
+            (CallConv::Win64, Arch::X86_64) |
+            (CallConv::AAPCS64, Arch::AArch64) |
+            (CallConv::Wasm, Arch::Wasm32) => Some(*self),
+            (CallConv::SysV64, _) | (CallConv::Win64, _) |
+            (CallConv::AAPCS64, _) | (CallConv::Wasm, _) => None,
+            (CallConv::Salt, _) | (CallConv::Fast, _) |
+            (CallConv::Cold, _) => Some(*self)
+        }
+    }
+
+    /// Get the largest aggregate, in bytes, that this convention
+    /// passes in registers rather than by reference.  This is only
+    /// meaningful for resolved conventions; others return `None`.
+    pub fn max_direct_size(&self) -> Option<u64> {
+        match self {
+            CallConv::SysV64 | CallConv::AAPCS64 => Some(16),
+            CallConv::Win64 | CallConv::Wasm => Some(8),
+            CallConv::C | CallConv::Salt | CallConv::Fast |
+            CallConv::Cold => None
+        }
+    }
+}
+
+impl ArgAbi {
+    /// Create an `ArgAbi` for a value of `size` bytes aligned to
+    /// `align` bytes, passed in the given way.
+    pub fn new(size: u64, align: u64, mode: PassMode) -> ArgAbi {
+        ArgAbi { size, align, mode }
+    }
+
+    /// Create an `ArgAbi` for a value that is not passed.
+    pub fn ignore() -> ArgAbi {
+        ArgAbi::new(0, 1, PassMode::Ignore)
+    }
+
+    /// Check whether the value is passed by reference.
+    pub fn is_indirect(&self) -> bool {
+        matches!(self.mode, PassMode::Indirect { .. })
+    }
+
+    /// Get the number of registers used to pass the value.
+    pub fn num_regs(&self) -> usize {
+        match &self.mode {
+            PassMode::Ignore => 0,
+            PassMode::Direct(_) | PassMode::Indirect { .. } => 1,
+            PassMode::Split(classes) => classes.len()
+        }
+    }
+}
+
+impl FnAbi {
+    /// Create an `FnAbi` for a function using `conv` that takes no
+    /// arguments and returns nothing.
+    pub fn new(conv: CallConv) -> FnAbi {
+        FnAbi { conv, ret: ArgAbi::ignore(), args: Vec::new(),
+                variadic: false }
+    }
+
+    /// Check whether the return value is passed through a pointer
+    /// supplied by the caller.
+    pub fn has_sret(&self) -> bool {
+        self.ret.is_indirect()
+    }
+}
+
+impl FromStr for CallConv {
+    type Err = UnknownCallConv;
+
+    fn from_str(s: &str) -> ::std::result::Result<CallConv,
+                                                  UnknownCallConv> {
+        CALL_CONVS.iter()
+            .find(|(_, name)| *name == s)
+            .map(|(conv, _)| *conv)
+            .ok_or_else(|| UnknownCallConv(s.to_string()))
+    }
+}
+
+impl Display for CallConv {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for UnknownCallConv {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "unknown calling convention `{}`", self.0)
+    }
+}
+
+#[test]
+fn test_call_conv_roundtrip() {
+    for (conv, name) in CALL_CONVS.iter() {
+        assert_eq!(conv.to_string(), *name);
+        assert_eq!(name.parse::<CallConv>(), Ok(*conv));
+    }
+
+    assert_eq!("stdcall".parse::<CallConv>(),
+               Err(UnknownCallConv(String::from("stdcall"))));
+}
+
+#[test]
+fn test_call_conv_resolve() {
+    let linux: Triple = "x86_64-unknown-linux-gnu".parse().unwrap();
+    let windows: Triple = "x86_64-pc-windows-msvc".parse().unwrap();
+    let arm: Triple = "aarch64-apple-darwin".parse().unwrap();
+
+    assert_eq!(CallConv::C.resolve(&linux), Some(CallConv::SysV64));
+    assert_eq!(CallConv::C.resolve(&windows), Some(CallConv::Win64));
+    assert_eq!(CallConv::C.resolve(&arm), Some(CallConv::AAPCS64));
+    assert_eq!(CallConv::Win64.resolve(&linux), Some(CallConv::Win64));
+    assert_eq!(CallConv::Win64.resolve(&arm), None);
+    assert_eq!(CallConv::Salt.resolve(&arm), Some(CallConv::Salt));
+    assert_eq!(CallConv::C.resolve(&linux).unwrap().max_direct_size(),
+               Some(16));
+}
+
+#[test]
+fn test_fn_abi() {
+    let mut abi = FnAbi::new(CallConv::SysV64);
+
+    abi.ret = ArgAbi::new(32, 8, PassMode::Indirect { by_val: false });
+    abi.args.push(ArgAbi::new(16, 8, PassMode::Split(vec![RegClass::Int,
+                                                          RegClass::Float])));
+
+    assert!(abi.has_sret());
+    assert_eq!(abi.args[0].num_regs(), 2);
+    assert_eq!(ArgAbi::ignore().num_regs(), 0);
+}
@@ -0,0 +1,490 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// Byte order of a target.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Endian {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first.
+    Big
+}
+
+/// Alignment requirements of a type, in bytes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Align {
+    /// The alignment required by the ABI.
+    pub abi: u64,
+    /// The preferred alignment, used where the layout is not otherwise
+    /// constrained (for example, for stack slots and globals).
+    pub pref: u64
+}
+
+/// The layout of a target's data: sizes, alignments, and byte order.
+///
+/// This is parsed from, and displayed as, an LLVM data layout string
+/// such as `e-m:e-p:64:64-i64:64-n8:16:32:64-S128`.  Specifications
+/// not present in the string take LLVM's defaults.  Only address space
+/// 0 is supported; layouts that describe other address spaces are
+/// rejected rather than silently misread.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DataLayout {
+    /// The byte order.
+    pub endian: Endian,
+    /// The size of a pointer, in bytes.
+    pub pointer_size: u64,
+    /// The alignment of a pointer.
+    pub pointer_align: Align,
+    /// Alignments of integer types, by width in bits, sorted by width.
+    pub int_aligns: Vec<(u64, Align)>,
+    /// Alignments of floating point types, by width in bits, sorted by
+    /// width.
+    pub float_aligns: Vec<(u64, Align)>,
+    /// Alignments of vector types, by width in bits, sorted by width.
+    pub vector_aligns: Vec<(u64, Align)>,
+    /// The alignment of aggregate types.  Structures are aligned to at
+    /// least `aggregate_align.abi`.
+    pub aggregate_align: Align,
+    /// The natural alignment of the stack in bytes, if specified.
+    pub stack_align: Option<u64>,
+    /// The integer widths, in bits, natively supported by the CPU.
+    pub native_ints: Vec<u64>,
+    /// The symbol mangling style (the `m:` specification), if any.
+    pub mangling: Option<char>
+}
+
+/// The layout of a structure computed by `DataLayout::struct_layout`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct StructLayout {
+    /// The size of the structure, in bytes, including trailing padding.
+    pub size: u64,
+    /// The alignment of the structure, in bytes.
+    pub align: u64,
+    /// The offset of each field, in bytes.
+    pub offsets: Vec<u64>
+}
+
+/// Errors that can occur when parsing a `DataLayout`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LayoutParseError {
+    /// A specification was not recognized.
+    UnknownSpec(String),
+    /// A specification is valid, but describes something this layout
+    /// does not model.
+    Unsupported(String),
+    /// A specification had the wrong number of fields.
+    BadFields(String),
+    /// A size or alignment was not a valid number.
+    BadNumber(String),
+    /// An alignment was not a power-of-two number of bytes.
+    BadAlign(String)
+}
+
+impl Align {
+    /// Create an `Align` whose ABI and preferred alignments are both
+    /// `align` bytes.
+    pub fn new(align: u64) -> Align {
+        Align { abi: align, pref: align }
+    }
+}
+
+/// Round `size` up to a multiple of `align`.
+fn align_to(size: u64, align: u64) -> u64 {
+    if align <= 1 {
+        size
+    } else {
+        size.div_ceil(align) * align
+    }
+}
+
+/// Insert or replace the entry for `width` in a sorted table.
+fn set_align(table: &mut Vec<(u64, Align)>, width: u64, align: Align) {
+    match table.binary_search_by_key(&width, |&(w, _)| w) {
+        Ok(idx) => table[idx].1 = align,
+        Err(idx) => table.insert(idx, (width, align))
+    }
+}
+
+fn lookup(table: &[(u64, Align)], width: u64) -> Option<Align> {
+    table.binary_search_by_key(&width, |&(w, _)| w)
+        .ok()
+        .map(|idx| table[idx].1)
+}
+
+impl DataLayout {
+    /// Create a `DataLayout` with LLVM's defaults: little-endian, 64-bit
+    /// pointers, and natural alignment for most types.
+    pub fn new() -> DataLayout {
+        DataLayout {
+            endian: Endian::Little,
+            pointer_size: 8,
+            pointer_align: Align::new(8),
+            int_aligns: vec![(1, Align::new(1)), (8, Align::new(1)),
+                             (16, Align::new(2)), (32, Align::new(4)),
+                             (64, Align { abi: 4, pref: 8 })],
+            float_aligns: vec![(16, Align::new(2)), (32, Align::new(4)),
+                               (64, Align::new(8)), (128, Align::new(16))],
+            vector_aligns: vec![(64, Align::new(8)), (128, Align::new(16))],
+            aggregate_align: Align { abi: 0, pref: 8 },
+            stack_align: None,
+            native_ints: Vec::new(),
+            mangling: None
+        }
+    }
+
+    /// Get the alignment of an integer type `bits` wide.  If there is
+    /// no entry for exactly that width, the entry for the next larger
+    /// width is used, or the largest entry if there is none larger;
+    /// this is LLVM's rule.
+    pub fn int_align(&self, bits: u64) -> Align {
+        match self.int_aligns.iter().find(|&&(w, _)| w >= bits) {
+            Some(&(_, align)) => align,
+            None => self.int_aligns.last()
+                .map(|&(_, align)| align)
+                .unwrap_or(Align::new(1))
+        }
+    }
+
+    /// Get the alignment of a floating point type `bits` wide, if the
+    /// target has one.
+    pub fn float_align(&self, bits: u64) -> Option<Align> {
+        lookup(&self.float_aligns, bits)
+    }
+
+    /// Get the alignment of a vector type `bits` wide.  Vectors with
+    /// no entry are aligned to their size, rounded up to a power of
+    /// two.
+    pub fn vector_align(&self, bits: u64) -> Align {
+        lookup(&self.vector_aligns, bits).unwrap_or_else(|| {
+            Align::new(bits.div_ceil(8).max(1).next_power_of_two())
+        })
+    }
+
+    /// Get the number of bytes an integer type `bits` wide occupies in
+    /// memory, including padding to its ABI alignment.
+    pub fn int_size(&self, bits: u64) -> u64 {
+        align_to(bits.div_ceil(8), self.int_align(bits).abi)
+    }
+
+    /// Check whether integers `bits` wide are natively supported.
+    pub fn is_native_int(&self, bits: u64) -> bool {
+        self.native_ints.contains(&bits)
+    }
+
+    /// Lay out a structure with the given fields, each given as its
+    /// size and ABI alignment in bytes.  Fields are placed in order,
+    /// each at the next offset that satisfies its alignment; a packed
+    /// structure has no padding and an alignment of 1.
+    pub fn struct_layout(&self, fields: &[(u64, u64)],
+                         packed: bool) -> StructLayout {
+        let mut offsets = Vec::with_capacity(fields.len());
+        let mut size = 0;
+        let mut align = if packed {
+            1
+        } else {
+            self.aggregate_align.abi.max(1)
+        };
+
+        for &(field_size, field_align) in fields {
+            if !packed {
+                size = align_to(size, field_align);
+                align = align.max(field_align);
+            }
+
+            offsets.push(size);
+            size += field_size;
+        }
+
+        StructLayout { size: align_to(size, align), align, offsets }
+    }
+
+    fn parse_spec(&mut self, spec: &str) -> ::std::result::Result<(),
+                                                           LayoutParseError> {
+        let err = |f: fn(String) -> LayoutParseError| f(spec.to_string());
+
+        match spec.as_bytes()[0] {
+            b'e' if spec.len() == 1 => self.endian = Endian::Little,
+            b'E' if spec.len() == 1 => self.endian = Endian::Big,
+            b'S' => self.stack_align = Some(parse_align(spec, &spec[1..])?),
+            b'm' => {
+                let mut chars = spec[1..].chars();
+
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some(':'), Some(c), None) => self.mangling = Some(c),
+                    _ => return Err(err(LayoutParseError::BadFields))
+                }
+            }
+            b'n' if spec.starts_with("ni:") =>
+                return Err(err(LayoutParseError::Unsupported)),
+            b'n' => {
+                self.native_ints = spec[1..].split(':')
+                    .map(|s| parse_size(spec, s))
+                    .collect::<::std::result::Result<Vec<u64>,
+                                                     LayoutParseError>>()?;
+            }
+            b'P' | b'A' | b'G' => {
+                if spec[1..] != *"0" {
+                    return Err(err(LayoutParseError::Unsupported));
+                }
+            }
+            b'p' => {
+                let fields: Vec<&str> = spec[1..].split(':').collect();
+
+                if !fields[0].is_empty() && fields[0] != "0" {
+                    return Err(err(LayoutParseError::Unsupported));
+                }
+
+                if fields.len() < 3 || fields.len() > 5 {
+                    return Err(err(LayoutParseError::BadFields));
+                }
+
+                let size = parse_size(spec, fields[1])?;
+
+                if size % 8 != 0 {
+                    return Err(err(LayoutParseError::BadAlign));
+                }
+
+                self.pointer_size = size / 8;
+                self.pointer_align = parse_abi_pref(spec, &fields[2..])?;
+            }
+            b'i' | b'f' | b'v' => {
+                let fields: Vec<&str> = spec[1..].split(':').collect();
+
+                if fields.len() < 2 || fields.len() > 3 {
+                    return Err(err(LayoutParseError::BadFields));
+                }
+
+                let width = parse_size(spec, fields[0])?;
+                let align = parse_abi_pref(spec, &fields[1..])?;
+                let table = match spec.as_bytes()[0] {
+                    b'i' => &mut self.int_aligns,
+                    b'f' => &mut self.float_aligns,
+                    _ => &mut self.vector_aligns
+                };
+
+                set_align(table, width, align);
+            }
+            b'a' => {
+                let fields: Vec<&str> = spec[1..].split(':').collect();
+
+                if !fields[0].is_empty() && fields[0] != "0" {
+                    return Err(err(LayoutParseError::BadFields));
+                }
+
+                if fields.len() < 2 || fields.len() > 3 {
+                    return Err(err(LayoutParseError::BadFields));
+                }
+
+                self.aggregate_align = parse_abi_pref(spec, &fields[1..])?;
+            }
+            b'F' => return Err(err(LayoutParseError::Unsupported)),
+            _ => return Err(err(LayoutParseError::UnknownSpec))
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_size(spec: &str, s: &str) -> ::std::result::Result<u64,
+                                                           LayoutParseError> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        Err(LayoutParseError::BadNumber(spec.to_string()))
+    } else {
+        s.parse().map_err(|_| LayoutParseError::BadNumber(spec.to_string()))
+    }
+}
+
+/// Parse an alignment given in bits, returning it in bytes.  An
+/// alignment of 0 is allowed, and means no requirement.
+fn parse_align(spec: &str, s: &str) -> ::std::result::Result<u64,
+                                                            LayoutParseError> {
+    let bits = parse_size(spec, s)?;
+
+    if bits % 8 != 0 || (bits != 0 && !bits.is_power_of_two()) {
+        Err(LayoutParseError::BadAlign(spec.to_string()))
+    } else {
+        Ok(bits / 8)
+    }
+}
+
+/// Parse an ABI alignment and an optional preferred alignment, which
+/// defaults to the ABI alignment.  Any further fields (such as the
+/// index size of a pointer specification) are ignored.
+fn parse_abi_pref(spec: &str, fields: &[&str])
+                  -> ::std::result::Result<Align, LayoutParseError> {
+    let abi = parse_align(spec, fields[0])?;
+    let pref = match fields.get(1) {
+        Some(s) => parse_align(spec, s)?,
+        None => abi
+    };
+
+    if pref < abi {
+        Err(LayoutParseError::BadAlign(spec.to_string()))
+    } else {
+        Ok(Align { abi, pref })
+    }
+}
+
+impl Default for DataLayout {
+    fn default() -> DataLayout {
+        DataLayout::new()
+    }
+}
+
+impl FromStr for DataLayout {
+    type Err = LayoutParseError;
+
+    fn from_str(s: &str) -> ::std::result::Result<DataLayout,
+                                                  LayoutParseError> {
+        let mut out = DataLayout::new();
+
+        if !s.is_empty() {
+            for spec in s.split('-') {
+                if spec.is_empty() {
+                    return Err(LayoutParseError::UnknownSpec(String::new()));
+                }
+
+                out.parse_spec(spec)?;
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+impl Display for Endian {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Endian::Little => write!(f, "little-endian"),
+            Endian::Big => write!(f, "big-endian")
+        }
+    }
+}
+
+fn write_align(f: &mut Formatter, align: &Align) -> Result {
+    if align.pref == align.abi {
+        write!(f, "{}", align.abi * 8)
+    } else {
+        write!(f, "{}:{}", align.abi * 8, align.pref * 8)
+    }
+}
+
+impl Display for DataLayout {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self.endian {
+            Endian::Little => write!(f, "e")?,
+            Endian::Big => write!(f, "E")?
+        }
+
+        if let Some(c) = self.mangling {
+            write!(f, "-m:{}", c)?;
+        }
+
+        write!(f, "-p:{}:", self.pointer_size * 8)?;
+        write_align(f, &self.pointer_align)?;
+
+        for (prefix, table) in [("i", &self.int_aligns),
+                                ("f", &self.float_aligns),
+                                ("v", &self.vector_aligns)] {
+            for (width, align) in table.iter() {
+                write!(f, "-{}{}:", prefix, width)?;
+                write_align(f, align)?;
+            }
+        }
+
+        write!(f, "-a:")?;
+        write_align(f, &self.aggregate_align)?;
+
+        if !self.native_ints.is_empty() {
+            let widths: Vec<String> = self.native_ints.iter()
+                .map(|w| w.to_string())
+                .collect();
+
+            write!(f, "-n{}", widths.join(":"))?;
+        }
+
+        if let Some(align) = self.stack_align {
+            write!(f, "-S{}", align * 8)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for LayoutParseError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            LayoutParseError::UnknownSpec(spec) =>
+                write!(f, "unknown data layout specification `{}`", spec),
+            LayoutParseError::Unsupported(spec) =>
+                write!(f, "unsupported data layout specification `{}`", spec),
+            LayoutParseError::BadFields(spec) =>
+                write!(f, "wrong number of fields in data layout \
+                           specification `{}`", spec),
+            LayoutParseError::BadNumber(spec) =>
+                write!(f, "invalid number in data layout specification `{}`",
+                       spec),
+            LayoutParseError::BadAlign(spec) =>
+                write!(f, "invalid alignment in data layout specification \
+                           `{}`", spec)
+        }
+    }
+}
+
+#[test]
+fn test_parse_x86_64() {
+    let layout: DataLayout = "e-m:e-p:64:64-i64:64-f80:128-n8:16:32:64-S128"
+        .parse().unwrap();
+
+    assert_eq!(layout.endian, Endian::Little);
+    assert_eq!(layout.mangling, Some('e'));
+    assert_eq!(layout.pointer_size, 8);
+    assert_eq!(layout.int_align(64), Align::new(8));
+    assert_eq!(layout.int_align(128), Align::new(8));
+    assert_eq!(layout.int_align(24), Align::new(4));
+    assert_eq!(layout.float_align(80), Some(Align::new(16)));
+    assert_eq!(layout.float_align(12), None);
+    assert_eq!(layout.stack_align, Some(16));
+    assert!(layout.is_native_int(32) && !layout.is_native_int(128));
+    assert_eq!(layout.int_size(24), 4);
+}
+
+#[test]
+fn test_display_roundtrip() {
+    let layout: DataLayout = "E-p:32:32-i64:64-a:0:32-n32-S64".parse().unwrap();
+    let shown = layout.to_string();
+
+    assert_eq!(layout.endian, Endian::Big);
+    assert_eq!(layout.pointer_size, 4);
+    assert_eq!(shown.parse::<DataLayout>(), Ok(layout));
+    assert_eq!("".parse::<DataLayout>(), Ok(DataLayout::new()));
+}
+
+#[test]
+fn test_parse_bad() {
+    assert_eq!("x".parse::<DataLayout>(),
+               Err(LayoutParseError::UnknownSpec(String::from("x"))));
+    assert_eq!("p1:64:64".parse::<DataLayout>(),
+               Err(LayoutParseError::Unsupported(String::from("p1:64:64"))));
+    assert_eq!("i32".parse::<DataLayout>(),
+               Err(LayoutParseError::BadFields(String::from("i32"))));
+    assert_eq!("i32:24".parse::<DataLayout>(),
+               Err(LayoutParseError::BadAlign(String::from("i32:24"))));
+    assert_eq!("i32:x".parse::<DataLayout>(),
+               Err(LayoutParseError::BadNumber(String::from("i32:x"))));
+}
+
+#[test]
+fn test_struct_layout() {
+    let layout = DataLayout::new();
+    let fields = [(1, 1), (8, 8), (2, 2)];
+
+    assert_eq!(layout.struct_layout(&fields, false),
+               StructLayout { size: 24, align: 8, offsets: vec![0, 8, 16] });
+    assert_eq!(layout.struct_layout(&fields, true),
+               StructLayout { size: 11, align: 1, offsets: vec![0, 1, 9] });
+    assert_eq!(layout.struct_layout(&[], false),
+               StructLayout { size: 0, align: 1, offsets: vec![] });
+}
@@ -0,0 +1,179 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// How a symbol is linked.  The names are the ones used by LLVM IR,
+/// and are what `Display` produces and `FromStr` accepts.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Linkage {
+    /// Visible to other objects, and must be defined exactly once
+    /// (`external`).
+    #[default]
+    External,
+    /// Visible only within the object that defines it (`internal`).
+    Internal,
+    /// Like `Internal`, but also omitted from the symbol table
+    /// (`private`).
+    Private,
+    /// Merged with other definitions of the same name; may be
+    /// overridden, and is kept even if unreferenced (`weak`).
+    Weak,
+    /// Like `Weak`, but all definitions are known to be equivalent
+    /// (`weak_odr`).
+    WeakODR,
+    /// Merged with other definitions of the same name, and discarded
+    /// if unreferenced (`linkonce`).
+    LinkOnce,
+    /// Like `LinkOnce`, but all definitions are known to be equivalent
+    /// (`linkonce_odr`).
+    LinkOnceODR,
+    /// A tentative definition, merged with others of the same name
+    /// (`common`).
+    Common,
+    /// A reference to a symbol that need not be defined anywhere
+    /// (`extern_weak`).
+    ExternWeak,
+    /// A definition that is known to be equivalent to one in another
+    /// object, usable for optimization but never emitted
+    /// (`available_externally`).
+    AvailableExternally
+}
+
+/// Whether a symbol is visible outside the shared object or executable
+/// that defines it.  The names are the ones used by LLVM IR and ELF.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Visibility {
+    /// Visible, and may be preempted by another definition
+    /// (`default`).
+    #[default]
+    Default,
+    /// Not visible outside the defining object (`hidden`).
+    Hidden,
+    /// Visible, but references from the defining object always bind
+    /// to its own definition (`protected`).
+    Protected
+}
+
+/// An error indicating that a linkage or visibility name was not
+/// recognized.  The name is carried in the error.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UnknownLinkage(pub String);
+
+const LINKAGES: [(Linkage, &str); 10] = [
+    (Linkage::External, "external"),
+    (Linkage::Internal, "internal"),
+    (Linkage::Private, "private"),
+    (Linkage::Weak, "weak"),
+    (Linkage::WeakODR, "weak_odr"),
+    (Linkage::LinkOnce, "linkonce"),
+    (Linkage::LinkOnceODR, "linkonce_odr"),
+    (Linkage::Common, "common"),
+    (Linkage::ExternWeak, "extern_weak"),
+    (Linkage::AvailableExternally, "available_externally")
+];
+
+impl Linkage {
+    /// Get the name of this linkage.
+    pub fn name(&self) -> &'static str {
+        LINKAGES.iter()
+            .find(|(linkage, _)| linkage == self)
+            .map(|(_, name)| *name)
+            .expect("linkage missing from table")
+    }
+
+    /// Check whether a symbol with this linkage is visible to other
+    /// objects at link time.
+    pub fn is_external(&self) -> bool {
+        !matches!(self, Linkage::Internal | Linkage::Private)
+    }
+
+    /// Check whether several objects may define a symbol with this
+    /// linkage, leaving the linker to pick one.
+    pub fn is_mergeable(&self) -> bool {
+        matches!(self, Linkage::Weak | Linkage::WeakODR | Linkage::LinkOnce |
+                       Linkage::LinkOnceODR | Linkage::Common)
+    }
+
+    /// Check whether this linkage describes a declaration only, so
+    /// that no definition is emitted.
+    pub fn is_declaration(&self) -> bool {
+        matches!(self, Linkage::ExternWeak | Linkage::AvailableExternally)
+    }
+}
+
+impl Visibility {
+    /// Get the name of this visibility.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Visibility::Default => "default",
+            Visibility::Hidden => "hidden",
+            Visibility::Protected => "protected"
+        }
+    }
+}
+
+impl FromStr for Linkage {
+    type Err = UnknownLinkage;
+
+    fn from_str(s: &str) -> ::std::result::Result<Linkage, UnknownLinkage> {
+        LINKAGES.iter()
+            .find(|(_, name)| *name == s)
+            .map(|(linkage, _)| *linkage)
+            .ok_or_else(|| UnknownLinkage(s.to_string()))
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = UnknownLinkage;
+
+    fn from_str(s: &str) -> ::std::result::Result<Visibility,
+                                                  UnknownLinkage> {
+        match s {
+            "default" => Ok(Visibility::Default),
+            "hidden" => Ok(Visibility::Hidden),
+            "protected" => Ok(Visibility::Protected),
+            _ => Err(UnknownLinkage(s.to_string()))
+        }
+    }
+}
+
+impl Display for Linkage {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for Visibility {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for UnknownLinkage {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "unknown linkage or visibility `{}`", self.0)
+    }
+}
+
+#[test]
+fn test_linkage_roundtrip() {
+    for (linkage, name) in LINKAGES.iter() {
+        assert_eq!(linkage.to_string(), *name);
+        assert_eq!(name.parse::<Linkage>(), Ok(*linkage));
+    }
+
+    assert_eq!("hidden".parse::<Visibility>(), Ok(Visibility::Hidden));
+    assert_eq!(Visibility::Protected.to_string(), "protected");
+    assert_eq!("static".parse::<Linkage>(),
+               Err(UnknownLinkage(String::from("static"))));
+}
+
+#[test]
+fn test_linkage_properties() {
+    assert!(Linkage::External.is_external());
+    assert!(!Linkage::Private.is_external());
+    assert!(Linkage::LinkOnceODR.is_mergeable());
+    assert!(!Linkage::External.is_mergeable());
+    assert!(Linkage::AvailableExternally.is_declaration());
+}
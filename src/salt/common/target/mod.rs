@@ -0,0 +1,28 @@
+//! Descriptions of compilation targets.
+//!
+//! These are the facts about a target that both frontends and
+//! backends depend on, kept in one place so that the sizes a frontend
+//! computes agree with what the backend emits.
+
+pub use self::abi::ArgAbi;
+pub use self::abi::CallConv;
+pub use self::abi::FnAbi;
+pub use self::abi::PassMode;
+pub use self::abi::RegClass;
+pub use self::abi::UnknownCallConv;
+pub use self::layout::Align;
+pub use self::layout::DataLayout;
+pub use self::layout::Endian;
+pub use self::layout::LayoutParseError;
+pub use self::layout::StructLayout;
+pub use self::linkage::Linkage;
+pub use self::linkage::UnknownLinkage;
+pub use self::linkage::Visibility;
+pub use self::triple::Arch;
+pub use self::triple::Triple;
+pub use self::triple::TripleParseError;
+
+mod abi;
+mod layout;
+mod linkage;
+mod triple;
@@ -0,0 +1,166 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+use std::str::FromStr;
+
+/// A CPU architecture.  Architectures the compiler does not know about
+/// are kept by name, so that they can still be passed to a backend.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Arch {
+    /// 32-bit x86 (`i686` and similar).
+    X86,
+    /// 64-bit x86 (`x86_64`).
+    X86_64,
+    /// 64-bit ARM (`aarch64`).
+    AArch64,
+    /// 64-bit RISC-V (`riscv64`, possibly with extension suffixes).
+    RiscV64,
+    /// 32-bit WebAssembly (`wasm32`).
+    Wasm32,
+    /// Any other architecture.
+    Other(String)
+}
+
+/// A target triple, of the form `arch-vendor-os[-env]`, such as
+/// `x86_64-unknown-linux-gnu`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Triple {
+    /// The architecture.
+    pub arch: Arch,
+    /// The vendor, such as `unknown`, `apple` or `pc`.
+    pub vendor: String,
+    /// The operating system, such as `linux` or `windows`.
+    pub os: String,
+    /// The environment or ABI, such as `gnu` or `msvc`, if any.
+    pub env: Option<String>
+}
+
+/// Errors that can occur when parsing a `Triple`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TripleParseError {
+    /// The triple did not have three or four components.
+    BadFormat,
+    /// A component was empty.
+    EmptyField
+}
+
+impl Arch {
+    /// Get the name of this architecture, as it appears in a triple.
+    pub fn name(&self) -> &str {
+        match self {
+            Arch::X86 => "i686",
+            Arch::X86_64 => "x86_64",
+            Arch::AArch64 => "aarch64",
+            Arch::RiscV64 => "riscv64",
+            Arch::Wasm32 => "wasm32",
+            Arch::Other(name) => name
+        }
+    }
+
+    /// Get the width of a pointer on this architecture, in bits, if
+    /// it is known.
+    pub fn pointer_width(&self) -> Option<u32> {
+        match self {
+            Arch::X86 | Arch::Wasm32 => Some(32),
+            Arch::X86_64 | Arch::AArch64 | Arch::RiscV64 => Some(64),
+            Arch::Other(_) => None
+        }
+    }
+}
+
+impl From<&str> for Arch {
+    fn from(name: &str) -> Arch {
+        match name {
+            "i386" | "i486" | "i586" | "i686" | "x86" => Arch::X86,
+            "x86_64" | "amd64" => Arch::X86_64,
+            "aarch64" | "arm64" => Arch::AArch64,
+            "wasm32" => Arch::Wasm32,
+            _ if name.starts_with("riscv64") => Arch::RiscV64,
+            _ => Arch::Other(name.to_string())
+        }
+    }
+}
+
+impl Triple {
+    /// Check whether the target runs Windows.
+    pub fn is_windows(&self) -> bool {
+        self.os == "windows"
+    }
+
+    /// Check whether the target is an Apple platform.
+    pub fn is_apple(&self) -> bool {
+        self.vendor == "apple"
+    }
+}
+
+impl FromStr for Triple {
+    type Err = TripleParseError;
+
+    fn from_str(s: &str) -> ::std::result::Result<Triple, TripleParseError> {
+        let parts: Vec<&str> = s.split('-').collect();
+
+        if parts.len() < 3 || parts.len() > 4 {
+            return Err(TripleParseError::BadFormat);
+        }
+
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(TripleParseError::EmptyField);
+        }
+
+        Ok(Triple { arch: Arch::from(parts[0]), vendor: parts[1].to_string(),
+                    os: parts[2].to_string(),
+                    env: parts.get(3).map(|env| env.to_string()) })
+    }
+}
+
+impl Display for Arch {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for Triple {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}-{}-{}", self.arch, self.vendor, self.os)?;
+
+        match &self.env {
+            Some(env) => write!(f, "-{}", env),
+            None => Ok(())
+        }
+    }
+}
+
+impl Display for TripleParseError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            TripleParseError::BadFormat =>
+                write!(f, "expected a target of the form arch-vendor-os[-env]"),
+            TripleParseError::EmptyField =>
+                write!(f, "empty component in target triple")
+        }
+    }
+}
+
+#[test]
+fn test_parse_triple() {
+    let triple: Triple = "x86_64-unknown-linux-gnu".parse().unwrap();
+
+    assert_eq!(triple.arch, Arch::X86_64);
+    assert_eq!(triple.os, "linux");
+    assert_eq!(triple.env, Some(String::from("gnu")));
+    assert_eq!(triple.to_string(), "x86_64-unknown-linux-gnu");
+
+    let triple: Triple = "mips-sgi-irix".parse().unwrap();
+
+    assert_eq!(triple.arch, Arch::Other(String::from("mips")));
+    assert_eq!(triple.arch.pointer_width(), None);
+    assert_eq!(triple.to_string(), "mips-sgi-irix");
+}
+
+#[test]
+fn test_parse_triple_bad() {
+    assert_eq!("x86_64-linux".parse::<Triple>(),
+               Err(TripleParseError::BadFormat));
+    assert_eq!("x86_64--linux".parse::<Triple>(),
+               Err(TripleParseError::EmptyField));
+}
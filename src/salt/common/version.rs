@@ -0,0 +1,332 @@
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result;
+#[cfg(feature = "fs")]
+use std::process::Command;
+use std::str::FromStr;
+
+/// Version information for a Salt tool.  This is a semantic version,
+/// plus optional build metadata identifying the exact build.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ToolVersion {
+    /// The major version number.
+    pub major: u32,
+    /// The minor version number.
+    pub minor: u32,
+    /// The patch version number.
+    pub patch: u32,
+    /// The pre-release tag (the part after `-`), if any.
+    pub pre: Option<String>,
+    /// The git commit hash from which the tool was built, if known.
+    pub git_hash: Option<String>,
+    /// The date on which the tool was built, if known.
+    pub build_date: Option<String>
+}
+
+/// Errors that can occur when parsing a `ToolVersion`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VersionParseError {
+    /// The version did not have exactly three numeric components.
+    BadFormat,
+    /// A numeric component could not be parsed.
+    BadNumber,
+    /// A pre-release or build metadata field was empty.
+    EmptyField
+}
+
+/// Produce a `ToolVersion` for the crate being compiled, using the
+/// package version from Cargo and build metadata recorded by
+/// `emit_build_env` in the crate's build script.
+#[macro_export]
+macro_rules! tool_version {
+    () => {
+        $crate::salt::common::version::ToolVersion::from_build_env(
+            env!("CARGO_PKG_VERSION"),
+            option_env!("SALT_GIT_HASH"),
+            option_env!("SALT_BUILD_DATE"))
+    }
+}
+
+/// Helper for build scripts.  Records the current git hash and build
+/// date in environment variables read by `tool_version!`.  If git is
+/// not available, the metadata is simply omitted.
+#[cfg(feature = "fs")]
+pub fn emit_build_env() {
+    let hash = command_output(Command::new("git")
+                              .args(["rev-parse", "--short", "HEAD"]));
+    let date = command_output(Command::new("git")
+                              .args(["log", "-1", "--format=%cd",
+                                     "--date=short"]));
+
+    if let Some(hash) = hash {
+        println!("cargo:rustc-env=SALT_GIT_HASH={}", hash);
+    }
+
+    if let Some(date) = date {
+        println!("cargo:rustc-env=SALT_BUILD_DATE={}", date);
+    }
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+}
+
+#[cfg(feature = "fs")]
+fn command_output(cmd: &mut Command) -> Option<String> {
+    let out = cmd.output().ok()?;
+
+    if out.status.success() {
+        let s = String::from_utf8(out.stdout).ok()?;
+        let s = s.trim();
+
+        if s.is_empty() {
+            None
+        } else {
+            Some(s.to_string())
+        }
+    } else {
+        None
+    }
+}
+
+impl ToolVersion {
+    /// Create a `ToolVersion` with no pre-release tag or build metadata.
+    pub fn new(major: u32, minor: u32, patch: u32) -> ToolVersion {
+        ToolVersion { major, minor, patch, pre: None, git_hash: None,
+                      build_date: None }
+    }
+
+    /// Create a `ToolVersion` from the values recorded at build time.
+    /// This is used by `tool_version!`, and panics if `version` is not a
+    /// valid semantic version.
+    pub fn from_build_env(version: &str, git_hash: Option<&str>,
+                          build_date: Option<&str>) -> ToolVersion {
+        let mut out: ToolVersion = version.parse()
+            .expect("package version is not a valid semantic version");
+
+        out.git_hash = git_hash.map(|s| s.to_string());
+        out.build_date = build_date.map(|s| s.to_string());
+
+        out
+    }
+
+    /// Get a string uniquely identifying this build, in semantic
+    /// version syntax (for example, `1.2.3-beta+abc1234.2018-10-24`).
+    /// This is suitable for validating on-disk caches.
+    pub fn cache_key(&self) -> String {
+        let mut out = format!("{}.{}.{}", self.major, self.minor, self.patch);
+
+        if let Some(ref pre) = self.pre {
+            out.push('-');
+            out.push_str(pre);
+        }
+
+        let meta: Vec<&str> = self.git_hash.iter()
+            .chain(self.build_date.iter())
+            .map(|s| s.as_str())
+            .collect();
+
+        if !meta.is_empty() {
+            out.push('+');
+            out.push_str(&meta.join("."));
+        }
+
+        out
+    }
+
+    /// Compare the semantic version precedence of two versions,
+    /// ignoring build metadata.
+    pub fn precedence(&self, other: &ToolVersion) -> Ordering {
+        self.major.cmp(&other.major)
+            .then(self.minor.cmp(&other.minor))
+            .then(self.patch.cmp(&other.patch))
+            .then_with(|| cmp_pre(&self.pre, &other.pre))
+    }
+}
+
+/// Compare pre-release tags according to semantic versioning rules.
+/// A version without a pre-release tag has higher precedence.
+fn cmp_pre(a: &Option<String>, b: &Option<String>) -> Ordering {
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => {
+            let mut aparts = a.split('.');
+            let mut bparts = b.split('.');
+
+            loop {
+                match (aparts.next(), bparts.next()) {
+                    (None, None) => return Ordering::Equal,
+                    (None, Some(_)) => return Ordering::Less,
+                    (Some(_), None) => return Ordering::Greater,
+                    (Some(a), Some(b)) => {
+                        let ord = match (a.parse::<u64>(), b.parse::<u64>()) {
+                            (Ok(a), Ok(b)) => a.cmp(&b),
+                            (Ok(_), Err(_)) => Ordering::Less,
+                            (Err(_), Ok(_)) => Ordering::Greater,
+                            (Err(_), Err(_)) => a.cmp(b)
+                        };
+
+                        if ord != Ordering::Equal {
+                            return ord;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Ord for ToolVersion {
+    fn cmp(&self, other: &ToolVersion) -> Ordering {
+        self.precedence(other)
+            .then_with(|| self.git_hash.cmp(&other.git_hash))
+            .then_with(|| self.build_date.cmp(&other.build_date))
+    }
+}
+
+impl PartialOrd for ToolVersion {
+    fn partial_cmp(&self, other: &ToolVersion) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl StableHash for ToolVersion {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.cache_key().stable_hash(hasher)
+    }
+}
+
+impl FromStr for ToolVersion {
+    type Err = VersionParseError;
+
+    fn from_str(s: &str) -> ::std::result::Result<ToolVersion,
+                                                  VersionParseError> {
+        let (rest, meta) = match s.find('+') {
+            Some(idx) => (&s[..idx], Some(&s[idx + 1..])),
+            None => (s, None)
+        };
+        let (nums, pre) = match rest.find('-') {
+            Some(idx) => (&rest[..idx], Some(&rest[idx + 1..])),
+            None => (rest, None)
+        };
+        let parts: Vec<&str> = nums.split('.').collect();
+
+        if parts.len() != 3 {
+            return Err(VersionParseError::BadFormat);
+        }
+
+        let mut out = ToolVersion::new(parse_num(parts[0])?,
+                                       parse_num(parts[1])?,
+                                       parse_num(parts[2])?);
+
+        if let Some(pre) = pre {
+            if pre.is_empty() {
+                return Err(VersionParseError::EmptyField);
+            }
+
+            out.pre = Some(pre.to_string());
+        }
+
+        if let Some(meta) = meta {
+            let mut fields = meta.splitn(2, '.');
+
+            match fields.next() {
+                Some(hash) if !hash.is_empty() =>
+                    out.git_hash = Some(hash.to_string()),
+                _ => return Err(VersionParseError::EmptyField)
+            }
+
+            match fields.next() {
+                Some("") => return Err(VersionParseError::EmptyField),
+                Some(date) => out.build_date = Some(date.to_string()),
+                None => {}
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+fn parse_num(s: &str) -> ::std::result::Result<u32, VersionParseError> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        Err(VersionParseError::BadNumber)
+    } else {
+        s.parse().map_err(|_| VersionParseError::BadNumber)
+    }
+}
+
+impl Display for ToolVersion {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(ref pre) = self.pre {
+            write!(f, "-{}", pre)?;
+        }
+
+        match (&self.git_hash, &self.build_date) {
+            (Some(hash), Some(date)) => write!(f, " ({} {})", hash, date),
+            (Some(hash), None) => write!(f, " ({})", hash),
+            (None, Some(date)) => write!(f, " ({})", date),
+            (None, None) => Ok(())
+        }
+    }
+}
+
+impl Display for VersionParseError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            VersionParseError::BadFormat =>
+                write!(f, "expected a version of the form MAJOR.MINOR.PATCH"),
+            VersionParseError::BadNumber =>
+                write!(f, "invalid version number"),
+            VersionParseError::EmptyField =>
+                write!(f, "empty pre-release or build metadata field")
+        }
+    }
+}
+
+#[test]
+fn test_parse_roundtrip() {
+    let v: ToolVersion = "1.2.3-beta.1+abc1234.2018-10-24".parse().unwrap();
+
+    assert_eq!(v.major, 1);
+    assert_eq!(v.pre, Some(String::from("beta.1")));
+    assert_eq!(v.git_hash, Some(String::from("abc1234")));
+    assert_eq!(v.build_date, Some(String::from("2018-10-24")));
+    assert_eq!(v.cache_key(), "1.2.3-beta.1+abc1234.2018-10-24");
+    assert_eq!(format!("{}", v), "1.2.3-beta.1 (abc1234 2018-10-24)");
+}
+
+#[test]
+fn test_parse_bad() {
+    assert_eq!("1.2".parse::<ToolVersion>(), Err(VersionParseError::BadFormat));
+    assert_eq!("1.x.3".parse::<ToolVersion>(),
+               Err(VersionParseError::BadNumber));
+    assert_eq!("1.2.3-".parse::<ToolVersion>(),
+               Err(VersionParseError::EmptyField));
+}
+
+#[test]
+fn test_precedence() {
+    let a: ToolVersion = "1.0.0-alpha".parse().unwrap();
+    let b: ToolVersion = "1.0.0-alpha.1".parse().unwrap();
+    let c: ToolVersion = "1.0.0-beta.2".parse().unwrap();
+    let d: ToolVersion = "1.0.0-beta.11".parse().unwrap();
+    let e: ToolVersion = "1.0.0".parse().unwrap();
+    let f: ToolVersion = "1.0.0+abc".parse().unwrap();
+
+    assert!(a < b && b < c && c < d && d < e);
+    assert_eq!(e.precedence(&f), Ordering::Equal);
+    assert_ne!(e, f);
+}
+
+#[test]
+fn test_macro() {
+    let v = tool_version!();
+
+    assert_eq!(v.precedence(&env!("CARGO_PKG_VERSION").parse().unwrap()),
+               Ordering::Equal);
+}
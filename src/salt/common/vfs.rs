@@ -0,0 +1,389 @@
+use salt::common::filename::normalize_path;
+use salt::common::hash::StableHash;
+use salt::common::hash::StableHasher;
+use salt::common::position::FileLoader;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Whether a path names a file or a directory.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FileKind {
+    /// A regular file.
+    File,
+    /// A directory.
+    Dir
+}
+
+/// What a `FileSystem` knows about a path, with symbolic links
+/// followed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Metadata {
+    /// Whether the path is a file or a directory.
+    pub kind: FileKind,
+    /// The length of a file in bytes, or 0 for a directory.
+    pub len: u64
+}
+
+/// A filesystem that source files are read from.  This lets the
+/// source manager and filename canonicalization work on files that are
+/// not on disk, as for unit tests, or a language server's unsaved
+/// buffers.
+pub trait FileSystem: Debug {
+    /// Read the contents of the file at `path`.
+    fn read(&self, path: &Path) -> Result<String>;
+
+    /// Get what is known about `path`.
+    fn metadata(&self, path: &Path) -> Result<Metadata>;
+
+    /// Get the paths of the entries of the directory `dir`, sorted.
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>>;
+
+    /// Get the canonical form of `path`: absolute, if the filesystem
+    /// has a notion of the current directory, and with symbolic links
+    /// resolved.
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf>;
+
+    /// Check whether `path` names a file or directory.
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+}
+
+/// The filesystem on disk.
+#[cfg(feature = "fs")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DiskFs;
+
+/// A filesystem held in memory, for tests.  Paths are normalized
+/// lexically, and directories exist when there are files in them.
+/// Relative paths stay relative, as there is no current directory.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, String>
+}
+
+/// A filesystem with files held in memory laid over another, such as
+/// the unsaved buffers of an editor over the files on disk.  Files in
+/// memory hide those with the same path underneath.
+#[derive(Clone, Debug, Default)]
+pub struct OverlayFs<F> {
+    overlay: MemoryFs,
+    base: F
+}
+
+/// Get an error for a missing `path`.
+fn not_found(path: &Path) -> Error {
+    Error::new(ErrorKind::NotFound,
+               format!("{}: no such file or directory", path.display()))
+}
+
+#[cfg(feature = "fs")]
+impl FileSystem for DiskFs {
+    fn read(&self, path: &Path) -> Result<String> {
+        ::std::fs::read_to_string(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let metadata = ::std::fs::metadata(path)?;
+        let kind = if metadata.is_dir() {
+            FileKind::Dir
+        } else {
+            FileKind::File
+        };
+        let len = if metadata.is_dir() { 0 } else { metadata.len() };
+
+        Ok(Metadata { kind, len })
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut paths = ::std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>>>()?;
+
+        paths.sort();
+
+        Ok(paths)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        ::std::fs::canonicalize(path)
+    }
+}
+
+impl MemoryFs {
+    /// Create an empty `MemoryFs`.
+    pub fn new() -> MemoryFs {
+        MemoryFs { files: BTreeMap::new() }
+    }
+
+    /// Add a file at `path` containing `contents`.
+    pub fn with_file<P, S>(mut self, path: P, contents: S) -> MemoryFs
+        where P: AsRef<Path>,
+              S: Into<String> {
+        self.insert(path, contents);
+        self
+    }
+
+    /// Set the contents of the file at `path`, returning those it had.
+    pub fn insert<P, S>(&mut self, path: P, contents: S) -> Option<String>
+        where P: AsRef<Path>,
+              S: Into<String> {
+        self.files.insert(normalize_path(path), contents.into())
+    }
+
+    /// Remove the file at `path`, returning its contents.
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Option<String> {
+        self.files.remove(&normalize_path(path))
+    }
+
+    /// Get the number of files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check whether there are no files.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Check whether there is a file under the directory `dir`.
+    fn has_dir(&self, dir: &Path) -> bool {
+        dir == Path::new(".") && !self.files.is_empty() ||
+        self.files.keys().any(|path| path != dir && path.starts_with(dir))
+    }
+}
+
+impl FileSystem for MemoryFs {
+    fn read(&self, path: &Path) -> Result<String> {
+        self.files.get(&normalize_path(path))
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let path = normalize_path(path);
+
+        match self.files.get(&path) {
+            Some(contents) => Ok(Metadata { kind: FileKind::File,
+                                            len: contents.len() as u64 }),
+            None if self.has_dir(&path) => Ok(Metadata { kind: FileKind::Dir,
+                                                         len: 0 }),
+            None => Err(not_found(&path))
+        }
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let dir = normalize_path(dir);
+
+        if !self.has_dir(&dir) {
+            return Err(not_found(&dir));
+        }
+
+        let base = if dir == Path::new(".") { Path::new("") } else { &dir };
+        let mut paths: Vec<PathBuf> = self.files.keys()
+            .filter_map(|path| path.strip_prefix(base).ok())
+            .filter_map(|rest| rest.components().next())
+            .map(|first| base.join(first))
+            .collect();
+
+        paths.dedup();
+
+        Ok(paths)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        let path = normalize_path(path);
+
+        if self.exists(&path) {
+            Ok(path)
+        } else {
+            Err(not_found(&path))
+        }
+    }
+}
+
+impl<F: FileSystem> OverlayFs<F> {
+    /// Create an `OverlayFs` with no files over `base`.
+    pub fn new(base: F) -> OverlayFs<F> {
+        OverlayFs { overlay: MemoryFs::new(), base }
+    }
+
+    /// Get the filesystem underneath.
+    pub fn base(&self) -> &F {
+        &self.base
+    }
+
+    /// Get the files in memory.
+    pub fn overlay(&self) -> &MemoryFs {
+        &self.overlay
+    }
+
+    /// Set the contents of the file at `path` in memory, returning
+    /// those it had there.
+    pub fn insert<P, S>(&mut self, path: P, contents: S) -> Option<String>
+        where P: AsRef<Path>,
+              S: Into<String> {
+        self.overlay.insert(path, contents)
+    }
+
+    /// Remove the file at `path` from memory, uncovering any file
+    /// underneath, and returning its contents in memory.
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Option<String> {
+        self.overlay.remove(path)
+    }
+}
+
+impl<F: FileSystem> FileSystem for OverlayFs<F> {
+    fn read(&self, path: &Path) -> Result<String> {
+        match self.overlay.read(path) {
+            Err(ref err) if err.kind() == ErrorKind::NotFound =>
+                self.base.read(path),
+            result => result
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        match self.overlay.metadata(path) {
+            Err(ref err) if err.kind() == ErrorKind::NotFound =>
+                self.base.metadata(path),
+            result => result
+        }
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let over = self.overlay.list(dir);
+        let under = self.base.list(dir);
+        let mut paths = match (over, under) {
+            (Ok(over), Ok(under)) => {
+                let mut dir = normalize_path(dir);
+
+                if dir == Path::new(".") {
+                    dir = PathBuf::new()
+                }
+
+                // Paths from the base are given as it spells them, so
+                // rebuild them from the directory to match the overlay.
+                under.iter()
+                    .filter_map(|path| path.file_name())
+                    .map(|name| dir.join(name))
+                    .chain(over)
+                    .collect()
+            }
+            (Ok(paths), Err(_)) | (Err(_), Ok(paths)) => paths,
+            (Err(_), Err(err)) => return Err(err)
+        };
+
+        paths.sort();
+        paths.dedup();
+
+        Ok(paths)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        if self.overlay.exists(path) {
+            Ok(normalize_path(path))
+        } else {
+            self.base.canonicalize(path)
+        }
+    }
+}
+
+impl StableHash for FileKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let tag: u8 = match self {
+            FileKind::File => 0,
+            FileKind::Dir => 1
+        };
+
+        tag.stable_hash(hasher)
+    }
+}
+
+impl StableHash for Metadata {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.kind.stable_hash(hasher);
+        self.len.stable_hash(hasher);
+    }
+}
+
+#[cfg(feature = "fs")]
+impl FileLoader for DiskFs {
+    fn load(&self, path: &Path) -> Result<String> {
+        self.read(path)
+    }
+}
+
+impl FileLoader for MemoryFs {
+    fn load(&self, path: &Path) -> Result<String> {
+        self.read(path)
+    }
+}
+
+impl<F: FileSystem> FileLoader for OverlayFs<F> {
+    fn load(&self, path: &Path) -> Result<String> {
+        self.read(path)
+    }
+}
+
+#[test]
+fn test_memory_fs() {
+    let fs = MemoryFs::new()
+        .with_file("src/a.salt", "a")
+        .with_file("./src/lib/b.salt", "bb")
+        .with_file("c.salt", "");
+
+    assert_eq!(fs.read(Path::new("src/./a.salt")).unwrap(), "a");
+    assert_eq!(fs.read(Path::new("a.salt")).unwrap_err().kind(),
+               ErrorKind::NotFound);
+    assert_eq!(fs.metadata(Path::new("src/lib/b.salt")).unwrap(),
+               Metadata { kind: FileKind::File, len: 2 });
+    assert_eq!(fs.metadata(Path::new("src/lib")).unwrap().kind,
+               FileKind::Dir);
+    assert!(fs.exists(Path::new("src")) && !fs.exists(Path::new("sr")));
+    assert_eq!(fs.list(Path::new("src")).unwrap(),
+               [PathBuf::from("src/a.salt"), PathBuf::from("src/lib")]);
+    assert_eq!(fs.list(Path::new(".")).unwrap(),
+               [PathBuf::from("c.salt"), PathBuf::from("src")]);
+    assert!(fs.list(Path::new("c.salt")).is_err());
+    assert_eq!(fs.canonicalize(Path::new("src/lib/../a.salt")).unwrap(),
+               PathBuf::from("src/a.salt"));
+}
+
+#[test]
+fn test_overlay_fs() {
+    let base = MemoryFs::new()
+        .with_file("src/a.salt", "saved")
+        .with_file("src/b.salt", "b");
+    let mut fs = OverlayFs::new(base);
+
+    fs.insert("src/a.salt", "unsaved");
+    fs.insert("src/new.salt", "new");
+
+    assert_eq!(fs.read(Path::new("src/a.salt")).unwrap(), "unsaved");
+    assert_eq!(fs.read(Path::new("src/b.salt")).unwrap(), "b");
+    assert_eq!(fs.list(Path::new("src")).unwrap(),
+               [PathBuf::from("src/a.salt"), PathBuf::from("src/b.salt"),
+                PathBuf::from("src/new.salt")]);
+    assert_eq!(fs.remove("src/a.salt"), Some("unsaved".to_string()));
+    assert_eq!(fs.read(Path::new("src/a.salt")).unwrap(), "saved");
+    assert!(!fs.exists(Path::new("src/gone.salt")));
+}
+
+#[test]
+fn test_source_cache_with_fs() {
+    use salt::common::filename::FilenameCtx;
+    use salt::common::position::SourceCache;
+    use salt::common::str::intern::StrIntern;
+
+    let mut names = StrIntern::new();
+    let fs = MemoryFs::new().with_file("a.salt", "one\ntwo\n");
+    let mut cache = SourceCache::new(fs);
+
+    assert_eq!(cache.load(names.filename("a.salt")).unwrap().line(2),
+               Some("two"));
+}